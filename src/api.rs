@@ -0,0 +1,283 @@
+use crate::DebianPackagesBuildpackError;
+use crate::config::RequestedPackage;
+use crate::create_package_index::read_packages_file;
+use crate::debian::{RepositoryPackage, RepositoryUri, SourceOrder};
+use crate::determine_packages_to_install::{
+    DeterminePackagesToInstallError, ResolutionOutput, resolve_packages_to_install,
+};
+use crate::errors::describe_error;
+use crate::install_packages::{ExtractOptions, build_download_url, extract};
+use indexmap::IndexSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+// NOTE: These types wrap the ones used by the buildpack so its internals can keep changing without
+//       breaking tools built on this API. Errors are reported with the same code and message the
+//       buildpack would display for them.
+
+/// The packages available from one or more Debian repositories.
+///
+/// When more than one package has the same name, the highest version is chosen, and packages from
+/// `Packages` files added earlier win ties between the same version.
+#[derive(Debug, Default)]
+pub struct PackageIndex {
+    inner: crate::debian::PackageIndex,
+    packages_files: usize,
+}
+
+impl PackageIndex {
+    /// Creates an empty package index.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the packages listed in an uncompressed `Packages` file from the repository at
+    /// `repository_uri` (e.g.; `http://archive.ubuntu.com/ubuntu`).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the file can't be read or any of its entries can't be parsed.
+    pub fn add_packages_file(
+        &mut self,
+        repository_uri: &str,
+        packages_file: &Path,
+    ) -> Result<(), Error> {
+        let packages = read_packages_file(
+            packages_file,
+            &RepositoryUri::from(repository_uri),
+            SourceOrder::new(self.packages_files, 0, 0),
+        )?;
+        for package in packages {
+            self.inner.add_package(package);
+        }
+        self.packages_files += 1;
+        Ok(())
+    }
+
+    /// Adds a single package to the index.
+    pub fn add_package(&mut self, package: Package) {
+        self.inner.add_package(package.inner);
+    }
+
+    /// The version of a package that would be installed, if it's in the index.
+    #[must_use]
+    pub fn get_package(&self, package_name: &str) -> Option<Package> {
+        self.inner
+            .get_highest_available_version(package_name)
+            .map(|package| Package::from(package.as_ref().clone()))
+    }
+
+    /// The packages whose name, `Provides` field, or description contains `pattern`, ignoring case.
+    /// Exact name matches come first, followed by partial name matches.
+    #[must_use]
+    pub fn search(&self, pattern: &str) -> Vec<Package> {
+        self.inner
+            .search(pattern)
+            .into_iter()
+            .cloned()
+            .map(Package::from)
+            .collect()
+    }
+}
+
+/// A package listed in a repository's `Packages` file.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Package {
+    inner: RepositoryPackage,
+}
+
+impl Package {
+    /// The name of the package (e.g.; `libvips42`).
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// The version of the package, without the epoch when it's `0`.
+    #[must_use]
+    pub fn version(&self) -> String {
+        self.inner.display_version()
+    }
+
+    /// The url the `.deb` archive of the package is downloaded from.
+    #[must_use]
+    pub fn url(&self) -> String {
+        build_download_url(&self.inner)
+    }
+
+    /// The checksum of the `.deb` archive, as listed in the `Packages` file.
+    #[must_use]
+    pub fn checksum(&self) -> String {
+        self.inner.checksum.to_string()
+    }
+}
+
+impl From<RepositoryPackage> for Package {
+    fn from(inner: RepositoryPackage) -> Self {
+        Self { inner }
+    }
+}
+
+impl Display for Package {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.inner.name_with_version())
+    }
+}
+
+/// Resolves the packages to install for `package_names` along with all of their dependencies, in
+/// the order they should be installed.
+///
+/// Packages installed on the current system aren't taken into account, so every dependency is
+/// included. Nothing is written to stdout.
+///
+/// # Errors
+///
+/// Returns an error when a package name is invalid or when a package, or one of its dependencies,
+/// can't be resolved.
+pub fn resolve_packages(
+    package_index: &PackageIndex,
+    package_names: &[&str],
+) -> Result<Vec<Package>, Error> {
+    let requested_packages = package_names
+        .iter()
+        .map(|package_name| {
+            RequestedPackage::from_str(package_name).map_err(|_| {
+                DeterminePackagesToInstallError::InvalidPackageName((*package_name).to_string())
+            })
+        })
+        .collect::<Result<IndexSet<_>, _>>()?;
+
+    let packages = resolve_packages_to_install(
+        &package_index.inner,
+        &[],
+        requested_packages,
+        &IndexSet::new(),
+        &HashMap::new(),
+        &BTreeMap::new(),
+        &BTreeMap::new(),
+        ResolutionOutput::Quiet,
+    )?;
+
+    Ok(packages.into_iter().map(Package::from).collect())
+}
+
+/// Extracts the files of the `.deb` archive at `package_path` into `output_dir`.
+///
+/// # Errors
+///
+/// Returns an error when the archive can't be read or its files can't be written.
+pub async fn extract_deb(package_path: &Path, output_dir: &Path) -> Result<(), Error> {
+    extract(
+        package_path.to_path_buf(),
+        output_dir.to_path_buf(),
+        ExtractOptions::default(),
+        HashSet::new(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// An error from reading a package index, resolving packages, or extracting a package.
+#[derive(Debug)]
+pub struct Error {
+    code: &'static str,
+    message: String,
+}
+
+impl Error {
+    /// The stable code of the error (e.g.; `DEB_PKG_E012`), the same one the buildpack displays.
+    #[must_use]
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<libcnb::Error<DebianPackagesBuildpackError>> for Error {
+    fn from(value: libcnb::Error<DebianPackagesBuildpackError>) -> Self {
+        let (code, message) = describe_error(value);
+        Self { code, message }
+    }
+}
+
+impl From<DeterminePackagesToInstallError> for Error {
+    fn from(value: DeterminePackagesToInstallError) -> Self {
+        Self::from(libcnb::Error::from(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const PACKAGES_FILE: &str = "\
+Package: curl
+Version: 8.5.0-2ubuntu10
+Depends: libcurl4t64 (= 8.5.0-2ubuntu10)
+Filename: pool/main/c/curl/curl_8.5.0-2ubuntu10_amd64.deb
+SHA256: 1111111111111111111111111111111111111111111111111111111111111111
+
+Package: libcurl4t64
+Version: 8.5.0-2ubuntu10
+Filename: pool/main/c/curl/libcurl4t64_8.5.0-2ubuntu10_amd64.deb
+SHA256: 2222222222222222222222222222222222222222222222222222222222222222
+";
+
+    #[test]
+    fn resolve_packages_from_packages_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let packages_file = temp_dir.path().join("Packages");
+        std::fs::write(&packages_file, PACKAGES_FILE).unwrap();
+
+        let mut package_index = PackageIndex::new();
+        package_index
+            .add_packages_file("http://archive.ubuntu.com/ubuntu", &packages_file)
+            .unwrap();
+
+        let curl = package_index.get_package("curl").unwrap();
+        assert_eq!(curl.name(), "curl");
+        assert_eq!(curl.version(), "8.5.0-2ubuntu10");
+        assert_eq!(
+            curl.url(),
+            "http://archive.ubuntu.com/ubuntu/pool/main/c/curl/curl_8.5.0-2ubuntu10_amd64.deb"
+        );
+
+        assert_eq!(
+            resolve_packages(&package_index, &["curl"])
+                .unwrap()
+                .iter()
+                .map(Package::name)
+                .collect::<Vec<_>>(),
+            vec!["libcurl4t64", "curl"]
+        );
+    }
+
+    #[test]
+    fn errors_have_the_buildpack_error_code() {
+        let error = resolve_packages(&PackageIndex::new(), &["not-a-package"]).unwrap_err();
+        assert!(error.code().starts_with("DEB_PKG_E"));
+        assert!(error.to_string().contains("not-a-package"));
+
+        let error = resolve_packages(&PackageIndex::new(), &["Not A Package"]).unwrap_err();
+        assert_eq!(error.code(), "DEB_PKG_E116");
+        assert!(error.to_string().contains("Not A Package"));
+
+        let error = PackageIndex::new()
+            .add_packages_file(
+                "http://archive.ubuntu.com/ubuntu",
+                Path::new("/does/not/exist/Packages"),
+            )
+            .unwrap_err();
+        assert!(error.to_string().contains("/does/not/exist/Packages"));
+    }
+}
@@ -0,0 +1,427 @@
+use crate::config::SecurityAdvisoriesMode;
+use crate::debian::{Distro, RepositoryPackage};
+use crate::o11y::*;
+use crate::{BuildpackResult, DebianPackagesBuildpackError};
+use bullet_stream::{global::print, style};
+use reqwest::header::CONTENT_TYPE;
+use reqwest_middleware::ClientWithMiddleware;
+use reqwest_middleware::Error::Reqwest;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use tracing::{info, instrument};
+
+// NOTE: Canonical publishes the Ubuntu Security Notices (USN) and the CVEs they address in the
+//       OSV format which is aggregated by osv.dev. Advisories are recorded against source
+//       packages so each binary package is queried using the source package it was built from.
+const OSV_QUERY_BATCH_URL: &str = "https://api.osv.dev/v1/querybatch";
+
+// osv.dev rejects batch requests with more queries than this
+const OSV_QUERY_BATCH_LIMIT: usize = 1000;
+
+#[instrument(skip_all)]
+pub(crate) async fn check_security_advisories(
+    client: &ClientWithMiddleware,
+    distro: &Distro,
+    packages_to_install: &[RepositoryPackage],
+    mode: &SecurityAdvisoriesMode,
+) -> BuildpackResult<()> {
+    if packages_to_install.is_empty() {
+        return Ok(());
+    }
+
+    print::header("Checking security advisories");
+    print::bullet(format!(
+        "Querying Ubuntu Security Notices for {count} packages",
+        count = packages_to_install.len()
+    ));
+
+    // A build configured to fail on advisories can't pass when they weren't checked, otherwise the
+    // check is skipped so an osv.dev outage doesn't block builds.
+    let affected_packages = match query_advisories(client, distro, packages_to_install).await {
+        Ok(affected_packages) => affected_packages,
+        Err(reason) => match mode {
+            SecurityAdvisoriesMode::Warn => {
+                print::warning(format!(
+                    "Unable to check security advisories, skipping this step: {reason}"
+                ));
+                return Ok(());
+            }
+            SecurityAdvisoriesMode::Fail => {
+                Err(CheckSecurityAdvisoriesError::QueryAdvisories(reason))?
+            }
+        },
+    };
+
+    info!(
+        { SECURITY_ADVISORIES_AFFECTED_PACKAGES } = affected_packages.len(),
+        "security advisories"
+    );
+
+    if affected_packages.is_empty() {
+        print::sub_bullet("No known advisories affect the packages being installed");
+        return Ok(());
+    }
+
+    for affected_package in &affected_packages {
+        print::sub_bullet(affected_package.to_string());
+    }
+
+    match mode {
+        SecurityAdvisoriesMode::Warn => {
+            print::warning(format!(
+                "{count} packages being installed are affected by known security advisories.",
+                count = affected_packages.len()
+            ));
+            Ok(())
+        }
+        SecurityAdvisoriesMode::Fail => {
+            Err(CheckSecurityAdvisoriesError::AffectedPackages(affected_packages).into())
+        }
+    }
+}
+
+async fn query_advisories(
+    client: &ClientWithMiddleware,
+    distro: &Distro,
+    packages_to_install: &[RepositoryPackage],
+) -> Result<Vec<AffectedPackage>, String> {
+    let mut affected_packages = vec![];
+    for packages in packages_to_install.chunks(OSV_QUERY_BATCH_LIMIT) {
+        affected_packages.extend(query_advisories_batch(client, distro, packages).await?);
+    }
+    Ok(affected_packages)
+}
+
+// NOTE: osv.dev limits the number of vulnerabilities returned for each query in a batch. Results
+//       with more have a `next_page_token` which their query is sent again with until every page
+//       has been returned.
+async fn query_advisories_batch(
+    client: &ClientWithMiddleware,
+    distro: &Distro,
+    packages_to_install: &[RepositoryPackage],
+) -> Result<Vec<AffectedPackage>, String> {
+    let mut vulnerabilities = packages_to_install
+        .iter()
+        .map(|_| vec![])
+        .collect::<Vec<_>>();
+    let mut request = OsvQueryBatchRequest::new(distro, packages_to_install);
+
+    while !request.queries.is_empty() {
+        let response = client
+            .post(OSV_QUERY_BATCH_URL)
+            .header(CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(&request).map_err(|e| e.to_string())?)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status().map_err(Reqwest))
+            .map_err(|e| e.to_string())?
+            .bytes()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let response = serde_json::from_slice::<OsvQueryBatchResponse>(&response)
+            .map_err(|e| format!("Invalid response from {OSV_QUERY_BATCH_URL}: {e}"))?;
+
+        request = request.next_page(response, &mut vulnerabilities);
+    }
+
+    Ok(affected_packages(packages_to_install, vulnerabilities))
+}
+
+fn osv_ecosystem(distro: &Distro) -> String {
+    // All supported Ubuntu releases are LTS releases which osv.dev qualifies with a suffix
+    format!("Ubuntu:{version}:LTS", version = distro.version)
+}
+
+#[derive(Debug, Serialize)]
+struct OsvQueryBatchRequest {
+    queries: Vec<OsvQuery>,
+}
+
+#[derive(Debug, Serialize)]
+struct OsvQuery {
+    // the position of the queried package in the packages being installed
+    #[serde(skip)]
+    package_index: usize,
+    package: OsvPackage,
+    version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    page_token: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct OsvPackage {
+    ecosystem: String,
+    name: String,
+}
+
+impl OsvQueryBatchRequest {
+    fn new(distro: &Distro, packages_to_install: &[RepositoryPackage]) -> Self {
+        let ecosystem = osv_ecosystem(distro);
+        Self {
+            queries: packages_to_install
+                .iter()
+                .enumerate()
+                .map(|(package_index, package)| {
+                    let (source_name, source_version) = package.get_source_package();
+                    OsvQuery {
+                        package_index,
+                        package: OsvPackage {
+                            ecosystem: ecosystem.clone(),
+                            name: source_name.to_string(),
+                        },
+                        version: source_version,
+                        page_token: None,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    // Records the vulnerabilities returned for each query and returns the queries that have more
+    // pages. Results are returned in the same order as the queries in the request.
+    fn next_page(
+        self,
+        response: OsvQueryBatchResponse,
+        vulnerabilities: &mut [Vec<OsvVulnerability>],
+    ) -> Self {
+        Self {
+            queries: self
+                .queries
+                .into_iter()
+                .zip(response.results)
+                .filter_map(|(mut query, result)| {
+                    vulnerabilities[query.package_index].extend(result.vulns);
+                    query.page_token = Some(result.next_page_token?);
+                    Some(query)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvQueryBatchResponse {
+    #[serde(default)]
+    results: Vec<OsvQueryResult>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvQueryResult {
+    #[serde(default)]
+    vulns: Vec<OsvVulnerability>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct OsvVulnerability {
+    id: String,
+}
+
+fn affected_packages(
+    packages_to_install: &[RepositoryPackage],
+    vulnerabilities: Vec<Vec<OsvVulnerability>>,
+) -> Vec<AffectedPackage> {
+    packages_to_install
+        .iter()
+        .zip(vulnerabilities)
+        .filter(|(_, vulnerabilities)| !vulnerabilities.is_empty())
+        .map(|(package, vulnerabilities)| AffectedPackage {
+            name: package.name.clone(),
+            version: package.version.to_string(),
+            advisories: vulnerabilities
+                .into_iter()
+                .map(|vulnerability| {
+                    // Ubuntu CVE records are prefixed to distinguish them from upstream ones
+                    vulnerability
+                        .id
+                        .strip_prefix("UBUNTU-")
+                        .map(ToString::to_string)
+                        .unwrap_or(vulnerability.id)
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct AffectedPackage {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) advisories: BTreeSet<String>,
+}
+
+impl Display for AffectedPackage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{name_with_version} is affected by {advisories}",
+            name_with_version = style::value(format!("{}@{}", self.name, self.version)),
+            advisories = self
+                .advisories
+                .iter()
+                .map(style::value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum CheckSecurityAdvisoriesError {
+    AffectedPackages(Vec<AffectedPackage>),
+    QueryAdvisories(String),
+}
+
+impl From<CheckSecurityAdvisoriesError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: CheckSecurityAdvisoriesError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::CheckSecurityAdvisories(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::debian::{ArchitectureName, DistroCodename, create_repository_package};
+
+    #[test]
+    fn test_query_batch_request_uses_source_packages() {
+        let request = OsvQueryBatchRequest::new(
+            &noble(),
+            &[
+                create_repository_package()
+                    .name("curl")
+                    .version("8.5.0-2ubuntu10.6")
+                    .call(),
+                create_repository_package()
+                    .name("libxmlsec1t64")
+                    .version("1.2.39-5build2")
+                    .source("xmlsec1 (1.2.39-5)")
+                    .call(),
+            ],
+        );
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "queries": [
+                    {
+                        "package": { "ecosystem": "Ubuntu:24.04:LTS", "name": "curl" },
+                        "version": "8.5.0-2ubuntu10.6"
+                    },
+                    {
+                        "package": { "ecosystem": "Ubuntu:24.04:LTS", "name": "xmlsec1" },
+                        "version": "1.2.39-5"
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_query_batch_response_affected_packages() {
+        let packages_to_install = [
+            create_repository_package()
+                .name("curl")
+                .version("8.5.0-2ubuntu10.6")
+                .call(),
+            create_repository_package()
+                .name("wget")
+                .version("1.21.4-1ubuntu4")
+                .call(),
+        ];
+        let response = serde_json::from_str::<OsvQueryBatchResponse>(
+            r#"{
+                "results": [
+                    { "vulns": [{ "id": "UBUNTU-CVE-2024-1234", "modified": "2024-01-01T00:00:00Z" }, { "id": "USN-7000-1" }] },
+                    {}
+                ]
+            }"#,
+        )
+        .unwrap();
+        let mut vulnerabilities = vec![vec![], vec![]];
+        let next_page = OsvQueryBatchRequest::new(&noble(), &packages_to_install)
+            .next_page(response, &mut vulnerabilities);
+        assert!(next_page.queries.is_empty());
+        assert_eq!(
+            affected_packages(&packages_to_install, vulnerabilities),
+            vec![AffectedPackage {
+                name: "curl".to_string(),
+                version: "8.5.0-2ubuntu10.6".to_string(),
+                advisories: BTreeSet::from(["CVE-2024-1234".to_string(), "USN-7000-1".to_string()]),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_query_batch_response_with_next_page() {
+        let packages_to_install = [
+            create_repository_package()
+                .name("curl")
+                .version("8.5.0-2ubuntu10.6")
+                .call(),
+            create_repository_package()
+                .name("wget")
+                .version("1.21.4-1ubuntu4")
+                .call(),
+        ];
+        let mut vulnerabilities = vec![vec![], vec![]];
+        let response = serde_json::from_str::<OsvQueryBatchResponse>(
+            r#"{
+                "results": [
+                    { "vulns": [{ "id": "USN-7000-1" }] },
+                    { "vulns": [{ "id": "USN-7100-1" }], "next_page_token": "page-2" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let next_page = OsvQueryBatchRequest::new(&noble(), &packages_to_install)
+            .next_page(response, &mut vulnerabilities);
+        assert_eq!(
+            serde_json::to_value(&next_page).unwrap(),
+            serde_json::json!({
+                "queries": [
+                    {
+                        "package": { "ecosystem": "Ubuntu:24.04:LTS", "name": "wget" },
+                        "version": "1.21.4-1ubuntu4",
+                        "page_token": "page-2"
+                    }
+                ]
+            })
+        );
+
+        let response = serde_json::from_str::<OsvQueryBatchResponse>(
+            r#"{ "results": [{ "vulns": [{ "id": "USN-7200-1" }] }] }"#,
+        )
+        .unwrap();
+        let next_page = next_page.next_page(response, &mut vulnerabilities);
+        assert!(next_page.queries.is_empty());
+        assert_eq!(
+            affected_packages(&packages_to_install, vulnerabilities),
+            vec![
+                AffectedPackage {
+                    name: "curl".to_string(),
+                    version: "8.5.0-2ubuntu10.6".to_string(),
+                    advisories: BTreeSet::from(["USN-7000-1".to_string()]),
+                },
+                AffectedPackage {
+                    name: "wget".to_string(),
+                    version: "1.21.4-1ubuntu4".to_string(),
+                    advisories: BTreeSet::from([
+                        "USN-7100-1".to_string(),
+                        "USN-7200-1".to_string()
+                    ]),
+                }
+            ]
+        );
+    }
+
+    fn noble() -> Distro {
+        Distro {
+            name: "ubuntu".to_string(),
+            version: "24.04".to_string(),
+            codename: DistroCodename::Noble,
+            architecture: ArchitectureName::AMD_64,
+        }
+    }
+}
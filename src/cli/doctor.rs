@@ -0,0 +1,339 @@
+use crate::cli::{CliError, CliSession, PackageIndexArgs};
+use crate::config::http_config::HttpConfig;
+use crate::debian::Source;
+use crate::local_repository::is_file_uri;
+use crate::o11y::remove_url_credentials;
+use crate::pgp::{load_certs, verify_signed_message};
+use crate::s3::is_s3_uri;
+use crate::{
+    BuildpackResult, create_http_client, create_source_list, print_distro_info, to_request_url,
+    user_agent,
+};
+use bullet_stream::global::print;
+use bullet_stream::style;
+use clap::Args;
+use libcnb::Env;
+use reqwest::Url;
+use reqwest_middleware::ClientWithMiddleware;
+use reqwest_middleware::Error::Reqwest;
+use std::fmt::{Display, Formatter};
+use std::net::ToSocketAddrs;
+use std::time::{Duration, Instant};
+
+#[derive(Args, Debug)]
+pub(crate) struct DoctorArgs {
+    #[command(flatten)]
+    package_index: PackageIndexArgs,
+}
+
+// Probes every source the build would download package indices from, one step at a time, so a
+// build failing with a network error can be narrowed down to name resolution, the connection, the
+// repository, or its signing keys. Requests aren't retried since a failing step should be
+// reported right away and later steps for a source are skipped once one fails.
+pub(super) fn run(args: &DoctorArgs) -> BuildpackResult<()> {
+    let session = CliSession::new(&args.package_index)?;
+    let source_list = create_source_list(&session.distro, &session.config);
+    let client = create_http_client(
+        &HttpConfig {
+            max_retries: Some(0),
+            ..session.config.http.clone()
+        },
+        &session.config.sources,
+        &Env::from_current(),
+        &user_agent(
+            &session.context.buildpack_descriptor.buildpack.version,
+            &session.distro,
+        ),
+    )?;
+
+    print_distro_info(&session.distro);
+
+    let mut failed_checks = 0;
+    for source in &source_list {
+        print::bullet(format!(
+            "Checking {uri}",
+            uri = style::url(remove_url_credentials(&source.uri))
+        ));
+        let checks = session
+            .runtime
+            .block_on(check_source(&client, source, &session.config.http));
+        for check in &checks {
+            print::sub_bullet(check.to_string());
+        }
+        failed_checks += checks
+            .iter()
+            .filter(|check| matches!(check.outcome, CheckOutcome::Fail(_)))
+            .count();
+    }
+
+    if failed_checks > 0 {
+        Err(CliError::DoctorChecksFailed(failed_checks))?;
+    }
+    print::bullet("All checks passed");
+
+    Ok(())
+}
+
+async fn check_source(
+    client: &ClientWithMiddleware,
+    source: &Source,
+    http_config: &HttpConfig,
+) -> Vec<Check> {
+    let mut checks = vec![];
+    let mut unreachable = false;
+
+    let network_source = !is_file_uri(source.uri.as_ref()) && !is_s3_uri(source.uri.as_ref());
+    let base_url = Url::parse(&to_request_url(&format!("{}/", source.uri))).ok();
+    match base_url
+        .as_ref()
+        .and_then(|url| Some((url, url.host_str()?)))
+    {
+        Some((base_url, host)) if network_source => {
+            let dns_check = check_dns(host, base_url, http_config);
+            unreachable = matches!(dns_check.outcome, CheckOutcome::Fail(_));
+            checks.push(dns_check);
+            if unreachable {
+                checks.push(Check::skipped("Connect", host));
+            } else {
+                let connection_check = check_connection(client, base_url, host).await;
+                unreachable = matches!(connection_check.outcome, CheckOutcome::Fail(_));
+                checks.push(connection_check);
+            }
+        }
+        _ => {}
+    }
+
+    for suite in &source.suites {
+        let release_file_url = format!("{}/dists/{suite}/InRelease", source.uri);
+        let display_url = remove_url_credentials(&release_file_url);
+        if unreachable {
+            checks.push(Check::skipped("Fetch", &display_url));
+            continue;
+        }
+
+        let start = Instant::now();
+        let release_file = client
+            .get(to_request_url(&release_file_url))
+            .send()
+            .await
+            .and_then(|res| res.error_for_status().map_err(Reqwest));
+        let release_file = match release_file {
+            Ok(response) => response.bytes().await.map_err(Reqwest),
+            Err(e) => Err(e),
+        };
+        let release_file = match release_file {
+            Ok(release_file) => {
+                checks.push(Check::pass("Fetch", &display_url, start.elapsed()));
+                release_file
+            }
+            Err(e) => {
+                checks.push(Check::fail("Fetch", &display_url, start.elapsed(), e));
+                continue;
+            }
+        };
+
+        let start = Instant::now();
+        checks.push(
+            match load_certs(&source.signed_by)
+                .and_then(|certs| verify_signed_message(certs, &release_file))
+            {
+                Ok(_) => Check::pass("Verify signature", &display_url, start.elapsed()),
+                Err(e) => Check::fail(
+                    "Verify signature",
+                    &display_url,
+                    start.elapsed(),
+                    format!("{e:#}"),
+                ),
+            },
+        );
+
+        if let Some(component) = source.components.first() {
+            let package_index_url = format!(
+                "{}/dists/{suite}/{component}/binary-{}/Packages.gz",
+                source.uri, source.arch
+            );
+            let display_url = remove_url_credentials(&package_index_url);
+            let start = Instant::now();
+            checks.push(
+                match client
+                    .head(to_request_url(&package_index_url))
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status().map_err(Reqwest))
+                {
+                    Ok(_) => Check::pass("Index", &display_url, start.elapsed()),
+                    Err(e) => Check::fail("Index", &display_url, start.elapsed(), e),
+                },
+            );
+        }
+    }
+
+    checks
+}
+
+// Hosts pinned with the `http.resolve` option are never looked up by the HTTP client so the
+// pinned addresses are reported instead.
+fn check_dns(host: &str, base_url: &Url, http_config: &HttpConfig) -> Check {
+    if let Some(addresses) = http_config.resolve.get(host) {
+        return Check::pass(
+            "DNS",
+            &format!("{host} (pinned to {})", join_addresses(addresses)),
+            Duration::ZERO,
+        );
+    }
+    let port = base_url.port_or_known_default().unwrap_or_default();
+    let start = Instant::now();
+    match (host, port).to_socket_addrs() {
+        Ok(addresses) => {
+            let addresses = addresses.map(|address| address.ip()).collect::<Vec<_>>();
+            Check::pass(
+                "DNS",
+                &format!("{host} ({})", join_addresses(&addresses)),
+                start.elapsed(),
+            )
+        }
+        Err(e) => Check::fail("DNS", host, start.elapsed(), e),
+    }
+}
+
+// Any response, even an error status, means the connection (and TLS handshake for https) worked.
+async fn check_connection(client: &ClientWithMiddleware, base_url: &Url, host: &str) -> Check {
+    let name = if base_url.scheme() == "https" {
+        "TLS"
+    } else {
+        "Connect"
+    };
+    let start = Instant::now();
+    match client.head(base_url.as_str()).send().await {
+        Ok(_) => Check::pass(name, host, start.elapsed()),
+        Err(e) => Check::fail(name, host, start.elapsed(), e),
+    }
+}
+
+fn join_addresses(addresses: &[impl Display]) -> String {
+    addresses
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct Check {
+    name: &'static str,
+    target: String,
+    latency: Duration,
+    outcome: CheckOutcome,
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum CheckOutcome {
+    Pass,
+    Fail(String),
+    Skipped,
+}
+
+impl Check {
+    fn pass(name: &'static str, target: &str, latency: Duration) -> Self {
+        Self {
+            name,
+            target: target.to_string(),
+            latency,
+            outcome: CheckOutcome::Pass,
+        }
+    }
+
+    fn fail(name: &'static str, target: &str, latency: Duration, error: impl Display) -> Self {
+        Self {
+            name,
+            target: target.to_string(),
+            latency,
+            outcome: CheckOutcome::Fail(error.to_string()),
+        }
+    }
+
+    fn skipped(name: &'static str, target: &str) -> Self {
+        Self {
+            name,
+            target: target.to_string(),
+            latency: Duration::ZERO,
+            outcome: CheckOutcome::Skipped,
+        }
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let Check {
+            name,
+            target,
+            latency,
+            outcome,
+        } = self;
+        let latency = latency.as_millis();
+        match outcome {
+            CheckOutcome::Pass => write!(f, "PASS {name} {target} ({latency}ms)"),
+            CheckOutcome::Fail(error) => {
+                write!(f, "FAIL {name} {target} ({latency}ms): {error}")
+            }
+            CheckOutcome::Skipped => write!(f, "SKIP {name} {target} (an earlier check failed)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indexmap::IndexMap;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    #[test]
+    fn display_check_outcomes() {
+        assert_eq!(
+            Check::pass(
+                "Fetch",
+                "http://archive.ubuntu.com/ubuntu/dists/noble/InRelease",
+                Duration::from_millis(42)
+            )
+            .to_string(),
+            "PASS Fetch http://archive.ubuntu.com/ubuntu/dists/noble/InRelease (42ms)"
+        );
+        assert_eq!(
+            Check::fail(
+                "DNS",
+                "mirror.example.com",
+                Duration::from_millis(3),
+                "failed to lookup address information"
+            )
+            .to_string(),
+            "FAIL DNS mirror.example.com (3ms): failed to lookup address information"
+        );
+        assert_eq!(
+            Check::skipped("Connect", "mirror.example.com").to_string(),
+            "SKIP Connect mirror.example.com (an earlier check failed)"
+        );
+    }
+
+    #[test]
+    fn dns_check_reports_pinned_addresses() {
+        let http_config = HttpConfig {
+            resolve: IndexMap::from([(
+                "mirror.example.com".to_string(),
+                vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))],
+            )]),
+            ..HttpConfig::default()
+        };
+        assert_eq!(
+            check_dns(
+                "mirror.example.com",
+                &Url::parse("https://mirror.example.com/ubuntu/").unwrap(),
+                &http_config
+            ),
+            Check::pass(
+                "DNS",
+                "mirror.example.com (pinned to 10.0.0.1)",
+                Duration::ZERO
+            )
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use crate::cli::{CliSession, PackageIndexArgs};
+use crate::determine_packages_to_install::{ResolutionOutput, resolve_packages_to_install};
+use crate::lockfile::{LOCKFILE_NAME, LockedTarget, Lockfile};
+use crate::{BuildpackResult, create_source_list};
+use bullet_stream::global::print;
+use bullet_stream::style;
+use clap::{Args, Subcommand};
+use indexmap::IndexSet;
+use std::collections::HashMap;
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum LockCommand {
+    /// Resolve the configured packages against the current package indices and update the lockfile
+    Update(UpdateArgs),
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct UpdateArgs {
+    /// Only update these packages, all other packages keep their locked versions if still available
+    packages: Vec<String>,
+    #[command(flatten)]
+    package_index: PackageIndexArgs,
+}
+
+pub(super) fn run(command: &LockCommand) -> BuildpackResult<()> {
+    match command {
+        LockCommand::Update(args) => update(args),
+    }
+}
+
+fn update(args: &UpdateArgs) -> BuildpackResult<()> {
+    let mut session = CliSession::new(&args.package_index)?;
+
+    if session.config.install.is_empty() {
+        print::warning(format!(
+            "No packages to install are configured in {project_toml} so there is nothing to lock.",
+            project_toml = style::value("project.toml")
+        ));
+        return Ok(());
+    }
+
+    let lockfile_path = session.context.app_dir.join(LOCKFILE_NAME);
+    let mut lockfile = Lockfile::read(&lockfile_path)?.unwrap_or_default();
+    let previous_target = lockfile
+        .get_target(&session.distro)
+        .cloned()
+        .unwrap_or_else(|| LockedTarget::new(&session.distro, &[]));
+
+    // When specific packages are requested for update then everything else stays pinned to
+    // what was previously locked, otherwise all packages are resolved to their latest versions.
+    let locked_versions = if args.packages.is_empty() {
+        HashMap::new()
+    } else {
+        previous_target
+            .locked_versions()
+            .into_iter()
+            .filter(|(name, _)| !args.packages.contains(name))
+            .collect()
+    };
+
+    let package_index = session.create_package_index()?;
+    let source_list = create_source_list(&session.distro, &session.config);
+
+    print::header("Determining packages to lock");
+    let packages = resolve_packages_to_install(
+        &package_index,
+        &source_list,
+        std::mem::take(&mut session.config.install),
+        &IndexSet::new(),
+        &locked_versions,
+        &session.config.prefer_alternatives,
+        &session.config.pins,
+        ResolutionOutput::Print,
+    )?;
+
+    let updated_target = LockedTarget::new(&session.distro, &packages);
+    let changes = previous_target.diff(&updated_target);
+
+    print::header(format!("Updating {LOCKFILE_NAME}"));
+    if changes.is_empty() {
+        print::bullet("No changes to locked package versions");
+    } else {
+        print::bullet(format!(
+            "Changes for {distro} {version} ({arch})",
+            distro = session.distro.name,
+            version = session.distro.version,
+            arch = session.distro.architecture
+        ));
+        for change in changes {
+            print::sub_bullet(change.to_string());
+        }
+    }
+
+    lockfile.set_target(updated_target);
+    lockfile.write(&lockfile_path)?;
+
+    print::bullet(format!(
+        "Wrote {lockfile}",
+        lockfile = style::value(lockfile_path.to_string_lossy())
+    ));
+
+    Ok(())
+}
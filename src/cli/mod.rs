@@ -0,0 +1,204 @@
+use crate::config::{BuildpackConfig, UnknownKey};
+use crate::create_package_index::create_package_index;
+use crate::debian::{Distro, PackageIndex};
+use crate::{
+    BuildpackResult, DebianPackagesBuildpack, DebianPackagesBuildpackError, create_async_runtime,
+    create_http_client, create_source_list, errors, get_config_file, print_distro_info,
+    print_proposed_suites_warning, user_agent,
+};
+use clap::{Args, Parser, Subcommand};
+use libcnb::Env;
+use libcnb::Target;
+use libcnb::build::BuildContext;
+use libcnb::data::buildpack_plan::BuildpackPlan;
+use libcnb::generic::GenericPlatform;
+use reqwest_middleware::ClientWithMiddleware;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+mod doctor;
+mod lock;
+mod outdated;
+mod search;
+mod validate;
+mod vendor;
+
+/// Tools for working with the package indices used by the Heroku .deb Packages buildpack.
+#[derive(Parser, Debug)]
+#[command(name = "deb-packages")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Search package names, provided virtual packages, and descriptions
+    Search(search::SearchArgs),
+    /// Manage the locked package versions in deb-packages.lock
+    #[command(subcommand)]
+    Lock(lock::LockCommand),
+    /// Report locked packages that have newer versions in the current package indices
+    Outdated(outdated::OutdatedArgs),
+    /// Download the configured packages into deb-vendor/ for offline builds
+    Vendor(vendor::VendorArgs),
+    /// Check the buildpack configuration for errors without running a build
+    Validate(validate::ValidateArgs),
+    /// Check the connection to every configured source and report what fails
+    Doctor(doctor::DoctorArgs),
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct PackageIndexArgs {
+    /// The Ubuntu version to use package indices from (e.g.; 22.04, 24.04)
+    #[arg(long, default_value = "24.04")]
+    distro_version: String,
+    /// The architecture to use package indices for (e.g.; amd64, arm64)
+    #[arg(long, default_value = "amd64")]
+    arch: String,
+    /// Where downloaded package indices are cached between runs [default: $XDG_CACHE_HOME/heroku-deb-packages]
+    #[arg(long)]
+    cache_dir: Option<PathBuf>,
+}
+
+#[derive(Debug)]
+pub(crate) enum CliError {
+    GetCurrentDir(std::io::Error),
+    CreateCacheDir(PathBuf, std::io::Error),
+    MissingConfigFile(PathBuf),
+    UnknownConfigKeys(PathBuf, Vec<UnknownKey>),
+    DoctorChecksFailed(usize),
+}
+
+impl From<CliError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: CliError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::Cli(value))
+    }
+}
+
+// The CNB lifecycle executes the buildpack through the `bin/detect` and `bin/build` entrypoints
+// so any other invocation of the binary (e.g.; running it directly) is treated as a CLI call.
+pub(crate) fn is_buildpack_invocation() -> bool {
+    std::env::args()
+        .next()
+        .as_deref()
+        .map(Path::new)
+        .and_then(Path::file_name)
+        .and_then(OsStr::to_str)
+        .is_some_and(|name| matches!(name, "detect" | "build"))
+}
+
+pub(crate) fn run() -> ExitCode {
+    let cli = Cli::parse();
+
+    let result = match cli.command {
+        Command::Search(args) => search::run(&args),
+        Command::Lock(command) => lock::run(&command),
+        Command::Outdated(args) => outdated::run(&args),
+        Command::Vendor(args) => vendor::run(&args),
+        Command::Validate(args) => validate::run(&args),
+        Command::Doctor(args) => doctor::run(&args),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            errors::on_error(error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// Holds everything needed to reuse the buildpack's package index creation from the command line.
+// The package indices are cached in the same layer format used during a build so repeated
+// invocations will only download what has changed.
+pub(crate) struct CliSession {
+    pub(crate) context: Arc<BuildContext<DebianPackagesBuildpack>>,
+    pub(crate) config: BuildpackConfig,
+    pub(crate) distro: Distro,
+    pub(crate) client: ClientWithMiddleware,
+    pub(crate) runtime: Runtime,
+}
+
+impl CliSession {
+    pub(crate) fn new(args: &PackageIndexArgs) -> BuildpackResult<Self> {
+        let app_dir = std::env::current_dir().map_err(CliError::GetCurrentDir)?;
+
+        let cache_dir = args.cache_dir.clone().unwrap_or_else(default_cache_dir);
+        std::fs::create_dir_all(&cache_dir)
+            .map_err(|e| CliError::CreateCacheDir(cache_dir.clone(), e))?;
+
+        let context = BuildContext {
+            layers_dir: cache_dir,
+            app_dir,
+            // NOTE: The buildpack directory is never read from when building the package index.
+            buildpack_dir: PathBuf::new(),
+            target: Target {
+                os: "linux".to_string(),
+                arch: args.arch.clone(),
+                arch_variant: None,
+                distro_name: "ubuntu".to_string(),
+                distro_version: args.distro_version.clone(),
+            },
+            platform: GenericPlatform::new(Env::from_current()),
+            buildpack_plan: BuildpackPlan { entries: vec![] },
+            buildpack_descriptor: toml::from_str(include_str!("../../buildpack.toml"))
+                .expect("The buildpack.toml should be a valid buildpack descriptor"),
+            store: None,
+        };
+
+        let config = match get_config_file(&context.app_dir)? {
+            Some(config_file) if BuildpackConfig::is_present(&config_file)? => {
+                BuildpackConfig::try_from(config_file)?
+            }
+            _ => BuildpackConfig::default(),
+        };
+
+        let distro = Distro::try_from(&context.target)?;
+
+        let client = create_http_client(
+            &config.http,
+            &config.sources,
+            &Env::from_current(),
+            &user_agent(&context.buildpack_descriptor.buildpack.version, &distro),
+        )?;
+
+        Ok(Self {
+            context: Arc::new(context),
+            config,
+            distro,
+            client,
+            runtime: create_async_runtime(),
+        })
+    }
+
+    pub(crate) fn create_package_index(&self) -> BuildpackResult<PackageIndex> {
+        let source_list = create_source_list(&self.distro, &self.config);
+
+        print_distro_info(&self.distro);
+        print_proposed_suites_warning(&source_list);
+
+        self.runtime.block_on(create_package_index(
+            &self.context,
+            &self.client,
+            &source_list,
+            self.config.index_max_age,
+            self.config.skip_phased_updates,
+        ))
+    }
+}
+
+fn default_cache_dir() -> PathBuf {
+    let env = Env::from_current();
+    env.get("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env.get("HOME")
+                .map(|home| PathBuf::from(home).join(".cache"))
+        })
+        .unwrap_or_else(std::env::temp_dir)
+        .join("heroku-deb-packages")
+}
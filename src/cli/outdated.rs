@@ -0,0 +1,235 @@
+use crate::cli::{CliSession, PackageIndexArgs};
+use crate::debian::{PackageIndex, Source};
+use crate::lockfile::{LOCKFILE_NAME, LockedTarget, Lockfile};
+use crate::{BuildpackResult, create_source_list};
+use bullet_stream::global::print;
+use bullet_stream::style;
+use clap::Args;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+#[derive(Args, Debug)]
+pub(crate) struct OutdatedArgs {
+    #[command(flatten)]
+    package_index: PackageIndexArgs,
+}
+
+// Compares the locked package versions with the versions `lock update` would pick from the current
+// package indices without changing the lockfile, so rebuilds for new versions (especially security
+// updates) can be scheduled instead of happening whenever the lockfile is next updated.
+pub(super) fn run(args: &OutdatedArgs) -> BuildpackResult<()> {
+    let session = CliSession::new(&args.package_index)?;
+
+    let lockfile_path = session.context.app_dir.join(LOCKFILE_NAME);
+    let Some(locked_target) = Lockfile::read(&lockfile_path)?
+        .and_then(|lockfile| lockfile.get_target(&session.distro).cloned())
+    else {
+        print::warning(format!(
+            "No packages are locked for {distro} {version} ({arch}) in {lockfile}. Run {lock_update} to create it.",
+            distro = session.distro.name,
+            version = session.distro.version,
+            arch = session.distro.architecture,
+            lockfile = style::value(LOCKFILE_NAME),
+            lock_update = style::value("deb-packages lock update")
+        ));
+        return Ok(());
+    };
+
+    let package_index = session.create_package_index()?;
+    let source_list = create_source_list(&session.distro, &session.config);
+
+    print::header("Checking for newer package versions");
+    let outdated_packages = find_outdated_packages(&locked_target, &package_index, &source_list);
+    if outdated_packages.is_empty() {
+        print::bullet(format!(
+            "All {count} locked packages are up to date",
+            count = locked_target.packages.len()
+        ));
+        return Ok(());
+    }
+
+    let security_updates = outdated_packages
+        .iter()
+        .filter(|outdated_package| outdated_package.security_update)
+        .count();
+    print::bullet(format!(
+        "{outdated} of {count} locked packages have newer versions ({security_updates} with security updates)",
+        outdated = outdated_packages.len(),
+        count = locked_target.packages.len(),
+    ));
+    for outdated_package in &outdated_packages {
+        if outdated_package.security_update {
+            print::sub_bullet(style::important(outdated_package.to_string()));
+        } else {
+            print::sub_bullet(outdated_package.to_string());
+        }
+    }
+    print::bullet(format!(
+        "Run {lock_update} to update the locked versions",
+        lock_update = style::value("deb-packages lock update")
+    ));
+
+    Ok(())
+}
+
+#[derive(Debug, Eq, PartialEq)]
+struct OutdatedPackage {
+    name: String,
+    locked_version: String,
+    latest_version: String,
+    latest_suite: Option<String>,
+    // a newer version is published in a `-security` suite, even if the latest version isn't
+    security_update: bool,
+}
+
+impl Display for OutdatedPackage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{name} {locked_version} -> {latest_version}",
+            name = self.name,
+            locked_version = self.locked_version,
+            latest_version = self.latest_version
+        )?;
+        if let Some(latest_suite) = &self.latest_suite {
+            write!(f, " ({latest_suite})")?;
+        }
+        if self.security_update {
+            write!(f, " [security update]")?;
+        }
+        Ok(())
+    }
+}
+
+// Locked packages that are no longer in the package index (or have a version that can't be
+// parsed) are left out since there's nothing newer to report for them.
+fn find_outdated_packages(
+    locked_target: &LockedTarget,
+    package_index: &PackageIndex,
+    source_list: &[Source],
+) -> Vec<OutdatedPackage> {
+    locked_target
+        .packages
+        .iter()
+        .filter_map(|locked_package| {
+            let locked_version = debversion::Version::from_str(&locked_package.version).ok()?;
+            let newer_packages = package_index
+                .get_packages(&locked_package.name)
+                .into_iter()
+                .filter(|package| package.version > locked_version)
+                .collect::<Vec<_>>();
+            let latest_package = newer_packages.first()?;
+            Some(OutdatedPackage {
+                name: locked_package.name.clone(),
+                locked_version: locked_package.version.clone(),
+                latest_version: latest_package.display_version(),
+                latest_suite: latest_package
+                    .source_order
+                    .suite(source_list)
+                    .map(ToString::to_string),
+                security_update: newer_packages.iter().any(|package| {
+                    package
+                        .source_order
+                        .suite(source_list)
+                        .is_some_and(|suite| suite.ends_with("-security"))
+                }),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::debian::ArchitectureName::AMD_64;
+    use crate::debian::{SourceOrder, create_repository_package};
+    use crate::lockfile::LockedPackage;
+
+    fn create_locked_package(name: &str, version: &str) -> LockedPackage {
+        LockedPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            checksum_type: "SHA256".to_string(),
+            checksum: "abc123".to_string(),
+        }
+    }
+
+    #[test]
+    fn find_outdated_packages_with_security_updates() {
+        let source_list = vec![
+            Source::new(
+                "http://archive.ubuntu.com/ubuntu",
+                vec!["noble", "noble-updates", "noble-security"],
+                vec!["main"],
+                "",
+                AMD_64,
+            ),
+            Source::new(
+                "https://packages.example.com/apt",
+                vec!["stable"],
+                vec!["main"],
+                "",
+                AMD_64,
+            ),
+        ];
+        let mut package_index = PackageIndex::default();
+        for package in [
+            create_repository_package()
+                .name("curl")
+                .version("8.5.0-2ubuntu10")
+                .source_order(SourceOrder::new(0, 0, 0))
+                .call(),
+            create_repository_package()
+                .name("curl")
+                .version("8.5.0-2ubuntu10.6")
+                .source_order(SourceOrder::new(0, 1, 0))
+                .call(),
+            create_repository_package()
+                .name("curl")
+                .version("8.5.0-2ubuntu10.6")
+                .source_order(SourceOrder::new(0, 2, 0))
+                .call(),
+            create_repository_package()
+                .name("libvips42")
+                .version("8.15.1-1")
+                .source_order(SourceOrder::new(0, 0, 0))
+                .call(),
+            create_repository_package()
+                .name("libvips42")
+                .version("8.15.1-2")
+                .source_order(SourceOrder::new(0, 1, 0))
+                .call(),
+            create_repository_package()
+                .name("tool")
+                .version("2.0.0")
+                .source_order(SourceOrder::new(1, 0, 0))
+                .call(),
+        ] {
+            package_index.add_package(package);
+        }
+        let locked_target = LockedTarget {
+            distro_name: "ubuntu".to_string(),
+            distro_version: "24.04".to_string(),
+            arch: "amd64".to_string(),
+            packages: vec![
+                create_locked_package("curl", "8.5.0-2ubuntu10"),
+                create_locked_package("libvips42", "8.15.1-1"),
+                create_locked_package("removed-package", "1.0.0"),
+                create_locked_package("tool", "2.0.0"),
+            ],
+        };
+
+        let outdated_packages =
+            find_outdated_packages(&locked_target, &package_index, &source_list);
+        assert_eq!(
+            outdated_packages
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "curl 8.5.0-2ubuntu10 -> 8.5.0-2ubuntu10.6 (noble-updates) [security update]",
+                "libvips42 8.15.1-1 -> 8.15.1-2 (noble-updates)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use crate::BuildpackResult;
+use crate::cli::{CliSession, PackageIndexArgs};
+use bullet_stream::global::print;
+use bullet_stream::style;
+use clap::Args;
+
+#[derive(Args, Debug)]
+pub(crate) struct SearchArgs {
+    /// Case-insensitive text to match against package names, Provides, and descriptions
+    pattern: String,
+    /// The maximum number of matching packages to display
+    #[arg(long, default_value_t = 50)]
+    limit: usize,
+    #[command(flatten)]
+    package_index: PackageIndexArgs,
+}
+
+pub(super) fn run(args: &SearchArgs) -> BuildpackResult<()> {
+    let session = CliSession::new(&args.package_index)?;
+    let package_index = session.create_package_index()?;
+
+    let results = package_index.search(&args.pattern);
+
+    print::bullet(format!(
+        "Search results for {pattern}",
+        pattern = style::value(&args.pattern)
+    ));
+
+    if results.is_empty() {
+        print::sub_bullet("No matching packages found");
+        return Ok(());
+    }
+
+    for package in results.iter().take(args.limit) {
+        let description = package
+            .description
+            .as_ref()
+            .map(|description| format!(" - {description}"))
+            .unwrap_or_default();
+        let provides = package
+            .provides
+            .as_ref()
+            .map(|provides| format!(" [provides: {provides}]"))
+            .unwrap_or_default();
+        print::sub_bullet(format!(
+            "{name} ({version}){description}{provides}",
+            name = style::value(&package.name),
+            version = package.display_version()
+        ));
+    }
+
+    if results.len() > args.limit {
+        print::sub_bullet(format!(
+            "...and {remaining} more (use {limit_flag} to show more results)",
+            remaining = results.len() - args.limit,
+            limit_flag = style::value("--limit")
+        ));
+    }
+
+    Ok(())
+}
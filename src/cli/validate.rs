@@ -0,0 +1,49 @@
+use crate::cli::CliError;
+use crate::config::BuildpackConfig;
+use crate::{BuildpackResult, get_config_file, print_deprecated_keys_warning};
+use bullet_stream::global::print;
+use bullet_stream::style;
+use clap::Args;
+use std::path::PathBuf;
+
+#[derive(Args, Debug)]
+pub(crate) struct ValidateArgs {
+    /// The configuration file to validate [default: deb-packages.toml or project.toml in the current directory]
+    config_file: Option<PathBuf>,
+}
+
+// Parses the configuration the same way a build does so any error is reported with the same
+// message the build would fail with. Keys the buildpack doesn't recognize are ignored by a build
+// but fail validation since they're usually typos.
+pub(super) fn run(args: &ValidateArgs) -> BuildpackResult<()> {
+    let config_file = if let Some(config_file) = &args.config_file {
+        config_file.clone()
+    } else {
+        let app_dir = std::env::current_dir().map_err(CliError::GetCurrentDir)?;
+        get_config_file(&app_dir)?.ok_or(CliError::MissingConfigFile(app_dir))?
+    };
+
+    print::bullet(format!(
+        "Validating {config_file}",
+        config_file = style::value(config_file.to_string_lossy())
+    ));
+
+    let config = BuildpackConfig::try_from(config_file.clone())?;
+
+    let unknown_keys = BuildpackConfig::find_unknown_keys(&config_file)?;
+    if !unknown_keys.is_empty() {
+        Err(CliError::UnknownConfigKeys(config_file, unknown_keys))?;
+    }
+
+    print_deprecated_keys_warning(&config);
+
+    print::sub_bullet(format!(
+        "Found {install} packages to install, {download} download URLs, and {sources} custom sources",
+        install = config.install.len(),
+        download = config.download.len(),
+        sources = config.sources.len()
+    ));
+    print::sub_bullet("Configuration is valid");
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use crate::cli::{CliSession, PackageIndexArgs};
+use crate::debian::PackageChecksum;
+use crate::determine_packages_to_install::{ResolutionOutput, resolve_packages_to_install};
+use crate::get_download_dir;
+use crate::install_packages::{DownloadTask, InstallPackagesError, calculate_sha256, download};
+use crate::lockfile::{LOCKFILE_NAME, LockedTarget, Lockfile};
+use crate::vendor::{
+    VENDOR_DIR, VENDOR_MANIFEST_NAME, VendorError, VendorManifest, VendoredPackage, VendoredTarget,
+    requested_from_config,
+};
+use crate::{BuildpackResult, create_source_list};
+use bullet_stream::global::print;
+use bullet_stream::style;
+use clap::Args;
+use futures::future::try_join_all;
+use indexmap::IndexSet;
+use std::path::Path;
+
+#[derive(Args, Debug)]
+pub(crate) struct VendorArgs {
+    #[command(flatten)]
+    package_index: PackageIndexArgs,
+}
+
+#[allow(clippy::too_many_lines)]
+pub(super) fn run(args: &VendorArgs) -> BuildpackResult<()> {
+    let mut session = CliSession::new(&args.package_index)?;
+
+    if session.config.install.is_empty() && session.config.download.is_empty() {
+        print::warning(format!(
+            "No packages to install or download are configured in {project_toml} so there is nothing to vendor.",
+            project_toml = style::value("project.toml")
+        ));
+        return Ok(());
+    }
+
+    let requested = requested_from_config(&session.config);
+
+    // Locked versions are respected so the vendored packages match what a regular build would install.
+    let locked_versions = Lockfile::read(&session.context.app_dir.join(LOCKFILE_NAME))?
+        .and_then(|lockfile| {
+            lockfile
+                .get_target(&session.distro)
+                .map(LockedTarget::locked_versions)
+        })
+        .unwrap_or_default();
+
+    let package_index = session.create_package_index()?;
+    let source_list = create_source_list(&session.distro, &session.config);
+
+    print::header("Determining packages to vendor");
+    let packages_to_install = resolve_packages_to_install(
+        &package_index,
+        &source_list,
+        std::mem::take(&mut session.config.install),
+        &IndexSet::new(),
+        &locked_versions,
+        &session.config.prefer_alternatives,
+        &session.config.pins,
+        ResolutionOutput::Print,
+    )?;
+
+    let vendor_dir = session.context.app_dir.join(VENDOR_DIR);
+    std::fs::create_dir_all(&vendor_dir)
+        .map_err(|e| VendorError::CreateVendorDir(vendor_dir.clone(), e))?;
+
+    let download_dir = get_download_dir().unwrap_or_else(std::env::temp_dir);
+    std::fs::create_dir_all(&download_dir)
+        .map_err(|e| InstallPackagesError::CreateDownloadDir(download_dir.clone(), e))?;
+
+    print::header(format!("Downloading packages into {VENDOR_DIR}"));
+    let timer = print::sub_start_timer("Downloading");
+    let download_tasks = packages_to_install
+        .iter()
+        .cloned()
+        .map(|package| DownloadTask::Package(Box::new(package)))
+        .chain(
+            session
+                .config
+                .download
+                .iter()
+                .cloned()
+                .map(|download_url| DownloadTask::Url(Box::new(download_url))),
+        )
+        .map(|download_task| download(session.client.clone(), download_task, download_dir.clone()));
+    let downloads = session.runtime.block_on(try_join_all(download_tasks))?;
+    timer.done();
+
+    let mut vendored_packages = vec![];
+    let mut downloads = downloads.into_iter();
+
+    for (package, (download_path, _)) in packages_to_install.iter().zip(downloads.by_ref()) {
+        // vendored packages are always verified with SHA256 so it's calculated for packages that
+        // were downloaded with a weaker checksum
+        let sha256 = match &package.checksum {
+            PackageChecksum::Sha256(sha256) => sha256.clone(),
+            _ => session
+                .runtime
+                .block_on(calculate_sha256(&download_path))
+                .map_err(|e| VendorError::ReadPackage(download_path.clone(), e))?,
+        };
+        let file = copy_to_vendor_dir(&download_path, &vendor_dir)?;
+        vendored_packages.push(VendoredPackage {
+            name: package.name.clone(),
+            version: Some(package.display_version()),
+            file,
+            sha256,
+        });
+    }
+
+    // packages from download urls are always hashed with SHA256 while downloading
+    for (download_url, (download_path, sha256)) in session.config.download.iter().zip(downloads) {
+        let file = copy_to_vendor_dir(&download_path, &vendor_dir)?;
+        vendored_packages.push(VendoredPackage {
+            name: download_url.to_string(),
+            version: None,
+            file,
+            sha256,
+        });
+    }
+
+    for vendored_package in &vendored_packages {
+        print::sub_bullet(format!(
+            "Vendored {name} as {file}",
+            name = style::value(&vendored_package.name),
+            file = style::value(&vendored_package.file)
+        ));
+    }
+
+    let mut manifest = VendorManifest::read(&vendor_dir)?.unwrap_or_default();
+    let unreferenced_files = manifest.set_target(VendoredTarget::new(
+        &session.distro,
+        requested,
+        vendored_packages,
+    ));
+
+    // Packages from a previous run that aren't needed by any target anymore are cleaned up. A
+    // failure here only leaves a stale file behind so it is not treated as an error.
+    for unreferenced_file in unreferenced_files {
+        if std::fs::remove_file(vendor_dir.join(&unreferenced_file)).is_ok() {
+            print::sub_bullet(format!(
+                "Removed {file}",
+                file = style::value(unreferenced_file)
+            ));
+        }
+    }
+
+    manifest.write(&vendor_dir)?;
+
+    print::bullet(format!(
+        "Wrote {manifest}",
+        manifest = style::value(vendor_dir.join(VENDOR_MANIFEST_NAME).to_string_lossy())
+    ));
+
+    Ok(())
+}
+
+fn copy_to_vendor_dir(download_path: &Path, vendor_dir: &Path) -> BuildpackResult<String> {
+    let mut file = download_path
+        .file_name()
+        .map(|file_name| file_name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // downloads from urls are saved without their .deb extension
+    if Path::new(&file)
+        .extension()
+        .is_none_or(|extension| extension != "deb")
+    {
+        file.push_str(".deb");
+    }
+    let vendored_path = vendor_dir.join(&file);
+    std::fs::copy(download_path, &vendored_path)
+        .map_err(|e| VendorError::CopyPackage(download_path.to_path_buf(), vendored_path, e))?;
+    let _ = std::fs::remove_file(download_path);
+    Ok(file)
+}
@@ -0,0 +1,145 @@
+use crate::DebianPackagesBuildpackError;
+use crate::config::custom_source::{ClientIdentity, CustomSource, PemSource};
+use crate::debian::RepositoryUri;
+use async_trait::async_trait;
+use http::Extensions;
+use libcnb::Env;
+use reqwest::{Client, ClientBuilder, Identity, Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use std::path::PathBuf;
+
+// Repositories that require mutual TLS get a dedicated HTTP client configured with the client
+// certificate for that source. Requests are routed to it by matching the repository URI as a
+// prefix of the request URL so index and package downloads from the source both present the
+// certificate while every other request goes through the shared client.
+pub(crate) struct ClientIdentityMiddleware {
+    clients: Vec<(String, Client)>,
+}
+
+impl ClientIdentityMiddleware {
+    pub(crate) fn new(
+        custom_sources: &[CustomSource],
+        create_client_builder: impl Fn() -> ClientBuilder,
+    ) -> Result<Self, ClientIdentityError> {
+        let mut clients = vec![];
+        for custom_source in custom_sources {
+            if let Some(client_identity) = &custom_source.client_identity {
+                let identity = read_identity(&custom_source.uri, client_identity)?;
+                let client = create_client_builder()
+                    .identity(identity)
+                    .build()
+                    .map_err(|e| {
+                        ClientIdentityError::InvalidClientIdentity(custom_source.uri.clone(), e)
+                    })?;
+                clients.push((
+                    custom_source.uri.as_ref().trim_end_matches('/').to_string(),
+                    client,
+                ));
+            }
+        }
+        Ok(Self { clients })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    fn find_client(&self, url: &str) -> Option<&Client> {
+        self.clients
+            .iter()
+            .find(|(uri_prefix, _)| is_repository_url(uri_prefix, url))
+            .map(|(_, client)| client)
+    }
+}
+
+fn is_repository_url(uri_prefix: &str, url: &str) -> bool {
+    url.strip_prefix(uri_prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[async_trait]
+impl Middleware for ClientIdentityMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        match self.find_client(req.url().as_str()) {
+            Some(client) => client
+                .execute(req)
+                .await
+                .map_err(reqwest_middleware::Error::Reqwest),
+            None => next.run(req, extensions).await,
+        }
+    }
+}
+
+fn read_identity(
+    uri: &RepositoryUri,
+    client_identity: &ClientIdentity,
+) -> Result<Identity, ClientIdentityError> {
+    let mut pem = read_pem(&client_identity.cert)?;
+    pem.push(b'\n');
+    pem.extend(read_pem(&client_identity.key)?);
+    Identity::from_pem(&pem).map_err(|e| ClientIdentityError::InvalidClientIdentity(uri.clone(), e))
+}
+
+// relative paths are resolved from the application directory which is the working directory
+// for both the build and the CLI
+fn read_pem(pem_source: &PemSource) -> Result<Vec<u8>, ClientIdentityError> {
+    match pem_source {
+        PemSource::Path(path) => {
+            std::fs::read(path).map_err(|e| ClientIdentityError::ReadPemFile(path.clone(), e))
+        }
+        PemSource::Env(name) => Env::from_current()
+            .get_string_lossy(name)
+            .map(String::into_bytes)
+            .ok_or_else(|| ClientIdentityError::MissingPemEnv(name.clone())),
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum ClientIdentityError {
+    ReadPemFile(PathBuf, std::io::Error),
+    MissingPemEnv(String),
+    InvalidClientIdentity(RepositoryUri, reqwest::Error),
+}
+
+impl From<ClientIdentityError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: ClientIdentityError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::ClientIdentity(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn is_repository_url_matches_requests_for_the_repository() {
+        let uri_prefix = "https://apt.example.com/ubuntu";
+        assert!(is_repository_url(
+            uri_prefix,
+            "https://apt.example.com/ubuntu/dists/noble/InRelease"
+        ));
+        assert!(!is_repository_url(
+            uri_prefix,
+            "https://apt.example.com/ubuntu-ports/dists/noble/InRelease"
+        ));
+        assert!(!is_repository_url(
+            uri_prefix,
+            "https://archive.ubuntu.com/ubuntu/dists/noble/InRelease"
+        ));
+    }
+
+    #[test]
+    fn read_pem_from_missing_env() {
+        match read_pem(&PemSource::Env("DEB_PACKAGES_TEST_MISSING_PEM".to_string())).unwrap_err() {
+            ClientIdentityError::MissingPemEnv(name) => {
+                assert_eq!(name, "DEB_PACKAGES_TEST_MISSING_PEM");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+}
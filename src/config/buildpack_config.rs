@@ -1,20 +1,141 @@
 use crate::DebianPackagesBuildpackError;
-use crate::config::custom_source::{CustomSource, ParseCustomSourceError};
-use crate::config::download_url::{DownloadUrl, ParseDownloadUrlError};
-use crate::config::{ParseRequestedPackageError, RequestedPackage};
+use crate::config::custom_source::{CUSTOM_SOURCE_KEYS, CustomSource, ParseCustomSourceError};
+use crate::config::default_suites::{DEFAULT_SUITES_KEYS, DefaultSuites};
+use crate::config::download_url::{DOWNLOAD_URL_KEYS, DownloadUrl, ParseDownloadUrlError};
+use crate::config::hooks_config::{HOOKS_CONFIG_KEYS, HooksConfig};
+use crate::config::http_config::{HTTP_CONFIG_KEYS, HttpConfig};
+use crate::config::{
+    ParseRequestedPackageError, REQUESTED_PACKAGE_KEYS, RequestedPackage, RequestedPackageDefaults,
+};
+use crate::debian::{PackageName, ParsePackageNameError};
+use bullet_stream::style;
+use edit_distance::edit_distance;
 use indexmap::IndexSet;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use toml_edit::{DocumentMut, TableLike};
+use std::time::Duration;
+use toml_edit::{DocumentMut, Item, TableLike};
 
 pub(crate) const NAMESPACED_CONFIG: &str = "com.heroku.buildpacks.deb-packages";
 
+// A dedicated config file for applications that can't add to their project.toml (e.g.; when it's
+// managed by another tool). The buildpack configuration is read from the root of this file
+// instead of the namespaced key used in project.toml.
+pub(crate) const STANDALONE_CONFIG_FILE: &str = "deb-packages.toml";
+
 #[derive(Debug, Default, Eq, PartialEq)]
+#[allow(clippy::struct_excessive_bools)]
 pub(crate) struct BuildpackConfig {
     pub(crate) install: IndexSet<RequestedPackage>,
+    pub(crate) profiles: BTreeMap<String, IndexSet<RequestedPackage>>,
     pub(crate) sources: Vec<CustomSource>,
     pub(crate) download: IndexSet<DownloadUrl>,
+    pub(crate) cache_key: Option<String>,
+    pub(crate) security_advisories: Option<SecurityAdvisoriesMode>,
+    pub(crate) essential_packages: EssentialPackagesMode,
+    pub(crate) prefer_alternatives: BTreeMap<String, String>,
+    pub(crate) env_order: BTreeMap<String, EnvOrder>,
+    pub(crate) pins: BTreeMap<String, String>,
+    pub(crate) default_suites: DefaultSuites,
+    pub(crate) default_components: Option<Vec<String>>,
+    pub(crate) default_source_signed_by: Option<String>,
+    pub(crate) index_max_age: Option<Duration>,
+    pub(crate) skip_phased_updates: bool,
+    pub(crate) prefer_https: bool,
+    pub(crate) strip_binaries: bool,
+    pub(crate) auto_resolve_libs: bool,
+    pub(crate) require_checksums: bool,
+    pub(crate) prune: Vec<PruneTarget>,
+    pub(crate) max_layer_size: Option<u64>,
+    pub(crate) max_download_cache_size: Option<u64>,
+    pub(crate) http: HttpConfig,
+    pub(crate) hooks: HooksConfig,
+    // the deprecated keys found in the configuration and why each one is deprecated
+    pub(crate) deprecated_keys: Vec<(&'static str, &'static str)>,
+    // the keys that were ignored since they aren't recognized (only without a `schema-version`)
+    pub(crate) unknown_keys: Vec<UnknownKey>,
+}
+
+// NOTE: The configuration schema is versioned with a `schema-version` key in the buildpack's own
+//       configuration since the `[_]` table of project.toml belongs to the project descriptor and
+//       platforms reject versions of it they don't know. Configuration without a version is read
+//       with the original schema where unknown keys are ignored, and newer versions are strict
+//       so a typo fails the build instead of being silently ignored.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum SchemaVersion {
+    #[default]
+    Unversioned,
+    V0_3,
+}
+
+// The values of `schema-version` accepted by this version of the buildpack.
+pub(crate) const SCHEMA_VERSIONS: [&str; 1] = ["0.3"];
+
+// Keys that still work with a versioned schema but warn, along with what replaces them.
+const DEPRECATED_CONFIG_KEYS: [(&str, &str); 0] = [];
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum SecurityAdvisoriesMode {
+    Warn,
+    Fail,
+}
+
+// How to handle resolved packages that are marked `Essential: yes` or `Priority: required` (e.g.;
+// `libc6`, `dpkg`) since overlaying a different version of them on the base image almost always
+// breaks it.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) enum EssentialPackagesMode {
+    Allow,
+    #[default]
+    Warn,
+    Fail,
+}
+
+// Where the layer directories go in a search path variable like `PATH` relative to the directories
+// already set by the base image and earlier buildpacks.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum EnvOrder {
+    #[default]
+    Prepend,
+    Append,
+}
+
+// The search path variables configured by the buildpack whose order can be changed.
+pub(crate) const ENV_ORDER_VARS: [&str; 7] = [
+    "PATH",
+    "LD_LIBRARY_PATH",
+    "LIBRARY_PATH",
+    "INCLUDE_PATH",
+    "CPATH",
+    "CPPPATH",
+    "PKG_CONFIG_PATH",
+];
+
+// Files that are removed from the installed packages since they are only needed to build against
+// the packages (e.g.; from `-dev` packages pulled in as dependencies) and add size to the image.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum PruneTarget {
+    // static libraries (`*.a`)
+    StaticLibs,
+    // C/C++ headers (`usr/include`)
+    Headers,
+}
+
+impl FromStr for PruneTarget {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "static-libs" => Ok(PruneTarget::StaticLibs),
+            "headers" => Ok(PruneTarget::Headers),
+            _ => Err(()),
+        }
+    }
 }
 
 impl BuildpackConfig {
@@ -27,6 +148,86 @@ impl BuildpackConfig {
             Err(e) => Err(e),
         }
     }
+
+    // Adds the packages requested with the `BP_DEB_PACKAGES_INSTALL` environment variable, given
+    // as package names separated by whitespace or commas. Packages already requested in the
+    // configuration file keep the options they were given there.
+    pub(crate) fn add_env_install(&mut self, value: &str) -> Result<(), ConfigError> {
+        for package_name in value
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|package_name| !package_name.is_empty())
+        {
+            let package_name =
+                PackageName::from_str(package_name).map_err(ConfigError::InvalidEnvPackageName)?;
+            if self
+                .install
+                .iter()
+                .any(|requested_package| requested_package.name == package_name)
+            {
+                continue;
+            }
+            self.install.insert(RequestedPackage {
+                name: package_name,
+                skip_dependencies: false,
+                force: false,
+                optional: false,
+                arch: None,
+                suite: None,
+                include_paths: vec![],
+                prefix: None,
+                launch_only: false,
+            });
+        }
+        Ok(())
+    }
+
+    // Adds the packages from the profile selected with the `BP_DEB_PACKAGES_PROFILE` environment
+    // variable to the base install list. A package requested in both keeps the options given to it
+    // by the profile.
+    pub(crate) fn apply_profile(&mut self, profile_name: &str) -> Result<(), ConfigError> {
+        let Some(profile_packages) = self.profiles.remove(profile_name) else {
+            return Err(ConfigError::UnknownProfile(
+                profile_name.to_string(),
+                self.profiles.keys().cloned().collect(),
+            ));
+        };
+        self.install.retain(|requested_package| {
+            !profile_packages
+                .iter()
+                .any(|profile_package| profile_package.name == requested_package.name)
+        });
+        self.install.extend(profile_packages);
+        Ok(())
+    }
+
+    pub(crate) fn from_standalone_str(contents: &str) -> Result<Self, ParseConfigError> {
+        let doc = parse_config_toml(contents)?;
+        let config = doc
+            .as_item()
+            .as_table_like()
+            .ok_or(ParseConfigError::WrongConfigType)?;
+        BuildpackConfig::try_from(config)
+    }
+
+    // Lists the keys in the configuration file that aren't recognized by the buildpack (e.g.;
+    // `instal`, `sources[0].signedby`). These are ignored when the configuration is parsed.
+    pub(crate) fn find_unknown_keys(config_file: &Path) -> Result<Vec<UnknownKey>, ConfigError> {
+        let contents = read_config_file(config_file)?;
+        let doc = parse_config_toml(&contents)
+            .map_err(|e| ConfigError::ParseConfig(config_file.to_path_buf(), e))?;
+        let config_item = if config_file
+            .file_name()
+            .is_some_and(|file_name| file_name == STANDALONE_CONFIG_FILE)
+        {
+            doc.as_item()
+                .as_table_like()
+                .ok_or(ParseConfigError::WrongConfigType)
+        } else {
+            get_buildpack_namespaced_config(&doc)
+        }
+        .map_err(|e| ConfigError::ParseConfig(config_file.to_path_buf(), e))?;
+        Ok(find_unknown_keys(config_item))
+    }
 }
 
 impl TryFrom<PathBuf> for BuildpackConfig {
@@ -34,7 +235,15 @@ impl TryFrom<PathBuf> for BuildpackConfig {
 
     fn try_from(value: PathBuf) -> Result<Self, Self::Error> {
         let contents = read_config_file(&value)?;
-        BuildpackConfig::from_str(&contents).map_err(|e| ConfigError::ParseConfig(value, e))
+        if value
+            .file_name()
+            .is_some_and(|file_name| file_name == STANDALONE_CONFIG_FILE)
+        {
+            BuildpackConfig::from_standalone_str(&contents)
+        } else {
+            BuildpackConfig::from_str(&contents)
+        }
+        .map_err(|e| ConfigError::ParseConfig(value, e))
     }
 }
 
@@ -51,20 +260,64 @@ impl FromStr for BuildpackConfig {
 impl TryFrom<&dyn TableLike> for BuildpackConfig {
     type Error = ParseConfigError;
 
+    #[allow(clippy::too_many_lines)]
     fn try_from(config_item: &dyn TableLike) -> Result<Self, Self::Error> {
+        let schema_version = match config_item.get("schema-version") {
+            None => SchemaVersion::Unversioned,
+            Some(item) => match item.as_str() {
+                Some("0.3") => SchemaVersion::V0_3,
+                _ => Err(ParseConfigError::UnsupportedSchemaVersion(
+                    item.to_string().trim().to_string(),
+                ))?,
+            },
+        };
+
+        let unknown_keys = find_unknown_keys(config_item);
+        let (deprecated_keys, unknown_keys) = match schema_version {
+            SchemaVersion::Unversioned => (vec![], unknown_keys),
+            SchemaVersion::V0_3 => {
+                if !unknown_keys.is_empty() {
+                    Err(ParseConfigError::UnknownKeys(unknown_keys))?;
+                }
+                (
+                    find_deprecated_keys(config_item, &DEPRECATED_CONFIG_KEYS),
+                    vec![],
+                )
+            }
+        };
+
         let mut install = IndexSet::new();
         let mut sources = Vec::new();
         let mut download = IndexSet::new();
 
+        let requested_package_defaults = RequestedPackageDefaults {
+            skip_dependencies: config_item
+                .get("skip_dependencies")
+                .and_then(Item::as_bool)
+                .unwrap_or_default(),
+            force: config_item
+                .get("force")
+                .and_then(Item::as_bool)
+                .unwrap_or_default(),
+        };
+
         if let Some(install_values) = config_item.get("install").and_then(|item| item.as_array()) {
             for install_value in install_values {
                 install.insert(
-                    RequestedPackage::try_from(install_value)
-                        .map_err(|e| Self::Error::ParseRequestedPackage(Box::new(e)))?,
+                    RequestedPackage::from_value_with_defaults(
+                        install_value,
+                        requested_package_defaults,
+                    )
+                    .map_err(|e| Self::Error::ParseRequestedPackage(Box::new(e)))?,
                 );
             }
         }
 
+        let profiles = match config_item.get("profiles") {
+            None => BTreeMap::new(),
+            Some(item) => parse_profiles(item, requested_package_defaults)?,
+        };
+
         if let Some(source_values) = config_item
             .get("sources")
             .and_then(|item| item.as_array_of_tables())
@@ -87,18 +340,563 @@ impl TryFrom<&dyn TableLike> for BuildpackConfig {
             }
         }
 
+        let cache_key = config_item
+            .get("cache_key")
+            .and_then(|item| item.as_str())
+            .map(ToString::to_string);
+
+        let security_advisories = match config_item.get("security_advisories") {
+            None => None,
+            Some(item) => match item.as_str() {
+                Some("off") => None,
+                Some("warn") => Some(SecurityAdvisoriesMode::Warn),
+                Some("fail") => Some(SecurityAdvisoriesMode::Fail),
+                _ => Err(ParseConfigError::InvalidSecurityAdvisories(
+                    item.to_string().trim().to_string(),
+                ))?,
+            },
+        };
+
+        let essential_packages = match config_item.get("essential_packages") {
+            None => EssentialPackagesMode::default(),
+            Some(item) => match item.as_str() {
+                Some("allow") => EssentialPackagesMode::Allow,
+                Some("warn") => EssentialPackagesMode::Warn,
+                Some("fail") => EssentialPackagesMode::Fail,
+                _ => Err(ParseConfigError::InvalidEssentialPackages(
+                    item.to_string().trim().to_string(),
+                ))?,
+            },
+        };
+
+        let prefer_alternatives = match config_item.get("prefer_alternatives") {
+            None => BTreeMap::new(),
+            Some(item) => parse_prefer_alternatives(item).ok_or_else(|| {
+                ParseConfigError::InvalidPreferAlternatives(item.to_string().trim().to_string())
+            })?,
+        };
+
+        let env_order = match config_item.get("env_order") {
+            None => BTreeMap::new(),
+            Some(item) => parse_env_order(item).ok_or_else(|| {
+                ParseConfigError::InvalidEnvOrder(item.to_string().trim().to_string())
+            })?,
+        };
+
+        let pins = match config_item.get("pins") {
+            None => BTreeMap::new(),
+            Some(item) => parse_pins(item).ok_or_else(|| {
+                ParseConfigError::InvalidPins(item.to_string().trim().to_string())
+            })?,
+        };
+
+        let default_suites = config_item
+            .get("default_suites")
+            .and_then(Item::as_table_like)
+            .map(DefaultSuites::from)
+            .unwrap_or_default();
+
+        let default_components = match config_item.get("default_components") {
+            None => None,
+            Some(item) => Some(parse_default_components(item).ok_or_else(|| {
+                ParseConfigError::InvalidDefaultComponents(item.to_string().trim().to_string())
+            })?),
+        };
+
+        // private mirrors of the Ubuntu archive often re-sign the Release files with their own key
+        let default_source_signed_by = match config_item.get("default_source_signed_by") {
+            None => None,
+            Some(item) => Some(
+                item.as_str()
+                    .filter(|signed_by| !signed_by.trim().is_empty())
+                    .map(ToString::to_string)
+                    .ok_or_else(|| {
+                        ParseConfigError::InvalidDefaultSourceSignedBy(
+                            item.to_string().trim().to_string(),
+                        )
+                    })?,
+            ),
+        };
+
+        let index_max_age = match config_item.get("index_max_age") {
+            None => None,
+            Some(item) => Some(item.as_str().and_then(parse_duration).ok_or_else(|| {
+                ParseConfigError::InvalidIndexMaxAge(item.to_string().trim().to_string())
+            })?),
+        };
+
+        let skip_phased_updates = config_item
+            .get("skip_phased_updates")
+            .and_then(Item::as_bool)
+            .unwrap_or_default();
+
+        let prefer_https = config_item
+            .get("prefer_https")
+            .and_then(Item::as_bool)
+            .unwrap_or_default();
+
+        let strip_binaries = config_item
+            .get("strip_binaries")
+            .and_then(Item::as_bool)
+            .unwrap_or_default();
+
+        let auto_resolve_libs = config_item
+            .get("auto_resolve_libs")
+            .and_then(Item::as_bool)
+            .unwrap_or_default();
+
+        // the contents of a download url can change without notice so a checksum can be required
+        // for each one to make sure the same file is installed on every build, unless the download
+        // explicitly opts out with `verify = false`
+        let require_checksums = config_item
+            .get("require_checksums")
+            .and_then(Item::as_bool)
+            .unwrap_or_default();
+        if require_checksums
+            && let Some(download_url) = download.iter().find(|download_url| {
+                download_url.sha256().is_none() && !download_url.is_unverified()
+            })
+        {
+            Err(ParseConfigError::MissingDownloadChecksum(
+                download_url.to_string(),
+            ))?;
+        }
+
+        let prune = match config_item.get("prune") {
+            None => vec![],
+            Some(item) => parse_prune(item).ok_or_else(|| {
+                ParseConfigError::InvalidPrune(item.to_string().trim().to_string())
+            })?,
+        };
+
+        let max_layer_size = match config_item.get("max_layer_size") {
+            None => None,
+            Some(item) => Some(item.as_str().and_then(parse_size).ok_or_else(|| {
+                ParseConfigError::InvalidMaxLayerSize(item.to_string().trim().to_string())
+            })?),
+        };
+
+        let max_download_cache_size = match config_item.get("max_download_cache_size") {
+            None => None,
+            Some(item) => Some(item.as_str().and_then(parse_size).ok_or_else(|| {
+                ParseConfigError::InvalidMaxDownloadCacheSize(item.to_string().trim().to_string())
+            })?),
+        };
+
+        let http = match config_item.get("http").and_then(Item::as_table_like) {
+            None => HttpConfig::default(),
+            Some(table) => HttpConfig::try_from(table)?,
+        };
+
+        let hooks = match config_item.get("hooks").and_then(Item::as_table_like) {
+            None => HooksConfig::default(),
+            Some(table) => HooksConfig::try_from(table)?,
+        };
+
         Ok(BuildpackConfig {
             install,
+            profiles,
             sources,
             download,
+            cache_key,
+            security_advisories,
+            essential_packages,
+            prefer_alternatives,
+            env_order,
+            pins,
+            default_suites,
+            default_components,
+            default_source_signed_by,
+            index_max_age,
+            skip_phased_updates,
+            prefer_https,
+            strip_binaries,
+            auto_resolve_libs,
+            require_checksums,
+            prune,
+            max_layer_size,
+            max_download_cache_size,
+            http,
+            hooks,
+            deprecated_keys,
+            unknown_keys,
         })
     }
 }
 
+// The keys recognized at the root of the buildpack configuration.
+pub(crate) const CONFIG_KEYS: [&str; 27] = [
+    "schema-version",
+    "install",
+    "profiles",
+    "sources",
+    "download",
+    "skip_dependencies",
+    "force",
+    "cache_key",
+    "security_advisories",
+    "essential_packages",
+    "prefer_alternatives",
+    "env_order",
+    "pins",
+    "default_suites",
+    "default_components",
+    "default_source_signed_by",
+    "index_max_age",
+    "skip_phased_updates",
+    "prefer_https",
+    "strip_binaries",
+    "auto_resolve_libs",
+    "require_checksums",
+    "prune",
+    "max_layer_size",
+    "max_download_cache_size",
+    "http",
+    "hooks",
+];
+
+fn find_deprecated_keys(
+    config_item: &dyn TableLike,
+    deprecated_config_keys: &[(&'static str, &'static str)],
+) -> Vec<(&'static str, &'static str)> {
+    deprecated_config_keys
+        .iter()
+        .filter(|(key, _)| config_item.contains_key(key))
+        .copied()
+        .collect()
+}
+
+#[allow(clippy::too_many_lines)]
+fn find_unknown_keys(config_item: &dyn TableLike) -> Vec<UnknownKey> {
+    let mut unknown_keys = Vec::new();
+
+    for (key, item) in config_item.iter() {
+        if !CONFIG_KEYS.contains(&key) {
+            unknown_keys.push(UnknownKey::new(key.to_string(), key, &CONFIG_KEYS));
+            continue;
+        }
+        match key {
+            "install" => {
+                for (index, value) in item.as_array().into_iter().flatten().enumerate() {
+                    if let Some(table) = value.as_inline_table() {
+                        for (table_key, _) in table {
+                            if !REQUESTED_PACKAGE_KEYS.contains(&table_key) {
+                                unknown_keys.push(UnknownKey::new(
+                                    format!("install[{index}].{table_key}"),
+                                    table_key,
+                                    &REQUESTED_PACKAGE_KEYS,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            "profiles" => {
+                for (profile_name, profile) in
+                    item.as_table_like().into_iter().flat_map(TableLike::iter)
+                {
+                    for (profile_key, profile_item) in profile
+                        .as_table_like()
+                        .into_iter()
+                        .flat_map(TableLike::iter)
+                    {
+                        if profile_key != "install" {
+                            unknown_keys.push(UnknownKey::new(
+                                format!("profiles.{profile_name}.{profile_key}"),
+                                profile_key,
+                                &["install"],
+                            ));
+                            continue;
+                        }
+                        for (index, value) in
+                            profile_item.as_array().into_iter().flatten().enumerate()
+                        {
+                            if let Some(table) = value.as_inline_table() {
+                                for (table_key, _) in table {
+                                    if !REQUESTED_PACKAGE_KEYS.contains(&table_key) {
+                                        unknown_keys.push(UnknownKey::new(
+                                            format!(
+                                                "profiles.{profile_name}.install[{index}].{table_key}"
+                                            ),
+                                            table_key,
+                                            &REQUESTED_PACKAGE_KEYS,
+                                        ));
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            "download" => {
+                for (index, value) in item.as_array().into_iter().flatten().enumerate() {
+                    if let Some(table) = value.as_inline_table() {
+                        for (table_key, _) in table {
+                            if !DOWNLOAD_URL_KEYS.contains(&table_key) {
+                                unknown_keys.push(UnknownKey::new(
+                                    format!("download[{index}].{table_key}"),
+                                    table_key,
+                                    &DOWNLOAD_URL_KEYS,
+                                ));
+                            }
+                        }
+                    }
+                }
+            }
+            "sources" => {
+                for (index, table) in item.as_array_of_tables().into_iter().flatten().enumerate() {
+                    for (table_key, _) in table {
+                        if !CUSTOM_SOURCE_KEYS.contains(&table_key) {
+                            unknown_keys.push(UnknownKey::new(
+                                format!("sources[{index}].{table_key}"),
+                                table_key,
+                                &CUSTOM_SOURCE_KEYS,
+                            ));
+                        }
+                    }
+                }
+            }
+            "default_suites" => {
+                for (table_key, _) in item.as_table_like().into_iter().flat_map(TableLike::iter) {
+                    if !DEFAULT_SUITES_KEYS.contains(&table_key) {
+                        unknown_keys.push(UnknownKey::new(
+                            format!("default_suites.{table_key}"),
+                            table_key,
+                            &DEFAULT_SUITES_KEYS,
+                        ));
+                    }
+                }
+            }
+            "http" => {
+                for (table_key, _) in item.as_table_like().into_iter().flat_map(TableLike::iter) {
+                    if !HTTP_CONFIG_KEYS.contains(&table_key) {
+                        unknown_keys.push(UnknownKey::new(
+                            format!("http.{table_key}"),
+                            table_key,
+                            &HTTP_CONFIG_KEYS,
+                        ));
+                    }
+                }
+            }
+            "hooks" => {
+                for (table_key, _) in item.as_table_like().into_iter().flat_map(TableLike::iter) {
+                    if !HOOKS_CONFIG_KEYS.contains(&table_key) {
+                        unknown_keys.push(UnknownKey::new(
+                            format!("hooks.{table_key}"),
+                            table_key,
+                            &HOOKS_CONFIG_KEYS,
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    unknown_keys
+}
+
+// A key that isn't recognized by the buildpack, given by its path in the configuration (e.g.;
+// `sources[0].signedby`), along with the closest valid key when it looks like a typo of one.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct UnknownKey {
+    pub(crate) path: String,
+    pub(crate) suggestion: Option<&'static str>,
+}
+
+impl UnknownKey {
+    fn new(path: String, key: &str, valid_keys: &[&'static str]) -> Self {
+        Self {
+            path,
+            suggestion: suggest_key(key, valid_keys),
+        }
+    }
+}
+
+impl Display for UnknownKey {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", style::value(&self.path))?;
+        if let Some(suggestion) = self.suggestion {
+            write!(f, " (did you mean {}?)", style::value(suggestion))?;
+        }
+        Ok(())
+    }
+}
+
+// Keys are compared ignoring case and with dashes treated as underscores first since those are
+// the most common typos (e.g.; `skip-dependencies`), then by edit distance for misspellings
+// (e.g.; `instal`, `signedby`).
+fn suggest_key(key: &str, valid_keys: &[&'static str]) -> Option<&'static str> {
+    let normalized_key = key.to_lowercase().replace('-', "_");
+    if let Some(valid_key) = valid_keys
+        .iter()
+        .find(|valid_key| valid_key.replace('-', "_") == normalized_key)
+    {
+        return Some(valid_key);
+    }
+    // short keys only get suggestions that are a single edit away to avoid unrelated matches
+    let max_distance = (key.len() / 4).clamp(1, 3);
+    valid_keys
+        .iter()
+        .map(|valid_key| {
+            let distance = edit_distance(&normalized_key, valid_key.replace('-', "_"));
+            (distance, *valid_key)
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, valid_key)| valid_key)
+}
+
+// Named sets of packages (e.g.; `[profiles.ci]`) that are only installed when selected, each with
+// an `install` list in the same format as the root `install` list.
+fn parse_profiles(
+    item: &Item,
+    requested_package_defaults: RequestedPackageDefaults,
+) -> Result<BTreeMap<String, IndexSet<RequestedPackage>>, ParseConfigError> {
+    let invalid_profile = || ParseConfigError::InvalidProfile(item.to_string().trim().to_string());
+    let mut profiles = BTreeMap::new();
+    for (profile_name, profile) in item.as_table_like().ok_or_else(invalid_profile)?.iter() {
+        let install_values = profile
+            .as_table_like()
+            .and_then(|profile| profile.get("install"))
+            .and_then(Item::as_array)
+            .ok_or_else(|| {
+                ParseConfigError::InvalidProfile(profile.to_string().trim().to_string())
+            })?;
+        let mut install = IndexSet::new();
+        for install_value in install_values {
+            install.insert(
+                RequestedPackage::from_value_with_defaults(
+                    install_value,
+                    requested_package_defaults,
+                )
+                .map_err(|e| ParseConfigError::ParseRequestedPackage(Box::new(e)))?,
+            );
+        }
+        profiles.insert(profile_name.to_string(), install);
+    }
+    Ok(profiles)
+}
+
+// The components published by the Ubuntu repositories the default sources are configured with.
+const UBUNTU_COMPONENTS: [&str; 4] = ["main", "universe", "restricted", "multiverse"];
+
+fn parse_default_components(item: &Item) -> Option<Vec<String>> {
+    let mut components = Vec::new();
+    for value in item.as_array()? {
+        let component = value
+            .as_str()
+            .filter(|component| UBUNTU_COMPONENTS.contains(component))?;
+        if !components.iter().any(|existing| existing == component) {
+            components.push(component.to_string());
+        }
+    }
+    if components.is_empty() {
+        None
+    } else {
+        Some(components)
+    }
+}
+
+// Maps a dependency name (e.g.; the virtual package `debconf-2.0`) to the package that should be
+// installed for it (e.g.; `cdebconf`) when it's one of several alternatives.
+fn parse_prefer_alternatives(item: &Item) -> Option<BTreeMap<String, String>> {
+    let mut prefer_alternatives = BTreeMap::new();
+    for (dependency, value) in item.as_table_like()?.iter() {
+        let preferred = value.as_str()?.trim();
+        PackageName::from_str(dependency).ok()?;
+        PackageName::from_str(preferred).ok()?;
+        prefer_alternatives.insert(dependency.to_string(), preferred.to_string());
+    }
+    Some(prefer_alternatives)
+}
+
+// Maps a search path variable (e.g.; `PATH`) to whether the layer directories are prepended or
+// appended to it.
+fn parse_env_order(item: &Item) -> Option<BTreeMap<String, EnvOrder>> {
+    let mut env_order = BTreeMap::new();
+    for (name, value) in item.as_table_like()?.iter() {
+        if !ENV_ORDER_VARS.contains(&name) {
+            None?;
+        }
+        let order = match value.as_str()? {
+            "prepend" => EnvOrder::Prepend,
+            "append" => EnvOrder::Append,
+            _ => None?,
+        };
+        env_order.insert(name.to_string(), order);
+    }
+    Some(env_order)
+}
+
+// Maps a package name (e.g.; `libssl3t64`) to the exact version that must be installed for it
+// wherever it appears in the dependency tree.
+fn parse_pins(item: &Item) -> Option<BTreeMap<String, String>> {
+    let mut pins = BTreeMap::new();
+    for (package, value) in item.as_table_like()?.iter() {
+        let version = value.as_str()?.trim();
+        PackageName::from_str(package).ok()?;
+        debversion::Version::from_str(version).ok()?;
+        pins.insert(package.to_string(), version.to_string());
+    }
+    Some(pins)
+}
+
+fn parse_prune(item: &Item) -> Option<Vec<PruneTarget>> {
+    let mut prune = Vec::new();
+    for value in item.as_array()? {
+        let prune_target = value.as_str().and_then(|value| value.parse().ok())?;
+        if !prune.contains(&prune_target) {
+            prune.push(prune_target);
+        }
+    }
+    Some(prune)
+}
+
+// Parses a duration given as a whole number followed by a unit (e.g.; "90s", "30m", "24h", "7d").
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let unit_index = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(unit_index);
+    let amount = amount.parse::<u64>().ok()?;
+    let seconds_per_unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => None?,
+    };
+    amount
+        .checked_mul(seconds_per_unit)
+        .map(Duration::from_secs)
+}
+
+// Parses a size in bytes given as a whole number followed by a decimal (e.g.; "500MB") or binary
+// (e.g.; "512MiB") unit.
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let unit_index = value.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = value.split_at(unit_index);
+    let amount = amount.parse::<u64>().ok()?;
+    let bytes_per_unit = match unit {
+        "B" => 1,
+        "KB" => 1000,
+        "MB" => 1000 * 1000,
+        "GB" => 1000 * 1000 * 1000,
+        "KiB" => 1024,
+        "MiB" => 1024 * 1024,
+        "GiB" => 1024 * 1024 * 1024,
+        _ => None?,
+    };
+    amount.checked_mul(bytes_per_unit)
+}
+
 #[derive(Debug)]
 pub(crate) enum ConfigError {
     ReadConfig(PathBuf, std::io::Error),
     ParseConfig(PathBuf, ParseConfigError),
+    InvalidEnvPackageName(ParsePackageNameError),
+    UnknownProfile(String, Vec<String>),
 }
 
 #[derive(Debug)]
@@ -108,6 +906,23 @@ pub(crate) enum ParseConfigError {
     ParseRequestedPackage(Box<ParseRequestedPackageError>),
     ParseCustomSource(Box<ParseCustomSourceError>),
     ParseDownloadUrl(Box<ParseDownloadUrlError>),
+    MissingDownloadChecksum(String),
+    InvalidSecurityAdvisories(String),
+    InvalidEssentialPackages(String),
+    InvalidPreferAlternatives(String),
+    InvalidEnvOrder(String),
+    InvalidPins(String),
+    InvalidDefaultComponents(String),
+    InvalidDefaultSourceSignedBy(String),
+    InvalidIndexMaxAge(String),
+    InvalidPrune(String),
+    InvalidMaxLayerSize(String),
+    InvalidMaxDownloadCacheSize(String),
+    InvalidHttpOption(String, String),
+    InvalidHook(String, String),
+    InvalidProfile(String),
+    UnsupportedSchemaVersion(String),
+    UnknownKeys(Vec<UnknownKey>),
     WrongConfigType,
 }
 
@@ -151,8 +966,8 @@ fn get_buildpack_namespaced_config(doc: &DocumentMut) -> Result<&dyn TableLike,
 
 #[cfg(test)]
 mod test {
-    use crate::debian::ArchitectureName::{AMD_64, ARM_64};
-    use crate::debian::PackageName;
+    use crate::config::custom_source::{ClientIdentity, PemSource};
+    use crate::debian::ArchitectureName::{AMD_64, ARM_64, I_386};
     use indoc::indoc;
 
     use super::*;
@@ -167,19 +982,42 @@ schema-version = "0.2"
 [com.heroku.buildpacks.deb-packages]
 install = [
     "package1",
-    { name = "package2" },
-    { name = "package3", skip_dependencies = true, force = true },
+    { name = "package2", suite = "noble-backports" },
+    { name = "package3", skip_dependencies = true, force = true, optional = true, prefix = "/opt/package3/", launch_only = true },
+    { name = "package4", arch = "i386", include_paths = ["./usr/lib/i386-linux-gnu/**"] },
 ]
 
 download = [
   "https://some.url/path/to/package.deb"
 ]
 
+cache_key = "v2"
+security_advisories = "warn"
+essential_packages = "fail"
+prefer_alternatives = { "debconf-2.0" = "cdebconf" }
+env_order = { PATH = "append", LD_LIBRARY_PATH = "prepend" }
+pins = { "libssl3t64" = "3.0.13-0ubuntu3.4" }
+default_suites = { updates = false, backports = true, proposed = false }
+default_components = ["main", "restricted"]
+default_source_signed_by = "/etc/apt/keyrings/mirror.gpg"
+index_max_age = "24h"
+skip_phased_updates = true
+prefer_https = true
+strip_binaries = true
+auto_resolve_libs = true
+prune = ["static-libs", "headers", "static-libs"]
+max_layer_size = "500MB"
+max_download_cache_size = "1GiB"
+http = { pool_max_idle_per_host = 32, tcp_keepalive = "60s" }
+hooks = { post_install = "bash scripts/fix-links.sh" }
+
 [[com.heroku.buildpacks.deb-packages.sources]]
 uri = "http://archive.ubuntu.com/ubuntu"
 suites = ["main"]
 components = ["multiverse"]
 arch = ["amd64", "arm64"]
+allow_weak_checksums = true
+verify_package_signatures = true
 signed_by = """-----BEGIN PGP PUBLIC KEY BLOCK-----
 
 NxRt3Z+7w5HMIN2laKp+ItxloPWGBdcHU4o2ZnWgsVT8Y/a+RED75DDbAQ6lS3fV
@@ -202,27 +1040,102 @@ iGa6i2oLaGzGaQZDpdqyQZiYpQEYw9xN+8g=
                         name: PackageName::from_str("package1").unwrap(),
                         skip_dependencies: false,
                         force: false,
+                        optional: false,
+                        arch: None,
+                        suite: None,
+                        include_paths: vec![],
+                        prefix: None,
+                        launch_only: false,
                     },
                     RequestedPackage {
                         name: PackageName::from_str("package2").unwrap(),
                         skip_dependencies: false,
                         force: false,
+                        optional: false,
+                        arch: None,
+                        suite: Some("noble-backports".to_string()),
+                        include_paths: vec![],
+                        prefix: None,
+                        launch_only: false,
                     },
                     RequestedPackage {
                         name: PackageName::from_str("package3").unwrap(),
                         skip_dependencies: true,
                         force: true,
+                        optional: true,
+                        arch: None,
+                        suite: None,
+                        include_paths: vec![],
+                        prefix: Some("opt/package3".to_string()),
+                        launch_only: true,
+                    },
+                    RequestedPackage {
+                        name: PackageName::from_str("package4").unwrap(),
+                        skip_dependencies: false,
+                        force: false,
+                        optional: false,
+                        arch: Some(I_386),
+                        suite: None,
+                        include_paths: vec!["usr/lib/i386-linux-gnu/**".to_string()],
+                        prefix: None,
+                        launch_only: false,
                     }
                 ]),
+                profiles: BTreeMap::new(),
                 download: IndexSet::from([DownloadUrl::from_str(
                     "https://some.url/path/to/package.deb"
                 )
                 .unwrap()]),
+                cache_key: Some("v2".to_string()),
+                security_advisories: Some(SecurityAdvisoriesMode::Warn),
+                essential_packages: EssentialPackagesMode::Fail,
+                prefer_alternatives: BTreeMap::from([(
+                    "debconf-2.0".to_string(),
+                    "cdebconf".to_string()
+                )]),
+                env_order: BTreeMap::from([
+                    ("LD_LIBRARY_PATH".to_string(), EnvOrder::Prepend),
+                    ("PATH".to_string(), EnvOrder::Append),
+                ]),
+                pins: BTreeMap::from([("libssl3t64".to_string(), "3.0.13-0ubuntu3.4".to_string())]),
+                default_suites: DefaultSuites {
+                    updates: Some(false),
+                    security: None,
+                    backports: Some(true),
+                    proposed: Some(false),
+                },
+                default_components: Some(vec!["main".to_string(), "restricted".to_string()]),
+                default_source_signed_by: Some("/etc/apt/keyrings/mirror.gpg".to_string()),
+                index_max_age: Some(Duration::from_hours(24)),
+                skip_phased_updates: true,
+                prefer_https: true,
+                strip_binaries: true,
+                auto_resolve_libs: true,
+                require_checksums: false,
+                prune: vec![PruneTarget::StaticLibs, PruneTarget::Headers],
+                max_layer_size: Some(500_000_000),
+                max_download_cache_size: Some(1024 * 1024 * 1024),
+                http: HttpConfig {
+                    http2_prior_knowledge: false,
+                    pool_max_idle_per_host: Some(32),
+                    pool_idle_timeout: None,
+                    tcp_keepalive: Some(Duration::from_mins(1)),
+                    ..HttpConfig::default()
+                },
+                hooks: HooksConfig {
+                    pre_install: None,
+                    post_install: Some("bash scripts/fix-links.sh".to_string()),
+                },
+                deprecated_keys: vec![],
+                unknown_keys: vec![],
                 sources: Vec::from([CustomSource {
                     uri: "http://archive.ubuntu.com/ubuntu".into(),
                     suites: vec!["main".into()],
                     components: vec!["multiverse".into()],
                     arch: vec![AMD_64, ARM_64],
+                    allow_weak_checksums: true,
+                    verify_package_signatures: true,
+                    client_identity: None,
                     signed_by: indoc! { "
                         -----BEGIN PGP PUBLIC KEY BLOCK-----
 
@@ -255,6 +1168,357 @@ schema-version = "0.2"
         assert_eq!(config, BuildpackConfig::default());
     }
 
+    #[test]
+    fn test_deserialize_standalone_config() {
+        let toml = r#"
+install = ["package1", { name = "package2", skip_dependencies = true }]
+strip_binaries = true
+
+[[sources]]
+uri = "http://archive.ubuntu.com/ubuntu"
+suites = ["noble"]
+components = ["multiverse"]
+arch = ["amd64"]
+signed_by = "-----BEGIN PGP PUBLIC KEY BLOCK-----"
+        "#
+        .trim();
+        let config = BuildpackConfig::from_standalone_str(toml).unwrap();
+        assert_eq!(
+            config.install,
+            IndexSet::from([
+                RequestedPackage {
+                    name: PackageName::from_str("package1").unwrap(),
+                    skip_dependencies: false,
+                    force: false,
+                    optional: false,
+                    arch: None,
+                    suite: None,
+                    include_paths: vec![],
+                    prefix: None,
+                    launch_only: false,
+                },
+                RequestedPackage {
+                    name: PackageName::from_str("package2").unwrap(),
+                    skip_dependencies: true,
+                    force: false,
+                    optional: false,
+                    arch: None,
+                    suite: None,
+                    include_paths: vec![],
+                    prefix: None,
+                    launch_only: false,
+                },
+            ])
+        );
+        assert_eq!(config.sources.len(), 1);
+        assert!(config.strip_binaries);
+    }
+
+    #[test]
+    fn test_read_standalone_config_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_file = temp_dir.path().join(STANDALONE_CONFIG_FILE);
+        fs::write(&config_file, r#"install = ["package1"]"#).unwrap();
+        assert!(BuildpackConfig::is_present(&config_file).unwrap());
+        assert_eq!(
+            BuildpackConfig::try_from(config_file)
+                .unwrap()
+                .install
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_add_env_install() {
+        let mut config = BuildpackConfig::from_standalone_str(r#"install = ["package1"]"#).unwrap();
+        config
+            .add_env_install(" package2,package3  package1\npackage4 ")
+            .unwrap();
+        assert_eq!(
+            config
+                .install
+                .iter()
+                .map(|package| package.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["package1", "package2", "package3", "package4"]
+        );
+    }
+
+    #[test]
+    fn test_add_env_install_with_invalid_package_name() {
+        match BuildpackConfig::default()
+            .add_env_install("package1 invalid!name")
+            .unwrap_err()
+        {
+            ConfigError::InvalidEnvPackageName(e) => assert_eq!(e.package_name, "invalid!name"),
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_apply_profile() {
+        let mut config = BuildpackConfig::from_standalone_str(indoc! { r#"
+            install = ["package1", "package2"]
+            skip_dependencies = true
+
+            [profiles.ci]
+            install = ["package3", { name = "package1", skip_dependencies = false }]
+
+            [profiles.review]
+            install = ["package4"]
+        "# })
+        .unwrap();
+        assert_eq!(
+            config.profiles.keys().collect::<Vec<_>>(),
+            vec!["ci", "review"]
+        );
+        assert!(config.profiles["ci"][0].skip_dependencies);
+
+        config.apply_profile("ci").unwrap();
+        assert_eq!(
+            config
+                .install
+                .iter()
+                .map(|package| (package.name.as_str(), package.skip_dependencies))
+                .collect::<Vec<_>>(),
+            vec![("package2", true), ("package3", true), ("package1", false)]
+        );
+
+        match config.apply_profile("production").unwrap_err() {
+            ConfigError::UnknownProfile(profile_name, available_profiles) => {
+                assert_eq!(profile_name, "production");
+                assert_eq!(available_profiles, vec!["review"]);
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_profile() {
+        for toml in [
+            "profiles = [\"ci\"]",
+            "profiles = { ci = [\"package1\"] }",
+            "profiles = { ci = { packages = [\"package1\"] } }",
+        ] {
+            match BuildpackConfig::from_standalone_str(toml).unwrap_err() {
+                ParseConfigError::InvalidProfile(_) => {}
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+        match BuildpackConfig::from_standalone_str(
+            "profiles = { ci = { install = [\"not-a-package*\"] } }",
+        )
+        .unwrap_err()
+        {
+            ParseConfigError::ParseRequestedPackage(_) => {}
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_schema_version_rejects_unknown_keys() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+schema-version = "0.3"
+instal = ["package1"]
+install = [{ name = "package2", skip-dependencies = true }]
+        "#;
+        match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+            ParseConfigError::UnknownKeys(unknown_keys) => {
+                assert_eq!(
+                    unknown_keys,
+                    vec![
+                        UnknownKey {
+                            path: "instal".to_string(),
+                            suggestion: Some("install"),
+                        },
+                        UnknownKey {
+                            path: "install[0].skip-dependencies".to_string(),
+                            suggestion: Some("skip_dependencies"),
+                        },
+                    ]
+                );
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_without_schema_version_ignores_unknown_keys() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+instal = ["package1"]
+require_checksums = true
+        "#;
+        let config = BuildpackConfig::from_str(toml.trim()).unwrap();
+        assert!(config.install.is_empty());
+        assert!(config.deprecated_keys.is_empty());
+        assert_eq!(
+            config.unknown_keys,
+            vec![UnknownKey {
+                path: "instal".to_string(),
+                suggestion: Some("install"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_find_deprecated_keys() {
+        let config = DocumentMut::from_str(
+            r#"
+install = ["package1"]
+old_key = true
+            "#
+            .trim(),
+        )
+        .unwrap();
+        assert_eq!(
+            find_deprecated_keys(
+                config.as_table(),
+                &[
+                    ("old_key", "`new_key` replaces it"),
+                    ("other_key", "unused")
+                ]
+            ),
+            vec![("old_key", "`new_key` replaces it")]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_with_schema_version_keeps_require_checksums() {
+        let toml = r#"
+schema-version = "0.3"
+download = ["https://example.com/unpinned.deb"]
+        "#;
+        let config = BuildpackConfig::from_standalone_str(toml.trim()).unwrap();
+        assert!(!config.require_checksums);
+        assert!(config.deprecated_keys.is_empty());
+    }
+
+    #[test]
+    fn test_deserialize_with_schema_version_allows_unverified_downloads() {
+        let toml = r#"
+schema-version = "0.3"
+require_checksums = true
+download = [{ url = "https://example.com/nightly.deb", verify = false }]
+        "#;
+        let config = BuildpackConfig::from_standalone_str(toml.trim()).unwrap();
+        assert!(config.download.iter().all(DownloadUrl::is_unverified));
+    }
+
+    #[test]
+    fn test_deserialize_with_unsupported_schema_version() {
+        for schema_version in [r#""0.4""#, r#""0.2""#, "3"] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+schema-version = {schema_version}
+        "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::UnsupportedSchemaVersion(value) => {
+                    assert_eq!(value, schema_version);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_find_unknown_keys() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_file = temp_dir.path().join("project.toml");
+        fs::write(
+            &config_file,
+            indoc! { r#"
+                [_]
+                schema-version = "0.2"
+
+                [com.heroku.buildpacks.deb-packages]
+                instal = ["package1"]
+                mystery = true
+                install = [{ name = "package2", skip-dependencies = true }]
+                default_suites = { backport = true }
+                download = [{ url = "https://example.com/package.deb", header = { Accept = "*/*" } }]
+                hooks = { postinstall = "bash scripts/fix-links.sh" }
+                profiles = { ci = { instal = ["strace"], install = [{ name = "gdb", prefixx = "opt" }] } }
+
+                [[com.heroku.buildpacks.deb-packages.sources]]
+                uri = "http://archive.ubuntu.com/ubuntu"
+                suites = ["main"]
+                signedby = "..."
+            "# },
+        )
+        .unwrap();
+        assert_eq!(
+            BuildpackConfig::find_unknown_keys(&config_file)
+                .unwrap()
+                .iter()
+                .map(|unknown_key| (unknown_key.path.as_str(), unknown_key.suggestion))
+                .collect::<Vec<_>>(),
+            vec![
+                ("instal", Some("install")),
+                ("mystery", None),
+                ("install[0].skip-dependencies", Some("skip_dependencies")),
+                ("default_suites.backport", Some("backports")),
+                ("download[0].header", Some("headers")),
+                ("hooks.postinstall", Some("post_install")),
+                ("profiles.ci.instal", Some("install")),
+                ("profiles.ci.install[0].prefixx", Some("prefix")),
+                ("sources[0].signedby", Some("signed_by"))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_suggest_key() {
+        assert_eq!(suggest_key("Install", &CONFIG_KEYS), Some("install"));
+        assert_eq!(
+            suggest_key("schema_version", &CONFIG_KEYS),
+            Some("schema-version")
+        );
+        assert_eq!(suggest_key("prun", &CONFIG_KEYS), Some("prune"));
+        assert_eq!(
+            suggest_key("strip_binary", &CONFIG_KEYS),
+            Some("strip_binaries")
+        );
+        assert_eq!(suggest_key("url", &CUSTOM_SOURCE_KEYS), Some("uri"));
+        assert_eq!(suggest_key("pkgs", &CONFIG_KEYS), None);
+    }
+
+    #[test]
+    fn test_find_unknown_keys_in_valid_config() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_file = temp_dir.path().join(STANDALONE_CONFIG_FILE);
+        fs::write(
+            &config_file,
+            indoc! { r#"
+                install = ["package1", { name = "package2", force = true, launch_only = true }]
+                strip_binaries = true
+                default_suites = { backports = true }
+
+                [profiles.ci]
+                install = ["strace", { name = "gdb", launch_only = true }]
+            "# },
+        )
+        .unwrap();
+        assert!(
+            BuildpackConfig::find_unknown_keys(&config_file)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
     #[test]
     fn test_missing_root_config() {
         let toml = r#"
@@ -304,6 +1568,84 @@ install = [
         }
     }
 
+    #[test]
+    fn test_deserialize_with_invalid_include_paths() {
+        for include_paths in ["[]", r#""usr/lib/**""#, r#"["usr/../etc/*"]"#, r#"[""]"#] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+install = [
+    {{ name = "package1", include_paths = {include_paths} }},
+]
+            "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::ParseRequestedPackage(error) => match *error {
+                    ParseRequestedPackageError::InvalidIncludePaths(value) => {
+                        assert_eq!(value, include_paths);
+                    }
+                    e => panic!("Not the expected error - {e:?}"),
+                },
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_suite() {
+        for suite in ["true", r#""""#, r#"" ""#] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+install = [
+    {{ name = "package1", suite = {suite} }},
+]
+            "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::ParseRequestedPackage(error) => match *error {
+                    ParseRequestedPackageError::InvalidSuite(value) => {
+                        assert_eq!(value, suite);
+                    }
+                    e => panic!("Not the expected error - {e:?}"),
+                },
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_prefix() {
+        for prefix in ["true", r#""/""#, r#""../opt/package1""#] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+install = [
+    {{ name = "package1", prefix = {prefix} }},
+]
+            "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::ParseRequestedPackage(error) => match *error {
+                    ParseRequestedPackageError::InvalidPrefix(value) => {
+                        assert_eq!(value, prefix);
+                    }
+                    e => panic!("Not the expected error - {e:?}"),
+                },
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
     #[test]
     fn test_root_config_not_a_table() {
         let toml = r#"
@@ -321,6 +1663,386 @@ deb-packages = ["wrong"]
         }
     }
 
+    #[test]
+    fn test_deserialize_with_global_skip_dependencies() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+skip_dependencies = true
+install = [
+    "package1",
+    { name = "package2" },
+    { name = "package3", skip_dependencies = false },
+]
+        "#
+        .trim();
+        let config = BuildpackConfig::from_str(toml).unwrap();
+        assert_eq!(
+            config
+                .install
+                .iter()
+                .map(|requested_package| (
+                    requested_package.name.as_str(),
+                    requested_package.skip_dependencies
+                ))
+                .collect::<Vec<_>>(),
+            vec![("package1", true), ("package2", true), ("package3", false)]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_with_global_force() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+force = true
+install = [
+    "package1",
+    { name = "package2", skip_dependencies = true },
+    { name = "package3", force = false },
+]
+        "#
+        .trim();
+        let config = BuildpackConfig::from_str(toml).unwrap();
+        assert_eq!(
+            config
+                .install
+                .iter()
+                .map(|requested_package| (requested_package.name.as_str(), requested_package.force))
+                .collect::<Vec<_>>(),
+            vec![("package1", true), ("package2", true), ("package3", false)]
+        );
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_security_advisories() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+security_advisories = "strict"
+        "#
+        .trim();
+        match BuildpackConfig::from_str(toml).unwrap_err() {
+            ParseConfigError::InvalidSecurityAdvisories(value) => {
+                assert_eq!(value, "\"strict\"");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_essential_packages() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+essential_packages = "refuse"
+        "#
+        .trim();
+        match BuildpackConfig::from_str(toml).unwrap_err() {
+            ParseConfigError::InvalidEssentialPackages(value) => {
+                assert_eq!(value, "\"refuse\"");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_prefer_alternatives() {
+        for prefer_alternatives in [
+            r#"["cdebconf"]"#,
+            r#"{ "debconf-2.0" = 1 }"#,
+            r#"{ "debconf-2.0" = "not a package" }"#,
+        ] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+prefer_alternatives = {prefer_alternatives}
+        "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::InvalidPreferAlternatives(value) => {
+                    assert_eq!(value, prefer_alternatives);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_env_order() {
+        for env_order in [
+            r#"["PATH"]"#,
+            r#"{ PATH = "last" }"#,
+            r"{ PATH = true }",
+            r#"{ PYTHONPATH = "append" }"#,
+        ] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+env_order = {env_order}
+        "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::InvalidEnvOrder(value) => {
+                    assert_eq!(value, env_order);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_pins() {
+        for pins in [
+            r#"["libssl3t64"]"#,
+            r#"{ "libssl3t64" = 3 }"#,
+            r#"{ "libssl3t64" = "not a version" }"#,
+            r#"{ "not a package" = "3.0.13-0ubuntu3.4" }"#,
+        ] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+pins = {pins}
+        "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::InvalidPins(value) => {
+                    assert_eq!(value, pins);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_default_components() {
+        for default_components in [r#"["main", "partner"]"#, "[]", r#""main""#] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+default_components = {default_components}
+            "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::InvalidDefaultComponents(value) => {
+                    assert_eq!(value, default_components);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_default_source_signed_by() {
+        for default_source_signed_by in [r#""""#, r#""  ""#, "true"] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+default_source_signed_by = {default_source_signed_by}
+            "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::InvalidDefaultSourceSignedBy(value) => {
+                    assert_eq!(value, default_source_signed_by);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_prune() {
+        for prune in [r#"["static-libs", "docs"]"#, r#""headers""#] {
+            let toml = format!(
+                r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+prune = {prune}
+            "#
+            );
+            match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+                ParseConfigError::InvalidPrune(value) => {
+                    assert_eq!(value, prune);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_require_checksums() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+require_checksums = true
+download = [
+    { url = "https://example.com/pinned.deb", sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855" },
+    "https://example.com/unpinned.deb",
+]
+        "#;
+        match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+            ParseConfigError::MissingDownloadChecksum(url) => {
+                assert_eq!(url, "https://example.com/unpinned.deb");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_source_with_client_identity() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[[com.heroku.buildpacks.deb-packages.sources]]
+uri = "https://apt.example.com/ubuntu"
+suites = ["noble"]
+components = ["main"]
+arch = ["amd64"]
+signed_by = "-----BEGIN PGP PUBLIC KEY BLOCK-----"
+client_cert = "certs/client.pem"
+client_key = { env = "APT_CLIENT_KEY" }
+        "#;
+        let config = BuildpackConfig::from_str(toml.trim()).unwrap();
+        assert_eq!(
+            config.sources[0].client_identity,
+            Some(ClientIdentity {
+                cert: PemSource::Path("certs/client.pem".into()),
+                key: PemSource::Env("APT_CLIENT_KEY".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn test_deserialize_source_with_incomplete_client_identity() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[[com.heroku.buildpacks.deb-packages.sources]]
+uri = "https://apt.example.com/ubuntu"
+suites = ["noble"]
+components = ["main"]
+arch = ["amd64"]
+signed_by = "-----BEGIN PGP PUBLIC KEY BLOCK-----"
+client_cert = "certs/client.pem"
+        "#;
+        match BuildpackConfig::from_str(toml.trim()).unwrap_err() {
+            ParseConfigError::ParseCustomSource(e) => {
+                assert!(matches!(
+                    *e,
+                    ParseCustomSourceError::IncompleteClientIdentity(_)
+                ));
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s"), Some(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Some(Duration::from_mins(30)));
+        assert_eq!(parse_duration("24h"), Some(Duration::from_hours(24)));
+        assert_eq!(parse_duration("7d"), Some(Duration::from_hours(7 * 24)));
+        assert_eq!(parse_duration("24"), None);
+        assert_eq!(parse_duration("h"), None);
+        assert_eq!(parse_duration("1.5h"), None);
+        assert_eq!(parse_duration("24 hours"), None);
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("100B"), Some(100));
+        assert_eq!(parse_size("500KB"), Some(500_000));
+        assert_eq!(parse_size("500MB"), Some(500_000_000));
+        assert_eq!(parse_size("2GB"), Some(2_000_000_000));
+        assert_eq!(parse_size("512KiB"), Some(512 * 1024));
+        assert_eq!(parse_size("512MiB"), Some(512 * 1024 * 1024));
+        assert_eq!(parse_size("2GiB"), Some(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("500"), None);
+        assert_eq!(parse_size("MB"), None);
+        assert_eq!(parse_size("1.5GB"), None);
+        assert_eq!(parse_size("500 MB"), None);
+        assert_eq!(parse_size("500mb"), None);
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_max_layer_size() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+max_layer_size = 500
+        "#
+        .trim();
+        match BuildpackConfig::from_str(toml).unwrap_err() {
+            ParseConfigError::InvalidMaxLayerSize(value) => assert_eq!(value, "500"),
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_max_download_cache_size() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+max_download_cache_size = "1 gigabyte"
+        "#
+        .trim();
+        match BuildpackConfig::from_str(toml).unwrap_err() {
+            ParseConfigError::InvalidMaxDownloadCacheSize(value) => {
+                assert_eq!(value, "\"1 gigabyte\"");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_deserialize_with_invalid_index_max_age() {
+        let toml = r#"
+[_]
+schema-version = "0.2"
+
+[com.heroku.buildpacks.deb-packages]
+index_max_age = 24
+        "#
+        .trim();
+        match BuildpackConfig::from_str(toml).unwrap_err() {
+            ParseConfigError::InvalidIndexMaxAge(value) => assert_eq!(value, "24"),
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
     #[test]
     fn test_invalid_toml() {
         let toml = r"
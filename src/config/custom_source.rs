@@ -1,5 +1,6 @@
 use crate::debian::{ArchitectureName, RepositoryUri, Source, UnsupportedArchitectureNameError};
-use toml_edit::{Table, Value};
+use std::path::PathBuf;
+use toml_edit::{Item, Table, Value};
 
 // Very similar in structure to a `Source` **except** it allows for multiple architectures
 // to be specified as configuration.
@@ -10,6 +11,24 @@ pub(crate) struct CustomSource {
     pub(crate) suites: Vec<String>,
     pub(crate) uri: RepositoryUri,
     pub(crate) signed_by: String,
+    pub(crate) allow_weak_checksums: bool,
+    pub(crate) verify_package_signatures: bool,
+    pub(crate) client_identity: Option<ClientIdentity>,
+}
+
+// The client certificate and private key presented to repositories that require mutual TLS.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct ClientIdentity {
+    pub(crate) cert: PemSource,
+    pub(crate) key: PemSource,
+}
+
+// PEM data is either read from a file or, to avoid committing secrets to the application source,
+// from an environment variable.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum PemSource {
+    Path(PathBuf),
+    Env(String),
 }
 
 impl CustomSource {
@@ -22,14 +41,31 @@ impl CustomSource {
                 components: self.components.clone(),
                 signed_by: self.signed_by.clone(),
                 arch: arch.clone(),
+                foreign_arches: vec![],
+                allow_weak_checksums: self.allow_weak_checksums,
+                keyserver_fingerprints: vec![],
             })
             .collect()
     }
 }
 
+// The keys recognized in a `[[sources]]` entry.
+pub(crate) const CUSTOM_SOURCE_KEYS: [&str; 9] = [
+    "uri",
+    "suites",
+    "components",
+    "arch",
+    "signed_by",
+    "allow_weak_checksums",
+    "verify_package_signatures",
+    "client_cert",
+    "client_key",
+];
+
 impl TryFrom<&Table> for CustomSource {
     type Error = ParseCustomSourceError;
 
+    #[allow(clippy::too_many_lines)]
     fn try_from(table: &Table) -> Result<Self, Self::Error> {
         let uri = table
             .get("uri")
@@ -111,16 +147,63 @@ impl TryFrom<&Table> for CustomSource {
             .ok_or_else(|| ParseCustomSourceError::MissingSignedBy(table.clone()))?
             .into();
 
+        // some older repositories only list SHA1 or MD5sum checksums for their packages
+        let allow_weak_checksums = table
+            .get("allow_weak_checksums")
+            .and_then(toml_edit::Item::as_bool)
+            .unwrap_or_default();
+
+        // some repositories also sign each package they publish (e.g.; with `debsigs` or
+        // `dpkg-sig`) with the same key as their Release file
+        let verify_package_signatures = table
+            .get("verify_package_signatures")
+            .and_then(toml_edit::Item::as_bool)
+            .unwrap_or_default();
+
+        let get_pem_source = |key: &str| match table.get(key) {
+            None => Ok(None),
+            Some(item) => get_pem_source(item).map(Some).ok_or(item),
+        };
+        let client_identity = match (get_pem_source("client_cert"), get_pem_source("client_key")) {
+            (Ok(Some(cert)), Ok(Some(key))) => Some(ClientIdentity { cert, key }),
+            (Ok(None), Ok(None)) => None,
+            (Err(item), _) | (_, Err(item)) => Err(ParseCustomSourceError::UnexpectedTomlValue(
+                table.clone(),
+                item.as_value()
+                    .cloned()
+                    .unwrap_or_else(|| Value::from(item.to_string().trim())),
+            ))?,
+            _ => Err(ParseCustomSourceError::IncompleteClientIdentity(
+                table.clone(),
+            ))?,
+        };
+
         Ok(CustomSource {
             arch,
             components,
             suites,
             uri,
             signed_by,
+            allow_weak_checksums,
+            verify_package_signatures,
+            client_identity,
         })
     }
 }
 
+// PEM data can be given as a path (e.g.; `client_cert = "certs/client.pem"`) or read from an
+// environment variable (e.g.; `client_key = { env = "APT_CLIENT_KEY" }`)
+fn get_pem_source(item: &Item) -> Option<PemSource> {
+    match item {
+        Item::Value(Value::String(path)) => Some(PemSource::Path(path.value().into())),
+        Item::Value(Value::InlineTable(inline_table)) => inline_table
+            .get("env")
+            .and_then(Value::as_str)
+            .map(|env| PemSource::Env(env.to_string())),
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum ParseCustomSourceError {
     MissingUri(Table),
@@ -130,4 +213,5 @@ pub(crate) enum ParseCustomSourceError {
     MissingArchitectureNames(Table),
     UnexpectedTomlValue(Table, Value),
     InvalidArchitectureName(Table, UnsupportedArchitectureNameError),
+    IncompleteClientIdentity(Table),
 }
@@ -0,0 +1,195 @@
+use crate::debian::{DistroCodename, Source};
+use std::collections::HashSet;
+use toml_edit::TableLike;
+
+// NOTE: Ubuntu publishes updates to a release through additional "pocket" suites named after the
+//       release codename (e.g.; noble-updates, noble-security, noble-backports). Each pocket can be
+//       toggled here and, when left unset, the default sources for the distribution are used as-is.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub(crate) struct DefaultSuites {
+    pub(crate) updates: Option<bool>,
+    pub(crate) security: Option<bool>,
+    pub(crate) backports: Option<bool>,
+    // pre-release updates that haven't been verified yet, never enabled by default
+    pub(crate) proposed: Option<bool>,
+}
+
+pub(crate) const DEFAULT_SUITES_KEYS: [&str; 4] = ["updates", "security", "backports", "proposed"];
+
+impl From<&dyn TableLike> for DefaultSuites {
+    fn from(table: &dyn TableLike) -> Self {
+        let get_bool = |key: &str| table.get(key).and_then(toml_edit::Item::as_bool);
+        DefaultSuites {
+            updates: get_bool("updates"),
+            security: get_bool("security"),
+            backports: get_bool("backports"),
+            proposed: get_bool("proposed"),
+        }
+    }
+}
+
+impl DefaultSuites {
+    pub(crate) fn apply(&self, codename: &DistroCodename, source_list: Vec<Source>) -> Vec<Source> {
+        let release_suite = codename.to_string();
+        let pockets = [
+            ("updates", self.updates),
+            ("security", self.security),
+            ("backports", self.backports),
+            ("proposed", self.proposed),
+        ];
+
+        // some pockets are served from a different repository (e.g.; security.ubuntu.com) so these
+        // are recorded to avoid adding a pocket to an architecture that already has it
+        let existing_suites = source_list
+            .iter()
+            .flat_map(|source| {
+                source
+                    .suites
+                    .iter()
+                    .map(|suite| (source.arch.to_string(), suite.clone()))
+            })
+            .collect::<HashSet<_>>();
+
+        source_list
+            .into_iter()
+            .filter_map(|mut source| {
+                for (pocket, enabled) in pockets {
+                    let pocket_suite = format!("{release_suite}-{pocket}");
+                    match enabled {
+                        Some(false) => source.suites.retain(|suite| suite != &pocket_suite),
+                        // pockets are only added to sources that also serve the release suite
+                        Some(true)
+                            if source.suites.contains(&release_suite)
+                                && !existing_suites
+                                    .contains(&(source.arch.to_string(), pocket_suite.clone())) =>
+                        {
+                            source.suites.push(pocket_suite);
+                        }
+                        _ => {}
+                    }
+                }
+                if source.suites.is_empty() {
+                    None
+                } else {
+                    Some(source)
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::debian::ArchitectureName::{AMD_64, ARM_64};
+
+    #[test]
+    fn test_apply_with_no_changes() {
+        let source_list = DefaultSuites::default().apply(&DistroCodename::Noble, noble_sources());
+        assert_eq!(
+            suites(&source_list),
+            vec![
+                vec!["noble", "noble-updates"],
+                vec!["noble-security"],
+                vec!["noble", "noble-updates", "noble-security"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_disables_pockets() {
+        let source_list = DefaultSuites {
+            updates: Some(false),
+            security: Some(false),
+            backports: None,
+            proposed: None,
+        }
+        .apply(&DistroCodename::Noble, noble_sources());
+        assert_eq!(suites(&source_list), vec![vec!["noble"], vec!["noble"]]);
+    }
+
+    #[test]
+    fn test_apply_enables_backports() {
+        let source_list = DefaultSuites {
+            updates: None,
+            security: Some(true),
+            backports: Some(true),
+            proposed: None,
+        }
+        .apply(&DistroCodename::Noble, noble_sources());
+        assert_eq!(
+            suites(&source_list),
+            vec![
+                vec!["noble", "noble-updates", "noble-backports"],
+                vec!["noble-security"],
+                vec![
+                    "noble",
+                    "noble-updates",
+                    "noble-security",
+                    "noble-backports"
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_enables_proposed() {
+        let source_list = DefaultSuites {
+            proposed: Some(true),
+            ..DefaultSuites::default()
+        }
+        .apply(&DistroCodename::Jammy, jammy_sources());
+        assert_eq!(
+            suites(&source_list),
+            vec![vec![
+                "jammy",
+                "jammy-security",
+                "jammy-updates",
+                "jammy-proposed"
+            ]]
+        );
+    }
+
+    fn jammy_sources() -> Vec<Source> {
+        vec![Source::new(
+            "http://archive.ubuntu.com/ubuntu",
+            vec!["jammy", "jammy-security", "jammy-updates"],
+            vec!["main"],
+            "",
+            AMD_64,
+        )]
+    }
+
+    fn noble_sources() -> Vec<Source> {
+        vec![
+            Source::new(
+                "http://archive.ubuntu.com/ubuntu",
+                vec!["noble", "noble-updates"],
+                vec!["main"],
+                "",
+                AMD_64,
+            ),
+            Source::new(
+                "http://security.ubuntu.com/ubuntu",
+                vec!["noble-security"],
+                vec!["main"],
+                "",
+                AMD_64,
+            ),
+            Source::new(
+                "http://ports.ubuntu.com/ubuntu-ports",
+                vec!["noble", "noble-updates", "noble-security"],
+                vec!["main"],
+                "",
+                ARM_64,
+            ),
+        ]
+    }
+
+    fn suites(source_list: &[Source]) -> Vec<Vec<&str>> {
+        source_list
+            .iter()
+            .map(|source| source.suites.iter().map(String::as_str).collect())
+            .collect()
+    }
+}
@@ -1,24 +1,171 @@
+use crate::package_signatures::PackageSignature;
 use reqwest::Url;
-use std::fmt::{Display, Formatter};
+use reqwest::header::{HeaderName, HeaderValue};
+use std::fmt::{Debug, Display, Formatter};
 use std::str::FromStr;
-use toml_edit::Value;
+use toml_edit::{InlineTable, Value};
+
+pub(crate) const DOWNLOAD_URL_KEYS: [&str; 8] = [
+    "url",
+    "headers",
+    "sha256",
+    "verify",
+    "signed_by",
+    "signature",
+    "signature_url",
+    "filename",
+];
+
+const DEB_EXTENSION: &str = ".deb";
+
+// the extension of the detached signature that's expected alongside a download url when only the
+// signing key is configured
+const DETACHED_SIGNATURE_EXTENSION: &str = ".asc";
+
+// Header values prefixed with `env:` are read from that environment variable when the package is
+// downloaded so tokens don't need to be committed to the application source.
+const ENV_HEADER_VALUE_PREFIX: &str = "env:";
+
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
+pub(crate) struct DownloadUrl {
+    url: Url,
+    headers: Vec<DownloadHeader>,
+    sha256: Option<String>,
+    verify: bool,
+    signed_by: Option<String>,
+    signature_url: Option<String>,
+    filename: Option<String>,
+}
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
-pub(crate) struct DownloadUrl(Url);
+pub(crate) struct DownloadHeader {
+    pub(crate) name: String,
+    pub(crate) value: DownloadHeaderValue,
+}
+
+// where a release asset (e.g.; `https://github.com/<owner>/<repo>/releases/download/<tag>/<name>`)
+// is published on GitHub
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub(crate) struct GithubReleaseAsset {
+    pub(crate) owner: String,
+    pub(crate) repo: String,
+    pub(crate) tag: String,
+    pub(crate) name: String,
+}
+
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub(crate) enum DownloadHeaderValue {
+    Literal(String),
+    Env(String),
+}
+
+// header values are often credentials so they're left out of anything that gets logged
+impl Debug for DownloadHeaderValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DownloadHeaderValue::Literal(_) => write!(f, "Literal(<redacted>)"),
+            DownloadHeaderValue::Env(name) => f.debug_tuple("Env").field(name).finish(),
+        }
+    }
+}
 
 impl DownloadUrl {
+    pub(crate) fn headers(&self) -> &[DownloadHeader] {
+        &self.headers
+    }
+
+    pub(crate) fn sha256(&self) -> Option<&str> {
+        self.sha256.as_deref()
+    }
+
+    // `verify = false` opts a download out of checksum verification (e.g.; a nightly build that's
+    // republished at the same url) so it's allowed without a `sha256` when checksums are required
+    pub(crate) fn is_unverified(&self) -> bool {
+        !self.verify
+    }
+
+    pub(crate) fn host_str(&self) -> Option<&str> {
+        self.url.host_str()
+    }
+
+    // packages downloaded from a url configured with `signed_by` are verified against the detached
+    // signature published at `signature_url` (or next to the package as `<url>.asc`)
+    pub(crate) fn signature(&self) -> Option<PackageSignature> {
+        self.signed_by
+            .as_ref()
+            .map(|signed_by| PackageSignature::Detached {
+                signature_url: self
+                    .signature_url
+                    .clone()
+                    .unwrap_or_else(|| format!("{}{DETACHED_SIGNATURE_EXTENSION}", self.url)),
+                signed_by: signed_by.clone(),
+            })
+    }
+
+    // the name of the downloaded file without the `.deb` extension, a configured `filename` gives
+    // urls with a query string or a hash for a name a stable one
     pub(crate) fn filename(&self) -> Option<&str> {
-        self.0
-            .path_segments()
-            .and_then(|mut paths| paths.next_back())
-            .and_then(|path| path.strip_suffix(".deb"))
-            .filter(|&path| !path.is_empty())
+        match &self.filename {
+            Some(filename) => strip_deb_extension(filename),
+            None => self
+                .url
+                .path_segments()
+                .and_then(|mut paths| paths.next_back())
+                .and_then(strip_deb_extension),
+        }
+    }
+
+    pub(crate) fn filename_override(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    // how the download is referred to in the build output
+    pub(crate) fn display_name(&self) -> String {
+        self.filename
+            .clone()
+            .unwrap_or_else(|| self.url.to_string())
+    }
+
+    pub(crate) fn is_github_release_asset(&self) -> bool {
+        self.github_release_asset().is_some()
+    }
+
+    // Release assets from private repositories can't be downloaded from their `github.com` url so
+    // the release they belong to is needed to request them from the GitHub API instead.
+    pub(crate) fn github_release_asset(&self) -> Option<GithubReleaseAsset> {
+        if self.url.host_str() != Some("github.com") {
+            return None;
+        }
+        match self.url.path_segments()?.collect::<Vec<_>>()[..] {
+            [owner, repo, "releases", "download", tag, name]
+                if !owner.is_empty() && !repo.is_empty() && !tag.is_empty() && !name.is_empty() =>
+            {
+                Some(GithubReleaseAsset {
+                    owner: owner.to_string(),
+                    repo: repo.to_string(),
+                    tag: tag.to_string(),
+                    name: name.to_string(),
+                })
+            }
+            _ => None,
+        }
     }
 }
 
+fn strip_deb_extension(filename: &str) -> Option<&str> {
+    filename
+        .len()
+        .checked_sub(DEB_EXTENSION.len())
+        .filter(|&stem_len| filename.is_char_boundary(stem_len))
+        .map(|stem_len| filename.split_at(stem_len))
+        .filter(|(_, extension)| extension.eq_ignore_ascii_case(DEB_EXTENSION))
+        .map(|(stem, _)| stem)
+        .filter(|stem| !stem.is_empty())
+}
+
 impl Display for DownloadUrl {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.url)
     }
 }
 
@@ -26,48 +173,202 @@ impl FromStr for DownloadUrl {
     type Err = ParseDownloadUrlError;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        let url = Url::parse(value).map_err(|e| ParseDownloadUrlError::InvalidUrl {
+        let invalid_url = |reason: &str| ParseDownloadUrlError::InvalidUrl {
             url: value.into(),
-            reason: e.to_string(),
-        })?;
-        if url.scheme() != "https" {
-            return Err(ParseDownloadUrlError::InvalidUrl {
-                url: value.into(),
-                reason: "must start with `https://`".into(),
-            });
-        }
+            reason: reason.into(),
+        };
+        let url = parse_https_url(value).map_err(|reason| invalid_url(&reason))?;
         match std::path::Path::new(url.path()).extension() {
             Some(ext) => {
                 if !ext.eq_ignore_ascii_case("deb") {
-                    return Err(ParseDownloadUrlError::InvalidUrl {
-                        url: value.into(),
-                        reason: "must end with `.deb`".into(),
-                    });
+                    return Err(invalid_url("must end with `.deb`"));
                 }
             }
             None => {
-                return Err(ParseDownloadUrlError::InvalidUrl {
-                    url: value.into(),
-                    reason: "file doesn't have an extension".into(),
-                });
+                return Err(invalid_url("file doesn't have an extension"));
             }
         }
-        Ok(DownloadUrl(url))
+        Ok(DownloadUrl::new(url))
+    }
+}
+
+impl DownloadUrl {
+    fn new(url: Url) -> Self {
+        DownloadUrl {
+            url,
+            headers: vec![],
+            sha256: None,
+            verify: true,
+            signed_by: None,
+            signature_url: None,
+            filename: None,
+        }
     }
 }
 
+// the `.deb` extension is only required of the url when no `filename` is configured so it's
+// checked separately
+fn parse_https_url(value: &str) -> Result<Url, String> {
+    let url = Url::parse(value).map_err(|e| e.to_string())?;
+    if url.scheme() == "https" {
+        Ok(url)
+    } else {
+        Err("must start with `https://`".into())
+    }
+}
+
+// a file name (e.g.; `pandoc.deb`) without any directories
+fn parse_filename(value: &Value) -> Option<String> {
+    value
+        .as_str()
+        .map(str::trim)
+        .filter(|filename| {
+            !filename.starts_with('.')
+                && !filename.contains(['/', '\\'])
+                && strip_deb_extension(filename).is_some()
+        })
+        .map(ToString::to_string)
+}
+
 impl TryFrom<&Value> for DownloadUrl {
     type Error = ParseDownloadUrlError;
 
     fn try_from(value: &Value) -> Result<Self, Self::Error> {
         if let Some(value) = value.as_str() {
             DownloadUrl::from_str(value)
+        } else if let Some(table) = value.as_inline_table() {
+            let url = table
+                .get("url")
+                .and_then(Value::as_str)
+                .ok_or_else(|| ParseDownloadUrlError::UnexpectedTomlValue(value.clone()))?;
+            let mut download_url = match table.get("filename") {
+                Some(filename) => DownloadUrl {
+                    filename: Some(parse_filename(filename).ok_or_else(|| {
+                        ParseDownloadUrlError::InvalidUrl {
+                            url: url.to_string(),
+                            reason: "`filename` must be a file name that ends with `.deb`".into(),
+                        }
+                    })?),
+                    ..DownloadUrl::new(parse_https_url(url).map_err(|reason| {
+                        ParseDownloadUrlError::InvalidUrl {
+                            url: url.to_string(),
+                            reason,
+                        }
+                    })?)
+                },
+                None => DownloadUrl::from_str(url)?,
+            };
+            if let Some(sha256) = table.get("sha256") {
+                download_url.sha256 = Some(
+                    sha256
+                        .as_str()
+                        .filter(|sha256| {
+                            sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit())
+                        })
+                        .map(str::to_ascii_lowercase)
+                        .ok_or_else(|| ParseDownloadUrlError::InvalidUrl {
+                            url: download_url.to_string(),
+                            reason: "`sha256` must be a hex-encoded SHA256 checksum".into(),
+                        })?,
+                );
+            }
+            download_url.verify =
+                parse_verify(table, download_url.sha256.is_some()).map_err(|reason| {
+                    ParseDownloadUrlError::InvalidUrl {
+                        url: download_url.to_string(),
+                        reason,
+                    }
+                })?;
+            if let Some(signed_by) = table.get("signed_by") {
+                download_url.signed_by = Some(
+                    signed_by
+                        .as_str()
+                        .map(str::trim)
+                        .filter(|signed_by| !signed_by.is_empty())
+                        .map(ToString::to_string)
+                        .ok_or_else(|| ParseDownloadUrlError::InvalidUrl {
+                            url: download_url.to_string(),
+                            reason: "`signed_by` must be an ASCII-armored GPG key or the path to a keyring file".into(),
+                        })?,
+                );
+            }
+            download_url.signature_url =
+                parse_signature_url(table, download_url.signed_by.is_some()).map_err(|reason| {
+                    ParseDownloadUrlError::InvalidUrl {
+                        url: download_url.to_string(),
+                        reason,
+                    }
+                })?;
+            if let Some(headers) = table.get("headers") {
+                let headers = headers
+                    .as_inline_table()
+                    .ok_or_else(|| ParseDownloadUrlError::UnexpectedTomlValue(value.clone()))?;
+                for (name, header_value) in headers {
+                    let invalid_header = || ParseDownloadUrlError::InvalidUrl {
+                        url: download_url.to_string(),
+                        reason: format!("invalid header `{name}`"),
+                    };
+                    HeaderName::from_str(name).map_err(|_| invalid_header())?;
+                    let header_value = header_value.as_str().ok_or_else(invalid_header)?;
+                    let header_value = if let Some(env_name) =
+                        header_value.strip_prefix(ENV_HEADER_VALUE_PREFIX)
+                    {
+                        DownloadHeaderValue::Env(env_name.to_string())
+                    } else {
+                        HeaderValue::from_str(header_value).map_err(|_| invalid_header())?;
+                        DownloadHeaderValue::Literal(header_value.to_string())
+                    };
+                    download_url.headers.push(DownloadHeader {
+                        name: name.to_string(),
+                        value: header_value,
+                    });
+                }
+            }
+            Ok(download_url)
         } else {
             Err(ParseDownloadUrlError::UnexpectedTomlValue(value.clone()))
         }
     }
 }
 
+// a checksum that's never checked would be misleading so `verify = false` can't be combined with one
+fn parse_verify(table: &InlineTable, has_sha256: bool) -> Result<bool, String> {
+    let verify = match table.get("verify") {
+        None => return Ok(true),
+        Some(verify) => verify
+            .as_bool()
+            .ok_or("`verify` must be `true` or `false`")?,
+    };
+    if !verify && has_sha256 {
+        return Err("`verify = false` can't be combined with `sha256`".into());
+    }
+    Ok(verify)
+}
+
+// `signature` is accepted as another name for `signature_url` since that's what vendors publishing
+// detached signatures tend to call it
+fn parse_signature_url(table: &InlineTable, has_signed_by: bool) -> Result<Option<String>, String> {
+    let signature_urls = ["signature", "signature_url"]
+        .into_iter()
+        .filter_map(|key| table.get(key).map(|signature_url| (key, signature_url)))
+        .collect::<Vec<_>>();
+    let (key, signature_url) = match signature_urls[..] {
+        [] => return Ok(None),
+        [signature_url] => signature_url,
+        _ => return Err("only one of `signature` or `signature_url` can be set".into()),
+    };
+    let signature_url = signature_url
+        .as_str()
+        .and_then(|signature_url| Url::parse(signature_url).ok())
+        .filter(|signature_url| signature_url.scheme() == "https")
+        .map(String::from)
+        .ok_or_else(|| format!("`{key}` must be a url that starts with `https://`"))?;
+    if !has_signed_by {
+        return Err(format!("`{key}` requires `signed_by`"));
+    }
+    Ok(Some(signature_url))
+}
+
 #[derive(Debug)]
 pub(crate) enum ParseDownloadUrlError {
     InvalidUrl { url: String, reason: String },
@@ -98,6 +399,302 @@ mod tests {
         assert_eq!(download_url.to_string(), url);
     }
 
+    #[test]
+    fn test_download_url_with_headers() {
+        let value = Value::from_str(
+            r#"{ url = "https://example.com/package.deb", headers = { "Authorization" = "env:ARTIFACT_TOKEN", "Accept" = "application/octet-stream" } }"#,
+        )
+        .unwrap();
+        let download_url = DownloadUrl::try_from(&value).unwrap();
+        assert_eq!(download_url.to_string(), "https://example.com/package.deb");
+        assert_eq!(
+            download_url.headers(),
+            [
+                DownloadHeader {
+                    name: "Authorization".to_string(),
+                    value: DownloadHeaderValue::Env("ARTIFACT_TOKEN".to_string()),
+                },
+                DownloadHeader {
+                    name: "Accept".to_string(),
+                    value: DownloadHeaderValue::Literal("application/octet-stream".to_string()),
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_download_url_with_sha256() {
+        let value = Value::from_str(
+            r#"{ url = "https://example.com/package.deb", sha256 = "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            DownloadUrl::try_from(&value).unwrap().sha256(),
+            Some("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+
+    #[test]
+    fn test_download_url_with_invalid_sha256() {
+        let value =
+            Value::from_str(r#"{ url = "https://example.com/package.deb", sha256 = "abc123" }"#)
+                .unwrap();
+        match DownloadUrl::try_from(&value).unwrap_err() {
+            ParseDownloadUrlError::InvalidUrl { reason, .. } => {
+                assert_eq!(reason, "`sha256` must be a hex-encoded SHA256 checksum");
+            }
+            ParseDownloadUrlError::UnexpectedTomlValue(_) => panic!("Expected InvalidUrl error"),
+        }
+    }
+
+    #[test]
+    fn test_download_url_with_verify() {
+        let value =
+            Value::from_str(r#"{ url = "https://example.com/package.deb", verify = false }"#)
+                .unwrap();
+        assert!(DownloadUrl::try_from(&value).unwrap().is_unverified());
+        assert!(
+            !DownloadUrl::from_str("https://example.com/package.deb")
+                .unwrap()
+                .is_unverified()
+        );
+
+        for (value, expected_reason) in [
+            (
+                r#"{ url = "https://example.com/package.deb", verify = "no" }"#,
+                "`verify` must be `true` or `false`",
+            ),
+            (
+                r#"{ url = "https://example.com/package.deb", verify = false, sha256 = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855" }"#,
+                "`verify = false` can't be combined with `sha256`",
+            ),
+        ] {
+            match DownloadUrl::try_from(&Value::from_str(value).unwrap()).unwrap_err() {
+                ParseDownloadUrlError::InvalidUrl { reason, .. } => {
+                    assert_eq!(reason, expected_reason);
+                }
+                ParseDownloadUrlError::UnexpectedTomlValue(_) => {
+                    panic!("Expected InvalidUrl error")
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_download_url_with_signature() {
+        let value = Value::from_str(
+            r#"{ url = "https://example.com/package.deb", signed_by = "-----BEGIN PGP PUBLIC KEY BLOCK-----" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            DownloadUrl::try_from(&value).unwrap().signature(),
+            Some(PackageSignature::Detached {
+                signature_url: "https://example.com/package.deb.asc".to_string(),
+                signed_by: "-----BEGIN PGP PUBLIC KEY BLOCK-----".to_string(),
+            })
+        );
+
+        let value = Value::from_str(
+            r#"{ url = "https://example.com/package.deb", signed_by = "-----BEGIN PGP PUBLIC KEY BLOCK-----", signature_url = "https://example.com/signatures/package.sig" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            DownloadUrl::try_from(&value).unwrap().signature(),
+            Some(PackageSignature::Detached {
+                signature_url: "https://example.com/signatures/package.sig".to_string(),
+                signed_by: "-----BEGIN PGP PUBLIC KEY BLOCK-----".to_string(),
+            })
+        );
+
+        let value = Value::from_str(
+            r#"{ url = "https://example.com/package.deb", signature = "https://example.com/package.deb.sig", signed_by = "/etc/apt/keyrings/vendor.gpg" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            DownloadUrl::try_from(&value).unwrap().signature(),
+            Some(PackageSignature::Detached {
+                signature_url: "https://example.com/package.deb.sig".to_string(),
+                signed_by: "/etc/apt/keyrings/vendor.gpg".to_string(),
+            })
+        );
+
+        assert_eq!(
+            DownloadUrl::from_str("https://example.com/package.deb")
+                .unwrap()
+                .signature(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_download_url_with_invalid_signature() {
+        for (value, expected_reason) in [
+            (
+                r#"{ url = "https://example.com/package.deb", signed_by = " " }"#,
+                "`signed_by` must be an ASCII-armored GPG key or the path to a keyring file",
+            ),
+            (
+                r#"{ url = "https://example.com/package.deb", signed_by = "key", signature_url = "http://example.com/package.deb.asc" }"#,
+                "`signature_url` must be a url that starts with `https://`",
+            ),
+            (
+                r#"{ url = "https://example.com/package.deb", signature_url = "https://example.com/package.deb.asc" }"#,
+                "`signature_url` requires `signed_by`",
+            ),
+            (
+                r#"{ url = "https://example.com/package.deb", signature = "https://example.com/package.deb.asc" }"#,
+                "`signature` requires `signed_by`",
+            ),
+            (
+                r#"{ url = "https://example.com/package.deb", signed_by = "key", signature = "https://example.com/a.asc", signature_url = "https://example.com/b.asc" }"#,
+                "only one of `signature` or `signature_url` can be set",
+            ),
+        ] {
+            match DownloadUrl::try_from(&Value::from_str(value).unwrap()).unwrap_err() {
+                ParseDownloadUrlError::InvalidUrl { reason, .. } => {
+                    assert_eq!(reason, expected_reason);
+                }
+                ParseDownloadUrlError::UnexpectedTomlValue(_) => {
+                    panic!("Expected InvalidUrl error")
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_download_url_with_filename() {
+        let value = Value::from_str(
+            r#"{ url = "https://artifacts.example.com/download?id=8f3a2c&token=abc", filename = "pandoc.deb" }"#,
+        )
+        .unwrap();
+        let download_url = DownloadUrl::try_from(&value).unwrap();
+        assert_eq!(download_url.filename(), Some("pandoc"));
+        assert_eq!(download_url.filename_override(), Some("pandoc.deb"));
+        assert_eq!(download_url.display_name(), "pandoc.deb");
+
+        let value = Value::from_str(
+            r#"{ url = "https://example.com/8f3a2c.deb", filename = "pandoc-3.1.deb" }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            DownloadUrl::try_from(&value).unwrap().filename(),
+            Some("pandoc-3.1")
+        );
+
+        let download_url = DownloadUrl::from_str("https://example.com/pandoc.deb").unwrap();
+        assert_eq!(download_url.filename_override(), None);
+        assert_eq!(
+            download_url.display_name(),
+            "https://example.com/pandoc.deb"
+        );
+    }
+
+    #[test]
+    fn test_download_url_with_invalid_filename() {
+        for filename in ["pandoc", ".deb", "../pandoc.deb", "dir/pandoc.deb", " "] {
+            let value = Value::from_str(&format!(
+                r#"{{ url = "https://example.com/download?id=1", filename = "{filename}" }}"#
+            ))
+            .unwrap();
+            match DownloadUrl::try_from(&value).unwrap_err() {
+                ParseDownloadUrlError::InvalidUrl { url, reason } => {
+                    assert_eq!(url, "https://example.com/download?id=1");
+                    assert_eq!(
+                        reason,
+                        "`filename` must be a file name that ends with `.deb`"
+                    );
+                }
+                ParseDownloadUrlError::UnexpectedTomlValue(_) => {
+                    panic!("Expected InvalidUrl error")
+                }
+            }
+        }
+
+        // the url must still use https when a filename is given
+        let value = Value::from_str(
+            r#"{ url = "http://example.com/download?id=1", filename = "pandoc.deb" }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            DownloadUrl::try_from(&value).unwrap_err(),
+            ParseDownloadUrlError::InvalidUrl { reason, .. } if reason == "must start with `https://`"
+        ));
+    }
+
+    #[test]
+    fn test_download_url_with_invalid_header_name() {
+        let value = Value::from_str(
+            r#"{ url = "https://example.com/package.deb", headers = { "Bad Header" = "value" } }"#,
+        )
+        .unwrap();
+        match DownloadUrl::try_from(&value).unwrap_err() {
+            ParseDownloadUrlError::InvalidUrl { url, reason } => {
+                assert_eq!(url, "https://example.com/package.deb");
+                assert_eq!(reason, "invalid header `Bad Header`");
+            }
+            ParseDownloadUrlError::UnexpectedTomlValue(_) => panic!("Expected InvalidUrl error"),
+        }
+    }
+
+    #[test]
+    fn test_download_header_literal_values_are_redacted() {
+        assert_eq!(
+            format!(
+                "{:?}",
+                DownloadHeaderValue::Literal("Bearer secret".to_string())
+            ),
+            "Literal(<redacted>)"
+        );
+    }
+
+    #[test]
+    fn test_github_release_asset() {
+        assert_eq!(
+            DownloadUrl::from_str(
+                "https://github.com/owner/repo/releases/download/v1.2.3/package_1.2.3_amd64.deb"
+            )
+            .unwrap()
+            .github_release_asset(),
+            Some(GithubReleaseAsset {
+                owner: "owner".to_string(),
+                repo: "repo".to_string(),
+                tag: "v1.2.3".to_string(),
+                name: "package_1.2.3_amd64.deb".to_string(),
+            })
+        );
+        assert_eq!(
+            DownloadUrl::from_str(
+                "https://github.com/owner/repo/releases/download/v1.2.3/nested/package.deb"
+            )
+            .unwrap()
+            .github_release_asset(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_github_release_asset() {
+        assert!(
+            DownloadUrl::from_str(
+                "https://github.com/owner/repo/releases/download/v1.2.3/package_1.2.3_amd64.deb"
+            )
+            .unwrap()
+            .is_github_release_asset()
+        );
+        assert!(
+            !DownloadUrl::from_str("https://github.com/owner/repo/raw/main/package.deb")
+                .unwrap()
+                .is_github_release_asset()
+        );
+        assert!(
+            !DownloadUrl::from_str(
+                "https://example.com/owner/repo/releases/download/v1/package.deb"
+            )
+            .unwrap()
+            .is_github_release_asset()
+        );
+    }
+
     #[test]
     fn test_invalid_scheme_file() {
         let url = "file:///path/to/package.deb";
@@ -0,0 +1,96 @@
+use crate::config::ParseConfigError;
+use serde::{Deserialize, Serialize};
+use toml_edit::TableLike;
+
+pub(crate) const HOOKS_CONFIG_KEYS: [&str; 2] = ["pre_install", "post_install"];
+
+// NOTE: Hooks are shell commands run from the application directory while the packages layer is
+//       being created (e.g.; to fix up a symlink or remove a file from a package) so a small fix-up
+//       doesn't require a separate buildpack. They only run when the layer is rebuilt since their
+//       changes are cached along with the rest of the layer.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct HooksConfig {
+    // runs before any packages are extracted into the layer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) pre_install: Option<String>,
+    // runs after every package is extracted into the layer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) post_install: Option<String>,
+}
+
+impl HooksConfig {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pre_install.is_none() && self.post_install.is_none()
+    }
+}
+
+impl TryFrom<&dyn TableLike> for HooksConfig {
+    type Error = ParseConfigError;
+
+    fn try_from(table: &dyn TableLike) -> Result<Self, Self::Error> {
+        let get_command = |key: &str| match table.get(key) {
+            None => Ok(None),
+            Some(item) => item
+                .as_str()
+                .map(str::trim)
+                .filter(|command| !command.is_empty())
+                .map(|command| Some(command.to_string()))
+                .ok_or_else(|| {
+                    ParseConfigError::InvalidHook(
+                        key.to_string(),
+                        item.to_string().trim().to_string(),
+                    )
+                }),
+        };
+
+        Ok(HooksConfig {
+            pre_install: get_command("pre_install")?,
+            post_install: get_command("post_install")?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use toml_edit::DocumentMut;
+
+    fn parse_hooks_config(contents: &str) -> Result<HooksConfig, ParseConfigError> {
+        let doc = DocumentMut::from_str(contents).unwrap();
+        HooksConfig::try_from(doc.as_table() as &dyn TableLike)
+    }
+
+    #[test]
+    fn parse_hooks_config_with_all_hooks() {
+        assert_eq!(
+            parse_hooks_config(
+                r#"
+                pre_install = "mkdir -p $DEB_PACKAGES_LAYER_DIR/opt"
+                post_install = " bash scripts/fix-links.sh "
+                "#
+            )
+            .unwrap(),
+            HooksConfig {
+                pre_install: Some("mkdir -p $DEB_PACKAGES_LAYER_DIR/opt".to_string()),
+                post_install: Some("bash scripts/fix-links.sh".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_hooks_config_with_invalid_hook() {
+        for (contents, value) in [
+            ("post_install = 1", "1"),
+            (r#"pre_install = " ""#, r#"" ""#),
+        ] {
+            match parse_hooks_config(contents).unwrap_err() {
+                ParseConfigError::InvalidHook(key, invalid_value) => {
+                    assert!(HOOKS_CONFIG_KEYS.contains(&key.as_str()));
+                    assert_eq!(invalid_value, value);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+}
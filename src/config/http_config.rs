@@ -0,0 +1,280 @@
+use crate::config::{ParseConfigError, parse_duration};
+use indexmap::IndexMap;
+use std::net::IpAddr;
+use std::time::Duration;
+use toml_edit::{Item, TableLike};
+
+pub(crate) const HTTP_CONFIG_KEYS: [&str; 9] = [
+    "http2_prior_knowledge",
+    "pool_max_idle_per_host",
+    "pool_idle_timeout",
+    "tcp_keepalive",
+    "max_retries",
+    "retry_jitter",
+    "host_retry_budget",
+    "resolve",
+    "root_certificates",
+];
+
+// NOTE: Builds that install a large number of packages make many requests to the same few
+//       repository hosts so these settings allow connections to be reused more aggressively.
+//       HTTP/2 with prior knowledge skips protocol negotiation entirely and only works against
+//       hosts (e.g.; internal mirrors) that are known to support it, the Ubuntu archive doesn't.
+//       When unset, the defaults from the HTTP client are used.
+//
+//       Requests that fail with a transient error are retried with an exponential backoff. The time
+//       spent waiting to retry requests is limited for each host so a single failing mirror gives
+//       up quickly instead of holding up the build with retries while the other sources are fine.
+//
+//       Hosts can be pinned to fixed IP addresses, like curl's `--resolve`, to work around a
+//       mirror whose DNS rotation includes a broken endpoint.
+//
+//       Certificates are verified against the system trust store by default (including any
+//       roots injected with `SSL_CERT_FILE` or `SSL_CERT_DIR`) which is what corporate proxies
+//       that intercept TLS rely on. Base images with a missing or outdated `ca-certificates`
+//       package can use the Mozilla roots bundled with the buildpack instead.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub(crate) struct HttpConfig {
+    pub(crate) http2_prior_knowledge: bool,
+    pub(crate) pool_max_idle_per_host: Option<usize>,
+    pub(crate) pool_idle_timeout: Option<Duration>,
+    pub(crate) tcp_keepalive: Option<Duration>,
+    pub(crate) max_retries: Option<u32>,
+    pub(crate) retry_jitter: Option<RetryJitter>,
+    pub(crate) host_retry_budget: Option<Duration>,
+    pub(crate) resolve: IndexMap<String, Vec<IpAddr>>,
+    pub(crate) root_certificates: RootCertificates,
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum RootCertificates {
+    #[default]
+    System,
+    Bundled,
+}
+
+impl RootCertificates {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "system" => Some(RootCertificates::System),
+            "bundled" => Some(RootCertificates::Bundled),
+            _ => None,
+        }
+    }
+}
+
+// How much randomness is added to the delay between retries so requests that failed at the same
+// time aren't all retried at the same time.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum RetryJitter {
+    // a random delay up to the calculated backoff
+    Full,
+    // a random delay between half of the minimum delay and the calculated backoff
+    Bounded,
+    // exactly the calculated backoff
+    None,
+}
+
+impl RetryJitter {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "full" => Some(RetryJitter::Full),
+            "bounded" => Some(RetryJitter::Bounded),
+            "none" => Some(RetryJitter::None),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<&dyn TableLike> for HttpConfig {
+    type Error = ParseConfigError;
+
+    fn try_from(table: &dyn TableLike) -> Result<Self, Self::Error> {
+        let invalid_option = |key: &str, item: &Item| {
+            ParseConfigError::InvalidHttpOption(
+                key.to_string(),
+                item.to_string().trim().to_string(),
+            )
+        };
+
+        let http2_prior_knowledge = match table.get("http2_prior_knowledge") {
+            None => false,
+            Some(item) => item
+                .as_bool()
+                .ok_or_else(|| invalid_option("http2_prior_knowledge", item))?,
+        };
+
+        let pool_max_idle_per_host = match table.get("pool_max_idle_per_host") {
+            None => None,
+            Some(item) => Some(
+                item.as_integer()
+                    .and_then(|value| usize::try_from(value).ok())
+                    .ok_or_else(|| invalid_option("pool_max_idle_per_host", item))?,
+            ),
+        };
+
+        let max_retries = match table.get("max_retries") {
+            None => None,
+            Some(item) => Some(
+                item.as_integer()
+                    .and_then(|value| u32::try_from(value).ok())
+                    .ok_or_else(|| invalid_option("max_retries", item))?,
+            ),
+        };
+
+        let retry_jitter = match table.get("retry_jitter") {
+            None => None,
+            Some(item) => Some(
+                item.as_str()
+                    .and_then(RetryJitter::parse)
+                    .ok_or_else(|| invalid_option("retry_jitter", item))?,
+            ),
+        };
+
+        let root_certificates = match table.get("root_certificates") {
+            None => RootCertificates::default(),
+            Some(item) => item
+                .as_str()
+                .and_then(RootCertificates::parse)
+                .ok_or_else(|| invalid_option("root_certificates", item))?,
+        };
+
+        let mut resolve = IndexMap::new();
+        if let Some(item) = table.get("resolve") {
+            let resolve_table = item
+                .as_table_like()
+                .ok_or_else(|| invalid_option("resolve", item))?;
+            for (host, item) in resolve_table.iter() {
+                let addresses = parse_ip_addresses(item)
+                    .ok_or_else(|| invalid_option(&format!("resolve.{host}"), item))?;
+                resolve.insert(host.to_lowercase(), addresses);
+            }
+        }
+
+        let get_duration = |key: &str| match table.get(key) {
+            None => Ok(None),
+            Some(item) => item
+                .as_str()
+                .and_then(parse_duration)
+                .map(Some)
+                .ok_or_else(|| invalid_option(key, item)),
+        };
+
+        Ok(HttpConfig {
+            http2_prior_knowledge,
+            pool_max_idle_per_host,
+            pool_idle_timeout: get_duration("pool_idle_timeout")?,
+            tcp_keepalive: get_duration("tcp_keepalive")?,
+            max_retries,
+            retry_jitter,
+            host_retry_budget: get_duration("host_retry_budget")?,
+            resolve,
+            root_certificates,
+        })
+    }
+}
+
+// a single address or an array of addresses that are tried in order
+fn parse_ip_addresses(item: &Item) -> Option<Vec<IpAddr>> {
+    let parse = |value: &toml_edit::Value| value.as_str()?.trim().parse::<IpAddr>().ok();
+    match item.as_value()? {
+        toml_edit::Value::Array(array) if !array.is_empty() => array.iter().map(parse).collect(),
+        value => parse(value).map(|address| vec![address]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    use toml_edit::DocumentMut;
+
+    fn parse_http_config(contents: &str) -> Result<HttpConfig, ParseConfigError> {
+        let doc = DocumentMut::from_str(contents).unwrap();
+        HttpConfig::try_from(doc.as_table() as &dyn TableLike)
+    }
+
+    #[test]
+    fn parse_http_config_with_all_options() {
+        assert_eq!(
+            parse_http_config(
+                r#"
+                http2_prior_knowledge = true
+                pool_max_idle_per_host = 32
+                pool_idle_timeout = "90s"
+                tcp_keepalive = "1m"
+                max_retries = 3
+                retry_jitter = "Bounded"
+                host_retry_budget = "30s"
+                resolve = { "Archive.Ubuntu.com" = "185.125.190.81", "mirror.example.com" = ["10.0.0.1", "::1"] }
+                root_certificates = "Bundled"
+                "#
+            )
+            .unwrap(),
+            HttpConfig {
+                http2_prior_knowledge: true,
+                pool_max_idle_per_host: Some(32),
+                pool_idle_timeout: Some(Duration::from_secs(90)),
+                tcp_keepalive: Some(Duration::from_mins(1)),
+                max_retries: Some(3),
+                retry_jitter: Some(RetryJitter::Bounded),
+                host_retry_budget: Some(Duration::from_secs(30)),
+                resolve: IndexMap::from([
+                    (
+                        "archive.ubuntu.com".to_string(),
+                        vec!["185.125.190.81".parse().unwrap()]
+                    ),
+                    (
+                        "mirror.example.com".to_string(),
+                        vec!["10.0.0.1".parse().unwrap(), "::1".parse().unwrap()]
+                    ),
+                ]),
+                root_certificates: RootCertificates::Bundled,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_http_config_with_invalid_option() {
+        match parse_http_config("pool_max_idle_per_host = -1").unwrap_err() {
+            ParseConfigError::InvalidHttpOption(key, value) => {
+                assert_eq!(key, "pool_max_idle_per_host");
+                assert_eq!(value, "-1");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_http_config_with_invalid_retry_option() {
+        for (contents, expected_key, expected_value) in [
+            ("max_retries = -1", "max_retries", "-1"),
+            (r#"retry_jitter = "random""#, "retry_jitter", r#""random""#),
+            ("host_retry_budget = 60", "host_retry_budget", "60"),
+            (r#"resolve = "10.0.0.1""#, "resolve", r#""10.0.0.1""#),
+            (
+                r#"root_certificates = "webpki""#,
+                "root_certificates",
+                r#""webpki""#,
+            ),
+            (
+                r#"resolve = { "archive.ubuntu.com" = "archive" }"#,
+                "resolve.archive.ubuntu.com",
+                r#""archive""#,
+            ),
+            (
+                r#"resolve = { "archive.ubuntu.com" = [] }"#,
+                "resolve.archive.ubuntu.com",
+                "[]",
+            ),
+        ] {
+            match parse_http_config(contents).unwrap_err() {
+                ParseConfigError::InvalidHttpOption(key, value) => {
+                    assert_eq!(key, expected_key);
+                    assert_eq!(value, expected_value);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            }
+        }
+    }
+}
@@ -3,5 +3,8 @@ pub(crate) use requested_package::*;
 
 mod buildpack_config;
 pub(crate) mod custom_source;
+pub(crate) mod default_suites;
 pub(crate) mod download_url;
+pub(crate) mod hooks_config;
+pub(crate) mod http_config;
 mod requested_package;
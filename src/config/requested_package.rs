@@ -1,15 +1,41 @@
-use crate::debian::{PackageName, ParsePackageNameError};
+use crate::debian::{ArchitectureName, PackageName, ParsePackageNameError};
 use serde::Serialize;
 use std::str::FromStr;
 use toml_edit::{Formatted, InlineTable, Value};
 
-#[derive(Debug, Eq, PartialEq, Hash, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize)]
+#[allow(clippy::struct_excessive_bools)]
 pub(crate) struct RequestedPackage {
     pub(crate) name: PackageName,
     pub(crate) skip_dependencies: bool,
     pub(crate) force: bool,
+    pub(crate) optional: bool,
+    // a foreign architecture (e.g.; i386) to install the package for instead of the native one
+    pub(crate) arch: Option<ArchitectureName>,
+    // the suite (e.g.; `noble-backports`) the package must be installed from instead of the one
+    // the package index would choose
+    pub(crate) suite: Option<String>,
+    // glob patterns (e.g.; `usr/lib/chromium/**`) limiting which files are extracted from the package
+    pub(crate) include_paths: Vec<String>,
+    // a directory in the layer (e.g.; `opt/wkhtmltopdf`) to extract the package into instead of the root
+    pub(crate) prefix: Option<String>,
+    // whether the package is only added to the environment at launch, not during the build
+    pub(crate) launch_only: bool,
 }
 
+// The keys recognized in the inline table form of a requested package.
+pub(crate) const REQUESTED_PACKAGE_KEYS: [&str; 9] = [
+    "name",
+    "skip_dependencies",
+    "force",
+    "optional",
+    "arch",
+    "suite",
+    "include_paths",
+    "prefix",
+    "launch_only",
+];
+
 impl FromStr for RequestedPackage {
     type Err = ParseRequestedPackageError;
 
@@ -19,36 +45,65 @@ impl FromStr for RequestedPackage {
                 .map_err(ParseRequestedPackageError::InvalidPackageName)?,
             skip_dependencies: false,
             force: false,
+            optional: false,
+            arch: None,
+            suite: None,
+            include_paths: vec![],
+            prefix: None,
+            launch_only: false,
         })
     }
 }
 
-impl TryFrom<&Value> for RequestedPackage {
-    type Error = ParseRequestedPackageError;
+// Values set at the root of the buildpack configuration that apply to every requested package
+// unless the package sets them itself.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct RequestedPackageDefaults {
+    pub(crate) skip_dependencies: bool,
+    pub(crate) force: bool,
+}
 
-    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+impl RequestedPackage {
+    // the name used for the package in the package index
+    pub(crate) fn qualified_name(&self) -> String {
+        match &self.arch {
+            Some(arch) => arch.qualify_package_name(self.name.as_str()),
+            None => self.name.to_string(),
+        }
+    }
+
+    pub(crate) fn from_value_with_defaults(
+        value: &Value,
+        defaults: RequestedPackageDefaults,
+    ) -> Result<Self, ParseRequestedPackageError> {
         match value {
-            Value::String(formatted_string) => RequestedPackage::try_from(formatted_string),
-            Value::InlineTable(inline_table) => RequestedPackage::try_from(inline_table),
-            _ => Err(ParseRequestedPackageError::UnexpectedTomlValue(
+            Value::String(formatted_string) => {
+                RequestedPackage::from_formatted_string(formatted_string, defaults)
+            }
+            Value::InlineTable(inline_table) => {
+                RequestedPackage::from_inline_table(inline_table, defaults)
+            }
+            _ => Err(ParseRequestedPackageError::UnexpectedTomlValue(Box::new(
                 value.clone(),
-            )),
+            ))),
         }
     }
-}
 
-impl TryFrom<&Formatted<String>> for RequestedPackage {
-    type Error = ParseRequestedPackageError;
-
-    fn try_from(formatted_string: &Formatted<String>) -> Result<Self, Self::Error> {
-        RequestedPackage::from_str(formatted_string.value())
+    fn from_formatted_string(
+        formatted_string: &Formatted<String>,
+        defaults: RequestedPackageDefaults,
+    ) -> Result<Self, ParseRequestedPackageError> {
+        Ok(RequestedPackage {
+            skip_dependencies: defaults.skip_dependencies,
+            force: defaults.force,
+            ..RequestedPackage::from_str(formatted_string.value())?
+        })
     }
-}
 
-impl TryFrom<&InlineTable> for RequestedPackage {
-    type Error = ParseRequestedPackageError;
-
-    fn try_from(table: &InlineTable) -> Result<Self, Self::Error> {
+    fn from_inline_table(
+        table: &InlineTable,
+        defaults: RequestedPackageDefaults,
+    ) -> Result<Self, ParseRequestedPackageError> {
         Ok(RequestedPackage {
             name: PackageName::from_str(
                 table
@@ -61,18 +116,131 @@ impl TryFrom<&InlineTable> for RequestedPackage {
             skip_dependencies: table
                 .get("skip_dependencies")
                 .and_then(Value::as_bool)
-                .unwrap_or_default(),
+                .unwrap_or(defaults.skip_dependencies),
 
             force: table
                 .get("force")
                 .and_then(Value::as_bool)
+                .unwrap_or(defaults.force),
+
+            optional: table
+                .get("optional")
+                .and_then(Value::as_bool)
+                .unwrap_or_default(),
+
+            arch: table
+                .get("arch")
+                .and_then(Value::as_str)
+                .map(|arch| {
+                    ArchitectureName::from_str(arch)
+                        .ok()
+                        .filter(ArchitectureName::is_foreign)
+                        .ok_or_else(|| {
+                            ParseRequestedPackageError::InvalidArchitecture(arch.to_string())
+                        })
+                })
+                .transpose()?,
+
+            suite: table
+                .get("suite")
+                .map(|value| {
+                    value
+                        .as_str()
+                        .filter(|suite| !suite.trim().is_empty())
+                        .map(ToString::to_string)
+                        .ok_or_else(|| {
+                            ParseRequestedPackageError::InvalidSuite(
+                                value.to_string().trim().to_string(),
+                            )
+                        })
+                })
+                .transpose()?,
+
+            include_paths: table
+                .get("include_paths")
+                .map(|value| {
+                    parse_include_paths(value).ok_or_else(|| {
+                        ParseRequestedPackageError::InvalidIncludePaths(
+                            value.to_string().trim().to_string(),
+                        )
+                    })
+                })
+                .transpose()?
+                .unwrap_or_default(),
+
+            prefix: table
+                .get("prefix")
+                .map(|value| {
+                    value
+                        .as_str()
+                        .and_then(parse_relative_path)
+                        .map(|prefix| prefix.trim_end_matches('/').to_string())
+                        .ok_or_else(|| {
+                            ParseRequestedPackageError::InvalidPrefix(
+                                value.to_string().trim().to_string(),
+                            )
+                        })
+                })
+                .transpose()?,
+
+            launch_only: table
+                .get("launch_only")
+                .and_then(Value::as_bool)
                 .unwrap_or_default(),
         })
     }
 }
 
+fn parse_include_paths(value: &Value) -> Option<Vec<String>> {
+    value
+        .as_array()?
+        .iter()
+        .map(|pattern| pattern.as_str().and_then(parse_relative_path))
+        .collect::<Option<Vec<_>>>()
+        .filter(|patterns| !patterns.is_empty())
+}
+
+// Paths are relative to the root of the filesystem the package is extracted into so a leading `/`
+// or `./` is accepted but paths can't reach outside of it.
+fn parse_relative_path(path: &str) -> Option<String> {
+    let path = path.strip_prefix("./").unwrap_or(path);
+    let path = path.trim_start_matches('/');
+    (!path.is_empty() && !path.split('/').any(|part| part == "..")).then(|| path.to_string())
+}
+
+impl TryFrom<&Value> for RequestedPackage {
+    type Error = ParseRequestedPackageError;
+
+    fn try_from(value: &Value) -> Result<Self, Self::Error> {
+        RequestedPackage::from_value_with_defaults(value, RequestedPackageDefaults::default())
+    }
+}
+
+impl TryFrom<&Formatted<String>> for RequestedPackage {
+    type Error = ParseRequestedPackageError;
+
+    fn try_from(formatted_string: &Formatted<String>) -> Result<Self, Self::Error> {
+        RequestedPackage::from_formatted_string(
+            formatted_string,
+            RequestedPackageDefaults::default(),
+        )
+    }
+}
+
+impl TryFrom<&InlineTable> for RequestedPackage {
+    type Error = ParseRequestedPackageError;
+
+    fn try_from(table: &InlineTable) -> Result<Self, Self::Error> {
+        RequestedPackage::from_inline_table(table, RequestedPackageDefaults::default())
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum ParseRequestedPackageError {
     InvalidPackageName(ParsePackageNameError),
-    UnexpectedTomlValue(Value),
+    UnexpectedTomlValue(Box<Value>),
+    InvalidArchitecture(String),
+    InvalidSuite(String),
+    InvalidIncludePaths(String),
+    InvalidPrefix(String),
 }
@@ -0,0 +1,433 @@
+use crate::config::RequestedPackage;
+use crate::create_package_index::read_release;
+use crate::debian::{MultiarchName, RepositoryUri, Source};
+use crate::missing_shared_libraries::get_system_library_dirs;
+use crate::o11y::*;
+use crate::{
+    BuildpackResult, DebianPackagesBuildpack, DebianPackagesBuildpackError, to_request_url,
+};
+use apt_parser::ReleaseHash;
+use async_compression::tokio::bufread::GzipDecoder;
+use bullet_stream::{global::print, style};
+use futures::TryStreamExt;
+use indexmap::IndexSet;
+use libcnb::build::BuildContext;
+use reqwest_middleware::ClientWithMiddleware;
+use reqwest_middleware::Error::Reqwest;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader as AsyncBufReader};
+use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tokio_util::io::InspectReader;
+use tracing::{info, instrument};
+
+// NOTE: The Contents index of a suite lists every file shipped by its packages along with the
+//       packages that ship it, one file per line (e.g.;
+//       `usr/lib/x86_64-linux-gnu/libexample.so.1    libs/libexample1`). Debian publishes one index
+//       per component while Ubuntu publishes a single index for the whole suite. These indices are
+//       large and only needed when a library is missing so, unlike the package indices, they're
+//       searched while downloading instead of being cached.
+
+// Finds the package providing each of the given libraries in the native library directories by
+// searching the Contents index of every suite in source list order. Libraries that no package
+// provides are left out and suites that don't publish a Contents index are skipped.
+#[instrument(skip_all)]
+pub(crate) async fn find_library_packages(
+    context: &Arc<BuildContext<DebianPackagesBuildpack>>,
+    client: &ClientWithMiddleware,
+    source_list: &[Source],
+    libraries: &BTreeSet<String>,
+    index_max_age: Option<Duration>,
+) -> BuildpackResult<BTreeMap<String, String>> {
+    let mut library_packages = BTreeMap::new();
+
+    for source in source_list {
+        let library_dirs = get_system_library_dirs(&[MultiarchName::from(&source.arch)])
+            .iter()
+            .map(|library_dir| {
+                library_dir
+                    .to_string_lossy()
+                    .trim_start_matches('/')
+                    .to_string()
+            })
+            .collect::<BTreeSet<_>>();
+
+        for suite in &source.suites {
+            let remaining_libraries = libraries
+                .iter()
+                .filter(|library| !library_packages.contains_key(*library))
+                .cloned()
+                .collect::<BTreeSet<_>>();
+            if remaining_libraries.is_empty() {
+                return Ok(library_packages);
+            }
+
+            let release = read_release(context, client, source, suite, index_max_age).await?;
+            let acquire_by_hash = release.acquire_by_hash.unwrap_or_default();
+            let contents_indexes = get_contents_indexes(
+                release.sha256sum.as_deref().unwrap_or_default(),
+                &source.components,
+                &source.arch.to_string(),
+            );
+            for contents_index in contents_indexes {
+                let contents_index_url = contents_index.url(&source.uri, suite, acquire_by_hash);
+                print::sub_bullet(format!(
+                    "Searching {url}",
+                    url = style::url(remove_url_credentials(&contents_index_url))
+                ));
+                let found_packages = search_contents_index(
+                    client,
+                    &contents_index_url,
+                    &contents_index.hash,
+                    &remaining_libraries,
+                    &library_dirs,
+                )
+                .await?;
+                for (library, package) in found_packages {
+                    library_packages.entry(library).or_insert(package);
+                }
+            }
+        }
+    }
+
+    Ok(library_packages)
+}
+
+// Adds a requested package for each package providing a missing library unless it's already
+// requested. These are optional since a package can be listed in the Contents index of a suite
+// without being in the package index (e.g.; when it's only published for another component).
+pub(crate) fn add_library_packages(
+    requested_packages: &IndexSet<RequestedPackage>,
+    library_packages: &BTreeMap<String, String>,
+) -> IndexSet<RequestedPackage> {
+    let mut requested_packages = requested_packages.clone();
+    for package in library_packages.values() {
+        if requested_packages
+            .iter()
+            .any(|requested_package| requested_package.name.as_str() == package)
+        {
+            continue;
+        }
+        if let Ok(requested_package) = RequestedPackage::from_str(package) {
+            requested_packages.insert(RequestedPackage {
+                optional: true,
+                ..requested_package
+            });
+        }
+    }
+    requested_packages
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct ContentsIndex {
+    // relative to the suite, e.g.; `main/Contents-amd64.gz`
+    filename: String,
+    hash: String,
+}
+
+impl ContentsIndex {
+    fn url(&self, repository_uri: &RepositoryUri, suite: &str, acquire_by_hash: bool) -> String {
+        if acquire_by_hash {
+            let by_hash_path = match Path::new(&self.filename).parent() {
+                Some(directory) if !directory.as_os_str().is_empty() => {
+                    format!(
+                        "{}/by-hash/SHA256/{}",
+                        directory.to_string_lossy(),
+                        self.hash
+                    )
+                }
+                _ => format!("by-hash/SHA256/{}", self.hash),
+            };
+            format!("{repository_uri}/dists/{suite}/{by_hash_path}")
+        } else {
+            format!("{repository_uri}/dists/{suite}/{}", self.filename)
+        }
+    }
+}
+
+// Prefers the index of each configured component and falls back to the index for the whole suite.
+fn get_contents_indexes(
+    release_hashes: &[ReleaseHash],
+    components: &[String],
+    arch: &str,
+) -> Vec<ContentsIndex> {
+    let find_contents_index = |filename: String| {
+        release_hashes
+            .iter()
+            .find(|release_hash| release_hash.filename == filename)
+            .map(|release_hash| ContentsIndex {
+                filename,
+                hash: release_hash.hash.clone(),
+            })
+    };
+
+    let component_contents_indexes = components
+        .iter()
+        .filter_map(|component| find_contents_index(format!("{component}/Contents-{arch}.gz")))
+        .collect::<Vec<_>>();
+    if component_contents_indexes.is_empty() {
+        find_contents_index(format!("Contents-{arch}.gz"))
+            .into_iter()
+            .collect()
+    } else {
+        component_contents_indexes
+    }
+}
+
+async fn search_contents_index(
+    client: &ClientWithMiddleware,
+    contents_index_url: &str,
+    expected_hash: &str,
+    libraries: &BTreeSet<String>,
+    library_dirs: &BTreeSet<String>,
+) -> BuildpackResult<BTreeMap<String, String>> {
+    info!(
+        { CONTENTS_INDEX_URL } = %remove_url_credentials(contents_index_url),
+        "contents index"
+    );
+
+    let response = client
+        .get(to_request_url(contents_index_url))
+        .send()
+        .await
+        .and_then(|res| res.error_for_status().map_err(Reqwest))
+        .map_err(ContentsIndexError::Request)?;
+
+    let mut hasher = Sha256::new();
+    let mut found_packages = BTreeMap::new();
+
+    {
+        // the inspect reader lets us pipe the compressed response to both the decoder and the hash digest
+        let compressed_reader = AsyncBufReader::new(InspectReader::new(
+            FuturesAsyncReadCompatExt::compat(
+                response
+                    .bytes_stream()
+                    .map_err(std::io::Error::other)
+                    .into_async_read(),
+            ),
+            |bytes| hasher.update(bytes),
+        ));
+        let mut decoder = GzipDecoder::new(compressed_reader);
+        decoder.multiple_members(true);
+
+        // The whole index is read, even once every library is found, so its checksum can be
+        // verified. Lines are read as bytes since file paths aren't guaranteed to be UTF-8.
+        let mut reader = AsyncBufReader::new(decoder);
+        let mut line = vec![];
+        while reader
+            .read_until(b'\n', &mut line)
+            .await
+            .map_err(|e| ContentsIndexError::Read(contents_index_url.to_string(), e))?
+            > 0
+        {
+            if let Some((library, package)) =
+                match_library_line(&String::from_utf8_lossy(&line), libraries, library_dirs)
+            {
+                found_packages.entry(library).or_insert(package);
+            }
+            line.clear();
+        }
+    }
+
+    let calculated_hash = hex::encode(hasher.finalize());
+    if calculated_hash != expected_hash {
+        Err(ContentsIndexError::ChecksumFailed {
+            url: contents_index_url.to_string(),
+            expected: expected_hash.to_string(),
+            actual: calculated_hash,
+        })?;
+    }
+
+    Ok(found_packages)
+}
+
+// The file path can contain spaces so the packages are split off at the last whitespace. Only
+// the first of the packages shipping a file is used and its section prefix (e.g.; `libs/` or
+// `universe/libs/`) is removed.
+fn match_library_line(
+    line: &str,
+    libraries: &BTreeSet<String>,
+    library_dirs: &BTreeSet<String>,
+) -> Option<(String, String)> {
+    let (path, packages) = line.trim_end().rsplit_once(char::is_whitespace)?;
+    let (library_dir, library) = path.trim_end().rsplit_once('/')?;
+    if !libraries.contains(library) || !library_dirs.contains(library_dir) {
+        return None;
+    }
+    let package = packages.split(',').next()?.rsplit('/').next()?;
+    if package.is_empty() {
+        return None;
+    }
+    Some((library.to_string(), package.to_string()))
+}
+
+#[derive(Debug)]
+pub(crate) enum ContentsIndexError {
+    Request(reqwest_middleware::Error),
+    Read(String, std::io::Error),
+    ChecksumFailed {
+        url: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl From<ContentsIndexError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: ContentsIndexError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::ContentsIndex(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn release_hash(filename: &str, hash: &str) -> ReleaseHash {
+        ReleaseHash {
+            filename: filename.to_string(),
+            hash: hash.to_string(),
+            size: 0,
+        }
+    }
+
+    #[test]
+    fn match_library_line_in_library_dirs() {
+        let libraries = BTreeSet::from(["libexample.so.1".to_string()]);
+        let library_dirs = BTreeSet::from([
+            "usr/lib".to_string(),
+            "usr/lib/x86_64-linux-gnu".to_string(),
+        ]);
+
+        assert_eq!(
+            match_library_line(
+                "usr/lib/x86_64-linux-gnu/libexample.so.1                   libs/libexample1,universe/libs/libexample1t64",
+                &libraries,
+                &library_dirs
+            ),
+            Some(("libexample.so.1".to_string(), "libexample1".to_string()))
+        );
+        assert_eq!(
+            match_library_line(
+                "usr/lib/debug/libexample.so.1 debug/libexample1-dbg",
+                &libraries,
+                &library_dirs
+            ),
+            None
+        );
+        assert_eq!(
+            match_library_line(
+                "usr/lib/x86_64-linux-gnu/libother.so.1 libs/libother1",
+                &libraries,
+                &library_dirs
+            ),
+            None
+        );
+        assert_eq!(
+            match_library_line(
+                "libexample.so.1 libs/libexample1",
+                &libraries,
+                &library_dirs
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn add_library_packages_as_optional() {
+        let requested_packages = IndexSet::from([
+            RequestedPackage::from_str("imagemagick").unwrap(),
+            RequestedPackage::from_str("libexample1").unwrap(),
+        ]);
+        let library_packages = BTreeMap::from([
+            ("libexample.so.1".to_string(), "libexample1".to_string()),
+            ("libmissing.so.2".to_string(), "libmissing2".to_string()),
+            ("libother.so.2".to_string(), "libmissing2".to_string()),
+        ]);
+
+        assert_eq!(
+            add_library_packages(&requested_packages, &library_packages),
+            IndexSet::from([
+                RequestedPackage::from_str("imagemagick").unwrap(),
+                RequestedPackage::from_str("libexample1").unwrap(),
+                RequestedPackage {
+                    optional: true,
+                    ..RequestedPackage::from_str("libmissing2").unwrap()
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn contents_indexes_prefer_components() {
+        let release_hashes = vec![
+            release_hash("Contents-amd64.gz", "suite"),
+            release_hash("main/Contents-amd64.gz", "main"),
+            release_hash("main/Contents-arm64.gz", "main-arm64"),
+            release_hash("contrib/Contents-amd64.gz", "contrib"),
+        ];
+
+        assert_eq!(
+            get_contents_indexes(
+                &release_hashes,
+                &["main".to_string(), "non-free".to_string()],
+                "amd64"
+            ),
+            vec![ContentsIndex {
+                filename: "main/Contents-amd64.gz".to_string(),
+                hash: "main".to_string(),
+            }]
+        );
+        assert_eq!(
+            get_contents_indexes(&release_hashes, &["universe".to_string()], "amd64"),
+            vec![ContentsIndex {
+                filename: "Contents-amd64.gz".to_string(),
+                hash: "suite".to_string(),
+            }]
+        );
+        assert_eq!(
+            get_contents_indexes(&release_hashes, &["main".to_string()], "riscv64"),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn contents_index_url() {
+        let component_contents_index = ContentsIndex {
+            filename: "main/Contents-amd64.gz".to_string(),
+            hash: "abc123".to_string(),
+        };
+        let suite_contents_index = ContentsIndex {
+            filename: "Contents-amd64.gz".to_string(),
+            hash: "def456".to_string(),
+        };
+
+        assert_eq!(
+            component_contents_index.url(
+                &RepositoryUri::from("http://deb.debian.org/debian"),
+                "bookworm",
+                false
+            ),
+            "http://deb.debian.org/debian/dists/bookworm/main/Contents-amd64.gz"
+        );
+        assert_eq!(
+            component_contents_index.url(
+                &RepositoryUri::from("http://deb.debian.org/debian"),
+                "bookworm",
+                true
+            ),
+            "http://deb.debian.org/debian/dists/bookworm/main/by-hash/SHA256/abc123"
+        );
+        assert_eq!(
+            suite_contents_index.url(
+                &RepositoryUri::from("http://archive.ubuntu.com/ubuntu"),
+                "noble",
+                true
+            ),
+            "http://archive.ubuntu.com/ubuntu/dists/noble/by-hash/SHA256/def456"
+        );
+    }
+}
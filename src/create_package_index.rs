@@ -2,41 +2,46 @@ use crate::debian::{
     ArchitectureName, PackageIndex, ParseRepositoryPackageError, RepositoryPackage, RepositoryUri,
     Source, SourceOrder,
 };
+use crate::fetcher::Fetcher;
 use crate::o11y::*;
-use crate::pgp::CertHelper;
-use crate::{BuildpackResult, DebianPackagesBuildpack, DebianPackagesBuildpackError};
-use apt_parser::Release;
+use crate::pgp::{load_certs, parse_certs, verify_signed_message};
+use crate::retry::error_for_status;
+use crate::{
+    BuildpackResult, DebianPackagesBuildpack, DebianPackagesBuildpackError, get_proxy_prefix,
+    is_clean_cache_requested, is_refresh_index_requested, to_request_url,
+};
 use apt_parser::errors::APTError;
-use async_compression::tokio::bufread::GzipDecoder;
+use apt_parser::{Release, ReleaseHash};
+use async_compression::tokio::bufread::{GzipDecoder, XzDecoder, ZstdDecoder};
 use bullet_stream::{global::print, style};
 use futures::StreamExt;
 use futures::TryStreamExt;
-use futures::io::AllowStdIo;
 use futures::stream::FuturesOrdered;
+use indexmap::IndexMap;
 use libcnb::build::BuildContext;
 use libcnb::data::layer::{LayerName, LayerNameError};
 use libcnb::layer::{
     CachedLayerDefinition, EmptyLayerCause, InvalidMetadataAction, LayerState, RestoredLayerAction,
 };
-use rayon::iter::{Either, IntoParallelIterator, ParallelBridge, ParallelIterator};
-use reqwest::header::ETAG;
-use reqwest_middleware::ClientWithMiddleware;
+use rayon::iter::{Either, ParallelDrainRange, ParallelIterator};
+use reqwest::StatusCode;
+use reqwest::header::{ETAG, HeaderMap};
 use reqwest_middleware::Error::Reqwest;
 use sequoia_openpgp::Cert;
-use sequoia_openpgp::cert::CertParser;
-use sequoia_openpgp::parse::Parse;
-use sequoia_openpgp::parse::stream::VerifierBuilder;
-use sequoia_openpgp::policy::StandardPolicy;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-use std::path::PathBuf;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::fs::{File as AsyncFile, read_to_string as async_read_to_string, write as async_write};
-use tokio::io::{
-    AsyncWriteExt, BufReader as AsyncBufReader, BufWriter as AsyncBufWriter, copy as async_copy,
-};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader as AsyncBufReader};
+use tokio::sync::Mutex as AsyncMutex;
+use tokio::sync::mpsc;
 use tokio::sync::oneshot::channel;
 use tokio::sync::oneshot::error::RecvError;
 use tokio::task::{JoinError, JoinSet};
@@ -45,10 +50,13 @@ use tokio_util::io::InspectReader;
 use tracing::{Instrument, info, instrument};
 
 #[instrument(skip_all)]
+#[allow(clippy::too_many_lines)]
 pub(crate) async fn create_package_index(
     context: &Arc<BuildContext<DebianPackagesBuildpack>>,
-    client: &ClientWithMiddleware,
+    client: &impl Fetcher,
     source_list: &[Source],
+    index_max_age: Option<Duration>,
+    skip_phased_updates: bool,
 ) -> BuildpackResult<PackageIndex> {
     print::header("Creating package index");
 
@@ -63,8 +71,15 @@ pub(crate) async fn create_package_index(
         }
     }
 
+    if let Some(proxy_prefix) = get_proxy_prefix() {
+        print::sub_bullet(format!(
+            "Requests are made through the caching proxy {proxy_prefix}",
+            proxy_prefix = style::url(remove_url_credentials(proxy_prefix))
+        ));
+    }
+
     let timer = print::sub_start_timer("Updating");
-    let updated_sources = update_sources(context, client, source_list).await?;
+    let updated_sources = update_sources(context, client, source_list, index_max_age).await?;
     timer.done();
 
     for updated_source in &updated_sources {
@@ -73,7 +88,11 @@ pub(crate) async fn create_package_index(
                 "Restored release file from cache {url}",
                 url = style::details(style::url(&updated_source.release_file.release_file_url))
             ),
-            UpdatedSourceCacheState::New => format!(
+            UpdatedSourceCacheState::Fresh => format!(
+                "Restored release file from cache without checking for updates {url}",
+                url = style::details(style::url(&updated_source.release_file.release_file_url))
+            ),
+            UpdatedSourceCacheState::New | UpdatedSourceCacheState::Shared => format!(
                 "Downloaded release file {url}",
                 url = style::url(&updated_source.release_file.release_file_url)
             ),
@@ -86,7 +105,7 @@ pub(crate) async fn create_package_index(
 
         for updated_package_index in &updated_source.package_indexes {
             print::sub_bullet(match &updated_package_index.cache_state {
-                UpdatedSourceCacheState::Cached => format!(
+                UpdatedSourceCacheState::Cached | UpdatedSourceCacheState::Fresh => format!(
                     "Restored package index from cache {url}",
                     url = style::details(style::url(&updated_package_index.package_index_url))
                 ),
@@ -94,6 +113,10 @@ pub(crate) async fn create_package_index(
                     "Downloaded package index {url}",
                     url = style::url(&updated_package_index.package_index_url)
                 ),
+                UpdatedSourceCacheState::Shared => format!(
+                    "Reused package index with identical contents {url}",
+                    url = style::details(style::url(&updated_package_index.package_index_url))
+                ),
                 UpdatedSourceCacheState::Invalidated(reason) => format!(
                     "Redownloaded package index {url} {reason}",
                     url = style::url(&updated_package_index.package_index_url),
@@ -103,9 +126,24 @@ pub(crate) async fn create_package_index(
         }
     }
 
+    let not_automatic_sources = updated_sources
+        .iter()
+        .filter(|updated_source| updated_source.not_automatic)
+        .map(|updated_source| {
+            (
+                updated_source.release_file.release_file_url.clone(),
+                updated_source
+                    .package_indexes
+                    .iter()
+                    .map(|updated_package_index| updated_package_index.source_order)
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect::<Vec<_>>();
+
     print::bullet("Building package index");
     let timer = print::sub_start_timer("Processing package files");
-    let package_index = build_package_index(
+    let mut package_index = build_package_index(
         updated_sources
             .into_iter()
             .flat_map(|updated_source| updated_source.package_indexes)
@@ -123,19 +161,43 @@ pub(crate) async fn create_package_index(
         package_index.packages_indexed
     ));
 
+    if !not_automatic_sources.is_empty() {
+        package_index.deprioritize_not_automatic(
+            &not_automatic_sources
+                .iter()
+                .flat_map(|(_, source_orders)| source_orders.iter().copied())
+                .collect(),
+        );
+        for (release_file_url, _) in &not_automatic_sources {
+            print::sub_bullet(format!(
+                "Only using packages from {url} when they're pinned or no other version is available",
+                url = style::url(release_file_url)
+            ));
+        }
+    }
+
+    if skip_phased_updates {
+        print::sub_bullet(format!(
+            "Held back updates still being phased in for {} packages",
+            package_index.hold_back_phased_updates()
+        ));
+    }
+
     Ok(package_index)
 }
 
 #[instrument(skip_all)]
 async fn update_sources(
     context: &Arc<BuildContext<DebianPackagesBuildpack>>,
-    client: &ClientWithMiddleware,
+    client: &impl Fetcher,
     sources: &[Source],
+    index_max_age: Option<Duration>,
 ) -> BuildpackResult<Vec<UpdatedSource>> {
     if sources.is_empty() {
         Err(CreatePackageIndexError::NoSources)?;
     }
 
+    let fetched_package_indexes = FetchedPackageIndexes::default();
     let mut tasks = FuturesOrdered::new();
 
     for (source_index, source) in sources.iter().enumerate() {
@@ -148,9 +210,14 @@ async fn update_sources(
                     suite.clone(),
                     source.components.clone(),
                     source.arch.clone(),
+                    source.foreign_arches.clone(),
                     source.signed_by.clone(),
+                    source.keyserver_fingerprints.clone(),
+                    source.allow_weak_checksums,
                     source_index,
                     suite_index,
+                    index_max_age,
+                    fetched_package_indexes.clone(),
                 )
                 .in_current_span(),
             ));
@@ -166,17 +233,22 @@ async fn update_sources(
 }
 
 #[instrument(skip_all)]
-#[allow(clippy::too_many_arguments)]
+#[allow(clippy::too_many_arguments, clippy::too_many_lines)]
 async fn update_source(
     context: Arc<BuildContext<DebianPackagesBuildpack>>,
-    client: ClientWithMiddleware,
+    client: impl Fetcher,
     repository_uri: RepositoryUri,
     suite: String,
     components: Vec<String>,
     arch: ArchitectureName,
+    foreign_arches: Vec<ArchitectureName>,
     signed_by: String,
+    keyserver_fingerprints: Vec<String>,
+    allow_weak_checksums: bool,
     source_index: usize,
     suite_index: usize,
+    index_max_age: Option<Duration>,
+    fetched_package_indexes: FetchedPackageIndexes,
 ) -> BuildpackResult<UpdatedSource> {
     let updated_release_file = get_release(
         context.clone(),
@@ -184,44 +256,73 @@ async fn update_source(
         repository_uri.clone(),
         suite.clone(),
         signed_by,
+        keyserver_fingerprints,
+        index_max_age,
     )
     .await?;
 
-    let release = async_read_to_string(&updated_release_file.release_file_path)
-        .await
-        .map_err(|e| {
-            CreatePackageIndexError::ReadReleaseFile(
-                updated_release_file.release_file_path.clone(),
-                e,
-            )
-        })
-        .and_then(|release_data| {
-            Release::from(&release_data).map_err(|e| {
-                CreatePackageIndexError::ParseReleaseFile(
-                    updated_release_file.release_file_path.clone(),
-                    e,
-                )
-            })
-        })?;
+    let (release, release_data) =
+        parse_release_file(&updated_release_file.release_file_path).await?;
 
     let acquire_by_hash = release.acquire_by_hash.unwrap_or_default();
-    let mut tasks = FuturesOrdered::new();
-
-    for (component_index, component) in components.iter().enumerate() {
-        let package_index = format!("{component}/binary-{arch}/Packages.gz");
-        let package_index_release_hash = release
+    let not_automatic = release.not_automatic.unwrap_or_default();
+    let release_hashes =
+        release
             .sha256sum
             .as_ref()
             .ok_or(CreatePackageIndexError::MissingSha256ReleaseHashes(
                 repository_uri.clone(),
-            ))?
-            .iter()
-            .find(|release_hash| release_hash.filename == package_index)
-            .ok_or(CreatePackageIndexError::MissingPackageIndexReleaseHash(
-                repository_uri.clone(),
-                package_index,
             ))?;
+    let mut package_indexes = vec![];
+
+    // the release file is shared by every architecture the repository serves
+    let native_arch = arch.clone();
+    let arches = std::iter::once(arch).chain(foreign_arches);
+    for (arch, (component_index, component)) in
+        arches.flat_map(|arch| std::iter::repeat(arch).zip(components.iter().enumerate()))
+    {
+        let mut package_index_variants =
+            get_package_index_variants(release_hashes, &format!("{component}/binary-{arch}"))
+                .into_iter();
+        let preferred_package_index = package_index_variants.next().ok_or(
+            CreatePackageIndexError::MissingPackageIndexReleaseHash(
+                repository_uri.clone(),
+                format!("{component}/binary-{arch}/Packages.gz"),
+            ),
+        )?;
+        package_indexes.push((
+            arch,
+            component_index,
+            component,
+            preferred_package_index,
+            package_index_variants.collect(),
+        ));
+    }
+
+    // Some repositories publish `Architecture: all` packages in a separate `binary-all` index
+    // instead of repeating them in the index of every architecture. These are installable on the
+    // native architecture so they're read along with its package indices.
+    if has_architecture_all_index(&release, &release_data) {
+        for (component_index, component) in components.iter().enumerate() {
+            let mut package_index_variants =
+                get_package_index_variants(release_hashes, &format!("{component}/binary-all"))
+                    .into_iter();
+            if let Some(preferred_package_index) = package_index_variants.next() {
+                package_indexes.push((
+                    native_arch.clone(),
+                    component_index,
+                    component,
+                    preferred_package_index,
+                    package_index_variants.collect(),
+                ));
+            }
+        }
+    }
 
+    let mut tasks = FuturesOrdered::new();
+    for (arch, component_index, component, preferred_package_index, fallback_package_indexes) in
+        package_indexes
+    {
         let source_order = SourceOrder::new(source_index, suite_index, component_index);
 
         tasks.push_back(tokio::spawn(
@@ -232,9 +333,12 @@ async fn update_source(
                 acquire_by_hash,
                 suite.clone(),
                 component.clone(),
-                arch.clone(),
-                package_index_release_hash.hash.clone(),
+                arch,
+                preferred_package_index,
+                fallback_package_indexes,
                 source_order,
+                allow_weak_checksums,
+                fetched_package_indexes.clone(),
             )
             .in_current_span(),
         ));
@@ -248,33 +352,100 @@ async fn update_source(
     Ok(UpdatedSource {
         release_file: updated_release_file,
         package_indexes: updated_package_indexes,
+        not_automatic,
     })
 }
 
+// Gets the verified release file of a suite for lookups made after the package index was created
+// (e.g.; the Contents index), reusing the release file layer written while creating it.
+pub(crate) async fn read_release(
+    context: &Arc<BuildContext<DebianPackagesBuildpack>>,
+    client: &impl Fetcher,
+    source: &Source,
+    suite: &str,
+    index_max_age: Option<Duration>,
+) -> BuildpackResult<Release> {
+    let updated_release_file = get_release(
+        context.clone(),
+        client.clone(),
+        source.uri.clone(),
+        suite.to_string(),
+        source.signed_by.clone(),
+        source.keyserver_fingerprints.clone(),
+        index_max_age,
+    )
+    .await?;
+    let (release, _) = parse_release_file(&updated_release_file.release_file_path).await?;
+    Ok(release)
+}
+
+async fn parse_release_file(release_file_path: &Path) -> BuildpackResult<(Release, String)> {
+    let release_data = async_read_to_string(release_file_path)
+        .await
+        .map_err(|e| CreatePackageIndexError::ReadReleaseFile(release_file_path.into(), e))?;
+    let release = Release::from(&release_data)
+        .map_err(|e| CreatePackageIndexError::ParseReleaseFile(release_file_path.into(), e))?;
+    Ok((release, release_data))
+}
+
 #[instrument(skip_all)]
+#[allow(clippy::too_many_lines)]
 async fn get_release(
     context: Arc<BuildContext<DebianPackagesBuildpack>>,
-    client: ClientWithMiddleware,
+    client: impl Fetcher,
     uri: RepositoryUri,
     suite: String,
     signed_by: String,
+    keyserver_fingerprints: Vec<String>,
+    index_max_age: Option<Duration>,
 ) -> BuildpackResult<UpdatedReleaseFile> {
     info!({ RELEASE_URI } = %remove_url_credentials(&uri), { RELEASE_SUITE } = %suite, "release info");
 
     let release_file_url = format!("{uri}/dists/{suite}/InRelease");
 
-    let response = client
-        .get(&release_file_url)
-        .send()
-        .await
-        .and_then(|res| res.error_for_status().map_err(Reqwest))
-        .map_err(CreatePackageIndexError::GetReleaseRequest)?;
-
     // it would be nice to use the url as the layer name but urls don't make for good file names
     // so instead we'll convert the url to a sha256 hex value
     let layer_name = LayerName::from_str(&hex::encode(Sha256::digest(&release_file_url)))
         .map_err(|e| CreatePackageIndexError::InvalidLayerName(release_file_url.clone(), e))?;
 
+    let bypass_cache_reason = get_bypass_cache_reason();
+
+    // A release file that was checked against the repository recently enough is used as-is without
+    // making any requests. Otherwise, the layer is kept so it can be compared with the ETag below.
+    if let Some(index_max_age) = index_max_age
+        && bypass_cache_reason.is_none()
+    {
+        let release_file_layer = context.cached_layer(
+            layer_name.clone(),
+            CachedLayerDefinition {
+                build: true,
+                launch: false,
+                restored_layer_action: &|old_metadata: &ReleaseFileMetadata, layer_path: &Path| {
+                    (
+                        RestoredLayerAction::KeepLayer,
+                        layer_path.join("release").exists()
+                            && old_metadata.is_checked_within(index_max_age),
+                    )
+                },
+                invalid_metadata_action: &|_| InvalidMetadataAction::DeleteLayer,
+            },
+        )?;
+
+        if let LayerState::Restored { cause: true } = release_file_layer.state {
+            return Ok(UpdatedReleaseFile {
+                release_file_url,
+                release_file_path: release_file_layer.path().join("release"),
+                cache_state: UpdatedSourceCacheState::Fresh,
+            });
+        }
+    }
+
+    let response = client
+        .fetch(&to_request_url(&release_file_url), HeaderMap::new())
+        .await
+        .and_then(error_for_status)
+        .map_err(CreatePackageIndexError::GetReleaseRequest)?;
+
     let new_metadata = ReleaseFileMetadata {
         etag: response.headers().get(ETAG).and_then(|header_value| {
             if let Ok(etag) = header_value.to_str() {
@@ -283,6 +454,7 @@ async fn get_release(
                 None
             }
         }),
+        checked_at: Some(unix_timestamp()),
     };
 
     let release_file_layer = context.cached_layer(
@@ -290,8 +462,11 @@ async fn get_release(
         CachedLayerDefinition {
             build: true,
             launch: false,
-            restored_layer_action: &|old_metadata: &ReleaseFileMetadata, _| {
-                if old_metadata == &new_metadata {
+            restored_layer_action: &|old_metadata: &ReleaseFileMetadata, layer_path: &Path| {
+                if old_metadata.etag == new_metadata.etag
+                    && layer_path.join("release").exists()
+                    && bypass_cache_reason.is_none()
+                {
                     RestoredLayerAction::KeepLayer
                 } else {
                     RestoredLayerAction::DeleteLayer
@@ -304,7 +479,11 @@ async fn get_release(
     let release_file_path = release_file_layer.path().join("release");
 
     let cache_state = match release_file_layer.state {
-        LayerState::Restored { .. } => UpdatedSourceCacheState::Cached,
+        LayerState::Restored { .. } => {
+            // records when the release file was last checked for the index max age
+            release_file_layer.write_metadata(new_metadata)?;
+            UpdatedSourceCacheState::Cached
+        }
         LayerState::Empty { cause } => {
             release_file_layer.write_metadata(new_metadata)?;
 
@@ -319,33 +498,41 @@ async fn get_release(
                 .map_err(CreatePackageIndexError::ReadGetReleaseResponse)?;
 
             // GPG verification
-            let policy = StandardPolicy::new();
-            let certs: Vec<Cert> = CertParser::from_bytes(signed_by.as_bytes())
-                .map_err(CreatePackageIndexError::CreatePgpCertificate)?
-                .collect::<sequoia_openpgp::Result<Vec<Cert>>>()
-                .map_err(CreatePackageIndexError::CreatePgpCertificate)?;
-            let cert_helper = CertHelper::new(certs);
-
-            let mut reader = FuturesAsyncReadCompatExt::compat(AllowStdIo::new(
-                VerifierBuilder::from_bytes(&unverified_response_body)
-                    .map_err(CreatePackageIndexError::CreatePgpVerifier)
-                    .and_then(|verifier_builder| {
-                        verifier_builder
-                            .with_policy(&policy, None, cert_helper)
-                            .map_err(CreatePackageIndexError::CreatePgpVerifier)
-                    })?,
-            ));
+            let certs =
+                load_certs(&signed_by).map_err(CreatePackageIndexError::CreatePgpCertificate)?;
+            let verified_release = match verify_signed_message(
+                certs,
+                unverified_response_body.as_bytes(),
+            ) {
+                Ok(verified_release) => verified_release,
+                Err(e) if keyserver_fingerprints.is_empty() => {
+                    Err(CreatePackageIndexError::CreatePgpVerifier(e))?
+                }
+                Err(e) => {
+                    let refreshed_certs =
+                        fetch_keyserver_certs(&client, &keyserver_fingerprints).await;
+                    match verify_signed_message(
+                        refreshed_certs,
+                        unverified_response_body.as_bytes(),
+                    ) {
+                        Ok(verified_release) => {
+                            print::warning(format!(
+                                "The release file at {release_file_url} was verified with signing keys refreshed from {keyserver}. A newer version of this buildpack may include these keys.",
+                                release_file_url = style::url(&release_file_url),
+                                keyserver = style::url(KEYSERVER_URL),
+                            ));
+                            verified_release
+                        }
+                        Err(_) => Err(CreatePackageIndexError::CreatePgpVerifier(e))?,
+                    }
+                }
+            };
 
-            let mut writer = AsyncFile::create(&release_file_path)
+            async_write(&release_file_path, verified_release)
                 .await
                 .map_err(|e| {
                     CreatePackageIndexError::WriteReleaseLayer(release_file_path.clone(), e)
-                })
-                .map(AsyncBufWriter::new)?;
-
-            async_copy(&mut reader, &mut writer).await.map_err(|e| {
-                CreatePackageIndexError::WriteReleaseLayer(release_file_path.clone(), e)
-            })?;
+                })?;
 
             match cause {
                 EmptyLayerCause::NewlyCreated => UpdatedSourceCacheState::New,
@@ -353,7 +540,11 @@ async fn get_release(
                     UpdatedSourceCacheState::Invalidated("Invalid metadata".to_string())
                 }
                 EmptyLayerCause::RestoredLayerAction { .. } => {
-                    UpdatedSourceCacheState::Invalidated("Stored ETag did not match".to_string())
+                    UpdatedSourceCacheState::Invalidated(
+                        bypass_cache_reason
+                            .unwrap_or("Stored ETag did not match")
+                            .to_string(),
+                    )
                 }
             }
         }
@@ -366,18 +557,59 @@ async fn get_release(
     })
 }
 
+const KEYSERVER_URL: &str = "https://keyserver.ubuntu.com";
+
+// Fetches the current version of each pinned key from the keyserver. Any key that can't be fetched
+// is skipped since the original verification error is reported if the refreshed keys don't work.
+async fn fetch_keyserver_certs(client: &impl Fetcher, fingerprints: &[String]) -> Vec<Cert> {
+    let mut certs = vec![];
+    for fingerprint in fingerprints {
+        // the keyserver isn't a package repository so it's never proxied
+        let keyserver_url =
+            format!("{KEYSERVER_URL}/pks/lookup?op=get&options=mr&search=0x{fingerprint}");
+        let fetched_certs = async {
+            let response = client.fetch(&keyserver_url, HeaderMap::new()).await.ok()?;
+            let body = response.error_for_status().ok()?.bytes().await.ok()?;
+            parse_certs(&body).ok()
+        }
+        .await;
+        if let Some(fetched_certs) = fetched_certs {
+            certs.extend(filter_pinned_certs(fetched_certs, fingerprints));
+        } else {
+            info!("failed to fetch key {fingerprint} from {keyserver_url}");
+        }
+    }
+    certs
+}
+
+// a keyserver can return any certificate so only the ones with a pinned fingerprint are trusted
+fn filter_pinned_certs(certs: Vec<Cert>, fingerprints: &[String]) -> Vec<Cert> {
+    certs
+        .into_iter()
+        .filter(|cert| {
+            let cert_fingerprint = cert.fingerprint().to_hex();
+            fingerprints
+                .iter()
+                .any(|fingerprint| fingerprint.eq_ignore_ascii_case(&cert_fingerprint))
+        })
+        .collect()
+}
+
 #[instrument(skip_all)]
 #[allow(clippy::too_many_arguments, clippy::too_many_lines)]
 async fn get_package_list(
     context: Arc<BuildContext<DebianPackagesBuildpack>>,
-    client: ClientWithMiddleware,
+    client: impl Fetcher,
     repository_uri: RepositoryUri,
     acquire_by_hash: bool,
     suite: String,
     component: String,
     arch: ArchitectureName,
-    hash: String,
+    preferred_package_index: PackageIndexVariant,
+    fallback_package_indexes: Vec<PackageIndexVariant>,
     source_order: SourceOrder,
+    allow_weak_checksums: bool,
+    fetched_package_indexes: FetchedPackageIndexes,
 ) -> BuildpackResult<UpdatedPackageIndex> {
     info!(
         { PACKAGE_LIST_URI } = %remove_url_credentials(&repository_uri),
@@ -388,26 +620,43 @@ async fn get_package_list(
         "package list info"
     );
 
-    let package_index_url = if acquire_by_hash {
-        format!("{repository_uri}/dists/{suite}/{component}/binary-{arch}/by-hash/SHA256/{hash}")
-    } else {
-        format!("{repository_uri}/dists/{suite}/{component}/binary-{arch}/Packages.gz")
-    };
+    let hash = preferred_package_index.hash.clone();
+    let mut package_index_url =
+        preferred_package_index.url(&repository_uri, &suite, acquire_by_hash);
 
-    // it would be nice to use the url as the layer name but urls don't make for good file names
-    // so instead we'll convert the url to a sha256 hex value
-    let layer_name = LayerName::from_str(&hex::encode(Sha256::digest(&package_index_url)))
+    // Package indices are cached by the SHA256 of their contents from the release file instead of
+    // by url so indices with identical contents (e.g.; the same suite served by multiple mirrors or
+    // an unchanged component shared between suites) are only downloaded and stored once.
+    let layer_name = LayerName::from_str(&hash)
         .map_err(|e| CreatePackageIndexError::InvalidLayerName(package_index_url.clone(), e))?;
 
+    // concurrent requests for the same contents wait for the first one to finish and reuse it
+    let fetched_package_index = fetched_package_indexes.get(&hash);
+    let mut fetched_package_index = fetched_package_index.lock().await;
+    if let Some(package_index_path) = fetched_package_index.as_ref() {
+        return Ok(UpdatedPackageIndex {
+            repository_uri,
+            arch,
+            source_order,
+            allow_weak_checksums,
+            package_index_path: package_index_path.clone(),
+            package_index_url,
+            cache_state: UpdatedSourceCacheState::Shared,
+            packages: None,
+        });
+    }
+
     let new_metadata = PackageIndexMetadata { hash: hash.clone() };
 
+    let bypass_cache_reason = get_bypass_cache_reason();
+
     let package_index_layer = context.cached_layer(
         layer_name,
         CachedLayerDefinition {
             build: true,
             launch: false,
             restored_layer_action: &|old_metadata: &PackageIndexMetadata, _| {
-                if old_metadata == &new_metadata {
+                if old_metadata == &new_metadata && bypass_cache_reason.is_none() {
                     RestoredLayerAction::KeepLayer
                 } else {
                     RestoredLayerAction::DeleteLayer
@@ -419,11 +668,25 @@ async fn get_package_list(
 
     let package_index_path = package_index_layer.path().join("package_index");
 
+    // only set when the package index was downloaded, cached package indices are read from disk
+    let mut packages = None;
+
     let cache_state = match package_index_layer.state {
         LayerState::Restored { .. } => UpdatedSourceCacheState::Cached,
         LayerState::Empty { cause } => {
             package_index_layer.write_metadata(new_metadata)?;
 
+            let (package_index_variant, response) = request_package_index(
+                &client,
+                &repository_uri,
+                &suite,
+                acquire_by_hash,
+                &preferred_package_index,
+                &fallback_package_indexes,
+            )
+            .await?;
+            package_index_url = package_index_variant.url(&repository_uri, &suite, acquire_by_hash);
+
             let package_index_url_path = package_index_layer.path().join(".url");
             async_write(&package_index_url_path, &package_index_url)
                 .await
@@ -431,63 +694,105 @@ async fn get_package_list(
                     CreatePackageIndexError::WritePackagesLayer(package_index_url_path, e)
                 })?;
 
-            let response = client
-                .get(&package_index_url)
-                .send()
-                .await
-                .and_then(|res| res.error_for_status().map_err(Reqwest))
-                .map_err(CreatePackageIndexError::GetPackagesRequest)?;
-
             let mut hasher = Sha256::new();
 
-            // the package list we request uses gzip compression so we'll decode that directly from the response
-            let mut reader = GzipDecoder::new(AsyncBufReader::new(
-                // the inspect reader lets us pipe this decompressed output to both the ouptut file and the hash digest
-                InspectReader::new(
-                    // and we need to convert the http stream into an async reader
-                    FuturesAsyncReadCompatExt::compat(
-                        response
-                            .bytes_stream()
-                            .map_err(std::io::Error::other)
-                            .into_async_read(),
-                    ),
-                    |bytes| hasher.update(bytes),
+            // the inspect reader lets us pipe the compressed response to both the decoder and the hash digest
+            let compressed_reader = AsyncBufReader::new(InspectReader::new(
+                // and we need to convert the http stream into an async reader
+                FuturesAsyncReadCompatExt::compat(
+                    response
+                        .bytes_stream()
+                        .map_err(std::io::Error::other)
+                        .into_async_read(),
                 ),
+                |bytes| hasher.update(bytes),
             ));
 
-            // Enable support for multistream gz files. In this mode, the reader expects the input to
-            // be a sequence of individually gzipped data streams, each with its own header and trailer,
-            // ending at EOF. This is standard behavior for gzip readers.
-            reader.multiple_members(true);
+            // the package index is decoded directly from the response based on which variant was served
+            let mut reader: Box<dyn AsyncRead + Unpin + Send + '_> =
+                match package_index_variant.compression {
+                    PackageIndexCompression::Gzip => {
+                        let mut decoder = GzipDecoder::new(compressed_reader);
+                        // Enable support for multistream gz files. In this mode, the reader expects the input to
+                        // be a sequence of individually gzipped data streams, each with its own header and trailer,
+                        // ending at EOF. This is standard behavior for gzip readers.
+                        decoder.multiple_members(true);
+                        Box::new(decoder)
+                    }
+                    PackageIndexCompression::Xz => Box::new(XzDecoder::new(compressed_reader)),
+                    PackageIndexCompression::Zstd => Box::new(ZstdDecoder::new(compressed_reader)),
+                    PackageIndexCompression::Uncompressed => Box::new(compressed_reader),
+                };
 
             let mut writer = AsyncFile::create(&package_index_path).await.map_err(|e| {
                 CreatePackageIndexError::WritePackagesLayer(package_index_path.clone(), e)
             })?;
 
-            async_copy(&mut reader, &mut writer).await.map_err(|e| {
-                CreatePackageIndexError::WritePackageIndexFromResponse(
-                    package_index_path.clone(),
-                    e,
-                )
-            })?;
+            // The decompressed package index is parsed while it's still downloading instead of
+            // reading it back from disk afterward so network and CPU work overlap. The parser
+            // blocks while it waits for the next chunk so it gets a thread of its own, holding one
+            // from the blocking pool would starve the file writes and DNS lookups feeding it.
+            let (chunk_sender, chunk_receiver) = mpsc::channel(PACKAGE_INDEX_CHUNKS_IN_FLIGHT);
+            let (parse_result_sender, parse_result) = channel();
+            {
+                let repository_uri = repository_uri.clone();
+                std::thread::spawn(move || {
+                    let _ = parse_result_sender.send(parse_packages(
+                        BufReader::new(ChannelReader::new(chunk_receiver)),
+                        &repository_uri,
+                        source_order,
+                        allow_weak_checksums,
+                    ));
+                });
+            }
 
-            writer.flush().await.map_err(|e| {
+            let write_error = |e| {
                 CreatePackageIndexError::WritePackageIndexFromResponse(
                     package_index_path.clone(),
                     e,
                 )
-            })?;
+            };
+            let mut buffer = vec![0; PACKAGE_INDEX_BUFFER_SIZE];
+            loop {
+                let bytes_read = reader.read(&mut buffer).await.map_err(write_error)?;
+                if bytes_read == 0 {
+                    break;
+                }
+                writer
+                    .write_all(&buffer[..bytes_read])
+                    .await
+                    .map_err(write_error)?;
+                // the parser only stops reading early if it's been dropped
+                let _ = chunk_sender.send(buffer[..bytes_read].to_vec()).await;
+            }
+            drop(chunk_sender);
+            // the reader borrows the hasher
+            drop(reader);
+
+            writer.flush().await.map_err(write_error)?;
 
             let calculated_hash = hex::encode(hasher.finalize());
 
-            if hash != calculated_hash {
+            if package_index_variant.hash != calculated_hash {
                 Err(CreatePackageIndexError::ChecksumFailed {
                     url: package_index_url.clone(),
-                    expected: hash,
+                    expected: package_index_variant.hash.clone(),
                     actual: calculated_hash,
                 })?;
             }
 
+            packages = Some(into_packages(
+                &package_index_path,
+                &package_index_url,
+                &arch,
+                parse_result
+                    .await
+                    .map_err(CreatePackageIndexError::CpuTaskFailed)?
+                    .map_err(|e| {
+                        CreatePackageIndexError::ReadPackagesFile(package_index_path.clone(), e)
+                    })?,
+            )?);
+
             match cause {
                 EmptyLayerCause::NewlyCreated => UpdatedSourceCacheState::New,
                 EmptyLayerCause::InvalidMetadataAction { .. } => {
@@ -495,29 +800,184 @@ async fn get_package_list(
                 }
                 EmptyLayerCause::RestoredLayerAction { .. } => {
                     UpdatedSourceCacheState::Invalidated(
-                        "Stored checksum did not match".to_string(),
+                        bypass_cache_reason
+                            .unwrap_or("Stored checksum did not match")
+                            .to_string(),
                     )
                 }
             }
         }
     };
 
+    *fetched_package_index = Some(package_index_path.clone());
+
     Ok(UpdatedPackageIndex {
         repository_uri,
+        arch,
         source_order,
+        allow_weak_checksums,
         package_index_path,
         package_index_url,
         cache_state,
+        packages,
     })
 }
 
+// Requests the preferred package index and, if the repository doesn't have it, each of the other
+// variants listed in the release file until one is found. Mirrors sometimes only publish some of
+// the compressed variants (e.g.; `Packages.xz` without `Packages.gz`) even though the release file
+// they copied lists all of them.
+async fn request_package_index<'a>(
+    client: &impl Fetcher,
+    repository_uri: &RepositoryUri,
+    suite: &str,
+    acquire_by_hash: bool,
+    preferred_package_index: &'a PackageIndexVariant,
+    fallback_package_indexes: &'a [PackageIndexVariant],
+) -> Result<(&'a PackageIndexVariant, reqwest::Response), CreatePackageIndexError> {
+    let mut fallback_package_indexes = fallback_package_indexes.iter();
+    let mut package_index_variant = preferred_package_index;
+    loop {
+        let package_index_url = package_index_variant.url(repository_uri, suite, acquire_by_hash);
+        match client
+            .fetch(&to_request_url(&package_index_url), HeaderMap::new())
+            .await
+            .and_then(|res| res.error_for_status().map_err(Reqwest))
+        {
+            Ok(response) => return Ok((package_index_variant, response)),
+            Err(e) if e.status() == Some(StatusCode::NOT_FOUND) => {
+                let Some(fallback_package_index) = fallback_package_indexes.next() else {
+                    return Err(CreatePackageIndexError::GetPackagesRequest(e));
+                };
+                info!(
+                    "package index {url} not found, trying {filename}",
+                    url = remove_url_credentials(&package_index_url),
+                    filename = fallback_package_index.filename()
+                );
+                package_index_variant = fallback_package_index;
+            }
+            Err(e) => return Err(CreatePackageIndexError::GetPackagesRequest(e)),
+        }
+    }
+}
+
+// Like apt, the `binary-all` index is only read when `all` is one of the release file's
+// `Architectures`. Repositories that also set `No-Support-for-Architecture-all: Packages` still list
+// the `Architecture: all` packages in the index of every architecture so apt ignores their
+// `binary-all` index, and so do we to avoid reading the same packages twice. This field is read
+// from the release file directly since `apt_parser` only recognizes a value of `yes` for it.
+fn has_architecture_all_index(release: &Release, release_data: &str) -> bool {
+    release
+        .architectures
+        .iter()
+        .any(|architecture| architecture == "all")
+        && !release_data.lines().any(|line| {
+            line.split_once(':').is_some_and(|(key, value)| {
+                key.eq_ignore_ascii_case("No-Support-for-Architecture-all")
+                    && value.trim() == "Packages"
+            })
+        })
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum PackageIndexCompression {
+    Gzip,
+    Xz,
+    Zstd,
+    Uncompressed,
+}
+
+impl PackageIndexCompression {
+    fn extension(self) -> &'static str {
+        match self {
+            PackageIndexCompression::Gzip => ".gz",
+            PackageIndexCompression::Xz => ".xz",
+            PackageIndexCompression::Zstd => ".zst",
+            PackageIndexCompression::Uncompressed => "",
+        }
+    }
+}
+
+// `Packages.gz` stays first since it's what every Debian and Ubuntu repository publishes, the
+// others are only requested when it's missing.
+const PACKAGE_INDEX_COMPRESSIONS: [PackageIndexCompression; 4] = [
+    PackageIndexCompression::Gzip,
+    PackageIndexCompression::Xz,
+    PackageIndexCompression::Zstd,
+    PackageIndexCompression::Uncompressed,
+];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct PackageIndexVariant {
+    // relative to the suite, e.g.; `main/binary-amd64`
+    directory: String,
+    compression: PackageIndexCompression,
+    hash: String,
+}
+
+impl PackageIndexVariant {
+    fn filename(&self) -> String {
+        format!(
+            "{}/Packages{}",
+            self.directory,
+            self.compression.extension()
+        )
+    }
+
+    fn url(&self, repository_uri: &RepositoryUri, suite: &str, acquire_by_hash: bool) -> String {
+        if acquire_by_hash {
+            format!(
+                "{repository_uri}/dists/{suite}/{}/by-hash/SHA256/{}",
+                self.directory, self.hash
+            )
+        } else {
+            format!("{repository_uri}/dists/{suite}/{}", self.filename())
+        }
+    }
+}
+
+// `directory` is relative to the suite, e.g.; `main/binary-amd64`
+fn get_package_index_variants(
+    release_hashes: &[ReleaseHash],
+    directory: &str,
+) -> Vec<PackageIndexVariant> {
+    PACKAGE_INDEX_COMPRESSIONS
+        .iter()
+        .filter_map(|compression| {
+            let filename = format!("{directory}/Packages{}", compression.extension());
+            release_hashes
+                .iter()
+                .find(|release_hash| release_hash.filename == filename)
+                .map(|release_hash| PackageIndexVariant {
+                    directory: directory.to_string(),
+                    compression: *compression,
+                    hash: release_hash.hash.clone(),
+                })
+        })
+        .collect()
+}
+
 #[instrument(skip_all)]
 async fn build_package_index(
     updated_sources: Vec<UpdatedPackageIndex>,
 ) -> BuildpackResult<PackageIndex> {
+    // package indices with identical contents are parsed once and the packages copied for every
+    // source they were referenced from
+    let mut shared_package_indexes: IndexMap<_, Vec<UpdatedPackageIndex>> = IndexMap::new();
+    for updated_source in updated_sources {
+        shared_package_indexes
+            .entry((
+                updated_source.package_index_path.clone(),
+                updated_source.arch.clone(),
+                updated_source.allow_weak_checksums,
+            ))
+            .or_default()
+            .push(updated_source);
+    }
+
     let mut get_packages_handles = JoinSet::new();
-    for update_source in updated_sources {
-        get_packages_handles.spawn(read_packages(update_source).in_current_span());
+    for updated_sources in shared_package_indexes.into_values() {
+        get_packages_handles.spawn(read_shared_packages(updated_sources).in_current_span());
     }
 
     let mut package_index = PackageIndex::default();
@@ -531,39 +991,92 @@ async fn build_package_index(
     Ok(package_index)
 }
 
+async fn read_shared_packages(
+    mut updated_sources: Vec<UpdatedPackageIndex>,
+) -> BuildpackResult<Vec<RepositoryPackage>> {
+    // packages parsed while the package index was downloading don't need to be read again
+    let parsed_index = updated_sources
+        .iter()
+        .position(|updated_source| updated_source.packages.is_some())
+        .unwrap_or_default();
+    let packages = read_packages(updated_sources.remove(parsed_index)).await?;
+    let mut shared_packages = Vec::with_capacity(packages.len() * (updated_sources.len() + 1));
+    for updated_source in updated_sources {
+        shared_packages.extend(packages.iter().map(|package| RepositoryPackage {
+            repository_uri: updated_source.repository_uri.clone(),
+            source_order: updated_source.source_order,
+            ..package.clone()
+        }));
+    }
+    shared_packages.extend(packages);
+    Ok(shared_packages)
+}
+
 // NOTE: Rayon is used here since this is a fairly CPU-intensive operation.
 //       See - https://ryhl.io/blog/async-what-is-blocking/
 #[instrument(skip_all)]
 async fn read_packages(
-    updated_source: UpdatedPackageIndex,
+    mut updated_source: UpdatedPackageIndex,
 ) -> BuildpackResult<Vec<RepositoryPackage>> {
-    let contents = async_read_to_string(&updated_source.package_index_path)
-        .await
-        .map_err(|e| {
-            CreatePackageIndexError::ReadPackagesFile(updated_source.package_index_path.clone(), e)
-        })?
-        .replace("\r\n", "\n")
-        .replace('\0', "");
-
+    if let Some(packages) = updated_source.packages.take() {
+        return Ok(packages);
+    }
+    let package_index_path = updated_source.package_index_path.clone();
     let (send, recv) = channel();
     rayon::spawn(move || {
-        let (errors, packages): (Vec<_>, Vec<_>) = contents
-            .trim()
-            .split("\n\n")
-            .filter(|entry| !entry.is_empty())
-            .par_bridge()
-            .into_par_iter()
-            .partition_map(|package_data| {
-                RepositoryPackage::parse_parallel(
-                    updated_source.repository_uri.clone(),
-                    updated_source.source_order,
-                    package_data,
-                )
-                .map_or_else(Either::Left, Either::Right)
-            });
-        let _ = send.send((packages, errors));
+        let result = File::open(&updated_source.package_index_path).and_then(|file| {
+            parse_packages(
+                BufReader::new(file),
+                &updated_source.repository_uri,
+                updated_source.source_order,
+                updated_source.allow_weak_checksums,
+            )
+        });
+        let _ = send.send(result);
     });
-    let (packages, errors) = recv.await.map_err(CreatePackageIndexError::CpuTaskFailed)?;
+    let parsed_packages = recv
+        .await
+        .map_err(CreatePackageIndexError::CpuTaskFailed)?
+        .map_err(|e| CreatePackageIndexError::ReadPackagesFile(package_index_path.clone(), e))?;
+    into_packages(
+        &package_index_path,
+        &updated_source.package_index_url,
+        &updated_source.arch,
+        parsed_packages,
+    )
+}
+
+type ParsedPackages = (Vec<RepositoryPackage>, Vec<ParseRepositoryPackageError>);
+
+fn parse_packages(
+    reader: impl BufRead,
+    repository_uri: &RepositoryUri,
+    source_order: SourceOrder,
+    allow_weak_checksums: bool,
+) -> std::io::Result<ParsedPackages> {
+    parse_package_entries(reader, |package_data| {
+        RepositoryPackage::parse_parallel(
+            repository_uri.clone(),
+            source_order,
+            package_data,
+            allow_weak_checksums,
+        )
+    })
+}
+
+fn into_packages(
+    package_index_path: &Path,
+    package_index_url: &str,
+    arch: &ArchitectureName,
+    (mut packages, errors): ParsedPackages,
+) -> BuildpackResult<Vec<RepositoryPackage>> {
+    // packages from a foreign architecture are qualified (e.g.; libc6:i386) so they don't collide
+    // with the native packages of the same name
+    if arch.is_foreign() {
+        for package in &mut packages {
+            package.qualify_architecture(arch);
+        }
+    }
     if errors.is_empty() {
         info!(
             { PACKAGE_LIST_SIZE } = packages.len(),
@@ -571,13 +1084,136 @@ async fn read_packages(
         );
         Ok(packages)
     } else {
-        Err(
-            CreatePackageIndexError::ParsePackages(updated_source.package_index_path, errors)
-                .into(),
-        )
+        Err(CreatePackageIndexError::ParsePackages {
+            url: remove_url_credentials(package_index_url),
+            path: package_index_path.to_path_buf(),
+            errors,
+        }
+        .into())
+    }
+}
+
+// Reads an uncompressed `Packages` file that's already on disk (e.g.; one downloaded by another
+// tool using the library API) instead of one fetched from a source.
+pub(crate) fn read_packages_file(
+    packages_file: &Path,
+    repository_uri: &RepositoryUri,
+    source_order: SourceOrder,
+) -> BuildpackResult<Vec<RepositoryPackage>> {
+    let (packages, errors) = File::open(packages_file)
+        .and_then(|file| parse_packages(BufReader::new(file), repository_uri, source_order, false))
+        .map_err(|e| CreatePackageIndexError::ReadPackagesFile(packages_file.to_path_buf(), e))?;
+    if errors.is_empty() {
+        Ok(packages)
+    } else {
+        Err(CreatePackageIndexError::ParsePackages {
+            url: remove_url_credentials(repository_uri.as_ref()),
+            path: packages_file.to_path_buf(),
+            errors,
+        }
+        .into())
+    }
+}
+
+// The number of decompressed chunks of a package index that can be waiting to be parsed before the
+// download is paused.
+const PACKAGE_INDEX_CHUNKS_IN_FLIGHT: usize = 64;
+const PACKAGE_INDEX_BUFFER_SIZE: usize = 64 * 1024;
+
+// Adapts the chunks of a package index sent while it's downloading into a blocking reader for the
+// parser. The reader ends once the sender is dropped.
+struct ChannelReader {
+    receiver: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl ChannelReader {
+    fn new(receiver: mpsc::Receiver<Vec<u8>>) -> Self {
+        Self {
+            receiver,
+            chunk: vec![],
+            position: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.chunk.len() {
+            match self.receiver.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let bytes_read = buf.len().min(self.chunk.len() - self.position);
+        buf[..bytes_read].copy_from_slice(&self.chunk[self.position..self.position + bytes_read]);
+        self.position += bytes_read;
+        Ok(bytes_read)
     }
 }
 
+// The approximate amount of package entries to hold in memory before they are parsed.
+const PACKAGE_ENTRIES_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+// Package indices for large repositories are hundreds of megabytes once decompressed so instead of
+// reading the whole file into memory, entries are read and parsed in chunks. Entries are separated
+// by blank lines and any carriage returns or null bytes are ignored.
+fn parse_package_entries<T, E>(
+    mut reader: impl BufRead,
+    parse: impl Fn(&str) -> Result<T, E> + Sync,
+) -> std::io::Result<(Vec<T>, Vec<E>)>
+where
+    T: Send,
+    E: Send,
+{
+    let mut values = vec![];
+    let mut errors = vec![];
+
+    let mut chunk: Vec<String> = vec![];
+    let mut chunk_size = 0;
+    let mut entry = String::new();
+    let mut line = String::new();
+
+    let mut parse_chunk = |chunk: &mut Vec<String>| {
+        let (chunk_errors, chunk_values): (Vec<_>, Vec<_>) = chunk
+            .par_drain(..)
+            .partition_map(|entry| parse(&entry).map_or_else(Either::Left, Either::Right));
+        values.extend(chunk_values);
+        errors.extend(chunk_errors);
+    };
+
+    loop {
+        line.clear();
+        let bytes_read = reader.read_line(&mut line)?;
+        line.retain(|c| c != '\r' && c != '\0');
+
+        if line.trim_end_matches('\n').is_empty() {
+            if entry.trim().is_empty() {
+                entry.clear();
+            } else {
+                chunk_size += entry.len();
+                chunk.push(std::mem::take(&mut entry));
+            }
+            if chunk_size >= PACKAGE_ENTRIES_CHUNK_SIZE || bytes_read == 0 {
+                parse_chunk(&mut chunk);
+                chunk_size = 0;
+            }
+        } else {
+            entry.push_str(&line);
+        }
+
+        if bytes_read == 0 {
+            break;
+        }
+    }
+
+    Ok((values, errors))
+}
+
 #[derive(Debug)]
 pub(crate) enum CreatePackageIndexError {
     NoSources,
@@ -602,7 +1238,11 @@ pub(crate) enum CreatePackageIndexError {
     },
     CpuTaskFailed(RecvError),
     ReadPackagesFile(PathBuf, std::io::Error),
-    ParsePackages(PathBuf, Vec<ParseRepositoryPackageError>),
+    ParsePackages {
+        url: String,
+        path: PathBuf,
+        errors: Vec<ParseRepositoryPackageError>,
+    },
 }
 
 impl From<CreatePackageIndexError> for libcnb::Error<DebianPackagesBuildpackError> {
@@ -611,6 +1251,18 @@ impl From<CreatePackageIndexError> for libcnb::Error<DebianPackagesBuildpackErro
     }
 }
 
+// Cached release files and package indices are ignored when a clean cache is requested or when only
+// the package indices need to be refreshed (e.g.; after an upstream repository was fixed in place).
+fn get_bypass_cache_reason() -> Option<&'static str> {
+    if is_clean_cache_requested() {
+        Some("Cache clean requested")
+    } else if is_refresh_index_requested() {
+        Some("Index refresh requested")
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 struct PackageIndexMetadata {
     hash: String,
@@ -619,18 +1271,60 @@ struct PackageIndexMetadata {
 #[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
 struct ReleaseFileMetadata {
     etag: Option<String>,
+    // seconds since the unix epoch when the release file was last checked, absent from older metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    checked_at: Option<u64>,
+}
+
+impl ReleaseFileMetadata {
+    fn is_checked_within(&self, max_age: Duration) -> bool {
+        self.checked_at.is_some_and(|checked_at| {
+            unix_timestamp().saturating_sub(checked_at) < max_age.as_secs()
+        })
+    }
+}
+
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+// Tracks where package indices were stored during this build by the SHA256 of their contents.
+#[derive(Debug, Default, Clone)]
+struct FetchedPackageIndexes(Arc<Mutex<HashMap<String, FetchedPackageIndex>>>);
+
+// the path of the package index once it's been fetched
+type FetchedPackageIndex = Arc<AsyncMutex<Option<PathBuf>>>;
+
+impl FetchedPackageIndexes {
+    fn get(&self, hash: &str) -> FetchedPackageIndex {
+        self.0
+            .lock()
+            .expect("Fetched package indexes lock should not be poisoned")
+            .entry(hash.to_string())
+            .or_default()
+            .clone()
+    }
 }
 
 #[derive(Debug)]
 struct UpdatedSource {
     release_file: UpdatedReleaseFile,
     package_indexes: Vec<UpdatedPackageIndex>,
+    // the release file is marked `NotAutomatic: yes` (e.g.; the `-backports` suites)
+    not_automatic: bool,
 }
 
 #[derive(Debug)]
 enum UpdatedSourceCacheState {
     Cached,
+    // restored without checking the repository since it's within the configured index max age
+    Fresh,
     New,
+    // the contents were already fetched for another package index during this build
+    Shared,
     Invalidated(String),
 }
 
@@ -644,20 +1338,256 @@ struct UpdatedReleaseFile {
 #[derive(Debug)]
 struct UpdatedPackageIndex {
     repository_uri: RepositoryUri,
+    arch: ArchitectureName,
     source_order: SourceOrder,
+    allow_weak_checksums: bool,
     package_index_path: PathBuf,
     package_index_url: String,
     cache_state: UpdatedSourceCacheState,
+    packages: Option<Vec<RepositoryPackage>>,
 }
 
 impl Display for UpdatedSourceCacheState {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             UpdatedSourceCacheState::Cached => write!(f, "cached"),
+            UpdatedSourceCacheState::Fresh => write!(f, "fresh"),
             UpdatedSourceCacheState::New => write!(f, "new"),
+            UpdatedSourceCacheState::Shared => write!(f, "shared"),
             UpdatedSourceCacheState::Invalidated(reason) => {
                 write!(f, "updated {}", style::details(reason))
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use std::io::Cursor;
+
+    #[test]
+    fn parse_package_entries_splits_on_blank_lines() {
+        let contents =
+            "\r\nPackage: a\r\nVersion: 1\r\n\r\nPackage: b\n Description\0\n\n\n\nPackage: c";
+        let (values, errors): (Vec<String>, Vec<()>) =
+            parse_package_entries(Cursor::new(contents), |entry| Ok(entry.to_string())).unwrap();
+        assert_eq!(
+            values,
+            vec![
+                "Package: a\nVersion: 1\n",
+                "Package: b\n Description\n",
+                "Package: c"
+            ]
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn parse_package_entries_in_multiple_chunks() {
+        let entry = format!("Package: {}\n\n", "a".repeat(1024));
+        let contents = entry.repeat(PACKAGE_ENTRIES_CHUNK_SIZE / 1024 + 10);
+        let (values, errors): (Vec<()>, Vec<usize>) =
+            parse_package_entries(Cursor::new(contents), |entry| Err(entry.len())).unwrap();
+        assert!(values.is_empty());
+        assert_eq!(errors.len(), PACKAGE_ENTRIES_CHUNK_SIZE / 1024 + 10);
+    }
+
+    #[test]
+    fn parse_package_entries_from_channel_chunks() {
+        let (sender, receiver) = mpsc::channel(8);
+        for chunk in [
+            "Package: a\nVer",
+            "sion: 1\n",
+            "\nPackage: b\n\n",
+            "",
+            "Package: c\n",
+        ] {
+            sender.try_send(chunk.as_bytes().to_vec()).unwrap();
+        }
+        drop(sender);
+        let (values, errors): (Vec<String>, Vec<()>) =
+            parse_package_entries(BufReader::new(ChannelReader::new(receiver)), |entry| {
+                Ok(entry.to_string())
+            })
+            .unwrap();
+        assert_eq!(
+            values,
+            vec!["Package: a\nVersion: 1\n", "Package: b\n", "Package: c\n"]
+        );
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn filter_pinned_certs_only_keeps_pinned_fingerprints() {
+        let generate_cert = || {
+            sequoia_openpgp::cert::CertBuilder::general_purpose(Some("test@example.com"))
+                .generate()
+                .unwrap()
+                .0
+        };
+        let pinned_cert = generate_cert();
+        let other_cert = generate_cert();
+
+        let filtered_certs = filter_pinned_certs(
+            vec![pinned_cert.clone(), other_cert],
+            &[pinned_cert.fingerprint().to_hex().to_lowercase()],
+        );
+        assert_eq!(filtered_certs, vec![pinned_cert]);
+    }
+
+    #[test]
+    fn package_index_variants_prefer_gzip_and_skip_unlisted() {
+        let release_hash = |filename: &str, hash: &str| ReleaseHash {
+            filename: filename.to_string(),
+            hash: hash.to_string(),
+            size: 0,
+        };
+        let release_hashes = vec![
+            release_hash("main/binary-amd64/Packages", "plain"),
+            release_hash("main/binary-amd64/Packages.xz", "xz"),
+            release_hash("main/binary-arm64/Packages.gz", "other-arch"),
+            release_hash("main/binary-amd64/Packages.gz", "gzip"),
+            release_hash("universe/binary-amd64/Packages.zst", "other-component"),
+        ];
+        let variants = get_package_index_variants(&release_hashes, "main/binary-amd64");
+        assert_eq!(
+            variants
+                .iter()
+                .map(|variant| (variant.filename(), variant.hash.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("main/binary-amd64/Packages.gz".to_string(), "gzip"),
+                ("main/binary-amd64/Packages.xz".to_string(), "xz"),
+                ("main/binary-amd64/Packages".to_string(), "plain"),
+            ]
+        );
+
+        let repository_uri = RepositoryUri::from("http://archive.ubuntu.com/ubuntu");
+        assert_eq!(
+            variants[1].url(&repository_uri, "noble", false),
+            "http://archive.ubuntu.com/ubuntu/dists/noble/main/binary-amd64/Packages.xz"
+        );
+        assert_eq!(
+            variants[1].url(&repository_uri, "noble", true),
+            "http://archive.ubuntu.com/ubuntu/dists/noble/main/binary-amd64/by-hash/SHA256/xz"
+        );
+    }
+
+    // serves the given urls and responds with a 404 to anything else
+    #[derive(Clone, Default)]
+    struct StubFetcher {
+        responses: Arc<HashMap<String, &'static str>>,
+        requested_urls: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Fetcher for StubFetcher {
+        async fn fetch(
+            &self,
+            url: &str,
+            _headers: HeaderMap,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            self.requested_urls
+                .lock()
+                .expect("Requested urls lock should not be poisoned")
+                .push(url.to_string());
+            let response = match self.responses.get(url) {
+                Some(body) => http::Response::builder().status(StatusCode::OK).body(*body),
+                None => http::Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(""),
+            };
+            Ok(reqwest::Response::from(
+                response.expect("Response should be valid"),
+            ))
+        }
+    }
+
+    #[test]
+    fn request_package_index_falls_back_when_not_found() {
+        let fetcher = StubFetcher {
+            responses: Arc::new(HashMap::from([(
+                "http://archive.ubuntu.com/ubuntu/dists/noble/main/binary-amd64/Packages.xz"
+                    .to_string(),
+                "Package: curl\n",
+            )])),
+            ..StubFetcher::default()
+        };
+        let variant = |compression| PackageIndexVariant {
+            directory: "main/binary-amd64".to_string(),
+            compression,
+            hash: String::new(),
+        };
+        let preferred_package_index = variant(PackageIndexCompression::Gzip);
+        let fallback_package_indexes = [
+            variant(PackageIndexCompression::Xz),
+            variant(PackageIndexCompression::Uncompressed),
+        ];
+
+        let (package_index_variant, response) = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(request_package_index(
+                &fetcher,
+                &RepositoryUri::from("http://archive.ubuntu.com/ubuntu"),
+                "noble",
+                false,
+                &preferred_package_index,
+                &fallback_package_indexes,
+            ))
+            .unwrap();
+
+        assert_eq!(
+            package_index_variant.compression,
+            PackageIndexCompression::Xz
+        );
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            *fetcher.requested_urls.lock().unwrap(),
+            vec![
+                "http://archive.ubuntu.com/ubuntu/dists/noble/main/binary-amd64/Packages.gz",
+                "http://archive.ubuntu.com/ubuntu/dists/noble/main/binary-amd64/Packages.xz",
+            ]
+        );
+    }
+
+    #[test]
+    fn architecture_all_index_unless_release_file_opts_out() {
+        for (release_data, expected) in [
+            (
+                indoc! {"
+                    Origin: Example
+                    Architectures: amd64 all
+                    Components: main
+                "},
+                true,
+            ),
+            (
+                indoc! {"
+                    Origin: Ubuntu
+                    Architectures: amd64 arm64 armhf i386 ppc64el riscv64 s390x
+                    Components: main restricted universe multiverse
+                "},
+                false,
+            ),
+            (
+                indoc! {"
+                    Origin: Debian
+                    Architectures: all amd64 arm64
+                    No-Support-for-Architecture-all: Packages
+                    Components: main contrib
+                "},
+                false,
+            ),
+        ] {
+            let release = Release::from(release_data).unwrap();
+            assert_eq!(
+                has_architecture_all_index(&release, release_data),
+                expected,
+                "{release_data}"
+            );
+        }
+    }
+}
@@ -3,12 +3,40 @@ use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Eq)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, Eq, Hash)]
 #[allow(non_camel_case_types)]
 // https://wiki.debian.org/Multiarch/Tuples
 pub(crate) enum ArchitectureName {
     AMD_64,
     ARM_64,
+    // only supported as a foreign architecture installed alongside amd64 packages
+    I_386,
+}
+
+impl ArchitectureName {
+    // https://wiki.debian.org/Multiarch/HOWTO
+    pub(crate) fn get_foreign_architectures(&self) -> Vec<ArchitectureName> {
+        match self {
+            ArchitectureName::AMD_64 => vec![ArchitectureName::I_386],
+            ArchitectureName::ARM_64 | ArchitectureName::I_386 => vec![],
+        }
+    }
+
+    pub(crate) fn is_foreign(&self) -> bool {
+        matches!(self, ArchitectureName::I_386)
+    }
+
+    // NOTE: Packages for a foreign architecture are named `<name>:<arch>` (e.g.; libc6:i386), the
+    //       same way dpkg refers to them, so they can be told apart from the native packages.
+    pub(crate) fn qualify_package_name(&self, package_name: &str) -> String {
+        format!("{package_name}:{self}")
+    }
+
+    pub(crate) fn from_qualified_package_name(package_name: &str) -> Option<ArchitectureName> {
+        package_name
+            .rsplit_once(':')
+            .and_then(|(_, arch)| ArchitectureName::from_str(arch).ok())
+    }
 }
 
 impl FromStr for ArchitectureName {
@@ -18,6 +46,7 @@ impl FromStr for ArchitectureName {
         match value {
             "amd64" => Ok(ArchitectureName::AMD_64),
             "arm64" => Ok(ArchitectureName::ARM_64),
+            "i386" => Ok(ArchitectureName::I_386),
             _ => Err(UnsupportedArchitectureNameError(value.to_string())),
         }
     }
@@ -28,6 +57,7 @@ impl Display for ArchitectureName {
         match self {
             ArchitectureName::AMD_64 => write!(f, "amd64"),
             ArchitectureName::ARM_64 => write!(f, "arm64"),
+            ArchitectureName::I_386 => write!(f, "i386"),
         }
     }
 }
@@ -42,6 +72,7 @@ impl Display for UnsupportedArchitectureNameError {
             Must be one of:
             - \"amd64\"
             - \"arm64\"
+            - \"i386\"
         ", invalid_name = &self.0 };
         write!(f, "{error}")
     }
@@ -70,5 +101,22 @@ mod tests {
     fn display_architecture_name() {
         assert_eq!(ArchitectureName::AMD_64.to_string(), "amd64");
         assert_eq!(ArchitectureName::ARM_64.to_string(), "arm64");
+        assert_eq!(ArchitectureName::I_386.to_string(), "i386");
+    }
+
+    #[test]
+    fn qualified_package_names() {
+        assert_eq!(
+            ArchitectureName::I_386.qualify_package_name("libstdc++6"),
+            "libstdc++6:i386"
+        );
+        assert_eq!(
+            ArchitectureName::from_qualified_package_name("libstdc++6:i386"),
+            Some(ArchitectureName::I_386)
+        );
+        assert_eq!(
+            ArchitectureName::from_qualified_package_name("libstdc++6"),
+            None
+        );
     }
 }
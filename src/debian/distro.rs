@@ -24,6 +24,13 @@ impl Distro {
         source_list
             .into_iter()
             .filter(|source| source.arch == self.architecture)
+            .map(|mut source| {
+                source.keyserver_fingerprints = UBUNTU_ARCHIVE_KEY_FINGERPRINTS
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect();
+                source
+            })
             .collect()
     }
 }
@@ -36,8 +43,11 @@ impl TryFrom<&Target> for Distro {
         let version = target.distro_version.clone();
         let target_arch = target.arch.clone();
 
-        let architecture =
-            ArchitectureName::from_str(&target_arch).map_err(|_| UnsupportedDistroError {
+        // foreign architectures can only be installed alongside a supported native one
+        let architecture = ArchitectureName::from_str(&target_arch)
+            .ok()
+            .filter(|architecture| !architecture.is_foreign())
+            .ok_or_else(|| UnsupportedDistroError {
                 name: name.clone(),
                 version: version.clone(),
                 architecture: target_arch.clone(),
@@ -71,6 +81,15 @@ impl TryFrom<&Target> for Distro {
     }
 }
 
+// The primary keys of the Ubuntu archive signing keys found in the ./keys directory. If the release
+// file of a default source fails to verify (e.g.; Canonical extended the expiry of a key or added a
+// new signing subkey), the current versions of these keys are fetched from the Ubuntu keyserver
+// and only certificates matching these fingerprints are trusted.
+const UBUNTU_ARCHIVE_KEY_FINGERPRINTS: [&str; 2] = [
+    "F6ECB3762474EDA9D21B7022871920D1991BC93C",
+    "790BC7277767219C42C86F933B4FE6ACC0B21F32",
+];
+
 // NOTE: Regarding http versus https for the repository urls that follow - these sources are extracted
 //       from the default sources configured on these distributions which do not use https. This is
 //       a trade-off between performance and privacy.
@@ -80,6 +99,9 @@ impl TryFrom<&Target> for Distro {
 //
 //       See: https://wiki.debian.org/SecureApt
 //
+//       Builds on networks that block plain http can switch these sources to https with the
+//       `prefer_https` option.
+//
 //       The corresponding certificates used to validate the PGP signatures can be regenerated by
 //       running <project-root>/scripts/extract_keys.sh.
 
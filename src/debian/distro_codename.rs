@@ -8,6 +8,17 @@ pub(crate) enum DistroCodename {
     Resolute,
 }
 
+impl DistroCodename {
+    // the version of the `libc6` package each release ships
+    pub(crate) fn glibc_version(&self) -> &'static str {
+        match self {
+            DistroCodename::Jammy => "2.35",
+            DistroCodename::Noble => "2.39",
+            DistroCodename::Resolute => "2.43",
+        }
+    }
+}
+
 impl Display for DistroCodename {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -1,7 +1,9 @@
 pub(crate) use architecture_name::*;
 pub(crate) use distro::*;
 pub(crate) use distro_codename::*;
+pub(crate) use multi_arch::*;
 pub(crate) use multiarch_name::*;
+pub(crate) use package_checksum::*;
 pub(crate) use package_index::*;
 pub(crate) use package_name::*;
 pub(crate) use repository_package::*;
@@ -12,7 +14,9 @@ pub(crate) use source_order::*;
 mod architecture_name;
 mod distro;
 mod distro_codename;
+mod multi_arch;
 mod multiarch_name;
+mod package_checksum;
 mod package_index;
 mod package_name;
 mod repository_package;
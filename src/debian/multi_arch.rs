@@ -0,0 +1,61 @@
+use std::str::FromStr;
+
+// NOTE: The Multi-Arch field describes how a package interacts with packages from other
+//       architectures. Packages without the field (or with an unrecognized value) can only
+//       satisfy dependencies from packages of their own architecture.
+//
+//       See https://wiki.ubuntu.com/MultiarchSpec#Extended_semantics_of_per-architecture_package_relationships
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub(crate) enum MultiArch {
+    #[default]
+    No,
+    // can be installed alongside the same package from another architecture
+    Same,
+    // can satisfy the dependencies of packages from any architecture
+    Foreign,
+    // can satisfy `:any` dependencies of packages from any architecture
+    Allowed,
+}
+
+impl MultiArch {
+    // whether a dependency qualified with `:any` (e.g.; python3:any) can be satisfied by the package
+    pub(crate) fn satisfies_any(self) -> bool {
+        matches!(self, MultiArch::Foreign | MultiArch::Allowed)
+    }
+}
+
+impl FromStr for MultiArch {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "same" => Ok(MultiArch::Same),
+            "foreign" => Ok(MultiArch::Foreign),
+            "allowed" => Ok(MultiArch::Allowed),
+            "no" => Ok(MultiArch::No),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_multi_arch() {
+        assert_eq!(MultiArch::from_str("same"), Ok(MultiArch::Same));
+        assert_eq!(MultiArch::from_str("foreign"), Ok(MultiArch::Foreign));
+        assert_eq!(MultiArch::from_str("allowed"), Ok(MultiArch::Allowed));
+        assert_eq!(MultiArch::from_str("no"), Ok(MultiArch::No));
+        assert_eq!(MultiArch::from_str("unknown"), Err(()));
+    }
+
+    #[test]
+    fn satisfies_any() {
+        assert!(!MultiArch::No.satisfies_any());
+        assert!(!MultiArch::Same.satisfies_any());
+        assert!(MultiArch::Foreign.satisfies_any());
+        assert!(MultiArch::Allowed.satisfies_any());
+    }
+}
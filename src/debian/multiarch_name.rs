@@ -7,6 +7,7 @@ use std::fmt::{Display, Formatter};
 pub(crate) enum MultiarchName {
     X86_64_LINUX_GNU,
     AARCH_64_LINUX_GNU,
+    I_386_LINUX_GNU,
 }
 
 impl From<&ArchitectureName> for MultiarchName {
@@ -14,6 +15,7 @@ impl From<&ArchitectureName> for MultiarchName {
         match value {
             ArchitectureName::AMD_64 => MultiarchName::X86_64_LINUX_GNU,
             ArchitectureName::ARM_64 => MultiarchName::AARCH_64_LINUX_GNU,
+            ArchitectureName::I_386 => MultiarchName::I_386_LINUX_GNU,
         }
     }
 }
@@ -23,6 +25,7 @@ impl Display for MultiarchName {
         match self {
             MultiarchName::X86_64_LINUX_GNU => write!(f, "x86_64-linux-gnu"),
             MultiarchName::AARCH_64_LINUX_GNU => write!(f, "aarch64-linux-gnu"),
+            MultiarchName::I_386_LINUX_GNU => write!(f, "i386-linux-gnu"),
         }
     }
 }
@@ -41,6 +44,10 @@ mod tests {
             MultiarchName::from(&ArchitectureName::ARM_64),
             MultiarchName::AARCH_64_LINUX_GNU
         );
+        assert_eq!(
+            MultiarchName::from(&ArchitectureName::I_386),
+            MultiarchName::I_386_LINUX_GNU
+        );
     }
 
     #[test]
@@ -53,5 +60,6 @@ mod tests {
             MultiarchName::AARCH_64_LINUX_GNU.to_string(),
             "aarch64-linux-gnu"
         );
+        assert_eq!(MultiarchName::I_386_LINUX_GNU.to_string(), "i386-linux-gnu");
     }
 }
@@ -0,0 +1,105 @@
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fmt::{Display, Formatter};
+
+// NOTE: Package index entries list the digest of each package with the `SHA256` field. The weaker
+//       `SHA1` and `MD5sum` digests are only used when a custom source explicitly allows them since
+//       some older repositories never started publishing `SHA256`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub(crate) enum PackageChecksum {
+    Sha256(String),
+    Sha1(String),
+    Md5(String),
+}
+
+impl PackageChecksum {
+    pub(crate) fn value(&self) -> &str {
+        match self {
+            PackageChecksum::Sha256(value)
+            | PackageChecksum::Sha1(value)
+            | PackageChecksum::Md5(value) => value,
+        }
+    }
+
+    pub(crate) fn is_weak(&self) -> bool {
+        !matches!(self, PackageChecksum::Sha256(_))
+    }
+
+    pub(crate) fn hasher(&self) -> PackageHasher {
+        match self {
+            PackageChecksum::Sha256(_) => PackageHasher::Sha256(Sha256::new()),
+            PackageChecksum::Sha1(_) => PackageHasher::Sha1(Sha1::new()),
+            PackageChecksum::Md5(_) => PackageHasher::Md5(Md5::new()),
+        }
+    }
+}
+
+// the name of the field the checksum was read from
+impl Display for PackageChecksum {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PackageChecksum::Sha256(_) => write!(f, "SHA256"),
+            PackageChecksum::Sha1(_) => write!(f, "SHA1"),
+            PackageChecksum::Md5(_) => write!(f, "MD5sum"),
+        }
+    }
+}
+
+pub(crate) enum PackageHasher {
+    Sha256(Sha256),
+    Sha1(Sha1),
+    Md5(Md5),
+}
+
+impl PackageHasher {
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        match self {
+            PackageHasher::Sha256(hasher) => hasher.update(bytes),
+            PackageHasher::Sha1(hasher) => hasher.update(bytes),
+            PackageHasher::Md5(hasher) => hasher.update(bytes),
+        }
+    }
+
+    pub(crate) fn finalize(self) -> String {
+        match self {
+            PackageHasher::Sha256(hasher) => hex::encode(hasher.finalize()),
+            PackageHasher::Sha1(hasher) => hex::encode(hasher.finalize()),
+            PackageHasher::Md5(hasher) => hex::encode(hasher.finalize()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hasher_matches_checksum_algorithm() {
+        for (checksum, expected) in [
+            (
+                PackageChecksum::Sha256(String::new()),
+                "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+            ),
+            (
+                PackageChecksum::Sha1(String::new()),
+                "aaf4c61ddcc5e8a2dabede0f3b482cd9aea9434d",
+            ),
+            (
+                PackageChecksum::Md5(String::new()),
+                "5d41402abc4b2a76b9719d911017c592",
+            ),
+        ] {
+            let mut hasher = checksum.hasher();
+            hasher.update(b"hello");
+            assert_eq!(hasher.finalize(), expected, "{checksum}");
+        }
+    }
+
+    #[test]
+    fn weak_checksums() {
+        assert!(!PackageChecksum::Sha256(String::new()).is_weak());
+        assert!(PackageChecksum::Sha1(String::new()).is_weak());
+        assert!(PackageChecksum::Md5(String::new()).is_weak());
+    }
+}
@@ -1,10 +1,14 @@
 use crate::debian::{RepositoryPackage, SourceOrder};
 use indexmap::{IndexMap, IndexSet};
 use std::cmp::Ordering;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 struct PackageResolutionKey {
+    not_automatic: bool,
+    held_back: bool,
     version: debversion::Version,
     source_order: SourceOrder,
 }
@@ -12,6 +16,8 @@ struct PackageResolutionKey {
 impl PackageResolutionKey {
     fn new(version: debversion::Version, source_order: SourceOrder) -> Self {
         Self {
+            not_automatic: false,
+            held_back: false,
             version,
             source_order,
         }
@@ -20,10 +26,12 @@ impl PackageResolutionKey {
 
 impl Ord for PackageResolutionKey {
     fn cmp(&self, other: &Self) -> Ordering {
-        // Higher version first, then lower source order first (first-declared wins)
-        other
-            .version
-            .cmp(&self.version)
+        // Versions from suites that aren't marked `NotAutomatic` first, then versions that aren't
+        // held back, then higher version first, then lower source order first (first-declared wins)
+        self.not_automatic
+            .cmp(&other.not_automatic)
+            .then(self.held_back.cmp(&other.held_back))
+            .then(other.version.cmp(&self.version))
             .then(self.source_order.cmp(&other.source_order))
     }
 }
@@ -34,13 +42,16 @@ impl PartialOrd for PackageResolutionKey {
     }
 }
 
+// NOTE: Packages are shared between the name and virtual package lookups, and with the resolver,
+//       since indexing a full Ubuntu archive adds around 70k packages and many of them are listed
+//       more than once through their `Provides` field.
 #[derive(Debug, Default)]
 pub(crate) struct PackageIndex {
     name_to_repository_packages:
-        IndexMap<String, BTreeMap<PackageResolutionKey, RepositoryPackage>>,
+        IndexMap<String, BTreeMap<PackageResolutionKey, Arc<RepositoryPackage>>>,
     // NOTE: virtual packages are declared in the `Provides` field of a package
     //       https://www.debian.org/doc/debian-policy/ch-relationships.html#virtual-packages-provides
-    virtual_package_to_implementing_packages: IndexMap<String, Vec<RepositoryPackage>>,
+    virtual_package_to_implementing_packages: IndexMap<String, Vec<Arc<RepositoryPackage>>>,
     pub(crate) packages_indexed: usize,
 }
 
@@ -48,14 +59,60 @@ impl PackageIndex {
     pub(crate) fn get_highest_available_version(
         &self,
         package_name: &str,
-    ) -> Option<&RepositoryPackage> {
+    ) -> Option<&Arc<RepositoryPackage>> {
         self.name_to_repository_packages
             .get(package_name)
             .and_then(|entries| entries.first_key_value())
             .map(|(_, pkg)| pkg)
     }
 
+    pub(crate) fn get_version(
+        &self,
+        package_name: &str,
+        version: &str,
+    ) -> Option<&Arc<RepositoryPackage>> {
+        // versions are compared after parsing so an implied epoch (e.g.; `0:1.0` and `1.0`)
+        // still matches
+        let version = debversion::Version::from_str(version).ok()?;
+        self.name_to_repository_packages
+            .get(package_name)
+            .and_then(|entries| entries.iter().find(|(key, _)| key.version == version))
+            .map(|(_, pkg)| pkg)
+    }
+
+    // every entry for a package in the order the resolver considers them, the version that would
+    // be installed first
+    pub(crate) fn get_packages(&self, package_name: &str) -> Vec<&Arc<RepositoryPackage>> {
+        self.name_to_repository_packages
+            .get(package_name)
+            .into_iter()
+            .flat_map(BTreeMap::values)
+            .collect()
+    }
+
+    // Finds a package or virtual package whose name only differs by case. Package names in the
+    // index are lowercase so this is only used when a package isn't found by its exact name.
+    pub(crate) fn find_package_name_ignoring_case(&self, package_name: &str) -> Option<&str> {
+        self.name_to_repository_packages
+            .keys()
+            .chain(self.virtual_package_to_implementing_packages.keys())
+            .find(|name| name.eq_ignore_ascii_case(package_name))
+            .map(String::as_str)
+    }
+
+    // every version of a package in the index, highest first
+    pub(crate) fn get_available_versions(&self, package_name: &str) -> Vec<String> {
+        self.name_to_repository_packages
+            .get(package_name)
+            .into_iter()
+            .flat_map(|entries| entries.values().map(|pkg| pkg.version.to_string()))
+            .collect::<IndexSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
     pub(crate) fn add_package(&mut self, package: RepositoryPackage) {
+        let package = Arc::new(package);
         for provides in package.provides_dependencies() {
             self.virtual_package_to_implementing_packages
                 .entry(provides.to_string())
@@ -75,6 +132,57 @@ impl PackageIndex {
         self.packages_indexed += 1;
     }
 
+    // NOTE: Suites with a release file marked `NotAutomatic: yes` (e.g.; `-backports`) are given a
+    //       lower priority by apt so their packages are only installed when requested. Versions
+    //       from these package indices are likewise only used when no other version of the package
+    //       is available, or when the version is pinned or locked.
+    pub(crate) fn deprioritize_not_automatic(&mut self, source_orders: &HashSet<SourceOrder>) {
+        for entries in self.name_to_repository_packages.values_mut() {
+            *entries = std::mem::take(entries)
+                .into_iter()
+                .map(|(key, package)| {
+                    let key = PackageResolutionKey {
+                        not_automatic: source_orders.contains(&package.source_order),
+                        ..key
+                    };
+                    (key, package)
+                })
+                .collect();
+        }
+    }
+
+    // NOTE: Versions that are still being phased in are only used when no other version of the
+    //       package is available, the same way apt holds them back on machines that aren't
+    //       included in the rollout yet. Returns the number of packages where a different version
+    //       is now used.
+    pub(crate) fn hold_back_phased_updates(&mut self) -> usize {
+        let mut packages_held_back = 0;
+        for entries in self.name_to_repository_packages.values_mut() {
+            let highest_version = entries.first_key_value().map(|(key, _)| key.clone());
+            *entries = std::mem::take(entries)
+                .into_iter()
+                .map(|(key, package)| {
+                    let key = PackageResolutionKey {
+                        held_back: package.is_phasing(),
+                        ..key
+                    };
+                    (key, package)
+                })
+                .collect();
+            let highest_version_held_back = entries
+                .first_key_value()
+                .zip(highest_version)
+                .is_some_and(|((key, _), highest_version)| {
+                    key.version != highest_version.version
+                        || key.source_order != highest_version.source_order
+                });
+            if highest_version_held_back {
+                packages_held_back += 1;
+            }
+        }
+        packages_held_back
+    }
+
     pub(crate) fn get_providers(&self, package: &str) -> IndexSet<&str> {
         self.virtual_package_to_implementing_packages
             .get(package)
@@ -101,11 +209,53 @@ impl PackageIndex {
         package_names.extend(virtual_package_names.iter());
         package_names
     }
+
+    // NOTE: Matching is a case-insensitive substring match against the package name, the
+    //       virtual packages it provides, and its description. Only the highest available
+    //       version of each package is considered and results are ordered so that exact
+    //       name matches come first, followed by partial name matches, then everything else.
+    pub(crate) fn search(&self, pattern: &str) -> Vec<&RepositoryPackage> {
+        let pattern = pattern.to_lowercase();
+        let mut results = self
+            .name_to_repository_packages
+            .keys()
+            .filter_map(|name| self.get_highest_available_version(name))
+            .map(AsRef::as_ref)
+            .filter_map(|package| search_rank(package, &pattern).map(|rank| (rank, package)))
+            .collect::<Vec<_>>();
+        results.sort_by(|(rank_a, package_a), (rank_b, package_b)| {
+            rank_a.cmp(rank_b).then(package_a.name.cmp(&package_b.name))
+        });
+        results.into_iter().map(|(_, package)| package).collect()
+    }
+}
+
+fn search_rank(package: &RepositoryPackage, pattern: &str) -> Option<u8> {
+    let name = package.name.to_lowercase();
+    if name == pattern {
+        Some(0)
+    } else if name.contains(pattern) {
+        Some(1)
+    } else if package
+        .provides_dependencies()
+        .iter()
+        .any(|provides| provides.to_lowercase().contains(pattern))
+    {
+        Some(2)
+    } else if package
+        .description
+        .as_ref()
+        .is_some_and(|description| description.to_lowercase().contains(pattern))
+    {
+        Some(3)
+    } else {
+        None
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::debian::RepositoryUri;
+    use crate::debian::{MultiArch, PackageChecksum, RepositoryUri};
 
     use super::*;
 
@@ -116,10 +266,18 @@ mod test {
             name: "test-name".to_string(),
             version: "1.0.0".parse().unwrap(),
             filename: "test-filename".to_string(),
-            sha256sum: "test-sha256sum".to_string(),
+            checksum: PackageChecksum::Sha256("test-sha256sum".to_string()),
             depends: None,
             pre_depends: None,
             provides: None,
+            description: None,
+            source: None,
+            architecture: None,
+            multi_arch: MultiArch::No,
+            phased_update_percentage: None,
+            essential: false,
+            priority_required: false,
+            size: None,
         }
     }
 
@@ -159,11 +317,158 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_get_packages_in_resolution_order() {
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(create_repository_package_with_source_order(
+            "package-a",
+            "1.0.0",
+            "repository-1",
+            SourceOrder::new(0, 0, 0),
+        ));
+        package_index.add_package(create_repository_package_with_source_order(
+            "package-a",
+            "1.0.1",
+            "repository-1",
+            SourceOrder::new(0, 1, 0),
+        ));
+        package_index.add_package(create_repository_package_with_source_order(
+            "package-a",
+            "1.0.1",
+            "repository-2",
+            SourceOrder::new(1, 0, 0),
+        ));
+        assert_eq!(
+            package_index
+                .get_packages("package-a")
+                .iter()
+                .map(|package| (package.version.to_string(), package.source_order))
+                .collect::<Vec<_>>(),
+            vec![
+                ("1.0.1".to_string(), SourceOrder::new(0, 1, 0)),
+                ("1.0.1".to_string(), SourceOrder::new(1, 0, 0)),
+                ("1.0.0".to_string(), SourceOrder::new(0, 0, 0)),
+            ]
+        );
+        assert!(package_index.get_packages("package-b").is_empty());
+    }
+
+    #[test]
+    fn test_deprioritize_not_automatic() {
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(create_repository_package_with_source_order(
+            "cmake",
+            "3.28.3-1build7",
+            "archive",
+            SourceOrder::new(0, 0, 0),
+        ));
+        package_index.add_package(create_repository_package_with_source_order(
+            "cmake",
+            "3.30.3-1~bpo24.04.1",
+            "archive",
+            SourceOrder::new(0, 1, 0),
+        ));
+        package_index.add_package(create_repository_package_with_source_order(
+            "backports-only",
+            "1.0.0",
+            "archive",
+            SourceOrder::new(0, 1, 0),
+        ));
+        package_index.deprioritize_not_automatic(&HashSet::from([SourceOrder::new(0, 1, 0)]));
+
+        assert_eq!(
+            package_index
+                .get_highest_available_version("cmake")
+                .unwrap()
+                .version
+                .to_string(),
+            "3.28.3-1build7"
+        );
+        // still available when pinned or when it's the only version
+        assert_eq!(
+            package_index
+                .get_version("cmake", "3.30.3-1~bpo24.04.1")
+                .unwrap()
+                .source_order,
+            SourceOrder::new(0, 1, 0)
+        );
+        assert_eq!(
+            package_index
+                .get_highest_available_version("backports-only")
+                .unwrap()
+                .version
+                .to_string(),
+            "1.0.0"
+        );
+    }
+
+    #[test]
+    fn test_hold_back_phased_updates() {
+        let mut package_index = PackageIndex::default();
+        let released = create_repository_package("package-a", "1.0.0");
+        let phasing = RepositoryPackage {
+            phased_update_percentage: Some(10),
+            essential: false,
+            priority_required: false,
+            ..create_repository_package("package-a", "1.0.1")
+        };
+        let phasing_without_alternative = RepositoryPackage {
+            phased_update_percentage: Some(10),
+            essential: false,
+            priority_required: false,
+            ..create_repository_package("package-b", "1.0.0")
+        };
+        let fully_phased = RepositoryPackage {
+            phased_update_percentage: Some(100),
+            essential: false,
+            priority_required: false,
+            ..create_repository_package("package-c", "1.0.1")
+        };
+        package_index.add_package(released.clone());
+        package_index.add_package(phasing.clone());
+        package_index.add_package(phasing_without_alternative.clone());
+        package_index.add_package(fully_phased.clone());
+
+        assert_eq!(
+            package_index
+                .get_highest_available_version("package-a")
+                .map(AsRef::as_ref),
+            Some(&phasing)
+        );
+        assert_eq!(package_index.hold_back_phased_updates(), 1);
+        assert_eq!(
+            package_index
+                .get_highest_available_version("package-a")
+                .map(AsRef::as_ref),
+            Some(&released)
+        );
+        assert_eq!(
+            package_index
+                .get_highest_available_version("package-b")
+                .map(AsRef::as_ref),
+            Some(&phasing_without_alternative)
+        );
+        assert_eq!(
+            package_index
+                .get_highest_available_version("package-c")
+                .map(AsRef::as_ref),
+            Some(&fully_phased)
+        );
+        assert_eq!(
+            package_index
+                .get_version("package-a", "1.0.1")
+                .map(AsRef::as_ref),
+            Some(&phasing)
+        );
+    }
+
     #[test]
     fn test_missing_package() {
         let package_index = PackageIndex::default();
         assert_eq!(
-            package_index.get_highest_available_version("my-package"),
+            package_index
+                .get_highest_available_version("my-package")
+                .map(AsRef::as_ref),
             None
         );
     }
@@ -173,11 +478,27 @@ mod test {
         let mut package_index = PackageIndex::default();
         package_index.add_package(create_repository_package("my-package", "1.0.0"));
         assert_eq!(
-            package_index.get_highest_available_version("my-package"),
+            package_index
+                .get_highest_available_version("my-package")
+                .map(AsRef::as_ref),
             Some(&create_repository_package("my-package", "1.0.0"))
         );
     }
 
+    #[test]
+    fn test_find_package_name_ignoring_case() {
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(create_repository_package("imagemagick", "1.0.0"));
+        assert_eq!(
+            package_index.find_package_name_ignoring_case("ImageMagick"),
+            Some("imagemagick")
+        );
+        assert_eq!(
+            package_index.find_package_name_ignoring_case("GraphicsMagick"),
+            None
+        );
+    }
+
     #[test]
     fn test_retrieving_highest_available_package_version() {
         let mut package_index = PackageIndex::default();
@@ -185,7 +506,9 @@ mod test {
         package_index.add_package(create_repository_package("my-package", "2.0.0"));
         package_index.add_package(create_repository_package("my-package", "1.5.0"));
         assert_eq!(
-            package_index.get_highest_available_version("my-package"),
+            package_index
+                .get_highest_available_version("my-package")
+                .map(AsRef::as_ref),
             Some(&create_repository_package("my-package", "2.0.0"))
         );
     }
@@ -265,6 +588,48 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_retrieving_specific_package_version() {
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(create_repository_package("my-package", "1.0.0"));
+        package_index.add_package(create_repository_package("my-package", "2.0.0"));
+        assert_eq!(
+            package_index
+                .get_version("my-package", "1.0.0")
+                .map(AsRef::as_ref),
+            Some(&create_repository_package("my-package", "1.0.0"))
+        );
+        assert_eq!(package_index.get_version("my-package", "3.0.0"), None);
+    }
+
+    #[test]
+    fn test_retrieving_specific_package_version_with_epoch() {
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(create_repository_package("my-package", "1:8.5.0-2ubuntu10"));
+        package_index.add_package(create_repository_package("other-package", "1.0.0"));
+        assert_eq!(
+            package_index
+                .get_version("my-package", "1:8.5.0-2ubuntu10")
+                .map(AsRef::as_ref),
+            Some(&create_repository_package(
+                "my-package",
+                "1:8.5.0-2ubuntu10"
+            ))
+        );
+        assert_eq!(
+            package_index
+                .get_version("my-package", "8.5.0-2ubuntu10")
+                .map(AsRef::as_ref),
+            None
+        );
+        assert_eq!(
+            package_index
+                .get_version("other-package", "0:1.0.0")
+                .map(AsRef::as_ref),
+            Some(&create_repository_package("other-package", "1.0.0"))
+        );
+    }
+
     #[test]
     fn test_get_virtual_package_providers() {
         let mut package_index = PackageIndex::default();
@@ -291,4 +656,62 @@ mod test {
         package_index.add_package(libvips_provider_1);
         assert!(package_index.get_providers("libvips42").is_empty());
     }
+
+    #[test]
+    fn test_search_orders_results_by_match_type() {
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(RepositoryPackage {
+            description: Some("Vips image processing library".to_string()),
+            ..create_repository_package("libvips42", "8.12.1-1build1")
+        });
+        package_index.add_package(create_repository_package_with_provides(
+            "libvips-tools",
+            "8.12.1-1build1",
+            "vips",
+        ));
+        package_index.add_package(create_repository_package_with_provides(
+            "image-processor",
+            "1.0.0",
+            "libimage, vips-compat",
+        ));
+        package_index.add_package(RepositoryPackage {
+            description: Some("Uses VIPS for thumbnails".to_string()),
+            ..create_repository_package("thumbnailer", "1.0.0")
+        });
+        package_index.add_package(create_repository_package("vips", "1.0.0"));
+        package_index.add_package(create_repository_package("curl", "8.5.0"));
+
+        assert_eq!(
+            package_index
+                .search("VIPS")
+                .iter()
+                .map(|package| package.name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "vips",
+                "libvips-tools",
+                "libvips42",
+                "image-processor",
+                "thumbnailer"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_only_returns_highest_available_version() {
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(create_repository_package("curl", "7.0.0"));
+        package_index.add_package(create_repository_package("curl", "8.5.0"));
+        assert_eq!(
+            package_index.search("curl"),
+            vec![&create_repository_package("curl", "8.5.0")]
+        );
+    }
+
+    #[test]
+    fn test_search_with_no_matches() {
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(create_repository_package("curl", "8.5.0"));
+        assert!(package_index.search("wget").is_empty());
+    }
 }
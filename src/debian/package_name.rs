@@ -2,7 +2,7 @@ use serde::Serialize;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 
-#[derive(Debug, Eq, PartialEq, Hash, Serialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize)]
 // https://www.debian.org/doc/debian-policy/ch-controlfields.html#source
 pub(crate) struct PackageName(String);
 
@@ -20,9 +20,13 @@ impl FromStr for PackageName {
         // lower case letters (a-z), digits (0-9), plus (+) and minus (-) signs,
         // and periods (.). They must be at least two characters long and must
         // start with an alphanumeric character.
+        //
+        // Upper case letters are also accepted since names are often typed the way a project
+        // capitalizes itself (e.g.; `ImageMagick`). These are corrected to the name from the
+        // package index when the packages to install are determined.
         let is_valid_package_name = value
             .chars()
-            .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '+' | '-' | '.'))
+            .all(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9' | '+' | '-' | '.'))
             && value.chars().count() >= 2
             && value.starts_with(|c: char| c.is_ascii_alphanumeric());
 
@@ -59,6 +63,7 @@ mod tests {
             "g++",            // alphanumeric to start followed by non-alphanumeric characters
             "libevent-2.1-6", // just a mix of allowed characters
             "a0+.-",          // all the allowed characters
+            "ImageMagick",    // uppercase is corrected using the package index
         ];
         for valid_name in valid_names {
             assert_eq!(
@@ -74,7 +79,6 @@ mod tests {
             "a",               // too short
             "+a",              // can't start with non-alphanumeric character
             "ab_c",            // can't contain invalid characters
-            "aBc",             // uppercase is not allowed
             "package=1.2.3-1", // versioning is not allowed, package name only
         ];
         for invalid_name in invalid_names {
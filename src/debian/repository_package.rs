@@ -1,4 +1,4 @@
-use crate::debian::{RepositoryUri, SourceOrder};
+use crate::debian::{ArchitectureName, MultiArch, PackageChecksum, RepositoryUri, SourceOrder};
 use bullet_stream::style;
 use rayon::iter::{IntoParallelIterator, ParallelBridge, ParallelIterator};
 use std::collections::{HashMap, HashSet};
@@ -12,20 +12,35 @@ pub(crate) struct RepositoryPackage {
     pub(crate) name: String,
     pub(crate) version: debversion::Version,
     pub(crate) filename: String,
-    pub(crate) sha256sum: String,
+    pub(crate) checksum: PackageChecksum,
     pub(crate) depends: Option<String>,
     pub(crate) pre_depends: Option<String>,
     pub(crate) provides: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) source: Option<String>,
+    pub(crate) architecture: Option<String>,
+    pub(crate) multi_arch: MultiArch,
+    pub(crate) phased_update_percentage: Option<u8>,
+    // `Essential: yes` packages are part of the base system and can never be removed
+    pub(crate) essential: bool,
+    // `Priority: required` packages are needed for the system to work properly
+    pub(crate) priority_required: bool,
+    // the size of the package archive in bytes
+    pub(crate) size: Option<u64>,
 }
 
 impl RepositoryPackage {
     // NOTE: This is a simpler parser than what is provided by the `apt-parser` crate
     //       because we're indexing a large number of packages and the default
     //       parser was too slow.
+    //
+    //       Entries without a `SHA256` checksum are rejected unless `allow_weak_checksums` is set,
+    //       in which case the `SHA1` or `MD5sum` checksum is used instead.
     pub(crate) fn parse_parallel(
         repository_uri: RepositoryUri,
         source_order: SourceOrder,
         contents: &str,
+        allow_weak_checksums: bool,
     ) -> Result<RepositoryPackage, ParseRepositoryPackageError> {
         let values = contents
             .lines()
@@ -37,9 +52,19 @@ impl RepositoryPackage {
                     VERSION_KEY,
                     FILENAME_KEY,
                     SHA256_KEY,
+                    SHA1_KEY,
+                    MD5_KEY,
                     DEPENDS_KEY,
                     PRE_DEPENDS_KEY,
                     PROVIDES_KEY,
+                    DESCRIPTION_KEY,
+                    SOURCE_KEY,
+                    ARCHITECTURE_KEY,
+                    MULTI_ARCH_KEY,
+                    PHASED_UPDATE_PERCENTAGE_KEY,
+                    ESSENTIAL_KEY,
+                    PRIORITY_KEY,
+                    SIZE_KEY,
                 ]
                 .iter()
                 .any(|key| line.starts_with(key))
@@ -73,40 +98,178 @@ impl RepositoryPackage {
                 .ok_or(ParseRepositoryPackageError::MissingFilename(
                     package_name.clone(),
                 ))?,
-            sha256sum: values
-                .get(SHA256_KEY)
-                .map(|v| v.trim().to_string())
-                .ok_or(ParseRepositoryPackageError::MissingSha256(package_name))?,
+            checksum: {
+                let get_checksum = |key: &str| values.get(key).map(|v| v.trim().to_string());
+                get_checksum(SHA256_KEY)
+                    .map(PackageChecksum::Sha256)
+                    .or_else(|| {
+                        allow_weak_checksums
+                            .then(|| {
+                                get_checksum(SHA1_KEY)
+                                    .map(PackageChecksum::Sha1)
+                                    .or_else(|| get_checksum(MD5_KEY).map(PackageChecksum::Md5))
+                            })
+                            .flatten()
+                    })
+                    .ok_or(ParseRepositoryPackageError::MissingSha256(package_name))?
+            },
             depends: values.get(DEPENDS_KEY).map(|v| v.trim().to_string()),
             pre_depends: values.get(PRE_DEPENDS_KEY).map(|v| v.trim().to_string()),
             provides: values.get(PROVIDES_KEY).map(|v| v.trim().to_string()),
+            // NOTE: Only the first line (the synopsis) of the description is captured here since
+            //       the extended description is spread across the continuation lines that follow.
+            description: values.get(DESCRIPTION_KEY).map(|v| v.trim().to_string()),
+            source: values.get(SOURCE_KEY).map(|v| v.trim().to_string()),
+            architecture: values.get(ARCHITECTURE_KEY).map(|v| v.trim().to_string()),
+            multi_arch: values
+                .get(MULTI_ARCH_KEY)
+                .and_then(|v| MultiArch::from_str(v.trim()).ok())
+                .unwrap_or_default(),
+            phased_update_percentage: values
+                .get(PHASED_UPDATE_PERCENTAGE_KEY)
+                .and_then(|v| v.trim().parse().ok()),
+            essential: values
+                .get(ESSENTIAL_KEY)
+                .is_some_and(|v| v.trim().eq_ignore_ascii_case("yes")),
+            priority_required: values
+                .get(PRIORITY_KEY)
+                .is_some_and(|v| v.trim() == "required"),
+            size: values.get(SIZE_KEY).and_then(|v| v.trim().parse().ok()),
         })
     }
 
-    // NOTE: This list deliberately ignores alternative dependencies specified by "|"
-    //       as described by the debian package spec for relationship fields
+    // NOTE: The `Source` field is omitted when the source package has the same name and version as
+    //       the binary package, otherwise it contains the source package name optionally followed
+    //       by the source version in parentheses (e.g.; `Source: xmlsec1 (1.2.39-5build2)`).
+    pub(crate) fn get_source_package(&self) -> (&str, String) {
+        match self.source.as_deref() {
+            Some(source) => match source.split_once(' ') {
+                Some((name, version)) => (
+                    name,
+                    version
+                        .trim()
+                        .trim_start_matches('(')
+                        .trim_end_matches(')')
+                        .to_string(),
+                ),
+                None => (source, self.version.to_string()),
+            },
+            // packages from a foreign architecture are built from the same source package
+            None => (
+                self.name
+                    .split_once(':')
+                    .map_or(self.name.as_str(), |(name, _)| name),
+                self.version.to_string(),
+            ),
+        }
+    }
+
+    // NOTE: An epoch of 0 is the implied default so it's left out when displaying the version
+    //       (e.g.; `0:1.2.3-1` and `1.2.3-1` are the same version) while any other epoch is kept
+    //       (e.g.; `1:8.5.0-2ubuntu10`). This keeps the version consistent across the build output,
+    //       the layer metadata, the lockfile, and the vendor manifest.
+    pub(crate) fn display_version(&self) -> String {
+        match self.version.epoch {
+            Some(0) => debversion::Version {
+                epoch: None,
+                ..self.version.clone()
+            }
+            .to_string(),
+            _ => self.version.to_string(),
+        }
+    }
+
+    pub(crate) fn name_with_version(&self) -> String {
+        format!(
+            "{name}@{version}",
+            name = self.name,
+            version = self.display_version()
+        )
+    }
+
+    // NOTE: Binary packages are stored in the pool as `<name>_<version>_<arch>.deb` where the
+    //       version leaves out the epoch (e.g.; `curl_8.5.0-2ubuntu10_amd64.deb` for version
+    //       `1:8.5.0-2ubuntu10`) but some third-party repositories publish filenames that include
+    //       it, either as-is or percent-encoded. The epoch is removed here so packages are always
+    //       downloaded and vendored to a file name without a `:` in it.
+    pub(crate) fn pool_file_name(&self) -> Option<String> {
+        let file_name = self
+            .filename
+            .rsplit('/')
+            .next()
+            .filter(|name| !name.is_empty())?;
+        Some(match self.version.epoch {
+            Some(epoch) => [
+                format!("_{epoch}:"),
+                format!("_{epoch}%3a"),
+                format!("_{epoch}%3A"),
+            ]
+            .iter()
+            .fold(file_name.to_string(), |file_name, epoch_prefix| {
+                file_name.replacen(epoch_prefix.as_str(), "_", 1)
+            }),
+            None => file_name.to_string(),
+        })
+    }
+
+    // https://wiki.ubuntu.com/MultiarchSpec#Architecture-independent_packages
+    pub(crate) fn is_architecture_independent(&self) -> bool {
+        self.architecture.as_deref() == Some("all")
+    }
+
+    // NOTE: Updates published to the -updates pocket are gradually rolled out to a percentage of
+    //       machines with the `Phased-Update-Percentage` field until they reach 100%.
+    //
+    //       See https://wiki.ubuntu.com/PhasedUpdates
+    pub(crate) fn is_phasing(&self) -> bool {
+        self.phased_update_percentage
+            .is_some_and(|percentage| percentage < 100)
+    }
+
+    // NOTE: Essential and required packages (e.g.; libc6, dpkg) are already part of the base image
+    //       and overlaying a different version of them in the packages layer almost always breaks
+    //       the image. Packages from a foreign architecture are exempt since they're installed to
+    //       their own multiarch directories instead of replacing the native ones.
+    pub(crate) fn is_base_system_package(&self) -> bool {
+        (self.essential || self.priority_required)
+            && !ArchitectureName::from_qualified_package_name(&self.name)
+                .is_some_and(|arch| arch.is_foreign())
+    }
+
+    // NOTE: Packages from a foreign architecture are qualified with that architecture
+    //       (e.g.; libc6:i386) along with every package they depend on or provide since a foreign
+    //       package can only be satisfied by other packages from the same architecture. Any
+    //       relationship that is already qualified (e.g.; python3:any) is left as-is.
+    //
+    //       See https://wiki.ubuntu.com/MultiarchSpec#Dependencies_involving_Architecture:_all_packages
+    pub(crate) fn qualify_architecture(&mut self, arch: &ArchitectureName) {
+        self.name = arch.qualify_package_name(&self.name);
+        for field in [&mut self.pre_depends, &mut self.depends, &mut self.provides]
+            .into_iter()
+            .flatten()
+        {
+            *field = qualify_relationship_field(field, arch);
+        }
+    }
+
+    // NOTE: Every alternative given for each dependency (i.e.; those separated by the `|` symbol)
+    //       is listed in the order they're given as described by the debian package spec for
+    //       relationship fields, leaving the choice of alternative to the resolver.
     //       https://www.debian.org/doc/debian-policy/ch-relationships#syntax-of-relationship-fields
     //
-    //       Until we want to support a more sophisticated dependency resolution process, this
-    //       should suffice for constructing a simple dependency list. As such, we're only concerned
-    //       here with packages names, not the version or architecture qualifiers that may be attached.
-    pub(crate) fn get_dependencies(&self) -> HashSet<&str> {
-        let mut results = HashSet::new();
+    //       We're only concerned here with packages names, not the version or architecture
+    //       restrictions that may be attached.
+    pub(crate) fn get_dependency_alternatives(&self) -> Vec<Vec<&str>> {
+        let mut results = vec![];
         for field in [&self.pre_depends, &self.depends].into_iter().flatten() {
             // all dependencies are separated by commas
             for dependency in field.split(',') {
-                // package name and optional version and/or architecture information is separated by whitespace
-                if let Some(name) = dependency.trim().split(' ').next() {
-                    // I couldn't find an explicit reference to why some packages have the
-                    // format <package-name>:any (e.g.; python3:any) in the Debian Policy Manual
-                    // but this seems limited to usage with virtual packages.
-                    let name = match name.split(':').next() {
-                        Some(virtual_package_name) => virtual_package_name.trim(),
-                        None => name.trim(),
-                    };
-                    if !name.is_empty() {
-                        results.insert(name);
-                    }
+                let alternatives = dependency
+                    .split('|')
+                    .filter_map(dependency_name)
+                    .collect::<Vec<_>>();
+                if !alternatives.is_empty() {
+                    results.push(alternatives);
                 }
             }
         }
@@ -129,6 +292,32 @@ impl RepositoryPackage {
     }
 }
 
+fn qualify_relationship_field(field: &str, arch: &ArchitectureName) -> String {
+    field
+        .split(',')
+        .map(|relationship| {
+            relationship
+                .split('|')
+                .map(|alternative| {
+                    let alternative = alternative.trim();
+                    match alternative.split_once(' ') {
+                        Some((name, _)) if name.contains(':') => alternative.to_string(),
+                        Some((name, version)) => {
+                            format!("{} {version}", arch.qualify_package_name(name))
+                        }
+                        None if alternative.is_empty() || alternative.contains(':') => {
+                            alternative.to_string()
+                        }
+                        None => arch.qualify_package_name(alternative),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(" | ")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Debug)]
 pub(crate) enum ParseRepositoryPackageError {
     MissingPackageName,
@@ -189,16 +378,96 @@ static PACKAGE_KEY: &str = "Package";
 static VERSION_KEY: &str = "Version";
 static FILENAME_KEY: &str = "Filename";
 static SHA256_KEY: &str = "SHA256";
+static SHA1_KEY: &str = "SHA1";
+static MD5_KEY: &str = "MD5sum";
 static DEPENDS_KEY: &str = "Depends";
 static PRE_DEPENDS_KEY: &str = "Pre-Depends";
 static PROVIDES_KEY: &str = "Provides";
+static DESCRIPTION_KEY: &str = "Description";
+static SOURCE_KEY: &str = "Source";
+static ARCHITECTURE_KEY: &str = "Architecture";
+static MULTI_ARCH_KEY: &str = "Multi-Arch";
+// package name and optional version and/or architecture information is separated by whitespace
+fn dependency_name(dependency: &str) -> Option<&str> {
+    let name = dependency.trim().split(' ').next()?;
+    // Dependencies qualified with `:any` (e.g.; python3:any) are kept as-is since the Multi-Arch
+    // field of the candidates decides what can satisfy them. The same goes for qualifiers of a
+    // foreign architecture (e.g.; libc6:i386) since those refer to a different package than the
+    // native one. Any other qualifier refers to the native architecture.
+    let name = match name.split_once(':') {
+        Some((package_name, qualifier))
+            if qualifier != "any"
+                && !ArchitectureName::from_qualified_package_name(name)
+                    .is_some_and(|arch| arch.is_foreign()) =>
+        {
+            package_name.trim()
+        }
+        _ => name.trim(),
+    };
+    (!name.is_empty()).then_some(name)
+}
+
+static PHASED_UPDATE_PERCENTAGE_KEY: &str = "Phased-Update-Percentage";
+static ESSENTIAL_KEY: &str = "Essential";
+static PRIORITY_KEY: &str = "Priority";
+static SIZE_KEY: &str = "Size";
+
+// A package for tests where any field that isn't set gets a placeholder value. Dependencies are
+// given as the packages they refer to.
+#[cfg(test)]
+#[bon::builder]
+pub(crate) fn create_repository_package(
+    name: &str,
+    version: Option<&str>,
+    repository_uri: Option<&str>,
+    source_order: Option<SourceOrder>,
+    filename: Option<&str>,
+    checksum: Option<PackageChecksum>,
+    provides: Option<Vec<&str>>,
+    depends: Option<Vec<&RepositoryPackage>>,
+    pre_depends: Option<Vec<&RepositoryPackage>>,
+    source: Option<&str>,
+    architecture: Option<&str>,
+    multi_arch: Option<MultiArch>,
+    size: Option<u64>,
+) -> RepositoryPackage {
+    let join_deps = |vs: Vec<&RepositoryPackage>| {
+        vs.iter()
+            .map(|v| v.name.as_str())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    RepositoryPackage {
+        name: name.to_string(),
+        version: version
+            .unwrap_or("1.0.0")
+            .parse()
+            .expect("Test package version should be valid"),
+        provides: provides.map(|vs| vs.join(",")),
+        description: None,
+        source: source.map(ToString::to_string),
+        repository_uri: RepositoryUri::from(repository_uri.unwrap_or_default()),
+        source_order: source_order.unwrap_or(SourceOrder::new(0, 0, 0)),
+        checksum: checksum.unwrap_or(PackageChecksum::Sha256(String::new())),
+        depends: depends.map(join_deps),
+        pre_depends: pre_depends.map(join_deps),
+        filename: filename.unwrap_or_default().to_string(),
+        architecture: architecture.map(ToString::to_string),
+        multi_arch: multi_arch.unwrap_or_default(),
+        phased_update_percentage: None,
+        essential: false,
+        priority_required: false,
+        size,
+    }
+}
 
 #[cfg(test)]
 mod test {
     use std::collections::HashSet;
 
     use crate::debian::{
-        ParseRepositoryPackageError, RepositoryPackage, RepositoryUri, SourceOrder,
+        ArchitectureName, MultiArch, PackageChecksum, ParseRepositoryPackageError,
+        RepositoryPackage, RepositoryUri, SourceOrder,
     };
 
     #[test]
@@ -207,6 +476,7 @@ mod test {
             RepositoryUri::from("test"),
             SourceOrder::new(0, 0, 0),
             "Package: test-pkg\nVersion: not!valid\nFilename: test.deb\nSHA256: abc123",
+            false,
         );
         assert!(matches!(
             result,
@@ -215,7 +485,200 @@ mod test {
         ));
     }
 
-    fn create_repository_package(
+    #[test]
+    fn test_parse_description_synopsis() {
+        let result = RepositoryPackage::parse_parallel(
+            RepositoryUri::from("test"),
+            SourceOrder::new(0, 0, 0),
+            "Package: test-pkg\nVersion: 1.0.0\nFilename: test.deb\nSHA256: abc123\nDescription: A test package\n An extended description.\nDescription-md5: 0123456789abcdef",
+            false,
+        )
+        .unwrap();
+        assert_eq!(result.description, Some("A test package".to_string()));
+    }
+
+    #[test]
+    fn test_parse_size() {
+        let result = RepositoryPackage::parse_parallel(
+            RepositoryUri::from("test"),
+            SourceOrder::new(0, 0, 0),
+            "Package: test-pkg\nVersion: 1.0.0\nInstalled-Size: 512\nFilename: test.deb\nSize: 226454\nSHA256: abc123",
+            false,
+        )
+        .unwrap();
+        assert_eq!(result.size, Some(226_454));
+    }
+
+    #[test]
+    fn test_parse_multi_arch() {
+        let result = RepositoryPackage::parse_parallel(
+            RepositoryUri::from("test"),
+            SourceOrder::new(0, 0, 0),
+            "Package: test-pkg\nArchitecture: all\nVersion: 1.0.0\nMulti-Arch: foreign\nFilename: test.deb\nSHA256: abc123",
+            false,
+        )
+        .unwrap();
+        assert_eq!(result.multi_arch, MultiArch::Foreign);
+        assert!(result.is_architecture_independent());
+    }
+
+    #[test]
+    fn test_parse_phased_update_percentage() {
+        let result = RepositoryPackage::parse_parallel(
+            RepositoryUri::from("test"),
+            SourceOrder::new(0, 0, 0),
+            "Package: test-pkg\nVersion: 1.0.0\nFilename: test.deb\nSHA256: abc123\nPhased-Update-Percentage: 30",
+            false,
+        )
+        .unwrap();
+        assert_eq!(result.phased_update_percentage, Some(30));
+        assert!(result.is_phasing());
+    }
+
+    #[test]
+    fn test_parse_essential_and_priority() {
+        let parse = |contents: &str| {
+            RepositoryPackage::parse_parallel(
+                RepositoryUri::from("test"),
+                SourceOrder::new(0, 0, 0),
+                &format!("Package: test-pkg\nVersion: 1.0.0\nFilename: test.deb\nSHA256: abc123\n{contents}"),
+                false,
+            )
+            .unwrap()
+        };
+
+        let essential = parse("Essential: yes\nPriority: optional");
+        assert!(essential.essential);
+        assert!(!essential.priority_required);
+        assert!(essential.is_base_system_package());
+
+        let required = parse("Priority: required");
+        assert!(!required.essential);
+        assert!(required.priority_required);
+        assert!(required.is_base_system_package());
+
+        let optional = parse("Priority: optional");
+        assert!(!optional.is_base_system_package());
+
+        let mut foreign = parse("Essential: yes\nPriority: required");
+        foreign.qualify_architecture(&ArchitectureName::I_386);
+        assert!(!foreign.is_base_system_package());
+    }
+
+    #[test]
+    fn test_parse_weak_checksums() {
+        let contents =
+            "Package: test-pkg\nVersion: 1.0.0\nFilename: test.deb\nMD5sum: def456\nSHA1: abc123";
+        let result = RepositoryPackage::parse_parallel(
+            RepositoryUri::from("test"),
+            SourceOrder::new(0, 0, 0),
+            contents,
+            false,
+        );
+        assert!(matches!(
+            result,
+            Err(ParseRepositoryPackageError::MissingSha256(name)) if name == "test-pkg"
+        ));
+
+        let result = RepositoryPackage::parse_parallel(
+            RepositoryUri::from("test"),
+            SourceOrder::new(0, 0, 0),
+            contents,
+            true,
+        )
+        .unwrap();
+        assert_eq!(result.checksum, PackageChecksum::Sha1("abc123".to_string()));
+
+        let result = RepositoryPackage::parse_parallel(
+            RepositoryUri::from("test"),
+            SourceOrder::new(0, 0, 0),
+            "Package: test-pkg\nVersion: 1.0.0\nFilename: test.deb\nMD5sum: def456\nSHA256: 0123ab",
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            result.checksum,
+            PackageChecksum::Sha256("0123ab".to_string())
+        );
+    }
+
+    #[test]
+    fn test_get_source_package() {
+        let mut repository_package = create_package_with_relationships(None, None, None);
+        assert_eq!(
+            repository_package.get_source_package(),
+            ("test-name", "1.0.0".to_string())
+        );
+
+        repository_package.source = Some("test-source".to_string());
+        assert_eq!(
+            repository_package.get_source_package(),
+            ("test-source", "1.0.0".to_string())
+        );
+
+        repository_package.source = Some("test-source (0.9.0-1)".to_string());
+        assert_eq!(
+            repository_package.get_source_package(),
+            ("test-source", "0.9.0-1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_display_version() {
+        let mut repository_package = create_package_with_relationships(None, None, None);
+        assert_eq!(repository_package.display_version(), "1.0.0");
+        assert_eq!(repository_package.name_with_version(), "test-name@1.0.0");
+
+        repository_package.version = "0:1.0.0".parse().unwrap();
+        assert_eq!(repository_package.display_version(), "1.0.0");
+
+        repository_package.version = "1:8.5.0-2ubuntu10".parse().unwrap();
+        assert_eq!(repository_package.display_version(), "1:8.5.0-2ubuntu10");
+        assert_eq!(
+            repository_package.name_with_version(),
+            "test-name@1:8.5.0-2ubuntu10"
+        );
+    }
+
+    #[test]
+    fn test_pool_file_name() {
+        let mut repository_package = create_package_with_relationships(None, None, None);
+        repository_package.version = "1:8.5.0-2ubuntu10".parse().unwrap();
+
+        repository_package.filename = "pool/main/c/curl/curl_8.5.0-2ubuntu10_amd64.deb".to_string();
+        assert_eq!(
+            repository_package.pool_file_name().as_deref(),
+            Some("curl_8.5.0-2ubuntu10_amd64.deb")
+        );
+
+        repository_package.filename =
+            "pool/main/c/curl/curl_1:8.5.0-2ubuntu10_amd64.deb".to_string();
+        assert_eq!(
+            repository_package.pool_file_name().as_deref(),
+            Some("curl_8.5.0-2ubuntu10_amd64.deb")
+        );
+
+        repository_package.filename =
+            "pool/main/c/curl/curl_1%3a8.5.0-2ubuntu10_amd64.deb".to_string();
+        assert_eq!(
+            repository_package.pool_file_name().as_deref(),
+            Some("curl_8.5.0-2ubuntu10_amd64.deb")
+        );
+
+        repository_package.filename = "pool/main/c/curl/".to_string();
+        assert_eq!(repository_package.pool_file_name(), None);
+    }
+
+    // the first alternative of each dependency, which is what gets installed by default
+    fn get_dependencies(repository_package: &RepositoryPackage) -> HashSet<&str> {
+        repository_package
+            .get_dependency_alternatives()
+            .into_iter()
+            .filter_map(|alternatives| alternatives.first().copied())
+            .collect()
+    }
+
+    fn create_package_with_relationships(
         depends: Option<&str>,
         pre_depends: Option<&str>,
         provides: Option<&str>,
@@ -226,33 +689,43 @@ mod test {
             name: "test-name".to_string(),
             version: "1.0.0".parse().unwrap(),
             filename: "test-filename".to_string(),
-            sha256sum: "test-sha256sum".to_string(),
+            checksum: PackageChecksum::Sha256("test-sha256sum".to_string()),
             depends: depends.map(ToString::to_string),
             pre_depends: pre_depends.map(ToString::to_string),
             provides: provides.map(ToString::to_string),
+            description: None,
+            source: None,
+            architecture: None,
+            multi_arch: MultiArch::No,
+            phased_update_percentage: None,
+            essential: false,
+            priority_required: false,
+            size: None,
         }
     }
 
     #[test]
     fn test_empty_dependency_fields() {
-        let repository_package = create_repository_package(None, None, None);
-        assert_eq!(repository_package.get_dependencies(), HashSet::from([]));
+        let repository_package = create_package_with_relationships(None, None, None);
+        assert_eq!(get_dependencies(&repository_package), HashSet::from([]));
     }
 
     #[test]
     fn test_depends_but_no_pre_depends_fields() {
-        let repository_package = create_repository_package(Some("package1, package2"), None, None);
+        let repository_package =
+            create_package_with_relationships(Some("package1, package2"), None, None);
         assert_eq!(
-            repository_package.get_dependencies(),
+            get_dependencies(&repository_package),
             HashSet::from(["package1", "package2"])
         );
     }
 
     #[test]
     fn test_pre_depends_but_no_depends_fields() {
-        let repository_package = create_repository_package(None, Some("package1, package2"), None);
+        let repository_package =
+            create_package_with_relationships(None, Some("package1, package2"), None);
         assert_eq!(
-            repository_package.get_dependencies(),
+            get_dependencies(&repository_package),
             HashSet::from(["package1", "package2"])
         );
     }
@@ -260,35 +733,76 @@ mod test {
     #[test]
     fn test_pre_depends_and_depends_fields() {
         let repository_package =
-            create_repository_package(Some("package1"), Some("package2"), None);
+            create_package_with_relationships(Some("package1"), Some("package2"), None);
         assert_eq!(
-            repository_package.get_dependencies(),
+            get_dependencies(&repository_package),
             HashSet::from(["package1", "package2"])
         );
     }
 
+    #[test]
+    fn test_dependency_alternatives() {
+        let repository_package = create_package_with_relationships(
+            Some("package1 | optional-package:any"),
+            Some("debconf (>= 0.5) | debconf-2.0, package2:amd64"),
+            None,
+        );
+        assert_eq!(
+            repository_package.get_dependency_alternatives(),
+            vec![
+                vec!["debconf", "debconf-2.0"],
+                vec!["package2"],
+                vec!["package1", "optional-package:any"],
+            ]
+        );
+    }
+
     #[test]
     fn test_package_dependency_variations() {
-        let repository_package = create_repository_package(
+        let repository_package = create_package_with_relationships(
             Some("package1 | optional-package"),
             Some("package2:any, package3 (>= 7:6.1), package4 (>= 2.34) [riscv64]"),
             None,
         );
         assert_eq!(
-            repository_package.get_dependencies(),
-            HashSet::from(["package1", "package2", "package3", "package4"])
+            get_dependencies(&repository_package),
+            HashSet::from(["package1", "package2:any", "package3", "package4"])
+        );
+    }
+
+    #[test]
+    fn test_qualify_architecture() {
+        let mut repository_package = create_package_with_relationships(
+            Some("libc6 (>= 2.34), libgcc-s1 | libgcc1, python3:any"),
+            Some(""),
+            Some("libstdc++ (= 1.0.0)"),
+        );
+        repository_package.qualify_architecture(&ArchitectureName::I_386);
+        assert_eq!(repository_package.name, "test-name:i386");
+        assert_eq!(
+            get_dependencies(&repository_package),
+            HashSet::from(["libc6:i386", "libgcc-s1:i386", "python3:any"])
+        );
+        assert_eq!(
+            repository_package.provides_dependencies(),
+            HashSet::from(["libstdc++:i386"])
+        );
+        assert_eq!(
+            repository_package.get_source_package(),
+            ("test-name", "1.0.0".to_string())
         );
     }
 
     #[test]
     fn test_package_dependency_empty_strings() {
-        let repository_package = create_repository_package(Some(""), Some(""), None);
-        assert_eq!(repository_package.get_dependencies(), HashSet::from([]));
+        let repository_package = create_package_with_relationships(Some(""), Some(""), None);
+        assert_eq!(get_dependencies(&repository_package), HashSet::from([]));
     }
 
     #[test]
     fn test_package_provides_variations() {
-        let repository_package = create_repository_package(None, None, Some("bar (= 1.0), foo"));
+        let repository_package =
+            create_package_with_relationships(None, None, Some("bar (= 1.0), foo"));
         assert_eq!(
             repository_package.provides_dependencies(),
             HashSet::from(["bar", "foo"])
@@ -297,7 +811,7 @@ mod test {
 
     #[test]
     fn test_package_provides_empty_string() {
-        let repository_package = create_repository_package(None, None, Some(""));
+        let repository_package = create_package_with_relationships(None, None, Some(""));
         assert_eq!(
             repository_package.provides_dependencies(),
             HashSet::from([])
@@ -10,6 +10,17 @@ impl From<&str> for RepositoryUri {
     }
 }
 
+impl RepositoryUri {
+    // the same repository served over https (e.g.; for networks that block plain http), other
+    // schemes are left alone
+    pub(crate) fn to_https(&self) -> Self {
+        match self.0.strip_prefix("http://") {
+            Some(rest) => Self(format!("https://{rest}")),
+            None => self.clone(),
+        }
+    }
+}
+
 impl Display for RepositoryUri {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.0)
@@ -38,4 +49,20 @@ mod test {
         let repository_from_string = RepositoryUri::from("http://archive.ubuntu.com/ubuntu");
         assert_eq!(repository, repository_from_string);
     }
+
+    #[test]
+    fn test_to_https() {
+        assert_eq!(
+            RepositoryUri::from("http://ports.ubuntu.com/ubuntu-ports").to_https(),
+            RepositoryUri::from("https://ports.ubuntu.com/ubuntu-ports")
+        );
+        assert_eq!(
+            RepositoryUri::from("https://archive.ubuntu.com/ubuntu").to_https(),
+            RepositoryUri::from("https://archive.ubuntu.com/ubuntu")
+        );
+        assert_eq!(
+            RepositoryUri::from("s3://bucket/ubuntu").to_https(),
+            RepositoryUri::from("s3://bucket/ubuntu")
+        );
+    }
 }
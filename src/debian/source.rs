@@ -9,14 +9,26 @@ use serde::Serialize;
 //       - Only one URI is allowed even though the source format says URIs is an array
 //       - Enabled is always true, so it's omitted here
 //       - Only the Signed-By option is supported
+//       - Foreign architectures are listed separately from the native one so a repository that serves
+//         packages for both (e.g.; amd64 and i386) only has its release file fetched once
+//       - Packages without a SHA256 checksum can be allowed with `allow_weak_checksums` which apt
+//         has no equivalent for
+//       - The default sources list the fingerprints of their signing keys in `keyserver_fingerprints`
+//         so the keys can be refreshed from a keyserver if they no longer verify the release file
 #[derive(Debug, Serialize)]
 pub(crate) struct Source {
     pub(crate) arch: ArchitectureName,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) foreign_arches: Vec<ArchitectureName>,
     pub(crate) components: Vec<String>,
     #[serde(skip)]
     pub(crate) signed_by: String,
     pub(crate) suites: Vec<String>,
     pub(crate) uri: RepositoryUri,
+    #[serde(skip)]
+    pub(crate) allow_weak_checksums: bool,
+    #[serde(skip)]
+    pub(crate) keyserver_fingerprints: Vec<String>,
 }
 
 impl Source {
@@ -38,6 +50,9 @@ impl Source {
             suites: suites.into_iter().map(Into::into).collect(),
             uri: uri.into(),
             arch,
+            foreign_arches: vec![],
+            allow_weak_checksums: false,
+            keyserver_fingerprints: vec![],
         }
     }
 }
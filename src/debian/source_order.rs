@@ -1,3 +1,5 @@
+use crate::debian::Source;
+
 /// Represents the declaration order of a package's source.
 /// Lower values indicate earlier declaration (first-declared source wins).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -15,6 +17,15 @@ impl SourceOrder {
             component,
         }
     }
+
+    // the name of the suite (e.g.; `noble-security`) from the source list the package index was
+    // created with
+    pub(crate) fn suite<'a>(&self, source_list: &'a [Source]) -> Option<&'a str> {
+        source_list
+            .get(self.source)
+            .and_then(|source| source.suites.get(self.suite))
+            .map(String::as_str)
+    }
 }
 
 #[cfg(test)]
@@ -46,6 +57,35 @@ mod test {
         assert!(SourceOrder::new(0, 0, 9) < SourceOrder::new(0, 1, 0));
     }
 
+    #[test]
+    fn test_suite_from_source_list() {
+        let source_list = vec![
+            Source::new(
+                "http://archive.ubuntu.com/ubuntu",
+                vec!["noble", "noble-updates"],
+                vec!["main"],
+                "",
+                crate::debian::ArchitectureName::AMD_64,
+            ),
+            Source::new(
+                "http://security.ubuntu.com/ubuntu",
+                vec!["noble-security"],
+                vec!["main"],
+                "",
+                crate::debian::ArchitectureName::AMD_64,
+            ),
+        ];
+        assert_eq!(
+            SourceOrder::new(0, 1, 0).suite(&source_list),
+            Some("noble-updates")
+        );
+        assert_eq!(
+            SourceOrder::new(1, 0, 0).suite(&source_list),
+            Some("noble-security")
+        );
+        assert_eq!(SourceOrder::new(2, 0, 0).suite(&source_list), None);
+    }
+
     #[test]
     fn test_equality() {
         assert_eq!(SourceOrder::new(1, 2, 3), SourceOrder::new(1, 2, 3));
@@ -1,84 +1,387 @@
-use crate::config::RequestedPackage;
-use crate::debian::{PackageIndex, RepositoryPackage};
-use crate::{BuildpackResult, DebianPackagesBuildpackError};
+use crate::config::{EssentialPackagesMode, RequestedPackage};
+use crate::debian::{ArchitectureName, MultiArch, PackageIndex, RepositoryPackage, Source};
+use crate::lockfile::LOCKFILE_NAME;
+use crate::{BuildpackResult, DebianPackagesBuildpackError, is_buildpack_debug_logging_enabled};
 use apt_parser::Control;
 use bullet_stream::{global::print, style};
 use edit_distance::edit_distance;
 use indexmap::IndexSet;
 use serde::Serialize;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Formatter};
 use std::fs::read_to_string;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 use tracing::instrument;
 
+// the packages installed on the system (i.e.; the build image)
+pub(crate) const DPKG_STATUS_PATH: &str = "/var/lib/dpkg/status";
+
 #[instrument(skip_all)]
 pub(crate) fn determine_packages_to_install(
     package_index: &PackageIndex,
+    source_list: &[Source],
     requested_packages: IndexSet<RequestedPackage>,
-) -> BuildpackResult<Vec<RepositoryPackage>> {
+    locked_versions: &HashMap<String, String>,
+    prefer_alternatives: &BTreeMap<String, String>,
+    pins: &BTreeMap<String, String>,
+) -> BuildpackResult<(Vec<RepositoryPackage>, HashMap<String, PackageProvenance>)> {
     if requested_packages.is_empty() {
-        return Ok(vec![]);
+        return Ok((vec![], HashMap::new()));
     }
 
     print::header("Determining packages to install");
     print::bullet("Collecting system install information");
-    let system_packages_path = PathBuf::from("/var/lib/dpkg/status");
-    let system_packages = read_to_string(&system_packages_path)
-        .map_err(|e| {
-            DeterminePackagesToInstallError::ReadSystemPackages(system_packages_path.clone(), e)
-        })?
-        .trim()
-        .split("\n\n")
-        .map(|control_data| {
-            Control::from(control_data)
-                .map_err(|e| {
-                    DeterminePackagesToInstallError::ParseSystemPackage(
-                        system_packages_path.clone(),
-                        control_data.to_string(),
-                        e,
-                    )
-                })
-                .map(SystemPackage::from)
+    let system_packages = read_system_packages(Path::new(DPKG_STATUS_PATH))?;
+
+    let (packages_to_install, provenance) = resolve_packages_with_provenance(
+        package_index,
+        source_list,
+        requested_packages,
+        &system_packages,
+        locked_versions,
+        prefer_alternatives,
+        pins,
+        ResolutionOutput::Print,
+    )?;
+
+    if is_buildpack_debug_logging_enabled() {
+        print::sub_stream_with("Simulated install", |mut simulation_log, _| {
+            for line in simulate_install(&packages_to_install, source_list) {
+                let _ = writeln!(&mut simulation_log, "{line}");
+            }
+            let _ = writeln!(&mut simulation_log);
+        });
+    }
+
+    Ok((packages_to_install, provenance))
+}
+
+// Lists the packages in install order the same way `apt-get -s install` does (e.g.;
+// `Inst curl (8.5.0-2ubuntu10.6 noble-updates)`) so the output can be compared with apt directly.
+fn simulate_install(packages: &[RepositoryPackage], source_list: &[Source]) -> Vec<String> {
+    packages
+        .iter()
+        .map(|package| {
+            let version = package.display_version();
+            match package.source_order.suite(source_list) {
+                Some(suite) => format!("Inst {} ({version} {suite})", package.name),
+                None => format!("Inst {} ({version})", package.name),
+            }
         })
-        .collect::<Result<IndexSet<_>, _>>()?;
+        .collect()
+}
+
+// Where the progress of resolving packages is reported. The buildpack and the command line tools
+// print it the same way, while the library API resolves packages without writing to the stdout of
+// the program using it.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum ResolutionOutput {
+    Print,
+    Quiet,
+}
+
+impl ResolutionOutput {
+    fn bullet(self, message: impl AsRef<str>) {
+        if self == ResolutionOutput::Print {
+            print::bullet(message);
+        }
+    }
+
+    fn sub_bullet(self, message: impl AsRef<str>) {
+        if self == ResolutionOutput::Print {
+            print::sub_bullet(message);
+        }
+    }
+
+    fn warning(self, message: impl AsRef<str>) {
+        if self == ResolutionOutput::Print {
+            print::warning(message);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn resolve_packages_to_install(
+    package_index: &PackageIndex,
+    source_list: &[Source],
+    requested_packages: IndexSet<RequestedPackage>,
+    system_packages: &IndexSet<SystemPackage>,
+    locked_versions: &HashMap<String, String>,
+    prefer_alternatives: &BTreeMap<String, String>,
+    pins: &BTreeMap<String, String>,
+    output: ResolutionOutput,
+) -> BuildpackResult<Vec<RepositoryPackage>> {
+    resolve_packages_with_provenance(
+        package_index,
+        source_list,
+        requested_packages,
+        system_packages,
+        locked_versions,
+        prefer_alternatives,
+        pins,
+        output,
+    )
+    .map(|(packages_to_install, _)| packages_to_install)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn resolve_packages_with_provenance(
+    package_index: &PackageIndex,
+    source_list: &[Source],
+    requested_packages: IndexSet<RequestedPackage>,
+    system_packages: &IndexSet<SystemPackage>,
+    locked_versions: &HashMap<String, String>,
+    prefer_alternatives: &BTreeMap<String, String>,
+    pins: &BTreeMap<String, String>,
+    output: ResolutionOutput,
+) -> BuildpackResult<(Vec<RepositoryPackage>, HashMap<String, PackageProvenance>)> {
+    if !locked_versions.is_empty() {
+        output.bullet(format!(
+            "Using locked package versions from {lockfile}",
+            lockfile = style::value(LOCKFILE_NAME)
+        ));
+    }
+
+    let policy = PinnedVersions {
+        pins,
+        next: LockedVersions {
+            locked_versions,
+            next: PreferredAlternatives {
+                prefer_alternatives,
+                next: SuiteCandidates {
+                    suite_candidates: get_suite_candidates(
+                        &requested_packages,
+                        package_index,
+                        source_list,
+                    )?,
+                    next: DefaultPolicy,
+                },
+            },
+        },
+    };
+    resolve_packages_with_policy(
+        package_index,
+        requested_packages,
+        system_packages,
+        &policy,
+        output,
+    )
+}
 
+fn resolve_packages_with_policy<'a>(
+    package_index: &'a PackageIndex,
+    requested_packages: IndexSet<RequestedPackage>,
+    system_packages: &IndexSet<SystemPackage>,
+    policy: &dyn ResolutionPolicy<'a>,
+    output: ResolutionOutput,
+) -> BuildpackResult<(Vec<RepositoryPackage>, HashMap<String, PackageProvenance>)> {
     let mut packages_marked_for_install = IndexSet::new();
+    let mut provenance = HashMap::new();
 
     for requested_package in requested_packages {
-        print::bullet(format!(
+        let package_name = resolve_dependency_name(
+            &correct_package_name_case(&requested_package.qualified_name(), package_index, output),
+            package_index,
+        );
+        output.bullet(format!(
             "Determining install requirements for requested package {package}",
-            package = style::value(requested_package.name.as_str())
+            package = style::value(&package_name)
         ));
         let mut visit_stack = IndexSet::new();
         let mut package_notifications = IndexSet::new();
 
+        // Optional packages let a single configuration be shared across distributions where some
+        // packages only exist on one of them.
+        if requested_package.optional && !is_available(&package_name, package_index) {
+            output.warning(format!(
+                "Skipping optional package {package} because it was not found in the package index.",
+                package = style::value(&package_name)
+            ));
+            continue;
+        }
+
         visit(
-            requested_package.name.as_str(),
+            &package_name,
             requested_package.skip_dependencies,
             requested_package.force,
-            &system_packages,
+            system_packages,
             package_index,
+            policy,
             &mut packages_marked_for_install,
             &mut visit_stack,
             &mut package_notifications,
         )?;
 
         if package_notifications.is_empty() {
-            print::sub_bullet("Nothing to add");
+            output.sub_bullet("Nothing to add");
         } else {
             for package_notification in package_notifications {
-                print::sub_bullet(package_notification.to_string());
+                output.sub_bullet(package_notification.to_string());
+                if let PackageNotification::Added {
+                    repository_package,
+                    dependency_path,
+                    ..
+                } = package_notification
+                {
+                    provenance.insert(
+                        repository_package.name.clone(),
+                        PackageProvenance {
+                            requested_by: package_name.clone(),
+                            dependency_path,
+                        },
+                    );
+                }
             }
         }
     }
 
     let packages_to_install = packages_marked_for_install
         .into_iter()
-        .map(|package_marked_for_install| package_marked_for_install.repository_package)
+        .map(|package_marked_for_install| {
+            Arc::unwrap_or_clone(package_marked_for_install.repository_package)
+        })
+        .collect::<Vec<_>>();
+
+    Ok((
+        sort_topologically(packages_to_install, package_index),
+        provenance,
+    ))
+}
+
+// NOTE: Packages are installed in this order so dependencies come before the packages that depend
+//       on them, with ties broken by name, which keeps the install order (and which package's copy
+//       of a file shared by several packages ends up in the layer) the same on every build.
+//       Dependency cycles (e.g.; between libc6 and libgcc-s1) are broken by taking the remaining
+//       package with the lowest name.
+fn sort_topologically(
+    packages: Vec<RepositoryPackage>,
+    package_index: &PackageIndex,
+) -> Vec<RepositoryPackage> {
+    let mut package_positions = HashMap::new();
+    for (position, package) in packages.iter().enumerate() {
+        package_positions.insert(package.name.as_str(), position);
+    }
+    let mut provider_positions = HashMap::new();
+    for (position, package) in packages.iter().enumerate() {
+        for provided in package.provides_dependencies() {
+            provider_positions.entry(provided).or_insert(position);
+        }
+    }
+
+    let mut unsorted_dependencies = vec![0; packages.len()];
+    let mut dependents = vec![vec![]; packages.len()];
+    for (position, package) in packages.iter().enumerate() {
+        let dependency_positions = package
+            .get_dependency_alternatives()
+            .into_iter()
+            .flatten()
+            .filter_map(|dependency| {
+                let dependency = resolve_dependency_name(dependency, package_index);
+                package_positions
+                    .get(dependency.as_str())
+                    .or_else(|| provider_positions.get(dependency.as_str()))
+                    .copied()
+            })
+            .filter(|dependency_position| *dependency_position != position)
+            .collect::<HashSet<_>>();
+        unsorted_dependencies[position] = dependency_positions.len();
+        for dependency_position in dependency_positions {
+            dependents[dependency_position].push(position);
+        }
+    }
+
+    let mut ready = BTreeSet::new();
+    let mut remaining = BTreeSet::new();
+    for (position, package) in packages.iter().enumerate() {
+        if unsorted_dependencies[position] == 0 {
+            ready.insert((package.name.as_str(), position));
+        } else {
+            remaining.insert((package.name.as_str(), position));
+        }
+    }
+
+    let mut sorted_positions = Vec::with_capacity(packages.len());
+    while let Some((name, position)) = ready.pop_first().or_else(|| remaining.pop_first()) {
+        remaining.remove(&(name, position));
+        sorted_positions.push(position);
+        for dependent in &dependents[position] {
+            unsorted_dependencies[*dependent] = unsorted_dependencies[*dependent].saturating_sub(1);
+            let dependent_key = (packages[*dependent].name.as_str(), *dependent);
+            if unsorted_dependencies[*dependent] == 0 && remaining.remove(&dependent_key) {
+                ready.insert(dependent_key);
+            }
+        }
+    }
+
+    let mut packages = packages.into_iter().map(Some).collect::<Vec<_>>();
+    sorted_positions
+        .into_iter()
+        .filter_map(|position| packages[position].take())
+        .collect()
+}
+
+// NOTE: Essential and required packages are almost always pulled in as dependencies of a requested
+//       package (e.g.; a dependency on a newer `libc6` than the one on the base image) since the
+//       versions already installed on the system are skipped.
+pub(crate) fn check_essential_packages(
+    packages_to_install: &[RepositoryPackage],
+    mode: &EssentialPackagesMode,
+) -> BuildpackResult<()> {
+    let essential_packages = packages_to_install
+        .iter()
+        .filter(|package| package.is_base_system_package())
+        .map(|package| format!("{} ({})", package.name, package.version))
         .collect::<Vec<_>>();
 
-    Ok(packages_to_install)
+    if essential_packages.is_empty() {
+        return Ok(());
+    }
+
+    match mode {
+        EssentialPackagesMode::Allow => Ok(()),
+        EssentialPackagesMode::Fail => {
+            Err(DeterminePackagesToInstallError::EssentialPackages(essential_packages).into())
+        }
+        EssentialPackagesMode::Warn => {
+            print::warning(format!(
+                "The following essential or required packages will be installed over the versions \
+                on the base image which can break the image:\n{packages}",
+                packages = essential_packages
+                    .iter()
+                    .map(|package| format!("- {package}"))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+            Ok(())
+        }
+    }
+}
+
+fn read_system_packages(system_packages_path: &Path) -> BuildpackResult<IndexSet<SystemPackage>> {
+    Ok(read_to_string(system_packages_path)
+        .map_err(|e| {
+            DeterminePackagesToInstallError::ReadSystemPackages(
+                system_packages_path.to_path_buf(),
+                e,
+            )
+        })?
+        .trim()
+        .split("\n\n")
+        .map(|control_data| {
+            Control::from(control_data)
+                .map_err(|e| {
+                    DeterminePackagesToInstallError::ParseSystemPackage(
+                        system_packages_path.to_path_buf(),
+                        control_data.to_string(),
+                        e,
+                    )
+                })
+                .map(SystemPackage::from)
+        })
+        .collect::<Result<IndexSet<_>, _>>()?)
 }
 
 // NOTE: Since this buildpack is not meant to be a replacement for a fully-featured dependency
@@ -90,11 +393,12 @@ pub(crate) fn determine_packages_to_install(
 //         an inconsistent state, it's always possible to rebuild with a different configuration.
 //
 //       - When adding dependencies for a package requested for install we ignore any alternative
-//         package names given for a dependency (i.e.; those separated by the `|` symbol).
+//         package names given for a dependency (i.e.; those separated by the `|` symbol) unless
+//         one of them has a preferred package configured with `prefer_alternatives`.
 //
 //       - No attempts are made to find the most appropriate version to install for a package given
 //         any version constraints listed for packages. The latest available version will always be
-//         chosen.
+//         chosen unless a different version is pinned with `pins` or in the lockfile.
 //
 //       - Any packages that are already on the system will not be installed.
 //
@@ -102,12 +406,13 @@ pub(crate) fn determine_packages_to_install(
 //       will be reported to the user and, if they aren't correct, the user may disable this dependency
 //       resolution on a per-package basis and specify a more appropriate set of packages.
 #[allow(clippy::too_many_arguments)]
-fn visit(
+fn visit<'a>(
     package: &str,
     skip_dependencies: bool,
     force_if_installed_on_system: bool,
     system_packages: &IndexSet<SystemPackage>,
-    package_index: &PackageIndex,
+    package_index: &'a PackageIndex,
+    policy: &dyn ResolutionPolicy<'a>,
     packages_marked_for_install: &mut IndexSet<PackageMarkedForInstall>,
     visit_stack: &mut IndexSet<String>,
     package_notifications: &mut IndexSet<PackageNotification>,
@@ -136,7 +441,9 @@ fn visit(
         return Ok(());
     }
 
-    if let Some(repository_package) = package_index.get_highest_available_version(package) {
+    if let Some(repository_package) =
+        policy.select_candidate(package, package_index, package_notifications)?
+    {
         packages_marked_for_install.insert(PackageMarkedForInstall {
             repository_package: repository_package.clone(),
             requested_by: visit_stack.first().cloned().unwrap_or(package.to_string()),
@@ -151,15 +458,27 @@ fn visit(
         visit_stack.insert(repository_package.name.clone());
 
         if !skip_dependencies {
-            for dependency in repository_package.get_dependencies() {
-                if should_visit_dependency(dependency, system_packages, packages_marked_for_install)
-                {
+            // dependencies are visited by name so the output is the same on every build
+            let dependencies = repository_package
+                .get_dependency_alternatives()
+                .into_iter()
+                .map(|alternatives| policy.choose_alternative(&alternatives, package_notifications))
+                .filter(|dependency| policy.filter_dependency(repository_package, dependency))
+                .collect::<BTreeSet<_>>();
+            for dependency in dependencies {
+                let dependency = resolve_dependency_name(&dependency, package_index);
+                if should_visit_dependency(
+                    &dependency,
+                    system_packages,
+                    packages_marked_for_install,
+                ) {
                     visit(
-                        dependency,
+                        &dependency,
                         skip_dependencies,
                         force_if_installed_on_system,
                         system_packages,
                         package_index,
+                        policy,
                         packages_marked_for_install,
                         visit_stack,
                         package_notifications,
@@ -171,7 +490,7 @@ fn visit(
         visit_stack.shift_remove(&repository_package.name);
     } else {
         let virtual_package_provider =
-            get_provider_for_virtual_package(package, package_index, package_notifications)?;
+            policy.choose_provider(package, package_index, package_notifications)?;
 
         visit_stack.insert(package.to_string());
 
@@ -181,6 +500,7 @@ fn visit(
             force_if_installed_on_system,
             system_packages,
             package_index,
+            policy,
             packages_marked_for_install,
             visit_stack,
             package_notifications,
@@ -192,17 +512,386 @@ fn visit(
     Ok(())
 }
 
-fn get_provider_for_virtual_package<'a>(
+// NOTE: The Multi-Arch field of the candidates decides which package satisfies a dependency that is
+//       qualified with an architecture:
+//       - `:any` dependencies (e.g.; python3:any) are satisfied by the package of the native
+//         architecture. For a virtual package, only providers marked as `Multi-Arch: allowed` or
+//         `Multi-Arch: foreign` are considered.
+//       - dependencies on a foreign architecture (e.g.; libc6:i386) are satisfied by the package of
+//         the native architecture when it's marked as `Multi-Arch: foreign` or when it's an
+//         architecture-independent package since the same files would be installed either way.
+//
+//       See https://wiki.ubuntu.com/MultiarchSpec#Dependencies_involving_Architecture:_all_packages
+fn resolve_dependency_name(dependency: &str, package_index: &PackageIndex) -> String {
+    let Some((package_name, qualifier)) = dependency.split_once(':') else {
+        return dependency.to_string();
+    };
+
+    let native_package = package_index.get_highest_available_version(package_name);
+
+    if qualifier == "any" {
+        if native_package.is_none() {
+            let providers = package_index
+                .get_providers(package_name)
+                .into_iter()
+                .filter(|provider| {
+                    package_index
+                        .get_highest_available_version(provider)
+                        .is_some_and(|provider| provider.multi_arch.satisfies_any())
+                })
+                .collect::<Vec<_>>();
+            if let [provider] = providers.as_slice() {
+                return (*provider).to_string();
+            }
+        }
+        return package_name.to_string();
+    }
+
+    match native_package {
+        Some(native_package)
+            if native_package.multi_arch == MultiArch::Foreign
+                || native_package.is_architecture_independent() =>
+        {
+            package_name.to_string()
+        }
+        _ => dependency.to_string(),
+    }
+}
+
+// NOTE: A package requested from a specific suite (e.g.; `cmake` from `noble-backports`) is only
+//       installed from the versions in that suite's package indices. The restriction applies to
+//       the requested package alone, its dependencies are resolved from every suite as usual.
+fn get_suite_candidates<'a>(
+    requested_packages: &IndexSet<RequestedPackage>,
+    package_index: &'a PackageIndex,
+    source_list: &[Source],
+) -> BuildpackResult<HashMap<String, Vec<&'a Arc<RepositoryPackage>>>> {
+    let mut suite_candidates = HashMap::new();
+    for requested_package in requested_packages {
+        let Some(suite) = &requested_package.suite else {
+            continue;
+        };
+        let mut package_name = requested_package.qualified_name();
+        if package_index.get_packages(&package_name).is_empty()
+            && let Some(canonical_name) =
+                package_index.find_package_name_ignoring_case(&package_name)
+        {
+            package_name = canonical_name.to_string();
+        }
+        let packages = package_index.get_packages(&package_name);
+        let candidates = packages
+            .iter()
+            .filter(|package| package.source_order.suite(source_list) == Some(suite.as_str()))
+            .copied()
+            .collect::<Vec<_>>();
+        // a package that isn't in the index at all is reported by the usual lookup
+        if candidates.is_empty() && !packages.is_empty() {
+            return Err(DeterminePackagesToInstallError::PackageNotFoundInSuite(
+                package_name,
+                suite.clone(),
+                packages
+                    .iter()
+                    .filter_map(|package| package.source_order.suite(source_list))
+                    .map(ToString::to_string)
+                    .collect::<IndexSet<_>>()
+                    .into_iter()
+                    .collect(),
+            )
+            .into());
+        }
+        suite_candidates.insert(package_name, candidates);
+    }
+    Ok(suite_candidates)
+}
+
+// NOTE: The decisions made while resolving packages are kept behind this trait so the resolver can
+//       be run with a different behavior (e.g.; in tests) without threading more options through
+//       every step. Policies are composed by wrapping another policy as `next` and only refining
+//       the decisions they care about, every other decision is left to `next`. At the end of the
+//       chain each decision falls back to the simplest choice, which is all `DefaultPolicy` makes.
+trait ResolutionPolicy<'a> {
+    // the policy deciding whatever this one leaves alone
+    fn next(&self) -> Option<&dyn ResolutionPolicy<'a>> {
+        None
+    }
+
+    // the version of a package to install, or `None` when there's no package with that name (i.e.;
+    // it's a virtual package)
+    fn select_candidate(
+        &self,
+        package: &str,
+        package_index: &'a PackageIndex,
+        package_notifications: &mut IndexSet<PackageNotification>,
+    ) -> BuildpackResult<Option<&'a Arc<RepositoryPackage>>> {
+        match self.next() {
+            Some(next) => next.select_candidate(package, package_index, package_notifications),
+            None => Ok(package_index.get_highest_available_version(package)),
+        }
+    }
+
+    // a specific version of a package that can be installed, used by policies that hold a package
+    // at a version
+    fn find_version(
+        &self,
+        package: &str,
+        version: &str,
+        package_index: &'a PackageIndex,
+    ) -> Option<&'a Arc<RepositoryPackage>> {
+        match self.next() {
+            Some(next) => next.find_version(package, version, package_index),
+            None => package_index.get_version(package, version),
+        }
+    }
+
+    // the package installed for a dependency given as alternatives (i.e.; separated by the `|`
+    // symbol), only the first alternative is installed by default
+    fn choose_alternative(
+        &self,
+        alternatives: &[&str],
+        package_notifications: &mut IndexSet<PackageNotification>,
+    ) -> String {
+        match self.next() {
+            Some(next) => next.choose_alternative(alternatives, package_notifications),
+            None => alternatives
+                .first()
+                .map(ToString::to_string)
+                .unwrap_or_default(),
+        }
+    }
+
+    // the package providing a virtual package, which must have only one provider by default
+    fn choose_provider(
+        &self,
+        package: &str,
+        package_index: &'a PackageIndex,
+        package_notifications: &mut IndexSet<PackageNotification>,
+    ) -> BuildpackResult<&'a Arc<RepositoryPackage>> {
+        match self.next() {
+            Some(next) => next.choose_provider(package, package_index, package_notifications),
+            None => get_only_provider(package, package_index, package_notifications),
+        }
+    }
+
+    // whether a dependency of a package being installed is resolved as well
+    fn filter_dependency(&self, package: &RepositoryPackage, dependency: &str) -> bool {
+        match self.next() {
+            Some(next) => next.filter_dependency(package, dependency),
+            None => true,
+        }
+    }
+}
+
+struct DefaultPolicy;
+
+impl ResolutionPolicy<'_> for DefaultPolicy {}
+
+// Restricts the versions of a package requested from a specific suite to the ones in that suite.
+struct SuiteCandidates<'a, P> {
+    suite_candidates: HashMap<String, Vec<&'a Arc<RepositoryPackage>>>,
+    next: P,
+}
+
+impl<'a, P: ResolutionPolicy<'a>> SuiteCandidates<'a, P> {
+    fn get(&self, package: &str) -> Option<&Vec<&'a Arc<RepositoryPackage>>> {
+        self.suite_candidates
+            .get(package)
+            .filter(|candidates| !candidates.is_empty())
+    }
+}
+
+impl<'a, P: ResolutionPolicy<'a>> ResolutionPolicy<'a> for SuiteCandidates<'a, P> {
+    fn next(&self) -> Option<&dyn ResolutionPolicy<'a>> {
+        Some(&self.next)
+    }
+
+    fn select_candidate(
+        &self,
+        package: &str,
+        package_index: &'a PackageIndex,
+        package_notifications: &mut IndexSet<PackageNotification>,
+    ) -> BuildpackResult<Option<&'a Arc<RepositoryPackage>>> {
+        match self.get(package) {
+            Some(candidates) => Ok(candidates.first().copied()),
+            None => self
+                .next
+                .select_candidate(package, package_index, package_notifications),
+        }
+    }
+
+    fn find_version(
+        &self,
+        package: &str,
+        version: &str,
+        package_index: &'a PackageIndex,
+    ) -> Option<&'a Arc<RepositoryPackage>> {
+        match self.get(package) {
+            Some(candidates) => {
+                let version = debversion::Version::from_str(version).ok()?;
+                candidates
+                    .iter()
+                    .find(|candidate| candidate.version == version)
+                    .copied()
+            }
+            None => self.next.find_version(package, version, package_index),
+        }
+    }
+}
+
+// A pin holds a package at a specific version wherever it appears in the dependency tree (e.g.; to
+// avoid a point release that regressed) so, unlike a locked version, it takes precedence over the
+// lockfile and the build fails when the version isn't available.
+struct PinnedVersions<'a, P> {
+    pins: &'a BTreeMap<String, String>,
+    next: P,
+}
+
+impl<'a, P: ResolutionPolicy<'a>> ResolutionPolicy<'a> for PinnedVersions<'a, P> {
+    fn next(&self) -> Option<&dyn ResolutionPolicy<'a>> {
+        Some(&self.next)
+    }
+
+    fn select_candidate(
+        &self,
+        package: &str,
+        package_index: &'a PackageIndex,
+        package_notifications: &mut IndexSet<PackageNotification>,
+    ) -> BuildpackResult<Option<&'a Arc<RepositoryPackage>>> {
+        let Some(pinned_version) = self.pins.get(package) else {
+            return self
+                .next
+                .select_candidate(package, package_index, package_notifications);
+        };
+        // a pin on a virtual package is left to the provider that gets chosen
+        if package_index
+            .get_highest_available_version(package)
+            .is_none()
+        {
+            return Ok(None);
+        }
+        let pinned_package = self
+            .find_version(package, pinned_version, package_index)
+            .ok_or_else(|| {
+                DeterminePackagesToInstallError::PinnedVersionNotFound(
+                    package.to_string(),
+                    pinned_version.clone(),
+                    package_index.get_available_versions(package),
+                )
+            })?;
+        package_notifications.insert(PackageNotification::PinnedVersion {
+            repository_package: pinned_package.clone(),
+        });
+        Ok(Some(pinned_package))
+    }
+}
+
+// A locked version can disappear from the package index when the upstream repository replaces it
+// (e.g.; a newer security update is published) so we fall back to the version `next` selects and
+// let the user know their lockfile needs to be updated.
+struct LockedVersions<'a, P> {
+    locked_versions: &'a HashMap<String, String>,
+    next: P,
+}
+
+impl<'a, P: ResolutionPolicy<'a>> ResolutionPolicy<'a> for LockedVersions<'a, P> {
+    fn next(&self) -> Option<&dyn ResolutionPolicy<'a>> {
+        Some(&self.next)
+    }
+
+    fn select_candidate(
+        &self,
+        package: &str,
+        package_index: &'a PackageIndex,
+        package_notifications: &mut IndexSet<PackageNotification>,
+    ) -> BuildpackResult<Option<&'a Arc<RepositoryPackage>>> {
+        let Some(available_package) =
+            self.next
+                .select_candidate(package, package_index, package_notifications)?
+        else {
+            return Ok(None);
+        };
+        let Some(locked_version) = self.locked_versions.get(package) else {
+            return Ok(Some(available_package));
+        };
+        Ok(self
+            .find_version(package, locked_version, package_index)
+            .or_else(|| {
+                package_notifications.insert(PackageNotification::LockedVersionUnavailable {
+                    package_name: package.to_string(),
+                    locked_version: locked_version.clone(),
+                    available_package: available_package.clone(),
+                });
+                Some(available_package)
+            }))
+    }
+}
+
+// Installs the configured package for a dependency given as alternatives, or for a virtual package
+// with several providers, instead of failing or taking the first alternative.
+struct PreferredAlternatives<'a, P> {
+    prefer_alternatives: &'a BTreeMap<String, String>,
+    next: P,
+}
+
+impl<'a, P: ResolutionPolicy<'a>> ResolutionPolicy<'a> for PreferredAlternatives<'a, P> {
+    fn next(&self) -> Option<&dyn ResolutionPolicy<'a>> {
+        Some(&self.next)
+    }
+
+    fn choose_alternative(
+        &self,
+        alternatives: &[&str],
+        package_notifications: &mut IndexSet<PackageNotification>,
+    ) -> String {
+        if alternatives.len() > 1
+            && let Some(preferred) = alternatives
+                .iter()
+                .find_map(|alternative| self.prefer_alternatives.get(*alternative))
+        {
+            package_notifications.insert(PackageNotification::PreferredAlternative {
+                dependency: alternatives.join(" | "),
+                preferred: preferred.clone(),
+            });
+            return preferred.clone();
+        }
+        self.next
+            .choose_alternative(alternatives, package_notifications)
+    }
+
+    fn choose_provider(
+        &self,
+        package: &str,
+        package_index: &'a PackageIndex,
+        package_notifications: &mut IndexSet<PackageNotification>,
+    ) -> BuildpackResult<&'a Arc<RepositoryPackage>> {
+        if let Some(preferred) = self.prefer_alternatives.get(package)
+            && package_index
+                .get_providers(package)
+                .iter()
+                .any(|provider| provider == preferred)
+            && let Some(repository_package) = package_index.get_highest_available_version(preferred)
+        {
+            package_notifications.insert(PackageNotification::PreferredAlternative {
+                dependency: package.to_string(),
+                preferred: preferred.clone(),
+            });
+            return Ok(repository_package);
+        }
+        self.next
+            .choose_provider(package, package_index, package_notifications)
+    }
+}
+
+fn get_only_provider<'a>(
     package: &str,
     package_index: &'a PackageIndex,
-    package_install_details: &mut IndexSet<PackageNotification>,
-) -> BuildpackResult<&'a RepositoryPackage> {
+    package_notifications: &mut IndexSet<PackageNotification>,
+) -> BuildpackResult<&'a Arc<RepositoryPackage>> {
     let providers = package_index.get_providers(package);
     Ok(match providers.iter().collect::<Vec<_>>().as_slice() {
         [providing_package] => package_index
             .get_highest_available_version(providing_package)
             .inspect(|repository_package| {
-                package_install_details.insert(
+                package_notifications.insert(
                     PackageNotification::VirtualPackageHasOnlyOneImplementor {
                         requested_package: package.to_string(),
                         implementor: (*repository_package).clone(),
@@ -229,6 +918,37 @@ fn get_provider_for_virtual_package<'a>(
     }?)
 }
 
+// Package names in the index are lowercase but are often typed the way a project capitalizes
+// itself (e.g.; `ImageMagick`) so a requested package that isn't found is looked up again ignoring
+// case.
+fn correct_package_name_case(
+    package_name: &str,
+    package_index: &PackageIndex,
+    output: ResolutionOutput,
+) -> String {
+    if is_available(package_name, package_index) {
+        return package_name.to_string();
+    }
+    match package_index.find_package_name_ignoring_case(package_name) {
+        Some(canonical_name) => {
+            output.warning(format!(
+                "Package {package_name} was not found in the package index but {canonical_name} was so it will be installed instead. Package names are case-sensitive, use {canonical_name} in your configuration to remove this warning.",
+                package_name = style::value(package_name),
+                canonical_name = style::value(canonical_name)
+            ));
+            canonical_name.to_string()
+        }
+        None => package_name.to_string(),
+    }
+}
+
+fn is_available(package: &str, package_index: &PackageIndex) -> bool {
+    package_index
+        .get_highest_available_version(package)
+        .is_some()
+        || !package_index.get_providers(package).is_empty()
+}
+
 fn find_system_package_by_name<'a>(
     package_name: &str,
     system_packages: &'a IndexSet<SystemPackage>,
@@ -296,6 +1016,10 @@ pub(crate) enum DeterminePackagesToInstallError {
     ParseSystemPackage(PathBuf, String, apt_parser::errors::APTError),
     PackageNotFound(String, Vec<String>),
     VirtualPackageMustBeSpecified(String, HashSet<String>),
+    EssentialPackages(Vec<String>),
+    PinnedVersionNotFound(String, String, Vec<String>),
+    PackageNotFoundInSuite(String, String, Vec<String>),
+    InvalidPackageName(String),
 }
 
 impl From<DeterminePackagesToInstallError> for libcnb::Error<DebianPackagesBuildpackError> {
@@ -309,7 +1033,7 @@ impl From<DeterminePackagesToInstallError> for libcnb::Error<DebianPackagesBuild
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 enum PackageNotification {
     Added {
-        repository_package: RepositoryPackage,
+        repository_package: Arc<RepositoryPackage>,
         dependency_path: Vec<String>,
         forced_install: bool,
     },
@@ -318,12 +1042,24 @@ enum PackageNotification {
         system_package_version: String,
     },
     AlreadyInstalledByOtherPackage {
-        installed_package: RepositoryPackage,
+        installed_package: Arc<RepositoryPackage>,
         installed_by: String,
     },
     VirtualPackageHasOnlyOneImplementor {
         requested_package: String,
-        implementor: RepositoryPackage,
+        implementor: Arc<RepositoryPackage>,
+    },
+    LockedVersionUnavailable {
+        package_name: String,
+        locked_version: String,
+        available_package: Arc<RepositoryPackage>,
+    },
+    PreferredAlternative {
+        dependency: String,
+        preferred: String,
+    },
+    PinnedVersion {
+        repository_package: Arc<RepositoryPackage>,
     },
 }
 
@@ -338,11 +1074,7 @@ impl Display for PackageNotification {
                 if dependency_path.is_empty() {
                     let added = format!(
                         "Adding {name_with_version}",
-                        name_with_version = style::value(format!(
-                            "{name}@{version}",
-                            name = repository_package.name,
-                            version = repository_package.version
-                        ))
+                        name_with_version = style::value(repository_package.name_with_version())
                     );
                     if *forced_install {
                         write!(f, "{added} (forced)")
@@ -353,11 +1085,7 @@ impl Display for PackageNotification {
                     write!(
                         f,
                         "Adding {name_with_version} [from {path}]",
-                        name_with_version = style::value(format!(
-                            "{name}@{version}",
-                            name = repository_package.name,
-                            version = repository_package.version
-                        )),
+                        name_with_version = style::value(repository_package.name_with_version()),
                         path = dependency_path
                             .iter()
                             .rev()
@@ -387,11 +1115,7 @@ impl Display for PackageNotification {
                     f,
                     "Skipping {package} because {name_with_version} was already installed as a dependency of {installed_by}",
                     package = style::value(&installed_package.name),
-                    name_with_version = style::value(format!(
-                        "{name}@{version}",
-                        name = installed_package.name,
-                        version = installed_package.version
-                    )),
+                    name_with_version = style::value(installed_package.name_with_version()),
                     installed_by = style::value(installed_by),
                 )
             }
@@ -403,11 +1127,39 @@ impl Display for PackageNotification {
                     f,
                     "Virtual package {package} is provided by {name_with_version}",
                     package = style::value(requested_package),
-                    name_with_version = style::value(format!(
-                        "{name}@{version}",
-                        name = implementor.name,
-                        version = implementor.version
-                    )),
+                    name_with_version = style::value(implementor.name_with_version()),
+                )
+            }
+            PackageNotification::LockedVersionUnavailable {
+                package_name,
+                locked_version,
+                available_package,
+            } => {
+                write!(
+                    f,
+                    "Locked version {locked_name_with_version} is no longer available, using {name_with_version} instead (update {lockfile} to resolve this)",
+                    locked_name_with_version =
+                        style::value(format!("{package_name}@{locked_version}")),
+                    name_with_version = style::value(available_package.name_with_version()),
+                    lockfile = style::value(LOCKFILE_NAME),
+                )
+            }
+            PackageNotification::PreferredAlternative {
+                dependency,
+                preferred,
+            } => {
+                write!(
+                    f,
+                    "Using preferred alternative {preferred} for {dependency}",
+                    preferred = style::value(preferred),
+                    dependency = style::value(dependency),
+                )
+            }
+            PackageNotification::PinnedVersion { repository_package } => {
+                write!(
+                    f,
+                    "Using pinned version {name_with_version}",
+                    name_with_version = style::value(repository_package.name_with_version()),
                 )
             }
         }
@@ -416,20 +1168,34 @@ impl Display for PackageNotification {
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 struct PackageMarkedForInstall {
-    repository_package: RepositoryPackage,
+    repository_package: Arc<RepositoryPackage>,
     requested_by: String,
 }
 
+// Why a package is being installed. The dependency path lists the packages between the requested
+// package and this one (e.g.; `curl` -> `libcurl4t64` for `libnghttp2-14`) and is empty for the
+// requested packages themselves.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize)]
+pub(crate) struct PackageProvenance {
+    pub(crate) requested_by: String,
+    pub(crate) dependency_path: Vec<String>,
+}
+
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize)]
-struct SystemPackage {
+pub(crate) struct SystemPackage {
     package_name: String,
     package_version: String,
 }
 
 impl From<Control> for SystemPackage {
     fn from(value: Control) -> Self {
+        // foreign packages are named the same way as the ones from the package index
+        let package_name = match ArchitectureName::from_str(&value.architecture) {
+            Ok(arch) if arch.is_foreign() => arch.qualify_package_name(&value.package),
+            _ => value.package,
+        };
         Self {
-            package_name: value.package,
+            package_name,
             package_version: value.version,
         }
     }
@@ -441,7 +1207,7 @@ mod test {
 
     use bon::builder;
 
-    use crate::debian::{RepositoryUri, SourceOrder};
+    use crate::debian::{SourceOrder, create_repository_package};
 
     #[test]
     fn install_package_already_on_the_system() {
@@ -496,7 +1262,7 @@ mod test {
         assert_eq!(
             package_notifications,
             IndexSet::from([PackageNotification::AlreadyInstalledByOtherPackage {
-                installed_package: package_b,
+                installed_package: package_b.into(),
                 installed_by: package_a.name,
             }])
         );
@@ -530,10 +1296,10 @@ mod test {
             IndexSet::from([
                 PackageNotification::VirtualPackageHasOnlyOneImplementor {
                     requested_package: virtual_package.to_string(),
-                    implementor: virtual_package_provider.clone()
+                    implementor: virtual_package_provider.clone().into()
                 },
                 PackageNotification::Added {
-                    repository_package: virtual_package_provider,
+                    repository_package: virtual_package_provider.into(),
                     dependency_path: vec![virtual_package.to_string()],
                     forced_install: false,
                 },
@@ -587,17 +1353,94 @@ mod test {
     }
 
     #[test]
-    fn install_virtual_package_when_there_are_no_providers() {
-        let virtual_package = "virtual-package";
+    fn install_preferred_provider_of_virtual_package_when_there_are_multiple_providers() {
+        let virtual_package = "debconf-2.0";
 
-        let package_with_edit_distance_1 = create_repository_package()
-            .name(&format!("{virtual_package}1"))
+        let debconf = create_repository_package()
+            .name("debconf")
+            .provides(vec![virtual_package])
             .call();
-        let package_with_virtual_package_edit_distance_2_and_3 = create_repository_package()
-            .name("another-virtual-package-provider")
-            .provides(vec![
-                &format!("{virtual_package}12"),
-                &format!("{virtual_package}123"),
+
+        let cdebconf = create_repository_package()
+            .name("cdebconf")
+            .provides(vec![virtual_package])
+            .call();
+
+        let (new_packages_marked_for_install, package_notifications) = test_install_state()
+            .with_package_index(vec![&debconf, &cdebconf])
+            .with_prefer_alternatives(BTreeMap::from([(
+                virtual_package.to_string(),
+                cdebconf.name.clone(),
+            )]))
+            .install(virtual_package)
+            .call()
+            .unwrap();
+
+        assert_eq!(
+            new_packages_marked_for_install,
+            IndexSet::from([create_package_marked_for_install()
+                .repository_package(&cdebconf)
+                .requested_by(virtual_package)
+                .call()])
+        );
+        assert!(
+            package_notifications.contains(&PackageNotification::PreferredAlternative {
+                dependency: virtual_package.to_string(),
+                preferred: cdebconf.name.clone(),
+            })
+        );
+    }
+
+    #[test]
+    fn install_preferred_dependency_alternative() {
+        let debconf = create_repository_package().name("debconf").call();
+        let cdebconf = create_repository_package()
+            .name("cdebconf")
+            .provides(vec!["debconf-2.0"])
+            .call();
+        let package_a = RepositoryPackage {
+            depends: Some("debconf (>= 0.5) | debconf-2.0".to_string()),
+            ..create_repository_package().name("package-a").call()
+        };
+
+        let install_with_preferences = |prefer_alternatives: BTreeMap<String, String>| {
+            test_install_state()
+                .with_package_index(vec![&package_a, &debconf, &cdebconf])
+                .with_prefer_alternatives(prefer_alternatives)
+                .install(&package_a.name)
+                .call()
+                .unwrap()
+                .0
+                .into_iter()
+                .map(|package| package.repository_package.name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            install_with_preferences(BTreeMap::new()),
+            vec!["package-a", "debconf"]
+        );
+        assert_eq!(
+            install_with_preferences(BTreeMap::from([(
+                "debconf-2.0".to_string(),
+                "cdebconf".to_string()
+            )])),
+            vec!["package-a", "cdebconf"]
+        );
+    }
+
+    #[test]
+    fn install_virtual_package_when_there_are_no_providers() {
+        let virtual_package = "virtual-package";
+
+        let package_with_edit_distance_1 = create_repository_package()
+            .name(&format!("{virtual_package}1"))
+            .call();
+        let package_with_virtual_package_edit_distance_2_and_3 = create_repository_package()
+            .name("another-virtual-package-provider")
+            .provides(vec![
+                &format!("{virtual_package}12"),
+                &format!("{virtual_package}123"),
             ])
             .call();
         let package_with_edit_distance_4 = create_repository_package()
@@ -790,7 +1633,7 @@ mod test {
         assert_eq!(
             package_notifications,
             IndexSet::from([PackageNotification::Added {
-                repository_package: package_v1,
+                repository_package: package_v1.into(),
                 dependency_path: vec![],
                 forced_install: false,
             }])
@@ -847,22 +1690,22 @@ mod test {
             package_notifications,
             IndexSet::from([
                 PackageNotification::Added {
-                    repository_package: package_a.clone(),
+                    repository_package: package_a.clone().into(),
                     dependency_path: vec![],
                     forced_install: false,
                 },
                 PackageNotification::Added {
-                    repository_package: package_b.clone(),
+                    repository_package: package_b.clone().into(),
                     dependency_path: vec![package_a.name.clone()],
                     forced_install: false,
                 },
                 PackageNotification::Added {
-                    repository_package: package_c.clone(),
+                    repository_package: package_c.clone().into(),
                     dependency_path: vec![package_a.name.clone(), package_b.name.clone()],
                     forced_install: false,
                 },
                 PackageNotification::Added {
-                    repository_package: package_d,
+                    repository_package: package_d.into(),
                     dependency_path: vec![
                         package_a.name.clone(),
                         package_b.name.clone(),
@@ -910,7 +1753,7 @@ mod test {
         assert_eq!(
             package_notifications,
             IndexSet::from([PackageNotification::Added {
-                repository_package: package_a,
+                repository_package: package_a.into(),
                 dependency_path: vec![],
                 forced_install: false,
             }])
@@ -942,7 +1785,7 @@ mod test {
         assert_eq!(
             package_notifications,
             IndexSet::from([PackageNotification::Added {
-                repository_package: package_a,
+                repository_package: package_a.into(),
                 dependency_path: vec![],
                 forced_install: false,
             }])
@@ -999,22 +1842,22 @@ mod test {
             package_notifications,
             IndexSet::from([
                 PackageNotification::Added {
-                    repository_package: package_a.clone(),
+                    repository_package: package_a.clone().into(),
                     dependency_path: vec![],
                     forced_install: false,
                 },
                 PackageNotification::Added {
-                    repository_package: package_b.clone(),
+                    repository_package: package_b.clone().into(),
                     dependency_path: vec![package_a.name.clone()],
                     forced_install: false,
                 },
                 PackageNotification::Added {
-                    repository_package: package_c.clone(),
+                    repository_package: package_c.clone().into(),
                     dependency_path: vec![package_a.name.clone(), package_b.name.clone()],
                     forced_install: false,
                 },
                 PackageNotification::Added {
-                    repository_package: package_d,
+                    repository_package: package_d.into(),
                     dependency_path: vec![package_a.name.clone(), package_b.name.clone()],
                     forced_install: false,
                 },
@@ -1047,7 +1890,7 @@ mod test {
             IndexSet::from([
                 PackageNotification::VirtualPackageHasOnlyOneImplementor {
                     requested_package: libvips.to_string(),
-                    implementor: libvips42t64.clone()
+                    implementor: libvips42t64.clone().into()
                 },
                 PackageNotification::AlreadyInstalledOnSystem {
                     system_package_name: libvips42t64.name.clone(),
@@ -1088,16 +1931,194 @@ mod test {
             IndexSet::from([
                 PackageNotification::VirtualPackageHasOnlyOneImplementor {
                     requested_package: libvips.to_string(),
-                    implementor: libvips42t64.clone()
+                    implementor: libvips42t64.clone().into()
                 },
                 PackageNotification::AlreadyInstalledByOtherPackage {
-                    installed_package: libvips42t64,
+                    installed_package: libvips42t64.into(),
                     installed_by: package_a.name.clone(),
                 }
             ])
         );
     }
 
+    #[test]
+    fn install_locked_package_version() {
+        let package_a_v1 = create_repository_package()
+            .name("package-a")
+            .version("1.0.0")
+            .call();
+        let package_a_v2 = create_repository_package()
+            .name("package-a")
+            .version("2.0.0")
+            .call();
+
+        let (new_packages_marked_for_install, package_notifications) = test_install_state()
+            .with_package_index(vec![&package_a_v1, &package_a_v2])
+            .with_locked_versions(HashMap::from([(
+                package_a_v1.name.clone(),
+                "1.0.0".to_string(),
+            )]))
+            .install(&package_a_v1.name)
+            .call()
+            .unwrap();
+
+        assert_eq!(
+            new_packages_marked_for_install,
+            IndexSet::from([create_package_marked_for_install()
+                .repository_package(&package_a_v1)
+                .call()])
+        );
+
+        assert_eq!(
+            package_notifications,
+            IndexSet::from([PackageNotification::Added {
+                repository_package: package_a_v1.into(),
+                dependency_path: vec![],
+                forced_install: false,
+            }])
+        );
+    }
+
+    #[test]
+    fn install_locked_package_version_that_is_no_longer_available() {
+        let package_a = create_repository_package()
+            .name("package-a")
+            .version("2.0.0")
+            .call();
+
+        let (new_packages_marked_for_install, package_notifications) = test_install_state()
+            .with_package_index(vec![&package_a])
+            .with_locked_versions(HashMap::from([(
+                package_a.name.clone(),
+                "1.0.0".to_string(),
+            )]))
+            .install(&package_a.name)
+            .call()
+            .unwrap();
+
+        assert_eq!(
+            new_packages_marked_for_install,
+            IndexSet::from([create_package_marked_for_install()
+                .repository_package(&package_a)
+                .call()])
+        );
+
+        assert_eq!(
+            package_notifications,
+            IndexSet::from([
+                PackageNotification::LockedVersionUnavailable {
+                    package_name: package_a.name.clone(),
+                    locked_version: "1.0.0".to_string(),
+                    available_package: package_a.clone().into(),
+                },
+                PackageNotification::Added {
+                    repository_package: package_a.into(),
+                    dependency_path: vec![],
+                    forced_install: false,
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn install_pinned_version_of_transitive_dependency() {
+        let package_b_v1 = create_repository_package()
+            .name("package-b")
+            .version("1.0.0")
+            .call();
+        let package_b_v2 = create_repository_package()
+            .name("package-b")
+            .version("2.0.0")
+            .call();
+        let package_a = create_repository_package()
+            .name("package-a")
+            .version("2.0.0")
+            .depends(vec![&package_b_v2])
+            .call();
+
+        let (new_packages_marked_for_install, package_notifications) = test_install_state()
+            .with_package_index(vec![&package_a, &package_b_v1, &package_b_v2])
+            .with_locked_versions(HashMap::from([(
+                package_b_v1.name.clone(),
+                "2.0.0".to_string(),
+            )]))
+            .with_pins(BTreeMap::from([(
+                package_b_v1.name.clone(),
+                "1.0.0".to_string(),
+            )]))
+            .install(&package_a.name)
+            .call()
+            .unwrap();
+
+        assert_eq!(
+            new_packages_marked_for_install,
+            IndexSet::from([
+                create_package_marked_for_install()
+                    .repository_package(&package_a)
+                    .call(),
+                create_package_marked_for_install()
+                    .repository_package(&package_b_v1)
+                    .requested_by(&package_a.name)
+                    .call()
+            ])
+        );
+
+        assert_eq!(
+            package_notifications,
+            IndexSet::from([
+                PackageNotification::Added {
+                    repository_package: package_a.clone().into(),
+                    dependency_path: vec![],
+                    forced_install: false,
+                },
+                PackageNotification::PinnedVersion {
+                    repository_package: package_b_v1.clone().into(),
+                },
+                PackageNotification::Added {
+                    repository_package: package_b_v1.into(),
+                    dependency_path: vec![package_a.name],
+                    forced_install: false,
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn install_pinned_version_that_is_not_available_fails() {
+        let package_a = create_repository_package()
+            .name("package-a")
+            .version("2.0.0")
+            .call();
+
+        let error = test_install_state()
+            .with_package_index(vec![&package_a])
+            .with_pins(BTreeMap::from([(
+                package_a.name.clone(),
+                "1.0.0".to_string(),
+            )]))
+            .install(&package_a.name)
+            .call()
+            .unwrap_err();
+
+        match error {
+            libcnb::Error::BuildpackError(
+                DebianPackagesBuildpackError::DeterminePackagesToInstall(error),
+            ) => match *error {
+                DeterminePackagesToInstallError::PinnedVersionNotFound(
+                    package_name,
+                    pinned_version,
+                    available_versions,
+                ) => {
+                    assert_eq!(package_name, "package-a");
+                    assert_eq!(pinned_version, "1.0.0");
+                    assert_eq!(available_versions, vec!["2.0.0".to_string()]);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            },
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
     #[test]
     fn force_install_a_package_already_on_the_system() {
         let package_a = create_repository_package().name("package-a").call();
@@ -1122,19 +2143,633 @@ mod test {
         assert_eq!(
             package_notifications,
             IndexSet::from([PackageNotification::Added {
-                repository_package: package_a,
+                repository_package: package_a.into(),
                 dependency_path: vec![],
                 forced_install: true,
             }])
         );
     }
 
+    #[test]
+    fn skip_optional_package_that_does_not_exist() {
+        let package_a = create_repository_package().name("package-a").call();
+
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(package_a.clone());
+
+        let packages_to_install = resolve_packages_to_install(
+            &package_index,
+            &[],
+            IndexSet::from([
+                RequestedPackage {
+                    name: "package-b".parse().unwrap(),
+                    skip_dependencies: false,
+                    force: false,
+                    optional: true,
+                    arch: None,
+                    suite: None,
+                    include_paths: vec![],
+                    prefix: None,
+                    launch_only: false,
+                },
+                RequestedPackage {
+                    name: package_a.name.parse().unwrap(),
+                    skip_dependencies: false,
+                    force: false,
+                    optional: true,
+                    arch: None,
+                    suite: None,
+                    include_paths: vec![],
+                    prefix: None,
+                    launch_only: false,
+                },
+            ]),
+            &IndexSet::new(),
+            &HashMap::new(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            ResolutionOutput::Print,
+        )
+        .unwrap();
+
+        assert_eq!(packages_to_install, vec![package_a]);
+    }
+
+    #[test]
+    fn resolve_packages_with_injected_policy() {
+        // installs the lowest version of each package and leaves out documentation dependencies
+        struct OldestWithoutDocs;
+
+        impl<'a> ResolutionPolicy<'a> for OldestWithoutDocs {
+            fn select_candidate(
+                &self,
+                package: &str,
+                package_index: &'a PackageIndex,
+                _package_notifications: &mut IndexSet<PackageNotification>,
+            ) -> BuildpackResult<Option<&'a Arc<RepositoryPackage>>> {
+                Ok(package_index.get_packages(package).last().copied())
+            }
+
+            fn filter_dependency(&self, _package: &RepositoryPackage, dependency: &str) -> bool {
+                !dependency.ends_with("-doc")
+            }
+        }
+
+        let library = create_repository_package()
+            .name("libexample1")
+            .version("1.0.0")
+            .call();
+        let docs = create_repository_package().name("example-doc").call();
+        let old_tool = create_repository_package()
+            .name("example")
+            .version("1.0.0")
+            .depends(vec![&library, &docs])
+            .call();
+        let new_tool = create_repository_package()
+            .name("example")
+            .version("2.0.0")
+            .depends(vec![&library, &docs])
+            .call();
+
+        let mut package_index = PackageIndex::default();
+        for package in [&library, &docs, &old_tool, &new_tool] {
+            package_index.add_package(package.clone());
+        }
+
+        let (packages_to_install, _) = resolve_packages_with_policy(
+            &package_index,
+            IndexSet::from([RequestedPackage::from_str("example").unwrap()]),
+            &IndexSet::new(),
+            &OldestWithoutDocs,
+            ResolutionOutput::Print,
+        )
+        .unwrap();
+
+        assert_eq!(packages_to_install, vec![library, old_tool]);
+    }
+
+    #[test]
+    fn resolve_packages_with_composed_policies() {
+        let mawk = create_repository_package().name("mawk").call();
+        let gawk = create_repository_package().name("gawk").call();
+        let old_tool = RepositoryPackage {
+            depends: Some("mawk | gawk".to_string()),
+            ..create_repository_package()
+                .name("example")
+                .version("1.0.0")
+                .call()
+        };
+        let new_tool = RepositoryPackage {
+            depends: Some("mawk | gawk".to_string()),
+            ..create_repository_package()
+                .name("example")
+                .version("2.0.0")
+                .call()
+        };
+
+        let mut package_index = PackageIndex::default();
+        for package in [&mawk, &gawk, &old_tool, &new_tool] {
+            package_index.add_package(package.clone());
+        }
+
+        let locked_versions = HashMap::from([("example".to_string(), "1.0.0".to_string())]);
+        let prefer_alternatives = BTreeMap::from([("gawk".to_string(), "gawk".to_string())]);
+        let policy = LockedVersions {
+            locked_versions: &locked_versions,
+            next: PreferredAlternatives {
+                prefer_alternatives: &prefer_alternatives,
+                next: DefaultPolicy,
+            },
+        };
+
+        let (packages_to_install, _) = resolve_packages_with_policy(
+            &package_index,
+            IndexSet::from([RequestedPackage::from_str("example").unwrap()]),
+            &IndexSet::new(),
+            &policy,
+            ResolutionOutput::Print,
+        )
+        .unwrap();
+
+        assert_eq!(packages_to_install, vec![gawk, old_tool]);
+    }
+
+    #[test]
+    fn simulate_install_lists_packages_with_suites() {
+        let source_list = vec![Source::new(
+            "http://archive.ubuntu.com/ubuntu",
+            vec!["noble", "noble-updates"],
+            vec!["main"],
+            "",
+            ArchitectureName::AMD_64,
+        )];
+        let packages = vec![
+            create_repository_package()
+                .name("libcurl4t64")
+                .version("0:8.5.0-2ubuntu10")
+                .call(),
+            create_repository_package()
+                .name("curl")
+                .version("8.5.0-2ubuntu10.6")
+                .source_order(SourceOrder::new(0, 1, 0))
+                .call(),
+            create_repository_package()
+                .name("tool")
+                .version("2.0.0")
+                .source_order(SourceOrder::new(1, 0, 0))
+                .call(),
+        ];
+
+        assert_eq!(
+            simulate_install(&packages, &source_list),
+            vec![
+                "Inst libcurl4t64 (8.5.0-2ubuntu10 noble)",
+                "Inst curl (8.5.0-2ubuntu10.6 noble-updates)",
+                "Inst tool (2.0.0)",
+            ]
+        );
+    }
+
+    #[test]
+    fn install_package_from_requested_suite() {
+        let source_list = vec![Source::new(
+            "http://archive.ubuntu.com/ubuntu",
+            vec!["noble", "noble-backports"],
+            vec!["main"],
+            "",
+            ArchitectureName::AMD_64,
+        )];
+        let release_package = create_repository_package()
+            .name("cmake")
+            .version("3.28.3-1build7")
+            .call();
+        let backports_package = create_repository_package()
+            .name("cmake")
+            .version("3.30.0-1~24.04")
+            .source_order(SourceOrder::new(0, 1, 0))
+            .call();
+
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(release_package.clone());
+        package_index.add_package(backports_package.clone());
+        package_index.deprioritize_not_automatic(&HashSet::from([SourceOrder::new(0, 1, 0)]));
+
+        let requested_package = |suite: Option<&str>| RequestedPackage {
+            name: "cmake".parse().unwrap(),
+            skip_dependencies: false,
+            force: false,
+            optional: false,
+            arch: None,
+            suite: suite.map(ToString::to_string),
+            include_paths: vec![],
+            prefix: None,
+            launch_only: false,
+        };
+        let resolve = |requested_package: RequestedPackage| {
+            resolve_packages_to_install(
+                &package_index,
+                &source_list,
+                IndexSet::from([requested_package]),
+                &IndexSet::new(),
+                &HashMap::new(),
+                &BTreeMap::new(),
+                &BTreeMap::new(),
+                ResolutionOutput::Print,
+            )
+        };
+
+        assert_eq!(
+            resolve(requested_package(None)).unwrap(),
+            vec![release_package]
+        );
+        assert_eq!(
+            resolve(requested_package(Some("noble-backports"))).unwrap(),
+            vec![backports_package]
+        );
+        let error = resolve(requested_package(Some("noble-proposed"))).unwrap_err();
+        assert!(matches!(
+            error,
+            libcnb::Error::BuildpackError(DebianPackagesBuildpackError::DeterminePackagesToInstall(
+                ref error
+            )) if matches!(
+                **error,
+                DeterminePackagesToInstallError::PackageNotFoundInSuite(_, ref suite, ref available_suites)
+                    if suite == "noble-proposed" && available_suites == &["noble", "noble-backports"]
+            )
+        ));
+    }
+
+    #[test]
+    fn install_package_requested_with_different_case() {
+        let package_a = create_repository_package().name("imagemagick").call();
+
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(package_a.clone());
+
+        let packages_to_install = resolve_packages_to_install(
+            &package_index,
+            &[],
+            IndexSet::from([RequestedPackage {
+                name: "ImageMagick".parse().unwrap(),
+                skip_dependencies: false,
+                force: false,
+                optional: false,
+                arch: None,
+                suite: None,
+                include_paths: vec![],
+                prefix: None,
+                launch_only: false,
+            }]),
+            &IndexSet::new(),
+            &HashMap::new(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            ResolutionOutput::Print,
+        )
+        .unwrap();
+
+        assert_eq!(packages_to_install, vec![package_a]);
+    }
+
+    #[test]
+    fn install_required_package_that_does_not_exist_fails() {
+        let package_index = PackageIndex::default();
+
+        let error = resolve_packages_to_install(
+            &package_index,
+            &[],
+            IndexSet::from([RequestedPackage {
+                name: "package-a".parse().unwrap(),
+                skip_dependencies: false,
+                force: false,
+                optional: false,
+                arch: None,
+                suite: None,
+                include_paths: vec![],
+                prefix: None,
+                launch_only: false,
+            }]),
+            &IndexSet::new(),
+            &HashMap::new(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            ResolutionOutput::Print,
+        )
+        .unwrap_err();
+
+        assert!(matches!(
+            error,
+            libcnb::Error::BuildpackError(DebianPackagesBuildpackError::DeterminePackagesToInstall(
+                ref error
+            )) if matches!(**error, DeterminePackagesToInstallError::PackageNotFound(..))
+        ));
+    }
+
+    #[test]
+    fn check_essential_packages_fails_when_configured() {
+        let package_a = create_repository_package().name("package-a").call();
+        let libc6 = RepositoryPackage {
+            essential: true,
+            ..create_repository_package().name("libc6").call()
+        };
+        let packages_to_install = vec![package_a, libc6];
+
+        assert!(
+            check_essential_packages(&packages_to_install, &EssentialPackagesMode::Allow).is_ok()
+        );
+        assert!(
+            check_essential_packages(&packages_to_install, &EssentialPackagesMode::Warn).is_ok()
+        );
+        match check_essential_packages(&packages_to_install, &EssentialPackagesMode::Fail) {
+            Err(libcnb::Error::BuildpackError(
+                DebianPackagesBuildpackError::DeterminePackagesToInstall(error),
+            )) => match *error {
+                DeterminePackagesToInstallError::EssentialPackages(packages) => {
+                    assert_eq!(packages, vec![format!("libc6 ({DEFAULT_VERSION})")]);
+                }
+                e => panic!("Not the expected error - {e:?}"),
+            },
+            result => panic!("Not the expected result - {result:?}"),
+        }
+    }
+
+    #[test]
+    fn install_foreign_architecture_package_with_dependencies() {
+        let mut package_a = create_repository_package().name("package-a").call();
+        package_a.qualify_architecture(&ArchitectureName::I_386);
+        let mut package_b = create_repository_package()
+            .name("package-b")
+            .depends(vec![&create_repository_package().name("package-a").call()])
+            .call();
+        package_b.qualify_architecture(&ArchitectureName::I_386);
+        // the native package with the same name should not be used to satisfy the dependency
+        let native_package_a = create_repository_package().name("package-a").call();
+
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(package_a.clone());
+        package_index.add_package(package_b.clone());
+        package_index.add_package(native_package_a);
+
+        let packages_to_install = resolve_packages_to_install(
+            &package_index,
+            &[],
+            IndexSet::from([RequestedPackage {
+                name: "package-b".parse().unwrap(),
+                skip_dependencies: false,
+                force: false,
+                optional: false,
+                arch: Some(ArchitectureName::I_386),
+                suite: None,
+                include_paths: vec![],
+                prefix: None,
+                launch_only: false,
+            }]),
+            &IndexSet::new(),
+            &HashMap::new(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            ResolutionOutput::Print,
+        )
+        .unwrap();
+
+        assert_eq!(packages_to_install, vec![package_a, package_b]);
+    }
+
+    #[test]
+    fn resolve_packages_with_dependency_provenance() {
+        let package_c = create_repository_package().name("package-c").call();
+        let package_b = create_repository_package()
+            .name("package-b")
+            .depends(vec![&package_c])
+            .call();
+        let package_a = create_repository_package()
+            .name("package-a")
+            .depends(vec![&package_b])
+            .call();
+
+        let mut package_index = PackageIndex::default();
+        package_index.add_package(package_a.clone());
+        package_index.add_package(package_b.clone());
+        package_index.add_package(package_c.clone());
+
+        let (packages_to_install, provenance) = resolve_packages_with_provenance(
+            &package_index,
+            &[],
+            IndexSet::from([RequestedPackage {
+                name: "package-a".parse().unwrap(),
+                skip_dependencies: false,
+                force: false,
+                optional: false,
+                arch: None,
+                suite: None,
+                include_paths: vec![],
+                prefix: None,
+                launch_only: false,
+            }]),
+            &IndexSet::new(),
+            &HashMap::new(),
+            &BTreeMap::new(),
+            &BTreeMap::new(),
+            ResolutionOutput::Print,
+        )
+        .unwrap();
+
+        assert_eq!(packages_to_install, vec![package_c, package_b, package_a]);
+        assert_eq!(
+            provenance,
+            HashMap::from([
+                (
+                    "package-a".to_string(),
+                    PackageProvenance {
+                        requested_by: "package-a".to_string(),
+                        dependency_path: vec![],
+                    }
+                ),
+                (
+                    "package-b".to_string(),
+                    PackageProvenance {
+                        requested_by: "package-a".to_string(),
+                        dependency_path: vec!["package-a".to_string()],
+                    }
+                ),
+                (
+                    "package-c".to_string(),
+                    PackageProvenance {
+                        requested_by: "package-a".to_string(),
+                        dependency_path: vec!["package-a".to_string(), "package-b".to_string()],
+                    }
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn sort_packages_to_install_topologically() {
+        let package_d = create_repository_package()
+            .name("package-d")
+            .provides(vec!["virtual-package"])
+            .call();
+        let package_c = RepositoryPackage {
+            depends: Some("virtual-package, package-unknown".to_string()),
+            ..create_repository_package().name("package-c").call()
+        };
+        let package_b = create_repository_package()
+            .name("package-b")
+            .depends(vec![&package_c])
+            .call();
+        let package_a = create_repository_package()
+            .name("package-a")
+            .depends(vec![&package_b, &package_d])
+            .call();
+        let package_z = create_repository_package().name("package-z").call();
+
+        let sorted_packages = sort_topologically(
+            vec![
+                package_a.clone(),
+                package_z.clone(),
+                package_b.clone(),
+                package_c.clone(),
+                package_d.clone(),
+            ],
+            &PackageIndex::default(),
+        );
+
+        assert_eq!(
+            sorted_packages,
+            vec![package_d, package_c, package_b, package_a, package_z]
+        );
+    }
+
+    #[test]
+    fn sort_packages_to_install_topologically_with_dependency_cycle() {
+        let package_a = RepositoryPackage {
+            depends: Some("package-b".to_string()),
+            ..create_repository_package().name("package-a").call()
+        };
+        let package_b = RepositoryPackage {
+            depends: Some("package-a".to_string()),
+            ..create_repository_package().name("package-b").call()
+        };
+        let package_c = RepositoryPackage {
+            depends: Some("package-b".to_string()),
+            ..create_repository_package().name("package-c").call()
+        };
+
+        for packages in [
+            vec![package_c.clone(), package_b.clone(), package_a.clone()],
+            vec![package_a.clone(), package_c.clone(), package_b.clone()],
+        ] {
+            assert_eq!(
+                sort_topologically(packages, &PackageIndex::default()),
+                vec![package_a.clone(), package_b.clone(), package_c.clone()]
+            );
+        }
+    }
+
+    #[test]
+    fn install_any_dependency_from_provider_allowing_it() {
+        let package_a = RepositoryPackage {
+            depends: Some("virtual-package:any".to_string()),
+            ..create_repository_package().name("package-a").call()
+        };
+        let provider_1 = create_repository_package()
+            .name("provider-1")
+            .provides(vec!["virtual-package"])
+            .call();
+        let provider_2 = create_repository_package()
+            .name("provider-2")
+            .provides(vec!["virtual-package"])
+            .multi_arch(MultiArch::Allowed)
+            .call();
+
+        let (packages_marked_for_install, _) = test_install_state()
+            .with_package_index(vec![&package_a, &provider_1, &provider_2])
+            .install(&package_a.name)
+            .call()
+            .unwrap();
+
+        assert_eq!(
+            packages_marked_for_install,
+            IndexSet::from([
+                create_package_marked_for_install()
+                    .repository_package(&package_a)
+                    .call(),
+                create_package_marked_for_install()
+                    .repository_package(&provider_2)
+                    .requested_by(&package_a.name)
+                    .call(),
+            ])
+        );
+    }
+
+    #[test]
+    fn install_foreign_dependency_from_native_package_when_allowed() {
+        let mut package_a = create_repository_package()
+            .name("package-a")
+            .depends(vec![
+                &create_repository_package().name("package-b").call(),
+                &create_repository_package().name("package-c").call(),
+                &create_repository_package().name("package-d").call(),
+            ])
+            .call();
+        package_a.qualify_architecture(&ArchitectureName::I_386);
+
+        // marked as foreign so the native package satisfies dependencies from any architecture
+        let native_package_b = create_repository_package()
+            .name("package-b")
+            .multi_arch(MultiArch::Foreign)
+            .call();
+        let mut package_b = native_package_b.clone();
+        package_b.qualify_architecture(&ArchitectureName::I_386);
+
+        // architecture-independent packages are the same for every architecture
+        let native_package_c = create_repository_package()
+            .name("package-c")
+            .architecture("all")
+            .call();
+        let mut package_c = native_package_c.clone();
+        package_c.qualify_architecture(&ArchitectureName::I_386);
+
+        // everything else needs the package from the same architecture
+        let native_package_d = create_repository_package()
+            .name("package-d")
+            .multi_arch(MultiArch::Same)
+            .call();
+        let mut package_d = native_package_d.clone();
+        package_d.qualify_architecture(&ArchitectureName::I_386);
+
+        let (packages_marked_for_install, _) = test_install_state()
+            .with_package_index(vec![
+                &package_a,
+                &native_package_b,
+                &package_b,
+                &native_package_c,
+                &package_c,
+                &native_package_d,
+                &package_d,
+            ])
+            .install(&package_a.name)
+            .call()
+            .unwrap();
+
+        assert_eq!(
+            packages_marked_for_install
+                .iter()
+                .map(|package| package.repository_package.name.as_str())
+                .collect::<HashSet<_>>(),
+            HashSet::from(["package-a:i386", "package-b", "package-c", "package-d:i386"])
+        );
+    }
+
     #[builder]
     fn test_install_state(
         install: &str,
         with_package_index: Vec<&RepositoryPackage>,
         with_installed: Option<IndexSet<PackageMarkedForInstall>>,
         with_system_packages: Option<IndexSet<SystemPackage>>,
+        with_locked_versions: Option<HashMap<String, String>>,
+        with_prefer_alternatives: Option<BTreeMap<String, String>>,
+        with_pins: Option<BTreeMap<String, String>>,
         skip_dependencies: Option<bool>,
         force: Option<bool>,
     ) -> BuildpackResult<(
@@ -1158,6 +2793,23 @@ mod test {
 
         let system_packages = with_system_packages.unwrap_or_default();
 
+        let locked_versions = with_locked_versions.unwrap_or_default();
+
+        let prefer_alternatives = with_prefer_alternatives.unwrap_or_default();
+
+        let pins = with_pins.unwrap_or_default();
+
+        let policy = PinnedVersions {
+            pins: &pins,
+            next: LockedVersions {
+                locked_versions: &locked_versions,
+                next: PreferredAlternatives {
+                    prefer_alternatives: &prefer_alternatives,
+                    next: DefaultPolicy,
+                },
+            },
+        };
+
         let mut package_notifications = IndexSet::new();
 
         let mut visit_stack = IndexSet::new();
@@ -1168,6 +2820,7 @@ mod test {
             force,
             &system_packages,
             &package_index,
+            &policy,
             &mut packages_marked_for_install,
             &mut visit_stack,
             &mut package_notifications,
@@ -1181,40 +2834,13 @@ mod test {
         Ok((new_packages_marked_for_install, package_notifications))
     }
 
-    #[builder]
-    fn create_repository_package(
-        name: &str,
-        version: Option<&str>,
-        provides: Option<Vec<&str>>,
-        depends: Option<Vec<&RepositoryPackage>>,
-        pre_depends: Option<Vec<&RepositoryPackage>>,
-    ) -> RepositoryPackage {
-        let join_deps = |vs: Vec<&RepositoryPackage>| {
-            vs.iter()
-                .map(|v| v.name.as_str())
-                .collect::<Vec<_>>()
-                .join(",")
-        };
-        RepositoryPackage {
-            name: name.to_string(),
-            version: version.unwrap_or(DEFAULT_VERSION).parse().unwrap(),
-            provides: provides.map(|vs| vs.join(",")),
-            repository_uri: RepositoryUri::from(""),
-            source_order: SourceOrder::new(0, 0, 0),
-            sha256sum: String::new(),
-            depends: depends.map(join_deps),
-            pre_depends: pre_depends.map(join_deps),
-            filename: String::new(),
-        }
-    }
-
     #[builder]
     fn create_package_marked_for_install(
         repository_package: &RepositoryPackage,
         requested_by: Option<&str>,
     ) -> PackageMarkedForInstall {
         PackageMarkedForInstall {
-            repository_package: repository_package.clone(),
+            repository_package: repository_package.clone().into(),
             requested_by: requested_by.unwrap_or(&repository_package.name).to_string(),
         }
     }
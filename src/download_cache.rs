@@ -0,0 +1,256 @@
+use crate::debian::RepositoryPackage;
+use crate::install_packages::calculate_checksum;
+use crate::{BuildpackResult, DebianPackagesBuildpack, format_size, is_clean_cache_requested};
+use libcnb::build::BuildContext;
+use libcnb::data::layer_name;
+use libcnb::layer::{CachedLayerDefinition, InvalidMetadataAction, RestoredLayerAction};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+// NOTE: Packages downloaded from a repository are kept in a cached layer so that changing a single
+//       package doesn't require downloading every other package again. A package is reused when
+//       its file is still in the cache and matches the checksum from the package index. Without a
+//       maximum size for the download cache only the packages installed by the most recent build
+//       are kept. With one, the modification time of each file is updated whenever it's used so
+//       the least recently used packages can be evicted once the cache grows past its maximum size.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+struct DownloadCacheMetadata {}
+
+pub(crate) fn create_download_cache_layer(
+    context: &BuildContext<DebianPackagesBuildpack>,
+) -> BuildpackResult<PathBuf> {
+    let clean_cache = is_clean_cache_requested();
+
+    let download_cache_layer = context.cached_layer(
+        layer_name!("download-cache"),
+        CachedLayerDefinition {
+            build: false,
+            launch: false,
+            invalid_metadata_action: &|_| InvalidMetadataAction::DeleteLayer,
+            restored_layer_action: &|_: &DownloadCacheMetadata, _| {
+                if clean_cache {
+                    RestoredLayerAction::DeleteLayer
+                } else {
+                    RestoredLayerAction::KeepLayer
+                }
+            },
+        },
+    )?;
+    download_cache_layer.write_metadata(DownloadCacheMetadata::default())?;
+
+    Ok(download_cache_layer.path())
+}
+
+// Returns the path to the cached download of the package if it's present and unchanged.
+pub(crate) async fn get_cached_download(
+    download_cache_dir: &Path,
+    repository_package: &RepositoryPackage,
+) -> Option<PathBuf> {
+    let cached_download_path = download_cache_dir.join(repository_package.pool_file_name()?);
+    if !cached_download_path.is_file() {
+        return None;
+    }
+
+    let checksum = calculate_checksum(&cached_download_path, repository_package.checksum.hasher())
+        .await
+        .ok();
+    if checksum.as_deref() != Some(repository_package.checksum.value()) {
+        let _ = fs::remove_file(&cached_download_path);
+        return None;
+    }
+
+    // failing to record the use only affects the order packages are evicted in
+    let _ = fs::File::options()
+        .write(true)
+        .open(&cached_download_path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+
+    Some(cached_download_path)
+}
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct DownloadCacheUsage {
+    pub(crate) size: u64,
+    pub(crate) max_size: u64,
+    pub(crate) evicted: usize,
+}
+
+impl Display for DownloadCacheUsage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Download cache is using {size} of {max_size}",
+            size = format_size(self.size),
+            max_size = format_size(self.max_size)
+        )?;
+        if self.evicted > 0 {
+            write!(
+                f,
+                " after removing {evicted} least recently used packages",
+                evicted = self.evicted
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// Eviction is best-effort since a file that can't be read or removed only means the cache is
+// larger than it should be until the next build.
+pub(crate) fn evict_least_recently_used(
+    download_cache_dir: &Path,
+    max_size: u64,
+) -> DownloadCacheUsage {
+    let mut cached_downloads = fs::read_dir(download_cache_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok().filter(fs::Metadata::is_file)?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            Some((entry.path(), modified, metadata.len()))
+        })
+        .collect::<Vec<_>>();
+    cached_downloads.sort_by_key(|(_, modified, _)| *modified);
+
+    let mut usage = DownloadCacheUsage {
+        size: cached_downloads.iter().map(|(_, _, size)| size).sum(),
+        max_size,
+        evicted: 0,
+    };
+
+    for (path, _, size) in cached_downloads {
+        if usage.size <= max_size {
+            break;
+        }
+        if fs::remove_file(path).is_ok() {
+            usage.size -= size;
+            usage.evicted += 1;
+        }
+    }
+
+    usage
+}
+
+// Removes every cached download except the given files, returning how many were removed. Like
+// eviction, this is best-effort.
+pub(crate) fn retain_cached_downloads(
+    download_cache_dir: &Path,
+    file_names: &HashSet<String>,
+) -> usize {
+    fs::read_dir(download_cache_dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| {
+            !entry
+                .file_name()
+                .to_str()
+                .is_some_and(|file_name| file_names.contains(file_name))
+        })
+        .filter(|entry| fs::remove_file(entry.path()).is_ok())
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn evict_least_recently_used_removes_oldest_downloads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let download_cache_dir = temp_dir.path();
+        let now = SystemTime::now();
+        for (file, age_in_days) in [
+            ("oldest_1.0_amd64.deb", 3),
+            ("newest_1.0_amd64.deb", 0),
+            ("older_1.0_amd64.deb", 2),
+            ("newer_1.0_amd64.deb", 1),
+        ] {
+            let path = download_cache_dir.join(file);
+            fs::write(&path, [0; 100]).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(now - Duration::from_hours(24 * age_in_days))
+                .unwrap();
+        }
+
+        assert_eq!(
+            evict_least_recently_used(download_cache_dir, 250),
+            DownloadCacheUsage {
+                size: 200,
+                max_size: 250,
+                evicted: 2,
+            }
+        );
+        assert!(!download_cache_dir.join("oldest_1.0_amd64.deb").exists());
+        assert!(!download_cache_dir.join("older_1.0_amd64.deb").exists());
+        assert!(download_cache_dir.join("newer_1.0_amd64.deb").exists());
+        assert!(download_cache_dir.join("newest_1.0_amd64.deb").exists());
+    }
+
+    #[test]
+    fn retain_cached_downloads_removes_other_downloads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let download_cache_dir = temp_dir.path();
+        for file in [
+            "curl_8.5.0-2ubuntu10.6_amd64.deb",
+            "curl_8.5.0-2ubuntu10_amd64.deb",
+            "removed_1.0_amd64.deb",
+        ] {
+            fs::write(download_cache_dir.join(file), [0; 100]).unwrap();
+        }
+
+        assert_eq!(
+            retain_cached_downloads(
+                download_cache_dir,
+                &HashSet::from(["curl_8.5.0-2ubuntu10.6_amd64.deb".to_string()])
+            ),
+            2
+        );
+        assert!(
+            download_cache_dir
+                .join("curl_8.5.0-2ubuntu10.6_amd64.deb")
+                .exists()
+        );
+        assert!(
+            !download_cache_dir
+                .join("curl_8.5.0-2ubuntu10_amd64.deb")
+                .exists()
+        );
+        assert!(!download_cache_dir.join("removed_1.0_amd64.deb").exists());
+    }
+
+    #[test]
+    fn evict_least_recently_used_under_max_size() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::write(temp_dir.path().join("package_1.0_amd64.deb"), [0; 100]).unwrap();
+        assert_eq!(
+            evict_least_recently_used(temp_dir.path(), 100),
+            DownloadCacheUsage {
+                size: 100,
+                max_size: 100,
+                evicted: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn download_cache_usage_display() {
+        assert_eq!(
+            DownloadCacheUsage {
+                size: 512 * 1024 * 1024,
+                max_size: 1024 * 1024 * 1024,
+                evicted: 3,
+            }
+            .to_string(),
+            "Download cache is using 512.0 MiB of 1024.0 MiB after removing 3 least recently used packages"
+        );
+    }
+}
@@ -1,27 +1,76 @@
+use crate::check_security_advisories::CheckSecurityAdvisoriesError;
+use crate::cli::CliError;
+use crate::client_identity::ClientIdentityError;
 use crate::config::custom_source::ParseCustomSourceError;
-use crate::config::download_url::ParseDownloadUrlError;
-use crate::config::{ConfigError, NAMESPACED_CONFIG, ParseConfigError, ParseRequestedPackageError};
+use crate::config::download_url::{DownloadHeaderValue, ParseDownloadUrlError};
+use crate::config::{
+    CONFIG_KEYS, ConfigError, ENV_ORDER_VARS, NAMESPACED_CONFIG, ParseConfigError,
+    ParseRequestedPackageError, SCHEMA_VERSIONS, STANDALONE_CONFIG_FILE,
+};
+use crate::contents_index::ContentsIndexError;
 use crate::create_package_index::CreatePackageIndexError;
-use crate::debian::UnsupportedDistroError;
+use crate::debian::{ParseRepositoryPackageError, UnsupportedDistroError};
 use crate::determine_packages_to_install::DeterminePackagesToInstallError;
 use crate::errors::ErrorType::{Framework, Internal, UserFacing};
-use crate::install_packages::InstallPackagesError;
+use crate::hooks::{HOOK_LAYER_DIR_ENV, HOOK_MANIFEST_ENV, HookError};
+use crate::host_credentials::{
+    BINDING_TYPE, CREDENTIALS_ENV, HostCredentialsError, NETRC_BINDING_TYPE, NETRC_ENV,
+};
+use crate::install_packages::{InstallPackagesError, UnsafeTarballEntry};
+use crate::lockfile::LockfileError;
+use crate::package_signatures::PackageSignatureError;
+use crate::retry::request_attempts;
+use crate::s3::S3Error;
+use crate::vendor::VendorError;
 use crate::{DebianPackagesBuildpackError, DetectError};
 use bon::builder;
-use bullet_stream::{Print, global::print, style};
+use bullet_stream::{Print, global::print, strip_ansi, style};
 use indoc::{formatdoc, indoc};
 use libcnb::Error;
 use std::collections::BTreeSet;
-use std::fmt;
 use std::path::Path;
+use std::{fmt, fs};
 
 const BUILDPACK_NAME: &str = "Heroku .deb Packages buildpack";
 
+// NOTE: Every error has a stable code (e.g.; `DEB_PKG_E012`) so platform tooling can classify
+//       failed builds without matching on the message. The code is printed with the message and
+//       written to this file in the layers directory. Codes are never renumbered or reused once
+//       released, so a new error takes the next unused code.
+const ERROR_REPORT_FILE: &str = "deb-packages-error.json";
+
 pub(crate) fn on_error(error: Error<DebianPackagesBuildpackError>) {
-    print_error(match error {
+    let error_message = to_error_message(error);
+    // the lifecycle sets the layers directory for builds, it's unset when running the CLI
+    if let Some(layers_dir) = std::env::var_os("CNB_LAYERS_DIR") {
+        write_error_report(
+            &Path::new(&layers_dir).join(ERROR_REPORT_FILE),
+            &error_message,
+        );
+    }
+    print_error(error_message);
+}
+
+// the error code and the message without any styling, for errors returned by the library API
+pub(crate) fn describe_error(error: Error<DebianPackagesBuildpackError>) -> (&'static str, String) {
+    let error_message = to_error_message(error);
+    (error_message.code, strip_ansi(error_message.to_string()))
+}
+
+fn to_error_message(error: Error<DebianPackagesBuildpackError>) -> ErrorMessage {
+    match error {
         Error::BuildpackError(e) => on_buildpack_error(e),
         e => on_framework_error(&e),
+    }
+}
+
+// A failure to write the report is ignored so it can't hide the error being reported.
+fn write_error_report(report_file: &Path, error_message: &ErrorMessage) {
+    let report = serde_json::json!({
+        "code": error_message.code,
+        "header": strip_ansi(&error_message.header),
     });
+    let _ = fs::write(report_file, report.to_string());
 }
 
 fn on_buildpack_error(error: DebianPackagesBuildpackError) -> ErrorMessage {
@@ -29,11 +78,22 @@ fn on_buildpack_error(error: DebianPackagesBuildpackError) -> ErrorMessage {
         DebianPackagesBuildpackError::Config(e) => on_config_error(e),
         DebianPackagesBuildpackError::UnsupportedDistro(e) => on_unsupported_distro_error(e),
         DebianPackagesBuildpackError::CreatePackageIndex(e) => on_create_package_index_error(e),
+        DebianPackagesBuildpackError::ContentsIndex(e) => on_contents_index_error(e),
         DebianPackagesBuildpackError::DeterminePackagesToInstall(e) => {
             on_determine_packages_to_install_error(*e)
         }
         DebianPackagesBuildpackError::InstallPackages(e) => on_install_packages_error(*e),
         DebianPackagesBuildpackError::Detect(e) => on_detect_error(e),
+        DebianPackagesBuildpackError::Cli(e) => on_cli_error(e),
+        DebianPackagesBuildpackError::Lockfile(e) => on_lockfile_error(e),
+        DebianPackagesBuildpackError::Vendor(e) => on_vendor_error(e),
+        DebianPackagesBuildpackError::CheckSecurityAdvisories(e) => {
+            on_check_security_advisories_error(e)
+        }
+        DebianPackagesBuildpackError::ClientIdentity(e) => on_client_identity_error(e),
+        DebianPackagesBuildpackError::HostCredentials(e) => on_host_credentials_error(e),
+        DebianPackagesBuildpackError::S3(e) => on_s3_error(e),
+        DebianPackagesBuildpackError::Hook(e) => on_hook_error(e),
     }
 }
 
@@ -43,6 +103,7 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
         ConfigError::ReadConfig(config_file, e) => {
             let config_file = file_value(config_file);
             create_error()
+                .code("DEB_PKG_E001")
                 .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
                 .header(format!("Error reading {config_file}"))
                 .body(formatdoc! { "
@@ -70,6 +131,7 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
             match error {
                 ParseConfigError::InvalidToml(error) => {
                     create_error()
+                        .code("DEB_PKG_E002")
                         .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
                         .header(format!("Error parsing {config_file} with invalid TOML file"))
                         .body(formatdoc! { "
@@ -85,6 +147,7 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
 
                 ParseConfigError::WrongConfigType => {
                     create_error()
+                        .code("DEB_PKG_E003")
                         .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
                         .header(format!("Error parsing {config_file} with invalid key"))
                         .body(formatdoc! { "
@@ -106,6 +169,7 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
                         let invalid_package_name = style::value(error.package_name);
 
                         create_error()
+                            .code("DEB_PKG_E004")
                             .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
                             .header(format!("Error parsing {config_file} with invalid package name"))
                             .body(formatdoc! { "
@@ -133,6 +197,7 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
                         let value = style::value(value.to_string());
 
                         create_error()
+                            .code("DEB_PKG_E005")
                             .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
                             .header(format!("Error parsing {config_file} with invalid package format"))
                             .body(formatdoc! { "
@@ -153,10 +218,111 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
                             .debug_info(format!("Invalid type {value_type} with value {value}"))
                             .call()
                     }
+
+                    ParseRequestedPackageError::InvalidArchitecture(arch) => {
+                        let arch = style::value(arch);
+                        let inline_table_example =
+                            r#"{ name = "libstdc++6", arch = "i386" }"#;
+
+                        create_error()
+                            .code("DEB_PKG_E006")
+                            .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                            .header(format!("Error parsing {config_file} with invalid package architecture"))
+                            .body(formatdoc! { "
+                                The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                                complete the build but we found an invalid architecture {arch} for \
+                                a package in the key {root_config_key}.
+
+                                Packages are installed for the architecture being built by default. \
+                                The architecture can only be set to install a package for a foreign \
+                                architecture (e.g.; {inline_table_example}) and the supported values are:
+                                - \"i386\" (when building for amd64)
+
+                                Suggestions:
+                                - See the buildpack documentation for the proper usage for this configuration at \
+                                {configuration_doc_url}
+                            " })
+                            .call()
+                    }
+
+                    ParseRequestedPackageError::InvalidSuite(suite) => {
+                        let suite = style::value(suite);
+                        let inline_table_example =
+                            r#"{ name = "cmake", suite = "noble-backports" }"#;
+
+                        create_error()
+                            .code("DEB_PKG_E007")
+                            .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                            .header(format!("Error parsing {config_file} with invalid package suite"))
+                            .body(formatdoc! { "
+                                The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                                complete the build but we found an invalid suite {suite} for \
+                                a package in the key {root_config_key}.
+
+                                The suite must be a non-empty string naming one of the suites the \
+                                package index is built from (e.g.; {inline_table_example}).
+
+                                Suggestions:
+                                - See the buildpack documentation for the proper usage for this configuration at \
+                                {configuration_doc_url}
+                            " })
+                            .call()
+                    }
+
+                    ParseRequestedPackageError::InvalidIncludePaths(include_paths) => {
+                        let include_paths = style::value(include_paths);
+                        let parent_dir = style::value("..");
+                        let inline_table_example = r#"{ name = "chromium-codecs-ffmpeg-extra", include_paths = ["usr/lib/chromium/**"] }"#;
+
+                        create_error()
+                            .code("DEB_PKG_E008")
+                            .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                            .header(format!("Error parsing {config_file} with invalid include paths"))
+                            .body(formatdoc! { "
+                                The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                                complete the build but we found invalid include paths {include_paths} for \
+                                a package in the key {root_config_key}.
+
+                                The include paths must be a non-empty array of glob patterns matching the \
+                                files to extract from the package (e.g.; {inline_table_example}). Patterns \
+                                can't contain {parent_dir} segments.
+
+                                Suggestions:
+                                - See the buildpack documentation for the proper usage for this configuration at \
+                                {configuration_doc_url}
+                            " })
+                            .call()
+                    }
+
+                    ParseRequestedPackageError::InvalidPrefix(prefix) => {
+                        let prefix = style::value(prefix);
+                        let parent_dir = style::value("..");
+                        let inline_table_example = r#"{ name = "wkhtmltopdf", prefix = "opt/wkhtmltopdf" }"#;
+
+                        create_error()
+                            .code("DEB_PKG_E009")
+                            .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                            .header(format!("Error parsing {config_file} with invalid install prefix"))
+                            .body(formatdoc! { "
+                                The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                                complete the build but we found an invalid install prefix {prefix} for \
+                                a package in the key {root_config_key}.
+
+                                The install prefix must be a string naming the directory within the layer \
+                                to extract the package into (e.g.; {inline_table_example}). It can't be \
+                                the root of the layer or contain {parent_dir} segments.
+
+                                Suggestions:
+                                - See the buildpack documentation for the proper usage for this configuration at \
+                                {configuration_doc_url}
+                            " })
+                            .call()
+                    }
                 },
 
                 ParseConfigError::MissingNamespacedConfig => {
                     create_error()
+                        .code("DEB_PKG_E010")
                         .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
                         .header(format!("Error parsing {config_file} with invalid key"))
                         .body(formatdoc! { "
@@ -176,6 +342,7 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
                 ParseConfigError::ParseCustomSource(error) => {
                     let custom_source_array_of_tables_key = "[[com.heroku.buildpacks.deb-packages.sources]]";
                     create_error()
+                        .code("DEB_PKG_E011")
                         .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
                         .header(format!("Error parsing {config_file} with invalid custom source"))
                         .body(formatdoc! { r#"
@@ -238,14 +405,42 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
                                 ---
                                 {e}
                             " },
+                            ParseCustomSourceError::IncompleteClientIdentity(table) => formatdoc! { "
+                                The \"client_cert\" and \"client_key\" fields must both be set to use mutual TLS for the following custom source:
+                                {custom_source_array_of_tables_key}
+                                {table}
+                            " },
                         })
                         .call()
                 }
 
+                ParseConfigError::MissingDownloadChecksum(url) => {
+                    let url = style::value(url);
+                    let require_checksums = style::value("require_checksums = true");
+                    let sha256_example = style::value(
+                        "{ url = \"https://example.com/package-1.2.3.deb\", sha256 = \"<checksum>\" }",
+                    );
+                    create_error()
+                        .code("DEB_PKG_E012")
+                        .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with missing download checksum"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but the download url {url} in the key {root_config_key} \
+                            doesn't have a checksum, which is required when {require_checksums} is set.
+
+                            Suggestions:
+                            - Add the SHA256 checksum of the package to the download url (e.g.; {sha256_example}). \
+                            The checksum is displayed in the build output when a package is downloaded without one.
+                        " })
+                        .call()
+                }
+
                 ParseConfigError::ParseDownloadUrl(error) => match *error {
                     ParseDownloadUrlError::InvalidUrl { url, reason } => {
                         let url = style::value(url);
                         create_error()
+                            .code("DEB_PKG_E013")
                             .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
                             .header(format!("Error parsing {config_file} with invalid download url"))
                             .body(formatdoc! { "
@@ -262,10 +457,12 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
                     }
                     ParseDownloadUrlError::UnexpectedTomlValue(value) => {
                         let string_example = "\"https://example.com/package-1.2.3.deb\"";
+                        let inline_table_example = "{ url = \"https://example.com/package-1.2.3.deb\", headers = { \"Authorization\" = \"env:ARTIFACT_TOKEN\" } }";
                         let value_type = style::value(value.type_name());
                         let value = style::value(value.to_string());
 
                         create_error()
+                            .code("DEB_PKG_E014")
                             .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
                             .header(format!("Error parsing {config_file} with invalid download url"))
                             .body(formatdoc! { "
@@ -275,6 +472,7 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
 
                                 Download urls must either be the following TOML values:
                                 - String (e.g.; {string_example})
+                                - Inline table (e.g.; {inline_table_example})
 
                                 Suggestions:
                                 - See the buildpack documentation for the proper usage for this configuration at \
@@ -284,519 +482,1986 @@ fn on_config_error(error: ConfigError) -> ErrorMessage {
                             .debug_info(format!("Invalid type {value_type} with value {value}"))
                             .call()
                     }
-                }
-            }
-        }
-    }
-}
+                },
 
-fn on_unsupported_distro_error(error: UnsupportedDistroError) -> ErrorMessage {
-    let UnsupportedDistroError {
-        name,
-        version,
-        architecture,
-    } = error;
+                ParseConfigError::InvalidEssentialPackages(value) => {
+                    let value = style::value(value);
+                    let essential_packages_key = style::value("essential_packages");
+                    create_error()
+                        .code("DEB_PKG_E015")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid essential packages mode"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {essential_packages_key} in the key {root_config_key}.
 
-    let buildpack_toml = style::value("buildpack.toml");
-    create_error()
-        .error_type(Internal)
-        .header("Unsupported distribution")
-        .body(formatdoc! { "
-            The {BUILDPACK_NAME} doesn't support the {name} {version} ({architecture}) \
-            distribution. See {buildpack_toml} for the configuration of supported distributions.
-        " })
-        .call()
-}
+                            The value must be one of the following TOML strings:
+                            - \"allow\" (essential packages are installed)
+                            - \"warn\" (the default, essential packages are installed and reported)
+                            - \"fail\" (essential packages are reported and the build fails)
 
-#[allow(clippy::too_many_lines)]
-fn on_create_package_index_error(error: CreatePackageIndexError) -> ErrorMessage {
-    let canonical_status_url = get_canonical_status_url();
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
 
-    match error {
-        CreatePackageIndexError::NoSources => {
-            create_error()
-                .error_type(Internal)
-                .header("No sources to update")
-                .body(indoc! { "
-                    The distribution has no sources to update packages from.
-                " })
-                .call()
-        }
+                ParseConfigError::InvalidSecurityAdvisories(value) => {
+                    let value = style::value(value);
+                    let security_advisories_key = style::value("security_advisories");
+                    create_error()
+                        .code("DEB_PKG_E016")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid security advisories mode"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {security_advisories_key} in the key {root_config_key}.
 
-        CreatePackageIndexError::TaskFailed(e) => {
-            create_error()
-                .error_type(Internal)
-                .header("Task failure while updating sources")
-                .body(indoc! { "
-                    A background task responsible for updating sources failed to complete.
-                " })
-                .debug_info(e.to_string())
-                .call()
-        }
+                            The value must be one of the following TOML strings:
+                            - \"off\" (the default, advisories are not checked)
+                            - \"warn\" (affected packages are reported)
+                            - \"fail\" (affected packages are reported and the build fails)
 
-        CreatePackageIndexError::InvalidLayerName(url, e) => {
-            create_error()
-                .error_type(Internal)
-                .header("Invalid layer name")
-                .body(formatdoc! { "
-                    For caching purposes, a unique layer name is generated for Debian Release files \
-                    and Package indices based on their download urls. The generated name for the \
-                    following url was invalid:
-                    - {url}
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+                ParseConfigError::InvalidIndexMaxAge(value) => {
+                    let value = style::value(value);
+                    let index_max_age_key = style::value("index_max_age");
+                    create_error()
+                        .code("DEB_PKG_E017")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid index max age"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {index_max_age_key} in the key {root_config_key}.
 
-                    You can find the invalid layer name in the debug information above.
-                " })
-                .debug_info(e.to_string())
-                .call()
-        }
+                            The value must be a TOML string containing a whole number followed by \
+                            one of the following units:
+                            - \"s\" (seconds)
+                            - \"m\" (minutes)
+                            - \"h\" (hours)
+                            - \"d\" (days)
 
-        CreatePackageIndexError::GetReleaseRequest(e) => {
-            create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to request Release file")
-                .body(formatdoc! { "
-                    While updating package sources, a request to download a Release file failed. \
-                    This error can occur due to an unstable network connection or an issue with the upstream \
-                    Debian package repository.
+                            For example, \"24h\" will reuse cached package indices for up to 24 hours \
+                            before checking the package repositories for updates.
 
-                    Suggestions:
-                    - Check the status of {canonical_status_url} for any reported issues.
-                " })
-                .debug_info(e.to_string())
-                .call()
-        }
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+                ParseConfigError::InvalidMaxLayerSize(value) => {
+                    let value = style::value(value);
+                    let max_layer_size_key = style::value("max_layer_size");
+                    create_error()
+                        .code("DEB_PKG_E018")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid max layer size"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {max_layer_size_key} in the key {root_config_key}.
 
-        CreatePackageIndexError::ReadGetReleaseResponse(e) => {
-            create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to download Release file")
-                .body(formatdoc! { "
-                    While updating package sources, an error occurred while downloading a Release file. \
-                    This error can occur due to an unstable network connection or an issue with the upstream \
-                    Debian package repository.
+                            The value must be a TOML string containing a whole number followed by \
+                            one of the following units:
+                            - \"B\" (bytes)
+                            - \"KB\", \"MB\", or \"GB\" (kilobytes, megabytes, or gigabytes)
+                            - \"KiB\", \"MiB\", or \"GiB\" (kibibytes, mebibytes, or gibibytes)
 
-                    Suggestions:
-                    - Check the status of {canonical_status_url} for any reported issues.
-                " })
-                .debug_info(e.to_string())
-                .call()
-        }
+                            For example, \"500MB\" will display a warning when the installed \
+                            packages are larger than 500 megabytes.
 
-        CreatePackageIndexError::CreatePgpCertificate(e) => {
-            create_error()
-                .error_type(Internal)
-                .header("Failed to load verifying PGP certificate")
-                .body(indoc! { "
-                    The PGP certificate used to verify downloaded release files failed to load. This \
-                    error indicates there's a problem with the format of the certificate file the \
-                    distribution uses.
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+                ParseConfigError::InvalidMaxDownloadCacheSize(value) => {
+                    let value = style::value(value);
+                    let max_download_cache_size_key = style::value("max_download_cache_size");
+                    create_error()
+                        .code("DEB_PKG_E019")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!(
+                            "Error parsing {config_file} with invalid max download cache size"
+                        ))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {max_download_cache_size_key} in the key {root_config_key}.
 
-                    Suggestions:
-                    - Verify the format of the certificates found in the ./keys directory of this \
-                    buildpack's repository. See https://cirw.in/gpg-decoder
-                    - Extract new certificates by running the ./scripts/extract_keys.sh script found \
-                    in this buildpack's repository.
-                " })
-                .debug_info(e.to_string())
-                .call()
-        }
+                            The value must be a TOML string containing a whole number followed by \
+                            one of the following units:
+                            - \"B\" (bytes)
+                            - \"KB\", \"MB\", or \"GB\" (kilobytes, megabytes, or gigabytes)
+                            - \"KiB\", \"MiB\", or \"GiB\" (kibibytes, mebibytes, or gibibytes)
 
-        CreatePackageIndexError::CreatePgpVerifier(e) => {
-            create_error()
-                .error_type(Internal)
-                .header("Failed to verify Release file")
-                .body(indoc! { "
-                    The PGP signature of the downloaded release file failed verification. This error can \
-                    occur if the maintainers of the Debian repository changed the process for signing \
-                    release files.
+                            For example, \"1GB\" will cache downloaded packages between builds \
+                            until they take up more than 1 gigabyte.
 
-                    Suggestions:
-                    - Verify if the keys changed by running the ./scripts/extract_keys.sh \
-                    script found in this buildpack's repository.
-                " })
-                .debug_info(e.to_string())
-                .call()
-        }
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+                ParseConfigError::InvalidPreferAlternatives(value) => {
+                    let value = style::value(value);
+                    let prefer_alternatives_key = style::value("prefer_alternatives");
+                    create_error()
+                        .code("DEB_PKG_E020")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid preferred alternatives"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {prefer_alternatives_key} in the key {root_config_key}.
 
-        CreatePackageIndexError::WriteReleaseLayer(file, e) => {
-            let file = file_value(file);
+                            The value must be a TOML inline table that maps a dependency name to the \
+                            name of the package to install for it (e.g.; {{ \"debconf-2.0\" = \"cdebconf\" }}).
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+                ParseConfigError::InvalidEnvOrder(value) => {
+                    let value = style::value(value);
+                    let env_order_key = style::value("env_order");
+                    create_error()
+                        .code("DEB_PKG_E021")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid environment order"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {env_order_key} in the key {root_config_key}.
+
+                            The value must be a TOML inline table that maps the name of an \
+                            environment variable configured by the buildpack ({env_order_vars}) to \
+                            either \"prepend\" (the default) or \"append\" (e.g.; {{ PATH = \"append\" }}).
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        ", env_order_vars = ENV_ORDER_VARS.join(", ") })
+                        .call()
+                }
+                ParseConfigError::InvalidPins(value) => {
+                    let value = style::value(value);
+                    let pins_key = style::value("pins");
+                    create_error()
+                        .code("DEB_PKG_E022")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid pinned versions"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {pins_key} in the key {root_config_key}.
+
+                            The value must be a TOML inline table that maps a package name to the \
+                            version of the package to install (e.g.; {{ \"libssl3t64\" = \"3.0.13-0ubuntu3.4\" }}).
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+                ParseConfigError::InvalidDefaultComponents(value) => {
+                    let value = style::value(value);
+                    let default_components_key = style::value("default_components");
+                    create_error()
+                        .code("DEB_PKG_E023")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid default components"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {default_components_key} in the key {root_config_key}.
+
+                            The value must be a TOML array containing one or more of the following \
+                            components:
+                            - \"main\" (Canonical-supported free and open-source software)
+                            - \"universe\" (Community-maintained free and open-source software)
+                            - \"restricted\" (Proprietary drivers for devices)
+                            - \"multiverse\" (Software restricted by copyright or legal issues)
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+                ParseConfigError::InvalidDefaultSourceSignedBy(value) => {
+                    let value = style::value(value);
+                    let default_source_signed_by_key = style::value("default_source_signed_by");
+                    create_error()
+                        .code("DEB_PKG_E024")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid default source signing key"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {default_source_signed_by_key} in the key {root_config_key}.
+
+                            The value must be a TOML string containing either an ASCII-armored OpenPGP \
+                            public key block or the path to a keyring file. It replaces the Ubuntu \
+                            archive signing keys used to verify the Release files of the default sources.
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+                ParseConfigError::InvalidHttpOption(key, value) => {
+                    let expected = match key.as_str() {
+                        "http2_prior_knowledge" => "a TOML boolean (e.g.; true or false)",
+                        "pool_max_idle_per_host" | "max_retries" => {
+                            "a TOML integer that is zero or greater"
+                        }
+                        "retry_jitter" => "one of \"full\", \"bounded\", or \"none\"",
+                        "root_certificates" => "one of \"system\" or \"bundled\"",
+                        "resolve" => {
+                            "a TOML inline table of host names to IP addresses \
+                            (e.g.; { \"archive.ubuntu.com\" = \"185.125.190.81\" })"
+                        }
+                        key if key.starts_with("resolve.") => {
+                            "an IP address or a non-empty array of IP addresses"
+                        }
+                        _ => {
+                            "a TOML string containing a whole number followed by a unit of \
+                            \"s\" (seconds), \"m\" (minutes), \"h\" (hours), or \"d\" (days)"
+                        }
+                    };
+                    let value = style::value(value);
+                    let key = style::value(format!("http.{key}"));
+                    create_error()
+                        .code("DEB_PKG_E025")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid HTTP option"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {key} in the key {root_config_key}.
+
+                            The value must be {expected}.
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+
+                ParseConfigError::InvalidHook(key, value) => {
+                    let value = style::value(value);
+                    let key = style::value(format!("hooks.{key}"));
+                    create_error()
+                        .code("DEB_PKG_E026")
+                        .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid hook"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {key} in the key {root_config_key}.
+
+                            The value must be a TOML string containing the command to run \
+                            (e.g.; \"bash scripts/fix-links.sh\").
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+
+                ParseConfigError::InvalidProfile(value) => {
+                    let value = style::value(value);
+                    let profiles_key = style::value("profiles");
+                    let install_key = style::value("install");
+                    create_error()
+                        .code("DEB_PKG_E027")
+                        .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid profile"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {profiles_key} in the key {root_config_key}.
+
+                            Each profile must be a TOML table with an {install_key} array of \
+                            packages to install when the profile is selected.
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+
+                ParseConfigError::UnsupportedSchemaVersion(value) => {
+                    let value = style::value(value);
+                    let schema_version_key = style::value("schema-version");
+                    let schema_versions = SCHEMA_VERSIONS
+                        .iter()
+                        .map(|version| style::value(format!("\"{version}\"")))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    create_error()
+                        .code("DEB_PKG_E028")
+                        .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with unsupported schema version"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an unsupported value {value} for \
+                            {schema_version_key} in the key {root_config_key}.
+
+                            This version of the buildpack accepts the following schema versions: \
+                            {schema_versions}. Configuration without a {schema_version_key} is read \
+                            with the original schema where unknown keys are ignored.
+
+                            Suggestions:
+                            - Use one of the accepted schema versions or upgrade the buildpack to a \
+                            version that supports {value}.
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+
+                ParseConfigError::UnknownKeys(unknown_keys) => {
+                    let schema_version = style::value("schema-version = \"0.3\"");
+                    let unknown_keys = unknown_keys
+                        .iter()
+                        .map(|key| format!("- {key}"))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let config_keys = CONFIG_KEYS
+                        .iter()
+                        .map(style::value)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    create_error()
+                        .code("DEB_PKG_E029")
+                        .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with unknown keys"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but the following keys in {root_config_key} aren't \
+                            recognized and {schema_version} doesn't allow unknown keys:
+
+                            {unknown_keys}
+
+                            This version of the buildpack accepts the following keys in \
+                            {root_config_key}: {config_keys}
+
+                            Suggestions:
+                            - Check the keys for typos.
+                            - See the buildpack documentation for the supported configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+
+                ParseConfigError::InvalidPrune(value) => {
+                    let value = style::value(value);
+                    let prune_key = style::value("prune");
+                    create_error()
+                        .code("DEB_PKG_E030")
+                        .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                        .header(format!("Error parsing {config_file} with invalid prune value"))
+                        .body(formatdoc! { "
+                            The {BUILDPACK_NAME} reads configuration from {config_file} to \
+                            complete the build but we found an invalid value {value} for \
+                            {prune_key} in the key {root_config_key}.
+
+                            The value must be a TOML array containing any of the following:
+                            - \"static-libs\" (removes static libraries)
+                            - \"headers\" (removes C/C++ header files)
+
+                            Suggestions:
+                            - See the buildpack documentation for the proper usage for this configuration at \
+                            {configuration_doc_url}
+                        " })
+                        .call()
+                }
+            }
+        }
+
+        ConfigError::InvalidEnvPackageName(error) => {
+            let install_env = style::value("BP_DEB_PACKAGES_INSTALL");
+            let invalid_package_name = style::value(error.package_name);
+            let debian_package_name_format_url = style::url(
+                "https://www.debian.org/doc/debian-policy/ch-controlfields.html#s-f-source",
+            );
+            let package_search_url = get_package_search_url();
+            create_error()
+                .code("DEB_PKG_E031")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header(format!("Error parsing {install_env} with invalid package name"))
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} reads the packages to install from the {install_env} \
+                    environment variable but we found an invalid package name {invalid_package_name}.
+
+                    Package names must consist only of lowercase letters (a-z), \
+                    digits (0-9), plus (+) and minus (-) signs, and periods (.). Names \
+                    must be at least two characters long and must start with an alphanumeric \
+                    character. See {debian_package_name_format_url}
+
+                    Suggestions:
+                    - Separate package names with spaces or commas.
+                    - Verify the package name is correct and exists for the target distribution at \
+                     {package_search_url}
+                " })
+                .call()
+        }
+
+        ConfigError::UnknownProfile(profile_name, available_profiles) => {
+            let profile_env = style::value("BP_DEB_PACKAGES_PROFILE");
+            let profile_table = style::value(format!("[profiles.{profile_name}]"));
+            let profile_name = style::value(profile_name);
+            let available_profiles = if available_profiles.is_empty() {
+                "No profiles are configured.".to_string()
+            } else {
+                format!(
+                    "The configured profiles are: {}.",
+                    available_profiles
+                        .iter()
+                        .map(style::value)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            };
+            create_error()
+                .code("DEB_PKG_E032")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Error selecting profile {profile_name}"))
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} installs the packages from the profile selected with the \
+                    {profile_env} environment variable but the profile {profile_name} isn't \
+                    configured. {available_profiles}
+
+                    Suggestions:
+                    - Add a {profile_table} table to the buildpack configuration.
+                    - Verify the value of {profile_env} for this build.
+                " })
+                .call()
+        }
+    }
+}
+
+fn on_unsupported_distro_error(error: UnsupportedDistroError) -> ErrorMessage {
+    let UnsupportedDistroError {
+        name,
+        version,
+        architecture,
+    } = error;
+
+    let buildpack_toml = style::value("buildpack.toml");
+    create_error()
+        .code("DEB_PKG_E033")
+        .error_type(Internal)
+        .header("Unsupported distribution")
+        .body(formatdoc! { "
+            The {BUILDPACK_NAME} doesn't support the {name} {version} ({architecture}) \
+            distribution. See {buildpack_toml} for the configuration of supported distributions.
+        " })
+        .call()
+}
+
+#[allow(clippy::too_many_lines)]
+fn on_create_package_index_error(error: CreatePackageIndexError) -> ErrorMessage {
+    let canonical_status_url = get_canonical_status_url();
+
+    match error {
+        CreatePackageIndexError::NoSources => {
+            create_error()
+                .code("DEB_PKG_E034")
+                .error_type(Internal)
+                .header("No sources to update")
+                .body(indoc! { "
+                    The distribution has no sources to update packages from.
+                " })
+                .call()
+        }
+
+        CreatePackageIndexError::TaskFailed(e) => {
+            create_error()
+                .code("DEB_PKG_E035")
+                .error_type(Internal)
+                .header("Task failure while updating sources")
+                .body(indoc! { "
+                    A background task responsible for updating sources failed to complete.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::InvalidLayerName(url, e) => {
+            create_error()
+                .code("DEB_PKG_E036")
+                .error_type(Internal)
+                .header("Invalid layer name")
+                .body(formatdoc! { "
+                    For caching purposes, a unique layer name is generated for Debian Release files \
+                    and Package indices based on their download urls. The generated name for the \
+                    following url was invalid:
+                    - {url}
+
+                    You can find the invalid layer name in the debug information above.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::GetReleaseRequest(e) => {
+            create_error()
+                .code("DEB_PKG_E037")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to request Release file")
+                .body(formatdoc! { "
+                    While updating package sources, a request to download a Release file failed. \
+                    This error can occur due to an unstable network connection or an issue with the upstream \
+                    Debian package repository.
+
+                    Suggestions:
+                    - Check the status of {canonical_status_url} for any reported issues.
+                " })
+                .debug_info(request_debug_info(&e))
+                .call()
+        }
+
+        CreatePackageIndexError::ReadGetReleaseResponse(e) => {
+            create_error()
+                .code("DEB_PKG_E038")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to download Release file")
+                .body(formatdoc! { "
+                    While updating package sources, an error occurred while downloading a Release file. \
+                    This error can occur due to an unstable network connection or an issue with the upstream \
+                    Debian package repository.
+
+                    Suggestions:
+                    - Check the status of {canonical_status_url} for any reported issues.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::CreatePgpCertificate(e) => {
+            let signed_by_key = style::value("signed_by");
+            create_error()
+                .code("DEB_PKG_E039")
+                .error_type(Internal)
+                .header("Failed to load verifying PGP certificate")
+                .body(formatdoc! { "
+                    The PGP certificate used to verify downloaded release files failed to load. This \
+                    error indicates there's a problem with the format of the certificate file the \
+                    distribution uses.
+
+                    Suggestions:
+                    - Verify the format of the certificates found in the ./keys directory of this \
+                    buildpack's repository. See https://cirw.in/gpg-decoder
+                    - Extract new certificates by running the ./scripts/extract_keys.sh script found \
+                    in this buildpack's repository.
+                    - For a custom source, verify that {signed_by_key} is an ASCII-armored key or the \
+                    path to an ASCII-armored (.asc) or binary (.gpg) keyring file relative to the \
+                    application directory.
+                " })
+                .debug_info(format!("{e:#}"))
+                .call()
+        }
+
+        CreatePackageIndexError::CreatePgpVerifier(e) => {
+            create_error()
+                .code("DEB_PKG_E040")
+                .error_type(Internal)
+                .header("Failed to verify Release file")
+                .body(indoc! { "
+                    The PGP signature of the downloaded release file failed verification. This error can \
+                    occur if the maintainers of the Debian repository changed the process for signing \
+                    release files.
+
+                    Suggestions:
+                    - Verify if the keys changed by running the ./scripts/extract_keys.sh \
+                    script found in this buildpack's repository.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::WriteReleaseLayer(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E041")
+                .error_type(Internal)
+                .header("Failed to write Release file to layer")
+                .body(formatdoc! { "
+                    An unexpected I/O error occurred while writing release data to {file}.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::ReadReleaseFile(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E042")
+                .error_type(Internal)
+                .header("Failed to read Release file from layer")
+                .body(formatdoc! { "
+                    An unexpected I/O error occurred while reading Release data from {file}.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::ParseReleaseFile(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E043")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to parse Release file data")
+                .body(formatdoc! { "
+                    We couldn't parse the Release file data stored in {file}. This error is most likely \
+                    a buildpack bug. It can also be caused by cached data that's no longer valid or an \
+                    issue with the upstream repository.
+
+                    Suggestions:
+                    - Run the build again with a clean cache.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::MissingSha256ReleaseHashes(release_uri) => {
+            let release_uri = style::url(&release_uri);
+            create_error()
+                .code("DEB_PKG_E044")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Missing SHA256 Release hash")
+                .body(formatdoc! { "
+                    The Release file from {release_uri} is missing the SHA256 key which is required \
+                    according to the documented Debian repository format. This error is most likely an issue \
+                    with the upstream repository. See https://wiki.debian.org/DebianRepository/Format
+                " })
+                .call()
+        }
+
+        CreatePackageIndexError::MissingPackageIndexReleaseHash(release_uri, package_index) => {
+            let release_uri = style::url(&release_uri);
+            let package_index = style::value(package_index);
+            let xz = style::value(".xz");
+            let zst = style::value(".zst");
+            create_error()
+                .code("DEB_PKG_E045")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Missing Package Index")
+                .body(formatdoc! { "
+                    The Release file from {release_uri} is missing an entry for {package_index}, or \
+                    its {xz}, {zst}, or uncompressed variants, within the SHA256 section. This error is most likely a buildpack bug but can also \
+                    be an issue with the upstream repository.
+
+                    Suggestions:
+                    - Verify if {package_index} is under the SHA256 section of {release_uri}
+                " })
+                .call()
+        }
+
+        CreatePackageIndexError::GetPackagesRequest(e) => {
+            create_error()
+                .code("DEB_PKG_E046")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to request Package Index file")
+                .body(formatdoc! { "
+                    While updating package sources, a request to download a Package Index file failed. \
+                    This error can occur due to an unstable network connection or an issue with the upstream \
+                    Debian package repository.
+
+                    Suggestions:
+                    - Check the status of {canonical_status_url} for any reported issues.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::WritePackagesLayer(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E047")
+                .error_type(Internal)
+                .header("Failed to write Package Index file to layer")
+                .body(formatdoc! { "
+                    An unexpected I/O error occurred while writing Package Index data to {file}.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::WritePackageIndexFromResponse(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E048")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to download Package Index file")
+                .body(formatdoc! { "
+                    While updating package sources, an error occurred while downloading a Package Index \
+                    file to {file}. This error can occur due to an unstable network connection or an issue \
+                    with the upstream Debian package repository.
+
+                    Suggestions:
+                    - Check the status of {canonical_status_url} for any reported issues.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::ChecksumFailed {
+            url,
+            expected,
+            actual,
+        } => {
+            let url = style::url(url);
+            let expected = style::value(expected);
+            let actual = style::value(actual);
+            create_error()
+                .code("DEB_PKG_E049")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header("Package Index checksum verification failed")
+                .body(formatdoc! { "
+                    While updating package sources, an error occurred while verifying the checksum \
+                    of the Package Index at {url}. This error can occur due to an issue with the upstream \
+                    Debian package repository.
+
+                    Checksum:
+                    - Expected: {expected}
+                    - Actual: {actual}
+                " })
+                .call()
+        }
+
+        CreatePackageIndexError::CpuTaskFailed(e) => {
+            create_error()
+                .code("DEB_PKG_E050")
+                .error_type(Internal)
+                .header("Task failure while reading Package Index data")
+                .body(indoc! { "
+                    A background task responsible for reading Package Index data failed to complete.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::ReadPackagesFile(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E051")
+                .error_type(Internal)
+                .header("Failed to read Package Index file")
+                .body(formatdoc! { "
+                    An unexpected I/O error occurred while reading Package Index data from {file}.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        CreatePackageIndexError::ParsePackages { url, path, errors } => {
+            let file = file_value(path);
+            let url = style::url(url);
+            let body_start = formatdoc! { "
+                We can't parse the Package Index from {url} stored in {file}. This error is most likely \
+                a buildpack bug. It can also be caused by cached data that's no longer valid or an issue \
+                with the upstream repository.
+
+                Parsing errors:
+            " }.trim_end().to_string();
+            let body_error_details = errors
+                .iter()
+                .map(|e| format!("- {e}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut body_end = indoc! { "
+                Suggestions:
+                - Run the build again with a clean cache.
+            " }
+            .to_string();
+            if errors
+                .iter()
+                .any(|e| matches!(e, ParseRepositoryPackageError::MissingSha256(_)))
+            {
+                body_end.push_str(&formatdoc! { "
+                    - If these packages are from a custom source that only lists {sha1} or {md5sum} \
+                    checksums, set {allow_weak_checksums} on that source to verify packages with the \
+                    weaker checksum instead.
+                ",
+                    sha1 = style::value("SHA1"),
+                    md5sum = style::value("MD5sum"),
+                    allow_weak_checksums = style::value("allow_weak_checksums = true"),
+                });
+            }
+            if errors
+                .iter()
+                .any(|e| matches!(e, ParseRepositoryPackageError::InvalidVersion(..)))
+            {
+                body_end.push_str(&formatdoc! { "
+                    - Packages with a version that isn't a valid Debian version can't be indexed. If \
+                    {url} is from a custom source, contact the maintainers of that repository or remove \
+                    the source from your configuration.
+                " });
+            }
+            create_error()
+                .code("DEB_PKG_E052")
+                .error_type(Internal)
+                .header("Failed to parse Package Index file")
+                .body(format!(
+                    "{body_start}\n{body_error_details}\n\n{body_end}"
+                ))
+                .call()
+        }
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn on_determine_packages_to_install_error(error: DeterminePackagesToInstallError) -> ErrorMessage {
+    match error {
+        DeterminePackagesToInstallError::ReadSystemPackages(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E053")
+                .error_type(Internal)
+                .header("Failed to read system packages")
+                .body(formatdoc! { "
+                    An unexpected I/O error occurred while reading system packages from {file}.
+                "})
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        DeterminePackagesToInstallError::ParseSystemPackage(file, package_data, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E054")
+                .error_type(Internal)
+                .header("Failed to parse system package")
+                .body(formatdoc! { "
+                    An unexpected parsing error occurred while reading system packages from {file}.
+                "})
+                .debug_info(format!("{e}\n\nPackage:\n{package_data}"))
+                .call()
+        }
+
+        DeterminePackagesToInstallError::PackageNotFound(package_name, suggested_packages) => {
+            let package_name = style::value(package_name);
+            let package_search_url = get_package_search_url();
+            let suggestions = if suggested_packages.is_empty() {
+                "- No similarly named packages found".to_string()
+            } else {
+                suggested_packages
+                    .into_iter()
+                    .map(|name| format!("- {}", style::value(name)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            create_error()
+                .code("DEB_PKG_E055")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header("Package not found")
+                .body(formatdoc! { "
+                    We can't find {package_name} in the Package Index. If this package is listed in the \
+                    packages to install for this buildpack then the name is most likely misspelled. Otherwise, \
+                    it can be an issue with the upstream Debian package repository.
+
+                    Did you mean?
+                    {suggestions}
+
+                    Suggestions:
+                    - Verify the package name is correct and exists for the target distribution at \
+                    {package_search_url}
+                " })
+                .call()
+        }
+
+        DeterminePackagesToInstallError::VirtualPackageMustBeSpecified(package, providers) => {
+            let package = style::value(package);
+            let body_start = indoc! { "
+                Sometimes there are several packages which offer more-or-less the same functionality. \
+                In this case, Debian repositories define a virtual package and one or more actual \
+                packages provide an implementation for this virtual package. When multiple providers \
+                are found for a requested package, this buildpack can't automatically choose which \
+                one is the desired implementation.
+
+                Providing packages:
+            " };
+            let body_provider_details = providers
+                .iter()
+                .collect::<BTreeSet<_>>()
+                .iter()
+                .map(|provider| format!("- {provider}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let body_end = formatdoc! { "
+                Suggestions:
+                - Replace the virtual package {package} with one of the above providers.
+            " };
+            create_error()
+                .code("DEB_PKG_E056")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header(format!(
+                    "Multiple providers were found for the package {package}"
+                ))
+                .body(format!("{body_start}{body_provider_details}\n\n{body_end}"))
+                .call()
+        }
+
+        DeterminePackagesToInstallError::EssentialPackages(packages) => {
+            let essential_packages_key = style::value("essential_packages");
+            let fail = style::value("\"fail\"");
+            let allow = style::value("\"allow\"");
+            let skip_dependencies = style::value("skip_dependencies = true");
+            let packages = packages
+                .iter()
+                .map(|package| format!("- {package}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            create_error()
+                .code("DEB_PKG_E057")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Essential packages would be installed")
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} is configured with {essential_packages_key} set to {fail} \
+                    and the following packages being installed are essential or required packages \
+                    that are already part of the base image:
+
+                    {packages}
+
+                    Installing a different version of these packages in a layer on top of the base \
+                    image almost always breaks the image.
+
+                    Suggestions:
+                    - Set {skip_dependencies} on the requested packages that depend on these \
+                    packages and list any other dependencies explicitly.
+                    - Set {essential_packages_key} to {allow} if these packages must be installed.
+                " })
+                .call()
+        }
+
+        DeterminePackagesToInstallError::PinnedVersionNotFound(
+            package_name,
+            pinned_version,
+            available_versions,
+        ) => {
+            let name_with_version = style::value(format!("{package_name}@{pinned_version}"));
+            let pins_key = style::value("pins");
+            let package_name = style::value(package_name);
+            let package_search_url = get_package_search_url();
+            let available_versions = if available_versions.is_empty() {
+                "- No versions found".to_string()
+            } else {
+                available_versions
+                    .into_iter()
+                    .map(|version| format!("- {}", style::value(version)))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            };
+            create_error()
+                .code("DEB_PKG_E058")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Pinned package version not found")
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} is configured to pin {package_name} to a specific version \
+                    with {pins_key} but we can't find {name_with_version} in the Package Index. \
+                    Upstream repositories usually only keep the latest version of a package so a \
+                    pinned version can disappear when a newer version is published.
+
+                    Available versions:
+                    {available_versions}
+
+                    Suggestions:
+                    - Update the pinned version of {package_name} to one of the available versions.
+                    - Add a source that still publishes the pinned version (e.g.; a snapshot of the \
+                    repository) or remove the pin.
+                    - Verify the package version exists for the target distribution at \
+                    {package_search_url}
+                " })
+                .call()
+        }
+
+        DeterminePackagesToInstallError::PackageNotFoundInSuite(
+            package_name,
+            suite,
+            available_suites,
+        ) => {
+            let package_name = style::value(package_name);
+            let suite = style::value(suite);
+            let suite_key = style::value("suite");
+            let default_suites_key = style::value("default_suites");
+            let package_search_url = get_package_search_url();
+            let available_suites = available_suites
+                .into_iter()
+                .map(|suite| format!("- {}", style::value(suite)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            create_error()
+                .code("DEB_PKG_E059")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Package not found in suite")
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} is configured to install {package_name} from the suite \
+                    {suite} with {suite_key} but we can't find {package_name} in the Package Index \
+                    for that suite.
+
+                    Suites containing {package_name}:
+                    {available_suites}
+
+                    Suggestions:
+                    - Change the {suite_key} of {package_name} to one of the suites containing it.
+                    - Add the suite to the sources the Package Index is built from (e.g.; with \
+                    {default_suites_key}) if it isn't one of them.
+                    - Verify the package exists in the suite for the target distribution at \
+                    {package_search_url}
+                " })
+                .call()
+        }
+
+        DeterminePackagesToInstallError::InvalidPackageName(package_name) => {
+            let package_name = style::value(package_name);
+            let debian_package_name_format_url = style::url(
+                "https://www.debian.org/doc/debian-policy/ch-controlfields.html#s-f-source",
+            );
+            create_error()
+                .code("DEB_PKG_E116")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Invalid package name")
+                .body(formatdoc! { "
+                    The package name {package_name} can't be resolved because it isn't a valid \
+                    Debian package name.
+
+                    Package names must consist only of lowercase letters (a-z), digits (0-9), plus \
+                    (+) and minus (-) signs, and periods (.). Names must be at least two characters \
+                    long and must start with an alphanumeric character. See \
+                    {debian_package_name_format_url}
+                " })
+                .call()
+        }
+    }
+}
+
+#[allow(clippy::too_many_lines)]
+fn on_install_packages_error(error: InstallPackagesError) -> ErrorMessage {
+    let canonical_status_url = get_canonical_status_url();
+
+    match error {
+        InstallPackagesError::TaskFailed(e) => create_error()
+            .code("DEB_PKG_E060")
+            .error_type(Internal)
+            .header("Task failure while installing packages")
+            .body(indoc! { "
+                A background task responsible for installing failed to complete.
+            " })
+            .debug_info(e.to_string())
+            .call(),
+
+        InstallPackagesError::InvalidFilename(package, filename) => {
+            let package = style::value(package);
+            let filename = style::value(filename);
+            create_error()
+                .code("DEB_PKG_E061")
+                .error_type(Internal)
+                .header(format!("Could not determine file name for {package}"))
+                .body(formatdoc! { "
+                    The package information for {package} contains a Filename field of {filename} \
+                    which produces an invalid name to use as a download path.
+                " })
+                .call()
+        }
+
+        InstallPackagesError::CreateDownloadDir(download_dir, e) => {
+            let download_dir = file_value(download_dir);
+            let download_dir_env = style::value("BP_DEB_PACKAGES_DOWNLOAD_DIR");
+            create_error()
+                .code("DEB_PKG_E062")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to create download directory")
+                .body(formatdoc! { "
+                    An unexpected I/O error occurred while creating the directory packages are \
+                    downloaded to at {download_dir}.
+
+                    Suggestions:
+                    - If {download_dir_env} is set, check that it's a path that can be written to.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::RequestPackage(package, e) => {
+            let package = style::value(package.name);
+            create_error()
+                .code("DEB_PKG_E063")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to request package")
+                .body(formatdoc! { "
+                    While installing packages, an error occurred while downloading {package}. \
+                    This error can occur due to an unstable network connection or an issue \
+                    with the upstream Debian package repository.
+
+                    Suggestions:
+                    - Check the status of {canonical_status_url} for any reported issues.
+                " })
+                .debug_info(request_debug_info(&e))
+                .call()
+        }
+
+        InstallPackagesError::RequestPackageUrl(download_url, e) => {
+            let github_suggestion = if download_url.is_github_release_asset() {
+                let github_token = style::value("GITHUB_TOKEN");
+                formatdoc! { "
+                    - For release assets from a private GitHub repository, set {github_token} to a \
+                    token with read access to the repository.
+                " }
+            } else {
+                String::new()
+            };
+            let url = style::url(download_url.to_string());
+            create_error()
+                .code("DEB_PKG_E064")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to request package from download url")
+                .body(formatdoc! { "
+                    While installing packages, an error occurred while downloading the package at {url}. \
+                    This error can occur due to an unstable network connection or an issue \
+                    with site this package is hosted at.
+
+                    Suggestions:
+                    - Check if {url} can be downloaded locally or if there's an error.
+                    {github_suggestion}
+                " })
+                .debug_info(request_debug_info(&e))
+                .call()
+        }
+
+        InstallPackagesError::ResolveDownloadHeader(download_url, header) => {
+            let url = style::url(download_url.to_string());
+            let header_name = style::value(&header.name);
+            let (header_value, suggestion) = match header.value {
+                DownloadHeaderValue::Env(name) => {
+                    let name = style::value(name);
+                    (
+                        format!("read from the environment variable {name}"),
+                        format!("- Set {name} to the value this header should be sent with."),
+                    )
+                }
+                DownloadHeaderValue::Literal(_) => (
+                    "configured".to_string(),
+                    "- Ensure the header value only contains visible ASCII characters.".to_string(),
+                ),
+            };
+            create_error()
+                .code("DEB_PKG_E065")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Invalid header for download url")
+                .body(formatdoc! { "
+                    While installing packages, the {header_name} header for the package at {url} couldn't \
+                    be sent because the value {header_value} is missing or isn't a valid header value.
+
+                    Suggestions:
+                    {suggestion}
+                " })
+                .call()
+        }
+
+        InstallPackagesError::ResolveGithubReleaseAsset(download_url, reason) => {
+            let url = style::url(download_url.to_string());
+            let github_token = style::value("GITHUB_TOKEN");
+            create_error()
+                .code("DEB_PKG_E118")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Failed to find GitHub release asset")
+                .body(formatdoc! { "
+                    While installing packages, the release asset at {url} couldn't be found with \
+                    the GitHub API. Since {github_token} is set, release assets are downloaded through \
+                    the GitHub API so assets from private repositories can be installed.
+
+                    Suggestions:
+                    - Check that the release has an asset with this exact name.
+                    - Check that {github_token} has read access to the repository.
+                " })
+                .debug_info(reason)
+                .call()
+        }
+
+        InstallPackagesError::WritePackage(package, download_url, destination_path, e) => {
+            let package = style::value(package.name);
+            let download_url = style::url(download_url);
+            let destination_path = file_value(destination_path);
+            create_error()
+                .code("DEB_PKG_E066")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to download package")
+                .body(formatdoc! { "
+                    An unexpected I/O error occured while downloading {package} from {download_url} \
+                    to {destination_path}. This error can occur due to an unstable network connection or an issue \
+                    with the upstream Debian package repository.
+
+                    Suggestions:
+                    - Check the status of {canonical_status_url} for any reported issues.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::WritePackageUrl(download_url, destination_path, e) => {
+            let download_url = style::url(download_url.to_string());
+            let destination_path = file_value(destination_path);
+            create_error()
+                .code("DEB_PKG_E067")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+                .header("Failed to download package")
+                .body(formatdoc! { "
+                    An unexpected I/O error occured while downloading the package at {download_url} \
+                    to {destination_path}. This error can occur due to an unstable network connection or an issue \
+                    with the site this packages is hosted at.
+
+                    Suggestions:
+                    - Check if {download_url} can be downloaded locally or if there's an error.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::RequestPackageSignature(signature_url, e) => {
+            let signature_url = style::url(signature_url);
+            let signature_url_key = style::value("signature_url");
+            create_error()
+                .code("DEB_PKG_E068")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header("Failed to request package signature")
+                .body(formatdoc! { "
+                    While installing packages, an error occurred while downloading the signature at \
+                    {signature_url}. This error can occur due to an unstable network connection or \
+                    because the signature isn't published at this url.
+
+                    Suggestions:
+                    - Check if {signature_url} can be downloaded locally or if there's an error.
+                    - Set {signature_url_key} on the download url if the signature is published \
+                    somewhere else.
+                " })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::VerifyPackageSignature(package, e) => {
+            let package = style::value(package);
+            let (reason, debug_info) = match e {
+                PackageSignatureError::ReadPackage(e) => (
+                    "the package archive couldn't be read".to_string(),
+                    Some(e.to_string()),
+                ),
+                PackageSignatureError::MissingSignature => (
+                    "the package isn't signed".to_string(),
+                    None,
+                ),
+                PackageSignatureError::LoadSigningKey(e) => (
+                    format!(
+                        "the {signed_by_key} key couldn't be loaded",
+                        signed_by_key = style::value("signed_by")
+                    ),
+                    Some(format!("{e:#}")),
+                ),
+                PackageSignatureError::InvalidSignature(e) => (
+                    "the signature doesn't match the package or wasn't made with the configured key"
+                        .to_string(),
+                    Some(e.to_string()),
+                ),
+                PackageSignatureError::UnsignedArchiveMember(name) => (
+                    format!(
+                        "the archive member {name} isn't covered by the signature",
+                        name = style::value(name)
+                    ),
+                    None,
+                ),
+                PackageSignatureError::DuplicateArchiveMember(name) => (
+                    format!(
+                        "the archive contains more than one {name} member",
+                        name = style::value(name)
+                    ),
+                    None,
+                ),
+                PackageSignatureError::UnexpectedArchiveMember(name) => (
+                    format!(
+                        "the archive member {name} isn't part of a Debian package",
+                        name = style::value(name)
+                    ),
+                    None,
+                ),
+            };
+            let signed_by_key = style::value("signed_by");
+            let verify_package_signatures_key = style::value("verify_package_signatures");
+            create_error()
+                .code("DEB_PKG_E069")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Package signature verification failed")
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} is configured to verify the signature of {package} but \
+                    verification failed because {reason}. This error can occur when a package has \
+                    been tampered with or when the package is signed with a different key.
+
+                    Suggestions:
+                    - Verify that {signed_by_key} is set to the key the package is signed with.
+                    - Remove {verify_package_signatures_key} from the source (or {signed_by_key} from \
+                    the download url) if the repository doesn't sign its packages.
+                " })
+                .maybe_debug_info(debug_info)
+                .call()
+        }
+
+        InstallPackagesError::ChecksumFailed {
+            url,
+            expected,
+            actual,
+        } => {
+            let url = style::url(url);
+            let expected = style::value(expected);
+            let actual = style::value(actual);
+            create_error()
+                .code("DEB_PKG_E070")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header("Package checksum verification failed")
+                .body(formatdoc! { "
+                    An error occurred while verifying the checksum of the package at {url}. \
+                    This error can occur due to an issue with the upstream Debian package repository.
+
+                    Checksum:
+                    - Expected: {expected}
+                    - Actual: {actual}
+                " })
+                .call()
+        }
+
+        InstallPackagesError::OpenPackageArchive(file, e) => {
+            let file = file_value(file);
             create_error()
+                .code("DEB_PKG_E071")
                 .error_type(Internal)
-                .header("Failed to write Release file to layer")
+                .header("Failed to open package archive")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while trying to open the archive at {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::OpenPackageArchiveEntry(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E072")
+                .error_type(Internal)
+                .header("Failed to read package archive entry")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while trying to read the entries of the \
+                    archive at {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::UnpackTarball(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E073")
+                .error_type(Internal)
+                .header("Failed to unpack package archive")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while trying to unpack the archive at {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::UnsafeTarballEntry(file, entry_path, reason) => {
+            let file = file_value(file);
+            let entry_path = file_value(entry_path);
+            let reason = match reason {
+                UnsafeTarballEntry::PathTraversal => {
+                    "its path contains a `..` component".to_string()
+                }
+                UnsafeTarballEntry::OutsideInstallDirectory => {
+                    "it would be linked outside of the install directory".to_string()
+                }
+                UnsafeTarballEntry::MissingLinkTarget => {
+                    "it's a hard link without a target".to_string()
+                }
+                UnsafeTarballEntry::SpecialFile(kind) => format!("it's a {kind}"),
+            };
+            create_error()
+                .code("DEB_PKG_E074")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::Yes))
+                .header("Unsafe entry in package archive")
                 .body(formatdoc! { "
-                    An unexpected I/O error occurred while writing release data to {file}.
+                    The package archive at {file} contains an entry for {entry_path} that can't be \
+                    safely unpacked because {reason}. This error can occur when a package is corrupt or \
+                    has been tampered with.
+
+                    Suggestions:
+                    - Verify that the package repository or download url is trusted.
                 " })
+                .call()
+        }
+
+        InstallPackagesError::UnsupportedCompression(file, format) => {
+            let file = file_value(file);
+            let format = style::value(format);
+            create_error()
+                .code("DEB_PKG_E075")
+                .error_type(Internal)
+                .header("Unsupported compression format for package archive")
+                .body(formatdoc! {
+                    "An unexpected compression format ({format}) was used for the package archive at {file}."
+                })
+                .call()
+        }
+
+        InstallPackagesError::ReadPackageConfig(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E076")
+                .error_type(Internal)
+                .header("Failed to read package config file")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while reading the package config file at {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::WritePackageManifest(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E077")
+                .error_type(Internal)
+                .header("Failed to write package manifest")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while writing the metadata for the installed packages to {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::WriteInstallPlan(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E078")
+                .error_type(Internal)
+                .header("Failed to write install plan")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while writing the packages to download and install to {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::WritePackageConfig(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E079")
+                .error_type(Internal)
+                .header("Failed to write package config file")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while writing the package config file to {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::ReadBinary(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E080")
+                .error_type(Internal)
+                .header("Failed to read binary")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while reading the installed binary at {file} to strip it."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::WriteBinary(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E081")
+                .error_type(Internal)
+                .header("Failed to write stripped binary")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while writing the stripped binary to {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+
+        InstallPackagesError::PruneFile(file, e) => {
+            let file = file_value(file);
+            create_error()
+                .code("DEB_PKG_E082")
+                .error_type(Internal)
+                .header("Failed to prune file")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while removing {file} from the installed packages."
+                })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        CreatePackageIndexError::ReadReleaseFile(file, e) => {
+        InstallPackagesError::NormalizeTimestamp(file, e) => {
             let file = file_value(file);
             create_error()
+                .code("DEB_PKG_E083")
                 .error_type(Internal)
-                .header("Failed to read Release file from layer")
-                .body(formatdoc! { "
-                    An unexpected I/O error occurred while reading Release data from {file}.
-                " })
+                .header("Failed to normalize file timestamp")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while setting the modification time of {file} in the installed packages."
+                })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        CreatePackageIndexError::ParseReleaseFile(file, e) => {
+        InstallPackagesError::ReadVendoredPackage(file, e) => {
             let file = file_value(file);
+            let vendor_command = style::value("deb-packages vendor");
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to parse Release file data")
+                .code("DEB_PKG_E084")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Failed to read vendored package")
                 .body(formatdoc! { "
-                    We couldn't parse the Release file data stored in {file}. This error is most likely \
-                    a buildpack bug. It can also be caused by cached data that's no longer valid or an \
-                    issue with the upstream repository.
+                    The vendored package at {file} is listed in the vendor manifest but can't be read.
 
                     Suggestions:
-                    - Run the build again with a clean cache.
+                    - Ensure the vendor directory is committed along with its manifest.
+                    - Re-run {vendor_command} to download the vendored packages again.
                 " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        CreatePackageIndexError::MissingSha256ReleaseHashes(release_uri) => {
-            let release_uri = style::url(&release_uri);
+        InstallPackagesError::VendoredPackageChecksumFailed {
+            path,
+            expected,
+            actual,
+        } => {
+            let file = file_value(path);
+            let expected = style::value(expected);
+            let actual = style::value(actual);
+            let vendor_command = style::value("deb-packages vendor");
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Missing SHA256 Release hash")
+                .code("DEB_PKG_E085")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Vendored package checksum verification failed")
                 .body(formatdoc! { "
-                    The Release file from {release_uri} is missing the SHA256 key which is required \
-                    according to the documented Debian repository format. This error is most likely an issue \
-                    with the upstream repository. See https://wiki.debian.org/DebianRepository/Format
+                    The checksum of the vendored package at {file} does not match the checksum \
+                    recorded in the vendor manifest.
+
+                    Checksum:
+                    - Expected: {expected}
+                    - Actual: {actual}
+
+                    Suggestions:
+                    - Re-run {vendor_command} to download the vendored packages again.
                 " })
                 .call()
         }
+    }
+}
 
-        CreatePackageIndexError::MissingPackageIndexReleaseHash(release_uri, package_index) => {
-            let release_uri = style::url(&release_uri);
-            let package_index = style::value(package_index);
+fn on_detect_error(error: DetectError) -> ErrorMessage {
+    match error {
+        DetectError::CheckExistsAptfile(file, e)
+        | DetectError::CheckExistsProjectToml(file, e)
+        | DetectError::CheckExistsDebPackagesToml(file, e) => {
+            let file = file_value(file);
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Missing Package Index")
+                .code("DEB_PKG_E086")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header("Unable to complete buildpack detection")
                 .body(formatdoc! { "
-                    The Release file from {release_uri} is missing an entry for {package_index} within \
-                    the SHA256 section. This error is most likely a buildpack bug but can also \
-                    be an issue with the upstream repository.
-
-                    Suggestions:
-                    - Verify if {package_index} is under the SHA256 section of {release_uri}
+                    An unexpected I/O error occurred while checking {file} to determine if the \
+                    {BUILDPACK_NAME} is compatible for this application.
                 " })
+                .debug_info(e.to_string())
                 .call()
         }
+    }
+}
 
-        CreatePackageIndexError::GetPackagesRequest(e) => {
+fn on_cli_error(error: CliError) -> ErrorMessage {
+    match error {
+        CliError::GetCurrentDir(e) => create_error()
+            .code("DEB_PKG_E087")
+            .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+            .header("Unable to determine the current directory")
+            .body(formatdoc! { "
+                The current directory is used to find a {project_toml} file with custom sources \
+                to include when creating the package index but it could not be read.
+            ", project_toml = style::value("project.toml") })
+            .debug_info(e.to_string())
+            .call(),
+
+        CliError::CreateCacheDir(cache_dir, e) => {
+            let cache_dir = file_value(cache_dir);
+            let cache_dir_flag = style::value("--cache-dir");
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to request Package Index file")
+                .code("DEB_PKG_E088")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Unable to create the cache directory")
                 .body(formatdoc! { "
-                    While updating package sources, a request to download a Package Index file failed. \
-                    This error can occur due to an unstable network connection or an issue with the upstream \
-                    Debian package repository.
+                    Downloaded package indices are cached in {cache_dir} but this directory could \
+                    not be created.
 
                     Suggestions:
-                    - Check the status of {canonical_status_url} for any reported issues.
+                    - Ensure you have write permissions for the parent directory.
+                    - Use the {cache_dir_flag} option to select a different location.
                 " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        CreatePackageIndexError::WritePackagesLayer(file, e) => {
-            let file = file_value(file);
+        CliError::MissingConfigFile(app_dir) => {
+            let app_dir = file_value(app_dir);
+            let standalone_config_file = style::value(STANDALONE_CONFIG_FILE);
+            let project_toml = style::value("project.toml");
             create_error()
-                .error_type(Internal)
-                .header("Failed to write Package Index file to layer")
+                .code("DEB_PKG_E089")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("No configuration file found")
                 .body(formatdoc! { "
-                    An unexpected I/O error occurred while writing Package Index data to {file}.
+                    A {standalone_config_file} or {project_toml} file was expected in {app_dir} \
+                    but neither file exists.
+
+                    Suggestions:
+                    - Run this command from the root of your application source directory.
+                    - Pass the path to the configuration file to validate as an argument.
                 " })
-                .debug_info(e.to_string())
                 .call()
         }
 
-        CreatePackageIndexError::WritePackageIndexFromResponse(file, e) => {
-            let file = file_value(file);
+        CliError::UnknownConfigKeys(config_file, unknown_keys) => {
+            let config_file = file_value(config_file);
+            let unknown_keys = unknown_keys
+                .iter()
+                .map(|key| format!("- {key}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let configuration_doc_url =
+                style::url("https://github.com/heroku/buildpacks-deb-packages#configuration");
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to download Package Index file")
+                .code("DEB_PKG_E090")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Unknown keys in {config_file}"))
                 .body(formatdoc! { "
-                    While updating package sources, an error occurred while downloading a Package Index \
-                    file to {file}. This error can occur due to an unstable network connection or an issue \
-                    with the upstream Debian package repository.
+                    The following keys in {config_file} aren't recognized by the {BUILDPACK_NAME} \
+                    and would be ignored during a build:
+
+                    {unknown_keys}
 
                     Suggestions:
-                    - Check the status of {canonical_status_url} for any reported issues.
+                    - Check the keys for typos.
+                    - See the buildpack documentation for the supported configuration at \
+                    {configuration_doc_url}
                 " })
-                .debug_info(e.to_string())
                 .call()
         }
 
-        CreatePackageIndexError::ChecksumFailed {
-            url,
-            expected,
-            actual,
-        } => {
-            let url = style::url(url);
-            let expected = style::value(expected);
-            let actual = style::value(actual);
+        CliError::DoctorChecksFailed(failed_checks) => {
+            let check_count = if failed_checks == 1 {
+                "1 check".to_string()
+            } else {
+                format!("{failed_checks} checks")
+            };
+            let credentials_env = style::value("BP_DEB_PACKAGES_CREDENTIALS");
+            let proxy_prefix_env = style::value("BP_DEB_PACKAGES_PROXY_PREFIX");
+            let prefer_https = style::value("prefer_https");
             create_error()
+                .code("DEB_PKG_E091")
                 .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
-                .header("Package Index checksum verification failed")
+                .header("Unable to reach every configured source")
                 .body(formatdoc! { "
-                    While updating package sources, an error occurred while verifying the checksum \
-                    of the Package Index at {url}. This error can occur due to an issue with the upstream \
-                    Debian package repository.
+                    {check_count} against the configured sources failed. Each failed check above \
+                    shows the step that failed and the error returned for it.
 
-                    Checksum:
-                    - Expected: {expected}
-                    - Actual: {actual}
-                " })
+                    Suggestions:
+                    - A failed DNS or connection check usually means the host is blocked by a \
+                    firewall or proxy on your network. Try {prefer_https} if plain HTTP is blocked \
+                    or route requests through a proxy with {proxy_prefix_env}.
+                    - A failed fetch with a 401 or 403 status means the repository requires \
+                    credentials, see {credentials_env}.
+                    - A failed signature check means the repository isn't signed with the \
+                    configured {signed_by} key.
+                    - Check the status page of the repository host for outages.
+                ", signed_by = style::value("signed_by") })
                 .call()
         }
+    }
+}
 
-        CreatePackageIndexError::CpuTaskFailed(e) => {
+fn on_lockfile_error(error: LockfileError) -> ErrorMessage {
+    let lock_update_command = style::value("deb-packages lock update");
+    match error {
+        LockfileError::Read(lockfile, e) => {
+            let lockfile = file_value(lockfile);
             create_error()
-                .error_type(Internal)
-                .header("Task failure while reading Package Index data")
-                .body(indoc! { "
-                    A background task responsible for reading Package Index data failed to complete.
+                .code("DEB_PKG_E092")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header(format!("Error reading {lockfile}"))
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} reads locked package versions from {lockfile} but the \
+                    file can't be read.
+
+                    Suggestions:
+                    - Ensure the file has read permissions.
                 " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        CreatePackageIndexError::ReadPackagesFile(file, e) => {
-            let file = file_value(file);
+        LockfileError::Parse(lockfile, e) => {
+            let lockfile = file_value(lockfile);
             create_error()
-                .error_type(Internal)
-                .header("Failed to read Package Index file")
+                .code("DEB_PKG_E093")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Error parsing {lockfile}"))
                 .body(formatdoc! { "
-                    An unexpected I/O error occurred while reading Package Index data from {file}.
+                    The {BUILDPACK_NAME} reads locked package versions from {lockfile} but the \
+                    contents of this file are not in the expected format.
+
+                    Suggestions:
+                    - Regenerate the lockfile with {lock_update_command} instead of editing it manually.
                 " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        CreatePackageIndexError::ParsePackages(file, errors) => {
-            let file = file_value(file);
-            let body_start = formatdoc! { "
-                We can't parse the Package Index file data stored in {file}. This error is most likely \
-                a buildpack bug. It can also be caused by cached data that's no longer valid or an issue \
-                with the upstream repository.
-
-                Parsing errors:
-            " }.trim_end().to_string();
-            let body_error_details = errors
-                .iter()
-                .map(|e| format!("- {e}"))
-                .collect::<Vec<_>>()
-                .join("\n");
-            let body_end = indoc! { "
-                Suggestions:
-                - Run the build again with a clean cache.
-            " };
+        LockfileError::Write(lockfile, e) => {
+            let lockfile = file_value(lockfile);
             create_error()
-                .error_type(Internal)
-                .header("Failed to parse Package Index file")
-                .body(format!(
-                    "{body_start}\n{body_error_details}\n\n{body_end}"
-                ))
+                .code("DEB_PKG_E094")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Error writing {lockfile}"))
+                .body(formatdoc! { "
+                    The resolved package versions could not be written to {lockfile}.
+
+                    Suggestions:
+                    - Ensure you have write permissions for the file and its directory.
+                " })
+                .debug_info(e.to_string())
                 .call()
         }
     }
 }
 
-fn on_determine_packages_to_install_error(error: DeterminePackagesToInstallError) -> ErrorMessage {
+fn on_vendor_error(error: VendorError) -> ErrorMessage {
+    let vendor_command = style::value("deb-packages vendor");
     match error {
-        DeterminePackagesToInstallError::ReadSystemPackages(file, e) => {
-            let file = file_value(file);
+        VendorError::ReadManifest(manifest, e) => {
+            let manifest = file_value(manifest);
             create_error()
-                .error_type(Internal)
-                .header("Failed to read system packages")
+                .code("DEB_PKG_E095")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header(format!("Error reading {manifest}"))
                 .body(formatdoc! { "
-                    An unexpected I/O error occurred while reading system packages from {file}.
-                "})
+                    The {BUILDPACK_NAME} reads the list of vendored packages from {manifest} but the \
+                    file can't be read.
+
+                    Suggestions:
+                    - Ensure the file has read permissions.
+                " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        DeterminePackagesToInstallError::ParseSystemPackage(file, package_data, e) => {
-            let file = file_value(file);
+        VendorError::ParseManifest(manifest, e) => {
+            let manifest = file_value(manifest);
             create_error()
-                .error_type(Internal)
-                .header("Failed to parse system package")
+                .code("DEB_PKG_E096")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Error parsing {manifest}"))
                 .body(formatdoc! { "
-                    An unexpected parsing error occurred while reading system packages from {file}.
-                "})
-                .debug_info(format!("{e}\n\nPackage:\n{package_data}"))
+                    The {BUILDPACK_NAME} reads the list of vendored packages from {manifest} but the \
+                    contents of this file are not in the expected format.
+
+                    Suggestions:
+                    - Regenerate the manifest with {vendor_command} instead of editing it manually.
+                " })
+                .debug_info(e.to_string())
                 .call()
         }
 
-        DeterminePackagesToInstallError::PackageNotFound(package_name, suggested_packages) => {
-            let package_name = style::value(package_name);
-            let package_search_url = get_package_search_url();
-            let suggestions = if suggested_packages.is_empty() {
-                "- No similarly named packages found".to_string()
-            } else {
-                suggested_packages
-                    .into_iter()
-                    .map(|name| format!("- {}", style::value(name)))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            };
+        VendorError::WriteManifest(manifest, e) => {
+            let manifest = file_value(manifest);
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
-                .header("Package not found")
+                .code("DEB_PKG_E097")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Error writing {manifest}"))
                 .body(formatdoc! { "
-                    We can't find {package_name} in the Package Index. If this package is listed in the \
-                    packages to install for this buildpack then the name is most likely misspelled. Otherwise, \
-                    it can be an issue with the upstream Debian package repository.
-
-                    Did you mean?
-                    {suggestions}
+                    The list of vendored packages could not be written to {manifest}.
 
                     Suggestions:
-                    - Verify the package name is correct and exists for the target distribution at \
-                    {package_search_url}
+                    - Ensure you have write permissions for the file and its directory.
                 " })
+                .debug_info(e.to_string())
                 .call()
         }
 
-        DeterminePackagesToInstallError::VirtualPackageMustBeSpecified(package, providers) => {
-            let package = style::value(package);
-            let body_start = indoc! { "
-                Sometimes there are several packages which offer more-or-less the same functionality. \
-                In this case, Debian repositories define a virtual package and one or more actual \
-                packages provide an implementation for this virtual package. When multiple providers \
-                are found for a requested package, this buildpack can't automatically choose which \
-                one is the desired implementation.
-
-                Providing packages:
-            " };
-            let body_provider_details = providers
-                .iter()
-                .collect::<BTreeSet<_>>()
-                .iter()
-                .map(|provider| format!("- {provider}"))
-                .collect::<Vec<_>>()
-                .join("\n");
-            let body_end = formatdoc! { "
-                Suggestions:
-                - Replace the virtual package {package} with one of the above providers.
-            " };
-            create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
-                .header(format!(
-                    "Multiple providers were found for the package {package}"
-                ))
-                .body(format!("{body_start}{body_provider_details}\n\n{body_end}"))
-                .call()
-        }
-    }
-}
-
-#[allow(clippy::too_many_lines)]
-fn on_install_packages_error(error: InstallPackagesError) -> ErrorMessage {
-    let canonical_status_url = get_canonical_status_url();
-
-    match error {
-        InstallPackagesError::TaskFailed(e) => create_error()
-            .error_type(Internal)
-            .header("Task failure while installing packages")
-            .body(indoc! { "
-                A background task responsible for installing failed to complete.
-            " })
-            .debug_info(e.to_string())
-            .call(),
-
-        InstallPackagesError::InvalidFilename(package, filename) => {
-            let package = style::value(package);
-            let filename = style::value(filename);
+        VendorError::CreateVendorDir(vendor_dir, e) => {
+            let vendor_dir = file_value(vendor_dir);
             create_error()
-                .error_type(Internal)
-                .header(format!("Could not determine file name for {package}"))
+                .code("DEB_PKG_E098")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Error creating {vendor_dir}"))
                 .body(formatdoc! { "
-                    The package information for {package} contains a Filename field of {filename} \
-                    which produces an invalid name to use as a download path.
+                    The directory to download vendored packages into could not be created at {vendor_dir}.
+
+                    Suggestions:
+                    - Ensure you have write permissions for the application directory.
                 " })
+                .debug_info(e.to_string())
                 .call()
         }
 
-        InstallPackagesError::RequestPackage(package, e) => {
-            let package = style::value(package.name);
+        VendorError::CopyPackage(from, to, e) => {
+            let from = file_value(from);
+            let to = file_value(to);
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to request package")
+                .code("DEB_PKG_E099")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Error copying vendored package")
                 .body(formatdoc! { "
-                    While installing packages, an error occurred while downloading {package}. \
-                    This error can occur due to an unstable network connection or an issue \
-                    with the upstream Debian package repository.
+                    The downloaded package at {from} could not be copied to {to}.
 
                     Suggestions:
-                    - Check the status of {canonical_status_url} for any reported issues.
+                    - Ensure you have write permissions for the vendor directory.
                 " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        InstallPackagesError::RequestPackageUrl(download_url, e) => {
-            let url = style::url(download_url.to_string());
+        VendorError::ReadPackage(file, e) => {
+            let file = file_value(file);
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to request package from download url")
+                .code("DEB_PKG_E100")
+                .error_type(Internal)
+                .header("Failed to read downloaded package")
+                .body(formatdoc! {
+                    "An unexpected I/O error occurred while calculating the checksum of the package at {file}."
+                })
+                .debug_info(e.to_string())
+                .call()
+        }
+    }
+}
+
+fn on_check_security_advisories_error(error: CheckSecurityAdvisoriesError) -> ErrorMessage {
+    match error {
+        CheckSecurityAdvisoriesError::AffectedPackages(affected_packages) => {
+            let security_advisories_key = style::value("security_advisories");
+            let fail = style::value("\"fail\"");
+            let warn = style::value("\"warn\"");
+            let ubuntu_security_notices_url = style::url("https://ubuntu.com/security/notices");
+            let affected_packages = affected_packages
+                .iter()
+                .map(|affected_package| format!("- {affected_package}"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            create_error()
+                .code("DEB_PKG_E101")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Packages affected by security advisories")
                 .body(formatdoc! { "
-                    While installing packages, an error occurred while downloading the package at {url}. \
-                    This error can occur due to an unstable network connection or an issue \
-                    with site this package is hosted at.
+                    The {BUILDPACK_NAME} is configured with {security_advisories_key} set to {fail} \
+                    and the following packages being installed are affected by known security advisories:
+
+                    {affected_packages}
 
                     Suggestions:
-                    - Check if {url} can be downloaded locally or if there's an error.
+                    - Review the advisories at {ubuntu_security_notices_url} and update the affected \
+                    packages once fixed versions are published.
+                    - Set {security_advisories_key} to {warn} to report the advisories without failing the build.
                 " })
-                .debug_info(e.to_string())
                 .call()
         }
 
-        InstallPackagesError::WritePackage(package, download_url, destination_path, e) => {
-            let package = style::value(package.name);
-            let download_url = style::url(download_url);
-            let destination_path = file_value(destination_path);
+        CheckSecurityAdvisoriesError::QueryAdvisories(reason) => {
+            let security_advisories_key = style::value("security_advisories");
+            let fail = style::value("\"fail\"");
+            let warn = style::value("\"warn\"");
+            let osv_status_url = style::url("https://osv.dev");
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to download package")
+                .code("DEB_PKG_E117")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header("Failed to check security advisories")
                 .body(formatdoc! { "
-                    An unexpected I/O error occured while downloading {package} from {download_url} \
-                    to {destination_path}. This error can occur due to an unstable network connection or an issue \
-                    with the upstream Debian package repository.
+                    The {BUILDPACK_NAME} is configured with {security_advisories_key} set to {fail} \
+                    but the security advisories affecting the packages being installed couldn't be \
+                    retrieved. This error can occur due to an unstable network connection or an issue \
+                    with {osv_status_url}.
 
                     Suggestions:
-                    - Check the status of {canonical_status_url} for any reported issues.
+                    - Set {security_advisories_key} to {warn} to skip the check when the advisories \
+                    can't be retrieved.
                 " })
-                .debug_info(e.to_string())
+                .debug_info(reason)
                 .call()
         }
+    }
+}
 
-        InstallPackagesError::WritePackageUrl(download_url, destination_path, e) => {
-            let download_url = style::url(download_url.to_string());
-            let destination_path = file_value(destination_path);
+fn on_contents_index_error(error: ContentsIndexError) -> ErrorMessage {
+    let auto_resolve_libs_key = style::value("auto_resolve_libs");
+    let canonical_status_url = get_canonical_status_url();
+    match error {
+        ContentsIndexError::Request(e) => create_error()
+            .code("DEB_PKG_E102")
+            .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
+            .header("Failed to request Contents Index file")
+            .body(formatdoc! { "
+                While finding packages that provide missing shared libraries for {auto_resolve_libs_key}, \
+                a request to download a Contents Index file failed. This error can occur due to an \
+                unstable network connection or an issue with the upstream Debian package repository.
+
+                Suggestions:
+                - Check the status of {canonical_status_url} for any reported issues.
+                - Add the packages providing the missing shared libraries to {install_key} and disable \
+                {auto_resolve_libs_key}.
+            ", install_key = style::value("install") })
+            .debug_info(e.to_string())
+            .call(),
+
+        ContentsIndexError::Read(url, e) => {
+            let url = style::url(url);
             create_error()
+                .code("DEB_PKG_E103")
                 .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::Yes))
-                .header("Failed to download package")
+                .header("Failed to read Contents Index file")
                 .body(formatdoc! { "
-                    An unexpected I/O error occured while downloading the package at {download_url} \
-                    to {destination_path}. This error can occur due to an unstable network connection or an issue \
-                    with the site this packages is hosted at.
-
-                    Suggestions:
-                    - Check if {download_url} can be downloaded locally or if there's an error.
+                    While finding packages that provide missing shared libraries for {auto_resolve_libs_key}, \
+                    an error occurred while reading the Contents Index at {url}. This error can occur \
+                    due to an unstable network connection or an issue with the upstream Debian package \
+                    repository.
                 " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        InstallPackagesError::ChecksumFailed {
+        ContentsIndexError::ChecksumFailed {
             url,
             expected,
             actual,
@@ -805,11 +2470,13 @@ fn on_install_packages_error(error: InstallPackagesError) -> ErrorMessage {
             let expected = style::value(expected);
             let actual = style::value(actual);
             create_error()
+                .code("DEB_PKG_E104")
                 .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
-                .header("Package checksum verification failed")
+                .header("Contents Index checksum verification failed")
                 .body(formatdoc! { "
-                    An error occurred while verifying the checksum of the package at {url}. \
-                    This error can occur due to an issue with the upstream Debian package repository.
+                    While finding packages that provide missing shared libraries for {auto_resolve_libs_key}, \
+                    an error occurred while verifying the checksum of the Contents Index at {url}. This \
+                    error can occur due to an issue with the upstream Debian package repository.
 
                     Checksum:
                     - Expected: {expected}
@@ -817,92 +2484,213 @@ fn on_install_packages_error(error: InstallPackagesError) -> ErrorMessage {
                 " })
                 .call()
         }
+    }
+}
 
-        InstallPackagesError::OpenPackageArchive(file, e) => {
-            let file = file_value(file);
+fn on_client_identity_error(error: ClientIdentityError) -> ErrorMessage {
+    let client_cert = style::value("client_cert");
+    let client_key = style::value("client_key");
+    match error {
+        ClientIdentityError::ReadPemFile(path, e) => {
+            let path = file_value(path);
             create_error()
-                .error_type(Internal)
-                .header("Failed to open package archive")
-                .body(formatdoc! {
-                    "An unexpected I/O error occurred while trying to open the archive at {file}."
-                })
+                .code("DEB_PKG_E105")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header(format!("Error reading {path}"))
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} reads the client certificate and private key configured with \
+                    {client_cert} and {client_key} for a custom source from {path} but the file can't be read.
+
+                    Suggestions:
+                    - Ensure the file exists and has read permissions.
+                    - Relative paths are resolved from the root of your application.
+                " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        InstallPackagesError::OpenPackageArchiveEntry(file, e) => {
-            let file = file_value(file);
+        ClientIdentityError::MissingPemEnv(name) => {
+            let name = style::value(name);
             create_error()
-                .error_type(Internal)
-                .header("Failed to read package archive entry")
-                .body(formatdoc! {
-                    "An unexpected I/O error occurred while trying to read the entries of the \
-                    archive at {file}."
-                })
+                .code("DEB_PKG_E106")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Missing environment variable {name}"))
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} reads the client certificate and private key configured with \
+                    {client_cert} and {client_key} for a custom source from the environment variable \
+                    {name} but this variable isn't set.
+
+                    Suggestions:
+                    - Set {name} to the PEM-encoded contents of the certificate or key.
+                " })
+                .call()
+        }
+
+        ClientIdentityError::InvalidClientIdentity(uri, e) => {
+            let uri = style::url(uri);
+            create_error()
+                .code("DEB_PKG_E107")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Invalid client certificate")
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} couldn't load the client certificate and private key configured \
+                    with {client_cert} and {client_key} for the custom source at {uri}.
+
+                    Suggestions:
+                    - Ensure {client_cert} is a PEM-encoded certificate (or certificate chain).
+                    - Ensure {client_key} is a PEM-encoded private key that matches the certificate.
+                " })
                 .debug_info(e.to_string())
                 .call()
         }
+    }
+}
 
-        InstallPackagesError::UnpackTarball(file, e) => {
-            let file = file_value(file);
+fn on_host_credentials_error(error: HostCredentialsError) -> ErrorMessage {
+    let binding_type = style::value(BINDING_TYPE);
+    let netrc_binding_type = style::value(NETRC_BINDING_TYPE);
+    match error {
+        HostCredentialsError::ReadBinding(path, e) => {
+            let path = file_value(path);
             create_error()
-                .error_type(Internal)
-                .header("Failed to unpack package archive")
-                .body(formatdoc! {
-                    "An unexpected I/O error occurred while trying to unpack the archive at {file}."
-                })
+                .code("DEB_PKG_E108")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header(format!("Error reading {path}"))
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} reads repository credentials from service bindings of the \
+                    {binding_type} or {netrc_binding_type} type but {path} can't be read.
+
+                    Suggestions:
+                    - Ensure the binding files exist and have read permissions.
+                " })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        InstallPackagesError::UnsupportedCompression(file, format) => {
-            let file = file_value(file);
-            let format = style::value(format);
+        HostCredentialsError::InvalidBinding(path, reason) => {
+            let path = file_value(path);
+            let host = style::value("host");
+            let username = style::value("username");
+            let password = style::value("password");
+            let token = style::value("token");
             create_error()
-                .error_type(Internal)
-                .header("Unsupported compression format for package archive")
-                .body(formatdoc! {
-                    "An unexpected compression format ({format}) was used for the package archive at {file}."
-                })
+                .code("DEB_PKG_E109")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Invalid repository credentials binding")
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} reads repository credentials from service bindings of the \
+                    {binding_type} or {netrc_binding_type} type but the binding at {path} is invalid \
+                    because {reason}.
+
+                    Suggestions:
+                    - For a {binding_type} binding, add a {host} entry with the host of the repository \
+                    (e.g.; {host_example}) and either {username} and {password} entries for basic \
+                    authentication or a {token} entry for a bearer token.
+                    - For a {netrc_binding_type} binding, add a {netrc_entry} entry with the contents of \
+                    a {netrc_entry} file.
+                ", host_example = style::value("apt.example.com"), netrc_entry = style::value(".netrc") })
                 .call()
         }
 
-        InstallPackagesError::ReadPackageConfig(file, e) => {
-            let file = file_value(file);
+        HostCredentialsError::ReadNetrc(path, e) => {
+            let path = file_value(path);
+            let netrc_env = style::value(NETRC_ENV);
             create_error()
-                .error_type(Internal)
-                .header("Failed to read package config file")
-                .body(formatdoc! {
-                    "An unexpected I/O error occurred while reading the package config file at {file}."
-                })
+                .code("DEB_PKG_E110")
+                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
+                .header(format!("Error reading {path}"))
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} reads repository credentials from the {netrc} file given \
+                    with {netrc_env} or a {netrc_binding_type} service binding but {path} can't be read.
+
+                    Suggestions:
+                    - Ensure the file exists and has read permissions.
+                    - Unset {netrc_env} if the file isn't needed for this build.
+                ", netrc = style::value(".netrc") })
                 .debug_info(e.to_string())
                 .call()
         }
 
-        InstallPackagesError::WritePackageConfig(file, e) => {
+        HostCredentialsError::InvalidCredentialsEnv(host) => {
+            let credentials_env = style::value(CREDENTIALS_ENV);
+            let host = style::value(host);
+            create_error()
+                .code("DEB_PKG_E111")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Invalid {credentials_env} entry"))
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} reads repository credentials from {credentials_env} but the \
+                    entry for {host} is invalid.
+
+                    Suggestions:
+                    - Separate entries with whitespace and write each one as {entry_example}.
+                ", entry_example = style::value("<username>:<password>@<host>") })
+                .call()
+        }
+    }
+}
+
+fn on_s3_error(error: S3Error) -> ErrorMessage {
+    match error {
+        S3Error::MissingEnvironmentVariables(names) => {
+            let s3_uri = style::value("s3://");
+            let missing = names
+                .iter()
+                .map(|name| format!("- {}", style::value(name)))
+                .collect::<Vec<_>>()
+                .join("\n");
+            create_error()
+                .code("DEB_PKG_E112")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header("Missing AWS credentials")
+                .body(formatdoc! { "
+                    The {BUILDPACK_NAME} signs requests to custom sources with an {s3_uri} uri using \
+                    the standard AWS environment variables but the following variables aren't set:
+
+                    {missing}
+
+                    Suggestions:
+                    - Set these variables to the credentials and region of an AWS user or role with \
+                    read access to the bucket.
+                    - {aws_default_region} is also accepted in place of {aws_region}.
+                ", aws_default_region = style::value("AWS_DEFAULT_REGION"), aws_region = style::value("AWS_REGION") })
+                .call()
+        }
+    }
+}
+
+fn on_hook_error(error: HookError) -> ErrorMessage {
+    match error {
+        HookError::WriteManifest(file, e) => {
             let file = file_value(file);
             create_error()
+                .code("DEB_PKG_E113")
                 .error_type(Internal)
-                .header("Failed to write package config file")
+                .header("Failed to write hook manifest")
                 .body(formatdoc! {
-                    "An unexpected I/O error occurred while writing the package config file to {file}."
+                    "An unexpected I/O error occurred while writing the manifest of installed packages to {file}."
                 })
                 .debug_info(e.to_string())
                 .call()
         }
-    }
-}
 
-fn on_detect_error(error: DetectError) -> ErrorMessage {
-    match error {
-        DetectError::CheckExistsAptfile(file, e) | DetectError::CheckExistsProjectToml(file, e) => {
-            let file = file_value(file);
+        HookError::RunHook(hook, e) => {
+            let hook_key = style::value(format!("hooks.{hook}"));
+            let layer_dir_env = style::value(HOOK_LAYER_DIR_ENV);
+            let manifest_env = style::value(HOOK_MANIFEST_ENV);
             create_error()
-                .error_type(UserFacing(SuggestRetryBuild::Yes, SuggestSubmitIssue::No))
-                .header("Unable to complete buildpack detection")
+                .code("DEB_PKG_E114")
+                .error_type(UserFacing(SuggestRetryBuild::No, SuggestSubmitIssue::No))
+                .header(format!("Failed to run the {hook} hook"))
                 .body(formatdoc! { "
-                    An unexpected I/O error occurred while checking {file} to determine if the \
-                    {BUILDPACK_NAME} is compatible for this application.
+                    The {BUILDPACK_NAME} runs the command configured in {hook_key} while installing \
+                    packages but the command failed.
+
+                    Suggestions:
+                    - Check the command output above for the cause of the failure.
+                    - The packages layer is available in {layer_dir_env} and a JSON manifest of the \
+                    installed packages in {manifest_env}. Relative paths are resolved from the \
+                    application directory.
                 " })
                 .debug_info(e.to_string())
                 .call()
@@ -912,6 +2700,7 @@ fn on_detect_error(error: DetectError) -> ErrorMessage {
 
 fn on_framework_error(error: &Error<DebianPackagesBuildpackError>) -> ErrorMessage {
     create_error()
+        .code("DEB_PKG_E115")
         .error_type(Framework)
         .header("Heroku Deb Packages Buildpack internal error")
         .body(formatdoc! {"
@@ -930,15 +2719,14 @@ fn on_framework_error(error: &Error<DebianPackagesBuildpackError>) -> ErrorMessa
 
 #[builder]
 fn create_error(
+    code: &'static str,
     header: impl AsRef<str>,
     body: impl AsRef<str>,
     error_type: ErrorType,
     debug_info: Option<String>,
 ) -> ErrorMessage {
-    let mut message_parts = vec![
-        header.as_ref().trim().to_string(),
-        body.as_ref().trim().to_string(),
-    ];
+    let header = header.as_ref().trim().to_string();
+    let mut message_parts = vec![header.clone(), body.as_ref().trim().to_string()];
     let issues_url = style::url("https://github.com/heroku/buildpacks-deb-packages/issues/new");
     let pack = style::value("pack");
     let pack_url =
@@ -980,9 +2768,13 @@ fn create_error(
         }
     }
 
+    message_parts.push(format!("Error code: {}", style::value(code)));
+
     let message = message_parts.join("\n\n");
 
     ErrorMessage {
+        code,
+        header,
         debug_info,
         message,
     }
@@ -996,6 +2788,15 @@ fn print_error(error_message: ErrorMessage) {
     print::error(error_message.message);
 }
 
+// includes the status and tracing headers of each attempt (e.g.; `X-Request-Id`) so the operators
+// of a repository can find the failed requests on their side
+fn request_debug_info(error: &reqwest_middleware::Error) -> String {
+    match request_attempts(error) {
+        Some(attempts) => format!("{error}\n\n{attempts}"),
+        None => error.to_string(),
+    }
+}
+
 fn file_value(value: impl AsRef<Path>) -> String {
     style::value(value.as_ref().to_string_lossy())
 }
@@ -1010,6 +2811,8 @@ fn get_package_search_url() -> String {
 
 #[derive(Debug)]
 struct ErrorMessage {
+    code: &'static str,
+    header: String,
     debug_info: Option<String>,
     message: String,
 }
@@ -1050,17 +2853,19 @@ enum SuggestSubmitIssue {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::check_security_advisories::AffectedPackage;
+    use crate::config::UnknownKey;
     use crate::config::download_url::DownloadUrl;
     use crate::debian::{
-        ParsePackageNameError, ParseRepositoryPackageError, RepositoryPackage, RepositoryUri,
-        SourceOrder, UnsupportedArchitectureNameError,
+        MultiArch, PackageChecksum, ParsePackageNameError, ParseRepositoryPackageError,
+        RepositoryPackage, RepositoryUri, SourceOrder, UnsupportedArchitectureNameError,
     };
     use anyhow::anyhow;
     use bullet_stream::strip_ansi;
     use insta::{assert_snapshot, with_settings};
     use libcnb::data::layer::LayerNameError;
     use reqwest::Url;
-    use std::collections::HashSet;
+    use std::collections::{BTreeSet, HashSet};
     use std::path::PathBuf;
     use std::str::FromStr;
     use toml_edit::Table;
@@ -1073,6 +2878,14 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn test_detect_check_exists_deb_packages_toml_error() {
+        assert_error_snapshot(&on_detect_error(DetectError::CheckExistsDebPackagesToml(
+            "/path/to/deb-packages.toml".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
     #[test]
     fn test_detect_check_exists_aptfile_error() {
         assert_error_snapshot(&on_detect_error(DetectError::CheckExistsAptfile(
@@ -1081,6 +2894,126 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn cli_get_current_dir_error() {
+        assert_error_snapshot(&on_cli_error(CliError::GetCurrentDir(create_io_error(
+            "test I/O error",
+        ))));
+    }
+
+    #[test]
+    fn cli_create_cache_dir_error() {
+        assert_error_snapshot(&on_cli_error(CliError::CreateCacheDir(
+            "/path/to/cache".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn cli_missing_config_file_error() {
+        assert_error_snapshot(&on_cli_error(CliError::MissingConfigFile(
+            "/path/to/app".into(),
+        )));
+    }
+
+    #[test]
+    fn cli_doctor_checks_failed_error() {
+        assert_error_snapshot(&on_cli_error(CliError::DoctorChecksFailed(3)));
+    }
+
+    #[test]
+    fn cli_unknown_config_keys_error() {
+        assert_error_snapshot(&on_cli_error(CliError::UnknownConfigKeys(
+            "/path/to/project.toml".into(),
+            vec![
+                UnknownKey {
+                    path: "instal".to_string(),
+                    suggestion: Some("install"),
+                },
+                UnknownKey {
+                    path: "sources[0].signedby".to_string(),
+                    suggestion: Some("signed_by"),
+                },
+            ],
+        )));
+    }
+
+    #[test]
+    fn lockfile_read_lockfile_error() {
+        assert_error_snapshot(&on_lockfile_error(LockfileError::Read(
+            "/path/to/deb-packages.lock".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn lockfile_parse_lockfile_error() {
+        assert_error_snapshot(&on_lockfile_error(LockfileError::Parse(
+            "/path/to/deb-packages.lock".into(),
+            Box::new(toml::from_str::<toml::Table>("[[target]]\ndistro_name =").unwrap_err()),
+        )));
+    }
+
+    #[test]
+    fn lockfile_write_lockfile_error() {
+        assert_error_snapshot(&on_lockfile_error(LockfileError::Write(
+            "/path/to/deb-packages.lock".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn config_invalid_env_package_name_error() {
+        assert_error_snapshot(&on_config_error(ConfigError::InvalidEnvPackageName(
+            ParsePackageNameError {
+                package_name: "invalid!package!name".to_string(),
+            },
+        )));
+    }
+
+    #[test]
+    fn config_unknown_profile_error() {
+        assert_error_snapshot(&on_config_error(ConfigError::UnknownProfile(
+            "production".to_string(),
+            vec!["ci".to_string(), "review".to_string()],
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_profile() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidProfile("[\"strace\"]".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_http_option() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidHttpOption("pool_idle_timeout".to_string(), "90".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_http_resolve_address() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidHttpOption(
+                "resolve.archive.ubuntu.com".to_string(),
+                "\"archive\"".to_string(),
+            ),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_hook() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidHook("post_install".to_string(), "1".to_string()),
+        )));
+    }
+
     #[test]
     fn config_read_config_error() {
         assert_error_snapshot(&on_config_error(ConfigError::ReadConfig(
@@ -1124,9 +3057,49 @@ mod tests {
         assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
             "/path/to/project.toml".into(),
             ParseConfigError::ParseRequestedPackage(Box::from(
-                ParseRequestedPackageError::UnexpectedTomlValue(
+                ParseRequestedPackageError::UnexpectedTomlValue(Box::new(
                     toml_edit::value(37).into_value().unwrap(),
-                ),
+                )),
+            )),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_requested_package_invalid_architecture() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::ParseRequestedPackage(Box::new(
+                ParseRequestedPackageError::InvalidArchitecture("arm64".to_string()),
+            )),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_suite() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::ParseRequestedPackage(Box::new(
+                ParseRequestedPackageError::InvalidSuite(r#""""#.to_string()),
+            )),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_include_paths() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::ParseRequestedPackage(Box::new(
+                ParseRequestedPackageError::InvalidIncludePaths("[]".to_string()),
+            )),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_prefix() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::ParseRequestedPackage(Box::new(
+                ParseRequestedPackageError::InvalidPrefix(r#""../opt""#.to_string()),
             )),
         )));
     }
@@ -1207,6 +3180,18 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn config_parse_config_error_for_custom_source_with_incomplete_client_identity() {
+        let mut table = create_custom_source_table();
+        table.insert("client_cert", toml_edit::value("certs/client.pem"));
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::ParseCustomSource(Box::from(
+                ParseCustomSourceError::IncompleteClientIdentity(table),
+            )),
+        )));
+    }
+
     #[test]
     fn config_parse_config_error_for_custom_source_with_missing_architecture_names() {
         let mut table = create_custom_source_table();
@@ -1247,6 +3232,16 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn config_parse_config_error_for_missing_download_checksum() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::MissingDownloadChecksum(
+                "https://example.com/package-1.2.3.deb".to_string(),
+            ),
+        )));
+    }
+
     #[test]
     fn config_parse_config_error_for_invalid_download_url_config_type() {
         assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
@@ -1259,6 +3254,119 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn config_parse_config_error_for_invalid_security_advisories() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidSecurityAdvisories("\"strict\"".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_essential_packages() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidEssentialPackages("\"refuse\"".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_prefer_alternatives() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidPreferAlternatives(r#"["cdebconf"]"#.to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_unsupported_schema_version() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::UnsupportedSchemaVersion(r#""0.4""#.to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_unknown_keys() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::UnknownKeys(vec![
+                UnknownKey {
+                    path: "instal".to_string(),
+                    suggestion: Some("install"),
+                },
+                UnknownKey {
+                    path: "sources[0].colour".to_string(),
+                    suggestion: None,
+                },
+            ]),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_env_order() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidEnvOrder(r#"{ PATH = "last" }"#.to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_pins() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidPins(r#"{ "libssl3t64" = 3 }"#.to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_index_max_age() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidIndexMaxAge("\"1 day\"".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_max_layer_size() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidMaxLayerSize("\"500 megabytes\"".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_max_download_cache_size() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidMaxDownloadCacheSize("\"1 gigabyte\"".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_default_components() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidDefaultComponents("[\"main\", \"partner\"]".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_default_source_signed_by() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidDefaultSourceSignedBy("\"\"".to_string()),
+        )));
+    }
+
+    #[test]
+    fn config_parse_config_error_for_invalid_prune() {
+        assert_error_snapshot(&on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::InvalidPrune("[\"static-libs\", \"docs\"]".to_string()),
+        )));
+    }
+
     #[test]
     fn unsupported_distro_error() {
         assert_error_snapshot(&on_unsupported_distro_error(UnsupportedDistroError {
@@ -1415,120 +3523,274 @@ mod tests {
     }
 
     #[test]
-    fn create_package_index_error_cpu_task_failed() {
-        assert_error_snapshot(&on_create_package_index_error(
-            CreatePackageIndexError::CpuTaskFailed(create_recv_error()),
+    fn create_package_index_error_cpu_task_failed() {
+        assert_error_snapshot(&on_create_package_index_error(
+            CreatePackageIndexError::CpuTaskFailed(create_recv_error()),
+        ));
+    }
+
+    #[test]
+    fn create_package_index_error_read_packages_file() {
+        assert_error_snapshot(&on_create_package_index_error(
+            CreatePackageIndexError::ReadPackagesFile(
+                "/path/to/layer/packages-file".into(),
+                create_io_error("entity not found"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn create_package_index_error_parse_packages() {
+        assert_error_snapshot(&on_create_package_index_error(
+            CreatePackageIndexError::ParsePackages {
+                url: "https://ppa.launchpadcontent.net/example/ppa/ubuntu/dists/noble/main/binary-amd64/Packages.gz".to_string(),
+                path: "/path/to/layer/packages-file".into(),
+                errors: vec![
+                    ParseRepositoryPackageError::MissingPackageName,
+                    ParseRepositoryPackageError::MissingVersion("package-a".to_string()),
+                    ParseRepositoryPackageError::InvalidVersion(
+                        "package-a2".to_string(),
+                        "not!valid".to_string(),
+                    ),
+                    ParseRepositoryPackageError::MissingFilename("package-b".to_string()),
+                    ParseRepositoryPackageError::MissingSha256("package-c".to_string()),
+                ],
+            },
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_read_system_packages() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::ReadSystemPackages(
+                "/var/lib/dpkg/status".into(),
+                create_io_error("entity not found"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_parse_system_packages() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::ParseSystemPackage(
+                "/var/lib/dpkg/status".into(),
+                "some-package".to_string(),
+                apt_parser::errors::APTError::KVError(apt_parser::errors::KVError),
+            ),
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_package_not_found() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::PackageNotFound(
+                "some-package".to_string(),
+                vec!["some-package1".to_string(), "some-package2".to_string()],
+            ),
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_package_not_found_with_no_suggestions() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::PackageNotFound("some-package".to_string(), vec![]),
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_virtual_package_must_be_specified() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::VirtualPackageMustBeSpecified(
+                "some-package".to_string(),
+                HashSet::from(["package-b".to_string(), "package-a".to_string()]),
+            ),
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_essential_packages() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::EssentialPackages(vec![
+                "libc6 (2.39-0ubuntu8.4)".to_string(),
+                "dpkg (1.22.6ubuntu6.1)".to_string(),
+            ]),
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_pinned_version_not_found() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::PinnedVersionNotFound(
+                "libssl3t64".to_string(),
+                "3.0.13-0ubuntu3.4".to_string(),
+                vec!["3.0.13-0ubuntu3.5".to_string()],
+            ),
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_package_not_found_in_suite() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::PackageNotFoundInSuite(
+                "cmake".to_string(),
+                "noble-backport".to_string(),
+                vec!["noble".to_string(), "noble-updates".to_string()],
+            ),
+        ));
+    }
+
+    #[test]
+    fn determine_packages_to_install_error_invalid_package_name() {
+        assert_error_snapshot(&on_determine_packages_to_install_error(
+            DeterminePackagesToInstallError::InvalidPackageName("Not A Package".to_string()),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_task_failed() {
+        assert_error_snapshot_with_filters(
+            &on_install_packages_error(InstallPackagesError::TaskFailed(create_join_error())),
+            vec![("task \\d+", "task <ID>")],
+        );
+    }
+
+    #[test]
+    fn install_packages_error_invalid_filename() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::InvalidFilename("some-package".to_string(), "..".to_string()),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_request_package() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::RequestPackage(
+                repository_package("some-package"),
+                create_reqwest_middleware_error(),
+            ),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_request_package_url() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::RequestPackageUrl(
+                DownloadUrl::from_str("https://example.com/custom-package.deb").unwrap(),
+                create_reqwest_middleware_error(),
+            ),
         ));
     }
 
     #[test]
-    fn create_package_index_error_read_packages_file() {
-        assert_error_snapshot(&on_create_package_index_error(
-            CreatePackageIndexError::ReadPackagesFile(
-                "/path/to/layer/packages-file".into(),
-                create_io_error("entity not found"),
+    fn install_packages_error_request_package_url_from_github_release() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::RequestPackageUrl(
+                DownloadUrl::from_str(
+                    "https://github.com/owner/repo/releases/download/v1.2.3/custom-package.deb",
+                )
+                .unwrap(),
+                create_reqwest_middleware_error(),
             ),
         ));
     }
 
     #[test]
-    fn create_package_index_error_parse_packages() {
-        assert_error_snapshot(&on_create_package_index_error(
-            CreatePackageIndexError::ParsePackages(
-                "/path/to/layer/packages-file".into(),
-                vec![
-                    ParseRepositoryPackageError::MissingPackageName,
-                    ParseRepositoryPackageError::MissingVersion("package-a".to_string()),
-                    ParseRepositoryPackageError::InvalidVersion(
-                        "package-a2".to_string(),
-                        "not!valid".to_string(),
-                    ),
-                    ParseRepositoryPackageError::MissingFilename("package-b".to_string()),
-                    ParseRepositoryPackageError::MissingSha256("package-c".to_string()),
-                ],
+    fn install_packages_error_request_package_signature() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::RequestPackageSignature(
+                "https://example.com/custom-package.deb.asc".to_string(),
+                create_reqwest_middleware_error(),
             ),
         ));
     }
 
     #[test]
-    fn determine_packages_to_install_error_read_system_packages() {
-        assert_error_snapshot(&on_determine_packages_to_install_error(
-            DeterminePackagesToInstallError::ReadSystemPackages(
-                "/var/lib/dpkg/status".into(),
-                create_io_error("entity not found"),
+    fn install_packages_error_verify_package_signature_missing_signature() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::VerifyPackageSignature(
+                "some-package@1.2.3".to_string(),
+                PackageSignatureError::MissingSignature,
             ),
         ));
     }
 
     #[test]
-    fn determine_packages_to_install_error_parse_system_packages() {
-        assert_error_snapshot(&on_determine_packages_to_install_error(
-            DeterminePackagesToInstallError::ParseSystemPackage(
-                "/var/lib/dpkg/status".into(),
-                "some-package".to_string(),
-                apt_parser::errors::APTError::KVError(apt_parser::errors::KVError),
+    fn install_packages_error_verify_package_signature_load_signing_key() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::VerifyPackageSignature(
+                "some-package@1.2.3".to_string(),
+                PackageSignatureError::LoadSigningKey(
+                    anyhow::anyhow!("No such file or directory (os error 2)")
+                        .context("Failed to read keyring file keys/vendor.gpg"),
+                ),
             ),
         ));
     }
 
     #[test]
-    fn determine_packages_to_install_error_package_not_found() {
-        assert_error_snapshot(&on_determine_packages_to_install_error(
-            DeterminePackagesToInstallError::PackageNotFound(
-                "some-package".to_string(),
-                vec!["some-package1".to_string(), "some-package2".to_string()],
+    fn install_packages_error_verify_package_signature_invalid_signature() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::VerifyPackageSignature(
+                "some-package@1.2.3".to_string(),
+                PackageSignatureError::InvalidSignature(anyhow::anyhow!("No signature")),
             ),
         ));
     }
 
     #[test]
-    fn determine_packages_to_install_error_package_not_found_with_no_suggestions() {
-        assert_error_snapshot(&on_determine_packages_to_install_error(
-            DeterminePackagesToInstallError::PackageNotFound("some-package".to_string(), vec![]),
+    fn install_packages_error_verify_package_signature_duplicate_archive_member() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::VerifyPackageSignature(
+                "some-package@1.2.3".to_string(),
+                PackageSignatureError::DuplicateArchiveMember("data.tar.xz".to_string()),
+            ),
         ));
     }
 
     #[test]
-    fn determine_packages_to_install_error_virtual_package_must_be_specified() {
-        assert_error_snapshot(&on_determine_packages_to_install_error(
-            DeterminePackagesToInstallError::VirtualPackageMustBeSpecified(
-                "some-package".to_string(),
-                HashSet::from(["package-b".to_string(), "package-a".to_string()]),
+    fn install_packages_error_verify_package_signature_unexpected_archive_member() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::VerifyPackageSignature(
+                "some-package@1.2.3".to_string(),
+                PackageSignatureError::UnexpectedArchiveMember("extra".to_string()),
             ),
         ));
     }
 
     #[test]
-    fn install_packages_error_task_failed() {
-        assert_error_snapshot_with_filters(
-            &on_install_packages_error(InstallPackagesError::TaskFailed(create_join_error())),
-            vec![("task \\d+", "task <ID>")],
-        );
-    }
-
-    #[test]
-    fn install_packages_error_invalid_filename() {
+    fn install_packages_error_resolve_download_header() {
+        let download_url = DownloadUrl::try_from(
+            &toml_edit::Value::from_str(
+                r#"{ url = "https://example.com/custom-package.deb", headers = { "Authorization" = "env:ARTIFACT_TOKEN" } }"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let header = download_url.headers()[0].clone();
         assert_error_snapshot(&on_install_packages_error(
-            InstallPackagesError::InvalidFilename("some-package".to_string(), "..".to_string()),
+            InstallPackagesError::ResolveDownloadHeader(download_url, header),
         ));
     }
 
     #[test]
-    fn install_packages_error_request_package() {
+    fn install_packages_error_resolve_github_release_asset() {
         assert_error_snapshot(&on_install_packages_error(
-            InstallPackagesError::RequestPackage(
-                repository_package("some-package"),
-                create_reqwest_middleware_error(),
+            InstallPackagesError::ResolveGithubReleaseAsset(
+                DownloadUrl::from_str(
+                    "https://github.com/owner/repo/releases/download/v1.2.3/custom-package.deb",
+                )
+                .unwrap(),
+                "release `v1.2.3` has no asset named `custom-package.deb`".to_string(),
             ),
         ));
     }
 
     #[test]
-    fn install_packages_error_request_package_url() {
+    fn install_packages_error_create_download_dir() {
         assert_error_snapshot(&on_install_packages_error(
-            InstallPackagesError::RequestPackageUrl(
-                DownloadUrl::from_str("https://example.com/custom-package.deb").unwrap(),
-                create_reqwest_middleware_error(),
+            InstallPackagesError::CreateDownloadDir(
+                "/path/to/downloads".into(),
+                create_io_error("permission denied"),
             ),
         ));
     }
@@ -1597,6 +3859,28 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn install_packages_error_unsafe_tarball_entry_path_traversal() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::UnsafeTarballEntry(
+                "/path/to/layer/archive-file.deb".into(),
+                "./usr/../../etc/passwd".into(),
+                UnsafeTarballEntry::PathTraversal,
+            ),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_unsafe_tarball_entry_special_file() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::UnsafeTarballEntry(
+                "/path/to/layer/archive-file.deb".into(),
+                "./dev/sda".into(),
+                UnsafeTarballEntry::SpecialFile("block device"),
+            ),
+        ));
+    }
+
     #[test]
     fn install_packages_error_unsupported_compression() {
         assert_error_snapshot(&on_install_packages_error(
@@ -1627,12 +3911,318 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn install_packages_error_write_package_manifest() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::WritePackageManifest(
+                "/path/to/layer/var/lib/deb-packages/manifest.json".into(),
+                create_io_error("no space left on device"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_write_install_plan() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::WriteInstallPlan(
+                "/path/to/layer/plan.json".into(),
+                create_io_error("no space left on device"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_read_binary() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::ReadBinary(
+                "/path/to/layer/usr/bin/somebinary".into(),
+                create_io_error("permission denied"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_write_binary() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::WriteBinary(
+                "/path/to/layer/usr/bin/somebinary".into(),
+                create_io_error("no space left on device"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_prune_file() {
+        assert_error_snapshot(&on_install_packages_error(InstallPackagesError::PruneFile(
+            "/path/to/layer/usr/include".into(),
+            create_io_error("permission denied"),
+        )));
+    }
+
+    #[test]
+    fn install_packages_error_normalize_timestamp() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::NormalizeTimestamp(
+                "/path/to/layer/usr/bin/curl".into(),
+                create_io_error("permission denied"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_read_vendored_package() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::ReadVendoredPackage(
+                "/path/to/app/deb-vendor/curl_8.5.0_amd64.deb".into(),
+                create_io_error("not found"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn install_packages_error_vendored_package_checksum_failed() {
+        assert_error_snapshot(&on_install_packages_error(
+            InstallPackagesError::VendoredPackageChecksumFailed {
+                path: "/path/to/app/deb-vendor/curl_8.5.0_amd64.deb".into(),
+                expected: "7931f51fd704f93171f36f5f6f1d7b7b".to_string(),
+                actual: "19a47cdb280539511523382fa1cabbe5".to_string(),
+            },
+        ));
+    }
+
+    #[test]
+    fn vendor_read_manifest_error() {
+        assert_error_snapshot(&on_vendor_error(VendorError::ReadManifest(
+            "/path/to/deb-vendor/manifest.toml".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn vendor_parse_manifest_error() {
+        assert_error_snapshot(&on_vendor_error(VendorError::ParseManifest(
+            "/path/to/deb-vendor/manifest.toml".into(),
+            Box::new(toml::from_str::<toml::Table>("[[target]]\ndistro_name =").unwrap_err()),
+        )));
+    }
+
+    #[test]
+    fn vendor_write_manifest_error() {
+        assert_error_snapshot(&on_vendor_error(VendorError::WriteManifest(
+            "/path/to/deb-vendor/manifest.toml".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn vendor_create_vendor_dir_error() {
+        assert_error_snapshot(&on_vendor_error(VendorError::CreateVendorDir(
+            "/path/to/deb-vendor".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn vendor_copy_package_error() {
+        assert_error_snapshot(&on_vendor_error(VendorError::CopyPackage(
+            "/path/to/cache/curl_8.5.0_amd64.deb".into(),
+            "/path/to/deb-vendor/curl_8.5.0_amd64.deb".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn vendor_read_package_error() {
+        assert_error_snapshot(&on_vendor_error(VendorError::ReadPackage(
+            "/path/to/cache/some-package.deb".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn check_security_advisories_error_affected_packages() {
+        assert_error_snapshot(&on_check_security_advisories_error(
+            CheckSecurityAdvisoriesError::AffectedPackages(vec![
+                AffectedPackage {
+                    name: "curl".to_string(),
+                    version: "8.5.0-2ubuntu10.6".to_string(),
+                    advisories: BTreeSet::from([
+                        "CVE-2024-1234".to_string(),
+                        "USN-7000-1".to_string(),
+                    ]),
+                },
+                AffectedPackage {
+                    name: "libxmlsec1t64".to_string(),
+                    version: "1.2.39-5build2".to_string(),
+                    advisories: BTreeSet::from(["CVE-2024-5678".to_string()]),
+                },
+            ]),
+        ));
+    }
+
+    #[test]
+    fn check_security_advisories_error_query_advisories() {
+        assert_error_snapshot(&on_check_security_advisories_error(
+            CheckSecurityAdvisoriesError::QueryAdvisories(
+                "error sending request for url (https://api.osv.dev/v1/querybatch)".to_string(),
+            ),
+        ));
+    }
+
+    #[test]
+    fn contents_index_error_request() {
+        assert_error_snapshot(&on_contents_index_error(ContentsIndexError::Request(
+            create_reqwest_middleware_error(),
+        )));
+    }
+
+    #[test]
+    fn contents_index_error_read() {
+        assert_error_snapshot(&on_contents_index_error(ContentsIndexError::Read(
+            "http://archive.ubuntu.com/ubuntu/dists/noble/Contents-amd64.gz".to_string(),
+            create_io_error("unexpected end of file"),
+        )));
+    }
+
+    #[test]
+    fn contents_index_error_checksum_failed() {
+        assert_error_snapshot(&on_contents_index_error(
+            ContentsIndexError::ChecksumFailed {
+                url: "http://archive.ubuntu.com/ubuntu/dists/noble/by-hash/SHA256/d41d8cd98f00b204e9800998ecf8427e".to_string(),
+                expected: "d41d8cd98f00b204e9800998ecf8427e".to_string(),
+                actual: "e62ff0123a74adfc6903d59a449cbdb0".to_string(),
+            },
+        ));
+    }
+
+    #[test]
+    fn client_identity_read_pem_file_error() {
+        assert_error_snapshot(&on_client_identity_error(ClientIdentityError::ReadPemFile(
+            "certs/client.pem".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn client_identity_missing_pem_env_error() {
+        assert_error_snapshot(&on_client_identity_error(
+            ClientIdentityError::MissingPemEnv("APT_CLIENT_KEY".to_string()),
+        ));
+    }
+
+    #[test]
+    fn client_identity_invalid_client_identity_error() {
+        assert_error_snapshot(&on_client_identity_error(
+            ClientIdentityError::InvalidClientIdentity(
+                RepositoryUri::from("https://apt.example.com/ubuntu"),
+                create_reqwest_error(),
+            ),
+        ));
+    }
+
+    #[test]
+    fn host_credentials_read_binding_error() {
+        assert_error_snapshot(&on_host_credentials_error(
+            HostCredentialsError::ReadBinding(
+                "/bindings/apt-example/host".into(),
+                create_io_error("test I/O error"),
+            ),
+        ));
+    }
+
+    #[test]
+    fn host_credentials_invalid_binding_error() {
+        assert_error_snapshot(&on_host_credentials_error(
+            HostCredentialsError::InvalidBinding(
+                "/bindings/apt-example".into(),
+                "the `host` entry is missing".to_string(),
+            ),
+        ));
+    }
+
+    #[test]
+    fn host_credentials_read_netrc_error() {
+        assert_error_snapshot(&on_host_credentials_error(HostCredentialsError::ReadNetrc(
+            "/workspace/.netrc".into(),
+            create_io_error("test I/O error"),
+        )));
+    }
+
+    #[test]
+    fn host_credentials_invalid_credentials_env_error() {
+        assert_error_snapshot(&on_host_credentials_error(
+            HostCredentialsError::InvalidCredentialsEnv("apt.example.com".to_string()),
+        ));
+    }
+
+    #[test]
+    fn s3_missing_environment_variables_error() {
+        assert_error_snapshot(&on_s3_error(S3Error::MissingEnvironmentVariables(vec![
+            "AWS_SECRET_ACCESS_KEY".to_string(),
+            "AWS_REGION".to_string(),
+        ])));
+    }
+
+    #[test]
+    fn hook_write_manifest_error() {
+        assert_error_snapshot(&on_hook_error(HookError::WriteManifest(
+            "/layers/downloads/deb-packages-manifest.json".into(),
+            create_io_error("disk full"),
+        )));
+    }
+
+    #[test]
+    fn hook_run_hook_error() {
+        assert_error_snapshot(&on_hook_error(HookError::RunHook(
+            "post_install".to_string(),
+            Box::new(fun_run::CmdError::SystemError(
+                "bash scripts/fix-links.sh".to_string(),
+                create_io_error("No such file or directory"),
+            )),
+        )));
+    }
+
     #[test]
     fn framework_error() {
         let error = Error::CannotWriteBuildSbom(create_io_error("operation interrupted"));
         assert_error_snapshot(&on_framework_error(&error));
     }
 
+    #[test]
+    fn error_codes_are_unique() {
+        let source = include_str!("errors.rs");
+        let mut codes = HashSet::new();
+        for code in source
+            .split(".code(\"")
+            .skip(1)
+            .filter_map(|part| part.split_once('"').map(|(code, _)| code))
+            .filter(|code| code.starts_with("DEB_PKG_E"))
+        {
+            assert!(codes.insert(code), "{code} is used by more than one error");
+        }
+        assert!(!codes.is_empty());
+    }
+
+    #[test]
+    fn write_error_report_with_code() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let report_file = temp_dir.path().join(ERROR_REPORT_FILE);
+        let error_message = on_config_error(ConfigError::ParseConfig(
+            "/path/to/project.toml".into(),
+            ParseConfigError::WrongConfigType,
+        ));
+        write_error_report(&report_file, &error_message);
+        let report: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&report_file).unwrap()).unwrap();
+        assert_eq!(
+            report,
+            serde_json::json!({
+                "code": error_message.code,
+                "header": "Error parsing `/path/to/project.toml` with invalid key",
+            })
+        );
+    }
+
     fn assert_error_snapshot(error: &ErrorMessage) {
         assert_error_snapshot_with_filters(error, vec![]);
     }
@@ -1733,10 +4323,18 @@ mod tests {
             filename: format!("{package_name}.tgz"),
             repository_uri: RepositoryUri::from("https://test/path/to/repository"),
             source_order: SourceOrder::new(0, 0, 0),
-            sha256sum: String::new(),
+            checksum: PackageChecksum::Sha256(String::new()),
             depends: None,
             pre_depends: None,
             provides: None,
+            description: None,
+            source: None,
+            architecture: None,
+            multi_arch: MultiArch::No,
+            phased_update_percentage: None,
+            essential: false,
+            priority_required: false,
+            size: None,
         }
     }
 }
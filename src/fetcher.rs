@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+use reqwest::Response;
+use reqwest::header::HeaderMap;
+use reqwest_middleware::ClientWithMiddleware;
+
+// NOTE: Release files, package indices, and packages are fetched through this trait instead of the
+//       HTTP client directly so another transport can be swapped in (e.g.; a test double serving
+//       fixtures without a network). Builds always use the HTTP client, where `file://` and `s3://`
+//       sources are handled by its middleware. The response is returned as is so callers decide
+//       which statuses are errors.
+#[async_trait]
+pub(crate) trait Fetcher: Clone + Send + Sync + 'static {
+    async fn fetch(&self, url: &str, headers: HeaderMap) -> reqwest_middleware::Result<Response>;
+}
+
+#[async_trait]
+impl Fetcher for ClientWithMiddleware {
+    async fn fetch(&self, url: &str, headers: HeaderMap) -> reqwest_middleware::Result<Response> {
+        self.get(url).headers(headers).send().await
+    }
+}
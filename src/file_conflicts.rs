@@ -0,0 +1,222 @@
+use bullet_stream::style;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+
+// the number of conflicting files listed for each group of packages before the rest are summarized
+const MAX_LISTED_FILES: usize = 5;
+
+// NOTE: Packages are extracted in parallel so, when two packages contain the same file, the copy
+//       that ends up in the layer would otherwise depend on which package happened to finish
+//       extracting last. Instead, the file from the package that comes last in the install order
+//       is always the one installed and the other packages skip it.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct FileConflicts {
+    conflicts: Vec<FileConflict>,
+    replaced_files: Vec<HashSet<PathBuf>>,
+}
+
+// A group of packages (in install order) that all contain the same files.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct FileConflict {
+    pub(crate) packages: Vec<String>,
+    pub(crate) files: Vec<PathBuf>,
+}
+
+impl FileConflicts {
+    // `package_files` is the name of each package and the files (relative to the install
+    // directory) it would write, in install order
+    pub(crate) fn find(package_files: &[(String, Vec<PathBuf>)]) -> Self {
+        let mut file_owners: BTreeMap<&PathBuf, Vec<usize>> = BTreeMap::new();
+        for (index, (_, files)) in package_files.iter().enumerate() {
+            for file in files {
+                let owners = file_owners.entry(file).or_default();
+                if owners.last() != Some(&index) {
+                    owners.push(index);
+                }
+            }
+        }
+
+        let mut replaced_files = vec![HashSet::new(); package_files.len()];
+        let mut conflicting_files: BTreeMap<Vec<usize>, Vec<PathBuf>> = BTreeMap::new();
+        for (file, owners) in file_owners {
+            if owners.len() < 2 {
+                continue;
+            }
+            for owner in &owners[..owners.len() - 1] {
+                replaced_files[*owner].insert(file.clone());
+            }
+            conflicting_files
+                .entry(owners)
+                .or_default()
+                .push(file.clone());
+        }
+
+        let conflicts = conflicting_files
+            .into_iter()
+            .map(|(owners, files)| FileConflict {
+                packages: owners
+                    .into_iter()
+                    .map(|owner| package_files[owner].0.clone())
+                    .collect(),
+                files,
+            })
+            .collect();
+
+        Self {
+            conflicts,
+            replaced_files,
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    pub(crate) fn conflicts(&self) -> &[FileConflict] {
+        &self.conflicts
+    }
+
+    pub(crate) fn file_count(&self) -> usize {
+        self.conflicts
+            .iter()
+            .map(|conflict| conflict.files.len())
+            .sum()
+    }
+
+    // the files a package must skip since they're replaced by a package later in the install order
+    pub(crate) fn replaced_files(&self, package_index: usize) -> HashSet<PathBuf> {
+        self.replaced_files
+            .get(package_index)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+impl FileConflict {
+    pub(crate) fn installed_from(&self) -> &str {
+        self.packages
+            .last()
+            .expect("A file conflict should always have packages")
+    }
+}
+
+impl Display for FileConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut files = self
+            .files
+            .iter()
+            .take(MAX_LISTED_FILES)
+            .map(|file| style::value(format!("/{}", file.to_string_lossy())))
+            .collect::<Vec<_>>();
+        if self.files.len() > MAX_LISTED_FILES {
+            files.push(format!(
+                "and {count} more",
+                count = self.files.len() - MAX_LISTED_FILES
+            ));
+        }
+        write!(
+            f,
+            "{packages} contain the same files, installing the files from {installed_from}: {files}",
+            packages = self
+                .packages
+                .iter()
+                .map(style::value)
+                .collect::<Vec<_>>()
+                .join(", "),
+            installed_from = style::value(self.installed_from()),
+            files = files.join(", ")
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_file_conflicts_installs_the_file_from_the_last_package() {
+        let package_files = vec![
+            (
+                "package-a@1.0".to_string(),
+                vec![
+                    PathBuf::from("usr/bin/a"),
+                    PathBuf::from("usr/share/doc/shared"),
+                ],
+            ),
+            (
+                "package-b@2.0".to_string(),
+                vec![
+                    PathBuf::from("usr/bin/b"),
+                    PathBuf::from("usr/share/doc/shared"),
+                    PathBuf::from("usr/lib/libshared.so"),
+                ],
+            ),
+            (
+                "package-c@3.0".to_string(),
+                vec![
+                    PathBuf::from("usr/lib/libshared.so"),
+                    PathBuf::from("usr/share/doc/shared"),
+                ],
+            ),
+        ];
+
+        let file_conflicts = FileConflicts::find(&package_files);
+
+        assert_eq!(
+            file_conflicts.conflicts(),
+            &[
+                FileConflict {
+                    packages: vec![
+                        "package-a@1.0".to_string(),
+                        "package-b@2.0".to_string(),
+                        "package-c@3.0".to_string()
+                    ],
+                    files: vec![PathBuf::from("usr/share/doc/shared")],
+                },
+                FileConflict {
+                    packages: vec!["package-b@2.0".to_string(), "package-c@3.0".to_string()],
+                    files: vec![PathBuf::from("usr/lib/libshared.so")],
+                },
+            ]
+        );
+        assert_eq!(file_conflicts.file_count(), 2);
+        assert_eq!(
+            file_conflicts.replaced_files(0),
+            HashSet::from([PathBuf::from("usr/share/doc/shared")])
+        );
+        assert_eq!(
+            file_conflicts.replaced_files(1),
+            HashSet::from([
+                PathBuf::from("usr/share/doc/shared"),
+                PathBuf::from("usr/lib/libshared.so")
+            ])
+        );
+        assert!(file_conflicts.replaced_files(2).is_empty());
+    }
+
+    #[test]
+    fn find_file_conflicts_without_shared_files() {
+        let file_conflicts = FileConflicts::find(&[
+            ("package-a".to_string(), vec![PathBuf::from("usr/bin/a")]),
+            ("package-b".to_string(), vec![PathBuf::from("usr/bin/b")]),
+        ]);
+        assert!(file_conflicts.is_empty());
+        assert!(file_conflicts.replaced_files(0).is_empty());
+    }
+
+    #[test]
+    fn display_file_conflict_summarizes_long_file_lists() {
+        let file_conflict = FileConflict {
+            packages: vec!["package-a".to_string(), "package-b".to_string()],
+            files: (1..=7)
+                .map(|n| PathBuf::from(format!("usr/bin/tool{n}")))
+                .collect(),
+        };
+        assert_eq!(file_conflict.installed_from(), "package-b");
+        let display = file_conflict.to_string();
+        assert!(display.contains("tool5"));
+        assert!(!display.contains("tool6"));
+        assert!(display.ends_with("and 2 more"));
+    }
+}
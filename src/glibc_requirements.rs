@@ -0,0 +1,341 @@
+use crate::BuildpackResult;
+use crate::install_packages::InstallPackagesError;
+use crate::strip_binaries::has_elf_magic;
+use bullet_stream::style;
+use object::build::elf::{Builder, VersionData};
+use object::elf::{ET_DYN, ET_EXEC};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use walkdir::WalkDir;
+
+// the number of binaries listed for each package before the rest are summarized
+const MAX_LISTED_BINARIES: usize = 3;
+
+// NOTE: Binaries linked against glibc record the versions of the glibc symbols they use (e.g.;
+//       `GLIBC_2.38`) in their version needs. The dynamic linker refuses to start a binary needing
+//       a version newer than the installed glibc with an error like `version 'GLIBC_2.38' not
+//       found`. This happens when a package from a custom repository was built for a newer
+//       release than the target distro so these binaries are found while building instead.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct NewerGlibcRequirements {
+    // the packages (or the binary itself when it wasn't installed from a package) with binaries
+    // needing a newer glibc, the newest version they need, and those binaries (relative to the
+    // install directory)
+    packages: BTreeMap<String, (GlibcVersion, BTreeSet<PathBuf>)>,
+}
+
+impl NewerGlibcRequirements {
+    pub(crate) fn find(
+        install_path: &Path,
+        glibc_version: &GlibcVersion,
+        file_packages: &HashMap<PathBuf, String>,
+    ) -> BuildpackResult<Self> {
+        let mut packages: BTreeMap<String, (GlibcVersion, BTreeSet<PathBuf>)> = BTreeMap::new();
+
+        for entry in WalkDir::new(install_path).into_iter().flatten() {
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path();
+            if !has_elf_magic(path).map_err(|e| InstallPackagesError::ReadBinary(path.into(), e))? {
+                continue;
+            }
+            let contents =
+                fs::read(path).map_err(|e| InstallPackagesError::ReadBinary(path.into(), e))?;
+            let Some(required_version) = read_required_glibc_version(&contents) else {
+                continue;
+            };
+            if &required_version <= glibc_version {
+                continue;
+            }
+            let binary = path.strip_prefix(install_path).unwrap_or(path);
+            let package = file_packages
+                .get(binary)
+                .cloned()
+                .unwrap_or_else(|| format!("/{}", binary.to_string_lossy()));
+            let (newest_version, binaries) = packages
+                .entry(package)
+                .or_insert_with(|| (required_version.clone(), BTreeSet::new()));
+            if &required_version > newest_version {
+                *newest_version = required_version;
+            }
+            binaries.insert(binary.to_path_buf());
+        }
+
+        Ok(Self { packages })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+    }
+
+    pub(crate) fn package_count(&self) -> usize {
+        self.packages.len()
+    }
+}
+
+impl Display for NewerGlibcRequirements {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lines = self
+            .packages
+            .iter()
+            .map(|(package, (required_version, binaries))| {
+                let mut needed_by = binaries
+                    .iter()
+                    .take(MAX_LISTED_BINARIES)
+                    .map(|binary| style::value(format!("/{}", binary.to_string_lossy())))
+                    .collect::<Vec<_>>();
+                if binaries.len() > MAX_LISTED_BINARIES {
+                    needed_by.push(format!(
+                        "and {count} more",
+                        count = binaries.len() - MAX_LISTED_BINARIES
+                    ));
+                }
+                format!(
+                    "- {package} requires glibc {required_version} (needed by {needed_by})",
+                    package = style::value(package),
+                    required_version = style::value(required_version.to_string()),
+                    needed_by = needed_by.join(", ")
+                )
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// A glibc release version (e.g.; `2.39`) compared component by component, so `2.2.5` is older
+// than `2.17`.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) struct GlibcVersion(Vec<u32>);
+
+impl FromStr for GlibcVersion {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        value
+            .split('.')
+            .map(|part| part.parse::<u32>().map_err(|_| ()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(GlibcVersion)
+    }
+}
+
+impl Display for GlibcVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let parts = self.0.iter().map(ToString::to_string).collect::<Vec<_>>();
+        write!(f, "{}", parts.join("."))
+    }
+}
+
+// Only versions named like `GLIBC_2.34` are glibc releases, others like `GLIBC_PRIVATE` aren't
+// versioned. Anything that can't be parsed, or isn't a linked ELF binary, needs no glibc version.
+fn read_required_glibc_version(contents: &[u8]) -> Option<GlibcVersion> {
+    let builder = Builder::read(contents).ok()?;
+
+    if !matches!(builder.header.e_type, ET_EXEC | ET_DYN) {
+        return None;
+    }
+
+    builder
+        .versions
+        .iter()
+        .filter_map(|version| match &version.data {
+            VersionData::Need(version_need) => String::from_utf8_lossy(&version_need.name)
+                .strip_prefix("GLIBC_")
+                .and_then(|version| GlibcVersion::from_str(version).ok()),
+            VersionData::Def(_) => None,
+        })
+        .max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::Endianness;
+    use object::build::elf::{SectionData, VersionNeed};
+    use object::elf::{
+        EM_X86_64, PF_R, PT_LOAD, SHF_ALLOC, SHT_DYNSYM, SHT_GNU_VERNEED, SHT_GNU_VERSYM,
+        SHT_STRTAB, STB_GLOBAL, STT_FUNC,
+    };
+
+    #[test]
+    fn find_newer_glibc_requirements() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let install_path = temp_dir.path();
+        for (path, versions) in [
+            (
+                "usr/bin/example",
+                vec!["GLIBC_2.2.5", "GLIBC_2.38", "GLIBC_PRIVATE"],
+            ),
+            ("usr/bin/compatible", vec!["GLIBC_2.2.5", "GLIBC_2.34"]),
+            ("usr/lib/libexample.so.1", vec!["GLIBC_2.36"]),
+            ("opt/bin/hooked", vec!["GLIBC_2.39"]),
+        ] {
+            let path = install_path.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, create_elf(&versions)).unwrap();
+        }
+
+        let newer_glibc_requirements = NewerGlibcRequirements::find(
+            install_path,
+            &GlibcVersion::from_str("2.35").unwrap(),
+            &HashMap::from([
+                (PathBuf::from("usr/bin/example"), "example".to_string()),
+                (PathBuf::from("usr/bin/compatible"), "example".to_string()),
+                (
+                    PathBuf::from("usr/lib/libexample.so.1"),
+                    "example".to_string(),
+                ),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(
+            newer_glibc_requirements,
+            NewerGlibcRequirements {
+                packages: BTreeMap::from([
+                    (
+                        "/opt/bin/hooked".to_string(),
+                        (
+                            GlibcVersion::from_str("2.39").unwrap(),
+                            BTreeSet::from([PathBuf::from("opt/bin/hooked")])
+                        )
+                    ),
+                    (
+                        "example".to_string(),
+                        (
+                            GlibcVersion::from_str("2.38").unwrap(),
+                            BTreeSet::from([
+                                PathBuf::from("usr/bin/example"),
+                                PathBuf::from("usr/lib/libexample.so.1")
+                            ])
+                        )
+                    ),
+                ])
+            }
+        );
+    }
+
+    #[test]
+    fn compare_glibc_versions() {
+        let version = |value: &str| GlibcVersion::from_str(value).unwrap();
+        assert!(version("2.2.5") < version("2.17"));
+        assert!(version("2.39") < version("2.40"));
+        assert!(version("2.35") == version("2.35"));
+        assert_eq!(version("2.2.5").to_string(), "2.2.5");
+        assert_eq!(GlibcVersion::from_str("PRIVATE"), Err(()));
+    }
+
+    #[test]
+    fn newer_glibc_requirements_display() {
+        let version = |value: &str| GlibcVersion::from_str(value).unwrap();
+        let newer_glibc_requirements = NewerGlibcRequirements {
+            packages: BTreeMap::from([
+                (
+                    "example-tool".to_string(),
+                    (
+                        version("2.38"),
+                        BTreeSet::from([
+                            PathBuf::from("usr/bin/a"),
+                            PathBuf::from("usr/bin/b"),
+                            PathBuf::from("usr/bin/c"),
+                            PathBuf::from("usr/bin/d"),
+                        ]),
+                    ),
+                ),
+                (
+                    "/opt/bin/hooked".to_string(),
+                    (
+                        version("2.36"),
+                        BTreeSet::from([PathBuf::from("opt/bin/hooked")]),
+                    ),
+                ),
+            ]),
+        };
+        assert_eq!(
+            newer_glibc_requirements.to_string(),
+            format!(
+                "- {} requires glibc {} (needed by {})\n- {} requires glibc {} (needed by {}, {}, {}, and 1 more)",
+                style::value("/opt/bin/hooked"),
+                style::value("2.36"),
+                style::value("/opt/bin/hooked"),
+                style::value("example-tool"),
+                style::value("2.38"),
+                style::value("/usr/bin/a"),
+                style::value("/usr/bin/b"),
+                style::value("/usr/bin/c"),
+            )
+        );
+    }
+
+    fn create_elf(versions: &[&str]) -> Vec<u8> {
+        let mut builder = Builder::new(Endianness::Little, true);
+        builder.header.e_type = ET_DYN;
+        builder.header.e_machine = EM_X86_64;
+        builder.header.e_phoff = 0x40;
+
+        let libc = builder.version_files.add(b"libc.so.6"[..].into());
+        // versions that no dynamic symbol uses aren't written
+        for (index, version) in versions.iter().enumerate() {
+            let version_id = builder.versions.add(VersionData::Need(VersionNeed {
+                file: libc,
+                name: version.as_bytes().into(),
+                flags: 0,
+            }));
+            let symbol = builder.dynamic_symbols.add();
+            symbol.name = format!("symbol{index}").into_bytes().into();
+            symbol.st_info = (STB_GLOBAL << 4) | STT_FUNC;
+            symbol.version = version_id;
+        }
+
+        let shstrtab = builder.sections.add();
+        shstrtab.name = b".shstrtab"[..].into();
+        shstrtab.sh_type = SHT_STRTAB;
+        shstrtab.data = SectionData::SectionString;
+
+        let mut section_ids = vec![];
+        for (name, sh_type, sh_addralign, data) in [
+            (&b".dynsym"[..], SHT_DYNSYM, 8, SectionData::DynamicSymbol),
+            (&b".dynstr"[..], SHT_STRTAB, 1, SectionData::DynamicString),
+            (
+                &b".gnu.version"[..],
+                SHT_GNU_VERSYM,
+                2,
+                SectionData::GnuVersym,
+            ),
+            (
+                &b".gnu.version_r"[..],
+                SHT_GNU_VERNEED,
+                8,
+                SectionData::GnuVerneed,
+            ),
+        ] {
+            let section = builder.sections.add();
+            section.name = name.into();
+            section.sh_type = sh_type;
+            section.sh_flags = u64::from(SHF_ALLOC);
+            section.sh_addralign = sh_addralign;
+            section.data = data;
+            section_ids.push(section.id());
+        }
+
+        builder.set_section_sizes();
+
+        let segment = builder.segments.add();
+        segment.p_type = PT_LOAD;
+        segment.p_flags = PF_R;
+        segment.p_filesz = 0x1000;
+        segment.p_memsz = 0x1000;
+        segment.p_align = 8;
+        for section_id in section_ids {
+            segment.append_section(builder.sections.get_mut(section_id));
+        }
+
+        let mut contents = Vec::new();
+        builder.write(&mut contents).unwrap();
+        contents
+    }
+}
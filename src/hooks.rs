@@ -0,0 +1,117 @@
+use crate::DebianPackagesBuildpackError;
+use crate::build_report::InstalledPackage;
+use bullet_stream::{global::print, style};
+use fun_run::{CmdError, CommandWithName};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// The environment variables hooks can use to find the packages layer and a JSON manifest of the
+// packages being installed (the same format as the installed packages image label).
+pub(crate) const HOOK_LAYER_DIR_ENV: &str = "DEB_PACKAGES_LAYER_DIR";
+pub(crate) const HOOK_MANIFEST_ENV: &str = "DEB_PACKAGES_MANIFEST";
+
+const HOOK_MANIFEST_NAME: &str = "deb-packages-manifest.json";
+
+#[derive(Debug, Clone)]
+pub(crate) struct HookContext {
+    app_dir: PathBuf,
+    layer_dir: PathBuf,
+    manifest_path: PathBuf,
+}
+
+impl HookContext {
+    // the manifest is written alongside the downloaded packages so it isn't left in the layer
+    pub(crate) fn new(
+        app_dir: &Path,
+        layer_dir: &Path,
+        download_dir: &Path,
+        installed_packages: &[InstalledPackage],
+    ) -> Result<Self, HookError> {
+        let manifest_path = download_dir.join(HOOK_MANIFEST_NAME);
+        let manifest = serde_json::to_string_pretty(installed_packages)
+            .expect("Installed packages should serialize to JSON");
+        std::fs::write(&manifest_path, manifest)
+            .map_err(|e| HookError::WriteManifest(manifest_path.clone(), e))?;
+        Ok(Self {
+            app_dir: app_dir.to_path_buf(),
+            layer_dir: layer_dir.to_path_buf(),
+            manifest_path,
+        })
+    }
+
+    // Hooks run with `bash` from the application directory and their output is streamed to the
+    // build log. A hook that exits with a non-zero status fails the build.
+    pub(crate) fn run(&self, hook: &str, command: &str) -> Result<(), HookError> {
+        print::sub_bullet(format!("Running {hook} hook", hook = style::value(hook)));
+        print::sub_stream_cmd(
+            Command::new("bash")
+                .args(["-c", command])
+                .current_dir(&self.app_dir)
+                .env(HOOK_LAYER_DIR_ENV, &self.layer_dir)
+                .env(HOOK_MANIFEST_ENV, &self.manifest_path)
+                .named(command),
+        )
+        .map(|_| ())
+        .map_err(|e| HookError::RunHook(hook.to_string(), Box::new(e)))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum HookError {
+    WriteManifest(PathBuf, std::io::Error),
+    RunHook(String, Box<CmdError>),
+}
+
+impl From<HookError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: HookError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::Hook(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn run_hook_exports_layer_dir_and_manifest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let layer_dir = temp_dir.path().join("layer");
+        std::fs::create_dir_all(&layer_dir).unwrap();
+        let hook_context = HookContext::new(
+            temp_dir.path(),
+            &layer_dir,
+            temp_dir.path(),
+            &[InstalledPackage {
+                name: "curl".to_string(),
+                version: Some("8.5.0-2ubuntu10".to_string()),
+                source: "http://archive.ubuntu.com/ubuntu".to_string(),
+            }],
+        )
+        .unwrap();
+
+        hook_context
+            .run(
+                "post_install",
+                r#"cp "$DEB_PACKAGES_MANIFEST" "$DEB_PACKAGES_LAYER_DIR/manifest.json""#,
+            )
+            .unwrap();
+
+        let manifest: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(layer_dir.join("manifest.json")).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(manifest[0]["name"], "curl");
+    }
+
+    #[test]
+    fn run_hook_with_non_zero_exit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let hook_context =
+            HookContext::new(temp_dir.path(), temp_dir.path(), temp_dir.path(), &[]).unwrap();
+
+        match hook_context.run("pre_install", "exit 3").unwrap_err() {
+            HookError::RunHook(hook, _) => assert_eq!(hook, "pre_install"),
+            e @ HookError::WriteManifest(..) => panic!("Not the expected error - {e:?}"),
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use crate::DebianPackagesBuildpackError;
+use crate::netrc::Netrc;
+use async_trait::async_trait;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use http::Extensions;
+use libcnb::Env;
+use reqwest::header::{AUTHORIZATION, HeaderValue};
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+// The type of service binding (https://github.com/buildpacks/spec/blob/main/extensions/bindings.md)
+// that holds credentials for a repository host.
+pub(crate) const BINDING_TYPE: &str = "deb-packages";
+pub(crate) const NETRC_BINDING_TYPE: &str = "netrc";
+pub(crate) const CREDENTIALS_ENV: &str = "BP_DEB_PACKAGES_CREDENTIALS";
+pub(crate) const NETRC_ENV: &str = "NETRC";
+
+// bindings are read from `<platform>/bindings` when `SERVICE_BINDING_ROOT` isn't set
+const DEFAULT_BINDING_ROOT: &str = "/platform/bindings";
+
+// Repositories that require authentication can be given credentials through a service binding or
+// the platform environment so secrets don't have to be committed to `project.toml`. Credentials are
+// keyed by host and added to every request for that host (e.g.; release files, package indices,
+// packages, and download urls) that doesn't already have an `Authorization` header. A `.netrc`
+// file can also be given, its `default` entry is used for any other host.
+pub(crate) struct HostCredentialsMiddleware {
+    host_credentials: Vec<HostCredentials>,
+    default_auth: Option<HostAuth>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct HostCredentials {
+    pub(crate) host: String,
+    pub(crate) auth: HostAuth,
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub(crate) enum HostAuth {
+    Basic { username: String, password: String },
+    Bearer(String),
+}
+
+// credentials are never logged
+impl std::fmt::Debug for HostAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HostAuth::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .finish_non_exhaustive(),
+            HostAuth::Bearer(_) => f.debug_tuple("Bearer").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl HostCredentialsMiddleware {
+    // `platform_env` holds the variables from `<platform>/env`, the binding root is read from the
+    // process environment where platforms set it
+    pub(crate) fn new(platform_env: &Env) -> Result<Option<Self>, HostCredentialsError> {
+        let get_env = |name: &str| {
+            platform_env
+                .get_string_lossy(name)
+                .or_else(|| Env::from_current().get_string_lossy(name))
+        };
+
+        let mut host_credentials = vec![];
+        let mut netrc_files = vec![];
+        let binding_root = Env::from_current()
+            .get("SERVICE_BINDING_ROOT")
+            .map_or_else(|| PathBuf::from(DEFAULT_BINDING_ROOT), PathBuf::from);
+        for binding in read_bindings(&binding_root)? {
+            match binding {
+                Binding::HostCredentials(credentials) => host_credentials.push(credentials),
+                Binding::Netrc(netrc) => netrc_files.push(netrc),
+            }
+        }
+        if let Some(value) = get_env(CREDENTIALS_ENV) {
+            host_credentials.extend(parse_credentials_env(&value)?);
+        }
+        if let Some(path) = get_env(NETRC_ENV).filter(|path| !path.trim().is_empty()) {
+            netrc_files.push(read_netrc(Path::new(path.trim()))?);
+        }
+
+        // credentials given for a specific host are used before any from a `.netrc` file
+        let mut default_auth = None;
+        for netrc in netrc_files {
+            host_credentials.extend(netrc.machines);
+            default_auth = default_auth.or(netrc.default);
+        }
+
+        Ok(
+            (!host_credentials.is_empty() || default_auth.is_some()).then_some(Self {
+                host_credentials,
+                default_auth,
+            }),
+        )
+    }
+
+    fn find_auth(&self, req: &Request) -> Option<&HostAuth> {
+        let host = req.url().host_str()?;
+        let host_with_port = req.url().port().map(|port| format!("{host}:{port}"));
+        self.host_credentials
+            .iter()
+            .find(|host_credentials| {
+                host_credentials.host.eq_ignore_ascii_case(host)
+                    || host_with_port.as_ref().is_some_and(|host_with_port| {
+                        host_credentials.host.eq_ignore_ascii_case(host_with_port)
+                    })
+            })
+            .map(|host_credentials| &host_credentials.auth)
+            .or(self.default_auth.as_ref())
+    }
+}
+
+#[async_trait]
+impl Middleware for HostCredentialsMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        // headers configured for a download url, or credentials in the url, take precedence
+        if !req.headers().contains_key(AUTHORIZATION)
+            && let Some(auth) = self.find_auth(&req)
+        {
+            let mut header_value = HeaderValue::try_from(auth.header_value())
+                .map_err(reqwest_middleware::Error::middleware)?;
+            header_value.set_sensitive(true);
+            req.headers_mut().insert(AUTHORIZATION, header_value);
+        }
+        next.run(req, extensions).await
+    }
+}
+
+impl HostAuth {
+    fn header_value(&self) -> String {
+        match self {
+            HostAuth::Basic { username, password } => {
+                format!("Basic {}", BASE64.encode(format!("{username}:{password}")))
+            }
+            HostAuth::Bearer(token) => format!("Bearer {token}"),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum Binding {
+    HostCredentials(HostCredentials),
+    Netrc(Netrc),
+}
+
+// Each binding of the `deb-packages` type holds the `host` the credentials are for and either a
+// `username` and `password` for basic authentication or a `token` sent as a bearer token. Each
+// binding of the `netrc` type holds a `.netrc` file in its `.netrc` (or `netrc`) entry. Bindings of
+// any other type are ignored.
+fn read_bindings(binding_root: &Path) -> Result<Vec<Binding>, HostCredentialsError> {
+    let entries = match std::fs::read_dir(binding_root) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+        Err(e) => Err(HostCredentialsError::ReadBinding(
+            binding_root.to_path_buf(),
+            e,
+        ))?,
+    };
+
+    let mut binding_dirs = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| HostCredentialsError::ReadBinding(binding_root.to_path_buf(), e))?;
+    binding_dirs.sort();
+
+    let mut bindings = vec![];
+    for binding_dir in binding_dirs.into_iter().filter(|path| path.is_dir()) {
+        match read_binding_entry(&binding_dir, "type")?.as_deref() {
+            Some(BINDING_TYPE) => {
+                bindings.push(Binding::HostCredentials(read_binding(&binding_dir)?));
+            }
+            Some(NETRC_BINDING_TYPE) => {
+                let netrc_path = [".netrc", "netrc"]
+                    .into_iter()
+                    .map(|name| binding_dir.join(name))
+                    .find(|path| path.is_file())
+                    .ok_or_else(|| {
+                        HostCredentialsError::InvalidBinding(
+                            binding_dir.clone(),
+                            "the `.netrc` entry is missing".into(),
+                        )
+                    })?;
+                bindings.push(Binding::Netrc(read_netrc(&netrc_path)?));
+            }
+            _ => {}
+        }
+    }
+    Ok(bindings)
+}
+
+fn read_netrc(path: &Path) -> Result<Netrc, HostCredentialsError> {
+    std::fs::read_to_string(path)
+        .map(|contents| Netrc::parse(&contents))
+        .map_err(|e| HostCredentialsError::ReadNetrc(path.to_path_buf(), e))
+}
+
+fn read_binding(binding_dir: &Path) -> Result<HostCredentials, HostCredentialsError> {
+    let invalid_binding = |reason: &str| {
+        HostCredentialsError::InvalidBinding(binding_dir.to_path_buf(), reason.into())
+    };
+
+    let host = read_binding_entry(binding_dir, "host")?
+        .ok_or_else(|| invalid_binding("the `host` entry is missing"))?;
+
+    let auth = match (
+        read_binding_entry(binding_dir, "username")?,
+        read_binding_entry(binding_dir, "password")?,
+        read_binding_entry(binding_dir, "token")?,
+    ) {
+        (Some(username), Some(password), None) => HostAuth::Basic { username, password },
+        (None, None, Some(token)) => HostAuth::Bearer(token),
+        _ => Err(invalid_binding(
+            "it must have either `username` and `password` entries or a `token` entry",
+        ))?,
+    };
+
+    Ok(HostCredentials { host, auth })
+}
+
+fn read_binding_entry(
+    binding_dir: &Path,
+    name: &str,
+) -> Result<Option<String>, HostCredentialsError> {
+    let path = binding_dir.join(name);
+    match std::fs::read_to_string(&path) {
+        Ok(value) => Ok(Some(value.trim().to_string()).filter(|value| !value.is_empty())),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(HostCredentialsError::ReadBinding(path, e)),
+    }
+}
+
+// The environment variable holds whitespace-separated `<username>:<password>@<host>` entries, the
+// same form as credentials in a url, for basic authentication.
+fn parse_credentials_env(value: &str) -> Result<Vec<HostCredentials>, HostCredentialsError> {
+    value
+        .split_whitespace()
+        .map(|entry| {
+            entry
+                .rsplit_once('@')
+                .and_then(|(userinfo, host)| {
+                    let (username, password) = userinfo.split_once(':')?;
+                    (!username.is_empty() && !password.is_empty() && !host.is_empty()).then(|| {
+                        HostCredentials {
+                            host: host.to_string(),
+                            auth: HostAuth::Basic {
+                                username: username.to_string(),
+                                password: password.to_string(),
+                            },
+                        }
+                    })
+                })
+                .ok_or_else(|| {
+                    HostCredentialsError::InvalidCredentialsEnv(
+                        entry
+                            .rsplit_once('@')
+                            .map_or_else(|| "<redacted>".to_string(), |(_, host)| host.to_string()),
+                    )
+                })
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub(crate) enum HostCredentialsError {
+    ReadBinding(PathBuf, std::io::Error),
+    InvalidBinding(PathBuf, String),
+    ReadNetrc(PathBuf, std::io::Error),
+    // the host of the invalid entry, the credentials are left out since they're secret
+    InvalidCredentialsEnv(String),
+}
+
+impl From<HostCredentialsError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: HostCredentialsError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::HostCredentials(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_binding(binding_root: &Path, name: &str, entries: &[(&str, &str)]) {
+        let binding_dir = binding_root.join(name);
+        std::fs::create_dir_all(&binding_dir).unwrap();
+        for (entry, value) in entries {
+            std::fs::write(binding_dir.join(entry), value).unwrap();
+        }
+    }
+
+    #[test]
+    fn read_bindings_of_deb_packages_type() {
+        let binding_root = tempfile::tempdir().unwrap();
+        write_binding(
+            binding_root.path(),
+            "apt-example",
+            &[
+                ("type", "deb-packages\n"),
+                ("host", "apt.example.com"),
+                ("username", "user"),
+                ("password", "secret\n"),
+            ],
+        );
+        write_binding(
+            binding_root.path(),
+            "artifacts",
+            &[
+                ("type", "deb-packages"),
+                ("host", "artifacts.example.com:8443"),
+                ("token", "abc123"),
+            ],
+        );
+        write_binding(
+            binding_root.path(),
+            "database",
+            &[("type", "postgresql"), ("host", "db.example.com")],
+        );
+
+        assert_eq!(
+            read_bindings(binding_root.path()).unwrap(),
+            vec![
+                Binding::HostCredentials(HostCredentials {
+                    host: "apt.example.com".to_string(),
+                    auth: HostAuth::Basic {
+                        username: "user".to_string(),
+                        password: "secret".to_string()
+                    }
+                }),
+                Binding::HostCredentials(HostCredentials {
+                    host: "artifacts.example.com:8443".to_string(),
+                    auth: HostAuth::Bearer("abc123".to_string())
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_bindings_of_netrc_type() {
+        let binding_root = tempfile::tempdir().unwrap();
+        write_binding(
+            binding_root.path(),
+            "netrc",
+            &[
+                ("type", "netrc"),
+                (
+                    ".netrc",
+                    "machine apt.example.com login user password secret\ndefault login anonymous password guest",
+                ),
+            ],
+        );
+
+        assert_eq!(
+            read_bindings(binding_root.path()).unwrap(),
+            vec![Binding::Netrc(Netrc::parse(
+                "machine apt.example.com login user password secret\ndefault login anonymous password guest"
+            ))]
+        );
+
+        write_binding(binding_root.path(), "empty-netrc", &[("type", "netrc")]);
+        match read_bindings(binding_root.path()).unwrap_err() {
+            HostCredentialsError::InvalidBinding(path, reason) => {
+                assert_eq!(path, binding_root.path().join("empty-netrc"));
+                assert_eq!(reason, "the `.netrc` entry is missing");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn read_binding_without_credentials() {
+        let binding_root = tempfile::tempdir().unwrap();
+        write_binding(
+            binding_root.path(),
+            "apt-example",
+            &[
+                ("type", "deb-packages"),
+                ("host", "apt.example.com"),
+                ("username", "user"),
+            ],
+        );
+
+        match read_bindings(binding_root.path()).unwrap_err() {
+            HostCredentialsError::InvalidBinding(path, _) => {
+                assert_eq!(path, binding_root.path().join("apt-example"));
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn read_bindings_from_missing_binding_root() {
+        assert!(
+            read_bindings(Path::new("/does/not/exist"))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn parse_credentials_from_env() {
+        assert_eq!(
+            parse_credentials_env(
+                " user:p@ss@apt.example.com\n other:secret@ppa.example.com:8080 "
+            )
+            .unwrap(),
+            vec![
+                HostCredentials {
+                    host: "apt.example.com".to_string(),
+                    auth: HostAuth::Basic {
+                        username: "user".to_string(),
+                        password: "p@ss".to_string()
+                    }
+                },
+                HostCredentials {
+                    host: "ppa.example.com:8080".to_string(),
+                    auth: HostAuth::Basic {
+                        username: "other".to_string(),
+                        password: "secret".to_string()
+                    }
+                },
+            ]
+        );
+
+        match parse_credentials_env("token@apt.example.com").unwrap_err() {
+            HostCredentialsError::InvalidCredentialsEnv(host) => {
+                assert_eq!(host, "apt.example.com");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn host_credentials_are_added_to_matching_requests() {
+        let middleware = HostCredentialsMiddleware {
+            host_credentials: vec![
+                HostCredentials {
+                    host: "apt.example.com".to_string(),
+                    auth: HostAuth::Basic {
+                        username: "user".to_string(),
+                        password: "secret".to_string(),
+                    },
+                },
+                HostCredentials {
+                    host: "artifacts.example.com:8443".to_string(),
+                    auth: HostAuth::Bearer("abc123".to_string()),
+                },
+            ],
+            default_auth: None,
+        };
+        let request = |url: &str| Request::new(reqwest::Method::GET, url.parse().unwrap());
+
+        assert_eq!(
+            middleware
+                .find_auth(&request("https://APT.example.com/dists/noble/InRelease"))
+                .map(HostAuth::header_value),
+            Some("Basic dXNlcjpzZWNyZXQ=".to_string())
+        );
+        assert_eq!(
+            middleware
+                .find_auth(&request("https://artifacts.example.com:8443/package.deb"))
+                .map(HostAuth::header_value),
+            Some("Bearer abc123".to_string())
+        );
+        assert!(
+            middleware
+                .find_auth(&request("https://artifacts.example.com/package.deb"))
+                .is_none()
+        );
+        assert!(
+            middleware
+                .find_auth(&request("http://archive.ubuntu.com/ubuntu"))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn netrc_default_is_used_for_other_hosts() {
+        let netrc = Netrc::parse(
+            "machine apt.example.com login user password secret default login anonymous password guest",
+        );
+        let middleware = HostCredentialsMiddleware {
+            host_credentials: netrc.machines,
+            default_auth: netrc.default,
+        };
+        let request = |url: &str| Request::new(reqwest::Method::GET, url.parse().unwrap());
+
+        assert_eq!(
+            middleware
+                .find_auth(&request("https://apt.example.com/dists/noble/InRelease"))
+                .map(HostAuth::header_value),
+            Some("Basic dXNlcjpzZWNyZXQ=".to_string())
+        );
+        assert_eq!(
+            middleware
+                .find_auth(&request("https://ppa.example.com/dists/noble/InRelease"))
+                .map(HostAuth::header_value),
+            Some("Basic YW5vbnltb3VzOmd1ZXN0".to_string())
+        );
+    }
+}
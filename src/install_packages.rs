@@ -1,67 +1,175 @@
-use crate::config::download_url::DownloadUrl;
-use crate::debian::{Distro, MultiarchName, RepositoryPackage};
+use crate::build_report::InstalledPackage;
+use crate::config::RequestedPackage;
+use crate::config::download_url::{
+    DownloadHeader, DownloadHeaderValue, DownloadUrl, GithubReleaseAsset,
+};
+use crate::config::hooks_config::HooksConfig;
+use crate::config::{EnvOrder, PruneTarget};
+use crate::debian::{
+    ArchitectureName, Distro, MultiarchName, PackageHasher, RepositoryPackage, RepositoryUri,
+};
+use crate::download_cache::{
+    create_download_cache_layer, evict_least_recently_used, get_cached_download,
+    retain_cached_downloads,
+};
+use crate::fetcher::Fetcher;
+use crate::file_conflicts::FileConflicts;
+use crate::glibc_requirements::{GlibcVersion, NewerGlibcRequirements};
+use crate::hooks::HookContext;
+use crate::install_plan::InstallPlan;
+use crate::missing_shared_libraries::{MissingSharedLibraries, get_system_library_dirs};
+use crate::normalize_timestamps::{get_source_date_epoch, normalize_timestamps};
 use crate::o11y::*;
+use crate::package_manifest::{PackageControl, write_package_file_lists, write_package_manifest};
+use crate::package_signatures::{
+    PackageSignature, PackageSignatureError, verify_detached_package_signature,
+    verify_embedded_signature,
+};
+use crate::prune_files::prune_files;
+use crate::retry::error_for_status;
+use crate::stored_resolution::StoredResolution;
+use crate::strip_binaries::strip_binaries as strip_elf_binaries;
+use crate::vendor::{VENDOR_DIR, VendoredPackage};
 use crate::{
-    BuildpackResult, DebianPackagesBuildpack, DebianPackagesBuildpackError,
-    is_buildpack_debug_logging_enabled,
+    BuildpackResult, DebianPackagesBuildpack, DebianPackagesBuildpackError, format_size,
+    get_github_token, get_parallelism, is_buildpack_debug_logging_enabled,
+    is_clean_cache_requested, to_request_url,
 };
-use ar::Archive as ArArchive;
+use ar::{Archive as ArArchive, Entry as ArEntry};
 use async_compression::tokio::bufread::{GzipDecoder, XzDecoder, ZstdDecoder};
 use bullet_stream::{global::print, style};
-use futures::TryStreamExt;
 use futures::io::AllowStdIo;
+use futures::{StreamExt, TryStreamExt};
 use indexmap::IndexSet;
+use libcnb::Env;
 use libcnb::build::BuildContext;
+use libcnb::data::layer_content_metadata::LayerContentMetadata;
 use libcnb::data::layer_name;
 use libcnb::layer::{
     CachedLayerDefinition, EmptyLayerCause, InvalidMetadataAction, LayerState, RestoredLayerAction,
+    UncachedLayerDefinition,
 };
 use libcnb::layer_env::{LayerEnv, ModificationBehavior, Scope};
-use reqwest_middleware::ClientWithMiddleware;
+use reqwest::Url;
+use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap, HeaderName, HeaderValue};
 use reqwest_middleware::Error::Reqwest;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::collections::HashMap;
-use std::env::temp_dir;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::OsString;
+use std::fmt::{Display, Formatter};
 use std::fs::File;
-use std::io::Write;
+use std::io::{BufRead, BufReader, Write};
 use std::os::unix::ffi::OsStringExt;
-use std::path::{Path, PathBuf};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
-use tokio::fs::{File as AsyncFile, read_to_string as async_read_to_string, write as async_write};
-use tokio::io::{BufReader as AsyncBufReader, BufWriter as AsyncBufWriter, copy as async_copy};
-use tokio::task::{JoinError, JoinSet};
-use tokio_tar::Archive as TarArchive;
+use tokio::fs::{
+    File as AsyncFile, read_to_string as async_read_to_string, remove_file, write as async_write,
+};
+use tokio::io::{
+    AsyncRead, AsyncWriteExt, BufReader as AsyncBufReader, BufWriter as AsyncBufWriter,
+};
+use tokio::runtime::Handle;
+use tokio::sync::{Semaphore, oneshot};
+use tokio::task::{JoinError, JoinSet, spawn_blocking};
+use tokio_tar::{Archive as TarArchive, EntryType};
 use tokio_util::compat::FuturesAsyncReadCompatExt;
-use tokio_util::io::InspectReader;
 use tracing::{Instrument, info, instrument};
 use walkdir::{DirEntry, WalkDir};
 
+// How the packages are installed into the packages layer.
+pub(crate) struct InstallOptions<'a> {
+    pub(crate) cache_key: Option<String>,
+    pub(crate) download_dir: Option<PathBuf>,
+    pub(crate) strip_binaries: bool,
+    pub(crate) prune: Vec<PruneTarget>,
+    pub(crate) extract_options: BTreeMap<String, ExtractOptions>,
+    pub(crate) hooks: HooksConfig,
+    pub(crate) env_order: BTreeMap<String, EnvOrder>,
+    pub(crate) installed_packages: &'a [InstalledPackage],
+    pub(crate) max_layer_size: Option<u64>,
+    pub(crate) max_download_cache_size: Option<u64>,
+    pub(crate) package_signing_keys: &'a HashMap<RepositoryUri, String>,
+    pub(crate) install_plan: &'a InstallPlan,
+    pub(crate) resolution: Option<StoredResolution>,
+}
+
 #[instrument(skip_all)]
 #[allow(clippy::too_many_lines)]
 pub(crate) async fn install_packages(
     context: &Arc<BuildContext<DebianPackagesBuildpack>>,
-    client: &ClientWithMiddleware,
+    client: &impl Fetcher,
     distro: &Distro,
     packages_to_install: Vec<RepositoryPackage>,
     packages_to_download: IndexSet<DownloadUrl>,
-) -> BuildpackResult<()> {
+    vendored_packages: Vec<VendoredPackage>,
+    install_options: InstallOptions<'_>,
+) -> BuildpackResult<MissingSharedLibraries> {
+    let InstallOptions {
+        cache_key,
+        download_dir,
+        strip_binaries,
+        prune,
+        extract_options,
+        hooks,
+        env_order,
+        installed_packages,
+        max_layer_size,
+        max_download_cache_size,
+        package_signing_keys,
+        install_plan,
+        resolution,
+    } = install_options;
+
     print::header("Installing packages");
 
+    let vendor_dir = context.app_dir.join(VENDOR_DIR);
+
     let new_metadata = InstallationMetadata {
         package_checksums: packages_to_install
             .iter()
-            .map(|package| (package.name.clone(), package.sha256sum.clone()))
+            .map(|package| (package.name.clone(), package.checksum.value().to_string()))
+            .chain(
+                vendored_packages
+                    .iter()
+                    .map(|package| (package.name.clone(), package.sha256.clone())),
+            )
+            .collect(),
+        package_versions: packages_to_install
+            .iter()
+            .map(|package| (package.name.clone(), package.display_version()))
+            .chain(vendored_packages.iter().filter_map(|package| {
+                package
+                    .version
+                    .clone()
+                    .map(|version| (package.name.clone(), version))
+            }))
             .collect(),
         distro: distro.clone(),
         download_urls: packages_to_download
             .iter()
             .map(ToString::to_string)
             .collect(),
+        cache_key,
+        strip_binaries,
+        prune: prune.clone(),
+        extract_options: extract_options.clone(),
+        hooks: hooks.clone(),
+        resolution,
     };
 
+    let clean_cache = is_clean_cache_requested();
+
+    // the contents of an unverified download can change at the same url (e.g.; a nightly build) so
+    // the layer isn't reused while there are any
+    let unverified_downloads = packages_to_download
+        .iter()
+        .filter(|download_url| download_url.is_unverified())
+        .cloned()
+        .collect::<Vec<_>>();
+
     let install_layer = context.cached_layer(
         layer_name!("packages"),
         CachedLayerDefinition {
@@ -69,106 +177,604 @@ pub(crate) async fn install_packages(
             launch: true,
             invalid_metadata_action: &|_| InvalidMetadataAction::DeleteLayer,
             restored_layer_action: &|old_metadata: &InstallationMetadata, _| {
-                if old_metadata == &new_metadata {
-                    RestoredLayerAction::KeepLayer
+                let changes = old_metadata.changes(&new_metadata);
+                // packages that haven't changed since the previous build can be reused from the
+                // download cache instead of downloaded again
+                let previous_package_checksums = old_metadata.package_checksums.clone();
+                if changes.is_empty() && !clean_cache && unverified_downloads.is_empty() {
+                    (
+                        RestoredLayerAction::KeepLayer,
+                        (changes, previous_package_checksums),
+                    )
                 } else {
-                    RestoredLayerAction::DeleteLayer
+                    (
+                        RestoredLayerAction::DeleteLayer,
+                        (changes, previous_package_checksums),
+                    )
                 }
             },
         },
     )?;
 
-    match install_layer.state {
+    // only checked when the packages are installed, a restored layer was checked by the build
+    // that installed it
+    let mut missing_shared_libraries = MissingSharedLibraries::default();
+
+    match &install_layer.state {
         LayerState::Restored { .. } => {
             print::bullet("Restoring packages from cache");
             for package_to_install in &packages_to_install {
-                print::sub_bullet(style::value(format!(
-                    "{name}@{version}",
-                    name = package_to_install.name,
-                    version = package_to_install.version
-                )));
+                print::sub_bullet(style::value(package_to_install.name_with_version()));
             }
+            for vendored_package in &vendored_packages {
+                print::sub_bullet(style::value(vendored_package_name(vendored_package)));
+            }
+            // the resolution can change without changing the packages (e.g.; a new lockfile)
+            install_layer.write_metadata(new_metadata)?;
         }
         LayerState::Empty { cause } => {
+            let previous_package_checksums = match cause {
+                EmptyLayerCause::RestoredLayerAction {
+                    cause: (_, previous_package_checksums),
+                } => previous_package_checksums.clone(),
+                _ => HashMap::new(),
+            };
             print::bullet(match cause {
                 EmptyLayerCause::NewlyCreated => "Requesting packages",
                 EmptyLayerCause::InvalidMetadataAction { .. } => {
                     "Requesting packages (invalid metadata)"
                 }
-                EmptyLayerCause::RestoredLayerAction { .. } => {
-                    "Requesting packages (packages changed)"
+                EmptyLayerCause::RestoredLayerAction {
+                    cause: (changes, _),
+                } if changes.is_empty() && !clean_cache => {
+                    "Requesting packages (unverified downloads are requested on every build)"
+                }
+                EmptyLayerCause::RestoredLayerAction {
+                    cause: (changes, _),
+                } if changes.is_empty() => "Requesting packages (cache clean requested)",
+                EmptyLayerCause::RestoredLayerAction {
+                    cause: (changes, _),
+                } => {
+                    info!(
+                        { PACKAGES_LAYER_CHANGED_FIELDS } = as_json_value(
+                            &changes
+                                .iter()
+                                .map(InstallationChange::field)
+                                .collect::<BTreeSet<_>>()
+                        ),
+                        "packages layer invalidated"
+                    );
+                    print::bullet("Changes since the last build");
+                    for change in changes {
+                        print::sub_bullet(change.to_string());
+                    }
+                    "Requesting packages"
                 }
             });
             for package_to_install in &packages_to_install {
                 print::sub_bullet(format!(
                     "{name_with_version} from {url}",
-                    name_with_version = style::value(format!(
-                        "{name}@{version}",
-                        name = package_to_install.name,
-                        version = package_to_install.version
-                    )),
+                    name_with_version = style::value(package_to_install.name_with_version()),
                     url = style::url(build_download_url(package_to_install))
                 ));
             }
             for download_url in &packages_to_download {
+                print::sub_bullet(match download_url.filename_override() {
+                    Some(filename) => format!(
+                        "{filename} from {url}",
+                        filename = style::value(filename),
+                        url = style::url(download_url.to_string())
+                    ),
+                    None => format!(
+                        "Package from {url}",
+                        url = style::url(download_url.to_string())
+                    ),
+                });
+            }
+            for vendored_package in &vendored_packages {
                 print::sub_bullet(format!(
-                    "Package from {url}",
-                    url = style::url(download_url.to_string())
+                    "{name_with_version} from {file}",
+                    name_with_version = style::value(vendored_package_name(vendored_package)),
+                    file = style::value(format!("{VENDOR_DIR}/{}", vendored_package.file))
                 ));
             }
+            for package_to_install in &packages_to_install {
+                if package_to_install.checksum.is_weak() {
+                    print::warning(format!(
+                        "Package {name_with_version} will be verified with the weaker {checksum} checksum because its source doesn't list a SHA256 checksum.",
+                        name_with_version = style::value(package_to_install.name_with_version()),
+                        checksum = style::value(package_to_install.checksum.to_string()),
+                    ));
+                }
+            }
+
+            let install_plan_path = install_plan.write(&install_layer.path())?;
+            print::sub_bullet(format!(
+                "Install plan written to {file}",
+                file = style::value(install_plan_path.to_string_lossy())
+            ));
 
             let timer = print::sub_start_timer("Downloading");
             install_layer.write_metadata(new_metadata)?;
 
-            let mut download_and_extract_handles = JoinSet::new();
+            // packages are downloaded to a layer that's discarded after the build by default
+            // since the system temp directory can be too small on some hosts (e.g.; tmpfs)
+            let download_dir = match download_dir {
+                Some(download_dir) => download_dir,
+                None => context
+                    .uncached_layer(
+                        layer_name!("downloads"),
+                        UncachedLayerDefinition {
+                            build: false,
+                            launch: false,
+                        },
+                    )?
+                    .path(),
+            };
+            std::fs::create_dir_all(&download_dir)
+                .map_err(|e| InstallPackagesError::CreateDownloadDir(download_dir.clone(), e))?;
+
+            let hook_context = if hooks.is_empty() {
+                None
+            } else {
+                Some(HookContext::new(
+                    &context.app_dir,
+                    &install_layer.path(),
+                    &download_dir,
+                    installed_packages,
+                )?)
+            };
+
+            if let (Some(hook_context), Some(command)) = (&hook_context, &hooks.pre_install) {
+                run_hook(hook_context, "pre_install", command).await?;
+            }
+
+            // without a maximum size, the download cache only keeps the packages installed by
+            // the most recent build so a change to one package doesn't download every other one
+            let download_cache_dir =
+                if max_download_cache_size.is_some() || !packages_to_install.is_empty() {
+                    Some(create_download_cache_layer(context)?)
+                } else {
+                    None
+                };
+            let cached_download_file_names = packages_to_install
+                .iter()
+                .filter_map(RepositoryPackage::pool_file_name)
+                .collect::<HashSet<_>>();
+
+            let extract_permits = Arc::new(Semaphore::new(get_parallelism()));
+            let mut download_handles = JoinSet::new();
 
             for repository_package in packages_to_install {
-                download_and_extract_handles.spawn(
-                    download_and_extract(
-                        client.clone(),
-                        DownloadTask::Package(repository_package),
-                        install_layer.path(),
+                let package_extract_options = extract_options
+                    .get(&repository_package.name)
+                    .cloned()
+                    .unwrap_or_default();
+                let package_signature = package_signing_keys
+                    .get(&repository_package.repository_uri)
+                    .map(|signed_by| PackageSignature::Embedded {
+                        signed_by: signed_by.clone(),
+                    });
+                // only the packages from the previous build are in the cache when it has no maximum
+                // size so the other packages don't need to be looked up
+                let unchanged_package = previous_package_checksums
+                    .get(&repository_package.name)
+                    .is_some_and(|checksum| checksum == repository_package.checksum.value());
+                let cached_download_lookup = max_download_cache_size.is_some() || unchanged_package;
+                download_handles.spawn(
+                    in_install_order(
+                        download_handles.len(),
+                        download_package(
+                            client.clone(),
+                            DownloadTask::Package(Box::new(repository_package)),
+                            download_dir.clone(),
+                            package_extract_options,
+                            download_cache_dir.clone(),
+                            cached_download_lookup,
+                            package_signature,
+                        ),
                     )
                     .in_current_span(),
                 );
             }
 
             for download_url in packages_to_download {
-                download_and_extract_handles.spawn(
-                    download_and_extract(
-                        client.clone(),
-                        DownloadTask::Url(download_url),
+                let package_signature = download_url.signature();
+                download_handles.spawn(
+                    in_install_order(
+                        download_handles.len(),
+                        download_package(
+                            client.clone(),
+                            DownloadTask::Url(Box::new(download_url)),
+                            download_dir.clone(),
+                            ExtractOptions::default(),
+                            // the contents of a url can change so these are always downloaded
+                            None,
+                            false,
+                            package_signature,
+                        ),
+                    )
+                    .in_current_span(),
+                );
+            }
+
+            for vendored_package in vendored_packages {
+                let vendored_package_path = vendor_dir.join(&vendored_package.file);
+                let package_extract_options = extract_options
+                    .get(&vendored_package.name)
+                    .cloned()
+                    .unwrap_or_default();
+                download_handles.spawn(
+                    in_install_order(
+                        download_handles.len(),
+                        verify_vendored_download(
+                            vendored_package,
+                            vendored_package_path,
+                            package_extract_options,
+                            extract_permits.clone(),
+                        ),
+                    )
+                    .in_current_span(),
+                );
+            }
+
+            let downloaded_packages = join_in_install_order(download_handles).await?;
+            let reused_downloads = downloaded_packages
+                .iter()
+                .filter(|downloaded_package| downloaded_package.reused_download)
+                .count();
+
+            // every package is listed before any are extracted so files included in more than
+            // one package are always installed from the same package
+            let mut list_files_handles = JoinSet::new();
+            for (index, downloaded_package) in downloaded_packages.iter().enumerate() {
+                list_files_handles.spawn(
+                    in_install_order(
+                        index,
+                        list_package_files(
+                            downloaded_package.path.clone(),
+                            downloaded_package.extract_options.clone(),
+                            extract_permits.clone(),
+                        ),
+                    )
+                    .in_current_span(),
+                );
+            }
+            let package_files = downloaded_packages
+                .iter()
+                .map(|downloaded_package| downloaded_package.name.clone())
+                .zip(join_in_install_order(list_files_handles).await?)
+                .collect::<Vec<_>>();
+            let file_conflicts = FileConflicts::find(&package_files);
+
+            let mut extract_handles = JoinSet::new();
+            for (index, downloaded_package) in downloaded_packages.into_iter().enumerate() {
+                extract_handles.spawn(
+                    extract_package(
+                        downloaded_package,
                         install_layer.path(),
+                        file_conflicts.replaced_files(index),
+                        extract_permits.clone(),
                     )
                     .in_current_span(),
                 );
             }
 
-            while let Some(download_and_extract_handle) =
-                download_and_extract_handles.join_next().await
-            {
-                download_and_extract_handle.map_err(InstallPackagesError::TaskFailed)??;
+            let mut extracted_packages = vec![];
+            while let Some(extract_handle) = extract_handles.join_next().await {
+                extracted_packages.push(extract_handle.map_err(InstallPackagesError::TaskFailed)??);
             }
+            // packages finish extracting in any order
+            extracted_packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+            write_package_file_lists(
+                &install_layer.path(),
+                &package_files
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (name, files))| {
+                        let replaced_files = file_conflicts.replaced_files(index);
+                        (
+                            name.as_str(),
+                            files
+                                .iter()
+                                .filter(|file| !replaced_files.contains(*file))
+                                .map(PathBuf::as_path)
+                                .collect(),
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
+
+            write_package_manifest(
+                &install_layer.path(),
+                &extracted_packages
+                    .iter()
+                    .map(|extracted_package| {
+                        (extracted_package.name.as_str(), &extracted_package.control)
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .await?;
 
             timer.done();
+
+            if reused_downloads > 0 {
+                print::sub_bullet(format!(
+                    "Reused {reused_downloads} unchanged packages from the download cache"
+                ));
+            }
+
+            for extracted_package in &extracted_packages {
+                if let Some((download_url, sha256)) = &extracted_package.unpinned_download
+                    && !download_url.is_unverified()
+                {
+                    print::sub_bullet(format!(
+                        "Downloaded {url} without a checksum, add {sha256} to pin it",
+                        url = style::url(download_url.to_string()),
+                        sha256 = style::value(format!("sha256 = \"{sha256}\""))
+                    ));
+                }
+            }
+
+            if !unverified_downloads.is_empty() {
+                info!(
+                    { INSTALL_PACKAGES_UNVERIFIED_DOWNLOADS } = unverified_downloads.len(),
+                    "install packages"
+                );
+                print::warning(format!(
+                    "The following packages were installed without verifying their checksum because their download url is configured with {verify}. Their contents can change between builds without any change to the configuration. Remove {verify} and add a {sha256} checksum once the package no longer changes.\n\n{unverified_downloads}",
+                    verify = style::value("verify = false"),
+                    sha256 = style::value("sha256"),
+                    unverified_downloads = unverified_downloads
+                        .iter()
+                        .map(|download_url| format!("- {}", style::url(download_url.to_string())))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ));
+            }
+
+            let packages_with_setid_files = extracted_packages
+                .iter()
+                .filter(|extracted_package| !extracted_package.setid_files.is_empty())
+                .map(|extracted_package| {
+                    format!(
+                        "- {name}: {files}",
+                        name = style::value(&extracted_package.name),
+                        files = extracted_package
+                            .setid_files
+                            .iter()
+                            .map(|file| style::value(format!("/{}", file.to_string_lossy())))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })
+                .collect::<Vec<_>>();
+            if !packages_with_setid_files.is_empty() {
+                print::warning(format!(
+                    "The following packages contain files with the setuid or setgid permission bits set. These bits were removed when the files were extracted since they have no effect in the packages layer.\n\n{packages_with_setid_files}",
+                    packages_with_setid_files = packages_with_setid_files.join("\n")
+                ));
+            }
+
+            if !file_conflicts.is_empty() {
+                info!(
+                    { INSTALL_PACKAGES_FILE_CONFLICTS } = file_conflicts.file_count(),
+                    "install packages"
+                );
+                print::warning(format!(
+                    "The following packages contain the same files. Only the files from the last package listed for each are installed.\n\n{file_conflicts}",
+                    file_conflicts = file_conflicts
+                        .conflicts()
+                        .iter()
+                        .map(|file_conflict| format!("- {file_conflict}"))
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                ));
+            }
+
+            if let (Some(hook_context), Some(command)) = (&hook_context, &hooks.post_install) {
+                run_hook(hook_context, "post_install", command).await?;
+            }
+
+            match (download_cache_dir, max_download_cache_size) {
+                (Some(download_cache_dir), Some(max_download_cache_size)) => {
+                    let download_cache_usage = spawn_blocking(move || {
+                        evict_least_recently_used(&download_cache_dir, max_download_cache_size)
+                    })
+                    .await
+                    .map_err(InstallPackagesError::TaskFailed)?;
+                    print::sub_bullet(download_cache_usage.to_string());
+                }
+                (Some(download_cache_dir), None) => {
+                    spawn_blocking(move || {
+                        retain_cached_downloads(&download_cache_dir, &cached_download_file_names)
+                    })
+                    .await
+                    .map_err(InstallPackagesError::TaskFailed)?;
+                }
+                (None, _) => {}
+            }
+
+            if !prune.is_empty() {
+                let timer = print::sub_start_timer("Pruning files");
+                let install_path = install_layer.path();
+                let pruned_files = spawn_blocking(move || prune_files(&install_path, &prune))
+                    .await
+                    .map_err(InstallPackagesError::TaskFailed)??;
+                timer.done();
+                print::sub_bullet(pruned_files.to_string());
+            }
+
+            if strip_binaries {
+                let timer = print::sub_start_timer("Stripping binaries");
+                let install_path = install_layer.path();
+                let stripped_binaries = spawn_blocking(move || strip_elf_binaries(&install_path))
+                    .await
+                    .map_err(InstallPackagesError::TaskFailed)??;
+                timer.done();
+                print::sub_bullet(stripped_binaries.to_string());
+            }
+
+            missing_shared_libraries = {
+                let install_path = install_layer.path();
+                let system_library_dirs = get_system_library_dirs(
+                    &std::iter::once(distro.architecture.clone())
+                        .chain(distro.architecture.get_foreign_architectures())
+                        .map(|architecture| MultiarchName::from(&architecture))
+                        .collect::<Vec<_>>(),
+                );
+                spawn_blocking(move || {
+                    MissingSharedLibraries::find(&install_path, &system_library_dirs)
+                })
+                .await
+                .map_err(InstallPackagesError::TaskFailed)??
+            };
+            if !missing_shared_libraries.is_empty() {
+                info!(
+                    { INSTALL_PACKAGES_MISSING_SHARED_LIBRARIES } =
+                        missing_shared_libraries.library_count(),
+                    "install packages"
+                );
+                print::warning(format!(
+                    "The following shared libraries are needed by binaries in the installed packages but can't be found in the packages layer or the library directories of the base image. These binaries will fail to start unless a package providing the libraries is added to {install_key} or {auto_resolve_libs_key} is enabled.\n\n{missing_shared_libraries}",
+                    install_key = style::value("install"),
+                    auto_resolve_libs_key = style::value("auto_resolve_libs")
+                ));
+            }
+
+            let newer_glibc_requirements = {
+                let install_path = install_layer.path();
+                let glibc_version = GlibcVersion::from_str(distro.codename.glibc_version())
+                    .expect("glibc version of a supported distro should be valid");
+                // the files of a package are attributed to the package that installed them last
+                let file_packages = package_files
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(index, (name, files))| {
+                        let replaced_files = file_conflicts.replaced_files(index);
+                        files
+                            .iter()
+                            .filter(move |file| !replaced_files.contains(*file))
+                            .map(move |file| (file.clone(), name.clone()))
+                    })
+                    .collect::<HashMap<_, _>>();
+                spawn_blocking(move || {
+                    NewerGlibcRequirements::find(&install_path, &glibc_version, &file_packages)
+                })
+                .await
+                .map_err(InstallPackagesError::TaskFailed)??
+            };
+            if !newer_glibc_requirements.is_empty() {
+                info!(
+                    { INSTALL_PACKAGES_NEWER_GLIBC_PACKAGES } =
+                        newer_glibc_requirements.package_count(),
+                    "install packages"
+                );
+                print::warning(format!(
+                    "The following packages contain binaries that need a newer version of glibc than {glibc_version}, the version shipped with {distro} {version}, so they will fail to start with an error like {loader_error}. These packages were likely built for a newer release, check whether their repository publishes packages for {codename}.\n\n{newer_glibc_requirements}",
+                    glibc_version = style::value(distro.codename.glibc_version()),
+                    distro = distro.name,
+                    version = distro.version,
+                    loader_error = style::value("version `GLIBC_x.y' not found"),
+                    codename = style::value(distro.codename.to_string()),
+                ));
+            }
         }
     }
 
-    let layer_env = configure_layer_environment(
-        &install_layer.path(),
-        &MultiarchName::from(&distro.architecture),
-    );
+    let prefixes = extract_options
+        .values()
+        .filter_map(ExtractOptions::install_prefix)
+        .collect::<BTreeSet<_>>();
+    let launch_only_prefixes = extract_options
+        .values()
+        .filter(|package_extract_options| package_extract_options.launch_only)
+        .filter_map(ExtractOptions::install_prefix)
+        .collect::<BTreeSet<_>>();
+
+    // scanning the installed files is blocking work that shouldn't hold up the async runtime
+    let layer_env = {
+        let install_path = install_layer.path();
+        let prefixes = prefixes
+            .difference(&launch_only_prefixes)
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        let multiarch_name = MultiarchName::from(&distro.architecture);
+        let foreign_multiarch_names = distro
+            .architecture
+            .get_foreign_architectures()
+            .iter()
+            .map(MultiarchName::from)
+            .collect::<Vec<_>>();
+        spawn_blocking(move || {
+            configure_layer_environment(
+                &install_path,
+                &multiarch_name,
+                &foreign_multiarch_names,
+                &prefixes,
+                &launch_only_prefixes,
+                &env_order,
+            )
+        })
+        .await
+        .map_err(InstallPackagesError::TaskFailed)?
+    };
 
     install_layer.write_env(layer_env)?;
 
-    rewrite_package_configs(&install_layer.path()).await?;
+    rewrite_package_configs(&install_layer.path(), &prefixes).await?;
+
+    // this runs last since everything before it can modify the files in the layer
+    {
+        let install_path = install_layer.path();
+        let source_date_epoch = get_source_date_epoch();
+        spawn_blocking(move || normalize_timestamps(&install_path, source_date_epoch))
+            .await
+            .map_err(InstallPackagesError::TaskFailed)??;
+    }
 
     print::bullet("Installation complete");
+
+    let layer_size = {
+        let install_path = install_layer.path();
+        spawn_blocking(move || get_layer_size(&install_path))
+            .await
+            .map_err(InstallPackagesError::TaskFailed)?
+    };
+    info!({ PACKAGES_LAYER_SIZE } = layer_size, "packages layer");
+    print::sub_bullet(format!("Layer size: {}", format_size(layer_size)));
+    if let Some(max_layer_size) = max_layer_size
+        && layer_size > max_layer_size
+    {
+        print::warning(format!(
+            "The installed packages are {layer_size} which is larger than the configured {max_layer_size_key} of {max_layer_size}. Consider removing packages that aren't needed or using the {prune_key} and {strip_binaries_key} options to reduce the size of the layer.",
+            layer_size = format_size(layer_size),
+            max_layer_size_key = style::value("max_layer_size"),
+            max_layer_size = format_size(max_layer_size),
+            prune_key = style::value("prune"),
+            strip_binaries_key = style::value("strip_binaries"),
+        ));
+    }
+
     if is_buildpack_debug_logging_enabled() {
         print_layer_contents(&install_layer.path());
     }
 
-    Ok(())
+    Ok(missing_shared_libraries)
+}
+
+// files with multiple hard links only take up space once
+fn get_layer_size(install_path: &Path) -> u64 {
+    let mut inodes = HashSet::new();
+    WalkDir::new(install_path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| !metadata.is_dir() && inodes.insert((metadata.dev(), metadata.ino())))
+        .map(|metadata| metadata.len())
+        .sum()
 }
 
 fn print_layer_contents(install_path: &Path) {
@@ -193,21 +799,301 @@ fn print_layer_contents(install_path: &Path) {
     });
 }
 
+// A package downloaded from a url without a configured checksum along with its calculated SHA256.
+type UnpinnedDownload = (DownloadUrl, String);
+
+// A package archive that's ready to be extracted into the install directory.
+#[derive(Debug)]
+struct DownloadedPackage {
+    name: String,
+    path: PathBuf,
+    extract_options: ExtractOptions,
+    unpinned_download: Option<UnpinnedDownload>,
+    // the archive is removed once it's extracted unless it's being cached or is vendored
+    remove_after_extract: bool,
+    // the archive was already in the download cache
+    reused_download: bool,
+}
+
+// What's reported about a package once every package has been extracted.
+#[derive(Debug)]
+struct ExtractedPackage {
+    name: String,
+    unpinned_download: Option<UnpinnedDownload>,
+    // files (relative to the install directory) that had their setuid or setgid bits removed
+    setid_files: Vec<PathBuf>,
+    control: PackageControl,
+}
+
+// tasks finish in any order so each result is tagged with the position of its package in the
+// install order
+async fn in_install_order<T>(
+    install_order: usize,
+    task: impl Future<Output = BuildpackResult<T>>,
+) -> BuildpackResult<(usize, T)> {
+    task.await.map(|value| (install_order, value))
+}
+
+async fn join_in_install_order<T: 'static>(
+    mut handles: JoinSet<BuildpackResult<(usize, T)>>,
+) -> BuildpackResult<Vec<T>> {
+    let mut results = vec![];
+    while let Some(handle) = handles.join_next().await {
+        results.push(handle.map_err(InstallPackagesError::TaskFailed)??);
+    }
+    results.sort_by_key(|(install_order, _)| *install_order);
+    Ok(results.into_iter().map(|(_, value)| value).collect())
+}
+
 #[instrument(skip_all)]
-async fn download_and_extract(
-    client: ClientWithMiddleware,
+async fn download_package(
+    client: impl Fetcher,
     download_task: DownloadTask,
+    download_dir: PathBuf,
+    extract_options: ExtractOptions,
+    download_cache_dir: Option<PathBuf>,
+    cached_download_lookup: bool,
+    package_signature: Option<PackageSignature>,
+) -> BuildpackResult<DownloadedPackage> {
+    let name = match &download_task {
+        DownloadTask::Package(repository_package) => repository_package.name_with_version(),
+        DownloadTask::Url(download_url) => download_url.display_name(),
+    };
+    let unpinned_download_url = match &download_task {
+        DownloadTask::Url(download_url) if download_url.sha256().is_none() => {
+            Some((**download_url).clone())
+        }
+        _ => None,
+    };
+    // only packages from a repository are kept in the download cache since the contents of a url
+    // can change
+    let download_cache_dir =
+        download_cache_dir.filter(|_| matches!(download_task, DownloadTask::Package(_)));
+    let keep_download = download_cache_dir.is_some();
+    let cached_download_path = match (&download_task, &download_cache_dir) {
+        (DownloadTask::Package(repository_package), Some(download_cache_dir))
+            if cached_download_lookup =>
+        {
+            get_cached_download(download_cache_dir, repository_package).await
+        }
+        _ => None,
+    };
+    let reused_download = cached_download_path.is_some();
+    // a detached signature is small so it's requested before the package to fail fast when it's
+    // missing
+    let detached_signature = match (&download_task, &package_signature) {
+        (
+            DownloadTask::Url(download_url),
+            Some(PackageSignature::Detached { signature_url, .. }),
+        ) => Some(download_signature(&client, download_url, signature_url).await?),
+        _ => None,
+    };
+    let (download_path, calculated_hash) = if let Some(cached_download_path) = cached_download_path
+    {
+        (cached_download_path, None)
+    } else {
+        let (download_path, calculated_hash) = download(
+            client,
+            download_task,
+            download_cache_dir.unwrap_or(download_dir),
+        )
+        .await?;
+        (download_path, Some(calculated_hash))
+    };
+    if let Some(package_signature) = package_signature {
+        verify_package_signature(
+            name.clone(),
+            download_path.clone(),
+            package_signature,
+            detached_signature,
+        )
+        .await?;
+    }
+    Ok(DownloadedPackage {
+        name,
+        path: download_path,
+        extract_options,
+        unpinned_download: unpinned_download_url.zip(calculated_hash),
+        remove_after_extract: !keep_download,
+        reused_download,
+    })
+}
+
+async fn download_signature(
+    client: &impl Fetcher,
+    download_url: &DownloadUrl,
+    signature_url: &str,
+) -> BuildpackResult<Vec<u8>> {
+    let mut headers = HeaderMap::new();
+    // headers configured for the download url are often credentials so they're only sent when the
+    // signature is published on the same host
+    if Url::parse(signature_url)
+        .is_ok_and(|signature_url| signature_url.host_str() == download_url.host_str())
+    {
+        append_download_headers(&mut headers, download_url)?;
+    }
+    let signature = async {
+        client
+            .fetch(signature_url, headers)
+            .await
+            .and_then(|res| res.error_for_status().map_err(Reqwest))?
+            .bytes()
+            .await
+            .map_err(Reqwest)
+    }
+    .await
+    .map_err(|e| InstallPackagesError::RequestPackageSignature(signature_url.to_string(), e))?;
+    Ok(signature.to_vec())
+}
+
+// NOTE: Signatures are checked after the package is downloaded (or restored from the download
+//       cache) and before anything is extracted from it.
+#[instrument(skip_all)]
+async fn verify_package_signature(
+    name: String,
+    download_path: PathBuf,
+    package_signature: PackageSignature,
+    detached_signature: Option<Vec<u8>>,
+) -> BuildpackResult<()> {
+    info!({ DOWNLOAD_PACKAGE_NAME } = %name, "verifying package signature");
+    spawn_blocking(move || match (&package_signature, detached_signature) {
+        (PackageSignature::Embedded { signed_by }, _) => {
+            verify_embedded_signature(&download_path, signed_by)
+        }
+        (PackageSignature::Detached { signed_by, .. }, Some(signature)) => {
+            verify_detached_package_signature(&download_path, &signature, signed_by)
+        }
+        (PackageSignature::Detached { .. }, None) => Err(PackageSignatureError::MissingSignature),
+    })
+    .await
+    .map_err(InstallPackagesError::TaskFailed)?
+    .map_err(|e| InstallPackagesError::VerifyPackageSignature(name, e))?;
+    Ok(())
+}
+
+#[instrument(skip_all)]
+async fn verify_vendored_download(
+    vendored_package: VendoredPackage,
+    vendored_package_path: PathBuf,
+    extract_options: ExtractOptions,
+    extract_permits: Arc<Semaphore>,
+) -> BuildpackResult<DownloadedPackage> {
+    let _permit = extract_permits
+        .acquire()
+        .await
+        .expect("Extraction semaphore should never be closed");
+    verify_vendored_package(&vendored_package, &vendored_package_path).await?;
+    Ok(DownloadedPackage {
+        name: vendored_package_name(&vendored_package),
+        path: vendored_package_path,
+        extract_options,
+        unpinned_download: None,
+        remove_after_extract: false,
+        reused_download: false,
+    })
+}
+
+#[instrument(skip_all)]
+async fn extract_package(
+    downloaded_package: DownloadedPackage,
     install_dir: PathBuf,
+    replaced_files: HashSet<PathBuf>,
+    extract_permits: Arc<Semaphore>,
+) -> BuildpackResult<ExtractedPackage> {
+    // downloads run concurrently but extraction is CPU-heavy so it's limited to the configured parallelism
+    let _permit = extract_permits
+        .acquire()
+        .await
+        .expect("Extraction semaphore should never be closed");
+    let (setid_files, control) = extract(
+        downloaded_package.path.clone(),
+        install_dir,
+        downloaded_package.extract_options,
+        replaced_files,
+    )
+    .await?;
+    if downloaded_package.remove_after_extract {
+        let _ = remove_file(&downloaded_package.path).await;
+    }
+    Ok(ExtractedPackage {
+        name: downloaded_package.name,
+        unpinned_download: downloaded_package.unpinned_download,
+        setid_files,
+        control,
+    })
+}
+
+fn vendored_package_name(vendored_package: &VendoredPackage) -> String {
+    match &vendored_package.version {
+        Some(version) => format!("{name}@{version}", name = vendored_package.name),
+        None => vendored_package.name.clone(),
+    }
+}
+
+#[instrument(skip_all)]
+async fn verify_vendored_package(
+    vendored_package: &VendoredPackage,
+    vendored_package_path: &Path,
 ) -> BuildpackResult<()> {
-    let download_path = download(client, download_task).await?;
-    extract(download_path, install_dir).await
+    info!(
+        { DOWNLOAD_PACKAGE_NAME } = %vendored_package.name,
+        "using vendored package"
+    );
+
+    let calculated_hash = calculate_sha256(vendored_package_path).await.map_err(|e| {
+        InstallPackagesError::ReadVendoredPackage(vendored_package_path.to_path_buf(), e)
+    })?;
+
+    if calculated_hash != vendored_package.sha256 {
+        Err(InstallPackagesError::VendoredPackageChecksumFailed {
+            path: vendored_package_path.to_path_buf(),
+            expected: vendored_package.sha256.clone(),
+            actual: calculated_hash,
+        })?;
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn calculate_sha256(path: &Path) -> Result<String, std::io::Error> {
+    calculate_checksum(path, PackageHasher::Sha256(Sha256::new())).await
+}
+
+// NOTE: Hashing is CPU-heavy so it runs on the rayon pool, which is sized to the configured
+//       parallelism, instead of the async runtime.
+pub(crate) async fn calculate_checksum(
+    path: &Path,
+    mut hasher: PackageHasher,
+) -> Result<String, std::io::Error> {
+    let path = path.to_path_buf();
+    let (send, recv) = oneshot::channel();
+    rayon::spawn(move || {
+        let result = File::open(path).and_then(|file| {
+            let mut reader = BufReader::new(file);
+            loop {
+                let buffer = reader.fill_buf()?;
+                if buffer.is_empty() {
+                    break;
+                }
+                hasher.update(buffer);
+                let length = buffer.len();
+                reader.consume(length);
+            }
+            Ok(hasher.finalize())
+        });
+        let _ = send.send(result);
+    });
+    recv.await.map_err(std::io::Error::other)?
 }
 
+// Returns the path of the downloaded package along with its calculated checksum.
 #[instrument(skip_all)]
-async fn download(
-    client: ClientWithMiddleware,
+#[allow(clippy::too_many_lines)]
+pub(crate) async fn download(
+    client: impl Fetcher,
     download_task: DownloadTask,
-) -> BuildpackResult<PathBuf> {
+    download_dir: PathBuf,
+) -> BuildpackResult<(PathBuf, String)> {
     match &download_task {
         DownloadTask::Package(repository_package) => {
             info!(
@@ -224,21 +1110,24 @@ async fn download(
         }
     }
 
-    let download_url = match &download_task {
-        DownloadTask::Package(repository_package) => build_download_url(repository_package),
-        DownloadTask::Url(download_url) => download_url.to_string(),
+    let (download_url, headers) = match &download_task {
+        DownloadTask::Package(repository_package) => (
+            to_request_url(&build_download_url(repository_package)),
+            HeaderMap::new(),
+        ),
+        DownloadTask::Url(download_url) => {
+            download_url_request(&client, download_url, get_github_token()).await?
+        }
     };
 
     let download_file_name = match &download_task {
-        DownloadTask::Package(repository_package) => {
-            PathBuf::from(repository_package.filename.as_str())
-                .file_name()
-                .map(ToOwned::to_owned)
-                .ok_or(InstallPackagesError::InvalidFilename(
-                    repository_package.name.clone(),
-                    repository_package.filename.clone(),
-                ))?
-        }
+        DownloadTask::Package(repository_package) => repository_package
+            .pool_file_name()
+            .map(OsString::from)
+            .ok_or(InstallPackagesError::InvalidFilename(
+                repository_package.name.clone(),
+                repository_package.filename.clone(),
+            ))?,
         DownloadTask::Url(download_url) => match download_url.filename().map(OsString::from_str) {
             Some(Ok(filename)) => filename,
             _ => Err(InstallPackagesError::InvalidFilename(
@@ -248,192 +1137,637 @@ async fn download(
         },
     };
 
-    let download_path = temp_dir().join::<&Path>(download_file_name.as_ref());
+    let download_path = download_dir.join::<&Path>(download_file_name.as_ref());
 
     let response = client
-        .get(&download_url)
-        .send()
+        .fetch(&download_url, headers)
         .await
-        .and_then(|res| res.error_for_status().map_err(Reqwest))
+        .and_then(error_for_status)
         .map_err(|e| match &download_task {
             DownloadTask::Package(repository_package) => {
-                InstallPackagesError::RequestPackage(repository_package.clone(), e)
+                InstallPackagesError::RequestPackage((**repository_package).clone(), e)
             }
             DownloadTask::Url(download_url) => {
-                InstallPackagesError::RequestPackageUrl(download_url.clone(), e)
+                InstallPackagesError::RequestPackageUrl((**download_url).clone(), e)
             }
         })?;
 
-    let mut hasher = Sha256::new();
+    let hasher = match &download_task {
+        DownloadTask::Package(repository_package) => repository_package.checksum.hasher(),
+        DownloadTask::Url(_) => PackageHasher::Sha256(Sha256::new()),
+    };
 
     let on_write_error_handler = |e| match &download_task {
         DownloadTask::Package(repository_package) => InstallPackagesError::WritePackage(
-            repository_package.clone(),
+            (**repository_package).clone(),
             download_url.clone(),
             download_path.clone(),
             e,
         ),
-        DownloadTask::Url(download_url) => {
-            InstallPackagesError::WritePackageUrl(download_url.clone(), download_path.clone(), e)
-        }
-    };
-
-    let mut writer = AsyncFile::create(&download_path)
-        .await
-        .map_err(on_write_error_handler)
-        .map(AsyncBufWriter::new)?;
-
-    // the inspect reader lets us pipe the response to both the output file and the hash digest
-    let mut reader = AsyncBufReader::new(InspectReader::new(
-        // and we need to convert the http stream into an async reader
-        FuturesAsyncReadCompatExt::compat(
-            response
-                .bytes_stream()
-                .map_err(std::io::Error::other)
-                .into_async_read(),
+        DownloadTask::Url(download_url) => InstallPackagesError::WritePackageUrl(
+            (**download_url).clone(),
+            download_path.clone(),
+            e,
         ),
-        |bytes| hasher.update(bytes),
-    ));
-
-    async_copy(&mut reader, &mut writer)
-        .await
-        .map_err(on_write_error_handler)?;
-
-    if let DownloadTask::Package(repository_package) = &download_task {
-        let calculated_hash = hex::encode(hasher.finalize());
-        let hash = repository_package.sha256sum.clone();
+    };
 
-        if hash != calculated_hash {
-            Err(InstallPackagesError::ChecksumFailed {
-                url: download_url,
-                expected: hash,
+    let write_result = async {
+        let mut writer = AsyncFile::create(&download_path)
+            .await
+            .map_err(on_write_error_handler)
+            .map(AsyncBufWriter::new)?;
+
+        let mut chunks = response.bytes_stream().map_err(std::io::Error::other);
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk.map_err(on_write_error_handler)?;
+            writer
+                .write_all(&chunk)
+                .await
+                .map_err(on_write_error_handler)?;
+        }
+        writer.flush().await.map_err(on_write_error_handler)?;
+
+        // Hashing a large download while reading the response would keep the async runtime busy
+        // with CPU-heavy work, starving the other downloads on builders with only a few cores. The
+        // finished file is hashed on the rayon pool instead, a hasher waiting for chunks would hold
+        // a thread while the file writes above can't progress without one of their own.
+        let calculated_hash = calculate_checksum(&download_path, hasher)
+            .await
+            .map_err(on_write_error_handler)?;
+        let expected_hash = match &download_task {
+            DownloadTask::Package(repository_package) => Some(repository_package.checksum.value()),
+            DownloadTask::Url(download_url) => download_url.sha256(),
+        };
+
+        if let Some(hash) = expected_hash
+            && hash != calculated_hash
+        {
+            return Err(InstallPackagesError::ChecksumFailed {
+                url: download_url.clone(),
+                expected: hash.to_string(),
                 actual: calculated_hash,
-            })?;
+            });
         }
+
+        Ok(calculated_hash)
     }
+    .await;
 
-    Ok(download_path)
+    // a partial or unverified download is removed right away so it doesn't use up space in the
+    // download directory for the rest of the build
+    if write_result.is_err() {
+        let _ = remove_file(&download_path).await;
+    }
+    let calculated_hash = write_result?;
+
+    Ok((download_path, calculated_hash))
 }
 
 #[instrument(skip_all)]
-async fn extract(download_path: PathBuf, output_dir: PathBuf) -> BuildpackResult<()> {
-    // a .deb file is an ar archive
-    // https://manpages.ubuntu.com/manpages/jammy/en/man5/deb.5.html
-    let mut debian_archive = File::open(&download_path)
-        .map_err(|e| InstallPackagesError::OpenPackageArchive(download_path.clone(), e))
-        .map(ArArchive::new)?;
+pub(crate) async fn extract(
+    download_path: PathBuf,
+    output_dir: PathBuf,
+    extract_options: ExtractOptions,
+    replaced_files: HashSet<PathBuf>,
+) -> BuildpackResult<(Vec<PathBuf>, PackageControl)> {
+    on_blocking_thread(extract_archive(
+        download_path,
+        output_dir,
+        extract_options,
+        replaced_files,
+    ))
+    .await
+}
+
+// NOTE: The package archive is read with blocking file I/O and its tarballs are decompressed and
+//       unpacked as they're read, which would stall the async runtime's workers for as long as a
+//       package takes to extract. The whole extraction runs on a blocking thread instead and the
+//       permits taken before extracting keep the number of these threads to the configured
+//       parallelism.
+async fn on_blocking_thread<T: Send + 'static>(
+    future: impl Future<Output = BuildpackResult<T>> + Send + 'static,
+) -> BuildpackResult<T> {
+    let runtime = Handle::current();
+    let future = future.in_current_span();
+    spawn_blocking(move || runtime.block_on(future))
+        .await
+        .map_err(InstallPackagesError::TaskFailed)?
+}
+
+async fn extract_archive(
+    download_path: PathBuf,
+    output_dir: PathBuf,
+    extract_options: ExtractOptions,
+    replaced_files: HashSet<PathBuf>,
+) -> BuildpackResult<(Vec<PathBuf>, PackageControl)> {
+    let output_dir = match &extract_options.install_prefix() {
+        Some(prefix) => {
+            let output_dir = output_dir.join(prefix);
+            tokio::fs::create_dir_all(&output_dir)
+                .await
+                .map_err(|e| InstallPackagesError::UnpackTarball(download_path.clone(), e))?;
+            output_dir
+        }
+        None => output_dir,
+    };
+
+    // the replaced files are relative to the install directory but the entries being unpacked are
+    // relative to the prefix
+    let skip_paths = replaced_files
+        .iter()
+        .filter_map(|replaced_file| match &extract_options.install_prefix() {
+            Some(prefix) => replaced_file
+                .strip_prefix(prefix)
+                .ok()
+                .map(Path::to_path_buf),
+            None => Some(replaced_file.clone()),
+        })
+        .collect::<HashSet<_>>();
+
+    let mut debian_archive = open_package_archive(&download_path)?;
 
+    let mut setid_files = vec![];
+    let mut package_control = PackageControl::default();
     while let Some(entry) = debian_archive.next_entry() {
         let entry = entry
             .map_err(|e| InstallPackagesError::OpenPackageArchiveEntry(download_path.clone(), e))?;
-        let entry_path = PathBuf::from(OsString::from_vec(entry.header().identifier().to_vec()));
-        let entry_reader =
-            AsyncBufReader::new(FuturesAsyncReadCompatExt::compat(AllowStdIo::new(entry)));
-
-        // https://manpages.ubuntu.com/manpages/noble/en/man5/deb.5.html
-        match (
-            entry_path.file_stem().and_then(|v| v.to_str()),
-            entry_path.extension().and_then(|v| v.to_str()),
-        ) {
-            (Some("data.tar"), Some("gz")) => {
-                info!({ EXTRACT_PACKAGE_DECODER } = "gzip", "extract package");
-                let mut tar_archive = TarArchive::new(GzipDecoder::new(entry_reader));
-                tar_archive
-                    .unpack(&output_dir)
-                    .await
-                    .map_err(|e| InstallPackagesError::UnpackTarball(download_path.clone(), e))?;
-            }
-            (Some("data.tar"), Some("zstd" | "zst")) => {
-                info!({ EXTRACT_PACKAGE_DECODER } = "zstd", "extract package");
-                let mut tar_archive = TarArchive::new(ZstdDecoder::new(entry_reader));
-                tar_archive
-                    .unpack(&output_dir)
-                    .await
-                    .map_err(|e| InstallPackagesError::UnpackTarball(download_path.clone(), e))?;
-            }
-            (Some("data.tar"), Some("xz")) => {
-                info!({ EXTRACT_PACKAGE_DECODER } = "xz", "extract package");
-                let mut tar_archive = TarArchive::new(XzDecoder::new(entry_reader));
-                tar_archive
-                    .unpack(&output_dir)
-                    .await
-                    .map_err(|e| InstallPackagesError::UnpackTarball(download_path.clone(), e))?;
-            }
-            (Some("data.tar"), Some(compression)) => {
-                info!({ EXTRACT_PACKAGE_DECODER } = compression, "extract package");
-                Err(InstallPackagesError::UnsupportedCompression(
-                    download_path.clone(),
-                    compression.to_string(),
-                ))?;
+        match open_package_tarball(&download_path, entry)? {
+            Some((PackageTarballKind::Data, data_tarball)) => {
+                setid_files = unpack_tarball(
+                    data_tarball,
+                    &output_dir,
+                    &download_path,
+                    &extract_options.include_paths,
+                    &skip_paths,
+                )
+                .await?;
             }
-            _ => {
-                // ignore other potential file entries (e.g.; debian-binary, control.tar)
+            Some((PackageTarballKind::Control, control_tarball)) => {
+                package_control = PackageControl::read(control_tarball, &download_path).await?;
             }
+            None => {}
         }
     }
 
-    Ok(())
+    if let Some(prefix) = &extract_options.install_prefix() {
+        setid_files = setid_files
+            .into_iter()
+            .map(|setid_file| Path::new(prefix).join(setid_file))
+            .collect();
+    }
+    if !setid_files.is_empty() {
+        info!(
+            { EXTRACT_PACKAGE_SETID_FILES } = as_json_value(&setid_files),
+            "extract package"
+        );
+    }
+
+    Ok((setid_files, package_control))
 }
 
+// Lists the files (relative to the install directory) that extracting the package would write.
+// Directories are left out since it's expected for packages to share them.
 #[instrument(skip_all)]
-fn configure_layer_environment(install_path: &Path, multiarch_name: &MultiarchName) -> LayerEnv {
-    let mut layer_env = LayerEnv::new();
+async fn list_package_files(
+    download_path: PathBuf,
+    extract_options: ExtractOptions,
+    extract_permits: Arc<Semaphore>,
+) -> BuildpackResult<Vec<PathBuf>> {
+    let _permit = extract_permits
+        .acquire()
+        .await
+        .expect("Extraction semaphore should never be closed");
+    on_blocking_thread(list_archive_files(download_path, extract_options)).await
+}
 
-    let bin_paths = [
-        install_path.join("bin"),
-        install_path.join("usr/bin"),
-        install_path.join("usr/sbin"),
-        install_path.join("usr/local/bin"),
-        install_path.join("usr/local/sbin"),
-    ];
-    prepend_to_env_var(&mut layer_env, "PATH", &bin_paths);
+async fn list_archive_files(
+    download_path: PathBuf,
+    extract_options: ExtractOptions,
+) -> BuildpackResult<Vec<PathBuf>> {
+    let mut debian_archive = open_package_archive(&download_path)?;
 
-    // support multi-arch and legacy filesystem layouts for debian packages
-    // https://wiki.ubuntu.com/MultiarchSpec
-    let library_paths = [
-        install_path.join(format!("usr/local/lib/{multiarch_name}")),
-        install_path.join("usr/local/lib"),
-        install_path.join(format!("usr/lib/{multiarch_name}")),
-        install_path.join("usr/lib"),
-        install_path.join(format!("lib/{multiarch_name}")),
-        install_path.join("lib"),
-    ]
-    .iter()
-    .fold(IndexSet::new(), |mut acc, lib_dir| {
-        for dir in find_all_dirs_containing(lib_dir, shared_library_file) {
-            acc.insert(dir);
+    let mut package_files = vec![];
+    while let Some(entry) = debian_archive.next_entry() {
+        let entry = entry
+            .map_err(|e| InstallPackagesError::OpenPackageArchiveEntry(download_path.clone(), e))?;
+        if let Some((PackageTarballKind::Data, data_tarball)) =
+            open_package_tarball(&download_path, entry)?
+        {
+            package_files =
+                list_tarball(data_tarball, &download_path, &extract_options.include_paths).await?;
         }
-        acc.insert(lib_dir.clone());
-        acc
-    });
-    prepend_to_env_var(&mut layer_env, "LD_LIBRARY_PATH", &library_paths);
-    prepend_to_env_var(&mut layer_env, "LIBRARY_PATH", &library_paths);
+    }
 
-    let include_paths = [
-        install_path.join(format!("usr/local/include/{multiarch_name}")),
-        install_path.join("usr/local/include"),
-        install_path.join(format!("usr/include/{multiarch_name}")),
-        install_path.join("usr/include"),
-    ]
-    .iter()
-    .fold(IndexSet::new(), |mut acc, include_dir| {
-        for dir in find_all_dirs_containing(include_dir, header_file) {
-            acc.insert(dir);
-        }
-        acc.insert(include_dir.clone());
-        acc
-    });
-    prepend_to_env_var(&mut layer_env, "INCLUDE_PATH", &include_paths);
-    prepend_to_env_var(&mut layer_env, "CPATH", &include_paths);
-    prepend_to_env_var(&mut layer_env, "CPPPATH", &include_paths);
+    if let Some(prefix) = &extract_options.install_prefix() {
+        package_files = package_files
+            .into_iter()
+            .map(|package_file| Path::new(prefix).join(package_file))
+            .collect();
+    }
 
-    let pkg_config_paths = [
-        install_path.join(format!("usr/local/lib/{multiarch_name}/pkgconfig")),
-        install_path.join("usr/local/lib/pkgconfig"),
-        install_path.join(format!("usr/lib/{multiarch_name}/pkgconfig")),
-        install_path.join("usr/lib/pkgconfig"),
-    ];
-    prepend_to_env_var(&mut layer_env, "PKG_CONFIG_PATH", &pkg_config_paths);
+    Ok(package_files)
+}
+
+// a .deb file is an ar archive
+// https://manpages.ubuntu.com/manpages/jammy/en/man5/deb.5.html
+fn open_package_archive(download_path: &Path) -> BuildpackResult<ArArchive<File>> {
+    Ok(File::open(download_path)
+        .map_err(|e| InstallPackagesError::OpenPackageArchive(download_path.to_path_buf(), e))
+        .map(ArArchive::new)?)
+}
+
+type PackageTarball<'a> = TarArchive<Box<dyn AsyncRead + Unpin + Send + 'a>>;
+
+// the members of a package archive that are tarballs
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum PackageTarballKind {
+    // the package metadata and maintainer scripts
+    Control,
+    // the files that get installed
+    Data,
+}
+
+// https://manpages.ubuntu.com/manpages/noble/en/man5/deb.5.html
+fn open_package_tarball<'a>(
+    download_path: &Path,
+    entry: ArEntry<'a, File>,
+) -> BuildpackResult<Option<(PackageTarballKind, PackageTarball<'a>)>> {
+    let entry_path = PathBuf::from(OsString::from_vec(entry.header().identifier().to_vec()));
+    let entry_reader =
+        AsyncBufReader::new(FuturesAsyncReadCompatExt::compat(AllowStdIo::new(entry)));
+
+    let (kind, compression) = match (
+        entry_path.file_stem().and_then(|v| v.to_str()),
+        entry_path.extension().and_then(|v| v.to_str()),
+    ) {
+        (Some("data.tar"), Some(compression)) => (PackageTarballKind::Data, compression),
+        (Some("control.tar"), Some(compression)) => (PackageTarballKind::Control, compression),
+        // an uncompressed control.tar is allowed since dpkg 1.17.6
+        (Some("control"), Some("tar")) => {
+            return Ok(Some((
+                PackageTarballKind::Control,
+                TarArchive::new(Box::new(entry_reader)),
+            )));
+        }
+        _ => {
+            // ignore other potential file entries (e.g.; debian-binary)
+            return Ok(None);
+        }
+    };
+
+    let decoder: Box<dyn AsyncRead + Unpin + Send + 'a> = match compression {
+        "gz" => {
+            info!({ EXTRACT_PACKAGE_DECODER } = "gzip", "extract package");
+            Box::new(GzipDecoder::new(entry_reader))
+        }
+        "zstd" | "zst" => {
+            info!({ EXTRACT_PACKAGE_DECODER } = "zstd", "extract package");
+            Box::new(ZstdDecoder::new(entry_reader))
+        }
+        "xz" => {
+            info!({ EXTRACT_PACKAGE_DECODER } = "xz", "extract package");
+            Box::new(XzDecoder::new(entry_reader))
+        }
+        compression => {
+            info!({ EXTRACT_PACKAGE_DECODER } = compression, "extract package");
+            Err(InstallPackagesError::UnsupportedCompression(
+                download_path.to_path_buf(),
+                compression.to_string(),
+            ))?
+        }
+    };
+
+    Ok(Some((kind, TarArchive::new(decoder))))
+}
+
+async fn list_tarball<R: AsyncRead + Unpin>(
+    mut tar_archive: TarArchive<R>,
+    package_path: &Path,
+    include_paths: &[String],
+) -> BuildpackResult<Vec<PathBuf>> {
+    let on_unpack_error = |e| InstallPackagesError::UnpackTarball(package_path.to_path_buf(), e);
+
+    let mut files = vec![];
+    let mut entries = tar_archive.entries().map_err(on_unpack_error)?;
+    while let Some(entry) = entries.next().await {
+        let entry = entry.map_err(on_unpack_error)?;
+        let entry_path = entry.path().map_err(on_unpack_error)?;
+        if entry.header().entry_type().is_dir()
+            || (!include_paths.is_empty() && !is_included_path(include_paths, &entry_path))
+        {
+            continue;
+        }
+        files.push(relative_archive_path(&entry_path));
+    }
+
+    Ok(files)
+}
+
+// NOTE: Every entry is checked before it's unpacked instead of relying on the tar defaults which
+//       silently skip entries containing `..` and would create device nodes or named pipes. Hard
+//       links are handled here as well so their target is guaranteed to be a file that was already
+//       unpacked into the install directory.
+//
+//       Permissions aren't preserved when unpacking so the setuid and setgid bits are always
+//       removed (the layer isn't owned by root so they have no effect). The files that had them are
+//       returned so they can be reported.
+//
+//       Files replaced by a package later in the install order are skipped along with any hard
+//       links to them since that package may not have been extracted yet.
+async fn unpack_tarball<R: AsyncRead + Unpin>(
+    mut tar_archive: TarArchive<R>,
+    output_dir: &Path,
+    package_path: &Path,
+    include_paths: &[String],
+    skip_paths: &HashSet<PathBuf>,
+) -> BuildpackResult<Vec<PathBuf>> {
+    let on_unpack_error = |e| InstallPackagesError::UnpackTarball(package_path.to_path_buf(), e);
+
+    let mut setid_files = vec![];
+    let mut entries = tar_archive.entries().map_err(on_unpack_error)?;
+    while let Some(entry) = entries.next().await {
+        let mut entry = entry.map_err(on_unpack_error)?;
+        let entry_path = entry.path().map_err(on_unpack_error)?.into_owned();
+        let entry_type = entry.header().entry_type();
+        let link_name = entry.link_name().map_err(on_unpack_error)?;
+
+        let on_unsafe_entry = |reason| {
+            InstallPackagesError::UnsafeTarballEntry(
+                package_path.to_path_buf(),
+                entry_path.clone(),
+                reason,
+            )
+        };
+
+        if let Some(reason) = check_tarball_entry(&entry_path, entry_type, link_name.as_deref()) {
+            Err(on_unsafe_entry(reason))?;
+        }
+
+        if !include_paths.is_empty() && !is_included_path(include_paths, &entry_path) {
+            continue;
+        }
+
+        if !entry_type.is_dir() && skip_paths.contains(&relative_archive_path(&entry_path)) {
+            continue;
+        }
+
+        if entry_type.is_hard_link()
+            && let Some(link_name) = &link_name
+            && skip_paths.contains(&relative_archive_path(link_name))
+        {
+            continue;
+        }
+
+        if entry_type.is_file() && entry.header().mode().map_err(on_unpack_error)? & 0o6000 != 0 {
+            setid_files.push(relative_archive_path(&entry_path));
+        }
+
+        match link_name {
+            Some(link_name) if entry_type.is_hard_link() => {
+                let link_target = output_dir.join(relative_archive_path(&link_name));
+                let link_path = output_dir.join(relative_archive_path(&entry_path));
+                if let Some(parent) = link_path.parent() {
+                    tokio::fs::create_dir_all(parent)
+                        .await
+                        .map_err(on_unpack_error)?;
+                }
+                // a symlink unpacked earlier could otherwise redirect the link outside of the
+                // install directory
+                let install_dir = output_dir.canonicalize().map_err(on_unpack_error)?;
+                for path in [Some(link_target.as_path()), link_path.parent()]
+                    .into_iter()
+                    .flatten()
+                {
+                    if !path
+                        .canonicalize()
+                        .map_err(on_unpack_error)?
+                        .starts_with(&install_dir)
+                    {
+                        Err(on_unsafe_entry(UnsafeTarballEntry::OutsideInstallDirectory))?;
+                    }
+                }
+                if tokio::fs::symlink_metadata(&link_path).await.is_ok() {
+                    remove_file(&link_path).await.map_err(on_unpack_error)?;
+                }
+                tokio::fs::hard_link(&link_target, &link_path)
+                    .await
+                    .map_err(on_unpack_error)?;
+            }
+            _ => {
+                entry.set_preserve_permissions(false);
+                entry.unpack_in(output_dir).await.map_err(on_unpack_error)?;
+            }
+        }
+    }
+
+    Ok(setid_files)
+}
+
+fn check_tarball_entry(
+    entry_path: &Path,
+    entry_type: EntryType,
+    link_name: Option<&Path>,
+) -> Option<UnsafeTarballEntry> {
+    let has_parent_dir = |path: &Path| path.components().any(|part| part == Component::ParentDir);
+
+    if has_parent_dir(entry_path) {
+        Some(UnsafeTarballEntry::PathTraversal)
+    } else if entry_type.is_block_special() {
+        Some(UnsafeTarballEntry::SpecialFile("block device"))
+    } else if entry_type.is_character_special() {
+        Some(UnsafeTarballEntry::SpecialFile("character device"))
+    } else if entry_type.is_fifo() {
+        Some(UnsafeTarballEntry::SpecialFile("named pipe"))
+    } else if entry_type.is_hard_link() {
+        match link_name {
+            Some(link_name) if has_parent_dir(link_name) => Some(UnsafeTarballEntry::PathTraversal),
+            Some(link_name) if relative_archive_path(link_name).as_os_str().is_empty() => {
+                Some(UnsafeTarballEntry::MissingLinkTarget)
+            }
+            Some(_) => None,
+            None => Some(UnsafeTarballEntry::MissingLinkTarget),
+        }
+    } else {
+        None
+    }
+}
+
+// Paths in a package archive are relative to the root of the filesystem (e.g.; `./usr/bin/curl`
+// or `/usr/bin/curl`) and get unpacked relative to the install directory instead.
+fn relative_archive_path(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|part| matches!(part, Component::Normal(_)))
+        .collect()
+}
+
+// hooks stream the output of a blocking process so they're kept off of the async runtime
+async fn run_hook(hook_context: &HookContext, hook: &str, command: &str) -> BuildpackResult<()> {
+    let hook_context = hook_context.clone();
+    let hook = hook.to_string();
+    let command = command.to_string();
+    spawn_blocking(move || hook_context.run(&hook, &command))
+        .await
+        .map_err(InstallPackagesError::TaskFailed)??;
+    Ok(())
+}
+
+// Entries that don't match any of the patterns are skipped. Parent directories of the entries that
+// are extracted are still created.
+fn is_included_path(include_paths: &[String], entry_path: &Path) -> bool {
+    let entry_path = relative_archive_path(entry_path);
+    let entry_path = entry_path.to_string_lossy();
+    let path_parts = entry_path.split('/').collect::<Vec<_>>();
+    include_paths
+        .iter()
+        .any(|pattern| matches_glob_parts(&pattern.split('/').collect::<Vec<_>>(), &path_parts))
+}
+
+// A `**` part matches any number of path parts while `*` and `?` only match within a single part.
+fn matches_glob_parts(pattern_parts: &[&str], path_parts: &[&str]) -> bool {
+    match pattern_parts.split_first() {
+        None => path_parts.is_empty(),
+        Some((&"**", pattern_rest)) => (0..=path_parts.len())
+            .any(|skipped| matches_glob_parts(pattern_rest, &path_parts[skipped..])),
+        Some((pattern_part, pattern_rest)) => {
+            path_parts
+                .split_first()
+                .is_some_and(|(path_part, path_rest)| {
+                    matches_glob_part(pattern_part.as_bytes(), path_part.as_bytes())
+                        && matches_glob_parts(pattern_rest, path_rest)
+                })
+        }
+    }
+}
+
+fn matches_glob_part(pattern: &[u8], value: &[u8]) -> bool {
+    match (pattern.split_first(), value.split_first()) {
+        (None, None) => true,
+        (Some((b'*', pattern_rest)), _) => {
+            (0..=value.len()).any(|skipped| matches_glob_part(pattern_rest, &value[skipped..]))
+        }
+        (Some((b'?', pattern_rest)), Some((_, value_rest))) => {
+            matches_glob_part(pattern_rest, value_rest)
+        }
+        (Some((pattern_char, pattern_rest)), Some((value_char, value_rest))) => {
+            pattern_char == value_char && matches_glob_part(pattern_rest, value_rest)
+        }
+        _ => false,
+    }
+}
+
+// Lets scripts, Procfiles, and hooks reference installed files without hardcoding where the
+// packages layer is (e.g.; `$DEB_PACKAGES_LAYER/opt/wkhtmltopdf/bin/wkhtmltopdf`).
+const PACKAGES_LAYER_ENV: &str = "DEB_PACKAGES_LAYER";
+
+const BIN_DIRS: [&str; 5] = [
+    "bin",
+    "usr/bin",
+    "usr/sbin",
+    "usr/local/bin",
+    "usr/local/sbin",
+];
+
+#[instrument(skip_all)]
+fn configure_layer_environment(
+    install_path: &Path,
+    multiarch_name: &MultiarchName,
+    foreign_multiarch_names: &[MultiarchName],
+    prefixes: &BTreeSet<String>,
+    launch_only_prefixes: &BTreeSet<String>,
+    env_order: &BTreeMap<String, EnvOrder>,
+) -> LayerEnv {
+    let mut layer_env = LayerEnv::new();
+
+    layer_env.insert(
+        Scope::All,
+        ModificationBehavior::Override,
+        PACKAGES_LAYER_ENV,
+        install_path,
+    );
+
+    // executables from packages installed under a prefix come after the ones from the layer root
+    // and only their other files stay isolated from the rest of the layer
+    let bin_paths = BIN_DIRS
+        .iter()
+        .map(|bin_dir| install_path.join(bin_dir))
+        .chain(prefixes.iter().flat_map(|prefix| {
+            BIN_DIRS
+                .iter()
+                .map(move |bin_dir| install_path.join(prefix).join(bin_dir))
+                .filter(|bin_path| bin_path.is_dir())
+        }))
+        .collect::<Vec<_>>();
+    add_to_env_var(&mut layer_env, env_order, "PATH", &bin_paths);
+
+    // support multi-arch and legacy filesystem layouts for debian packages
+    // https://wiki.ubuntu.com/MultiarchSpec
+    //
+    // libraries from foreign architectures (e.g.; i386) are only added when they were installed
+    // and are never used for headers or pkg-config since those are meant for the native toolchain
+    let library_paths = ["usr/local/lib", "usr/lib", "lib"]
+        .iter()
+        .flat_map(|lib_dir| {
+            let foreign_lib_dirs = foreign_multiarch_names
+                .iter()
+                .map(move |foreign_multiarch_name| {
+                    install_path.join(format!("{lib_dir}/{foreign_multiarch_name}"))
+                })
+                .filter(|foreign_lib_dir| foreign_lib_dir.is_dir());
+            [install_path.join(format!("{lib_dir}/{multiarch_name}"))]
+                .into_iter()
+                .chain(foreign_lib_dirs)
+                .chain([install_path.join(lib_dir)])
+        })
+        .collect::<Vec<_>>()
+        .iter()
+        .fold(IndexSet::new(), |mut acc, lib_dir| {
+            for dir in find_all_dirs_containing(lib_dir, shared_library_file) {
+                acc.insert(dir);
+            }
+            acc.insert(lib_dir.clone());
+            acc
+        });
+    add_to_env_var(&mut layer_env, env_order, "LD_LIBRARY_PATH", &library_paths);
+    add_to_env_var(&mut layer_env, env_order, "LIBRARY_PATH", &library_paths);
+
+    let include_paths = [
+        install_path.join(format!("usr/local/include/{multiarch_name}")),
+        install_path.join("usr/local/include"),
+        install_path.join(format!("usr/include/{multiarch_name}")),
+        install_path.join("usr/include"),
+    ]
+    .iter()
+    .fold(IndexSet::new(), |mut acc, include_dir| {
+        for dir in find_all_dirs_containing(include_dir, header_file) {
+            acc.insert(dir);
+        }
+        acc.insert(include_dir.clone());
+        acc
+    });
+    add_to_env_var(&mut layer_env, env_order, "INCLUDE_PATH", &include_paths);
+    add_to_env_var(&mut layer_env, env_order, "CPATH", &include_paths);
+    add_to_env_var(&mut layer_env, env_order, "CPPPATH", &include_paths);
+
+    let pkg_config_paths = [
+        install_path.join(format!("usr/local/lib/{multiarch_name}/pkgconfig")),
+        install_path.join("usr/local/lib/pkgconfig"),
+        install_path.join(format!("usr/lib/{multiarch_name}/pkgconfig")),
+        install_path.join("usr/lib/pkgconfig"),
+    ];
+    add_to_env_var(
+        &mut layer_env,
+        env_order,
+        "PKG_CONFIG_PATH",
+        &pkg_config_paths,
+    );
+
+    configure_launch_only_environment(
+        &mut layer_env,
+        install_path,
+        multiarch_name,
+        launch_only_prefixes,
+        env_order,
+    );
 
     info!(
         { ENV_PATH } = as_json_value(&bin_paths),
@@ -446,6 +1780,61 @@ fn configure_layer_environment(install_path: &Path, multiarch_name: &MultiarchNa
     layer_env
 }
 
+// Launch-only packages only add their executables and shared libraries to the environment of the
+// running application, never to the build.
+fn configure_launch_only_environment(
+    layer_env: &mut LayerEnv,
+    install_path: &Path,
+    multiarch_name: &MultiarchName,
+    launch_only_prefixes: &BTreeSet<String>,
+    env_order: &BTreeMap<String, EnvOrder>,
+) {
+    let launch_only_bin_paths = launch_only_prefixes
+        .iter()
+        .flat_map(|prefix| {
+            BIN_DIRS
+                .iter()
+                .map(move |bin_dir| install_path.join(prefix).join(bin_dir))
+        })
+        .filter(|bin_path| bin_path.is_dir())
+        .collect::<Vec<_>>();
+    let launch_only_library_paths = launch_only_prefixes
+        .iter()
+        .flat_map(|prefix| {
+            [
+                format!("usr/local/lib/{multiarch_name}"),
+                "usr/local/lib".to_string(),
+                format!("usr/lib/{multiarch_name}"),
+                "usr/lib".to_string(),
+                format!("lib/{multiarch_name}"),
+                "lib".to_string(),
+            ]
+            .map(|lib_dir| install_path.join(prefix).join(lib_dir))
+        })
+        .fold(IndexSet::new(), |mut acc, lib_dir| {
+            acc.extend(find_all_dirs_containing(&lib_dir, shared_library_file));
+            acc
+        });
+    if !launch_only_bin_paths.is_empty() {
+        add_to_env_var_in_scope(
+            layer_env,
+            env_order,
+            Scope::Launch,
+            "PATH",
+            &launch_only_bin_paths,
+        );
+    }
+    if !launch_only_library_paths.is_empty() {
+        add_to_env_var_in_scope(
+            layer_env,
+            env_order,
+            Scope::Launch,
+            "LD_LIBRARY_PATH",
+            &launch_only_library_paths,
+        );
+    }
+}
+
 fn find_all_dirs_containing(
     starting_dir: &Path,
     condition: impl Fn(&Path) -> bool,
@@ -484,16 +1873,44 @@ fn header_file(path: &Path) -> bool {
     matches!(path.extension(), Some(ext) if ext == "h")
 }
 
-fn prepend_to_env_var<I, T>(layer_env: &mut LayerEnv, name: &str, paths: I)
-where
+fn add_to_env_var<I, T>(
+    layer_env: &mut LayerEnv,
+    env_order: &BTreeMap<String, EnvOrder>,
+    name: &str,
+    paths: I,
+) where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    add_to_env_var_in_scope(layer_env, env_order, Scope::All, name, paths);
+}
+
+// The layer paths are prepended unless the variable was configured to append them (e.g.; so the
+// `curl` from the base image is still found before the one installed as a dependency).
+fn add_to_env_var_in_scope<I, T>(
+    layer_env: &mut LayerEnv,
+    env_order: &BTreeMap<String, EnvOrder>,
+    scope: Scope,
+    name: &str,
+    paths: I,
+) where
     I: IntoIterator<Item = T>,
     T: Into<OsString>,
 {
     let separator = ":";
-    layer_env.insert(Scope::All, ModificationBehavior::Delimiter, name, separator);
+    let modification_behavior = match env_order.get(name).copied().unwrap_or_default() {
+        EnvOrder::Prepend => ModificationBehavior::Prepend,
+        EnvOrder::Append => ModificationBehavior::Append,
+    };
     layer_env.insert(
-        Scope::All,
-        ModificationBehavior::Prepend,
+        scope.clone(),
+        ModificationBehavior::Delimiter,
+        name,
+        separator,
+    );
+    layer_env.insert(
+        scope,
+        modification_behavior,
         name,
         paths
             .into_iter()
@@ -503,15 +1920,32 @@ where
     );
 }
 
-async fn rewrite_package_configs(install_path: &Path) -> BuildpackResult<()> {
-    let package_configs = WalkDir::new(install_path)
-        .into_iter()
-        .flatten()
-        .filter(is_package_config)
-        .map(|entry| entry.path().to_path_buf());
+async fn rewrite_package_configs(
+    install_path: &Path,
+    prefixes: &BTreeSet<String>,
+) -> BuildpackResult<()> {
+    let package_configs = {
+        let install_path = install_path.to_path_buf();
+        spawn_blocking(move || {
+            WalkDir::new(install_path)
+                .into_iter()
+                .flatten()
+                .filter(is_package_config)
+                .map(|entry| entry.path().to_path_buf())
+                .collect::<Vec<_>>()
+        })
+        .await
+        .map_err(InstallPackagesError::TaskFailed)?
+    };
 
     for package_config in package_configs {
-        rewrite_package_config(&package_config, install_path).await?;
+        // package configs from packages installed under a prefix are relative to that prefix
+        let package_root = prefixes
+            .iter()
+            .map(|prefix| install_path.join(prefix))
+            .find(|prefix_path| package_config.starts_with(prefix_path))
+            .unwrap_or_else(|| install_path.to_path_buf());
+        rewrite_package_config(&package_config, &package_root).await?;
     }
 
     Ok(())
@@ -551,10 +1985,148 @@ async fn rewrite_package_config(package_config: &Path, install_path: &Path) -> B
         .map_err(|e| InstallPackagesError::WritePackageConfig(package_config.to_path_buf(), e))?)
 }
 
-fn build_download_url(repository_package: &RepositoryPackage) -> String {
+const GITHUB_API_URL: &str = "https://api.github.com";
+
+// The url and headers a package listed in `download` is requested with.
+async fn download_url_request(
+    client: &impl Fetcher,
+    download_url: &DownloadUrl,
+    github_token: Option<String>,
+) -> BuildpackResult<(String, HeaderMap)> {
+    let mut headers = HeaderMap::new();
+    append_download_headers(&mut headers, download_url)?;
+    match (download_url.github_release_asset(), github_token) {
+        (Some(github_release_asset), Some(github_token)) => {
+            let mut authorization = HeaderValue::from_str(&format!("Bearer {github_token}"))
+                .map_err(|_| {
+                    InstallPackagesError::ResolveDownloadHeader(
+                        download_url.clone(),
+                        DownloadHeader {
+                            name: AUTHORIZATION.to_string(),
+                            value: DownloadHeaderValue::Env("GITHUB_TOKEN".to_string()),
+                        },
+                    )
+                })?;
+            authorization.set_sensitive(true);
+            let asset_url = request_github_release_asset_url(
+                client,
+                download_url,
+                &github_release_asset,
+                &authorization,
+            )
+            .await?;
+            headers.insert(AUTHORIZATION, authorization);
+            headers.insert(ACCEPT, HeaderValue::from_static("application/octet-stream"));
+            Ok((asset_url, headers))
+        }
+        _ => Ok((download_url.to_string(), headers)),
+    }
+}
+
+// NOTE: Release assets from private repositories aren't served from their `github.com` url even
+//       with a token so they're downloaded through the GitHub API instead. The release is looked up
+//       by its tag to find the id of the asset, which is then requested with an
+//       `application/octet-stream` Accept header. The API redirects to a signed url on GitHub's
+//       objects CDN and, since that's a different host, the token isn't sent along.
+async fn request_github_release_asset_url(
+    client: &impl Fetcher,
+    download_url: &DownloadUrl,
+    github_release_asset: &GithubReleaseAsset,
+    authorization: &HeaderValue,
+) -> BuildpackResult<String> {
+    let GithubReleaseAsset {
+        owner,
+        repo,
+        tag,
+        name,
+    } = github_release_asset;
+
+    let mut headers = HeaderMap::new();
+    headers.insert(AUTHORIZATION, authorization.clone());
+    headers.insert(
+        ACCEPT,
+        HeaderValue::from_static("application/vnd.github+json"),
+    );
+    let release = client
+        .fetch(
+            &format!("{GITHUB_API_URL}/repos/{owner}/{repo}/releases/tags/{tag}"),
+            headers,
+        )
+        .await
+        .and_then(error_for_status)
+        .map_err(|e| InstallPackagesError::RequestPackageUrl(download_url.clone(), e))?
+        .bytes()
+        .await
+        .map_err(|e| InstallPackagesError::RequestPackageUrl(download_url.clone(), Reqwest(e)))?;
+
+    let release = serde_json::from_slice::<GithubRelease>(&release).map_err(|e| {
+        InstallPackagesError::ResolveGithubReleaseAsset(download_url.clone(), e.to_string())
+    })?;
+    let asset = release
+        .assets
+        .into_iter()
+        .find(|asset| asset.name == *name)
+        .ok_or_else(|| {
+            InstallPackagesError::ResolveGithubReleaseAsset(
+                download_url.clone(),
+                format!("release `{tag}` has no asset named `{name}`"),
+            )
+        })?;
+
+    Ok(format!(
+        "{GITHUB_API_URL}/repos/{owner}/{repo}/releases/assets/{id}",
+        id = asset.id
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubRelease {
+    assets: Vec<GithubReleaseAssetResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GithubReleaseAssetResponse {
+    id: u64,
+    name: String,
+}
+
+fn append_download_headers(
+    headers: &mut HeaderMap,
+    download_url: &DownloadUrl,
+) -> BuildpackResult<()> {
+    for header in download_url.headers() {
+        let resolve_error =
+            || InstallPackagesError::ResolveDownloadHeader(download_url.clone(), header.clone());
+        let header_name = HeaderName::from_str(&header.name).map_err(|_| resolve_error())?;
+        let header_value = resolve_download_header(header).ok_or_else(resolve_error)?;
+        headers.append(header_name, header_value);
+    }
+    Ok(())
+}
+
+// Values read from the environment are marked as sensitive so they're never displayed in debug
+// output.
+fn resolve_download_header(header: &DownloadHeader) -> Option<HeaderValue> {
+    let value = match &header.value {
+        DownloadHeaderValue::Literal(value) => value.clone(),
+        DownloadHeaderValue::Env(name) => Env::from_current()
+            .get_string_lossy(name)
+            .filter(|value| !value.is_empty())?,
+    };
+    let mut value = HeaderValue::from_str(&value).ok()?;
+    value.set_sensitive(true);
+    Some(value)
+}
+
+// NOTE: The `Filename` from the package index is used as-is since that's where the repository
+//       published the package. For filenames that contain the version epoch (e.g.;
+//       `pool/main/c/curl/curl_1:8.5.0_amd64.deb`) the `:` is percent-encoded the same way apt
+//       requests them so it can't be mistaken for a port or scheme separator along the way.
+pub(crate) fn build_download_url(repository_package: &RepositoryPackage) -> String {
     format!(
         "{}/{}",
-        repository_package.repository_uri, repository_package.filename
+        repository_package.repository_uri,
+        repository_package.filename.replace(':', "%3a")
     )
 }
 
@@ -562,8 +2134,17 @@ fn build_download_url(repository_package: &RepositoryPackage) -> String {
 pub(crate) enum InstallPackagesError {
     TaskFailed(JoinError),
     InvalidFilename(String, String),
+    CreateDownloadDir(PathBuf, std::io::Error),
+    ReadBinary(PathBuf, std::io::Error),
+    WriteBinary(PathBuf, std::io::Error),
+    PruneFile(PathBuf, std::io::Error),
+    NormalizeTimestamp(PathBuf, std::io::Error),
     RequestPackage(RepositoryPackage, reqwest_middleware::Error),
     RequestPackageUrl(DownloadUrl, reqwest_middleware::Error),
+    ResolveDownloadHeader(DownloadUrl, DownloadHeader),
+    ResolveGithubReleaseAsset(DownloadUrl, String),
+    RequestPackageSignature(String, reqwest_middleware::Error),
+    VerifyPackageSignature(String, PackageSignatureError),
     WritePackage(RepositoryPackage, String, PathBuf, std::io::Error),
     WritePackageUrl(DownloadUrl, PathBuf, std::io::Error),
     ChecksumFailed {
@@ -574,9 +2155,26 @@ pub(crate) enum InstallPackagesError {
     OpenPackageArchive(PathBuf, std::io::Error),
     OpenPackageArchiveEntry(PathBuf, std::io::Error),
     UnpackTarball(PathBuf, std::io::Error),
+    UnsafeTarballEntry(PathBuf, PathBuf, UnsafeTarballEntry),
     UnsupportedCompression(PathBuf, String),
+    WritePackageManifest(PathBuf, std::io::Error),
+    WriteInstallPlan(PathBuf, std::io::Error),
     ReadPackageConfig(PathBuf, std::io::Error),
     WritePackageConfig(PathBuf, std::io::Error),
+    ReadVendoredPackage(PathBuf, std::io::Error),
+    VendoredPackageChecksumFailed {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+}
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum UnsafeTarballEntry {
+    PathTraversal,
+    OutsideInstallDirectory,
+    MissingLinkTarget,
+    SpecialFile(&'static str),
 }
 
 impl From<InstallPackagesError> for libcnb::Error<DebianPackagesBuildpackError> {
@@ -590,25 +2188,909 @@ impl From<InstallPackagesError> for libcnb::Error<DebianPackagesBuildpackError>
 #[derive(Debug, Serialize, Deserialize, Eq, PartialEq, Clone)]
 struct InstallationMetadata {
     package_checksums: HashMap<String, String>,
+    // only used for reporting changes, absent from older metadata
+    #[serde(default)]
+    package_versions: HashMap<String, String>,
     distro: Distro,
     download_urls: Vec<String>,
+    // user-controlled value that invalidates the layer when changed, absent from older metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cache_key: Option<String>,
+    // absent from older metadata, which never had binaries stripped
+    #[serde(default)]
+    strip_binaries: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    prune: Vec<PruneTarget>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    extract_options: BTreeMap<String, ExtractOptions>,
+    #[serde(default, skip_serializing_if = "HooksConfig::is_empty")]
+    hooks: HooksConfig,
+    // not compared for changes since the same packages can be resolved from different inputs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resolution: Option<StoredResolution>,
+}
+
+// The packages layer metadata is read before the layer is restored so the package index doesn't
+// need to be created when the resolution stored by the previous build can be reused. Any problem
+// reading it only means the packages are resolved again.
+pub(crate) fn read_stored_resolution(
+    context: &BuildContext<DebianPackagesBuildpack>,
+) -> Option<StoredResolution> {
+    let contents = std::fs::read_to_string(context.layers_dir.join("packages.toml")).ok()?;
+    toml::from_str::<LayerContentMetadata<InstallationMetadata>>(&contents)
+        .ok()?
+        .metadata
+        .resolution
 }
 
-enum DownloadTask {
-    Package(RepositoryPackage),
-    Url(DownloadUrl),
+// Settings from the requested packages that change which files are extracted from a package.
+#[derive(Debug, Default, Serialize, Deserialize, Eq, PartialEq, Clone)]
+pub(crate) struct ExtractOptions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include_paths: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    launch_only: bool,
+}
+
+// Launch-only packages are extracted under this directory of the layer so their executables and
+// libraries can be left out of the build environment (e.g.; a different OpenSSL that shouldn't be
+// linked against when compiling the application).
+const LAUNCH_ONLY_DIR: &str = "launch-only";
+
+impl ExtractOptions {
+    // only packages that change how they're extracted are included, keyed by the package name
+    pub(crate) fn from_requested_packages(
+        requested_packages: &IndexSet<RequestedPackage>,
+    ) -> BTreeMap<String, ExtractOptions> {
+        requested_packages
+            .iter()
+            .map(|requested_package| {
+                (
+                    // requested package names are corrected to the lowercase names from the
+                    // package index when they're resolved
+                    requested_package.qualified_name().to_lowercase(),
+                    ExtractOptions {
+                        include_paths: requested_package.include_paths.clone(),
+                        prefix: requested_package.prefix.clone(),
+                        launch_only: requested_package.launch_only,
+                    },
+                )
+            })
+            .filter(|(_, extract_options)| *extract_options != ExtractOptions::default())
+            .collect()
+    }
+
+    // the directory of the layer the package is extracted into when it's not the root
+    fn install_prefix(&self) -> Option<String> {
+        match (&self.prefix, self.launch_only) {
+            (Some(prefix), true) => Some(format!("{LAUNCH_ONLY_DIR}/{prefix}")),
+            (None, true) => Some(LAUNCH_ONLY_DIR.to_string()),
+            (prefix, false) => prefix.clone(),
+        }
+    }
+}
+
+pub(crate) enum DownloadTask {
+    Package(Box<RepositoryPackage>),
+    Url(Box<DownloadUrl>),
+}
+
+impl InstallationMetadata {
+    // Packages are compared by checksum so metadata written before versions were recorded won't
+    // invalidate the layer, the versions are only used to describe what changed.
+    fn changes(&self, new: &InstallationMetadata) -> Vec<InstallationChange> {
+        let mut changes = vec![];
+
+        if self.distro.architecture != new.distro.architecture {
+            changes.push(InstallationChange::Architecture {
+                old: self.distro.architecture.clone(),
+                new: new.distro.architecture.clone(),
+            });
+        }
+
+        if self.distro.name != new.distro.name
+            || self.distro.version != new.distro.version
+            || self.distro.codename != new.distro.codename
+        {
+            changes.push(InstallationChange::Distro {
+                old: self.distro.clone(),
+                new: new.distro.clone(),
+            });
+        }
+
+        let names = self
+            .package_checksums
+            .keys()
+            .chain(new.package_checksums.keys())
+            .collect::<BTreeSet<_>>();
+        for name in names {
+            let old_version = self.package_versions.get(name).cloned();
+            let new_version = new.package_versions.get(name).cloned();
+            match (
+                self.package_checksums.get(name),
+                new.package_checksums.get(name),
+            ) {
+                (None, Some(_)) => changes.push(InstallationChange::AddedPackage {
+                    name: name.clone(),
+                    version: new_version,
+                }),
+                (Some(_), None) => changes.push(InstallationChange::RemovedPackage {
+                    name: name.clone(),
+                    version: old_version,
+                }),
+                (Some(old_checksum), Some(new_checksum)) if old_checksum != new_checksum => {
+                    changes.push(InstallationChange::UpdatedPackage {
+                        name: name.clone(),
+                        old_version,
+                        new_version,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        for download_url in &new.download_urls {
+            if !self.download_urls.contains(download_url) {
+                changes.push(InstallationChange::AddedDownloadUrl(download_url.clone()));
+            }
+        }
+        for download_url in &self.download_urls {
+            if !new.download_urls.contains(download_url) {
+                changes.push(InstallationChange::RemovedDownloadUrl(download_url.clone()));
+            }
+        }
+
+        if self.cache_key != new.cache_key {
+            changes.push(InstallationChange::CacheKey);
+        }
+
+        if self.strip_binaries != new.strip_binaries {
+            changes.push(InstallationChange::StripBinaries(new.strip_binaries));
+        }
+
+        if self.prune != new.prune {
+            changes.push(InstallationChange::Prune);
+        }
+
+        if self.extract_options != new.extract_options {
+            changes.push(InstallationChange::ExtractOptions);
+        }
+
+        if self.hooks != new.hooks {
+            changes.push(InstallationChange::Hooks);
+        }
+
+        changes
+    }
+}
+
+#[derive(Debug, Eq, PartialEq)]
+enum InstallationChange {
+    Architecture {
+        old: ArchitectureName,
+        new: ArchitectureName,
+    },
+    Distro {
+        old: Distro,
+        new: Distro,
+    },
+    AddedPackage {
+        name: String,
+        version: Option<String>,
+    },
+    RemovedPackage {
+        name: String,
+        version: Option<String>,
+    },
+    UpdatedPackage {
+        name: String,
+        old_version: Option<String>,
+        new_version: Option<String>,
+    },
+    AddedDownloadUrl(String),
+    RemovedDownloadUrl(String),
+    CacheKey,
+    StripBinaries(bool),
+    Prune,
+    ExtractOptions,
+    Hooks,
+}
+
+impl InstallationChange {
+    // the metadata field that caused the change, used for reporting why the layer was invalidated
+    fn field(&self) -> &'static str {
+        match self {
+            InstallationChange::Architecture { .. } => "architecture",
+            InstallationChange::Distro { .. } => "distro",
+            InstallationChange::AddedPackage { .. }
+            | InstallationChange::RemovedPackage { .. }
+            | InstallationChange::UpdatedPackage { .. } => "package_checksums",
+            InstallationChange::AddedDownloadUrl(_) | InstallationChange::RemovedDownloadUrl(_) => {
+                "download_urls"
+            }
+            InstallationChange::CacheKey => "cache_key",
+            InstallationChange::StripBinaries(_) => "strip_binaries",
+            InstallationChange::Prune => "prune",
+            InstallationChange::ExtractOptions => "extract_options",
+            InstallationChange::Hooks => "hooks",
+        }
+    }
+}
+
+impl Display for InstallationChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name_with_version = |name: &str, version: &Option<String>| match version {
+            Some(version) => style::value(format!("{name}@{version}")),
+            None => style::value(name),
+        };
+        let distro_name = |distro: &Distro| {
+            style::value(format!(
+                "{name} {version}",
+                name = distro.name,
+                version = distro.version
+            ))
+        };
+        match self {
+            InstallationChange::Architecture { old, new } => write!(
+                f,
+                "Architecture changed from {old} to {new}",
+                old = style::value(old.to_string()),
+                new = style::value(new.to_string())
+            ),
+            InstallationChange::Distro { old, new } => write!(
+                f,
+                "Distribution changed from {old} to {new}",
+                old = distro_name(old),
+                new = distro_name(new)
+            ),
+            InstallationChange::AddedPackage { name, version } => {
+                write!(f, "Added {}", name_with_version(name, version))
+            }
+            InstallationChange::RemovedPackage { name, version } => {
+                write!(f, "Removed {}", name_with_version(name, version))
+            }
+            InstallationChange::UpdatedPackage {
+                name,
+                old_version: Some(old_version),
+                new_version: Some(new_version),
+            } if old_version != new_version => write!(
+                f,
+                "Updated {name} from {old_version} to {new_version}",
+                name = style::value(name),
+                old_version = style::value(old_version),
+                new_version = style::value(new_version)
+            ),
+            InstallationChange::UpdatedPackage { name, .. } => write!(
+                f,
+                "Updated {name} (checksum changed)",
+                name = style::value(name)
+            ),
+            InstallationChange::AddedDownloadUrl(url) => {
+                write!(f, "Added package from {url}", url = style::url(url))
+            }
+            InstallationChange::RemovedDownloadUrl(url) => {
+                write!(f, "Removed package from {url}", url = style::url(url))
+            }
+            InstallationChange::CacheKey => write!(f, "Cache key changed"),
+            InstallationChange::StripBinaries(true) => write!(f, "Binary stripping enabled"),
+            InstallationChange::StripBinaries(false) => write!(f, "Binary stripping disabled"),
+            InstallationChange::Prune => write!(f, "Pruned files changed"),
+            InstallationChange::ExtractOptions => write!(f, "Extracted package paths changed"),
+            InstallationChange::Hooks => write!(f, "Hooks changed"),
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use std::ffi::OsString;
+    use std::os::unix::fs::PermissionsExt;
     use std::path::{Path, PathBuf};
 
+    use libcnb::Env;
     use libcnb::layer_env::Scope;
     use tempfile::TempDir;
 
-    use crate::debian::MultiarchName;
-    use crate::install_packages::configure_layer_environment;
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+    use std::str::FromStr;
+
+    use crate::DebianPackagesBuildpackError;
+    use crate::config::download_url::DownloadUrl;
+    use crate::config::hooks_config::HooksConfig;
+    use crate::config::{EnvOrder, PruneTarget};
+    use crate::debian::{
+        ArchitectureName, Distro, DistroCodename, MultiarchName, create_repository_package,
+    };
+    use crate::fetcher::Fetcher;
+    use crate::install_packages::{
+        DownloadTask, ExtractOptions, InstallPackagesError, InstallationChange,
+        InstallationMetadata, PACKAGES_LAYER_ENV, UnsafeTarballEntry, build_download_url,
+        calculate_sha256, check_tarball_entry, configure_layer_environment, download,
+        download_url_request, extract, get_layer_size, is_included_path, list_tarball,
+        unpack_tarball,
+    };
+    use crate::stored_resolution::StoredResolution;
+    use ar::{Builder as ArBuilder, Header as ArHeader};
+    use async_compression::tokio::write::GzipEncoder;
+    use reqwest::header::{ACCEPT, AUTHORIZATION, HeaderMap};
+    use std::sync::Arc;
+    use tokio::io::AsyncWriteExt;
+    use tokio_tar::{Archive as TarArchive, Builder as TarBuilder, EntryType, Header};
+
+    #[test]
+    fn check_tarball_entry_rejects_unsafe_entries() {
+        let file = EntryType::Regular;
+        assert_eq!(
+            check_tarball_entry(Path::new("./usr/bin/curl"), file, None),
+            None
+        );
+        assert_eq!(
+            check_tarball_entry(Path::new("./usr/../../etc/passwd"), file, None),
+            Some(UnsafeTarballEntry::PathTraversal)
+        );
+        assert_eq!(
+            check_tarball_entry(Path::new("./dev/sda"), EntryType::Block, None),
+            Some(UnsafeTarballEntry::SpecialFile("block device"))
+        );
+        assert_eq!(
+            check_tarball_entry(Path::new("./dev/tty"), EntryType::Char, None),
+            Some(UnsafeTarballEntry::SpecialFile("character device"))
+        );
+        assert_eq!(
+            check_tarball_entry(Path::new("./run/pipe"), EntryType::Fifo, None),
+            Some(UnsafeTarballEntry::SpecialFile("named pipe"))
+        );
+        assert_eq!(
+            check_tarball_entry(
+                Path::new("./usr/bin/link"),
+                EntryType::Link,
+                Some(Path::new("./usr/bin/curl"))
+            ),
+            None
+        );
+        assert_eq!(
+            check_tarball_entry(
+                Path::new("./usr/bin/link"),
+                EntryType::Link,
+                Some(Path::new("../../etc/passwd"))
+            ),
+            Some(UnsafeTarballEntry::PathTraversal)
+        );
+        assert_eq!(
+            check_tarball_entry(
+                Path::new("./usr/bin/link"),
+                EntryType::Link,
+                Some(Path::new("./"))
+            ),
+            Some(UnsafeTarballEntry::MissingLinkTarget)
+        );
+    }
+
+    #[test]
+    fn extract_unpacks_package_archive() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = temp_dir.path().join("package.deb");
+        let install_dir = temp_dir.path().join("install");
+        std::fs::create_dir(&install_dir).unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut builder = TarBuilder::new(Vec::new());
+            let mut header = Header::new_gnu();
+            header.set_size(5);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, "./usr/bin/hello", &b"hello"[..])
+                .await
+                .unwrap();
+            let tarball = builder.into_inner().await.unwrap();
+            let mut encoder = GzipEncoder::new(Vec::new());
+            encoder.write_all(&tarball).await.unwrap();
+            encoder.shutdown().await.unwrap();
+            let data_tarball = encoder.into_inner();
+
+            let mut archive = ArBuilder::new(Vec::new());
+            archive
+                .append(&ArHeader::new(b"debian-binary".to_vec(), 4), &b"2.0\n"[..])
+                .unwrap();
+            archive
+                .append(
+                    &ArHeader::new(b"data.tar.gz".to_vec(), data_tarball.len() as u64),
+                    &data_tarball[..],
+                )
+                .unwrap();
+            std::fs::write(&package_path, archive.into_inner().unwrap()).unwrap();
+
+            extract(
+                package_path.clone(),
+                install_dir.clone(),
+                ExtractOptions::default(),
+                HashSet::new(),
+            )
+            .await
+            .unwrap();
+        });
+        assert_eq!(
+            std::fs::read_to_string(install_dir.join("usr/bin/hello")).unwrap(),
+            "hello"
+        );
+    }
+
+    #[test]
+    fn unpack_tarball_with_hard_link() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut builder = TarBuilder::new(Vec::new());
+            let mut header = Header::new_gnu();
+            header.set_size(5);
+            header.set_mode(0o644);
+            builder
+                .append_data(&mut header, "./usr/lib/libfoo.so.1", &b"hello"[..])
+                .await
+                .unwrap();
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Link);
+            header.set_link_name("./usr/lib/libfoo.so.1").unwrap();
+            header.set_size(0);
+            builder
+                .append_data(&mut header, "./usr/lib/libfoo.so", &b""[..])
+                .await
+                .unwrap();
+            let tarball = builder.into_inner().await.unwrap();
+
+            unpack_tarball(
+                TarArchive::new(&tarball[..]),
+                install_dir.path(),
+                Path::new("package.deb"),
+                &[],
+                &HashSet::new(),
+            )
+            .await
+            .unwrap();
+        });
+        assert_eq!(
+            std::fs::read_to_string(install_dir.path().join("usr/lib/libfoo.so")).unwrap(),
+            "hello"
+        );
+    }
+
+    #[test]
+    fn unpack_tarball_skips_replaced_files() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let package_files = runtime.block_on(async {
+            let mut builder = TarBuilder::new(Vec::new());
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Directory);
+            header.set_size(0);
+            header.set_mode(0o755);
+            builder
+                .append_data(&mut header, "./usr/bin/", &b""[..])
+                .await
+                .unwrap();
+            for path in ["./usr/bin/tool", "./usr/bin/shared"] {
+                let mut header = Header::new_gnu();
+                header.set_size(5);
+                header.set_mode(0o755);
+                builder
+                    .append_data(&mut header, path, &b"hello"[..])
+                    .await
+                    .unwrap();
+            }
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Link);
+            header.set_link_name("./usr/bin/shared").unwrap();
+            header.set_size(0);
+            builder
+                .append_data(&mut header, "./usr/bin/shared-link", &b""[..])
+                .await
+                .unwrap();
+            let tarball = builder.into_inner().await.unwrap();
+
+            unpack_tarball(
+                TarArchive::new(&tarball[..]),
+                install_dir.path(),
+                Path::new("package.deb"),
+                &[],
+                &HashSet::from([PathBuf::from("usr/bin/shared")]),
+            )
+            .await
+            .unwrap();
+
+            list_tarball(TarArchive::new(&tarball[..]), Path::new("package.deb"), &[])
+                .await
+                .unwrap()
+        });
+        assert_eq!(
+            package_files,
+            vec![
+                PathBuf::from("usr/bin/tool"),
+                PathBuf::from("usr/bin/shared"),
+                PathBuf::from("usr/bin/shared-link")
+            ]
+        );
+        assert!(install_dir.path().join("usr/bin/tool").exists());
+        assert!(!install_dir.path().join("usr/bin/shared").exists());
+        assert!(!install_dir.path().join("usr/bin/shared-link").exists());
+    }
+
+    #[test]
+    fn unpack_tarball_with_include_paths() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut builder = TarBuilder::new(Vec::new());
+            for path in [
+                "./usr/lib/chromium/libffmpeg.so",
+                "./usr/lib/chromium/plugins/libwidevine.so",
+                "./usr/share/doc/chromium/copyright",
+            ] {
+                let mut header = Header::new_gnu();
+                header.set_size(5);
+                header.set_mode(0o644);
+                builder
+                    .append_data(&mut header, path, &b"hello"[..])
+                    .await
+                    .unwrap();
+            }
+            let tarball = builder.into_inner().await.unwrap();
+
+            unpack_tarball(
+                TarArchive::new(&tarball[..]),
+                install_dir.path(),
+                Path::new("package.deb"),
+                &["usr/lib/chromium/**".to_string()],
+                &HashSet::new(),
+            )
+            .await
+            .unwrap();
+        });
+        assert!(
+            install_dir
+                .path()
+                .join("usr/lib/chromium/libffmpeg.so")
+                .exists()
+        );
+        assert!(
+            install_dir
+                .path()
+                .join("usr/lib/chromium/plugins/libwidevine.so")
+                .exists()
+        );
+        assert!(!install_dir.path().join("usr/share").exists());
+    }
+
+    #[test]
+    fn unpack_tarball_removes_setuid_and_setgid_bits() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let setid_files = runtime.block_on(async {
+            let mut builder = TarBuilder::new(Vec::new());
+            for (path, mode) in [
+                ("./usr/bin/sudo", 0o4755),
+                ("./usr/bin/crontab", 0o2755),
+                ("./usr/bin/curl", 0o755),
+            ] {
+                let mut header = Header::new_gnu();
+                header.set_size(5);
+                header.set_mode(mode);
+                builder
+                    .append_data(&mut header, path, &b"hello"[..])
+                    .await
+                    .unwrap();
+            }
+            let tarball = builder.into_inner().await.unwrap();
+
+            unpack_tarball(
+                TarArchive::new(&tarball[..]),
+                install_dir.path(),
+                Path::new("package.deb"),
+                &[],
+                &HashSet::new(),
+            )
+            .await
+            .unwrap()
+        });
+        assert_eq!(
+            setid_files,
+            vec![
+                PathBuf::from("usr/bin/sudo"),
+                PathBuf::from("usr/bin/crontab")
+            ]
+        );
+        for file in ["usr/bin/sudo", "usr/bin/crontab", "usr/bin/curl"] {
+            let metadata = std::fs::metadata(install_dir.path().join(file)).unwrap();
+            assert_eq!(metadata.permissions().mode() & 0o7777, 0o755);
+        }
+    }
+
+    #[test]
+    fn is_included_path_matches_glob_patterns() {
+        let include_paths = [
+            "usr/lib/chromium/**".to_string(),
+            "usr/bin/wkhtml*".to_string(),
+            "etc/fonts/conf.d/??-*.conf".to_string(),
+        ];
+        for path in [
+            "./usr/lib/chromium",
+            "./usr/lib/chromium/libffmpeg.so",
+            "./usr/lib/chromium/plugins/libwidevine.so",
+            "/usr/bin/wkhtmltopdf",
+            "./etc/fonts/conf.d/10-hinting.conf",
+        ] {
+            assert!(
+                is_included_path(&include_paths, Path::new(path)),
+                "{path} should be included"
+            );
+        }
+        for path in [
+            "./usr/lib",
+            "./usr/lib/chromium-browser/chrome",
+            "./usr/bin/wkhtml/wkhtmltopdf",
+            "./etc/fonts/conf.d/100-hinting.conf",
+            "./usr/share/doc/chromium/copyright",
+        ] {
+            assert!(
+                !is_included_path(&include_paths, Path::new(path)),
+                "{path} should not be included"
+            );
+        }
+    }
+
+    #[test]
+    fn get_layer_size_counts_hard_links_once() {
+        let install_dir = tempfile::tempdir().unwrap();
+        let install_path = install_dir.path();
+        std::fs::create_dir_all(install_path.join("usr/bin")).unwrap();
+        std::fs::write(install_path.join("usr/bin/program"), [0; 1000]).unwrap();
+        std::fs::hard_link(
+            install_path.join("usr/bin/program"),
+            install_path.join("usr/bin/program-alias"),
+        )
+        .unwrap();
+        std::fs::write(install_path.join("usr/bin/script"), [0; 24]).unwrap();
+        assert_eq!(get_layer_size(install_path), 1024);
+    }
+
+    #[test]
+    fn calculate_sha256_of_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("package.deb");
+        std::fs::write(&path, "hello").unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert_eq!(
+            runtime.block_on(calculate_sha256(&path)).unwrap(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    // answers every request with the same body
+    #[derive(Clone)]
+    struct StubFetcher(&'static str);
+
+    #[async_trait::async_trait]
+    impl Fetcher for StubFetcher {
+        async fn fetch(
+            &self,
+            _url: &str,
+            _headers: HeaderMap,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            let response = http::Response::builder()
+                .status(http::StatusCode::OK)
+                .body(self.0)
+                .expect("Response should be valid");
+            Ok(reqwest::Response::from(response))
+        }
+    }
+
+    #[test]
+    fn download_hashes_the_written_package() {
+        let download_dir = tempfile::tempdir().unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let (download_path, calculated_hash) = runtime
+            .block_on(download(
+                StubFetcher("hello"),
+                DownloadTask::Url(Box::new(
+                    DownloadUrl::from_str("https://example.com/hello.deb").unwrap(),
+                )),
+                download_dir.path().to_path_buf(),
+            ))
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&download_path).unwrap(), "hello");
+        assert_eq!(
+            calculated_hash,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    // answers like the GitHub API for a release with a single asset, which redirects to the CDN
+    #[derive(Default)]
+    struct StubGithub {
+        requests: std::sync::Mutex<Vec<(String, HeaderMap)>>,
+    }
+
+    #[async_trait::async_trait]
+    impl reqwest_middleware::Middleware for StubGithub {
+        async fn handle(
+            &self,
+            req: reqwest::Request,
+            _extensions: &mut http::Extensions,
+            _next: reqwest_middleware::Next<'_>,
+        ) -> reqwest_middleware::Result<reqwest::Response> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((req.url().to_string(), req.headers().clone()));
+            let response = match req.url().as_str() {
+                "https://api.github.com/repos/owner/repo/releases/tags/v1.2.3" => {
+                    http::Response::builder().status(http::StatusCode::OK).body(
+                        r#"{ "assets": [{ "id": 7, "name": "other.deb" }, { "id": 42, "name": "package.deb" }] }"#,
+                    )
+                }
+                "https://api.github.com/repos/owner/repo/releases/assets/42" => {
+                    http::Response::builder()
+                        .status(http::StatusCode::FOUND)
+                        .header(
+                            http::header::LOCATION,
+                            "https://objects.githubusercontent.com/release-asset/42?sig=abc",
+                        )
+                        .body("")
+                }
+                "https://objects.githubusercontent.com/release-asset/42?sig=abc" => {
+                    http::Response::builder()
+                        .status(http::StatusCode::OK)
+                        .body("hello")
+                }
+                _ => http::Response::builder()
+                    .status(http::StatusCode::NOT_FOUND)
+                    .body(""),
+            };
+            Ok(reqwest::Response::from(response.unwrap()))
+        }
+    }
+
+    #[test]
+    fn download_url_request_for_github_release_asset_uses_the_api() {
+        rustls::crypto::ring::default_provider()
+            .install_default()
+            .ok();
+        let stub_github = Arc::new(StubGithub::default());
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(crate::redirect::RedirectMiddleware)
+            .with_arc(stub_github.clone())
+            .build();
+        let download_url = DownloadUrl::from_str(
+            "https://github.com/owner/repo/releases/download/v1.2.3/package.deb",
+        )
+        .unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let body = runtime.block_on(async {
+            let (url, headers) =
+                download_url_request(&client, &download_url, Some("secret".to_string()))
+                    .await
+                    .unwrap();
+            client
+                .fetch(&url, headers)
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap()
+        });
+
+        assert_eq!(body, "hello");
+        let requests = stub_github.requests.lock().unwrap();
+        assert_eq!(
+            requests
+                .iter()
+                .map(|(url, _)| url.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://api.github.com/repos/owner/repo/releases/tags/v1.2.3",
+                "https://api.github.com/repos/owner/repo/releases/assets/42",
+                "https://objects.githubusercontent.com/release-asset/42?sig=abc",
+            ]
+        );
+        assert_eq!(requests[1].1[AUTHORIZATION], "Bearer secret");
+        assert_eq!(requests[1].1[ACCEPT], "application/octet-stream");
+        assert!(!requests[2].1.contains_key(AUTHORIZATION));
+    }
+
+    #[test]
+    fn download_url_request_for_missing_github_release_asset() {
+        rustls::crypto::ring::default_provider()
+            .install_default()
+            .ok();
+        let client = reqwest_middleware::ClientBuilder::new(reqwest::Client::new())
+            .with(StubGithub::default())
+            .build();
+        let download_url = DownloadUrl::from_str(
+            "https://github.com/owner/repo/releases/download/v1.2.3/missing.deb",
+        )
+        .unwrap();
+        let error = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(download_url_request(
+                &client,
+                &download_url,
+                Some("secret".to_string()),
+            ))
+            .unwrap_err();
+        match error {
+            libcnb::Error::BuildpackError(DebianPackagesBuildpackError::InstallPackages(error)) => {
+                match *error {
+                    InstallPackagesError::ResolveGithubReleaseAsset(_, reason) => {
+                        assert_eq!(reason, "release `v1.2.3` has no asset named `missing.deb`");
+                    }
+                    e => panic!("Unexpected error: {e:?}"),
+                }
+            }
+            e => panic!("Unexpected error: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn download_url_request_for_github_release_asset_without_token() {
+        let download_url = DownloadUrl::from_str(
+            "https://github.com/owner/repo/releases/download/v1.2.3/package.deb",
+        )
+        .unwrap();
+        let (url, headers) = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(download_url_request(
+                &StubFetcher("unused"),
+                &download_url,
+                None,
+            ))
+            .unwrap();
+        assert_eq!(
+            url,
+            "https://github.com/owner/repo/releases/download/v1.2.3/package.deb"
+        );
+        assert!(headers.is_empty());
+    }
+
+    #[test]
+    fn build_download_url_encodes_epoch_in_filename() {
+        let mut repository_package = create_repository_package()
+            .repository_uri("http://archive.ubuntu.com/ubuntu")
+            .name("curl")
+            .version("1:8.5.0-2ubuntu10")
+            .filename("pool/main/c/curl/curl_8.5.0-2ubuntu10_amd64.deb")
+            .call();
+        assert_eq!(
+            build_download_url(&repository_package),
+            "http://archive.ubuntu.com/ubuntu/pool/main/c/curl/curl_8.5.0-2ubuntu10_amd64.deb"
+        );
+
+        repository_package.filename =
+            "pool/main/c/curl/curl_1:8.5.0-2ubuntu10_amd64.deb".to_string();
+        assert_eq!(
+            build_download_url(&repository_package),
+            "http://archive.ubuntu.com/ubuntu/pool/main/c/curl/curl_1%3a8.5.0-2ubuntu10_amd64.deb"
+        );
+    }
 
     #[test]
     fn configure_layer_environment_adds_nested_directories_with_shared_libraries_to_library_path() {
@@ -623,7 +3105,14 @@ mod test {
             "usr/not-a-lib-dir/shared-library.so.6"
         ]);
         let install_path = install_dir.path();
-        let layer_env = configure_layer_environment(install_path, &arch);
+        let layer_env = configure_layer_environment(
+            install_path,
+            &arch,
+            &[],
+            &BTreeSet::new(),
+            &BTreeSet::new(),
+            &BTreeMap::new(),
+        );
         assert_eq!(
             split_into_paths(layer_env.apply_to_empty(Scope::All).get("LD_LIBRARY_PATH")),
             vec![
@@ -642,6 +3131,37 @@ mod test {
         );
     }
 
+    #[test]
+    fn configure_layer_environment_adds_installed_foreign_architectures_to_library_path() {
+        let arch = MultiarchName::X86_64_LINUX_GNU;
+        let foreign_arch = MultiarchName::I_386_LINUX_GNU;
+        let install_dir = create_installation(bon::vec![
+            format!("usr/lib/{arch}/shared-library.so.1"),
+            format!("usr/lib/{foreign_arch}/shared-library.so.1"),
+        ]);
+        let install_path = install_dir.path();
+        let layer_env = configure_layer_environment(
+            install_path,
+            &arch,
+            std::slice::from_ref(&foreign_arch),
+            &BTreeSet::new(),
+            &BTreeSet::new(),
+            &BTreeMap::new(),
+        );
+        assert_eq!(
+            split_into_paths(layer_env.apply_to_empty(Scope::All).get("LD_LIBRARY_PATH")),
+            vec![
+                install_path.join(format!("usr/local/lib/{arch}")),
+                install_path.join("usr/local/lib"),
+                install_path.join(format!("usr/lib/{arch}")),
+                install_path.join(format!("usr/lib/{foreign_arch}")),
+                install_path.join("usr/lib"),
+                install_path.join(format!("lib/{arch}")),
+                install_path.join("lib"),
+            ]
+        );
+    }
+
     #[test]
     fn configure_layer_environment_adds_nested_directories_with_headers_to_include_path() {
         let arch = MultiarchName::X86_64_LINUX_GNU;
@@ -653,7 +3173,14 @@ mod test {
             "usr/not-an-include-dir/header.h"
         ]);
         let install_path = install_dir.path();
-        let layer_env = configure_layer_environment(install_path, &arch);
+        let layer_env = configure_layer_environment(
+            install_path,
+            &arch,
+            &[],
+            &BTreeSet::new(),
+            &BTreeSet::new(),
+            &BTreeMap::new(),
+        );
         assert_eq!(
             split_into_paths(layer_env.apply_to_empty(Scope::All).get("INCLUDE_PATH")),
             vec![
@@ -668,6 +3195,271 @@ mod test {
         );
     }
 
+    #[test]
+    fn configure_layer_environment_adds_bin_directories_from_prefixes_to_path() {
+        let arch = MultiarchName::X86_64_LINUX_GNU;
+        let install_dir = create_installation(bon::vec![
+            "opt/wkhtmltopdf/usr/local/bin/wkhtmltopdf",
+            "opt/wkhtmltopdf/usr/local/lib/libwkhtmltox.so",
+        ]);
+        let install_path = install_dir.path();
+        let layer_env = configure_layer_environment(
+            install_path,
+            &arch,
+            &[],
+            &BTreeSet::from(["opt/wkhtmltopdf".to_string()]),
+            &BTreeSet::new(),
+            &BTreeMap::new(),
+        );
+        let env = layer_env.apply_to_empty(Scope::All);
+        assert_eq!(
+            env.get(PACKAGES_LAYER_ENV),
+            Some(&OsString::from(install_path))
+        );
+        assert_eq!(
+            split_into_paths(env.get("PATH")),
+            vec![
+                install_path.join("bin"),
+                install_path.join("usr/bin"),
+                install_path.join("usr/sbin"),
+                install_path.join("usr/local/bin"),
+                install_path.join("usr/local/sbin"),
+                install_path.join("opt/wkhtmltopdf/usr/local/bin"),
+            ]
+        );
+        assert!(
+            !split_into_paths(env.get("LD_LIBRARY_PATH"))
+                .contains(&install_path.join("opt/wkhtmltopdf/usr/local/lib"))
+        );
+    }
+
+    #[test]
+    fn configure_layer_environment_adds_launch_only_directories_at_launch() {
+        let arch = MultiarchName::X86_64_LINUX_GNU;
+        let install_dir = create_installation(bon::vec![
+            "usr/bin/curl",
+            "launch-only/usr/bin/openssl",
+            format!("launch-only/usr/lib/{arch}/libssl.so.3"),
+            format!("launch-only/usr/include/{arch}/openssl/opensslconf.h"),
+        ]);
+        let install_path = install_dir.path();
+        let layer_env = configure_layer_environment(
+            install_path,
+            &arch,
+            &[],
+            &BTreeSet::new(),
+            &BTreeSet::from(["launch-only".to_string()]),
+            &BTreeMap::new(),
+        );
+
+        let build_env = layer_env.apply_to_empty(Scope::Build);
+        for name in ["PATH", "LD_LIBRARY_PATH", "LIBRARY_PATH", "CPATH"] {
+            assert!(
+                !split_into_paths(build_env.get(name))
+                    .iter()
+                    .any(|path| path.starts_with(install_path.join("launch-only"))),
+                "{name} should not contain launch-only directories at build"
+            );
+        }
+
+        let launch_env = layer_env.apply_to_empty(Scope::Launch);
+        assert!(
+            split_into_paths(launch_env.get("PATH"))
+                .contains(&install_path.join("launch-only/usr/bin"))
+        );
+        assert!(
+            split_into_paths(launch_env.get("LD_LIBRARY_PATH"))
+                .contains(&install_path.join(format!("launch-only/usr/lib/{arch}")))
+        );
+        assert!(
+            !split_into_paths(launch_env.get("CPATH"))
+                .iter()
+                .any(|path| path.starts_with(install_path.join("launch-only")))
+        );
+    }
+
+    #[test]
+    fn configure_layer_environment_appends_directories_with_env_order() {
+        let arch = MultiarchName::X86_64_LINUX_GNU;
+        let install_dir = create_installation(bon::vec![
+            "usr/bin/curl",
+            "launch-only/usr/bin/openssl",
+            format!("usr/lib/{arch}/libcurl.so.4"),
+        ]);
+        let install_path = install_dir.path();
+        let layer_env = configure_layer_environment(
+            install_path,
+            &arch,
+            &[],
+            &BTreeSet::new(),
+            &BTreeSet::from(["launch-only".to_string()]),
+            &BTreeMap::from([
+                ("PATH".to_string(), EnvOrder::Append),
+                ("LD_LIBRARY_PATH".to_string(), EnvOrder::Prepend),
+            ]),
+        );
+
+        let mut env = Env::new();
+        env.insert("PATH", "/usr/bin");
+        env.insert("LD_LIBRARY_PATH", "/usr/lib");
+        let launch_env = layer_env.apply(Scope::Launch, &env);
+
+        let path = split_into_paths(launch_env.get("PATH"));
+        assert_eq!(path.first(), Some(&PathBuf::from("/usr/bin")));
+        assert!(path.contains(&install_path.join("usr/bin")));
+        assert!(path.contains(&install_path.join("launch-only/usr/bin")));
+
+        let library_path = split_into_paths(launch_env.get("LD_LIBRARY_PATH"));
+        assert_eq!(library_path.last(), Some(&PathBuf::from("/usr/lib")));
+        assert!(library_path.contains(&install_path.join(format!("usr/lib/{arch}"))));
+    }
+
+    #[test]
+    fn extract_options_install_prefix_for_launch_only_packages() {
+        let extract_options = |prefix: Option<&str>, launch_only| ExtractOptions {
+            include_paths: vec![],
+            prefix: prefix.map(ToString::to_string),
+            launch_only,
+        };
+        assert_eq!(extract_options(None, false).install_prefix(), None);
+        assert_eq!(
+            extract_options(Some("opt/wkhtmltopdf"), false).install_prefix(),
+            Some("opt/wkhtmltopdf".to_string())
+        );
+        assert_eq!(
+            extract_options(None, true).install_prefix(),
+            Some("launch-only".to_string())
+        );
+        assert_eq!(
+            extract_options(Some("opt/wkhtmltopdf"), true).install_prefix(),
+            Some("launch-only/opt/wkhtmltopdf".to_string())
+        );
+    }
+
+    #[test]
+    fn installation_metadata_changes_reports_package_changes() {
+        let old = installation_metadata(&[
+            ("curl", "8.5.0", "curl-sha256"),
+            ("libcurl4", "8.5.0", "libcurl4-sha256"),
+            ("wget", "1.21", "wget-sha256"),
+        ]);
+        let new = InstallationMetadata {
+            download_urls: vec!["https://example.com/package.deb".to_string()],
+            cache_key: Some("v2".to_string()),
+            strip_binaries: true,
+            prune: vec![PruneTarget::StaticLibs],
+            hooks: HooksConfig {
+                pre_install: None,
+                post_install: Some("bash scripts/fix-links.sh".to_string()),
+            },
+            extract_options: BTreeMap::from([(
+                "chromium-codecs-ffmpeg-extra".to_string(),
+                ExtractOptions {
+                    include_paths: vec!["usr/lib/chromium/**".to_string()],
+                    prefix: None,
+                    launch_only: false,
+                },
+            )]),
+            ..installation_metadata(&[
+                ("curl", "8.5.1", "curl-new-sha256"),
+                ("libcurl4", "8.5.0", "libcurl4-sha256"),
+                ("libssl3", "3.0.13", "libssl3-sha256"),
+            ])
+        };
+        assert_eq!(
+            old.changes(&new),
+            vec![
+                InstallationChange::UpdatedPackage {
+                    name: "curl".to_string(),
+                    old_version: Some("8.5.0".to_string()),
+                    new_version: Some("8.5.1".to_string()),
+                },
+                InstallationChange::AddedPackage {
+                    name: "libssl3".to_string(),
+                    version: Some("3.0.13".to_string()),
+                },
+                InstallationChange::RemovedPackage {
+                    name: "wget".to_string(),
+                    version: Some("1.21".to_string()),
+                },
+                InstallationChange::AddedDownloadUrl("https://example.com/package.deb".to_string()),
+                InstallationChange::CacheKey,
+                InstallationChange::StripBinaries(true),
+                InstallationChange::Prune,
+                InstallationChange::ExtractOptions,
+                InstallationChange::Hooks,
+            ]
+        );
+    }
+
+    #[test]
+    fn installation_metadata_changes_ignores_versions_missing_from_older_metadata() {
+        let new = installation_metadata(&[("curl", "8.5.0", "curl-sha256")]);
+        let old = InstallationMetadata {
+            package_versions: HashMap::new(),
+            ..new.clone()
+        };
+        assert_eq!(old.changes(&new), vec![]);
+    }
+
+    #[test]
+    fn installation_metadata_changes_ignores_resolution() {
+        let old = installation_metadata(&[("curl", "8.5.0", "curl-sha256")]);
+        let new = InstallationMetadata {
+            resolution: Some(StoredResolution::new("fingerprint".to_string(), &[])),
+            ..old.clone()
+        };
+        assert_eq!(old.changes(&new), vec![]);
+    }
+
+    #[test]
+    fn installation_metadata_changes_reports_architecture_separately_from_distro() {
+        let old = installation_metadata(&[("curl", "8.5.0", "curl-sha256")]);
+        let mut new = old.clone();
+        new.distro.architecture = ArchitectureName::ARM_64;
+        let changes = old.changes(&new);
+        assert_eq!(
+            changes,
+            vec![InstallationChange::Architecture {
+                old: ArchitectureName::AMD_64,
+                new: ArchitectureName::ARM_64,
+            }]
+        );
+        assert_eq!(
+            changes
+                .iter()
+                .map(InstallationChange::field)
+                .collect::<Vec<_>>(),
+            vec!["architecture"]
+        );
+    }
+
+    fn installation_metadata(packages: &[(&str, &str, &str)]) -> InstallationMetadata {
+        InstallationMetadata {
+            package_checksums: packages
+                .iter()
+                .map(|(name, _, checksum)| ((*name).to_string(), (*checksum).to_string()))
+                .collect(),
+            package_versions: packages
+                .iter()
+                .map(|(name, version, _)| ((*name).to_string(), (*version).to_string()))
+                .collect(),
+            distro: Distro {
+                name: "ubuntu".to_string(),
+                version: "24.04".to_string(),
+                codename: DistroCodename::Noble,
+                architecture: ArchitectureName::AMD_64,
+            },
+            download_urls: vec![],
+            cache_key: None,
+            strip_binaries: false,
+            prune: vec![],
+            extract_options: BTreeMap::new(),
+            hooks: HooksConfig::default(),
+            resolution: None,
+        }
+    }
+
     fn create_installation(files: Vec<String>) -> TempDir {
         let install_dir = tempfile::tempdir().unwrap();
         for file in files {
@@ -0,0 +1,1116 @@
+//! The package resolution and installation logic of the Heroku .deb Packages buildpack.
+//!
+//! The buildpack binary runs on top of [`run`], while [`PackageIndex`], [`resolve_packages`], and
+//! [`extract_deb`] let other buildpacks and tools resolve and unpack packages the same way without
+//! running a build.
+
+use crate::build_report::{create_build_report, create_launch_labels};
+use crate::check_security_advisories::{CheckSecurityAdvisoriesError, check_security_advisories};
+use crate::cli::CliError;
+use crate::client_identity::{ClientIdentityError, ClientIdentityMiddleware};
+use crate::config::custom_source::CustomSource;
+use crate::config::http_config::{HttpConfig, RootCertificates};
+use crate::config::{
+    BuildpackConfig, ConfigError, NAMESPACED_CONFIG, RequestedPackage, STANDALONE_CONFIG_FILE,
+};
+use crate::contents_index::{ContentsIndexError, add_library_packages, find_library_packages};
+use crate::create_package_index::{CreatePackageIndexError, create_package_index};
+use crate::debian::{Distro, Source, UnsupportedDistroError};
+use crate::determine_packages_to_install::{
+    DPKG_STATUS_PATH, DeterminePackagesToInstallError, check_essential_packages,
+    determine_packages_to_install,
+};
+use crate::hooks::HookError;
+use crate::host_credentials::{HostCredentialsError, HostCredentialsMiddleware};
+use crate::install_packages::{
+    ExtractOptions, InstallOptions, InstallPackagesError, install_packages, read_stored_resolution,
+};
+use crate::install_plan::InstallPlan;
+use crate::local_repository::{LocalRepositoryMiddleware, to_local_repository_url};
+use crate::lockfile::{LOCKFILE_NAME, LockedTarget, Lockfile, LockfileError};
+use crate::o11y::*;
+use crate::redirect::RedirectMiddleware;
+use crate::retry::HostRetryMiddleware;
+use crate::s3::{S3Error, S3Middleware};
+use crate::stored_resolution::{StoredResolution, resolution_fingerprint};
+use crate::vendor::{VENDOR_DIR, VendorError, VendorManifest, requested_from_config};
+use bullet_stream::{global::print, style};
+use indexmap::IndexSet;
+use indoc::formatdoc;
+use libcnb::build::{BuildContext, BuildResult, BuildResultBuilder};
+use libcnb::detect::{DetectContext, DetectResult, DetectResultBuilder};
+use libcnb::generic::{GenericMetadata, GenericPlatform};
+use libcnb::{Buildpack, Env, Platform};
+#[cfg(test)]
+use libcnb_test as _;
+#[cfg(test)]
+use regex as _;
+use reqwest::redirect::Policy;
+use reqwest::{Certificate, Client};
+use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
+use reqwest_tracing::{SpanBackendWithUrl, TracingMiddleware};
+use rustls::crypto::CryptoProvider;
+use rustls::crypto::ring::default_provider;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tracing::{error, info};
+
+pub use crate::api::{Error, Package, PackageIndex, extract_deb, resolve_packages};
+
+mod api;
+mod build_report;
+mod check_security_advisories;
+mod cli;
+mod client_identity;
+mod config;
+mod contents_index;
+mod create_package_index;
+mod debian;
+mod determine_packages_to_install;
+mod download_cache;
+mod errors;
+mod fetcher;
+mod file_conflicts;
+mod glibc_requirements;
+mod hooks;
+mod host_credentials;
+mod install_packages;
+mod install_plan;
+mod local_repository;
+mod lockfile;
+mod missing_shared_libraries;
+mod netrc;
+mod normalize_timestamps;
+mod o11y;
+mod package_manifest;
+mod package_signatures;
+mod pgp;
+mod prune_files;
+mod redirect;
+mod retry;
+mod s3;
+mod stored_resolution;
+mod strip_binaries;
+mod vendor;
+
+/// Runs the buildpack when invoked by the CNB lifecycle (as `bin/detect` or `bin/build`), and the
+/// `deb-packages` command line tools otherwise.
+#[doc(hidden)]
+#[must_use]
+pub fn run() -> ExitCode {
+    if cli::is_buildpack_invocation() {
+        libcnb::libcnb_runtime(&DebianPackagesBuildpack);
+        ExitCode::SUCCESS
+    } else {
+        cli::run()
+    }
+}
+
+type BuildpackResult<T> = Result<T, libcnb::Error<DebianPackagesBuildpackError>>;
+
+struct DebianPackagesBuildpack;
+
+impl Buildpack for DebianPackagesBuildpack {
+    type Platform = GenericPlatform;
+    type Metadata = GenericMetadata;
+    type Error = DebianPackagesBuildpackError;
+
+    fn detect(&self, context: DetectContext<Self>) -> libcnb::Result<DetectResult, Self::Error> {
+        if get_standalone_config_file(&context.app_dir)?.is_some() {
+            info!({ STANDALONE_CONFIG_FILE_DETECTED } = true);
+            DetectResultBuilder::pass().build()
+        } else if let Some(project_toml) = get_project_toml(&context.app_dir)? {
+            info!({ PROJECT_TOML_DETECTED } = true);
+            if BuildpackConfig::is_present(project_toml)? {
+                DetectResultBuilder::pass().build()
+            } else if get_env_install().is_some() {
+                info!({ ENV_INSTALL_DETECTED } = true);
+                DetectResultBuilder::pass().build()
+            } else {
+                print::plain(
+                    "project.toml found, but no [com.heroku.buildpacks.deb-packages] configuration present.",
+                );
+                info!({ PROJECT_TOML_NO_CONFIG } = true);
+                DetectResultBuilder::fail().build()
+            }
+        } else if get_env_install().is_some() {
+            info!({ ENV_INSTALL_DETECTED } = true);
+            DetectResultBuilder::pass().build()
+        } else if get_aptfile(&context.app_dir)?.is_some() {
+            // NOTE: This buildpack doesn't use an Aptfile, but we'll pass detection to display a message
+            //       to users in the build step detailing how to migrate away from the Aptfile format.
+            info!({ APTFILE_DETECTED } = true);
+            DetectResultBuilder::pass().build()
+        } else {
+            print::plain(format!(
+                "No {STANDALONE_CONFIG_FILE}, project.toml, or Aptfile found and BP_DEB_PACKAGES_INSTALL isn't set."
+            ));
+            DetectResultBuilder::fail().build()
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn build(&self, context: BuildContext<Self>) -> libcnb::Result<BuildResult, Self::Error> {
+        // This buildpack does a lot of async work, so the context needs to be sharable
+        // across async boundaries.
+        let context = Arc::new(context);
+
+        let runtime = create_async_runtime();
+
+        let started = print::buildpack(format!(
+            "{buildpack_name} (v{buildpack_version})",
+            buildpack_name = context
+                .buildpack_descriptor
+                .buildpack
+                .name
+                .as_ref()
+                .expect("buildpack name should be set"),
+            buildpack_version = context.buildpack_descriptor.buildpack.version
+        ));
+
+        if get_aptfile(&context.app_dir)?.is_some() {
+            print::plain(style::important(migrate_from_aptfile_help_message()));
+            // If we passed detect from the Aptfile but there is no configuration file or packages
+            // requested from the environment then print the warning and exit early.
+            if get_config_file(&context.app_dir)?.is_none() && get_env_install().is_none() {
+                info!({ EARLY_EXIT_REASON } = "migrate_aptfile", "early exit");
+                return BuildResultBuilder::new().build();
+            }
+        }
+
+        let config_file = get_config_file(&context.app_dir)?;
+
+        if let Some(config_file) = &config_file
+            && config_file.ends_with(STANDALONE_CONFIG_FILE)
+            && let Some(project_toml) = get_project_toml(&context.app_dir)?
+            && BuildpackConfig::is_present(project_toml)?
+        {
+            print::warning(format!(
+                "Configuration was found in both {standalone_config_file} and {project_toml}. Only {standalone_config_file} will be used.",
+                standalone_config_file = style::value(STANDALONE_CONFIG_FILE),
+                project_toml = style::value("project.toml"),
+            ));
+        }
+
+        // packages can be requested from the environment without any configuration file (or a
+        // project.toml that doesn't configure this buildpack)
+        let mut config = match config_file {
+            Some(config_file) if BuildpackConfig::is_present(config_file.clone())? => {
+                let config = BuildpackConfig::try_from(config_file.clone())?;
+                print_unknown_keys_warning(&config_file, &config);
+                config
+            }
+            _ => BuildpackConfig::default(),
+        };
+        print_deprecated_keys_warning(&config);
+
+        if let Some(profile_name) = get_env_profile() {
+            config.apply_profile(&profile_name)?;
+        }
+
+        if let Some(env_install) = get_env_install() {
+            config.add_env_install(&env_install)?;
+        }
+
+        if config.install.is_empty() && config.download.is_empty() {
+            info!({ EARLY_EXIT_REASON } = "nothing_to_install", "early exit");
+
+            print::plain(style::important(empty_config_help_message()));
+            return BuildResultBuilder::new().build();
+        }
+
+        let distro = Distro::try_from(&context.target)?;
+
+        let source_list = create_source_list(&distro, &config);
+
+        let client = create_http_client(
+            &config.http,
+            &config.sources,
+            context.platform.env(),
+            &user_agent(&context.buildpack_descriptor.buildpack.version, &distro),
+        )?;
+
+        info!(
+            { DISTRO_NAME } = %distro.name,
+            { DISTRO_VERSION } =  %distro.version,
+            { DISTRO_CODENAME } = %distro.codename,
+            { DISTRO_ARCH } = %distro.architecture,
+            { SOURCE_LIST } = as_json_value(&source_list),
+            { CONFIG_INSTALL } = as_json_value(&config.install.iter().collect::<Vec<_>>()),
+            { CONFIG_DOWNLOAD } = as_json_value(&config.download.iter().map(ToString::to_string).collect::<Vec<_>>()),
+            "configuration"
+        );
+
+        print_distro_info(&distro);
+        print_proposed_suites_warning(&source_list);
+
+        // a cache key from the environment takes precedence over the one from project.toml
+        let cache_key = Env::from_current()
+            .get_string_lossy("BP_DEB_PACKAGES_CACHE_KEY")
+            .or_else(|| config.cache_key.clone());
+
+        let extract_options = ExtractOptions::from_requested_packages(&config.install);
+
+        let vendored_target = VendorManifest::read(&context.app_dir.join(VENDOR_DIR))?
+            .and_then(|manifest| manifest.get_target(&distro).cloned());
+
+        let requested_packages = config
+            .install
+            .iter()
+            .map(RequestedPackage::qualified_name)
+            .collect::<Vec<_>>();
+
+        // kept for resolving the packages again when `auto_resolve_libs` adds packages
+        let requested_install = config.install.clone();
+        let mut locked_versions = HashMap::new();
+        let mut resolved_package_index = None;
+
+        let (packages_to_install, packages_to_download, vendored_packages, provenance, resolution) =
+            if let Some(vendored_target) = vendored_target {
+                info!({ VENDORED_PACKAGES_DETECTED } = true);
+                print::bullet(format!(
+                    "Using vendored packages from {vendor_dir}",
+                    vendor_dir = style::value(VENDOR_DIR)
+                ));
+                if vendored_target.requested != requested_from_config(&config) {
+                    print::warning(vendored_config_changed_help_message());
+                }
+                (
+                    vec![],
+                    IndexSet::new(),
+                    vendored_target.packages,
+                    HashMap::new(),
+                    None,
+                )
+            } else {
+                let locked_target = Lockfile::read(&context.app_dir.join(LOCKFILE_NAME))?
+                    .and_then(|lockfile| lockfile.get_target(&distro).cloned());
+                locked_versions = locked_target
+                    .as_ref()
+                    .map(LockedTarget::locked_versions)
+                    .unwrap_or_default();
+
+                let fingerprint = resolution_fingerprint(
+                    &distro,
+                    &source_list,
+                    &config,
+                    locked_target.as_ref(),
+                    &std::fs::read(DPKG_STATUS_PATH).unwrap_or_default(),
+                );
+
+                // security advisories are checked against the resolved packages on every build
+                let reusable_resolution = read_stored_resolution(&context)
+                    .filter(|stored_resolution| {
+                        config.security_advisories.is_none()
+                            && !is_clean_cache_requested()
+                            && !is_refresh_index_requested()
+                            && stored_resolution.is_reusable(
+                                &fingerprint,
+                                locked_target.is_some(),
+                                config.index_max_age,
+                            )
+                    })
+                    .and_then(|stored_resolution| {
+                        stored_resolution
+                            .to_repository_packages(&source_list)
+                            .map(|packages_to_install| (stored_resolution, packages_to_install))
+                    });
+
+                if let Some((stored_resolution, packages_to_install)) = reusable_resolution {
+                    info!({ STORED_RESOLUTION_REUSED } = true);
+                    print::bullet(if locked_target.is_some() {
+                        format!(
+                            "Reusing the packages resolved by the previous build since the configuration and {lockfile} haven't changed",
+                            lockfile = style::value(LOCKFILE_NAME)
+                        )
+                    } else {
+                        format!(
+                            "Reusing the packages resolved by the previous build since the configuration hasn't changed and the resolution is within the {index_max_age}",
+                            index_max_age = style::value("index_max_age")
+                        )
+                    });
+                    (
+                        packages_to_install,
+                        config.download,
+                        vec![],
+                        HashMap::new(),
+                        Some(stored_resolution),
+                    )
+                } else {
+                    let package_index = runtime.block_on(create_package_index(
+                        &context,
+                        &client,
+                        &source_list,
+                        config.index_max_age,
+                        config.skip_phased_updates,
+                    ))?;
+
+                    let (packages_to_install, provenance) = determine_packages_to_install(
+                        &package_index,
+                        &source_list,
+                        config.install,
+                        &locked_versions,
+                        &config.prefer_alternatives,
+                        &config.pins,
+                    )?;
+
+                    check_essential_packages(&packages_to_install, &config.essential_packages)?;
+
+                    if let Some(mode) = &config.security_advisories {
+                        runtime.block_on(check_security_advisories(
+                            &client,
+                            &distro,
+                            &packages_to_install,
+                            mode,
+                        ))?;
+                    }
+
+                    let resolution = StoredResolution::new(fingerprint, &packages_to_install);
+                    resolved_package_index = Some(package_index);
+
+                    (
+                        packages_to_install,
+                        config.download,
+                        vec![],
+                        provenance,
+                        Some(resolution),
+                    )
+                }
+            };
+
+        let install_plan = InstallPlan::new(
+            requested_packages,
+            &packages_to_install,
+            &provenance,
+            &packages_to_download,
+            &vendored_packages,
+        );
+
+        let installed_packages = create_build_report(
+            &packages_to_install,
+            &packages_to_download,
+            &vendored_packages,
+        );
+
+        // packages from a source configured with `verify_package_signatures` must be signed with
+        // the same key as the source's Release file
+        let package_signing_keys = config
+            .sources
+            .iter()
+            .filter(|custom_source| custom_source.verify_package_signatures)
+            .map(|custom_source| (custom_source.uri.clone(), custom_source.signed_by.clone()))
+            .collect::<HashMap<_, _>>();
+
+        // vendored packages are installed as-is so there's no resolution to add packages to
+        let fingerprint = resolution
+            .as_ref()
+            .map(|resolution| resolution.fingerprint().to_string());
+        let resolved_packages = packages_to_install
+            .iter()
+            .map(|package| package.name.clone())
+            .collect::<HashSet<_>>();
+
+        let missing_shared_libraries = runtime.block_on(install_packages(
+            &context,
+            &client,
+            &distro,
+            packages_to_install,
+            packages_to_download.clone(),
+            vendored_packages,
+            InstallOptions {
+                cache_key: cache_key.clone(),
+                download_dir: get_download_dir(),
+                strip_binaries: config.strip_binaries,
+                prune: config.prune.clone(),
+                extract_options,
+                hooks: config.hooks.clone(),
+                env_order: config.env_order.clone(),
+                installed_packages: &installed_packages,
+                max_layer_size: config.max_layer_size,
+                max_download_cache_size: config.max_download_cache_size,
+                package_signing_keys: &package_signing_keys,
+                install_plan: &install_plan,
+                resolution,
+            },
+        ))?;
+
+        // NOTE: The packages providing missing shared libraries are added to the requested
+        //       packages which are then resolved and installed again. The new resolution is stored
+        //       with the fingerprint of the configuration so the next build reuses it and installs
+        //       everything in one pass.
+        let installed_packages = match fingerprint {
+            Some(fingerprint)
+                if config.auto_resolve_libs && !missing_shared_libraries.is_empty() =>
+            {
+                print::header("Resolving missing shared libraries");
+                let library_names = missing_shared_libraries.library_names();
+                let library_packages = runtime.block_on(find_library_packages(
+                    &context,
+                    &client,
+                    &source_list,
+                    &library_names,
+                    config.index_max_age,
+                ))?;
+
+                info!(
+                    { INSTALL_PACKAGES_AUTO_RESOLVED_LIBRARIES } = library_packages.len(),
+                    "install packages"
+                );
+                for (library, package) in &library_packages {
+                    print::sub_bullet(format!(
+                        "Adding {package} which provides {library}",
+                        package = style::value(package),
+                        library = style::value(library)
+                    ));
+                }
+                for library in library_names
+                    .iter()
+                    .filter(|library| !library_packages.contains_key(*library))
+                {
+                    print::sub_bullet(format!(
+                        "No package provides {library}",
+                        library = style::value(library)
+                    ));
+                }
+
+                let install = add_library_packages(&requested_install, &library_packages);
+                if install.len() == requested_install.len() {
+                    installed_packages
+                } else {
+                    let package_index = match resolved_package_index {
+                        Some(package_index) => package_index,
+                        None => runtime.block_on(create_package_index(
+                            &context,
+                            &client,
+                            &source_list,
+                            config.index_max_age,
+                            config.skip_phased_updates,
+                        ))?,
+                    };
+
+                    let requested_packages = install
+                        .iter()
+                        .map(RequestedPackage::qualified_name)
+                        .collect::<Vec<_>>();
+                    let extract_options = ExtractOptions::from_requested_packages(&install);
+
+                    let (packages_to_install, provenance) = determine_packages_to_install(
+                        &package_index,
+                        &source_list,
+                        install,
+                        &locked_versions,
+                        &config.prefer_alternatives,
+                        &config.pins,
+                    )?;
+
+                    check_essential_packages(&packages_to_install, &config.essential_packages)?;
+
+                    if let Some(mode) = &config.security_advisories {
+                        let added_packages = packages_to_install
+                            .iter()
+                            .filter(|package| !resolved_packages.contains(&package.name))
+                            .cloned()
+                            .collect::<Vec<_>>();
+                        runtime.block_on(check_security_advisories(
+                            &client,
+                            &distro,
+                            &added_packages,
+                            mode,
+                        ))?;
+                    }
+
+                    let resolution = StoredResolution::new(fingerprint, &packages_to_install);
+
+                    let install_plan = InstallPlan::new(
+                        requested_packages,
+                        &packages_to_install,
+                        &provenance,
+                        &packages_to_download,
+                        &[],
+                    );
+
+                    let installed_packages =
+                        create_build_report(&packages_to_install, &packages_to_download, &[]);
+
+                    runtime.block_on(install_packages(
+                        &context,
+                        &client,
+                        &distro,
+                        packages_to_install,
+                        packages_to_download,
+                        vec![],
+                        InstallOptions {
+                            cache_key,
+                            download_dir: get_download_dir(),
+                            strip_binaries: config.strip_binaries,
+                            prune: config.prune,
+                            extract_options,
+                            hooks: config.hooks,
+                            env_order: config.env_order,
+                            installed_packages: &installed_packages,
+                            max_layer_size: config.max_layer_size,
+                            max_download_cache_size: config.max_download_cache_size,
+                            package_signing_keys: &package_signing_keys,
+                            install_plan: &install_plan,
+                            resolution: Some(resolution),
+                        },
+                    ))?;
+
+                    installed_packages
+                }
+            }
+            _ => installed_packages,
+        };
+
+        print::all_done(&Some(started));
+
+        BuildResultBuilder::new()
+            .launch(create_launch_labels(&installed_packages))
+            .build()
+    }
+
+    fn on_error(&self, error: libcnb::Error<Self::Error>) {
+        error!({ ERROR } = ?error);
+        errors::on_error(error);
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum DebianPackagesBuildpackError {
+    Config(ConfigError),
+    UnsupportedDistro(UnsupportedDistroError),
+    CreatePackageIndex(CreatePackageIndexError),
+    ContentsIndex(ContentsIndexError),
+    DeterminePackagesToInstall(Box<DeterminePackagesToInstallError>),
+    InstallPackages(Box<InstallPackagesError>),
+    Detect(DetectError),
+    Cli(CliError),
+    Lockfile(LockfileError),
+    Vendor(VendorError),
+    CheckSecurityAdvisories(CheckSecurityAdvisoriesError),
+    ClientIdentity(ClientIdentityError),
+    HostCredentials(HostCredentialsError),
+    S3(S3Error),
+    Hook(HookError),
+}
+
+impl From<DebianPackagesBuildpackError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: DebianPackagesBuildpackError) -> Self {
+        Self::BuildpackError(value)
+    }
+}
+
+#[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
+pub(crate) enum DetectError {
+    CheckExistsAptfile(PathBuf, std::io::Error),
+    CheckExistsProjectToml(PathBuf, std::io::Error),
+    CheckExistsDebPackagesToml(PathBuf, std::io::Error),
+}
+
+impl From<DetectError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: DetectError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::Detect(value))
+    }
+}
+
+pub(crate) fn is_buildpack_debug_logging_enabled() -> bool {
+    Env::from_current()
+        .get("BP_LOG_LEVEL")
+        .is_some_and(|value| value.eq_ignore_ascii_case("debug"))
+}
+
+// Deletes the cached package index and packages layers even when their metadata matches. Useful
+// when cache corruption is suspected without having to clear the entire builder cache.
+pub(crate) fn is_clean_cache_requested() -> bool {
+    Env::from_current()
+        .get("BP_DEB_PACKAGES_CLEAN_CACHE")
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+// Redownloads the release files and package indices even when they are cached while leaving the
+// installed packages layer alone.
+pub(crate) fn is_refresh_index_requested() -> bool {
+    Env::from_current()
+        .get("BP_DEB_PACKAGES_REFRESH_INDEX")
+        .is_some_and(|value| value.eq_ignore_ascii_case("true"))
+}
+
+// Packages to install given as a list of package names separated by whitespace or commas. This
+// lets platform operators add packages to builds without changes to the application.
+fn get_env_install() -> Option<String> {
+    Env::from_current()
+        .get_string_lossy("BP_DEB_PACKAGES_INSTALL")
+        .filter(|value| !value.trim().is_empty())
+}
+
+// The name of a profile from the buildpack configuration whose packages are installed along with
+// the base install list (e.g.; debugging tools for review apps that production images shouldn't
+// carry).
+fn get_env_profile() -> Option<String> {
+    Env::from_current()
+        .get_string_lossy("BP_DEB_PACKAGES_PROFILE")
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// Packages are downloaded to a scratch directory before being extracted which can be moved off of
+// the default location for hosts with limited disk space there.
+pub(crate) fn get_download_dir() -> Option<PathBuf> {
+    Env::from_current()
+        .get("BP_DEB_PACKAGES_DOWNLOAD_DIR")
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+// Requests to package repositories can be routed through a caching proxy (e.g.; apt-cacher-ng)
+// which expects the repository url, without its scheme, to follow the url of the proxy (e.g.;
+// `http://apt-cache.internal:3142/archive.ubuntu.com/ubuntu/dists/noble/InRelease`).
+pub(crate) fn get_proxy_prefix() -> Option<String> {
+    Env::from_current()
+        .get_string_lossy("BP_DEB_PACKAGES_PROXY_PREFIX")
+        .map(|value| value.trim().trim_end_matches('/').to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// A token used to download release assets from private GitHub repositories listed in `download`.
+pub(crate) fn get_github_token() -> Option<String> {
+    Env::from_current()
+        .get_string_lossy("GITHUB_TOKEN")
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+// The url requested for a file from a package repository. Local repositories are answered by
+// the HTTP client without any network access and other repositories can be routed through a
+// caching proxy. Requests to S3 are signed for the bucket's endpoint so they can't be proxied.
+pub(crate) fn to_request_url(url: &str) -> String {
+    if let Some(local_repository_url) = to_local_repository_url(url) {
+        return local_repository_url;
+    }
+    apply_proxy_prefix(url, get_proxy_prefix().as_deref())
+}
+
+// Only plain HTTP repositories are proxied. Requesting an HTTPS repository through the proxy would
+// downgrade it to plain HTTP and skip the credentials and client certificates configured for its
+// host.
+fn apply_proxy_prefix(url: &str, proxy_prefix: Option<&str>) -> String {
+    match (proxy_prefix, url.strip_prefix("http://")) {
+        (Some(proxy_prefix), Some(url_without_scheme)) => {
+            format!("{proxy_prefix}/{url_without_scheme}")
+        }
+        _ => url.to_string(),
+    }
+}
+
+pub(crate) fn create_http_client(
+    http_config: &HttpConfig,
+    custom_sources: &[CustomSource],
+    platform_env: &Env,
+    user_agent: &str,
+) -> BuildpackResult<ClientWithMiddleware> {
+    // the provider can only be installed once per process and some CLI commands create more
+    // than one client
+    if CryptoProvider::get_default().is_none() {
+        default_provider()
+            .install_default()
+            .expect("Should be able to install the default rustls crypto provider");
+    }
+
+    let client_identity_middleware = ClientIdentityMiddleware::new(custom_sources, || {
+        create_client_builder(http_config, user_agent)
+    })?;
+    let s3_middleware = S3Middleware::new(custom_sources)?;
+    let local_repository_middleware = LocalRepositoryMiddleware::new(custom_sources);
+    let host_credentials_middleware = HostCredentialsMiddleware::new(platform_env)?;
+
+    let mut client_builder = ClientBuilder::new(
+        create_client_builder(http_config, user_agent)
+            .build()
+            .expect("Should be able to construct the HTTP Client"),
+    )
+    .with(RedirectMiddleware)
+    .with(HostRetryMiddleware::new(http_config))
+    .with(TracingMiddleware::<SpanBackendWithUrl>::new());
+
+    // added last so retries and tracing also apply to requests sent to S3, local repositories, or
+    // with a client certificate or host credentials, S3 requests are also signed again on every retry
+    if let Some(host_credentials_middleware) = host_credentials_middleware {
+        client_builder = client_builder.with(host_credentials_middleware);
+    }
+    if let Some(local_repository_middleware) = local_repository_middleware {
+        client_builder = client_builder.with(local_repository_middleware);
+    }
+    if let Some(s3_middleware) = s3_middleware {
+        client_builder = client_builder.with(s3_middleware);
+    }
+    if !client_identity_middleware.is_empty() {
+        client_builder = client_builder.with(client_identity_middleware);
+    }
+
+    Ok(client_builder.build())
+}
+
+// Requests identify the buildpack, and the distribution it's installing packages for, so mirror
+// operators and caching proxies can attribute traffic and apply policy to it.
+pub(crate) fn user_agent(buildpack_version: &impl Display, distro: &Distro) -> String {
+    format!(
+        "heroku-deb-packages/{buildpack_version} ({codename}; {arch})",
+        codename = distro.codename,
+        arch = distro.architecture
+    )
+}
+
+fn create_client_builder(http_config: &HttpConfig, user_agent: &str) -> reqwest::ClientBuilder {
+    let mut client_builder = Client::builder()
+        .user_agent(user_agent)
+        .use_rustls_tls()
+        .connect_timeout(Duration::from_secs(10))
+        .read_timeout(Duration::from_secs(10))
+        // redirects are followed by `RedirectMiddleware` which drops configured headers when the
+        // host changes
+        .redirect(Policy::none());
+
+    // connections are kept to HTTP/1.1 unless HTTP/2 is explicitly requested
+    client_builder = if http_config.http2_prior_knowledge {
+        client_builder.http2_prior_knowledge()
+    } else {
+        client_builder.http1_only()
+    };
+    if let Some(pool_max_idle_per_host) = http_config.pool_max_idle_per_host {
+        client_builder = client_builder.pool_max_idle_per_host(pool_max_idle_per_host);
+    }
+    if let Some(pool_idle_timeout) = http_config.pool_idle_timeout {
+        client_builder = client_builder.pool_idle_timeout(pool_idle_timeout);
+    }
+    if let Some(tcp_keepalive) = http_config.tcp_keepalive {
+        client_builder = client_builder.tcp_keepalive(tcp_keepalive);
+    }
+    if http_config.root_certificates == RootCertificates::Bundled {
+        client_builder = client_builder.tls_certs_only(
+            webpki_root_certs::TLS_SERVER_ROOT_CERTS
+                .iter()
+                .filter_map(|root_certificate| Certificate::from_der(root_certificate).ok()),
+        );
+    }
+    // the port is always taken from the request url
+    for (host, addresses) in &http_config.resolve {
+        let addresses = addresses
+            .iter()
+            .map(|address| SocketAddr::new(*address, 0))
+            .collect::<Vec<_>>();
+        client_builder = client_builder.resolve_to_addrs(host, &addresses);
+    }
+    client_builder
+}
+
+// The number of threads used for CPU-heavy work (e.g.; parsing package indices, hashing and
+// extracting packages) defaults to the number of available CPUs but can be lowered so small build
+// containers aren't overwhelmed.
+pub(crate) fn get_parallelism() -> usize {
+    Env::from_current()
+        .get_string_lossy("BP_DEB_PACKAGES_PARALLELISM")
+        .and_then(|value| value.trim().parse::<NonZeroUsize>().ok())
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+}
+
+pub(crate) fn create_async_runtime() -> Runtime {
+    let parallelism = get_parallelism();
+
+    // package indices are parsed and packages are hashed on the global rayon pool which can only
+    // be configured once, the blocking pool is left uncapped since file I/O and DNS lookups use it
+    let _ = rayon::ThreadPoolBuilder::new()
+        .num_threads(parallelism)
+        .build_global();
+
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(parallelism)
+        .enable_io()
+        .enable_time()
+        .build()
+        .expect("Should be able to construct the Async Runtime")
+}
+
+pub(crate) fn create_source_list(distro: &Distro, config: &BuildpackConfig) -> Vec<Source> {
+    // official source list from distro with any configured changes to the default suites
+    let mut source_list = config
+        .default_suites
+        .apply(&distro.codename, distro.get_source_list());
+    if let Some(default_components) = &config.default_components {
+        for source in &mut source_list {
+            source.components.clone_from(default_components);
+        }
+    }
+    if config.prefer_https {
+        for source in &mut source_list {
+            source.uri = source.uri.to_https();
+        }
+    }
+    // the Ubuntu archive keys can't be refreshed from the keyserver for a mirror that re-signs its
+    // release files with its own key
+    if let Some(signed_by) = &config.default_source_signed_by {
+        for source in &mut source_list {
+            source.signed_by.clone_from(signed_by);
+            source.keyserver_fingerprints.clear();
+        }
+    }
+
+    // package indices for foreign architectures are only needed when a package requests one
+    let foreign_arches = distro
+        .architecture
+        .get_foreign_architectures()
+        .into_iter()
+        .filter(|foreign_arch| {
+            config
+                .install
+                .iter()
+                .any(|requested_package| requested_package.arch.as_ref() == Some(foreign_arch))
+        })
+        .collect::<Vec<_>>();
+    for source in &mut source_list {
+        source.foreign_arches.clone_from(&foreign_arches);
+    }
+
+    // custom sources from configuration
+    for custom_source in &config.sources {
+        let (native_sources, foreign_sources): (Vec<_>, Vec<_>) = custom_source
+            .to_sources()
+            .into_iter()
+            .filter(|source| {
+                source.arch == distro.architecture || foreign_arches.contains(&source.arch)
+            })
+            .partition(|source| source.arch == distro.architecture);
+        match native_sources.into_iter().next() {
+            Some(mut source) => {
+                source.foreign_arches = foreign_sources
+                    .into_iter()
+                    .map(|foreign_source| foreign_source.arch)
+                    .collect();
+                source_list.push(source);
+            }
+            None => source_list.extend(foreign_sources),
+        }
+    }
+
+    source_list
+}
+
+pub(crate) fn print_distro_info(distro: &Distro) {
+    print::bullet("Distribution Info");
+    print::sub_bullet(format!("Name: {}", distro.name));
+    print::sub_bullet(format!("Version: {}", distro.version));
+    print::sub_bullet(format!("Codename: {}", distro.codename));
+    print::sub_bullet(format!("Architecture: {}", distro.architecture));
+}
+
+// Packages in the `-proposed` pockets haven't finished Ubuntu's verification process yet and any
+// newer version found there is installed over the one from the release or `-updates`.
+pub(crate) fn print_proposed_suites_warning(source_list: &[Source]) {
+    let proposed_suites = source_list
+        .iter()
+        .flat_map(|source| &source.suites)
+        .filter(|suite| suite.ends_with("-proposed"))
+        .map(style::value)
+        .collect::<IndexSet<_>>();
+    if !proposed_suites.is_empty() {
+        print::warning(formatdoc! { "
+            Packages can be installed from {suites}. These are pre-release updates that haven't \
+            been verified by Ubuntu yet and newer versions from them are installed over the ones \
+            from the other suites. Only enable them to get a specific fix and disable them once \
+            the fix is released to {updates_suite}.
+        ",
+            suites = proposed_suites.into_iter().collect::<Vec<_>>().join(", "),
+            updates_suite = style::value("-updates"),
+        });
+    }
+}
+
+// NOTE: Unknown keys are only a warning without a `schema-version` since failing the build for
+//       them would break existing configurations. Setting `schema-version = "0.3"` turns them
+//       into an error instead.
+fn print_unknown_keys_warning(config_file: &Path, config: &BuildpackConfig) {
+    if config.unknown_keys.is_empty() {
+        return;
+    }
+    print::warning(formatdoc! { "
+        The following keys in {config_file} aren't recognized and are ignored:
+
+        {unknown_keys}
+
+        Set {schema_version} in the buildpack configuration to fail the build for unknown keys.
+    ",
+        config_file = style::value(
+            config_file
+                .file_name()
+                .unwrap_or(config_file.as_os_str())
+                .to_string_lossy()
+        ),
+        unknown_keys = config
+            .unknown_keys
+            .iter()
+            .map(|unknown_key| format!("- {unknown_key}"))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        schema_version = style::value("schema-version = \"0.3\""),
+    });
+}
+
+pub(crate) fn print_deprecated_keys_warning(config: &BuildpackConfig) {
+    for (key, reason) in &config.deprecated_keys {
+        print::warning(format!(
+            "The {key} configuration key is deprecated since {reason}.",
+            key = style::value(*key)
+        ));
+    }
+}
+
+// shown in tenths of a mebibyte (e.g.; 12.3 MiB) to avoid floating point conversions
+pub(crate) fn format_size(bytes: u64) -> String {
+    let tenths_of_mebibytes = bytes * 10 / (1024 * 1024);
+    format!(
+        "{whole}.{fraction} MiB",
+        whole = tenths_of_mebibytes / 10,
+        fraction = tenths_of_mebibytes % 10
+    )
+}
+
+fn get_aptfile(app_dir: &Path) -> Result<Option<PathBuf>, DetectError> {
+    let aptfile = app_dir.join("Aptfile");
+    aptfile
+        .try_exists()
+        .map_err(|e| DetectError::CheckExistsAptfile(aptfile.clone(), e))
+        .map(|exists| if exists { Some(aptfile) } else { None })
+}
+
+fn get_standalone_config_file(app_dir: &Path) -> Result<Option<PathBuf>, DetectError> {
+    let standalone_config_file = app_dir.join(STANDALONE_CONFIG_FILE);
+    standalone_config_file
+        .try_exists()
+        .map_err(|e| DetectError::CheckExistsDebPackagesToml(standalone_config_file.clone(), e))
+        .map(|exists| {
+            if exists {
+                Some(standalone_config_file)
+            } else {
+                None
+            }
+        })
+}
+
+// The standalone config file takes precedence over the configuration in project.toml.
+pub(crate) fn get_config_file(app_dir: &Path) -> Result<Option<PathBuf>, DetectError> {
+    match get_standalone_config_file(app_dir)? {
+        Some(standalone_config_file) => Ok(Some(standalone_config_file)),
+        None => get_project_toml(app_dir),
+    }
+}
+
+pub(crate) fn get_project_toml(app_dir: &Path) -> Result<Option<PathBuf>, DetectError> {
+    let project_toml = app_dir.join("project.toml");
+    project_toml
+        .try_exists()
+        .map_err(|e| DetectError::CheckExistsProjectToml(project_toml.clone(), e))
+        .map(|exists| if exists { Some(project_toml) } else { None })
+}
+
+fn empty_config_help_message() -> String {
+    formatdoc! {"
+        No configured packages to install found in project.toml file. You may need to \
+        add a list of packages to install in your project.toml like this:
+
+        [{NAMESPACED_CONFIG}]
+        install = [
+            \"package-name\",
+        ]
+    " }
+    .trim()
+    .to_string()
+}
+
+fn vendored_config_changed_help_message() -> String {
+    let vendor_dir = style::value(VENDOR_DIR);
+    let project_toml = style::value("project.toml");
+    let vendor_command = style::value("deb-packages vendor");
+    formatdoc! { "
+        The packages in {vendor_dir} were vendored from a different configuration than what's \
+        currently in {project_toml}. Run {vendor_command} to update the vendored packages.
+    " }
+    .trim()
+    .to_string()
+}
+
+fn migrate_from_aptfile_help_message() -> String {
+    let aptfile = style::value("Aptfile");
+    let apt_buildpack_name = style::value("heroku-community/apt");
+    let project_toml = style::value("project.toml");
+    let configuration_readme_url = style::url(
+        "https://github.com/heroku/buildpacks-deb-packages?tab=readme-ov-file#configuration",
+    );
+    formatdoc! { "
+        The use of an {aptfile} is deprecated!
+
+        Users of the {apt_buildpack_name} buildpack can migrate their installed packages to be compatible \
+        with this buildpack's configuration by adding a {project_toml} file with:
+
+            [_]
+            schema-version = \"0.2\"
+
+            [com.heroku.buildpacks.deb-packages]
+            install = [
+                # copy the contents of your Aptfile here, e.g.;
+                # \"package-a\",
+                # \"package-b\",
+                # \"package-c\"
+            ]
+
+        If your {aptfile} contains a package name that uses wildcards (e.g.; mysql-*) this must be replaced \
+        with the full list of matching package names. See {configuration_readme_url}
+    " }
+    .trim()
+    .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn apply_proxy_prefix_to_http_url() {
+        assert_eq!(
+            apply_proxy_prefix(
+                "http://archive.ubuntu.com/ubuntu/dists/noble/InRelease",
+                Some("http://apt-cache.internal:3142")
+            ),
+            "http://apt-cache.internal:3142/archive.ubuntu.com/ubuntu/dists/noble/InRelease"
+        );
+    }
+
+    #[test]
+    fn apply_proxy_prefix_skips_https_url() {
+        assert_eq!(
+            apply_proxy_prefix(
+                "https://packages.example.com/debian/dists/stable/InRelease",
+                Some("http://apt-cache.internal:3142")
+            ),
+            "https://packages.example.com/debian/dists/stable/InRelease"
+        );
+    }
+
+    #[test]
+    fn apply_proxy_prefix_without_proxy() {
+        assert_eq!(
+            apply_proxy_prefix(
+                "http://archive.ubuntu.com/ubuntu/dists/noble/InRelease",
+                None
+            ),
+            "http://archive.ubuntu.com/ubuntu/dists/noble/InRelease"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use crate::config::custom_source::CustomSource;
+use async_trait::async_trait;
+use http::{Extensions, StatusCode};
+use reqwest::{Body, Request, Response, ResponseBuilderExt, Url};
+use reqwest_middleware::{Middleware, Next};
+use std::io::ErrorKind;
+use tokio::fs::File as AsyncFile;
+use tokio_util::io::ReaderStream;
+
+// The HTTP client only accepts urls with a host so requests for `file://` urls are sent to this
+// reserved host instead and answered by the middleware below. The `.invalid` top-level domain is
+// guaranteed to never resolve.
+const LOCAL_REPOSITORY_HOST: &str = "local-repository.deb-packages.invalid";
+
+pub(crate) fn is_file_uri(uri: &str) -> bool {
+    uri.starts_with("file://")
+}
+
+pub(crate) fn to_local_repository_url(url: &str) -> Option<String> {
+    url.strip_prefix("file://")
+        .map(|path| path.strip_prefix("localhost").unwrap_or(path))
+        .map(|path| format!("http://{LOCAL_REPOSITORY_HOST}{path}"))
+}
+
+// Custom sources can point at a repository on the local filesystem with a `file://` uri (e.g.;
+// one vendored into the application directory or a mounted volume) so builds don't need network
+// access to reach it. Requests for these urls are answered from disk with a response shaped like
+// the one an HTTP server would return so the rest of the buildpack handles them the same way,
+// including a `404 Not Found` status for files that don't exist.
+pub(crate) struct LocalRepositoryMiddleware;
+
+impl LocalRepositoryMiddleware {
+    pub(crate) fn new(custom_sources: &[CustomSource]) -> Option<Self> {
+        custom_sources
+            .iter()
+            .any(|custom_source| is_file_uri(custom_source.uri.as_ref()))
+            .then_some(Self)
+    }
+}
+
+#[async_trait]
+impl Middleware for LocalRepositoryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        if req.url().host_str() != Some(LOCAL_REPOSITORY_HOST) {
+            return next.run(req, extensions).await;
+        }
+
+        let path = Url::parse(&format!("file://{}", req.url().path()))
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .ok_or_else(|| {
+                reqwest_middleware::Error::middleware(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid file url: {}", req.url()),
+                ))
+            })?;
+
+        let (status, body) = match AsyncFile::open(&path).await {
+            Ok(file) => (StatusCode::OK, Body::wrap_stream(ReaderStream::new(file))),
+            Err(e) if e.kind() == ErrorKind::NotFound => (StatusCode::NOT_FOUND, Body::from("")),
+            Err(e) => Err(reqwest_middleware::Error::middleware(e))?,
+        };
+
+        http::Response::builder()
+            .status(status)
+            .url(req.url().clone())
+            .body(body)
+            .map(Response::from)
+            .map_err(reqwest_middleware::Error::middleware)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest_middleware::ClientBuilder;
+
+    #[test]
+    fn reads_files_from_local_repository() {
+        let repository_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(repository_dir.path().join("dists/noble")).unwrap();
+        std::fs::write(
+            repository_dir.path().join("dists/noble/InRelease"),
+            "release file contents",
+        )
+        .unwrap();
+        let repository_url = Url::from_directory_path(repository_dir.path()).unwrap();
+        let request_url =
+            |path: &str| to_local_repository_url(repository_url.join(path).unwrap().as_str());
+
+        rustls::crypto::ring::default_provider()
+            .install_default()
+            .ok();
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(LocalRepositoryMiddleware)
+            .build();
+
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let response = client
+                    .get(request_url("dists/noble/InRelease").unwrap())
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(response.text().await.unwrap(), "release file contents");
+
+                let response = client
+                    .get(request_url("dists/noble/Release").unwrap())
+                    .send()
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::NOT_FOUND);
+            });
+    }
+}
@@ -0,0 +1,416 @@
+use crate::debian::{Distro, RepositoryPackage};
+use crate::{BuildpackResult, DebianPackagesBuildpackError};
+use bullet_stream::style;
+use indoc::formatdoc;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+pub(crate) const LOCKFILE_NAME: &str = "deb-packages.lock";
+
+// NOTE: The lockfile records the resolved packages for each distribution/architecture the
+//       application is built for. Since the base image can't be inspected when the lockfile is
+//       generated, the full dependency tree is recorded here and packages that are already on
+//       the system during a build will still be skipped.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Lockfile {
+    #[serde(default, rename = "target")]
+    pub(crate) targets: Vec<LockedTarget>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LockedTarget {
+    pub(crate) distro_name: String,
+    pub(crate) distro_version: String,
+    pub(crate) arch: String,
+    #[serde(default, rename = "package")]
+    pub(crate) packages: Vec<LockedPackage>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct LockedPackage {
+    pub(crate) name: String,
+    pub(crate) version: String,
+    pub(crate) checksum_type: String,
+    pub(crate) checksum: String,
+}
+
+impl Lockfile {
+    pub(crate) fn read(path: &Path) -> BuildpackResult<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map(Some)
+                .map_err(|e| LockfileError::Parse(path.to_path_buf(), Box::new(e)).into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(LockfileError::Read(path.to_path_buf(), e).into()),
+        }
+    }
+
+    pub(crate) fn write(&self, path: &Path) -> BuildpackResult<()> {
+        let contents = formatdoc! { "
+            # This file is generated by the Heroku .deb Packages buildpack. Do not edit it manually.
+            # To update the locked package versions, run: deb-packages lock update
+
+            {lockfile}",
+            lockfile = toml::to_string(self).expect("Lockfile should serialize to TOML")
+        };
+        std::fs::write(path, contents)
+            .map_err(|e| LockfileError::Write(path.to_path_buf(), e).into())
+    }
+
+    pub(crate) fn get_target(&self, distro: &Distro) -> Option<&LockedTarget> {
+        self.targets
+            .iter()
+            .find(|target| target.is_for_distro(distro))
+    }
+
+    pub(crate) fn set_target(&mut self, target: LockedTarget) {
+        if let Some(existing) = self
+            .targets
+            .iter_mut()
+            .find(|existing| existing.is_same_target(&target))
+        {
+            *existing = target;
+        } else {
+            self.targets.push(target);
+        }
+    }
+}
+
+impl LockedTarget {
+    pub(crate) fn new(distro: &Distro, packages: &[RepositoryPackage]) -> Self {
+        let mut packages = packages
+            .iter()
+            .map(|package| LockedPackage {
+                name: package.name.clone(),
+                version: package.display_version(),
+                checksum_type: package.checksum.to_string(),
+                checksum: package.checksum.value().to_string(),
+            })
+            .collect::<Vec<_>>();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            distro_name: distro.name.to_lowercase(),
+            distro_version: distro.version.clone(),
+            arch: distro.architecture.to_string(),
+            packages,
+        }
+    }
+
+    pub(crate) fn locked_versions(&self) -> HashMap<String, String> {
+        self.packages
+            .iter()
+            .map(|package| (package.name.clone(), package.version.clone()))
+            .collect()
+    }
+
+    pub(crate) fn diff(&self, updated: &LockedTarget) -> Vec<LockfileChange> {
+        let mut changes = BTreeMap::new();
+        let old_versions = self.locked_versions();
+        let new_versions = updated.locked_versions();
+
+        for (name, old_version) in &old_versions {
+            match new_versions.get(name) {
+                Some(new_version) if new_version != old_version => {
+                    changes.insert(
+                        name.clone(),
+                        LockfileChange::Updated {
+                            name: name.clone(),
+                            old_version: old_version.clone(),
+                            new_version: new_version.clone(),
+                        },
+                    );
+                }
+                Some(_) => {}
+                None => {
+                    changes.insert(
+                        name.clone(),
+                        LockfileChange::Removed {
+                            name: name.clone(),
+                            version: old_version.clone(),
+                        },
+                    );
+                }
+            }
+        }
+
+        for (name, new_version) in &new_versions {
+            if !old_versions.contains_key(name) {
+                changes.insert(
+                    name.clone(),
+                    LockfileChange::Added {
+                        name: name.clone(),
+                        version: new_version.clone(),
+                    },
+                );
+            }
+        }
+
+        changes.into_values().collect()
+    }
+
+    fn is_for_distro(&self, distro: &Distro) -> bool {
+        self.distro_name.eq_ignore_ascii_case(&distro.name)
+            && self.distro_version == distro.version
+            && self.arch == distro.architecture.to_string()
+    }
+
+    fn is_same_target(&self, other: &LockedTarget) -> bool {
+        self.distro_name.eq_ignore_ascii_case(&other.distro_name)
+            && self.distro_version == other.distro_version
+            && self.arch == other.arch
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum LockfileChange {
+    Added {
+        name: String,
+        version: String,
+    },
+    Removed {
+        name: String,
+        version: String,
+    },
+    Updated {
+        name: String,
+        old_version: String,
+        new_version: String,
+    },
+}
+
+impl Display for LockfileChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockfileChange::Added { name, version } => {
+                write!(
+                    f,
+                    "Added {name_with_version}",
+                    name_with_version = style::value(format!("{name}@{version}"))
+                )
+            }
+            LockfileChange::Removed { name, version } => {
+                write!(
+                    f,
+                    "Removed {name_with_version}",
+                    name_with_version = style::value(format!("{name}@{version}"))
+                )
+            }
+            LockfileChange::Updated {
+                name,
+                old_version,
+                new_version,
+            } => {
+                write!(
+                    f,
+                    "Updated {name} from {old_version} to {new_version}",
+                    name = style::value(name),
+                    old_version = style::value(old_version),
+                    new_version = style::value(new_version)
+                )
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) enum LockfileError {
+    Read(PathBuf, std::io::Error),
+    Parse(PathBuf, Box<toml::de::Error>),
+    Write(PathBuf, std::io::Error),
+}
+
+impl From<LockfileError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: LockfileError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::Lockfile(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::debian::{
+        ArchitectureName, DistroCodename, PackageChecksum, create_repository_package,
+    };
+    use indoc::indoc;
+
+    #[test]
+    fn test_read_missing_lockfile() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let lockfile = Lockfile::read(&temp_dir.path().join(LOCKFILE_NAME)).unwrap();
+        assert_eq!(lockfile, None);
+    }
+
+    #[test]
+    fn test_write_and_read_lockfile() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let lockfile_path = temp_dir.path().join(LOCKFILE_NAME);
+
+        let mut lockfile = Lockfile::default();
+        lockfile.set_target(LockedTarget::new(
+            &noble_amd64(),
+            &[
+                create_repository_package()
+                    .name("libvips42")
+                    .version("8.15.1-1.1build4")
+                    .checksum(PackageChecksum::Sha256("libvips42-sha256".to_string()))
+                    .call(),
+                create_repository_package()
+                    .name("libcfitsio10")
+                    .version("4.3.1-1.1build2")
+                    .checksum(PackageChecksum::Sha256("libcfitsio10-sha256".to_string()))
+                    .call(),
+            ],
+        ));
+        lockfile.write(&lockfile_path).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&lockfile_path).unwrap(),
+            indoc! { r#"
+                # This file is generated by the Heroku .deb Packages buildpack. Do not edit it manually.
+                # To update the locked package versions, run: deb-packages lock update
+
+                [[target]]
+                distro_name = "ubuntu"
+                distro_version = "24.04"
+                arch = "amd64"
+
+                [[target.package]]
+                name = "libcfitsio10"
+                version = "4.3.1-1.1build2"
+                checksum_type = "SHA256"
+                checksum = "libcfitsio10-sha256"
+
+                [[target.package]]
+                name = "libvips42"
+                version = "8.15.1-1.1build4"
+                checksum_type = "SHA256"
+                checksum = "libvips42-sha256"
+            "# }
+        );
+        assert_eq!(Lockfile::read(&lockfile_path).unwrap(), Some(lockfile));
+    }
+
+    #[test]
+    fn test_read_invalid_lockfile() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let lockfile_path = temp_dir.path().join(LOCKFILE_NAME);
+        std::fs::write(&lockfile_path, "[[target]]\ndistro_name = 1").unwrap();
+        match Lockfile::read(&lockfile_path).unwrap_err() {
+            libcnb::Error::BuildpackError(DebianPackagesBuildpackError::Lockfile(
+                LockfileError::Parse(path, _),
+            )) => assert_eq!(path, lockfile_path),
+            e => panic!("Unexpected error: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_set_target_replaces_matching_target() {
+        let mut lockfile = Lockfile::default();
+        lockfile.set_target(LockedTarget::new(
+            &noble_amd64(),
+            &[create_repository_package()
+                .name("curl")
+                .version("1.0.0")
+                .call()],
+        ));
+        lockfile.set_target(LockedTarget::new(
+            &noble_amd64(),
+            &[create_repository_package()
+                .name("curl")
+                .version("2.0.0")
+                .call()],
+        ));
+        assert_eq!(lockfile.targets.len(), 1);
+        assert_eq!(
+            lockfile
+                .get_target(&noble_amd64())
+                .unwrap()
+                .locked_versions(),
+            HashMap::from([("curl".to_string(), "2.0.0".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_get_target_for_other_architecture() {
+        let mut lockfile = Lockfile::default();
+        lockfile.set_target(LockedTarget::new(
+            &noble_amd64(),
+            &[create_repository_package()
+                .name("curl")
+                .version("1.0.0")
+                .call()],
+        ));
+        let noble_arm64 = Distro {
+            architecture: ArchitectureName::ARM_64,
+            ..noble_amd64()
+        };
+        assert_eq!(lockfile.get_target(&noble_arm64), None);
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = LockedTarget::new(
+            &noble_amd64(),
+            &[
+                create_repository_package()
+                    .name("curl")
+                    .version("1.0.0")
+                    .call(),
+                create_repository_package()
+                    .name("libcurl4")
+                    .version("1.0.0")
+                    .call(),
+                create_repository_package()
+                    .name("wget")
+                    .version("1.0.0")
+                    .call(),
+            ],
+        );
+        let new = LockedTarget::new(
+            &noble_amd64(),
+            &[
+                create_repository_package()
+                    .name("curl")
+                    .version("1.0.1")
+                    .call(),
+                create_repository_package()
+                    .name("libcurl4")
+                    .version("1.0.0")
+                    .call(),
+                create_repository_package()
+                    .name("libssl3")
+                    .version("3.0.0")
+                    .call(),
+            ],
+        );
+        assert_eq!(
+            old.diff(&new),
+            vec![
+                LockfileChange::Updated {
+                    name: "curl".to_string(),
+                    old_version: "1.0.0".to_string(),
+                    new_version: "1.0.1".to_string(),
+                },
+                LockfileChange::Added {
+                    name: "libssl3".to_string(),
+                    version: "3.0.0".to_string(),
+                },
+                LockfileChange::Removed {
+                    name: "wget".to_string(),
+                    version: "1.0.0".to_string(),
+                },
+            ]
+        );
+    }
+
+    fn noble_amd64() -> Distro {
+        Distro {
+            name: "ubuntu".to_string(),
+            version: "24.04".to_string(),
+            codename: DistroCodename::Noble,
+            architecture: ArchitectureName::AMD_64,
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use crate::BuildpackResult;
+use crate::debian::MultiarchName;
+use crate::install_packages::InstallPackagesError;
+use crate::strip_binaries::has_elf_magic;
+use bullet_stream::style;
+use object::build::elf::{Builder, Dynamic, SectionData};
+use object::elf::{DT_NEEDED, ET_DYN, ET_EXEC};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// the number of binaries listed for each missing library before the rest are summarized
+const MAX_LISTED_BINARIES: usize = 3;
+
+// The library directories of the base image searched by the dynamic linker by default.
+const SYSTEM_LIBRARY_DIRS: [&str; 4] = ["/lib", "/usr/lib", "/lib64", "/usr/lib64"];
+
+// NOTE: The shared libraries a binary needs (its `DT_NEEDED` entries) are only loaded when it
+//       starts so a library that isn't installed goes unnoticed until the binary crashes at
+//       launch. The libraries needed by every executable and shared library in the layer are
+//       looked up by name in the layer (in any directory, since packages configured with `prefix`
+//       or binaries using `RUNPATH` load them from non-standard locations) and then in the library
+//       directories of the base image. The build image is checked since that's the image the build
+//       runs on, so libraries only present on the build image are still missed.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct MissingSharedLibraries {
+    // the name of each missing library and the binaries (relative to the install directory)
+    // that need it
+    libraries: BTreeMap<String, BTreeSet<PathBuf>>,
+}
+
+impl MissingSharedLibraries {
+    pub(crate) fn find(
+        install_path: &Path,
+        system_library_dirs: &[PathBuf],
+    ) -> BuildpackResult<Self> {
+        let mut layer_files = HashSet::new();
+        let mut needed_libraries: BTreeMap<String, BTreeSet<PathBuf>> = BTreeMap::new();
+
+        for entry in WalkDir::new(install_path).into_iter().flatten() {
+            if entry.file_type().is_dir() {
+                continue;
+            }
+            layer_files.insert(entry.file_name().to_string_lossy().to_string());
+
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.path();
+            if !has_elf_magic(path).map_err(|e| InstallPackagesError::ReadBinary(path.into(), e))? {
+                continue;
+            }
+            let contents =
+                fs::read(path).map_err(|e| InstallPackagesError::ReadBinary(path.into(), e))?;
+            let binary = path.strip_prefix(install_path).unwrap_or(path);
+            for library in read_needed_libraries(&contents) {
+                needed_libraries
+                    .entry(library)
+                    .or_default()
+                    .insert(binary.to_path_buf());
+            }
+        }
+
+        needed_libraries.retain(|library, _| {
+            // libraries given as a path (e.g.; `/opt/lib/libexample.so`) are loaded from that path
+            // instead of being searched for
+            !library.contains('/')
+                && !layer_files.contains(library)
+                && !system_library_dirs
+                    .iter()
+                    .any(|library_dir| library_dir.join(library).exists())
+        });
+
+        Ok(Self {
+            libraries: needed_libraries,
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.libraries.is_empty()
+    }
+
+    pub(crate) fn library_count(&self) -> usize {
+        self.libraries.len()
+    }
+
+    pub(crate) fn library_names(&self) -> BTreeSet<String> {
+        self.libraries.keys().cloned().collect()
+    }
+}
+
+impl Display for MissingSharedLibraries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lines = self
+            .libraries
+            .iter()
+            .map(|(library, binaries)| {
+                let mut needed_by = binaries
+                    .iter()
+                    .take(MAX_LISTED_BINARIES)
+                    .map(|binary| style::value(format!("/{}", binary.to_string_lossy())))
+                    .collect::<Vec<_>>();
+                if binaries.len() > MAX_LISTED_BINARIES {
+                    needed_by.push(format!(
+                        "and {count} more",
+                        count = binaries.len() - MAX_LISTED_BINARIES
+                    ));
+                }
+                format!(
+                    "- {library} (needed by {needed_by})",
+                    library = style::value(library),
+                    needed_by = needed_by.join(", ")
+                )
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// the directories of the base image the dynamic linker searches for the native architecture and
+// any foreign architectures packages can be installed for
+pub(crate) fn get_system_library_dirs(multiarch_names: &[MultiarchName]) -> Vec<PathBuf> {
+    let mut system_library_dirs = SYSTEM_LIBRARY_DIRS
+        .iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    for multiarch_name in multiarch_names {
+        for library_dir in ["/lib", "/usr/lib"] {
+            system_library_dirs.push(Path::new(library_dir).join(multiarch_name.to_string()));
+        }
+    }
+    system_library_dirs
+}
+
+// Anything that can't be parsed, or isn't a linked ELF binary, is treated as needing nothing
+// rather than failing the build.
+fn read_needed_libraries(contents: &[u8]) -> Vec<String> {
+    let Ok(builder) = Builder::read(contents) else {
+        return vec![];
+    };
+
+    if !matches!(builder.header.e_type, ET_EXEC | ET_DYN) {
+        return vec![];
+    }
+
+    builder
+        .sections
+        .iter()
+        .filter_map(|section| match &section.data {
+            SectionData::Dynamic(dynamics) => Some(dynamics),
+            _ => None,
+        })
+        .flatten()
+        .filter_map(|dynamic| match dynamic {
+            Dynamic::String { tag, val } if *tag == DT_NEEDED => {
+                Some(String::from_utf8_lossy(val).to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::Endianness;
+    use object::elf::{
+        EM_X86_64, PF_R, PF_W, PT_LOAD, SHF_ALLOC, SHF_WRITE, SHT_DYNAMIC, SHT_DYNSYM, SHT_STRTAB,
+    };
+
+    #[test]
+    fn find_missing_shared_libraries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let install_path = temp_dir.path().join("layer");
+        let system_library_dir = temp_dir.path().join("usr/lib/x86_64-linux-gnu");
+        fs::create_dir_all(&system_library_dir).unwrap();
+        fs::write(system_library_dir.join("libc.so.6"), "").unwrap();
+
+        for (path, needed) in [
+            (
+                "usr/bin/example",
+                vec!["libc.so.6", "libexample.so.1", "libmissing.so.2"],
+            ),
+            ("usr/bin/other", vec!["libmissing.so.2", "libgone.so.0"]),
+            (
+                "usr/lib/x86_64-linux-gnu/libexample.so.1.0.0",
+                vec!["libc.so.6"],
+            ),
+        ] {
+            let path = install_path.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, create_elf(&needed)).unwrap();
+        }
+        std::os::unix::fs::symlink(
+            "libexample.so.1.0.0",
+            install_path.join("usr/lib/x86_64-linux-gnu/libexample.so.1"),
+        )
+        .unwrap();
+        fs::write(install_path.join("usr/bin/script"), "#!/bin/sh\n").unwrap();
+
+        let missing_shared_libraries =
+            MissingSharedLibraries::find(&install_path, &[system_library_dir]).unwrap();
+
+        assert_eq!(
+            missing_shared_libraries,
+            MissingSharedLibraries {
+                libraries: BTreeMap::from([
+                    (
+                        "libgone.so.0".to_string(),
+                        BTreeSet::from([PathBuf::from("usr/bin/other")])
+                    ),
+                    (
+                        "libmissing.so.2".to_string(),
+                        BTreeSet::from([
+                            PathBuf::from("usr/bin/example"),
+                            PathBuf::from("usr/bin/other")
+                        ])
+                    ),
+                ])
+            }
+        );
+    }
+
+    #[test]
+    fn missing_shared_libraries_display() {
+        let missing_shared_libraries = MissingSharedLibraries {
+            libraries: BTreeMap::from([(
+                "libmissing.so.2".to_string(),
+                BTreeSet::from([
+                    PathBuf::from("usr/bin/a"),
+                    PathBuf::from("usr/bin/b"),
+                    PathBuf::from("usr/bin/c"),
+                    PathBuf::from("usr/bin/d"),
+                ]),
+            )]),
+        };
+        assert_eq!(
+            missing_shared_libraries.to_string(),
+            format!(
+                "- {} (needed by {}, {}, {}, and 1 more)",
+                style::value("libmissing.so.2"),
+                style::value("/usr/bin/a"),
+                style::value("/usr/bin/b"),
+                style::value("/usr/bin/c"),
+            )
+        );
+    }
+
+    #[test]
+    fn system_library_dirs_include_multiarch_dirs() {
+        assert_eq!(
+            get_system_library_dirs(&[MultiarchName::X86_64_LINUX_GNU]),
+            vec![
+                PathBuf::from("/lib"),
+                PathBuf::from("/usr/lib"),
+                PathBuf::from("/lib64"),
+                PathBuf::from("/usr/lib64"),
+                PathBuf::from("/lib/x86_64-linux-gnu"),
+                PathBuf::from("/usr/lib/x86_64-linux-gnu"),
+            ]
+        );
+    }
+
+    fn create_elf(needed: &[&str]) -> Vec<u8> {
+        let mut builder = Builder::new(Endianness::Little, true);
+        builder.header.e_type = ET_DYN;
+        builder.header.e_machine = EM_X86_64;
+        builder.header.e_phoff = 0x40;
+
+        let shstrtab = builder.sections.add();
+        shstrtab.name = b".shstrtab"[..].into();
+        shstrtab.sh_type = SHT_STRTAB;
+        shstrtab.data = SectionData::SectionString;
+
+        // the dynamic string table is only read back when it belongs to a dynamic symbol table
+        let dynsym = builder.sections.add();
+        dynsym.name = b".dynsym"[..].into();
+        dynsym.sh_type = SHT_DYNSYM;
+        dynsym.sh_flags = u64::from(SHF_ALLOC);
+        dynsym.sh_addralign = 8;
+        dynsym.data = SectionData::DynamicSymbol;
+        let dynsym_id = dynsym.id();
+
+        let dynstr = builder.sections.add();
+        dynstr.name = b".dynstr"[..].into();
+        dynstr.sh_type = SHT_STRTAB;
+        dynstr.sh_flags = u64::from(SHF_ALLOC);
+        dynstr.sh_addralign = 1;
+        dynstr.data = SectionData::DynamicString;
+        let dynstr_id = dynstr.id();
+
+        let dynamic = builder.sections.add();
+        dynamic.name = b".dynamic"[..].into();
+        dynamic.sh_type = SHT_DYNAMIC;
+        dynamic.sh_flags = u64::from(SHF_ALLOC | SHF_WRITE);
+        dynamic.sh_addralign = 8;
+        dynamic.sh_link_section = Some(dynstr_id);
+        dynamic.data = SectionData::Dynamic(
+            needed
+                .iter()
+                .map(|library| Dynamic::String {
+                    tag: DT_NEEDED,
+                    val: library.as_bytes().into(),
+                })
+                .collect(),
+        );
+        let dynamic_id = dynamic.id();
+
+        builder.set_section_sizes();
+
+        let segment = builder.segments.add();
+        segment.p_type = PT_LOAD;
+        segment.p_flags = PF_R | PF_W;
+        segment.p_filesz = 0x1000;
+        segment.p_memsz = 0x1000;
+        segment.p_align = 8;
+        segment.append_section(builder.sections.get_mut(dynsym_id));
+        segment.append_section(builder.sections.get_mut(dynstr_id));
+        segment.append_section(builder.sections.get_mut(dynamic_id));
+
+        let mut contents = Vec::new();
+        builder.write(&mut contents).unwrap();
+        contents
+    }
+}
@@ -0,0 +1,222 @@
+use crate::host_credentials::{HostAuth, HostCredentials};
+
+// The credentials from a `.netrc` file (see https://everything.curl.dev/usingcurl/netrc.html).
+// Like curl, the first `machine` entry matching a host is used and the `default` entry is only
+// used for hosts without a `machine` entry. Entries without both a `login` and `password` are
+// ignored since there's nobody to prompt for the missing value during a build.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct Netrc {
+    pub(crate) machines: Vec<HostCredentials>,
+    pub(crate) default: Option<HostAuth>,
+}
+
+#[derive(Default)]
+struct NetrcEntry {
+    // `None` for the `default` entry
+    machine: Option<String>,
+    login: Option<String>,
+    password: Option<String>,
+}
+
+impl Netrc {
+    pub(crate) fn parse(contents: &str) -> Self {
+        let mut netrc = Netrc::default();
+        let mut entry: Option<NetrcEntry> = None;
+        let mut tokens = tokenize(&strip_macros(contents)).into_iter();
+
+        while let Some(token) = tokens.next() {
+            match token.as_str() {
+                "machine" => {
+                    netrc.add(entry.take());
+                    entry = tokens.next().map(|machine| NetrcEntry {
+                        machine: Some(machine),
+                        ..NetrcEntry::default()
+                    });
+                }
+                "default" => {
+                    netrc.add(entry.take());
+                    entry = Some(NetrcEntry::default());
+                }
+                "login" => {
+                    if let Some(entry) = entry.as_mut() {
+                        entry.login = tokens.next();
+                    }
+                }
+                "password" => {
+                    if let Some(entry) = entry.as_mut() {
+                        entry.password = tokens.next();
+                    }
+                }
+                "account" => {
+                    tokens.next();
+                }
+                _ => {}
+            }
+        }
+        netrc.add(entry);
+        netrc
+    }
+
+    fn add(&mut self, entry: Option<NetrcEntry>) {
+        let Some(NetrcEntry {
+            machine,
+            login: Some(username),
+            password: Some(password),
+        }) = entry
+        else {
+            return;
+        };
+        let auth = HostAuth::Basic { username, password };
+        match machine {
+            Some(host) => self.machines.push(HostCredentials { host, auth }),
+            None => {
+                self.default.get_or_insert(auth);
+            }
+        }
+    }
+}
+
+// A `macdef` defines a macro that runs until the next blank line and can contain anything so it's
+// removed before the rest of the file is split into tokens.
+fn strip_macros(contents: &str) -> String {
+    let mut stripped = String::new();
+    let mut in_macro = false;
+    for line in contents.lines() {
+        if in_macro {
+            in_macro = !line.trim().is_empty();
+            continue;
+        }
+        match line.split_once("macdef") {
+            Some((before, _)) if before.is_empty() || before.ends_with(char::is_whitespace) => {
+                stripped.push_str(before);
+                in_macro = true;
+            }
+            _ => stripped.push_str(line),
+        }
+        stripped.push('\n');
+    }
+    stripped
+}
+
+// Tokens are separated by whitespace and can be double-quoted to include whitespace, with `\"`,
+// `\\`, `\n`, `\r`, and `\t` escapes, the same as curl.
+fn tokenize(contents: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = contents.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '#' {
+            // comments run to the end of the line
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break;
+                }
+            }
+        } else if c == '"' {
+            chars.next();
+            let mut token = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => match chars.next() {
+                        Some('n') => token.push('\n'),
+                        Some('r') => token.push('\r'),
+                        Some('t') => token.push('\t'),
+                        Some(escaped) => token.push(escaped),
+                        None => {}
+                    },
+                    c => token.push(c),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn basic(username: &str, password: &str) -> HostAuth {
+        HostAuth::Basic {
+            username: username.to_string(),
+            password: password.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_netrc_machines_and_default() {
+        let netrc = Netrc::parse(
+            r#"
+            # credentials for the private mirror
+            machine apt.example.com
+              login user
+              password secret
+
+            machine artifacts.example.com login "build bot" password "p\"ss word"
+            machine apt.example.com login other password ignored
+            machine incomplete.example.com login user
+            default login anonymous password guest
+            "#,
+        );
+        assert_eq!(
+            netrc,
+            Netrc {
+                machines: vec![
+                    HostCredentials {
+                        host: "apt.example.com".to_string(),
+                        auth: basic("user", "secret"),
+                    },
+                    HostCredentials {
+                        host: "artifacts.example.com".to_string(),
+                        auth: basic("build bot", "p\"ss word"),
+                    },
+                    HostCredentials {
+                        host: "apt.example.com".to_string(),
+                        auth: basic("other", "ignored"),
+                    },
+                ],
+                default: Some(basic("anonymous", "guest")),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_netrc_skips_accounts_and_macros() {
+        let netrc = Netrc::parse(indoc::indoc! { "
+            machine apt.example.com login user account billing password secret macdef init
+            machine macro.example.com login user password secret
+            cd /pub
+
+            machine ppa.example.com login other password hunter2
+        " });
+        assert_eq!(
+            netrc,
+            Netrc {
+                machines: vec![
+                    HostCredentials {
+                        host: "apt.example.com".to_string(),
+                        auth: basic("user", "secret"),
+                    },
+                    HostCredentials {
+                        host: "ppa.example.com".to_string(),
+                        auth: basic("other", "hunter2"),
+                    },
+                ],
+                default: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use crate::BuildpackResult;
+use crate::install_packages::InstallPackagesError;
+use filetime::{FileTime, set_symlink_file_times};
+use libcnb::Env;
+use std::path::Path;
+use walkdir::WalkDir;
+
+// 1980-01-01T00:00:01Z, the same timestamp the CNB lifecycle gives the files of exported layers
+const DEFAULT_SOURCE_DATE_EPOCH: i64 = 315_532_801;
+
+// NOTE: The modification times of the installed files would otherwise come from whenever they
+//       were extracted, stripped, or rewritten so the packages layer would differ on every build
+//       even when the same package versions are installed. Every file, directory, and symlink in
+//       the layer is given the same timestamp instead, which is `SOURCE_DATE_EPOCH` when set
+//       (see https://reproducible-builds.org/docs/source-date-epoch/).
+//
+//       Ownership and permissions are already normalized during extraction since neither is
+//       preserved from the package archive (every file is owned by the build user).
+pub(crate) fn normalize_timestamps(
+    install_path: &Path,
+    timestamp: FileTime,
+) -> BuildpackResult<()> {
+    for entry in WalkDir::new(install_path) {
+        let entry = entry.map_err(|e| {
+            let path = e.path().unwrap_or(install_path).to_path_buf();
+            InstallPackagesError::NormalizeTimestamp(path, e.into())
+        })?;
+        set_symlink_file_times(entry.path(), timestamp, timestamp)
+            .map_err(|e| InstallPackagesError::NormalizeTimestamp(entry.path().to_path_buf(), e))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn get_source_date_epoch() -> FileTime {
+    let seconds = Env::from_current()
+        .get_string_lossy("SOURCE_DATE_EPOCH")
+        .and_then(|value| value.trim().parse::<i64>().ok())
+        .unwrap_or(DEFAULT_SOURCE_DATE_EPOCH);
+    FileTime::from_unix_time(seconds, 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn normalize_timestamps_of_files_directories_and_symlinks() {
+        let install_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(install_dir.path().join("usr/bin")).unwrap();
+        std::fs::write(install_dir.path().join("usr/bin/curl"), "curl").unwrap();
+        symlink("curl", install_dir.path().join("usr/bin/curl-link")).unwrap();
+        // a dangling symlink can't be followed so only the link itself can be updated
+        symlink("missing", install_dir.path().join("usr/bin/dangling")).unwrap();
+
+        let timestamp = FileTime::from_unix_time(1_700_000_000, 0);
+        normalize_timestamps(install_dir.path(), timestamp).unwrap();
+
+        for path in [
+            "",
+            "usr",
+            "usr/bin",
+            "usr/bin/curl",
+            "usr/bin/curl-link",
+            "usr/bin/dangling",
+        ] {
+            let metadata = std::fs::symlink_metadata(install_dir.path().join(path)).unwrap();
+            assert_eq!(
+                FileTime::from_last_modification_time(&metadata),
+                timestamp,
+                "{path}"
+            );
+        }
+    }
+}
@@ -11,10 +11,28 @@ pub(crate) const PROJECT_TOML_DETECTED: &str = formatcp!("{NAMESPACE}.project_to
 // Helps identify potential configuration issues
 pub(crate) const PROJECT_TOML_NO_CONFIG: &str = formatcp!("{NAMESPACE}.project_toml.no_config");
 
+// Indicates whether a standalone deb-packages.toml file was detected in the application root
+// Useful for understanding how many users configure the buildpack outside of project.toml
+pub(crate) const STANDALONE_CONFIG_FILE_DETECTED: &str =
+    formatcp!("{NAMESPACE}.standalone_config_file.detected");
+
+// Indicates whether packages were requested with the BP_DEB_PACKAGES_INSTALL environment variable
+// Useful for understanding how often platform operators inject packages without app configuration
+pub(crate) const ENV_INSTALL_DETECTED: &str = formatcp!("{NAMESPACE}.env_install.detected");
+
 // Indicates whether an Aptfile was detected in the build context
 // Useful for understanding if users are using a legacy Aptfile vs. project.toml
 pub(crate) const APTFILE_DETECTED: &str = formatcp!("{NAMESPACE}.aptfile.detected");
 
+// Indicates whether vendored packages were found for the target distribution
+// Useful for understanding how many users are running offline builds from a vendor directory
+pub(crate) const VENDORED_PACKAGES_DETECTED: &str =
+    formatcp!("{NAMESPACE}.vendored_packages.detected");
+
+// Indicates whether the packages resolved by the previous build were reused
+// Useful for understanding how often builds skip creating the package index
+pub(crate) const STORED_RESOLUTION_REUSED: &str = formatcp!("{NAMESPACE}.stored_resolution.reused");
+
 // Captures the reason for early exit from the build process
 // Helps identify buildpack misconfigurations for a user's application
 pub(crate) const EARLY_EXIT_REASON: &str = formatcp!("{NAMESPACE}.early_exit.reason");
@@ -103,10 +121,59 @@ pub(crate) const DOWNLOAD_PACKAGE_VERSION: &str = formatcp!("{DOWNLOAD_PACKAGE}.
 // Helps track individual download urls and potential issues
 pub(crate) const DOWNLOAD_PACKAGE_URL: &str = formatcp!("{DOWNLOAD_PACKAGE}.url");
 
+// The installation metadata fields that changed when the cached packages layer was invalidated
+// (e.g., "architecture", "distro", "package_checksums")
+// Helps explain why a packages layer wasn't restored from cache
+pub(crate) const PACKAGES_LAYER_CHANGED_FIELDS: &str =
+    formatcp!("{NAMESPACE}.packages_layer.changed_fields");
+
+// The total size in bytes of the files in the packages layer after installation
+// Helps identify applications shipping very large layers
+pub(crate) const PACKAGES_LAYER_SIZE: &str = formatcp!("{NAMESPACE}.packages_layer.size");
+
 // The decoder being used for package extraction (e.g. "gzip", "xz", "zstd")
 // Helps track package format and extraction method
 pub(crate) const EXTRACT_PACKAGE_DECODER: &str = formatcp!("{NAMESPACE}.extract_package.decoder");
 
+// The files extracted from a package that had their setuid or setgid bits removed
+// Helps security reviews track which packages ship privileged executables
+pub(crate) const EXTRACT_PACKAGE_SETID_FILES: &str =
+    formatcp!("{NAMESPACE}.extract_package.setid_files");
+
+// The number of files included in more than one of the packages being installed
+// Helps understand how often packages overwrite each other's files in the packages layer
+pub(crate) const INSTALL_PACKAGES_FILE_CONFLICTS: &str =
+    formatcp!("{NAMESPACE}.install_packages.file_conflicts");
+
+// The number of packages downloaded from urls configured with `verify = false`
+// Helps understand how often builds install packages whose checksums aren't verified
+pub(crate) const INSTALL_PACKAGES_UNVERIFIED_DOWNLOADS: &str =
+    formatcp!("{NAMESPACE}.install_packages.unverified_downloads");
+
+// The number of shared libraries needed by binaries in the packages layer that couldn't be found
+// Helps understand how often installed binaries would fail to start at launch
+pub(crate) const INSTALL_PACKAGES_MISSING_SHARED_LIBRARIES: &str =
+    formatcp!("{NAMESPACE}.install_packages.missing_shared_libraries");
+
+// The url of a Contents index searched for packages providing missing shared libraries
+// Helps track which repositories are searched by `auto_resolve_libs`
+pub(crate) const CONTENTS_INDEX_URL: &str = formatcp!("{NAMESPACE}.contents_index.url");
+
+// The number of packages with binaries needing a newer glibc than the target distro ships
+// Helps understand how often packages from custom repositories are built for a newer release
+pub(crate) const INSTALL_PACKAGES_NEWER_GLIBC_PACKAGES: &str =
+    formatcp!("{NAMESPACE}.install_packages.newer_glibc_packages");
+
+// The number of packages added to the install set because they provide a missing shared library
+// Helps understand how often `auto_resolve_libs` finds packages that weren't requested
+pub(crate) const INSTALL_PACKAGES_AUTO_RESOLVED_LIBRARIES: &str =
+    formatcp!("{NAMESPACE}.install_packages.auto_resolved_libraries");
+
+// The number of packages being installed that are affected by known security advisories
+// Helps understand how often builds install packages with outstanding Ubuntu Security Notices
+pub(crate) const SECURITY_ADVISORIES_AFFECTED_PACKAGES: &str =
+    formatcp!("{NAMESPACE}.security_advisories.affected_packages");
+
 const ENV: &str = formatcp!("{NAMESPACE}.env");
 
 // The `PATH` environment variable value exported by the buildpack
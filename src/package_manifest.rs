@@ -0,0 +1,277 @@
+use crate::BuildpackResult;
+use crate::install_packages::InstallPackagesError;
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio_tar::Archive as TarArchive;
+
+// Metadata about the installed packages is kept in the packages layer next to the installed files
+// in the same location on every build so other tools (and the buildpack on later builds) can
+// inspect what was installed without the package archives.
+pub(crate) const PACKAGE_METADATA_DIR: &str = "var/lib/deb-packages";
+const MANIFEST_FILE: &str = "manifest.json";
+
+// https://www.debian.org/doc/debian-policy/ch-maintainerscripts.html
+const MAINTAINER_SCRIPTS: [&str; 5] = ["preinst", "postinst", "prerm", "postrm", "config"];
+
+#[derive(Debug, Serialize)]
+struct PackageManifest<'a> {
+    packages: Vec<PackageManifestEntry<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct PackageManifestEntry<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    control: &'a PackageControl,
+}
+
+// What's read from the `control.tar` member of a package archive. Maintainer scripts are never run
+// since packages are only unpacked, but knowing which packages have them helps explain why an
+// installed package doesn't work the way it would after an `apt install`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize)]
+pub(crate) struct PackageControl {
+    pub(crate) fields: BTreeMap<String, String>,
+    pub(crate) conffiles: Vec<String>,
+    pub(crate) maintainer_scripts: Vec<String>,
+}
+
+impl PackageControl {
+    pub(crate) async fn read<R: AsyncRead + Unpin>(
+        mut tar_archive: TarArchive<R>,
+        package_path: &Path,
+    ) -> BuildpackResult<Self> {
+        let on_unpack_error =
+            |e| InstallPackagesError::UnpackTarball(package_path.to_path_buf(), e);
+
+        let mut package_control = PackageControl::default();
+        let mut entries = tar_archive.entries().map_err(on_unpack_error)?;
+        while let Some(entry) = entries.next().await {
+            let mut entry = entry.map_err(on_unpack_error)?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let entry_path = entry.path().map_err(on_unpack_error)?;
+            let Some(file_name) = entry_path
+                .strip_prefix("./")
+                .unwrap_or(&entry_path)
+                .to_str()
+                .map(ToString::to_string)
+            else {
+                continue;
+            };
+            match file_name.as_str() {
+                "control" => {
+                    let mut contents = String::new();
+                    entry
+                        .read_to_string(&mut contents)
+                        .await
+                        .map_err(on_unpack_error)?;
+                    package_control.fields = parse_control_fields(&contents);
+                }
+                "conffiles" => {
+                    let mut contents = String::new();
+                    entry
+                        .read_to_string(&mut contents)
+                        .await
+                        .map_err(on_unpack_error)?;
+                    package_control.conffiles = contents
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(ToString::to_string)
+                        .collect();
+                }
+                script if MAINTAINER_SCRIPTS.contains(&script) => {
+                    package_control.maintainer_scripts.push(file_name);
+                }
+                _ => {}
+            }
+        }
+        package_control.maintainer_scripts.sort();
+
+        Ok(package_control)
+    }
+}
+
+// Control files use the deb822 format where a field can continue onto the following lines when
+// they start with whitespace (e.g.; the extended `Description`).
+// https://www.debian.org/doc/debian-policy/ch-controlfields.html#syntax-of-control-files
+fn parse_control_fields(contents: &str) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    let mut current_field: Option<(String, String)> = None;
+    for line in contents.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = current_field.as_mut() {
+                value.push('\n');
+                value.push_str(line.trim());
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            fields.extend(current_field.take());
+            current_field = Some((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    fields.extend(current_field);
+    fields
+}
+
+pub(crate) async fn write_package_manifest(
+    install_path: &Path,
+    packages: &[(&str, &PackageControl)],
+) -> BuildpackResult<PathBuf> {
+    let metadata_dir = install_path.join(PACKAGE_METADATA_DIR);
+    let manifest_path = metadata_dir.join(MANIFEST_FILE);
+    let manifest = PackageManifest {
+        packages: packages
+            .iter()
+            .map(|(name, control)| PackageManifestEntry { name, control })
+            .collect(),
+    };
+    let contents =
+        serde_json::to_string_pretty(&manifest).expect("Package manifest should serialize to JSON");
+    tokio::fs::create_dir_all(&metadata_dir)
+        .await
+        .map_err(|e| InstallPackagesError::WritePackageManifest(manifest_path.clone(), e))?;
+    tokio::fs::write(&manifest_path, contents)
+        .await
+        .map_err(|e| InstallPackagesError::WritePackageManifest(manifest_path.clone(), e))?;
+    Ok(manifest_path)
+}
+
+// Like the `/var/lib/dpkg/info/<package>.list` files written by dpkg, each installed package gets a
+// list of the files it contributed to the layer, one absolute path (as seen from the root of the
+// layer) per line, so it's possible to find out which package installed a file. Directories are
+// left out since packages share them and files replaced by a package later in the install order
+// are only listed for that package.
+pub(crate) async fn write_package_file_lists(
+    install_path: &Path,
+    packages: &[(&str, Vec<&Path>)],
+) -> BuildpackResult<()> {
+    let metadata_dir = install_path.join(PACKAGE_METADATA_DIR);
+    tokio::fs::create_dir_all(&metadata_dir)
+        .await
+        .map_err(|e| InstallPackagesError::WritePackageManifest(metadata_dir.clone(), e))?;
+    for (name, files) in packages {
+        let list_path = metadata_dir.join(package_file_list_name(name));
+        tokio::fs::write(&list_path, format_package_file_list(files))
+            .await
+            .map_err(|e| InstallPackagesError::WritePackageManifest(list_path.clone(), e))?;
+    }
+    Ok(())
+}
+
+// package names can't contain a `/` but the names of packages from download urls aren't checked
+fn package_file_list_name(name: &str) -> String {
+    format!("{}.list", name.replace('/', "_"))
+}
+
+fn format_package_file_list(files: &[&Path]) -> String {
+    let mut file_list = String::new();
+    for file in files {
+        file_list.push('/');
+        file_list.push_str(&file.to_string_lossy());
+        file_list.push('\n');
+    }
+    file_list
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio_tar::{Builder as TarBuilder, Header};
+
+    #[test]
+    fn parse_control_fields_with_continuation_lines() {
+        let fields = parse_control_fields(indoc::indoc! { "
+            Package: curl
+            Version: 8.5.0-2ubuntu10.6
+            Depends: libc6 (>= 2.34),
+             libcurl4t64 (= 8.5.0-2ubuntu10.6)
+            Description: command line tool for transferring data with URL syntax
+             curl is a command line tool for transferring data with URL syntax.
+             .
+             Supported protocols include HTTP and HTTPS.
+        " });
+        assert_eq!(
+            fields,
+            BTreeMap::from([
+                ("Package".to_string(), "curl".to_string()),
+                ("Version".to_string(), "8.5.0-2ubuntu10.6".to_string()),
+                (
+                    "Depends".to_string(),
+                    "libc6 (>= 2.34),\nlibcurl4t64 (= 8.5.0-2ubuntu10.6)".to_string()
+                ),
+                (
+                    "Description".to_string(),
+                    "command line tool for transferring data with URL syntax\ncurl is a command line tool for transferring data with URL syntax.\n.\nSupported protocols include HTTP and HTTPS.".to_string()
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn format_package_file_list_with_absolute_paths() {
+        assert_eq!(
+            format_package_file_list(&[
+                Path::new("usr/bin/curl"),
+                Path::new("usr/share/doc/curl/copyright")
+            ]),
+            "/usr/bin/curl\n/usr/share/doc/curl/copyright\n"
+        );
+        assert_eq!(format_package_file_list(&[]), "");
+        assert_eq!(package_file_list_name("libc6:i386"), "libc6:i386.list");
+        assert_eq!(package_file_list_name("tool/v1"), "tool_v1.list");
+    }
+
+    #[test]
+    fn read_package_control_from_tarball() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let package_control = runtime.block_on(async {
+            let mut builder = TarBuilder::new(Vec::new());
+            for (path, contents) in [
+                ("./control", "Package: nginx-common\nVersion: 1.24.0\n"),
+                (
+                    "./conffiles",
+                    "/etc/nginx/nginx.conf\n\n/etc/default/nginx\n",
+                ),
+                ("./postinst", "#!/bin/sh\n"),
+                (
+                    "./md5sums",
+                    "d41d8cd98f00b204e9800998ecf8427e  usr/sbin/nginx\n",
+                ),
+                ("./preinst", "#!/bin/sh\n"),
+            ] {
+                let mut header = Header::new_gnu();
+                header.set_size(contents.len() as u64);
+                header.set_mode(0o755);
+                builder
+                    .append_data(&mut header, path, contents.as_bytes())
+                    .await
+                    .unwrap();
+            }
+            let tarball = builder.into_inner().await.unwrap();
+
+            PackageControl::read(TarArchive::new(&tarball[..]), Path::new("nginx.deb"))
+                .await
+                .unwrap()
+        });
+        assert_eq!(
+            package_control,
+            PackageControl {
+                fields: BTreeMap::from([
+                    ("Package".to_string(), "nginx-common".to_string()),
+                    ("Version".to_string(), "1.24.0".to_string()),
+                ]),
+                conffiles: vec![
+                    "/etc/nginx/nginx.conf".to_string(),
+                    "/etc/default/nginx".to_string()
+                ],
+                maintainer_scripts: vec!["postinst".to_string(), "preinst".to_string()],
+            }
+        );
+    }
+}
@@ -0,0 +1,522 @@
+use crate::pgp::{load_certs, verify_detached_signature, verify_signed_message};
+use ar::Archive as ArArchive;
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// the archive member written by `debsigs` which is a detached signature over the contents of
+// `debian-binary`, `control.tar.*`, and `data.tar.*` (in that order)
+const DEBSIGS_SIGNATURE_MEMBER: &str = "_gpgorigin";
+
+// the archive member written by `dpkg-sig` which is a clearsigned manifest that lists the size and
+// checksums of every other member of the archive
+const DPKG_SIG_SIGNATURE_MEMBER: &str = "_gpgbuilder";
+
+// How a downloaded package is checked against the key it must be signed with.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum PackageSignature {
+    // a signature embedded in the `.deb` archive by `debsigs` or `dpkg-sig`
+    Embedded {
+        signed_by: String,
+    },
+    // a signature published alongside the `.deb` file (e.g.; `package.deb.asc`)
+    Detached {
+        signature_url: String,
+        signed_by: String,
+    },
+}
+
+#[derive(Debug)]
+pub(crate) enum PackageSignatureError {
+    ReadPackage(std::io::Error),
+    MissingSignature,
+    LoadSigningKey(anyhow::Error),
+    InvalidSignature(anyhow::Error),
+    UnsignedArchiveMember(String),
+    DuplicateArchiveMember(String),
+    UnexpectedArchiveMember(String),
+}
+
+pub(crate) fn verify_embedded_signature(
+    package_path: &Path,
+    signed_by: &str,
+) -> Result<(), PackageSignatureError> {
+    let mut archive =
+        ArArchive::new(File::open(package_path).map_err(PackageSignatureError::ReadPackage)?);
+
+    let mut debsigs_signature = None;
+    let mut dpkg_sig_manifest = None;
+    let mut signed_members = vec![];
+    let mut member_checksums = BTreeMap::new();
+    let mut member_kinds = HashSet::new();
+    let mut index = 0;
+    while let Some(entry) = archive.next_entry() {
+        let mut entry = entry.map_err(PackageSignatureError::ReadPackage)?;
+        let identifier = String::from_utf8_lossy(entry.header().identifier()).to_string();
+        let Some(kind) = package_member_kind(&identifier) else {
+            return Err(PackageSignatureError::UnexpectedArchiveMember(identifier));
+        };
+        if !member_kinds.insert(kind.to_string()) {
+            return Err(PackageSignatureError::DuplicateArchiveMember(identifier));
+        }
+        if identifier == DEBSIGS_SIGNATURE_MEMBER {
+            debsigs_signature = Some(read_member(&mut entry)?);
+        } else if identifier == DPKG_SIG_SIGNATURE_MEMBER {
+            dpkg_sig_manifest = Some(read_member(&mut entry)?);
+        } else if !identifier.starts_with("_gpg") {
+            // both tools sign the members that make up the package itself
+            let size = entry.header().size();
+            member_checksums.insert(identifier, (sha1_member(&mut entry)?, size));
+            signed_members.push(index);
+        }
+        index += 1;
+    }
+
+    if let Some(signature) = debsigs_signature {
+        let reader = BufReader::new(ArchiveMembersReader::new(archive, signed_members));
+        let certs = load_certs(signed_by).map_err(PackageSignatureError::LoadSigningKey)?;
+        verify_detached_signature(certs, &signature, reader)
+            .map_err(PackageSignatureError::InvalidSignature)
+    } else if let Some(manifest) = dpkg_sig_manifest {
+        let certs = load_certs(signed_by).map_err(PackageSignatureError::LoadSigningKey)?;
+        let manifest = verify_signed_message(certs, &manifest)
+            .map_err(PackageSignatureError::InvalidSignature)?;
+        check_dpkg_sig_manifest(&String::from_utf8_lossy(&manifest), &member_checksums)
+    } else {
+        Err(PackageSignatureError::MissingSignature)
+    }
+}
+
+// The kinds of members a package archive is made of, each of which can only appear once. Every
+// `control.tar.*` and `data.tar.*` member is extracted so anything else, like a second
+// `data.tar.xz` ahead of the signed one, could otherwise be installed without being verified.
+fn package_member_kind(identifier: &str) -> Option<&str> {
+    match identifier {
+        "debian-binary" => Some(identifier),
+        _ if identifier.starts_with("control.tar") => Some("control.tar"),
+        _ if identifier.starts_with("data.tar") => Some("data.tar"),
+        _ if identifier.starts_with("_gpg") => Some(identifier),
+        _ => None,
+    }
+}
+
+pub(crate) fn verify_detached_package_signature(
+    package_path: &Path,
+    signature: &[u8],
+    signed_by: &str,
+) -> Result<(), PackageSignatureError> {
+    let reader =
+        BufReader::new(File::open(package_path).map_err(PackageSignatureError::ReadPackage)?);
+    let certs = load_certs(signed_by).map_err(PackageSignatureError::LoadSigningKey)?;
+    verify_detached_signature(certs, signature, reader)
+        .map_err(PackageSignatureError::InvalidSignature)
+}
+
+fn read_member(reader: &mut impl Read) -> Result<Vec<u8>, PackageSignatureError> {
+    let mut contents = vec![];
+    reader
+        .read_to_end(&mut contents)
+        .map_err(PackageSignatureError::ReadPackage)?;
+    Ok(contents)
+}
+
+fn sha1_member(reader: impl Read) -> Result<String, PackageSignatureError> {
+    let mut reader = BufReader::new(reader);
+    let mut hasher = Sha1::new();
+    loop {
+        let buffer = reader
+            .fill_buf()
+            .map_err(PackageSignatureError::ReadPackage)?;
+        if buffer.is_empty() {
+            break;
+        }
+        hasher.update(buffer);
+        let length = buffer.len();
+        reader.consume(length);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+// The manifest lists each signed member as `<md5> <sha1> <size> <name>` below the `Files:` field.
+// The SHA1 checksum and size are checked since MD5 alone isn't collision resistant.
+fn check_dpkg_sig_manifest(
+    manifest: &str,
+    member_checksums: &BTreeMap<String, (String, u64)>,
+) -> Result<(), PackageSignatureError> {
+    let signed_checksums = manifest
+        .lines()
+        .skip_while(|line| !line.starts_with("Files:"))
+        .skip(1)
+        .take_while(|line| line.starts_with([' ', '\t']))
+        .filter_map(
+            |line| match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [_md5, sha1, size, name] => Some((name, (sha1, size.parse::<u64>().ok()?))),
+                _ => None,
+            },
+        )
+        .collect::<BTreeMap<_, _>>();
+
+    for (name, (sha1, size)) in member_checksums {
+        if signed_checksums.get(name.as_str()) != Some(&(sha1.as_str(), *size)) {
+            return Err(PackageSignatureError::UnsignedArchiveMember(name.clone()));
+        }
+    }
+    Ok(())
+}
+
+// Reads the contents of several archive members one after the other. Each read jumps back to
+// the current member since an archive entry borrows the archive it was read from.
+struct ArchiveMembersReader {
+    archive: ArArchive<File>,
+    members: Vec<usize>,
+    member_offset: u64,
+}
+
+impl ArchiveMembersReader {
+    fn new(archive: ArArchive<File>, mut members: Vec<usize>) -> Self {
+        members.reverse();
+        Self {
+            archive,
+            members,
+            member_offset: 0,
+        }
+    }
+}
+
+impl Read for ArchiveMembersReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while let Some(member) = self.members.last() {
+            let mut entry = self.archive.jump_to_entry(*member)?;
+            entry.seek(SeekFrom::Start(self.member_offset))?;
+            let bytes_read = entry.read(buf)?;
+            if bytes_read > 0 {
+                self.member_offset += bytes_read as u64;
+                return Ok(bytes_read);
+            }
+            self.members.pop();
+            self.member_offset = 0;
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sequoia_openpgp::Cert;
+    use sequoia_openpgp::cert::CertBuilder;
+    use sequoia_openpgp::crypto::KeyPair;
+    use sequoia_openpgp::policy::StandardPolicy;
+    use sequoia_openpgp::serialize::SerializeInto;
+    use sequoia_openpgp::serialize::stream::{Armorer, Message, Signer};
+    use std::io::Write;
+
+    const DEBIAN_BINARY: &[u8] = b"2.0\n";
+    const CONTROL_TARBALL: &[u8] = b"control tarball contents";
+    const DATA_TARBALL: &[u8] = b"data tarball contents";
+
+    #[test]
+    fn verify_debsigs_signature() {
+        let (signed_by, keypair) = create_signing_key();
+        let signature = sign_detached(
+            &keypair,
+            &[DEBIAN_BINARY, CONTROL_TARBALL, DATA_TARBALL].concat(),
+        );
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", DATA_TARBALL),
+                ("_gpgorigin", &signature),
+            ],
+        );
+
+        verify_embedded_signature(&package_path, &signed_by).unwrap();
+    }
+
+    #[test]
+    fn verify_debsigs_signature_from_another_key() {
+        let (_, keypair) = create_signing_key();
+        let (other_signed_by, _) = create_signing_key();
+        let signature = sign_detached(
+            &keypair,
+            &[DEBIAN_BINARY, CONTROL_TARBALL, DATA_TARBALL].concat(),
+        );
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", DATA_TARBALL),
+                ("_gpgorigin", &signature),
+            ],
+        );
+
+        match verify_embedded_signature(&package_path, &other_signed_by).unwrap_err() {
+            PackageSignatureError::InvalidSignature(_) => {}
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn verify_debsigs_signature_of_modified_package() {
+        let (signed_by, keypair) = create_signing_key();
+        let signature = sign_detached(
+            &keypair,
+            &[DEBIAN_BINARY, CONTROL_TARBALL, DATA_TARBALL].concat(),
+        );
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", b"modified data tarball contents"),
+                ("_gpgorigin", &signature),
+            ],
+        );
+
+        match verify_embedded_signature(&package_path, &signed_by).unwrap_err() {
+            PackageSignatureError::InvalidSignature(_) => {}
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn verify_dpkg_sig_signature() {
+        let (signed_by, keypair) = create_signing_key();
+        let manifest = create_dpkg_sig_manifest(&[
+            ("debian-binary", DEBIAN_BINARY),
+            ("control.tar.xz", CONTROL_TARBALL),
+            ("data.tar.xz", DATA_TARBALL),
+        ]);
+        let signed_manifest = sign_cleartext(&keypair, manifest.as_bytes());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", DATA_TARBALL),
+                ("_gpgbuilder", &signed_manifest),
+            ],
+        );
+
+        verify_embedded_signature(&package_path, &signed_by).unwrap();
+    }
+
+    #[test]
+    fn verify_dpkg_sig_signature_of_modified_package() {
+        let (signed_by, keypair) = create_signing_key();
+        let manifest = create_dpkg_sig_manifest(&[
+            ("debian-binary", DEBIAN_BINARY),
+            ("control.tar.xz", CONTROL_TARBALL),
+            ("data.tar.xz", DATA_TARBALL),
+        ]);
+        let signed_manifest = sign_cleartext(&keypair, manifest.as_bytes());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", b"modified data tarball contents"),
+                ("_gpgbuilder", &signed_manifest),
+            ],
+        );
+
+        match verify_embedded_signature(&package_path, &signed_by).unwrap_err() {
+            PackageSignatureError::UnsignedArchiveMember(name) => {
+                assert_eq!(name, "data.tar.xz");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn verify_dpkg_sig_signature_with_duplicate_member() {
+        let (signed_by, keypair) = create_signing_key();
+        let manifest = create_dpkg_sig_manifest(&[
+            ("debian-binary", DEBIAN_BINARY),
+            ("control.tar.xz", CONTROL_TARBALL),
+            ("data.tar.xz", DATA_TARBALL),
+        ]);
+        let signed_manifest = sign_cleartext(&keypair, manifest.as_bytes());
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", b"unsigned data tarball contents"),
+                ("data.tar.xz", DATA_TARBALL),
+                ("_gpgbuilder", &signed_manifest),
+            ],
+        );
+
+        match verify_embedded_signature(&package_path, &signed_by).unwrap_err() {
+            PackageSignatureError::DuplicateArchiveMember(name) => {
+                assert_eq!(name, "data.tar.xz");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn verify_package_with_unexpected_member() {
+        let (signed_by, _) = create_signing_key();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", DATA_TARBALL),
+                ("extra", b"extra contents"),
+            ],
+        );
+
+        match verify_embedded_signature(&package_path, &signed_by).unwrap_err() {
+            PackageSignatureError::UnexpectedArchiveMember(name) => {
+                assert_eq!(name, "extra");
+            }
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn verify_unsigned_package() {
+        let (signed_by, _) = create_signing_key();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", DATA_TARBALL),
+            ],
+        );
+
+        match verify_embedded_signature(&package_path, &signed_by).unwrap_err() {
+            PackageSignatureError::MissingSignature => {}
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    #[test]
+    fn verify_detached_signature_of_package() {
+        let (signed_by, keypair) = create_signing_key();
+        let temp_dir = tempfile::tempdir().unwrap();
+        let package_path = create_package(
+            temp_dir.path(),
+            &[
+                ("debian-binary", DEBIAN_BINARY),
+                ("control.tar.xz", CONTROL_TARBALL),
+                ("data.tar.xz", DATA_TARBALL),
+            ],
+        );
+        let signature = sign_detached(&keypair, &std::fs::read(&package_path).unwrap());
+
+        verify_detached_package_signature(&package_path, &signature, &signed_by).unwrap();
+
+        match verify_detached_package_signature(&package_path, b"not a signature", &signed_by)
+            .unwrap_err()
+        {
+            PackageSignatureError::InvalidSignature(_) => {}
+            e => panic!("Not the expected error - {e:?}"),
+        }
+    }
+
+    fn create_signing_key() -> (String, KeyPair) {
+        let (cert, _) = CertBuilder::general_purpose(Some("Test Signing Key <test@example.com>"))
+            .generate()
+            .unwrap();
+        let keypair = signing_keypair(&cert);
+        let armored_public_key =
+            String::from_utf8(cert.strip_secret_key_material().armored().to_vec().unwrap())
+                .unwrap();
+        (armored_public_key, keypair)
+    }
+
+    fn signing_keypair(cert: &Cert) -> KeyPair {
+        cert.keys()
+            .unencrypted_secret()
+            .with_policy(&StandardPolicy::new(), None)
+            .supported()
+            .alive()
+            .revoked(false)
+            .for_signing()
+            .next()
+            .unwrap()
+            .key()
+            .clone()
+            .into_keypair()
+            .unwrap()
+    }
+
+    fn sign_detached(keypair: &KeyPair, data: &[u8]) -> Vec<u8> {
+        let mut signature = vec![];
+        let message = Armorer::new(Message::new(&mut signature))
+            .kind(sequoia_openpgp::armor::Kind::Signature)
+            .build()
+            .unwrap();
+        let mut signer = Signer::new(message, keypair.clone())
+            .unwrap()
+            .detached()
+            .build()
+            .unwrap();
+        signer.write_all(data).unwrap();
+        signer.finalize().unwrap();
+        signature
+    }
+
+    fn sign_cleartext(keypair: &KeyPair, data: &[u8]) -> Vec<u8> {
+        let mut signed_message = vec![];
+        let message = Message::new(&mut signed_message);
+        let mut signer = Signer::new(message, keypair.clone())
+            .unwrap()
+            .cleartext()
+            .build()
+            .unwrap();
+        signer.write_all(data).unwrap();
+        signer.finalize().unwrap();
+        signed_message
+    }
+
+    fn create_dpkg_sig_manifest(members: &[(&str, &[u8])]) -> String {
+        let files = members
+            .iter()
+            .map(|(name, contents)| {
+                format!(
+                    "\t{md5} {sha1} {size} {name}",
+                    md5 = "d41d8cd98f00b204e9800998ecf8427e",
+                    sha1 = hex::encode(Sha1::digest(contents)),
+                    size = contents.len()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "Version: 4\nSigner: \nDate: Mon Jan  1 00:00:00 2024\nRole: builder\nFiles: \n{files}\n"
+        )
+    }
+
+    fn create_package(dir: &Path, members: &[(&str, &[u8])]) -> std::path::PathBuf {
+        let package_path = dir.join("package.deb");
+        let mut builder = ar::Builder::new(File::create(&package_path).unwrap());
+        for (name, contents) in members {
+            builder
+                .append(
+                    &ar::Header::new(name.as_bytes().to_vec(), contents.len() as u64),
+                    *contents,
+                )
+                .unwrap();
+        }
+        package_path
+    }
+}
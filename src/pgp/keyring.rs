@@ -0,0 +1,79 @@
+use crate::pgp::parse_certs;
+use anyhow::Context;
+use sequoia_openpgp::Cert;
+use sequoia_openpgp::Result;
+
+// `signed_by` is either an ASCII-armored key block or the path to a keyring file. Vendors often only
+// distribute a binary keyring (e.g.; the `.gpg` files placed in `/etc/apt/keyrings`) so keyring
+// files can be binary or ASCII-armored. Relative paths are resolved from the application directory
+// which is the working directory for both the build and the CLI.
+pub(crate) fn load_certs(signed_by: &str) -> Result<Vec<Cert>> {
+    let signed_by = signed_by.trim();
+    if is_armored_key(signed_by) {
+        parse_certs(signed_by.as_bytes())
+    } else {
+        let keyring = std::fs::read(signed_by)
+            .with_context(|| format!("Failed to read keyring file {signed_by}"))?;
+        parse_certs(&keyring).with_context(|| format!("Failed to parse keyring file {signed_by}"))
+    }
+}
+
+fn is_armored_key(signed_by: &str) -> bool {
+    signed_by.starts_with("-----BEGIN PGP")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use sequoia_openpgp::armor::Kind;
+    use sequoia_openpgp::cert::CertBuilder;
+    use sequoia_openpgp::serialize::SerializeInto;
+
+    fn create_cert() -> Cert {
+        CertBuilder::general_purpose(Some("test@example.com"))
+            .generate()
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn load_certs_from_armored_key() {
+        let cert = create_cert();
+        let armored = String::from_utf8(cert.armored().to_vec().unwrap()).unwrap();
+        assert_eq!(load_certs(&format!("\n{armored}")).unwrap(), vec![cert]);
+    }
+
+    #[test]
+    fn load_certs_from_keyring_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let cert = create_cert();
+        let other_cert = create_cert();
+
+        let binary_keyring = temp_dir.path().join("vendor.gpg");
+        let mut keyring = cert.to_vec().unwrap();
+        keyring.extend(other_cert.to_vec().unwrap());
+        std::fs::write(&binary_keyring, keyring).unwrap();
+        assert_eq!(
+            load_certs(&binary_keyring.to_string_lossy()).unwrap(),
+            vec![cert.clone(), other_cert]
+        );
+
+        let armored_keyring = temp_dir.path().join("vendor.asc");
+        let mut writer = sequoia_openpgp::armor::Writer::new(vec![], Kind::PublicKey).unwrap();
+        std::io::Write::write_all(&mut writer, &cert.to_vec().unwrap()).unwrap();
+        std::fs::write(&armored_keyring, writer.finalize().unwrap()).unwrap();
+        assert_eq!(
+            load_certs(&armored_keyring.to_string_lossy()).unwrap(),
+            vec![cert]
+        );
+    }
+
+    #[test]
+    fn load_certs_from_missing_keyring_file() {
+        let error = load_certs("keys/missing.gpg").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to read keyring file keys/missing.gpg"
+        );
+    }
+}
@@ -1,3 +1,7 @@
 pub(crate) use cert_helper::*;
+pub(crate) use keyring::*;
+pub(crate) use verify::*;
 
 mod cert_helper;
+mod keyring;
+mod verify;
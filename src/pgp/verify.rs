@@ -0,0 +1,36 @@
+use crate::pgp::CertHelper;
+use sequoia_openpgp::Cert;
+use sequoia_openpgp::Result;
+use sequoia_openpgp::cert::CertParser;
+use sequoia_openpgp::parse::Parse;
+use sequoia_openpgp::parse::stream::{DetachedVerifierBuilder, VerifierBuilder};
+use sequoia_openpgp::policy::StandardPolicy;
+use std::io::Read;
+
+// `keyring` holds one or more certificates, either ASCII-armored or binary
+pub(crate) fn parse_certs(keyring: &[u8]) -> Result<Vec<Cert>> {
+    CertParser::from_bytes(keyring)?.collect::<Result<Vec<Cert>>>()
+}
+
+// Verifies a detached signature (e.g.; a `.asc` file published alongside a package) over the data
+// read from `reader`.
+pub(crate) fn verify_detached_signature<R: Read + Send + Sync>(
+    certs: Vec<Cert>,
+    signature: &[u8],
+    reader: R,
+) -> Result<()> {
+    let policy = StandardPolicy::new();
+    DetachedVerifierBuilder::from_bytes(signature)?
+        .with_policy(&policy, None, CertHelper::new(certs))?
+        .verify_reader(reader)
+}
+
+// Verifies an inline signed message (e.g.; a clearsigned release file) and returns its contents.
+pub(crate) fn verify_signed_message(certs: Vec<Cert>, message: &[u8]) -> Result<Vec<u8>> {
+    let policy = StandardPolicy::new();
+    let mut verifier =
+        VerifierBuilder::from_bytes(message)?.with_policy(&policy, None, CertHelper::new(certs))?;
+    let mut contents = vec![];
+    verifier.read_to_end(&mut contents)?;
+    Ok(contents)
+}
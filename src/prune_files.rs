@@ -0,0 +1,171 @@
+use crate::config::PruneTarget;
+use crate::install_packages::InstallPackagesError;
+use crate::{BuildpackResult, format_size};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use walkdir::{DirEntry, WalkDir};
+
+const AR_MAGIC: &[u8; 8] = b"!<arch>\n";
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct PrunedFiles {
+    pub(crate) files: usize,
+    pub(crate) bytes_saved: u64,
+}
+
+impl Display for PrunedFiles {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Removed {files} files, saving {size}",
+            files = self.files,
+            size = format_size(self.bytes_saved)
+        )
+    }
+}
+
+pub(crate) fn prune_files(
+    install_path: &Path,
+    prune: &[PruneTarget],
+) -> BuildpackResult<PrunedFiles> {
+    let mut paths_to_remove = vec![];
+
+    for prune_target in prune {
+        match prune_target {
+            PruneTarget::StaticLibs => {
+                for entry in WalkDir::new(install_path).into_iter().flatten() {
+                    if is_static_library(&entry)
+                        .map_err(|e| InstallPackagesError::PruneFile(entry.path().into(), e))?
+                    {
+                        paths_to_remove.push(entry.into_path());
+                    }
+                }
+            }
+            PruneTarget::Headers => {
+                let include_dir = install_path.join("usr/include");
+                if include_dir.is_dir() {
+                    paths_to_remove.push(include_dir);
+                }
+            }
+        }
+    }
+
+    let mut pruned_files = PrunedFiles::default();
+
+    for path in paths_to_remove {
+        // a static library can be listed again under a directory that was already removed
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            for entry in WalkDir::new(&path).into_iter().flatten() {
+                if !entry.file_type().is_dir() {
+                    pruned_files.files += 1;
+                    pruned_files.bytes_saved +=
+                        entry.metadata().map(|m| m.len()).unwrap_or_default();
+                }
+            }
+            fs::remove_dir_all(&path)
+        } else {
+            pruned_files.files += 1;
+            pruned_files.bytes_saved += metadata.len();
+            fs::remove_file(&path)
+        }
+        .map_err(|e| InstallPackagesError::PruneFile(path.clone(), e))?;
+    }
+
+    Ok(pruned_files)
+}
+
+// Only files with the `ar` archive signature are removed so an unrelated file that happens to
+// use the `.a` extension is left alone. Symlinks to static libraries (e.g.; `libfoo.a ->
+// libfoo-1.2.a`) are also removed.
+fn is_static_library(entry: &DirEntry) -> std::io::Result<bool> {
+    if entry
+        .path()
+        .extension()
+        .is_none_or(|extension| extension != "a")
+    {
+        return Ok(false);
+    }
+    if entry.path_is_symlink() {
+        return Ok(true);
+    }
+    if !entry.file_type().is_file() {
+        return Ok(false);
+    }
+    let mut magic = [0; AR_MAGIC.len()];
+    match fs::File::open(entry.path())?.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == AR_MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn prune_files_removes_static_libraries_and_headers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let install_path = temp_dir.path();
+        for (file, contents) in [
+            ("usr/lib/x86_64-linux-gnu/libexample.a", "!<arch>\nobjects"),
+            ("usr/lib/x86_64-linux-gnu/libexample.so.1", "shared library"),
+            ("usr/include/example.h", "header"),
+            ("usr/include/example/nested.h", "nested header"),
+            ("usr/share/doc/example/not-an-archive.a", "text"),
+        ] {
+            let path = install_path.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        symlink(
+            "libexample.a",
+            install_path.join("usr/lib/x86_64-linux-gnu/libexample-1.0.a"),
+        )
+        .unwrap();
+
+        let pruned_files = prune_files(
+            install_path,
+            &[PruneTarget::StaticLibs, PruneTarget::Headers],
+        )
+        .unwrap();
+
+        assert_eq!(pruned_files.files, 4);
+        assert!(!install_path.join("usr/include").exists());
+        assert!(
+            !install_path
+                .join("usr/lib/x86_64-linux-gnu/libexample.a")
+                .exists()
+        );
+        assert!(
+            !install_path
+                .join("usr/lib/x86_64-linux-gnu/libexample-1.0.a")
+                .is_symlink()
+        );
+        assert!(
+            install_path
+                .join("usr/lib/x86_64-linux-gnu/libexample.so.1")
+                .exists()
+        );
+        assert!(
+            install_path
+                .join("usr/share/doc/example/not-an-archive.a")
+                .exists()
+        );
+    }
+
+    #[test]
+    fn prune_files_with_nothing_to_prune() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert_eq!(
+            prune_files(temp_dir.path(), &[PruneTarget::Headers]).unwrap(),
+            PrunedFiles::default()
+        );
+    }
+}
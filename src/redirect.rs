@@ -0,0 +1,229 @@
+use anyhow::anyhow;
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::header::{
+    AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, COOKIE, HeaderMap, LOCATION, PROXY_AUTHORIZATION,
+    WWW_AUTHENTICATE,
+};
+use reqwest::{Method, Request, Response, StatusCode, Url};
+use reqwest_middleware::{Middleware, Next};
+
+// the same limit the HTTP client uses when it follows redirects itself
+const MAX_REDIRECTS: usize = 10;
+
+// NOTE: Redirects are followed here instead of by the HTTP client so the headers configured for a
+//       download (e.g.; a `PRIVATE-TOKEN` read from the environment) are dropped when a redirect goes
+//       to a different host. The client only drops the standard credential headers (e.g.;
+//       `Authorization`) so any other secret would be sent to whatever host a download redirects
+//       to (e.g.; a signed CDN url). Header values marked as sensitive are treated as credentials
+//       for the host they were configured for. Every hop goes through the rest of the middleware
+//       so retries and host credentials apply to the host being requested.
+pub(crate) struct RedirectMiddleware;
+
+#[async_trait]
+impl Middleware for RedirectMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let mut req = req;
+        for _ in 0..=MAX_REDIRECTS {
+            // a request with a streaming body can't be sent again so its redirect is returned as is
+            let duplicate_request = req.try_clone();
+            let response = next.clone().run(req, extensions).await?;
+            let Some(redirect) = duplicate_request
+                .and_then(|duplicate_request| follow_redirect(duplicate_request, &response))
+            else {
+                return Ok(response);
+            };
+            req = redirect;
+        }
+        Err(reqwest_middleware::Error::Middleware(anyhow!(
+            "Too many redirects requesting {}",
+            req.url()
+        )))
+    }
+}
+
+// Builds the request for the redirect in `response`, if it is one.
+fn follow_redirect(mut req: Request, response: &Response) -> Option<Request> {
+    let status = response.status();
+    if !status.is_redirection() || status == StatusCode::NOT_MODIFIED {
+        return None;
+    }
+    let location = response.headers().get(LOCATION)?.to_str().ok()?;
+    let url = req.url().join(location).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+
+    let keeps_method = matches!(
+        status,
+        StatusCode::TEMPORARY_REDIRECT | StatusCode::PERMANENT_REDIRECT
+    );
+    if !keeps_method && req.method() != Method::GET && req.method() != Method::HEAD {
+        *req.method_mut() = Method::GET;
+        *req.body_mut() = None;
+        req.headers_mut().remove(CONTENT_TYPE);
+        req.headers_mut().remove(CONTENT_LENGTH);
+    }
+
+    if is_cross_host(req.url(), &url) {
+        remove_credentials(req.headers_mut());
+    }
+    *req.url_mut() = url;
+    Some(req)
+}
+
+fn is_cross_host(previous: &Url, next: &Url) -> bool {
+    next.host_str() != previous.host_str()
+        || next.port_or_known_default() != previous.port_or_known_default()
+        || next.scheme() != previous.scheme()
+}
+
+fn remove_credentials(headers: &mut HeaderMap) {
+    for name in [AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, WWW_AUTHENTICATE] {
+        headers.remove(name);
+    }
+    let sensitive_headers = headers
+        .iter()
+        .filter(|(_, value)| value.is_sensitive())
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    for name in sensitive_headers {
+        headers.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use reqwest_middleware::ClientBuilder;
+    use std::sync::Mutex;
+
+    // answers for two hosts, the first redirects every request to the second
+    #[derive(Default)]
+    struct StubHosts {
+        requests: Mutex<Vec<(String, HeaderMap)>>,
+    }
+
+    #[async_trait]
+    impl Middleware for StubHosts {
+        async fn handle(
+            &self,
+            req: Request,
+            _extensions: &mut Extensions,
+            _next: Next<'_>,
+        ) -> reqwest_middleware::Result<Response> {
+            self.requests
+                .lock()
+                .unwrap()
+                .push((req.url().to_string(), req.headers().clone()));
+            let response = match req.url().host_str() {
+                Some("git.example.com") => {
+                    http::Response::builder().status(StatusCode::FOUND).header(
+                        LOCATION,
+                        "https://cdn.example.com/signed/package.deb?sig=abc",
+                    )
+                }
+                _ => http::Response::builder().status(StatusCode::OK),
+            };
+            Ok(Response::from(response.body("").unwrap()))
+        }
+    }
+
+    fn send(client: &reqwest_middleware::ClientWithMiddleware, url: &str, headers: HeaderMap) {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(client.get(url).headers(headers).send())
+            .unwrap();
+    }
+
+    #[test]
+    fn configured_headers_are_dropped_on_cross_host_redirect() {
+        rustls::crypto::ring::default_provider()
+            .install_default()
+            .ok();
+        let stub_hosts = std::sync::Arc::new(StubHosts::default());
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(RedirectMiddleware)
+            .with_arc(stub_hosts.clone())
+            .build();
+
+        let mut private_token = HeaderValue::from_static("secret");
+        private_token.set_sensitive(true);
+        let mut headers = HeaderMap::new();
+        headers.insert("private-token", private_token);
+        headers.insert(
+            "accept",
+            HeaderValue::from_static("application/octet-stream"),
+        );
+        send(
+            &client,
+            "https://git.example.com/api/v4/projects/1/packages/generic/package.deb",
+            headers,
+        );
+
+        let requests = stub_hosts.requests.lock().unwrap();
+        assert_eq!(
+            requests
+                .iter()
+                .map(|(url, _)| url.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://git.example.com/api/v4/projects/1/packages/generic/package.deb",
+                "https://cdn.example.com/signed/package.deb?sig=abc"
+            ]
+        );
+        assert_eq!(requests[0].1["private-token"], "secret");
+        assert!(!requests[1].1.contains_key("private-token"));
+        assert_eq!(requests[1].1["accept"], "application/octet-stream");
+    }
+
+    #[test]
+    fn headers_are_kept_on_same_host_redirect() {
+        let mut private_token = HeaderValue::from_static("secret");
+        private_token.set_sensitive(true);
+        let mut req = Request::new(
+            Method::GET,
+            "https://git.example.com/old/package.deb".parse().unwrap(),
+        );
+        req.headers_mut().insert("private-token", private_token);
+        let response = Response::from(
+            http::Response::builder()
+                .status(StatusCode::MOVED_PERMANENTLY)
+                .header(LOCATION, "/new/package.deb")
+                .body("")
+                .unwrap(),
+        );
+
+        let redirect = follow_redirect(req, &response).unwrap();
+
+        assert_eq!(
+            redirect.url().as_str(),
+            "https://git.example.com/new/package.deb"
+        );
+        assert_eq!(redirect.headers()["private-token"], "secret");
+    }
+
+    #[test]
+    fn responses_that_arent_redirects_are_returned() {
+        let req = Request::new(
+            Method::GET,
+            "https://git.example.com/package.deb".parse().unwrap(),
+        );
+        let response = Response::from(
+            http::Response::builder()
+                .status(StatusCode::OK)
+                .header(LOCATION, "https://cdn.example.com/package.deb")
+                .body("")
+                .unwrap(),
+        );
+
+        assert!(follow_redirect(req, &response).is_none());
+    }
+}
@@ -0,0 +1,391 @@
+use crate::config::http_config::{HttpConfig, RetryJitter};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use http::Extensions;
+use reqwest::{Request, Response};
+use reqwest_middleware::{Middleware, Next};
+use reqwest_retry::policies::ExponentialBackoff;
+use reqwest_retry::{
+    DefaultRetryableStrategy, Jitter, RetryDecision, RetryError, RetryPolicy, Retryable,
+    RetryableStrategy,
+};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+const DEFAULT_MAX_RETRIES: u32 = 5;
+const DEFAULT_HOST_RETRY_BUDGET: Duration = Duration::from_mins(2);
+
+// Response headers set by servers, load balancers, and CDNs to identify a request, which the
+// operators of a repository need to find a failed request in their logs.
+const TRACE_HEADERS: [&str; 6] = [
+    "x-request-id",
+    "x-amz-request-id",
+    "x-amz-cf-id",
+    "cf-ray",
+    "x-served-by",
+    "x-cache",
+];
+
+// Requests that fail with a transient error are retried with an exponential backoff, the same as
+// `reqwest_retry::RetryTransientMiddleware`, except the time spent waiting between retries is also
+// tracked for each host. Once a host has used up its budget, requests to it fail on the next
+// transient error instead of retrying, so a mirror that's down doesn't hold up the build while
+// the requests to the other sources keep their own budgets.
+pub(crate) struct HostRetryMiddleware {
+    retry_policy: ExponentialBackoff,
+    budgets: HostRetryBudgets,
+}
+
+impl HostRetryMiddleware {
+    pub(crate) fn new(http_config: &HttpConfig) -> Self {
+        let jitter = match http_config.retry_jitter.unwrap_or(RetryJitter::Full) {
+            RetryJitter::Full => Jitter::Full,
+            RetryJitter::Bounded => Jitter::Bounded,
+            RetryJitter::None => Jitter::None,
+        };
+        Self {
+            retry_policy: ExponentialBackoff::builder()
+                .jitter(jitter)
+                .build_with_max_retries(http_config.max_retries.unwrap_or(DEFAULT_MAX_RETRIES)),
+            budgets: HostRetryBudgets::new(
+                http_config
+                    .host_retry_budget
+                    .unwrap_or(DEFAULT_HOST_RETRY_BUDGET),
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl Middleware for HostRetryMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let host = request_host(&req);
+        let start_time = SystemTime::now();
+        let mut past_retries = 0;
+        let mut attempts = vec![];
+        loop {
+            let duplicate_request = req.try_clone().ok_or_else(|| {
+                reqwest_middleware::Error::Middleware(anyhow!(
+                    "Request object is not cloneable. Are you passing a streaming body?"
+                ))
+            })?;
+
+            let result = next.clone().run(duplicate_request, extensions).await;
+            attempts.push(RequestAttempt::from(&result));
+
+            if let Some(Retryable::Transient) = DefaultRetryableStrategy.handle(&result)
+                && let RetryDecision::Retry { execute_after } =
+                    self.retry_policy.should_retry(start_time, past_retries)
+            {
+                let delay = execute_after
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default();
+                if self.budgets.try_spend(&host, delay) {
+                    tokio::time::sleep(delay).await;
+                    past_retries += 1;
+                    continue;
+                }
+                info!(
+                    host,
+                    "Retry budget for {host} is used up, not retrying {}",
+                    req.url()
+                );
+            }
+
+            let attempts = RequestAttempts(attempts);
+            break match result {
+                Ok(mut response) => {
+                    response.extensions_mut().insert(attempts);
+                    Ok(response)
+                }
+                Err(err) => {
+                    let source = if past_retries > 0 {
+                        RetryError::WithRetries {
+                            retries: past_retries,
+                            err,
+                        }
+                    } else {
+                        RetryError::Error(err)
+                    };
+                    Err(reqwest_middleware::Error::Middleware(
+                        AttemptedRequestError {
+                            source: source.into(),
+                            attempts,
+                        }
+                        .into(),
+                    ))
+                }
+            };
+        }
+    }
+}
+
+// Like `Response::error_for_status` except the attempts made for the request are kept with the
+// error so they can be included in the error output.
+pub(crate) fn error_for_status(mut response: Response) -> reqwest_middleware::Result<Response> {
+    let attempts = response.extensions_mut().remove::<RequestAttempts>();
+    match (response.error_for_status(), attempts) {
+        (Ok(mut response), Some(attempts)) => {
+            response.extensions_mut().insert(attempts);
+            Ok(response)
+        }
+        (Ok(response), None) => Ok(response),
+        (Err(e), Some(attempts)) => Err(reqwest_middleware::Error::Middleware(
+            AttemptedRequestError {
+                source: e.into(),
+                attempts,
+            }
+            .into(),
+        )),
+        (Err(e), None) => Err(reqwest_middleware::Error::Reqwest(e)),
+    }
+}
+
+// The attempts made for a failed request, when it was sent by a client with the retry middleware.
+pub(crate) fn request_attempts(error: &reqwest_middleware::Error) -> Option<&RequestAttempts> {
+    match error {
+        reqwest_middleware::Error::Middleware(e) => e
+            .downcast_ref::<AttemptedRequestError>()
+            .map(|e| &e.attempts),
+        reqwest_middleware::Error::Reqwest(_) => None,
+    }
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct RequestAttempts(Vec<RequestAttempt>);
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct RequestAttempt {
+    // the response status (e.g.; `503 Service Unavailable`) or the error when there's no response
+    outcome: String,
+    trace_headers: Vec<(&'static str, String)>,
+}
+
+impl From<&reqwest_middleware::Result<Response>> for RequestAttempt {
+    fn from(result: &reqwest_middleware::Result<Response>) -> Self {
+        match result {
+            Ok(response) => RequestAttempt {
+                outcome: response.status().to_string(),
+                trace_headers: TRACE_HEADERS
+                    .into_iter()
+                    .filter_map(|name| {
+                        let value = response.headers().get(name)?.to_str().ok()?;
+                        Some((name, value.to_string()))
+                    })
+                    .collect(),
+            },
+            Err(e) => RequestAttempt {
+                outcome: e.to_string(),
+                trace_headers: vec![],
+            },
+        }
+    }
+}
+
+impl Display for RequestAttempts {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lines = self
+            .0
+            .iter()
+            .enumerate()
+            .map(|(index, attempt)| {
+                let line = format!("Attempt {}: {}", index + 1, attempt.outcome);
+                if attempt.trace_headers.is_empty() {
+                    return line;
+                }
+                let trace_headers = attempt
+                    .trace_headers
+                    .iter()
+                    .map(|(name, value)| format!("{name}: {value}"))
+                    .collect::<Vec<_>>();
+                format!("{line} ({})", trace_headers.join(", "))
+            })
+            .collect::<Vec<_>>();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+// Displayed the same as the error it wraps so only the output that asks for the attempts shows them.
+#[derive(Debug)]
+struct AttemptedRequestError {
+    source: anyhow::Error,
+    attempts: RequestAttempts,
+}
+
+impl Display for AttemptedRequestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl std::error::Error for AttemptedRequestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source.chain().nth(1)
+    }
+}
+
+// requests to the same host on a different port are counted separately
+fn request_host(req: &Request) -> String {
+    let url = req.url();
+    match (url.host_str(), url.port_or_known_default()) {
+        (Some(host), Some(port)) => format!("{host}:{port}"),
+        (Some(host), None) => host.to_string(),
+        (None, _) => String::new(),
+    }
+}
+
+struct HostRetryBudgets {
+    budget: Duration,
+    spent: Mutex<HashMap<String, Duration>>,
+}
+
+impl HostRetryBudgets {
+    fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            spent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Records the delay against the host's budget and returns `false`, without recording
+    // anything, when waiting that long would go over the budget.
+    fn try_spend(&self, host: &str, delay: Duration) -> bool {
+        let mut spent = self
+            .spent
+            .lock()
+            .expect("Retry budget lock should not be poisoned");
+        let host_spent = spent.entry(host.to_string()).or_default();
+        match host_spent.checked_add(delay) {
+            Some(total) if total <= self.budget => {
+                *host_spent = total;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use reqwest::StatusCode;
+    use reqwest_middleware::ClientBuilder;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // responds with each of the given statuses and headers in turn
+    struct StubResponses {
+        responses: Vec<(StatusCode, Vec<(&'static str, &'static str)>)>,
+        sent: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Middleware for StubResponses {
+        async fn handle(
+            &self,
+            _req: Request,
+            _extensions: &mut Extensions,
+            _next: Next<'_>,
+        ) -> reqwest_middleware::Result<Response> {
+            let (status, headers) = &self.responses[self.sent.fetch_add(1, Ordering::SeqCst)];
+            let mut response = http::Response::builder().status(*status);
+            for (name, value) in headers {
+                response = response.header(*name, *value);
+            }
+            Ok(Response::from(response.body("").unwrap()))
+        }
+    }
+
+    #[test]
+    fn failed_requests_keep_the_attempts_made() {
+        rustls::crypto::ring::default_provider()
+            .install_default()
+            .ok();
+        let client = ClientBuilder::new(reqwest::Client::new())
+            .with(HostRetryMiddleware {
+                retry_policy: ExponentialBackoff::builder()
+                    .retry_bounds(Duration::from_millis(1), Duration::from_millis(1))
+                    .build_with_max_retries(2),
+                budgets: HostRetryBudgets::new(Duration::from_secs(1)),
+            })
+            .with(StubResponses {
+                responses: vec![
+                    (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        vec![("x-request-id", "abc123")],
+                    ),
+                    (
+                        StatusCode::BAD_GATEWAY,
+                        vec![
+                            ("x-amz-cf-id", "cf-456"),
+                            ("x-cache", "Error from cloudfront"),
+                        ],
+                    ),
+                    (StatusCode::NOT_FOUND, vec![]),
+                ],
+                sent: AtomicUsize::new(0),
+            })
+            .build();
+
+        let error = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(async {
+                client
+                    .get("http://mirror.example.com/ubuntu/dists/noble/InRelease")
+                    .send()
+                    .await
+                    .and_then(error_for_status)
+            })
+            .unwrap_err();
+
+        assert!(error.to_string().contains("404 Not Found"));
+        assert_eq!(
+            request_attempts(&error).unwrap().to_string(),
+            [
+                "Attempt 1: 503 Service Unavailable (x-request-id: abc123)",
+                "Attempt 2: 502 Bad Gateway (x-amz-cf-id: cf-456, x-cache: Error from cloudfront)",
+                "Attempt 3: 404 Not Found",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn retry_budget_is_tracked_per_host() {
+        let budgets = HostRetryBudgets::new(Duration::from_secs(10));
+        assert!(budgets.try_spend("mirror.example.com:443", Duration::from_secs(4)));
+        assert!(budgets.try_spend("mirror.example.com:443", Duration::from_secs(4)));
+        assert!(!budgets.try_spend("mirror.example.com:443", Duration::from_secs(4)));
+        // a smaller delay can still fit in what's left of the budget
+        assert!(budgets.try_spend("mirror.example.com:443", Duration::from_secs(2)));
+        assert!(!budgets.try_spend("mirror.example.com:443", Duration::from_secs(1)));
+        // other hosts are unaffected
+        assert!(budgets.try_spend("archive.ubuntu.com:80", Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn request_host_includes_port() {
+        let request = |url: &str| Request::new(reqwest::Method::GET, url.parse().unwrap());
+        assert_eq!(
+            request_host(&request("https://archive.ubuntu.com/ubuntu")),
+            "archive.ubuntu.com:443"
+        );
+        assert_eq!(
+            request_host(&request("http://archive.ubuntu.com/ubuntu")),
+            "archive.ubuntu.com:80"
+        );
+        assert_eq!(
+            request_host(&request("https://mirror.example.com:8443/ubuntu")),
+            "mirror.example.com:8443"
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::install_packages::InstallPackagesError;
+use crate::{BuildpackResult, format_size};
+use object::build::elf::{Builder, SectionData};
+use object::elf::{ET_DYN, ET_EXEC};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use walkdir::WalkDir;
+
+const ELF_MAGIC: &[u8; 4] = b"\x7fELF";
+
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct StrippedBinaries {
+    pub(crate) files: usize,
+    pub(crate) bytes_saved: u64,
+}
+
+impl Display for StrippedBinaries {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Stripped {files} binaries, saving {size}",
+            files = self.files,
+            size = format_size(self.bytes_saved)
+        )
+    }
+}
+
+// NOTE: This is the equivalent of `strip --strip-all` for the executables and shared libraries
+//       installed from packages. Only linked ELF files are modified since relocatable objects
+//       (e.g.; kernel modules, the members of static libraries) still need their symbol tables.
+//       Anything that can't be parsed is left as-is rather than failing the build.
+pub(crate) fn strip_binaries(install_path: &Path) -> BuildpackResult<StrippedBinaries> {
+    let mut stripped_binaries = StrippedBinaries::default();
+
+    for entry in WalkDir::new(install_path)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+    {
+        let path = entry.path();
+
+        if !has_elf_magic(path).map_err(|e| InstallPackagesError::ReadBinary(path.into(), e))? {
+            continue;
+        }
+
+        let contents =
+            fs::read(path).map_err(|e| InstallPackagesError::ReadBinary(path.into(), e))?;
+
+        let Some(stripped_contents) = strip_elf(&contents) else {
+            continue;
+        };
+
+        write_binary(path, &stripped_contents)
+            .map_err(|e| InstallPackagesError::WriteBinary(path.into(), e))?;
+
+        stripped_binaries.files += 1;
+        stripped_binaries.bytes_saved += (contents.len() - stripped_contents.len()) as u64;
+    }
+
+    Ok(stripped_binaries)
+}
+
+pub(crate) fn has_elf_magic(path: &Path) -> std::io::Result<bool> {
+    let mut magic = [0; ELF_MAGIC.len()];
+    match fs::File::open(path)?.read_exact(&mut magic) {
+        Ok(()) => Ok(&magic == ELF_MAGIC),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+// Returns the stripped file contents or `None` when the file isn't a linked ELF binary or there
+// was nothing to remove.
+fn strip_elf(contents: &[u8]) -> Option<Vec<u8>> {
+    let mut builder = Builder::read(contents).ok()?;
+
+    if !matches!(builder.header.e_type, ET_EXEC | ET_DYN) {
+        return None;
+    }
+
+    let mut symbol_table_deleted = false;
+    for section in &mut builder.sections {
+        let is_debug_section =
+            section.name.starts_with(b".debug") || section.name.starts_with(b".zdebug");
+        let is_symbol_table = matches!(
+            section.data,
+            SectionData::Symbol | SectionData::SymbolSectionIndex | SectionData::String
+        );
+        if !section.is_alloc() && (is_debug_section || is_symbol_table) {
+            section.delete = true;
+            symbol_table_deleted |= matches!(section.data, SectionData::Symbol);
+        }
+    }
+
+    // the dynamic symbols needed at runtime live in `.dynsym` which is left untouched
+    if symbol_table_deleted {
+        for symbol in &mut builder.symbols {
+            symbol.delete = true;
+        }
+    }
+
+    builder.delete_orphans();
+
+    let mut stripped_contents = Vec::new();
+    builder.write(&mut stripped_contents).ok()?;
+
+    if stripped_contents.len() < contents.len() {
+        Some(stripped_contents)
+    } else {
+        None
+    }
+}
+
+// files from packages can be installed without write permission (e.g.; 0555) so the permission
+// is added for the write and the original mode restored afterward
+fn write_binary(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let permissions = fs::metadata(path)?.permissions();
+    let mode = permissions.mode();
+    if mode & 0o200 == 0 {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode | 0o200))?;
+    }
+    // the file is overwritten in place so any hard links to it see the stripped contents
+    fs::write(path, contents)?;
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::Endianness;
+    use object::elf::{EM_X86_64, SHF_ALLOC, SHF_EXECINSTR, SHT_PROGBITS, SHT_STRTAB, SHT_SYMTAB};
+    use object::read::elf::ElfFile64;
+    use object::{Object, ObjectSection};
+
+    #[test]
+    fn strip_binaries_removes_debug_sections_and_symbols() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let install_path = temp_dir.path();
+
+        let binary = create_elf(ET_DYN);
+        let binary_path = install_path.join("usr/lib/libexample.so");
+        fs::create_dir_all(binary_path.parent().unwrap()).unwrap();
+        fs::write(&binary_path, &binary).unwrap();
+        fs::set_permissions(&binary_path, fs::Permissions::from_mode(0o555)).unwrap();
+
+        let object_path = install_path.join("usr/lib/example.o");
+        fs::write(&object_path, create_elf(object::elf::ET_REL)).unwrap();
+
+        let script_path = install_path.join("usr/bin/example");
+        fs::create_dir_all(script_path.parent().unwrap()).unwrap();
+        fs::write(&script_path, "#!/bin/sh\n").unwrap();
+
+        let stripped_binaries = strip_binaries(install_path).unwrap();
+
+        let stripped_binary = fs::read(&binary_path).unwrap();
+        assert_eq!(
+            stripped_binaries,
+            StrippedBinaries {
+                files: 1,
+                bytes_saved: (binary.len() - stripped_binary.len()) as u64,
+            }
+        );
+        let section_names = ElfFile64::<Endianness>::parse(stripped_binary.as_slice())
+            .unwrap()
+            .sections()
+            .map(|section| section.name().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert!(section_names.contains(&".text".to_string()));
+        assert!(!section_names.contains(&".debug_info".to_string()));
+        assert!(!section_names.contains(&".symtab".to_string()));
+        assert!(!section_names.contains(&".strtab".to_string()));
+        assert_eq!(
+            fs::metadata(&binary_path).unwrap().permissions().mode() & 0o777,
+            0o555
+        );
+
+        assert_eq!(
+            fs::read(&object_path).unwrap(),
+            create_elf(object::elf::ET_REL)
+        );
+        assert_eq!(fs::read_to_string(&script_path).unwrap(), "#!/bin/sh\n");
+    }
+
+    #[test]
+    fn stripped_binaries_display() {
+        assert_eq!(
+            StrippedBinaries {
+                files: 3,
+                bytes_saved: 5 * 1024 * 1024 + 300 * 1024,
+            }
+            .to_string(),
+            "Stripped 3 binaries, saving 5.2 MiB"
+        );
+    }
+
+    fn create_elf(e_type: u16) -> Vec<u8> {
+        let mut builder = Builder::new(Endianness::Little, true);
+        builder.header.e_type = e_type;
+        builder.header.e_machine = EM_X86_64;
+
+        let text = builder.sections.add();
+        text.name = b".text"[..].into();
+        text.sh_type = SHT_PROGBITS;
+        text.sh_flags = u64::from(SHF_ALLOC | SHF_EXECINSTR);
+        text.sh_addralign = 16;
+        text.data = SectionData::Data(vec![0xc3; 16].into());
+        let text_id = text.id();
+
+        let debug_info = builder.sections.add();
+        debug_info.name = b".debug_info"[..].into();
+        debug_info.sh_type = SHT_PROGBITS;
+        debug_info.sh_addralign = 1;
+        debug_info.data = SectionData::Data(vec![0; 4096].into());
+
+        let strtab = builder.sections.add();
+        strtab.name = b".strtab"[..].into();
+        strtab.sh_type = SHT_STRTAB;
+        strtab.sh_addralign = 1;
+        strtab.data = SectionData::String;
+        let strtab_id = strtab.id();
+
+        let symtab = builder.sections.add();
+        symtab.name = b".symtab"[..].into();
+        symtab.sh_type = SHT_SYMTAB;
+        symtab.sh_addralign = 8;
+        symtab.sh_link_section = Some(strtab_id);
+        symtab.data = SectionData::Symbol;
+
+        let shstrtab = builder.sections.add();
+        shstrtab.name = b".shstrtab"[..].into();
+        shstrtab.sh_type = SHT_STRTAB;
+        shstrtab.sh_addralign = 1;
+        shstrtab.data = SectionData::SectionString;
+
+        let symbol = builder.symbols.add();
+        symbol.name = b"example_function"[..].into();
+        symbol.section = Some(text_id);
+
+        let mut contents = Vec::new();
+        builder.write(&mut contents).unwrap();
+        contents
+    }
+}
@@ -0,0 +1,242 @@
+use crate::config::{BuildpackConfig, RequestedPackage};
+use crate::debian::Distro;
+use crate::{BuildpackResult, DebianPackagesBuildpackError};
+use indoc::formatdoc;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub(crate) const VENDOR_DIR: &str = "deb-vendor";
+pub(crate) const VENDOR_MANIFEST_NAME: &str = "manifest.toml";
+
+// NOTE: The vendor manifest lists the `.deb` files downloaded into the vendor directory for each
+//       distribution/architecture along with their checksums. When a build finds an entry for its
+//       target, these files are installed directly and no package repositories are contacted.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct VendorManifest {
+    #[serde(default, rename = "target")]
+    pub(crate) targets: Vec<VendoredTarget>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct VendoredTarget {
+    pub(crate) distro_name: String,
+    pub(crate) distro_version: String,
+    pub(crate) arch: String,
+    // the packages and download urls from the configuration this target was vendored from
+    #[serde(default)]
+    pub(crate) requested: Vec<String>,
+    #[serde(default, rename = "package")]
+    pub(crate) packages: Vec<VendoredPackage>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct VendoredPackage {
+    pub(crate) name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) version: Option<String>,
+    pub(crate) file: String,
+    pub(crate) sha256: String,
+}
+
+impl VendorManifest {
+    pub(crate) fn read(vendor_dir: &Path) -> BuildpackResult<Option<Self>> {
+        let manifest_path = vendor_dir.join(VENDOR_MANIFEST_NAME);
+        match std::fs::read_to_string(&manifest_path) {
+            Ok(contents) => toml::from_str(&contents)
+                .map(Some)
+                .map_err(|e| VendorError::ParseManifest(manifest_path, Box::new(e)).into()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(VendorError::ReadManifest(manifest_path, e).into()),
+        }
+    }
+
+    pub(crate) fn write(&self, vendor_dir: &Path) -> BuildpackResult<()> {
+        let manifest_path = vendor_dir.join(VENDOR_MANIFEST_NAME);
+        let contents = formatdoc! { "
+            # This file is generated by the Heroku .deb Packages buildpack. Do not edit it manually.
+            # To update the vendored packages, run: deb-packages vendor
+
+            {manifest}",
+            manifest = toml::to_string(self).expect("Vendor manifest should serialize to TOML")
+        };
+        std::fs::write(&manifest_path, contents)
+            .map_err(|e| VendorError::WriteManifest(manifest_path, e).into())
+    }
+
+    pub(crate) fn get_target(&self, distro: &Distro) -> Option<&VendoredTarget> {
+        self.targets
+            .iter()
+            .find(|target| target.is_for_distro(distro))
+    }
+
+    // Replaces the entry for the same target and returns any files that are no longer
+    // referenced by any target so they can be removed from the vendor directory.
+    pub(crate) fn set_target(&mut self, target: VendoredTarget) -> Vec<String> {
+        let previous_files = self
+            .targets
+            .iter()
+            .find(|existing| existing.is_same_target(&target))
+            .map(|existing| {
+                existing
+                    .packages
+                    .iter()
+                    .map(|package| package.file.clone())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        self.targets
+            .retain(|existing| !existing.is_same_target(&target));
+        self.targets.push(target);
+
+        previous_files
+            .into_iter()
+            .filter(|file| {
+                !self
+                    .targets
+                    .iter()
+                    .flat_map(|target| &target.packages)
+                    .any(|package| &package.file == file)
+            })
+            .collect()
+    }
+}
+
+impl VendoredTarget {
+    pub(crate) fn new(
+        distro: &Distro,
+        requested: Vec<String>,
+        mut packages: Vec<VendoredPackage>,
+    ) -> Self {
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        Self {
+            distro_name: distro.name.to_lowercase(),
+            distro_version: distro.version.clone(),
+            arch: distro.architecture.to_string(),
+            requested,
+            packages,
+        }
+    }
+
+    fn is_for_distro(&self, distro: &Distro) -> bool {
+        self.distro_name.eq_ignore_ascii_case(&distro.name)
+            && self.distro_version == distro.version
+            && self.arch == distro.architecture.to_string()
+    }
+
+    fn is_same_target(&self, other: &VendoredTarget) -> bool {
+        self.distro_name.eq_ignore_ascii_case(&other.distro_name)
+            && self.distro_version == other.distro_version
+            && self.arch == other.arch
+    }
+}
+
+pub(crate) fn requested_from_config(config: &BuildpackConfig) -> Vec<String> {
+    config
+        .install
+        .iter()
+        .map(RequestedPackage::qualified_name)
+        .chain(config.download.iter().map(ToString::to_string))
+        .collect()
+}
+
+#[derive(Debug)]
+pub(crate) enum VendorError {
+    ReadManifest(PathBuf, std::io::Error),
+    ParseManifest(PathBuf, Box<toml::de::Error>),
+    WriteManifest(PathBuf, std::io::Error),
+    CreateVendorDir(PathBuf, std::io::Error),
+    CopyPackage(PathBuf, PathBuf, std::io::Error),
+    ReadPackage(PathBuf, std::io::Error),
+}
+
+impl From<VendorError> for libcnb::Error<DebianPackagesBuildpackError> {
+    fn from(value: VendorError) -> Self {
+        Self::BuildpackError(DebianPackagesBuildpackError::Vendor(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::debian::{ArchitectureName, DistroCodename};
+
+    #[test]
+    fn test_read_missing_manifest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        assert_eq!(VendorManifest::read(temp_dir.path()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_write_and_read_manifest() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut manifest = VendorManifest::default();
+        manifest.set_target(VendoredTarget::new(
+            &noble(ArchitectureName::AMD_64),
+            vec!["curl".to_string()],
+            vec![
+                vendored_package("libcurl4", "libcurl4_8.5.0_amd64.deb"),
+                vendored_package("curl", "curl_8.5.0_amd64.deb"),
+            ],
+        ));
+        manifest.write(temp_dir.path()).unwrap();
+        assert_eq!(
+            VendorManifest::read(temp_dir.path()).unwrap(),
+            Some(manifest.clone())
+        );
+        assert_eq!(
+            manifest
+                .get_target(&noble(ArchitectureName::AMD_64))
+                .unwrap()
+                .packages
+                .iter()
+                .map(|package| package.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["curl", "libcurl4"]
+        );
+        assert_eq!(manifest.get_target(&noble(ArchitectureName::ARM_64)), None);
+    }
+
+    #[test]
+    fn test_set_target_returns_unreferenced_files() {
+        let mut manifest = VendorManifest::default();
+        manifest.set_target(VendoredTarget::new(
+            &noble(ArchitectureName::AMD_64),
+            vec!["curl".to_string()],
+            vec![
+                vendored_package("curl", "curl_8.5.0_amd64.deb"),
+                vendored_package("tzdata", "tzdata_2024a_all.deb"),
+            ],
+        ));
+        manifest.set_target(VendoredTarget::new(
+            &noble(ArchitectureName::ARM_64),
+            vec!["tzdata".to_string()],
+            vec![vendored_package("tzdata", "tzdata_2024a_all.deb")],
+        ));
+        let unreferenced_files = manifest.set_target(VendoredTarget::new(
+            &noble(ArchitectureName::AMD_64),
+            vec!["curl".to_string()],
+            vec![vendored_package("curl", "curl_8.5.1_amd64.deb")],
+        ));
+        assert_eq!(manifest.targets.len(), 2);
+        assert_eq!(unreferenced_files, vec!["curl_8.5.0_amd64.deb".to_string()]);
+    }
+
+    fn noble(architecture: ArchitectureName) -> Distro {
+        Distro {
+            name: "ubuntu".to_string(),
+            version: "24.04".to_string(),
+            codename: DistroCodename::Noble,
+            architecture,
+        }
+    }
+
+    fn vendored_package(name: &str, file: &str) -> VendoredPackage {
+        VendoredPackage {
+            name: name.to_string(),
+            version: Some("1.0.0".to_string()),
+            file: file.to_string(),
+            sha256: format!("{name}-sha256"),
+        }
+    }
+}
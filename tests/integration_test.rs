@@ -10,7 +10,11 @@ use std::str::FromStr;
 
 use insta::{assert_snapshot, with_settings};
 use libcnb_test::{BuildConfig, BuildpackReference, PackResult, TestContext, TestRunner, assert_contains, assert_contains_match, assert_not_contains};
-use toml_edit::{Array, DocumentMut, InlineTable, value};
+use toml_edit::{Array, ArrayOfTables, DocumentMut, InlineTable, Item, Table, value};
+
+use crate::support::{AptRepository, FIXTURE_COMPONENT, FIXTURE_SUITE, FixturePackage};
+
+mod support;
 
 #[test]
 #[ignore = "integration test"]
@@ -201,7 +205,7 @@ fn test_package_config_rewrite() {
 
 #[test]
 #[ignore = "integration test"]
-#[allow(clippy::match_same_arms)]
+#[allow(clippy::match_same_arms, clippy::too_many_lines)]
 fn test_cache_invalidated_when_configuration_changes() {
     integration_test_with_config(
         "fixtures/project_file_with_empty_config",
@@ -266,7 +270,8 @@ fn test_cache_invalidated_when_configuration_changes() {
                 }),
                 |ctx| match (get_integration_test_builder().as_str(), get_integration_test_arch().as_str()) {
                     ("heroku/builder:22", "amd64") => {
-                        assert_contains!(ctx.pack_stdout, "Requesting packages (packages changed)");
+                        assert_contains!(ctx.pack_stdout, "Changes since the last build");
+                        assert_contains!(ctx.pack_stdout, "Added `libgwenhywfar-data@5.9.0-1`");
                         assert_contains!(ctx.pack_stdout, "Adding `libgwenhywfar-data@5.9.0-1`");
                         assert_contains!(ctx.pack_stdout, "`libgwenhywfar-data@5.9.0-1` from http://archive.ubuntu.com/ubuntu/pool/universe/libg/libgwenhywfar/libgwenhywfar-data_5.9.0-1_all.deb");
 
@@ -274,7 +279,8 @@ fn test_cache_invalidated_when_configuration_changes() {
                         assert_not_contains!(ctx.pack_stdout, "`libxmlsec1@1.2.33-1build2` from http://archive.ubuntu.com/ubuntu/pool/main/x/xmlsec1/libxmlsec1_1.2.33-1build2_amd64.deb");
                     }
                     ("heroku/builder:24", "amd64") => {
-                        assert_contains!(ctx.pack_stdout, "Requesting packages (packages changed)");
+                        assert_contains!(ctx.pack_stdout, "Changes since the last build");
+                        assert_contains!(ctx.pack_stdout, "Added `libgwenhywfar-data@5.10.2-2.1build4`");
                         assert_contains!(ctx.pack_stdout, "Adding `libgwenhywfar-data@5.10.2-2.1build4`");
                         assert_contains!(ctx.pack_stdout, "`libgwenhywfar-data@5.10.2-2.1build4` from http://archive.ubuntu.com/ubuntu/pool/universe/libg/libgwenhywfar/libgwenhywfar-data_5.10.2-2.1build4_all.deb");
 
@@ -282,7 +288,8 @@ fn test_cache_invalidated_when_configuration_changes() {
                         assert_not_contains!(ctx.pack_stdout, "`libxmlsec1t64@1.2.39-5build2` from http://archive.ubuntu.com/ubuntu/pool/main/x/xmlsec1/libxmlsec1t64@1.2.39-5build2_amd64.deb");
                     }
                     ("heroku/builder:24", "arm64") => {
-                        assert_contains!(ctx.pack_stdout, "Requesting packages (packages changed)");
+                        assert_contains!(ctx.pack_stdout, "Changes since the last build");
+                        assert_contains!(ctx.pack_stdout, "Added `libgwenhywfar-data@5.10.2-2.1build4`");
                         assert_contains!(ctx.pack_stdout, "Adding `libgwenhywfar-data@5.10.2-2.1build4`");
                         assert_contains!(ctx.pack_stdout, "`libgwenhywfar-data@5.10.2-2.1build4` from http://ports.ubuntu.com/ubuntu-ports/pool/universe/libg/libgwenhywfar/libgwenhywfar-data_5.10.2-2.1build4_all.deb");
 
@@ -290,7 +297,8 @@ fn test_cache_invalidated_when_configuration_changes() {
                         assert_not_contains!(ctx.pack_stdout, "`libxmlsec1t64@1.2.39-5build2` from http://ports.ubuntu.com/ubuntu-ports/pool/main/x/xmlsec1/libxmlsec1t64_1.2.39-5build2_arm64.deb");
                     }
                     ("heroku/builder:26", "amd64") => {
-                        assert_contains!(ctx.pack_stdout, "Requesting packages (packages changed)");
+                        assert_contains!(ctx.pack_stdout, "Changes since the last build");
+                        assert_contains!(ctx.pack_stdout, "Added `libgwenhywfar-data@5.14.1-2`");
                         assert_contains!(ctx.pack_stdout, "Adding `libgwenhywfar-data@5.14.1-2`");
                         assert_contains!(ctx.pack_stdout, "`libgwenhywfar-data@5.14.1-2` from http://archive.ubuntu.com/ubuntu/pool/universe/libg/libgwenhywfar/libgwenhywfar-data_5.14.1-2_all.deb");
 
@@ -298,7 +306,8 @@ fn test_cache_invalidated_when_configuration_changes() {
                         assert_not_contains!(ctx.pack_stdout, "`libxmlsec1-1@1.3.9-1` from http://archive.ubuntu.com/ubuntu/pool/main/x/xmlsec1/libxmlsec1-1_1.3.9-1_amd64.deb");
                     }
                     ("heroku/builder:26", "arm64") => {
-                        assert_contains!(ctx.pack_stdout, "Requesting packages (packages changed)");
+                        assert_contains!(ctx.pack_stdout, "Changes since the last build");
+                        assert_contains!(ctx.pack_stdout, "Added `libgwenhywfar-data@5.14.1-2`");
                         assert_contains!(ctx.pack_stdout, "Adding `libgwenhywfar-data@5.14.1-2`");
                         assert_contains!(ctx.pack_stdout, "`libgwenhywfar-data@5.14.1-2` from http://ports.ubuntu.com/ubuntu-ports/pool/universe/libg/libgwenhywfar/libgwenhywfar-data_5.14.1-2_all.deb");
 
@@ -312,6 +321,34 @@ fn test_cache_invalidated_when_configuration_changes() {
     );
 }
 
+#[test]
+#[ignore = "integration test"]
+fn test_cache_invalidated_when_clean_cache_requested() {
+    integration_test("fixtures/general_usage", |ctx| {
+        let mut config = ctx.config.clone();
+        config.env("BP_DEB_PACKAGES_CLEAN_CACHE", "true");
+        ctx.rebuild(config, |ctx| {
+            assert_contains!(ctx.pack_stdout, "(Cache clean requested)");
+            assert_contains!(ctx.pack_stdout, "Requesting packages (cache clean requested)");
+            assert_not_contains!(ctx.pack_stdout, "Restoring packages from cache");
+        });
+    });
+}
+
+#[test]
+#[ignore = "integration test"]
+fn test_package_index_refreshed_when_requested() {
+    integration_test("fixtures/general_usage", |ctx| {
+        let mut config = ctx.config.clone();
+        config.env("BP_DEB_PACKAGES_REFRESH_INDEX", "true");
+        ctx.rebuild(config, |ctx| {
+            assert_contains!(ctx.pack_stdout, "(Index refresh requested)");
+            assert_not_contains!(ctx.pack_stdout, "Restored package index from cache");
+            assert_contains!(ctx.pack_stdout, "Restoring packages from cache");
+        });
+    });
+}
+
 #[test]
 #[ignore = "integration test"]
 fn ffmpeg_usage() {
@@ -400,6 +437,91 @@ fn custom_deb_url_for_jammy_distro() {
     });
 }
 
+#[test]
+#[ignore = "integration test"]
+fn local_repository_source() {
+    let repository = AptRepository::new(FIXTURE_PACKAGES);
+    let signed_by = repository.signing_key();
+    let server = repository.serve();
+    integration_test_with_config(
+        "fixtures/local_repository",
+        |config| add_fixture_source(config, server.uri(), signed_by),
+        |ctx| {
+            assert_contains!(ctx.pack_stdout, &format!("dists/{FIXTURE_SUITE}/InRelease"));
+            assert_contains!(ctx.pack_stdout, "Adding `fixture-hello");
+            assert_contains!(ctx.pack_stdout, "Adding `fixture-greeting");
+            assert_contains!(ctx.run_shell_command("fixture-hello").stdout, "Hello from the fixture repository");
+        },
+    );
+}
+
+#[test]
+#[ignore = "integration test"]
+fn local_repository_package_with_invalid_checksum() {
+    let mut repository = AptRepository::new(FIXTURE_PACKAGES);
+    repository.replace_file(FIXTURE_PACKAGES[0].filename(), "not the package listed in the index");
+    let signed_by = repository.signing_key();
+    let server = repository.serve();
+    integration_test_with_config(
+        "fixtures/local_repository",
+        |config| {
+            add_fixture_source(config, server.uri(), signed_by);
+            config.expected_pack_result(PackResult::Failure);
+        },
+        |ctx| {
+            assert_contains!(ctx.pack_stdout, "Package checksum verification failed");
+        },
+    );
+}
+
+#[test]
+#[ignore = "integration test"]
+fn local_repository_release_signed_by_untrusted_key() {
+    let signed_by = AptRepository::new(FIXTURE_PACKAGES).signing_key();
+    let server = AptRepository::new(FIXTURE_PACKAGES).serve();
+    integration_test_with_config(
+        "fixtures/local_repository",
+        |config| {
+            add_fixture_source(config, server.uri(), signed_by);
+            config.expected_pack_result(PackResult::Failure);
+        },
+        |ctx| {
+            assert_contains!(ctx.pack_stdout, "Failed to verify Release file");
+        },
+    );
+}
+
+const FIXTURE_PACKAGES: &[FixturePackage] = &[
+    FixturePackage {
+        name: "fixture-hello",
+        version: "1.0.0",
+        depends: Some("fixture-greeting (>= 1.0.0)"),
+        files: &[("./usr/bin/fixture-hello", "#!/bin/sh\necho \"Hello from the fixture repository\"\n")],
+    },
+    FixturePackage {
+        name: "fixture-greeting",
+        version: "1.0.0",
+        depends: None,
+        files: &[("./usr/share/fixture-greeting/greeting", "Hello\n")],
+    },
+];
+
+fn add_fixture_source(config: &mut BuildConfig, uri: String, signed_by: String) {
+    config.app_dir_preprocessor(move |app_dir| {
+        update_project_toml(&app_dir, |doc| {
+            let mut source = Table::new();
+            source.insert("uri", value(uri.as_str()));
+            source.insert("suites", value(Array::from_iter([FIXTURE_SUITE])));
+            source.insert("components", value(Array::from_iter([FIXTURE_COMPONENT])));
+            source.insert("arch", value(Array::from_iter([get_integration_test_arch()])));
+            source.insert("signed_by", value(signed_by.as_str()));
+            let mut sources = ArrayOfTables::new();
+            sources.push(source);
+            doc["com"]["heroku"]["buildpacks"]["deb-packages"]["sources"] = Item::ArrayOfTables(sources);
+        });
+    });
+}
+
 const REBUILD_SEPARATOR: &str = "\
 --------------------------------------------- REBUILD ---------------------------------------------";
 
@@ -531,7 +653,7 @@ fn integration_test(fixture: &str, test_body: fn(TestContext)) {
     integration_test_with_config(fixture, |_| {}, test_body);
 }
 
-fn integration_test_with_config(fixture: &str, with_config: fn(&mut BuildConfig), test_body: fn(TestContext)) {
+fn integration_test_with_config(fixture: &str, with_config: impl FnOnce(&mut BuildConfig), test_body: fn(TestContext)) {
     let builder = get_integration_test_builder();
     let app_dir = PathBuf::from("tests").join(fixture);
 
@@ -0,0 +1,203 @@
+//! A hermetic Debian repository for integration tests.
+//!
+//! Builds a tiny signed repository in memory (`InRelease`, `Packages.gz` and the `.deb` files it lists)
+//! and serves it from a local HTTP listener so tests can exercise custom sources, checksum verification,
+//! and signature verification without depending on the availability of a public repository.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+use async_compression::tokio::write::GzipEncoder;
+use sequoia_openpgp::Cert;
+use sequoia_openpgp::cert::CertBuilder;
+use sequoia_openpgp::policy::StandardPolicy;
+use sequoia_openpgp::serialize::SerializeInto;
+use sequoia_openpgp::serialize::stream::{Message, Signer};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+use tokio_tar::{Builder as TarBuilder, Header};
+
+pub(crate) const FIXTURE_SUITE: &str = "fixtures";
+pub(crate) const FIXTURE_COMPONENT: &str = "main";
+const FIXTURE_ARCHITECTURES: [&str; 2] = ["amd64", "arm64"];
+
+/// A package to publish in the fixture repository. Every package is built for `Architecture: all`
+/// so the same repository works for each architecture the integration tests run on.
+pub(crate) struct FixturePackage {
+    pub(crate) name: &'static str,
+    pub(crate) version: &'static str,
+    pub(crate) depends: Option<&'static str>,
+    pub(crate) files: &'static [(&'static str, &'static str)],
+}
+
+impl FixturePackage {
+    /// The path of the package's `.deb` file, relative to the repository root.
+    pub(crate) fn filename(&self) -> String {
+        let first_letter = &self.name[..1];
+        format!("pool/{FIXTURE_COMPONENT}/{first_letter}/{name}/{name}_{version}_all.deb", name = self.name, version = self.version)
+    }
+}
+
+pub(crate) struct AptRepository {
+    files: HashMap<String, Vec<u8>>,
+    signing_key: Cert,
+}
+
+impl AptRepository {
+    /// Builds a repository with a single suite containing `packages`, signed with a newly generated key.
+    pub(crate) fn new(packages: &[FixturePackage]) -> Self {
+        let signing_key = CertBuilder::general_purpose(Some("Fixture Repository <fixtures@example.com>")).generate().unwrap().0;
+
+        let mut files = HashMap::new();
+        let mut packages_file = String::new();
+        for package in packages {
+            let deb = create_deb(package);
+            packages_file.push_str(&create_packages_entry(package, &deb));
+            files.insert(package.filename(), deb);
+        }
+        let packages_gz = gzip(packages_file.as_bytes());
+
+        let mut release_hashes = Vec::new();
+        for architecture in FIXTURE_ARCHITECTURES {
+            let packages_gz_path = format!("{FIXTURE_COMPONENT}/binary-{architecture}/Packages.gz");
+            release_hashes.push(format!(" {} {} {packages_gz_path}", hex::encode(Sha256::digest(&packages_gz)), packages_gz.len()));
+            files.insert(format!("dists/{FIXTURE_SUITE}/{packages_gz_path}"), packages_gz.clone());
+        }
+        let release = format!(
+            "Suite: {FIXTURE_SUITE}\nCodename: {FIXTURE_SUITE}\nArchitectures: {architectures}\nComponents: {FIXTURE_COMPONENT}\nSHA256:\n{release_hashes}\n",
+            architectures = FIXTURE_ARCHITECTURES.join(" "),
+            release_hashes = release_hashes.join("\n")
+        );
+        files.insert(format!("dists/{FIXTURE_SUITE}/InRelease"), clearsign(&signing_key, &release));
+
+        Self { files, signing_key }
+    }
+
+    /// The ASCII-armored public key that the repository's `InRelease` file is signed with, to be used
+    /// as the `signed_by` value of a custom source.
+    pub(crate) fn signing_key(&self) -> String {
+        String::from_utf8(self.signing_key.armored().to_vec().unwrap()).unwrap()
+    }
+
+    /// Replaces the contents served for `path` (e.g.; to serve a package that doesn't match the
+    /// checksum listed in the package index).
+    pub(crate) fn replace_file(&mut self, path: impl Into<String>, contents: impl Into<Vec<u8>>) {
+        self.files.insert(path.into(), contents.into());
+    }
+
+    /// Serves the repository on an ephemeral port until the test process exits.
+    pub(crate) fn serve(self) -> AptRepositoryServer {
+        let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let files = Arc::new(self.files);
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let files = Arc::clone(&files);
+                std::thread::spawn(move || handle_request(stream, &files));
+            }
+        });
+        AptRepositoryServer { port }
+    }
+}
+
+pub(crate) struct AptRepositoryServer {
+    port: u16,
+}
+
+impl AptRepositoryServer {
+    /// The uri of the repository as seen from inside the build container. The listener runs on the
+    /// host so this uses the Docker host address, which can be overridden with
+    /// `INTEGRATION_TEST_REPOSITORY_HOST` when the default doesn't apply (e.g.; a custom bridge network).
+    pub(crate) fn uri(&self) -> String {
+        let host = std::env::var("INTEGRATION_TEST_REPOSITORY_HOST").unwrap_or_else(|_| if cfg!(target_os = "linux") { "172.17.0.1" } else { "host.docker.internal" }.to_string());
+        format!("http://{host}:{port}", port = self.port)
+    }
+}
+
+// Answers a single `GET` request with the matching repository file, or a `404 Not Found`.
+fn handle_request(mut stream: TcpStream, files: &HashMap<String, Vec<u8>>) {
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // the request headers aren't needed but have to be consumed before responding
+    let mut header_line = String::new();
+    while reader.read_line(&mut header_line).is_ok_and(|read| read > 2) {
+        header_line.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default().trim_start_matches('/');
+    let (status, body) = match files.get(path) {
+        Some(contents) => ("200 OK", contents.as_slice()),
+        None => ("404 Not Found", &b""[..]),
+    };
+    let _ = write!(stream, "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+    let _ = stream.write_all(body);
+}
+
+fn create_packages_entry(package: &FixturePackage, deb: &[u8]) -> String {
+    let mut fields = vec![format!("Package: {}", package.name), format!("Version: {}", package.version), "Architecture: all".to_string()];
+    if let Some(depends) = package.depends {
+        fields.push(format!("Depends: {depends}"));
+    }
+    fields.extend([
+        format!("Filename: {}", package.filename()),
+        format!("Size: {}", deb.len()),
+        format!("SHA256: {}", hex::encode(Sha256::digest(deb))),
+        format!("Description: {} fixture package", package.name),
+    ]);
+    format!("{}\n\n", fields.join("\n"))
+}
+
+fn create_deb(package: &FixturePackage) -> Vec<u8> {
+    let control = format!("Package: {}\nVersion: {}\nArchitecture: all\nMaintainer: Fixtures <fixtures@example.com>\nDescription: {} fixture package\n", package.name, package.version, package.name);
+    let control_tar_gz = gzip(&create_tarball(&[("./control", control.as_str(), 0o644)]));
+    let data_tar_gz = gzip(&create_tarball(&package.files.iter().map(|(path, contents)| (*path, *contents, 0o755)).collect::<Vec<_>>()));
+
+    let mut deb = ar::Builder::new(Vec::new());
+    for (name, contents) in [("debian-binary", &b"2.0\n"[..]), ("control.tar.gz", &control_tar_gz), ("data.tar.gz", &data_tar_gz)] {
+        deb.append(&ar::Header::new(name.as_bytes().to_vec(), contents.len() as u64), contents).unwrap();
+    }
+    deb.into_inner().unwrap()
+}
+
+fn create_tarball(entries: &[(&str, &str, u32)]) -> Vec<u8> {
+    block_on(async {
+        let mut builder = TarBuilder::new(Vec::new());
+        for (path, contents, mode) in entries {
+            let mut header = Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(*mode);
+            builder.append_data(&mut header, path, contents.as_bytes()).await.unwrap();
+        }
+        builder.into_inner().await.unwrap()
+    })
+}
+
+fn gzip(contents: &[u8]) -> Vec<u8> {
+    block_on(async {
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.write_all(contents).await.unwrap();
+        encoder.shutdown().await.unwrap();
+        encoder.into_inner()
+    })
+}
+
+fn block_on<T>(future: impl Future<Output = T>) -> T {
+    tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(future)
+}
+
+fn clearsign(signing_key: &Cert, contents: &str) -> Vec<u8> {
+    let policy = StandardPolicy::new();
+    let keypair = signing_key.keys().unencrypted_secret().with_policy(&policy, None).supported().for_signing().next().unwrap().key().clone().into_keypair().unwrap();
+
+    let mut clearsigned = Vec::new();
+    let message = Message::new(&mut clearsigned);
+    let mut signer = Signer::new(message, keypair).unwrap().cleartext().build().unwrap();
+    signer.write_all(contents.as_bytes()).unwrap();
+    signer.finalize().unwrap();
+    clearsigned
+}